@@ -0,0 +1,59 @@
+//! Diff construction and line rendering for large deltas, the work done on
+//! every frame the diff viewer is open.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use osmogrep::ui::diff::{render_diff, Diff};
+
+const LINE_COUNTS: &[usize] = &[500, 5_000, 50_000];
+
+/// Returns a file of `lines` lines and an edited copy touching roughly one
+/// line in `stride`, so hunks are spread across the whole file.
+fn delta(lines: usize, stride: usize) -> (String, String) {
+    let mut before = String::new();
+    let mut after = String::new();
+    for i in 0..lines {
+        let line = format!("    let value_{i} = compute(input, {i}) + offset;\n");
+        before.push_str(&line);
+        if i % stride == 0 {
+            after.push_str(&format!(
+                "    let value_{i} = compute(input, {i}).saturating_add(offset);\n"
+            ));
+        } else if i % stride == 1 {
+            continue;
+        } else {
+            after.push_str(&line);
+        }
+    }
+    (before, after)
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+
+    for &lines in LINE_COUNTS {
+        let (before, after) = delta(lines, 40);
+
+        group.bench_with_input(
+            BenchmarkId::new("from_texts", lines),
+            &(&before, &after),
+            |b, (before, after)| {
+                b.iter(|| Diff::from_texts("src/lib.rs".to_string(), before, after))
+            },
+        );
+
+        let built = Diff::from_texts("src/lib.rs".to_string(), &before, &after);
+        group.bench_with_input(BenchmarkId::new("render", lines), &built, |b, built| {
+            b.iter(|| render_diff(built, black_box(120)))
+        });
+    }
+
+    let (before, after) = delta(2_000, 1);
+    group.bench_function("from_texts/rewrite_2000", |b| {
+        b.iter(|| Diff::from_texts("src/lib.rs".to_string(), &before, &after))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_diff);
+criterion_main!(benches);
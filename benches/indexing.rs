@@ -0,0 +1,96 @@
+//! Context indexing over synthetic repos of increasing size.
+//!
+//! `cold` rebuilds the whole index (tree-sitter parse of every file), `warm`
+//! hits the cached `.context/` path that only rehashes and reloads.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use osmogrep::context::indexer;
+use uuid::Uuid;
+
+const REPO_SIZES: &[usize] = &[25, 100, 400];
+
+fn rust_module(i: usize) -> String {
+    let mut src = format!("//! module {i}\n\nuse std::collections::HashMap;\n\n");
+    for f in 0..8 {
+        src.push_str(&format!(
+            "/// Computes value {f} for module {i}.\n\
+             pub fn compute_{i}_{f}(input: &[u32], scale: u32) -> u32 {{\n    \
+             let mut seen = HashMap::new();\n    \
+             for v in input {{\n        \
+             *seen.entry(v % 7).or_insert(0) += v * scale;\n    \
+             }}\n    \
+             helper_{i}(seen.values().sum())\n\
+             }}\n\n"
+        ));
+    }
+    src.push_str(&format!(
+        "fn helper_{i}(v: u32) -> u32 {{\n    v.wrapping_mul(31).rotate_left(3)\n}}\n"
+    ));
+    src
+}
+
+fn python_module(i: usize) -> String {
+    let mut src = format!("import os\n\n\nclass Worker{i}:\n    \"\"\"Worker {i}.\"\"\"\n\n");
+    for f in 0..6 {
+        src.push_str(&format!(
+            "    def step_{f}(self, items, limit=10):\n        \
+             \"\"\"Step {f}.\"\"\"\n        \
+             out = [x * {f} for x in items if x < limit]\n        \
+             return sorted(out)\n\n"
+        ));
+    }
+    src.push_str(&format!(
+        "\ndef run_{i}(path):\n    return Worker{i}().step_0(os.listdir(path))\n"
+    ));
+    src
+}
+
+fn synthetic_repo(files: usize) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("osmogrep-bench-{}", Uuid::new_v4()));
+    for i in 0..files {
+        let dir = root.join(format!("src/pkg{}", i % 10));
+        fs::create_dir_all(&dir).unwrap();
+        if i % 3 == 2 {
+            fs::write(dir.join(format!("mod_{i}.py")), python_module(i)).unwrap();
+        } else {
+            fs::write(dir.join(format!("mod_{i}.rs")), rust_module(i)).unwrap();
+        }
+    }
+    root
+}
+
+fn clear_cache(root: &Path) {
+    let _ = fs::remove_dir_all(root.join(".context"));
+}
+
+fn bench_indexing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("context_index");
+    group.sample_size(10);
+
+    for &files in REPO_SIZES {
+        let root = synthetic_repo(files);
+
+        group.bench_with_input(BenchmarkId::new("cold", files), &root, |b, root| {
+            b.iter_batched(
+                || clear_cache(root),
+                |_| indexer::load_or_build(root),
+                BatchSize::PerIteration,
+            )
+        });
+
+        indexer::load_or_build(&root);
+        group.bench_with_input(BenchmarkId::new("warm", files), &root, |b, root| {
+            b.iter(|| indexer::load_or_build(root))
+        });
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_indexing);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+//! Title/body tokenization and Jaccard similarity, the inner loop of triage
+//! duplicate detection (quadratic in open items).
+
+use std::collections::HashSet;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use osmogrep::triage;
+
+const ITEM_COUNTS: &[usize] = &[50, 200];
+
+const WORDS: &[&str] = &[
+    "panic",
+    "parser",
+    "fixing",
+    "crashes",
+    "indexer",
+    "timeout",
+    "retrying",
+    "tokens",
+    "cache",
+    "windows",
+    "render",
+    "diff",
+    "viewer",
+    "scrolling",
+    "login",
+    "oauth",
+    "docs",
+    "readme",
+    "latency",
+    "regression",
+    "config",
+    "loaded",
+    "agent",
+    "tool",
+    "shell",
+    "permission",
+];
+
+fn item_text(i: usize) -> String {
+    let mut text = format!("Issue {i}: ");
+    for w in 0..40 {
+        text.push_str(WORDS[(i * 7 + w * 3) % WORDS.len()]);
+        text.push(' ');
+    }
+    text
+}
+
+fn bench_similarity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("triage");
+
+    let body = item_text(0).repeat(20);
+    group.bench_function("tokenize/800_words", |b| {
+        b.iter(|| triage::tokenize(black_box(&body)))
+    });
+
+    for &items in ITEM_COUNTS {
+        let tokens = (0..items)
+            .map(|i| triage::tokenize(&item_text(i)))
+            .collect::<Vec<HashSet<String>>>();
+
+        group.bench_with_input(
+            BenchmarkId::new("jaccard_pairs", items),
+            &tokens,
+            |b, tokens| {
+                b.iter(|| {
+                    let mut close = 0usize;
+                    for i in 0..tokens.len() {
+                        for j in (i + 1)..tokens.len() {
+                            if triage::jaccard(&tokens[i], &tokens[j]) > 0.6 {
+                                close += 1;
+                            }
+                        }
+                    }
+                    close
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_similarity);
+criterion_main!(benches);
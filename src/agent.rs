@@ -1,12 +1,12 @@
 use std::{
+    collections::BTreeMap,
     env, fs,
     io::Read,
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
+    path::{Component, Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc::{self, Receiver, Sender},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
     },
     thread,
     time::{Duration, Instant},
@@ -14,11 +14,38 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use url::Url;
 
+use crate::approvals::{self, Approvals};
+use crate::change_budget::{BudgetDecision, ChangeBudget, RunChanges};
+use crate::file_log::LoggingConfig;
+use crate::file_refs::EditorConfig;
 use crate::harness::{clip, RunLedger};
+use crate::http_policy::{self, RetryNotice, RetryPolicy};
+use crate::images::{self, ImageAttachment};
+use crate::keymap::{KeySpec, Keymap};
+use crate::lifecycle_hooks::LifecycleHooks;
+use crate::lint_hooks::{self, LintHooksConfig, RepairLoop};
+use crate::net::{self, NetworkConfig};
+use crate::notify::NotificationsConfig;
+use crate::permissions::{Decision, PermissionPolicy};
+use crate::pricing::ModelPrice;
+use crate::protected_paths::{self, ProtectMode, ProtectedPaths};
+use crate::response_cache::{self, Recorder, Replay, ResponseCacheConfig, ResponseStore};
+use crate::run_budget::{self, RunBudget, RunSpend};
 use crate::state::{DiffSnapshot, PermissionProfile, PlanItem};
+use crate::test_provider::{is_test_provider, TestProvider};
+use crate::testgen;
+use crate::text_tools;
+use crate::timefmt;
+use crate::tool_budget::OutputBudget;
 use crate::tool_guard::ToolLoopGuard;
-use crate::tools::{ToolRegistry, ToolSafety, ToolScope};
+use crate::tools::{
+    ToolProfile, ToolRegistry, ToolRegistryBuilder, ToolSafety, ToolScope, WebSearchConfig,
+    SUBAGENT_MAX_DEPTH, SUBAGENT_MAX_TOKENS, SUBAGENT_TOKEN_BUDGET,
+};
+use crate::transcript::{reported_tokens, FileDiff, ToolCallRecord};
+use crate::voice::VoiceConfig;
 
 #[derive(Debug)]
 pub enum AgentEvent {
@@ -76,6 +103,8 @@ pub enum AgentEvent {
         target: String,
         before: String,
         after: String,
+        created: bool,
+        deleted: bool,
     },
     PreviewDiff {
         tool: String,
@@ -95,24 +124,137 @@ pub enum AgentEvent {
     PermissionRequest {
         tool_name: String,
         args_summary: String,
+        /// Set for edits to protected paths: auto-approve does not apply
+        /// and the user confirms twice.
+        protected: Option<String>,
+        /// Pattern an `[a]lways` answer stores in `.osmogrep/approvals.json`.
+        always: String,
         reply_tx: Sender<bool>,
     },
+    /// Tools offered to the model this run, after its capability profile.
+    ToolsExposed {
+        profile: String,
+        exposed: Vec<String>,
+        dropped: Vec<String>,
+    },
+    /// The run's edits passed `[change_budget]`; it waits for the reply.
+    ChangeBudgetExceeded {
+        summary: String,
+        changes: Vec<DiffSnapshot>,
+        reply_tx: Sender<BudgetDecision>,
+    },
     ConversationUpdate(Vec<Value>),
+    /// A model request failed and is retried after `wait`.
+    Retrying(RetryNotice),
+    /// Tokens one model turn spent, as reported by the provider or
+    /// `estimated` from the text; `tools` are the calls the turn requested.
+    Usage {
+        provider: String,
+        model: String,
+        input_tokens: usize,
+        output_tokens: usize,
+        estimated: bool,
+        tools: Vec<String>,
+    },
+    /// A `spawn_subagent` child started, made progress or finished.
+    Subagent(SubagentStatus),
+    /// Sent before the `Error` of a failed run: the conversation as of its
+    /// last model turn, tool outputs included, for `/continue`.
+    /// `transient` when the model request failed in a way worth retrying.
+    Checkpoint {
+        messages: Vec<Value>,
+        transient: bool,
+    },
+    /// The run's transcript was written to this JSON file (the `.md`
+    /// rendering sits next to it). Sent before `Error`/`Cancelled`.
+    Transcript(PathBuf),
     Cancelled,
     Error(String),
     Done,
 }
 
+/// A sub-agent as the UI shows it, in a tree under the main agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubagentStatus {
+    pub id: usize,
+    /// The sub-agent that spawned this one; `None` for the main agent's.
+    pub parent: Option<usize>,
+    pub task: String,
+    pub state: SubagentState,
+    pub tool_calls: usize,
+    pub tokens: usize,
+    pub token_budget: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubagentState {
+    Running,
+    Done,
+    /// Stopped after spending its token budget.
+    OverBudget,
+    Failed,
+    Cancelled,
+}
+
+impl SubagentState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::OverBudget => "over budget",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+static NEXT_SUBAGENT_ID: AtomicUsize = AtomicUsize::new(1);
+
 struct ModelResponse {
     value: Value,
     output_streamed: bool,
 }
 
+enum ModelCallError {
+    /// Retrying the whole request will not help (or already happened).
+    Final(String),
+    /// Worth another attempt; `status` and `retry_after` come from the
+    /// failed response, when there was one.
+    Transient {
+        message: String,
+        status: Option<u16>,
+        retry_after: Option<Duration>,
+    },
+}
+
+impl From<String> for ModelCallError {
+    fn from(message: String) -> Self {
+        Self::Transient {
+            message,
+            status: None,
+            retry_after: None,
+        }
+    }
+}
+
+impl From<&str> for ModelCallError {
+    fn from(e: &str) -> Self {
+        e.to_string().into()
+    }
+}
+
 #[derive(Clone)]
 pub struct CancelToken {
     cancelled: Arc<AtomicBool>,
 }
 
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CancelToken {
     pub fn new() -> Self {
         Self {
@@ -135,6 +277,38 @@ struct Config {
     api_key: Option<String>,
     #[serde(default)]
     model: Option<ModelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    network: Option<NetworkConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    editor: Option<EditorConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tool_profiles: BTreeMap<String, ToolProfile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    protected_paths: Option<ProtectedPaths>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    change_budget: Option<ChangeBudget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_output: Option<OutputBudget>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pricing: BTreeMap<String, ModelPrice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    voice: Option<VoiceConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    keys: BTreeMap<String, KeySpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    web_search: Option<WebSearchConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lint_hooks: Option<LintHooksConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    steer_templates: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    run_budget: Option<RunBudget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    logging: Option<LoggingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response_cache: Option<ResponseCacheConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notifications: Option<NotificationsConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,13 +332,170 @@ impl Default for ModelConfig {
     }
 }
 
-fn config_path() -> PathBuf {
+pub fn config_path() -> PathBuf {
     let mut dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
     dir.push("osmogrep");
     dir.push("config.toml");
     dir
 }
 
+/// `[network]` section of the config file.
+pub fn network_config() -> Option<NetworkConfig> {
+    load_config().and_then(|cfg| cfg.network)
+}
+
+pub fn editor_config() -> Option<EditorConfig> {
+    load_config().and_then(|cfg| cfg.editor)
+}
+
+/// `[tool_profiles]` overrides, keyed by `provider` or `provider/model`.
+pub fn tool_profile_overrides() -> BTreeMap<String, ToolProfile> {
+    load_config()
+        .map(|cfg| cfg.tool_profiles)
+        .unwrap_or_default()
+}
+
+/// `[pricing]` overrides, keyed by `provider` or `provider/model`.
+pub fn pricing_overrides() -> BTreeMap<String, ModelPrice> {
+    load_config().map(|cfg| cfg.pricing).unwrap_or_default()
+}
+
+/// `[voice]` section of the config file; the vLLM backend by default.
+pub fn voice_config() -> VoiceConfig {
+    load_config().and_then(|cfg| cfg.voice).unwrap_or_default()
+}
+
+/// `[keys]` bindings, by action name.
+pub fn keys_config() -> BTreeMap<String, KeySpec> {
+    load_config().map(|cfg| cfg.keys).unwrap_or_default()
+}
+
+/// `[web_search]` section of the config file; DuckDuckGo by default.
+pub fn web_search_config() -> WebSearchConfig {
+    load_config()
+        .and_then(|cfg| cfg.web_search)
+        .unwrap_or_default()
+}
+
+/// `[lint_hooks]` section of the config file; detected linters run after
+/// edits by default.
+pub fn lint_hooks_config() -> LintHooksConfig {
+    load_config()
+        .and_then(|cfg| cfg.lint_hooks)
+        .unwrap_or_default()
+}
+
+/// Steer templates available without configuration.
+pub const BUILTIN_STEER_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "be-concise",
+        "Keep answers short: state the result and the evidence, skip recaps and restated plans.",
+    ),
+    (
+        "tests-first",
+        "Write or update a failing test for the behavior before changing the code, then make it pass.",
+    ),
+    (
+        "small-diffs",
+        "Keep the change minimal: touch only what the task needs and leave unrelated code alone.",
+    ),
+];
+
+/// Named `/steer` templates: the built-ins, overridden and extended by the
+/// `[steer_templates]` section of the config file.
+pub fn steer_templates() -> BTreeMap<String, String> {
+    let mut templates: BTreeMap<String, String> = BUILTIN_STEER_TEMPLATES
+        .iter()
+        .map(|(name, text)| (name.to_string(), text.to_string()))
+        .collect();
+    if let Some(cfg) = load_config() {
+        templates.extend(cfg.steer_templates);
+    }
+    templates
+}
+
+/// `[protected_paths]` section of the config file; nothing is protected by
+/// default.
+pub fn protected_paths_config() -> ProtectedPaths {
+    load_config()
+        .and_then(|cfg| cfg.protected_paths)
+        .unwrap_or_default()
+}
+
+/// `[change_budget]` section of the config file; unlimited by default.
+pub fn change_budget_config() -> ChangeBudget {
+    load_config()
+        .and_then(|cfg| cfg.change_budget)
+        .unwrap_or_default()
+}
+
+/// `[run_budget]` section of the config file; no limits by default.
+pub fn run_budget_config() -> RunBudget {
+    load_config()
+        .and_then(|cfg| cfg.run_budget)
+        .unwrap_or_default()
+}
+
+/// `[logging]` section of the config file; info-level daily files by
+/// default.
+pub fn logging_config() -> LoggingConfig {
+    load_config()
+        .and_then(|cfg| cfg.logging)
+        .unwrap_or_default()
+}
+
+/// `[response_cache]` section of the config file; off by default.
+pub fn response_cache_config() -> ResponseCacheConfig {
+    load_config()
+        .and_then(|cfg| cfg.response_cache)
+        .unwrap_or_default()
+}
+
+/// `[notifications]` section of the config file; OSC 9 and a bell by
+/// default.
+pub fn notifications_config() -> NotificationsConfig {
+    load_config()
+        .and_then(|cfg| cfg.notifications)
+        .unwrap_or_default()
+}
+
+/// `[tool_output]` section of the config file; 40k characters per result by
+/// default.
+pub fn tool_output_config() -> OutputBudget {
+    load_config()
+        .and_then(|cfg| cfg.tool_output)
+        .unwrap_or_default()
+}
+
+/// Parse errors for `text` as the agent's config file, with location.
+pub fn validate_config(text: &str) -> Result<(), String> {
+    let cfg: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+    if let Some(invalid) = cfg
+        .protected_paths
+        .as_ref()
+        .and_then(|p| p.invalid_patterns().into_iter().next())
+    {
+        return Err(format!(
+            "protected_paths.patterns: invalid pattern {invalid:?}"
+        ));
+    }
+    if let Some(problem) = Keymap::from_config(&cfg.keys).problems.into_iter().next() {
+        return Err(problem);
+    }
+    if let Some(web_search) = &cfg.web_search {
+        web_search.validate()?;
+    }
+    match cfg.model {
+        Some(model) if model.provider.trim().is_empty() => {
+            Err("model.provider must not be empty".to_string())
+        }
+        Some(model) if model.model.trim().is_empty() => {
+            Err("model.model must not be empty".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
 fn load_config() -> Option<Config> {
     fs::read_to_string(config_path())
         .ok()
@@ -173,6 +504,40 @@ fn load_config() -> Option<Config> {
 
 fn save_config(cfg: &Config) -> std::io::Result<()> {
     let path = config_path();
+    // Network, editor, tool profile, protected path, change budget, tool
+    // output, pricing, voice, key, web search, lint hook, steer template, run
+    // budget, logging, response cache and notification settings are only
+    // edited by hand; keep whatever is on disk.
+    let on_disk = load_config();
+    let cfg = &Config {
+        api_key: cfg.api_key.clone(),
+        model: cfg.model.clone(),
+        network: on_disk.as_ref().and_then(|c| c.network.clone()),
+        editor: on_disk.as_ref().and_then(|c| c.editor.clone()),
+        tool_profiles: on_disk
+            .as_ref()
+            .map(|c| c.tool_profiles.clone())
+            .unwrap_or_default(),
+        protected_paths: on_disk.as_ref().and_then(|c| c.protected_paths.clone()),
+        change_budget: on_disk.as_ref().and_then(|c| c.change_budget),
+        tool_output: on_disk.as_ref().and_then(|c| c.tool_output),
+        pricing: on_disk
+            .as_ref()
+            .map(|c| c.pricing.clone())
+            .unwrap_or_default(),
+        voice: on_disk.as_ref().and_then(|c| c.voice.clone()),
+        keys: on_disk.as_ref().map(|c| c.keys.clone()).unwrap_or_default(),
+        web_search: on_disk.as_ref().and_then(|c| c.web_search.clone()),
+        lint_hooks: on_disk.as_ref().and_then(|c| c.lint_hooks.clone()),
+        steer_templates: on_disk
+            .as_ref()
+            .map(|c| c.steer_templates.clone())
+            .unwrap_or_default(),
+        run_budget: on_disk.as_ref().and_then(|c| c.run_budget),
+        logging: on_disk.as_ref().and_then(|c| c.logging.clone()),
+        response_cache: on_disk.as_ref().and_then(|c| c.response_cache.clone()),
+        notifications: on_disk.and_then(|c| c.notifications),
+    };
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
@@ -234,12 +599,16 @@ fn take_chars(text: &str, max_bytes: usize) -> String {
     out
 }
 
+/// Recorded with generated-test provenance; bump when the system prompt
+/// changes in a way that affects how tests are written.
+pub const PROMPT_VERSION: u32 = 1;
+
 fn system_prompt(repo_root: &std::path::Path) -> Value {
     let mut content =
         "You are Osmogrep, an AI coding agent working inside this repository.\n\
             \n\
             This repository may include a machine-generated index stored as `.context/context.json` at the repository root.\n\
-            This file, if present, describes the structure of the codebase: files, symbols, and call relationships.\n\
+            This file, if present, describes the structure of the codebase: repo facts (languages, manifests, test commands), files, the import graph between them, symbols, and call relationships.\n\
             \n\
             The index is a regular file and must be discovered and read using tools.\n\
             It may or may not exist.\n\
@@ -250,6 +619,7 @@ fn system_prompt(repo_root: &std::path::Path) -> Value {
             - Use tools to inspect other files or make changes as needed.\n\
             - If `.context/context.json` is missing or insufficient, proceed normally and use tools freely.\n\
             - Prefer high-leverage workflows over many tiny manual steps.\n\
+            - When a change spans several places or files, batch the replacements into one `multi_edit` call rather than many `edit_file` calls; it writes all of them or none.\n\
             - When the task asks for current information, online research, cross-source verification, or a Claude Code-style workflow, use `dynamic_workflow` to fan out bounded research agents and bring back cited evidence instead of manually looping search/fetch calls.\n\
             - If the user ends a request with `ultracode`, treat it as an explicit dynamic-workflow sentinel: plan the workflow, delegate/fan out where useful, keep context compact, and synthesize verified results.\n\
             - For multi-step work, keep a durable progress plan with `update_plan`; set the plan before implementation, mark each item done immediately after completing it, and keep exactly one next item in progress. Treat the plan as scratchpad memory and verify against real files before acting.\n\
@@ -317,7 +687,7 @@ fn workspace_fact_block(repo_root: &std::path::Path) -> String {
     lines.join("\n")
 }
 
-fn detect_manifests(repo_root: &std::path::Path) -> Vec<String> {
+pub fn detect_manifests(repo_root: &std::path::Path) -> Vec<String> {
     [
         "Cargo.toml",
         "package.json",
@@ -333,7 +703,7 @@ fn detect_manifests(repo_root: &std::path::Path) -> Vec<String> {
     .collect()
 }
 
-fn detect_verify_commands(repo_root: &std::path::Path) -> Vec<String> {
+pub fn detect_verify_commands(repo_root: &std::path::Path) -> Vec<String> {
     let mut commands = Vec::new();
     if repo_root.join("Cargo.toml").is_file() {
         push_unique(&mut commands, "cargo test --color never");
@@ -407,9 +777,14 @@ fn push_unique(items: &mut Vec<String>, item: &str) {
     }
 }
 
+/// Customizes the tool registry built for each agent run.
+pub type ToolSetup = dyn Fn(ToolRegistryBuilder) -> ToolRegistryBuilder + Send + Sync;
+
 pub struct Agent {
     model_cfg: ModelConfig,
     api_key: Option<String>,
+    tool_setup: Option<Arc<ToolSetup>>,
+    responses: ResponseStore,
 }
 
 pub struct RunControl {
@@ -417,6 +792,35 @@ pub struct RunControl {
     pub steer_tx: Sender<String>,
 }
 
+/// What the user sent: the prompt and any images attached to it.
+#[derive(Debug, Clone, Default)]
+pub struct UserPrompt {
+    pub text: String,
+    pub images: Vec<ImageAttachment>,
+    /// Files or globs of the approved plan's open steps; when set, edits
+    /// anywhere else are refused.
+    pub plan_scope: Vec<String>,
+    /// Replaces the `[run_budget]` limits for this run.
+    pub budget: Option<RunBudget>,
+}
+
+impl From<String> for UserPrompt {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            images: Vec::new(),
+            plan_scope: Vec::new(),
+            budget: None,
+        }
+    }
+}
+
+impl Default for Agent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Agent {
     pub fn new() -> Self {
         let cfg = load_config();
@@ -448,11 +852,51 @@ impl Agent {
             .unwrap_or_else(default_api_key_env);
         let api_key = env::var(&env_key_name).ok().or(cfg_api_key);
 
-        Self { model_cfg, api_key }
+        Self {
+            model_cfg,
+            api_key,
+            tool_setup: None,
+            responses: ResponseStore::from_config(
+                &cfg.and_then(|c| c.response_cache).unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Re-read the model and API key from the config file and environment,
+    /// keeping any tool setup and recording.
+    pub fn reload_config(&mut self) {
+        let fresh = Self::new();
+        self.model_cfg = fresh.model_cfg;
+        self.api_key = fresh.api_key;
+    }
+
+    /// Applies `setup` to the registry of every run spawned by this agent, so
+    /// embedders can add tools or change safety levels.
+    pub fn with_tools(
+        mut self,
+        setup: impl Fn(ToolRegistryBuilder) -> ToolRegistryBuilder + Send + Sync + 'static,
+    ) -> Self {
+        self.tool_setup = Some(Arc::new(setup));
+        self
+    }
+
+    /// Writes every model response of the runs spawned by this agent to
+    /// `recorder`.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.responses = self.responses.with_recorder(recorder);
+        self
+    }
+
+    /// Answers model calls from `replay` instead of the API, as the recorded
+    /// model so request keys line up.
+    pub fn with_replay(mut self, replay: Replay) -> Self {
+        self.model_cfg.model = replay.model().to_string();
+        self.responses = self.responses.with_replay(replay);
+        self
     }
 
     pub fn is_configured(&self) -> bool {
-        self.api_key.is_some()
+        self.api_key.is_some() || is_test_provider(&self.model_cfg) || self.responses.is_replaying()
     }
 
     pub fn set_api_key(&mut self, key: String) {
@@ -465,6 +909,22 @@ impl Agent {
         let _ = save_config(&Config {
             api_key: Some(key),
             model: Some(self.model_cfg.clone()),
+            network: None,
+            editor: None,
+            tool_profiles: BTreeMap::new(),
+            protected_paths: None,
+            change_budget: None,
+            tool_output: None,
+            pricing: BTreeMap::new(),
+            voice: None,
+            keys: BTreeMap::new(),
+            web_search: None,
+            lint_hooks: None,
+            steer_templates: BTreeMap::new(),
+            run_budget: None,
+            logging: None,
+            response_cache: None,
+            notifications: None,
         });
     }
 
@@ -472,6 +932,13 @@ impl Agent {
         &self.model_cfg
     }
 
+    pub fn base_url(&self) -> String {
+        self.model_cfg
+            .base_url
+            .clone()
+            .unwrap_or_else(|| default_base_url_for(&self.model_cfg.provider))
+    }
+
     pub fn api_key(&self) -> Option<String> {
         self.api_key.clone()
     }
@@ -489,13 +956,29 @@ impl Agent {
         let _ = save_config(&Config {
             api_key: self.api_key.clone(),
             model: Some(self.model_cfg.clone()),
+            network: None,
+            editor: None,
+            tool_profiles: BTreeMap::new(),
+            protected_paths: None,
+            change_budget: None,
+            tool_output: None,
+            pricing: BTreeMap::new(),
+            voice: None,
+            keys: BTreeMap::new(),
+            web_search: None,
+            lint_hooks: None,
+            steer_templates: BTreeMap::new(),
+            run_budget: None,
+            logging: None,
+            response_cache: None,
+            notifications: None,
         });
     }
 
     pub fn spawn(
         &self,
         repo_root: PathBuf,
-        user_text: String,
+        prompt: UserPrompt,
         prior_messages: Vec<Value>,
         steer: Option<String>,
         permission_profile: PermissionProfile,
@@ -507,20 +990,74 @@ impl Agent {
         let cancel = CancelToken::new();
         let cancel_worker = cancel.clone();
         let (steer_tx, steer_rx) = mpsc::channel::<String>();
+        let tool_setup = self.tool_setup.clone();
+        let responses = self.responses.clone();
 
         thread::spawn(move || {
+            let test_provider = match TestProvider::from_config(&model_cfg).transpose() {
+                Ok(provider) => provider.map(Arc::new),
+                Err(e) => {
+                    let _ = tx.send(AgentEvent::Error(e));
+                    let _ = tx.send(AgentEvent::Done);
+                    return;
+                }
+            };
+            let UserPrompt {
+                text: user_text,
+                images,
+                plan_scope,
+                budget,
+            } = prompt;
             let mut tool_scope = ToolScope::for_prompt(&user_text);
             if permission_profile == PermissionProfile::ReadOnly {
                 tool_scope = tool_scope.read_only();
             }
+            let permissions = match PermissionPolicy::load(&repo_root) {
+                Ok(policy) => policy,
+                Err(e) => {
+                    let _ = tx.send(AgentEvent::Error(e));
+                    let _ = tx.send(AgentEvent::Done);
+                    return;
+                }
+            };
+            let hooks = match LifecycleHooks::load(&repo_root) {
+                Ok(hooks) => hooks,
+                Err(e) => {
+                    let _ = tx.send(AgentEvent::Error(e));
+                    let _ = tx.send(AgentEvent::Done);
+                    return;
+                }
+            };
+            let mut tools = ToolRegistry::builder(repo_root.clone()).permissions(permissions);
+            if let Some(setup) = tool_setup.as_ref() {
+                tools = setup(tools);
+            }
+            let (tool_profile, tool_profile_source) = ToolProfile::resolve(
+                &model_cfg.provider,
+                &model_cfg.model,
+                &tool_profile_overrides(),
+            );
             let runner = RunAgent {
-                tools: ToolRegistry::with_root(repo_root.clone()),
+                tools: Arc::new(tools.build()),
                 tool_scope,
+                tool_profile,
+                tool_profile_source,
+                protected_paths: protected_paths_config(),
+                change_budget: change_budget_config(),
+                run_budget: budget.unwrap_or_else(run_budget_config),
+                lint_hooks: lint_hooks_config(),
+                hooks,
                 model_cfg,
                 api_key,
                 auto_approve,
                 permission_profile,
                 cancel: cancel_worker.clone(),
+                test_provider,
+                responses,
+                images,
+                plan_scope,
+                subagent: None,
+                depth: 0,
             };
 
             if let Err(e) = runner.run(repo_root, &user_text, prior_messages, steer, steer_rx, &tx)
@@ -545,13 +1082,39 @@ impl Agent {
 }
 
 struct RunAgent {
-    tools: ToolRegistry,
+    tools: Arc<ToolRegistry>,
     tool_scope: ToolScope,
+    tool_profile: ToolProfile,
+    tool_profile_source: String,
+    protected_paths: ProtectedPaths,
+    change_budget: ChangeBudget,
+    run_budget: RunBudget,
+    lint_hooks: LintHooksConfig,
+    /// `.osmogrep/hooks.toml`.
+    hooks: LifecycleHooks,
     model_cfg: ModelConfig,
     api_key: Option<String>,
     auto_approve: bool,
     permission_profile: PermissionProfile,
     cancel: CancelToken,
+    test_provider: Option<Arc<TestProvider>>,
+    /// Replay, cache and recording of model responses.
+    responses: ResponseStore,
+    /// Sent with the user message, for vision models.
+    images: Vec<ImageAttachment>,
+    /// See [`UserPrompt::plan_scope`].
+    plan_scope: Vec<String>,
+    /// This run's id when it is a `spawn_subagent` child.
+    subagent: Option<usize>,
+    /// How many sub-agents deep this run is; 0 for the main agent.
+    depth: usize,
+}
+
+/// Where a run got to, reported when it fails.
+#[derive(Default)]
+struct Checkpoint {
+    messages: Vec<Value>,
+    transient: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -573,28 +1136,129 @@ impl RunAgent {
         steer_rx: Receiver<String>,
         tx: &Sender<AgentEvent>,
     ) -> Result<(), String> {
-        let api_key = self.api_key.as_ref().ok_or("OPENAI_API_KEY not set")?;
-        let max_iterations = max_iterations();
-        let mut iteration = 0usize;
-        let mut run_notes: Vec<String> = Vec::new();
-        let mut tool_guard = ToolLoopGuard::default();
-        let mut verify_on_stop_attempts = 0usize;
+        let mut checkpoint = Checkpoint::default();
         let mut ledger = RunLedger::start(
             &repo_root,
             user_text,
             &self.model_cfg,
             self.permission_profile,
-            max_iterations,
+            max_iterations(),
         );
+        let result = self.run_turns(
+            &repo_root,
+            user_text,
+            prior_messages,
+            steer,
+            steer_rx,
+            tx,
+            &mut ledger,
+            &mut checkpoint,
+        );
+        if matches!(&result, Err(e) if e != "cancelled") && !checkpoint.messages.is_empty() {
+            let _ = tx.send(AgentEvent::Checkpoint {
+                messages: checkpoint.messages,
+                transient: checkpoint.transient,
+            });
+        }
+        if self.depth == 0 {
+            self.run_finished_hooks(&repo_root, &mut ledger);
+            match ledger.finish() {
+                Ok(path) => {
+                    let _ = tx.send(AgentEvent::Transcript(path));
+                }
+                Err(e) => tracing::warn!(error = %e, "could not write the run transcript"),
+            }
+        }
+        result
+    }
+
+    /// `Err` with the reason when a `pre_tool_call` hook denies the call.
+    fn pre_tool_call_hooks(
+        &self,
+        repo_root: &Path,
+        run_id: &str,
+        name: &str,
+        args: &Value,
+        targets: &[String],
+    ) -> Result<(), String> {
+        if self.hooks.pre_tool_call.is_empty() {
+            return Ok(());
+        }
+        self.hooks.pre_tool_call(
+            repo_root,
+            name,
+            json!({
+                "run_id": run_id,
+                "repo_root": repo_root,
+                "tool": name,
+                "args": args,
+                "targets": targets,
+            }),
+        )
+    }
+
+    fn run_finished_hooks(&self, repo_root: &Path, ledger: &mut RunLedger) {
+        if self.hooks.run_finished.is_empty() {
+            return;
+        }
+        let transcript = ledger.transcript();
+        let mut changed_files: Vec<&str> = Vec::new();
+        for diff in transcript.tool_calls.iter().flat_map(|call| &call.diffs) {
+            if !changed_files.contains(&diff.path.as_str()) {
+                changed_files.push(&diff.path);
+            }
+        }
+        let payload = json!({
+            "run_id": ledger.run_id(),
+            "repo_root": repo_root,
+            "status": transcript.status,
+            "final_answer": transcript.final_answer,
+            "error": transcript.error,
+            "changed_files": changed_files,
+        });
+        for failure in self.hooks.run_finished(repo_root, payload) {
+            tracing::warn!(run_id = %ledger.run_id(), failure = %failure, "run_finished hook failed");
+            ledger.status("hooks", failure, 0);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_turns(
+        &self,
+        repo_root: &Path,
+        user_text: &str,
+        prior_messages: Vec<Value>,
+        steer: Option<String>,
+        steer_rx: Receiver<String>,
+        tx: &Sender<AgentEvent>,
+        ledger: &mut RunLedger,
+        checkpoint: &mut Checkpoint,
+    ) -> Result<(), String> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .or(self.test_provider.as_ref().map(|_| ""))
+            .or(self.responses.is_replaying().then_some(""))
+            .ok_or("OPENAI_API_KEY not set")?;
+        let max_iterations = max_iterations();
+        let mut iteration = 0usize;
+        let mut run_notes: Vec<String> = Vec::new();
+        let mut tool_guard = ToolLoopGuard::default();
+        let mut verify_on_stop_attempts = 0usize;
+        let mut lint_repair = RepairLoop::new(self.lint_hooks.clone());
+        let mut run_changes = RunChanges::default();
+        let mut spend = RunSpend::start();
+        let mut plan: Vec<PlanItem> = Vec::new();
+        self.report_tools(ledger, tx);
 
         let mut persisted = if prior_messages.is_empty() {
-            vec![system_prompt(&repo_root)]
+            vec![system_prompt(repo_root)]
         } else {
             prior_messages
         };
         if let Some(Value::Object(obj)) = persisted.get_mut(0) {
             if let Some(Value::String(content)) = obj.get_mut("content") {
-                let suffix = repo_instruction_suffix(&repo_root);
+                let suffix = repo_instruction_suffix(repo_root);
                 if !suffix.is_empty() && !content.contains("Repository-specific instructions") {
                     content.push_str(&suffix);
                 }
@@ -606,7 +1270,10 @@ impl RunAgent {
                 "content": format!("[steer]\n{}", steer_text),
             }));
         }
-        persisted.push(json!({ "role": "user", "content": user_text }));
+        persisted.push(json!({
+            "role": "user",
+            "content": images::user_content(user_text, &self.images),
+        }));
 
         let mut input = Value::Array(persisted.clone());
 
@@ -616,7 +1283,20 @@ impl RunAgent {
                 ledger.error("cancelled", iteration);
                 return Err("cancelled".into());
             }
+            if let Some(over) = spend.over(&self.run_budget) {
+                let text = run_budget::stop_summary(&over, &spend, &run_changes.paths(), &plan);
+                ledger.status("run_budget", over, iteration);
+                let _ = tx.send(AgentEvent::OutputText(text.clone()));
+                ledger.final_text(&text, iteration);
+                persisted.push(json!({
+                    "role": "assistant",
+                    "content": assistant_memory_text(&text, &run_notes, ledger)
+                }));
+                let _ = tx.send(AgentEvent::ConversationUpdate(persisted));
+                return Ok(());
+            }
 
+            checkpoint.messages = input.as_array().cloned().unwrap_or_default();
             iteration += 1;
             if iteration > max_iterations {
                 let msg = format!("iteration budget exceeded ({max_iterations})");
@@ -637,9 +1317,34 @@ impl RunAgent {
                 iteration,
             );
 
-            let model_response = self.call_openai_with_retry(api_key, &input, tx)?;
+            let model_response = match self.call_openai_with_retry(api_key, &input, tx) {
+                Ok(response) => response,
+                Err(ModelCallError::Final(e)) => {
+                    ledger.error(&e, iteration);
+                    return Err(e);
+                }
+                Err(ModelCallError::Transient { message, .. }) => {
+                    checkpoint.transient = true;
+                    ledger.error(&message, iteration);
+                    return Err(message);
+                }
+            };
             let output_streamed = model_response.output_streamed;
-            let resp = model_response.value;
+            let mut resp = model_response.value;
+            if self.tool_profile.text_tools {
+                text_tools::adapt_output(&mut resp, &self.exposed_tool_names());
+            }
+            ledger.usage(resp.get("usage"));
+            let usage = turn_usage(&self.model_cfg, &input, &resp);
+            if let AgentEvent::Usage {
+                input_tokens,
+                output_tokens,
+                ..
+            } = &usage
+            {
+                spend.tokens += input_tokens + output_tokens;
+            }
+            let _ = tx.send(usage);
 
             let output = resp
                 .get("output")
@@ -671,21 +1376,35 @@ impl RunAgent {
                     }
 
                     Some("function_call") => {
-                        let batch = collect_parallel_safe_batch(output, batch_start, &self.tools)?;
+                        // Calls past the run budget are dropped; the loop
+                        // head then stops the run.
+                        if spend.over(&self.run_budget).is_some() {
+                            continue;
+                        }
+                        let mut batch =
+                            collect_parallel_safe_batch(output, batch_start, &self.tools)?;
+                        if let Some(max) = self.run_budget.max_tool_calls {
+                            batch.truncate(max - spend.tool_calls);
+                        }
                         if batch.len() > 1 {
                             let batch_len = batch.len();
+                            spend.tool_calls += batch_len;
                             saw_tool = true;
                             self.execute_parallel_safe_batch(
                                 batch,
-                                &repo_root,
+                                repo_root,
                                 tx,
-                                &mut ledger,
+                                ledger,
                                 &mut tool_guard,
                                 &mut run_notes,
                                 &mut next_messages,
                                 iteration,
                                 max_iterations,
                             );
+                            if self.cancel.is_cancelled() {
+                                ledger.error("cancelled", iteration);
+                                return Err("cancelled".into());
+                            }
                             idx = batch_start + batch_len;
                             continue;
                         }
@@ -698,11 +1417,12 @@ impl RunAgent {
                         let args = invocation.args;
                         let args_summary = invocation.args_summary;
 
-                        emit_agent_events(tx, pre_tool_events(&name, &args, &repo_root));
+                        emit_agent_events(tx, pre_tool_events(&name, &args, repo_root));
                         let _ = tx.send(AgentEvent::ToolCall {
                             name: name.clone(),
-                            args: normalize_tool_event_args(&name, &args, &repo_root),
+                            args: normalize_tool_event_args(&name, &args, repo_root),
                         });
+                        spend.tool_calls += 1;
                         send_run_status(
                             tx,
                             "tool",
@@ -712,6 +1432,19 @@ impl RunAgent {
                         );
                         ledger.tool_started(&name, &args_summary, iteration);
 
+                        if self.tool_scope.excludes(&name) {
+                            let error = format!("`{name}` is not in this sub-agent's tool set");
+                            ledger.permission(&name, "blocked-scope", iteration);
+                            run_notes.push(format!("- blocked `{name}` ({args_summary}): {error}"));
+                            next_messages.push(item.clone());
+                            next_messages.push(json!({
+                                "type": "function_call_output",
+                                "call_id": call_id,
+                                "output": json!({ "error": error }).to_string()
+                            }));
+                            continue;
+                        }
+
                         let dangerous = self.tools.safety(&name) == Some(ToolSafety::Dangerous);
                         if dangerous && self.permission_profile == PermissionProfile::ReadOnly {
                             ledger.permission(&name, "blocked-read-only", iteration);
@@ -727,9 +1460,74 @@ impl RunAgent {
                             continue;
                         }
 
-                        if let Some((target, before, after)) =
-                            preview_diff_from_args(&name, &args, &repo_root)
-                        {
+                        let targets = match mutation_targets(&name, &args, repo_root) {
+                            Ok(targets) => targets,
+                            Err(reason) => {
+                                ledger.permission(&name, "blocked-outside-repo", iteration);
+                                run_notes
+                                    .push(format!("- blocked `{name}` ({args_summary}): {reason}"));
+                                next_messages.push(item.clone());
+                                next_messages.push(json!({
+                                    "type": "function_call_output",
+                                    "call_id": call_id,
+                                    "output": json!({ "error": reason }).to_string()
+                                }));
+                                continue;
+                            }
+                        };
+                        let existed = target_existence(&targets, repo_root);
+                        let policy = self.tools.permission_decision(&name, &args, &targets);
+                        if let Decision::Deny(reason) = &policy {
+                            ledger.permission(&name, "blocked-policy", iteration);
+                            run_notes
+                                .push(format!("- blocked `{name}` ({args_summary}): {reason}"));
+                            next_messages.push(item.clone());
+                            next_messages.push(json!({
+                                "type": "function_call_output",
+                                "call_id": call_id,
+                                "output": json!({ "error": reason }).to_string()
+                            }));
+                            continue;
+                        }
+                        if let Some(reason) = outside_plan(&self.plan_scope, &targets) {
+                            ledger.permission(&name, "blocked-plan", iteration);
+                            run_notes
+                                .push(format!("- blocked `{name}` ({args_summary}): {reason}"));
+                            next_messages.push(item.clone());
+                            next_messages.push(json!({
+                                "type": "function_call_output",
+                                "call_id": call_id,
+                                "output": json!({ "error": reason }).to_string()
+                            }));
+                            continue;
+                        }
+                        if let Err(reason) = self.pre_tool_call_hooks(
+                            repo_root,
+                            ledger.run_id(),
+                            &name,
+                            &args,
+                            &targets,
+                        ) {
+                            ledger.permission(&name, "blocked-hook", iteration);
+                            run_notes
+                                .push(format!("- blocked `{name}` ({args_summary}): {reason}"));
+                            next_messages.push(item.clone());
+                            next_messages.push(json!({
+                                "type": "function_call_output",
+                                "call_id": call_id,
+                                "output": json!({ "error": reason }).to_string()
+                            }));
+                            continue;
+                        }
+
+                        // A rename previews every file it would touch.
+                        let previews = match name.as_str() {
+                            "rename_symbol" => crate::tools::rename_preview(repo_root, &args),
+                            _ => preview_diff_from_args(&name, &args, repo_root)
+                                .into_iter()
+                                .collect(),
+                        };
+                        for (target, before, after) in previews {
                             let _ = tx.send(AgentEvent::PreviewDiff {
                                 tool: name.clone(),
                                 target,
@@ -738,28 +1536,80 @@ impl RunAgent {
                             });
                         }
 
-                        let should_prompt = dangerous
-                            && self.permission_profile != PermissionProfile::FullAccess
-                            && !self.auto_approve;
+                        let violations = self.protected_paths.check(&targets);
+                        if !violations.is_empty()
+                            && self.protected_paths.mode == ProtectMode::Refuse
+                        {
+                            let message =
+                                protected_paths::refusal_message(&violations, ProtectMode::Refuse);
+                            ledger.permission(&name, "blocked-protected", iteration);
+                            run_notes
+                                .push(format!("- blocked `{name}` ({args_summary}): {message}"));
+                            next_messages.push(item.clone());
+                            next_messages.push(json!({
+                                "type": "function_call_output",
+                                "call_id": call_id,
+                                "output": json!({ "error": message }).to_string()
+                            }));
+                            continue;
+                        }
+                        let protected = (!violations.is_empty()).then(|| {
+                            violations
+                                .iter()
+                                .map(|v| v.describe())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        });
+
+                        // The repo policy can pre-approve a call, but never a
+                        // protected-path edit.
+                        let mut should_prompt = dangerous
+                            && (protected.is_some()
+                                || (policy != Decision::Allow
+                                    && self.permission_profile != PermissionProfile::FullAccess
+                                    && !self.auto_approve));
+                        // So can an earlier [a]lways answer.
+                        if should_prompt && protected.is_none() {
+                            let approvals = Approvals::load(repo_root);
+                            if approvals.find(&name, &args, &targets).is_some() {
+                                should_prompt = false;
+                                ledger.permission(&name, "approved-remembered", iteration);
+                            }
+                        }
                         if should_prompt {
                             let (reply_tx, reply_rx) = mpsc::channel::<bool>();
                             let _ = tx.send(AgentEvent::PermissionRequest {
                                 tool_name: name.clone(),
                                 args_summary: args_summary.clone(),
+                                protected: protected.clone(),
+                                always: approvals::pattern_for(&name, &args, &targets),
                                 reply_tx,
                             });
 
                             let allow = reply_rx.recv().map_err(|_| "permission channel closed")?;
                             if !allow {
                                 ledger.permission(&name, "denied", iteration);
-                                run_notes.push(format!(
-                                    "- denied `{name}` ({args_summary}): user denied permission"
-                                ));
+                                record_rejected_test_edit(
+                                    repo_root,
+                                    &name,
+                                    &args,
+                                    &self.model_cfg.model,
+                                );
+                                let error = if protected.is_some() {
+                                    protected_paths::refusal_message(
+                                        &violations,
+                                        ProtectMode::Confirm,
+                                    )
+                                } else {
+                                    "user denied permission".to_string()
+                                };
+                                run_notes
+                                    .push(format!("- denied `{name}` ({args_summary}): {error}"));
                                 next_messages.push(item.clone());
                                 next_messages.push(json!({
                                     "type": "function_call_output",
                                     "call_id": call_id,
-                                    "output": "{\"error\":\"user denied permission\"}"
+                                    "output": json!({ "error": error }).to_string()
                                 }));
                                 continue;
                             }
@@ -768,10 +1618,13 @@ impl RunAgent {
 
                         let started = Instant::now();
                         let cancel = self.cancel.clone();
-                        let mut result = self
-                            .tools
-                            .call_cancellable(&name, args.clone(), &|| cancel.is_cancelled())
-                            .unwrap_or_else(|e| json!({ "error": e }));
+                        let mut result = if name == "spawn_subagent" {
+                            self.run_subagent(repo_root, &args, tx)
+                        } else {
+                            self.tools
+                                .call_cancellable(&name, args.clone(), &|| cancel.is_cancelled())
+                                .unwrap_or_else(|e| json!({ "error": e }))
+                        };
                         if self.cancel.is_cancelled() {
                             ledger.error("cancelled", iteration);
                             return Err("cancelled".into());
@@ -786,18 +1639,53 @@ impl RunAgent {
                         }
                         let duration_ms = started.elapsed().as_millis();
                         let ok = result.get("error").is_none();
+                        if name == "spawn_subagent" {
+                            spend.tokens += result
+                                .get("tokens")
+                                .and_then(Value::as_u64)
+                                .unwrap_or_default()
+                                as usize;
+                        }
+                        if name == "git_commit"
+                            && result.get("commit_exit_code").and_then(Value::as_i64) == Some(0)
+                        {
+                            if let Err(e) = testgen::record_commit(
+                                repo_root,
+                                &self.model_cfg.model,
+                                PROMPT_VERSION,
+                            ) {
+                                run_notes.push(format!("- testgen ledger not updated: {e}"));
+                            }
+                        }
 
-                        if let (Some(before), Some(after), Some(path)) = (
-                            result.get("before").and_then(Value::as_str),
-                            result.get("after").and_then(Value::as_str),
-                            result.get("path").and_then(Value::as_str),
-                        ) {
-                            let _ = tx.send(AgentEvent::ToolDiff {
-                                tool: name.clone(),
-                                target: repo_relative_path(&repo_root, path),
-                                before: before.to_string(),
-                                after: after.to_string(),
-                            });
+                        let mut diffs = Vec::new();
+                        for (path, before, after) in result_diffs(&result) {
+                            let target = repo_relative_path(repo_root, path);
+                            diffs.push(FileDiff::new(&target, before, after));
+                            let change =
+                                tool_change(&name, target, before, after, &existed, repo_root);
+                            run_changes.record(&change);
+                            let _ = tx.send(tool_diff_event(change));
+                        }
+                        if !diffs.is_empty() && !self.hooks.post_edit.is_empty() {
+                            let failures = self.hooks.post_edit(
+                                repo_root,
+                                &name,
+                                json!({
+                                    "run_id": ledger.run_id(),
+                                    "repo_root": repo_root,
+                                    "tool": name,
+                                    "files": diffs.iter().map(|d| &d.path).collect::<Vec<_>>(),
+                                }),
+                            );
+                            for failure in &failures {
+                                ledger.status("hooks", failure.as_str(), iteration);
+                            }
+                            if !failures.is_empty() {
+                                if let Some(map) = result.as_object_mut() {
+                                    map.insert("post_edit_hook_errors".into(), json!(failures));
+                                }
+                            }
                         }
 
                         let mut summary = tool_result_summary(&name, &result);
@@ -805,24 +1693,27 @@ impl RunAgent {
                             summary = format!("{summary}; {}", warning.message);
                         }
                         if let Some(items) = plan_items_from_result(&name, &result) {
+                            plan = items.clone();
                             let _ = tx.send(AgentEvent::PlanUpdate { items });
                         }
                         emit_agent_events(
                             tx,
-                            post_tool_events(&name, &args, &result, &summary, ok, &repo_root),
+                            post_tool_events(&name, &args, &result, &summary, ok, repo_root),
                         );
                         let status = if ok { "ok" } else { "error" };
-                        ledger.tool_finished(
-                            &name,
+                        ledger.tool_finished(ToolCallRecord {
+                            iteration,
+                            tool: name.clone(),
+                            args: args.clone(),
                             status,
-                            summary.clone(),
+                            summary: summary.clone(),
                             duration_ms,
-                            iteration,
-                        );
+                            diffs,
+                        });
                         send_run_status(
                             tx,
                             if ok { "tool_done" } else { "tool_error" },
-                            format!("{name} {status} in {duration_ms}ms"),
+                            format!("{name} {status} in {}", timefmt::duration_ms(duration_ms)),
                             iteration,
                             max_iterations,
                         );
@@ -837,18 +1728,56 @@ impl RunAgent {
                         next_messages.push(json!({
                             "type": "function_call_output",
                             "call_id": call_id,
-                            "output": serde_json::to_string(&result).unwrap()
+                            "output": serde_json::to_string(
+                                &self.tools.budget_result(&call_id, result)
+                            )
+                            .unwrap()
                         }));
+
+                        if let Some(over) = run_changes.over_budget(&self.change_budget) {
+                            let decision = ask_change_budget(tx, &over, &run_changes)?;
+                            ledger.status(
+                                "change_budget",
+                                format!("{over}: {}", decision.as_str()),
+                                iteration,
+                            );
+                            if decision == BudgetDecision::Continue {
+                                run_changes.allow_more();
+                                continue;
+                            }
+                            let text =
+                                stop_at_change_budget(decision, &over, &run_changes, repo_root, tx);
+                            let _ = tx.send(AgentEvent::OutputText(text.clone()));
+                            ledger.final_text(&text, iteration);
+                            persisted.push(json!({
+                                "role": "assistant",
+                                "content": assistant_memory_text(&text, &run_notes, ledger)
+                            }));
+                            let _ = tx.send(AgentEvent::ConversationUpdate(persisted));
+                            return Ok(());
+                        }
                     }
 
                     Some("output_text") => {
                         if let Some(text) = item.get("text").and_then(Value::as_str) {
+                            if let Some(follow_up) = self.lint_follow_up(
+                                repo_root,
+                                &mut lint_repair,
+                                &run_changes,
+                                iteration,
+                                ledger,
+                                tx,
+                            ) {
+                                queue_follow_up(&mut next_messages, text, follow_up);
+                                input = Value::Array(next_messages);
+                                continue 'agent_loop;
+                            }
                             if queue_verify_on_stop(
-                                &repo_root,
+                                repo_root,
                                 &mut verify_on_stop_attempts,
                                 text,
                                 iteration,
-                                &mut ledger,
+                                ledger,
                                 &mut run_notes,
                                 &mut next_messages,
                             ) {
@@ -859,7 +1788,7 @@ impl RunAgent {
                             ledger.final_text(text, iteration);
                             persisted.push(json!({
                                 "role": "assistant",
-                                "content": assistant_memory_text(text, &run_notes, &ledger)
+                                "content": assistant_memory_text(text, &run_notes, ledger)
                             }));
                             let _ = tx.send(AgentEvent::ConversationUpdate(persisted));
                             return Ok(());
@@ -871,12 +1800,24 @@ impl RunAgent {
                             for c in content {
                                 if c.get("type").and_then(Value::as_str) == Some("output_text") {
                                     if let Some(text) = c.get("text").and_then(Value::as_str) {
+                                        if let Some(follow_up) = self.lint_follow_up(
+                                            repo_root,
+                                            &mut lint_repair,
+                                            &run_changes,
+                                            iteration,
+                                            ledger,
+                                            tx,
+                                        ) {
+                                            queue_follow_up(&mut next_messages, text, follow_up);
+                                            input = Value::Array(next_messages);
+                                            continue 'agent_loop;
+                                        }
                                         if queue_verify_on_stop(
-                                            &repo_root,
+                                            repo_root,
                                             &mut verify_on_stop_attempts,
                                             text,
                                             iteration,
-                                            &mut ledger,
+                                            ledger,
                                             &mut run_notes,
                                             &mut next_messages,
                                         ) {
@@ -890,7 +1831,7 @@ impl RunAgent {
                                             "content": assistant_memory_text(
                                                 text,
                                                 &run_notes,
-                                                &ledger
+                                                ledger
                                             )
                                         }));
                                         let _ = tx.send(AgentEvent::ConversationUpdate(persisted));
@@ -900,18 +1841,187 @@ impl RunAgent {
                             }
                         }
                     }
-
-                    _ => {}
+
+                    _ => {}
+                }
+            }
+
+            if !saw_tool && spend.over(&self.run_budget).is_none() {
+                ledger.error("model returned no tool calls and no output_text", iteration);
+                return Err("model returned no tool calls and no output_text".into());
+            }
+
+            input = Value::Array(next_messages);
+        }
+    }
+
+    /// Runs a `spawn_subagent` call: a child agent with a fresh
+    /// conversation, a narrower tool scope and a token budget. Its tool
+    /// activity, edits and permission prompts pass through to `tx`; its
+    /// final text comes back as the result.
+    fn run_subagent(&self, repo_root: &Path, args: &Value, tx: &Sender<AgentEvent>) -> Value {
+        let task = args
+            .get("task")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .unwrap_or("");
+        if task.is_empty() {
+            return json!({ "error": "task is required" });
+        }
+        let token_budget = args
+            .get("max_tokens")
+            .and_then(Value::as_u64)
+            .map_or(SUBAGENT_TOKEN_BUDGET, |n| {
+                (n as usize).clamp(1_000, SUBAGENT_MAX_TOKENS)
+            });
+        let tools = args.get("tools").and_then(Value::as_array).map(|names| {
+            names
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        });
+        let depth = self.depth + 1;
+        let id = NEXT_SUBAGENT_ID.fetch_add(1, Ordering::SeqCst);
+        let child = RunAgent {
+            tools: Arc::clone(&self.tools),
+            tool_scope: self
+                .tool_scope
+                .clone()
+                .for_subagent(tools, depth < SUBAGENT_MAX_DEPTH),
+            tool_profile: self.tool_profile.clone(),
+            tool_profile_source: self.tool_profile_source.clone(),
+            protected_paths: self.protected_paths.clone(),
+            change_budget: self.change_budget,
+            // Bounded by its token budget; its spend counts toward this run's.
+            run_budget: RunBudget::default(),
+            lint_hooks: self.lint_hooks.clone(),
+            hooks: self.hooks.clone(),
+            model_cfg: self.model_cfg.clone(),
+            api_key: self.api_key.clone(),
+            auto_approve: self.auto_approve,
+            permission_profile: self.permission_profile,
+            cancel: CancelToken::new(),
+            test_provider: self.test_provider.clone(),
+            responses: self.responses.clone(),
+            images: Vec::new(),
+            plan_scope: self.plan_scope.clone(),
+            subagent: Some(id),
+            depth,
+        };
+        let child_cancel = child.cancel.clone();
+
+        let mut status = SubagentStatus {
+            id,
+            parent: self.subagent,
+            task: task.lines().next().unwrap_or(task).to_string(),
+            state: SubagentState::Running,
+            tool_calls: 0,
+            tokens: 0,
+            token_budget,
+        };
+        let _ = tx.send(AgentEvent::Subagent(status.clone()));
+
+        let prompt = subagent_prompt(task);
+        let (child_tx, child_rx) = mpsc::channel::<AgentEvent>();
+        let (_steer_tx, steer_rx) = mpsc::channel::<String>();
+        let mut text = String::new();
+        let mut streamed = String::new();
+        let mut over_budget = false;
+        let outcome = thread::scope(|scope| {
+            let worker = scope.spawn(move || {
+                child.run(
+                    repo_root.to_path_buf(),
+                    &prompt,
+                    Vec::new(),
+                    None,
+                    steer_rx,
+                    &child_tx,
+                )
+            });
+            loop {
+                match child_rx.recv_timeout(Duration::from_millis(100)) {
+                    Ok(event) => {
+                        // The child's answer is the tool result, not output.
+                        match event {
+                            AgentEvent::StreamDelta(delta) => streamed.push_str(&delta),
+                            AgentEvent::StreamDone => text = std::mem::take(&mut streamed),
+                            AgentEvent::OutputText(output) => text = output,
+                            AgentEvent::RunStatus { .. }
+                            | AgentEvent::PlanUpdate { .. }
+                            | AgentEvent::ConversationUpdate(_)
+                            | AgentEvent::Checkpoint { .. }
+                            | AgentEvent::ToolsExposed { .. }
+                            | AgentEvent::Cancelled
+                            | AgentEvent::Error(_)
+                            | AgentEvent::Done => {}
+                            event => {
+                                let progress = match &event {
+                                    AgentEvent::ToolCall { .. } => {
+                                        status.tool_calls += 1;
+                                        true
+                                    }
+                                    AgentEvent::Usage {
+                                        input_tokens,
+                                        output_tokens,
+                                        ..
+                                    } => {
+                                        status.tokens += input_tokens + output_tokens;
+                                        true
+                                    }
+                                    _ => false,
+                                };
+                                let _ = tx.send(event);
+                                if progress {
+                                    let _ = tx.send(AgentEvent::Subagent(status.clone()));
+                                }
+                            }
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if status.tokens >= token_budget && !over_budget {
+                    over_budget = true;
+                    child_cancel.cancel();
+                }
+                if self.cancel.is_cancelled() {
+                    child_cancel.cancel();
                 }
             }
+            worker
+                .join()
+                .unwrap_or_else(|_| Err("sub-agent panicked".to_string()))
+        });
 
-            if !saw_tool {
-                ledger.error("model returned no tool calls and no output_text", iteration);
-                return Err("model returned no tool calls and no output_text".into());
+        status.state = match &outcome {
+            Ok(()) => SubagentState::Done,
+            Err(_) if over_budget => SubagentState::OverBudget,
+            Err(e) if e == "cancelled" => SubagentState::Cancelled,
+            Err(_) => SubagentState::Failed,
+        };
+        let _ = tx.send(AgentEvent::Subagent(status.clone()));
+
+        let mut result = json!({
+            "subagent": id,
+            "status": status.state.as_str(),
+            "tool_calls": status.tool_calls,
+            "tokens": status.tokens,
+            "token_budget": token_budget,
+        });
+        if !text.trim().is_empty() {
+            result["summary"] = json!(text.trim());
+        }
+        match outcome {
+            Ok(()) => {}
+            Err(_) if over_budget => {
+                result["error"] = json!(format!(
+                    "sub-agent spent its {token_budget}-token budget before finishing"
+                ));
             }
-
-            input = Value::Array(next_messages);
+            Err(e) => result["error"] = json!(e),
         }
+        result
     }
 
     fn execute_parallel_safe_batch(
@@ -926,10 +2036,14 @@ impl RunAgent {
         iteration: usize,
         max_iterations: usize,
     ) {
+        let workers = max_parallel_tools().min(batch.len());
         send_run_status(
             tx,
             "tool",
-            format!("parallel read-only batch ({} tools)", batch.len()),
+            format!(
+                "parallel read-only batch ({} tools, {workers} at a time)",
+                batch.len()
+            ),
             iteration,
             max_iterations,
         );
@@ -946,31 +2060,37 @@ impl RunAgent {
             ledger.tool_started(&invocation.name, &invocation.args_summary, iteration);
         }
 
-        let results = thread::scope(|scope| {
-            let mut handles = Vec::new();
-            for invocation in &batch {
-                let tools = &self.tools;
-                let name = invocation.name.clone();
-                let args = invocation.args.clone();
-                let cancel = self.cancel.clone();
-                handles.push(scope.spawn(move || {
-                    let started = Instant::now();
-                    let result = tools
-                        .call_parallel_safe_cancellable(&name, args, &|| cancel.is_cancelled())
-                        .unwrap_or_else(|e| json!({ "error": e }));
-                    (result, started.elapsed().as_millis())
-                }));
+        let run_id = ledger.run_id().to_string();
+        let results = parallel_map(&batch, workers, |invocation| {
+            let started = Instant::now();
+            if self.tool_scope.excludes(&invocation.name) {
+                let error = format!("`{}` is not in this sub-agent's tool set", invocation.name);
+                return (json!({ "error": error }), 0);
             }
-
-            handles
-                .into_iter()
-                .map(|handle| {
-                    handle
-                        .join()
-                        .unwrap_or_else(|_| (json!({ "error": "parallel tool panicked" }), 0))
+            let targets = match mutation_targets(&invocation.name, &invocation.args, repo_root) {
+                Ok(targets) => targets,
+                Err(error) => return (json!({ "error": error }), started.elapsed().as_millis()),
+            };
+            if let Err(error) = self.pre_tool_call_hooks(
+                repo_root,
+                &run_id,
+                &invocation.name,
+                &invocation.args,
+                &targets,
+            ) {
+                return (json!({ "error": error }), started.elapsed().as_millis());
+            }
+            let result = self
+                .tools
+                .call_parallel_safe_cancellable(&invocation.name, invocation.args.clone(), &|| {
+                    self.cancel.is_cancelled()
                 })
-                .collect::<Vec<_>>()
-        });
+                .unwrap_or_else(|e| json!({ "error": e }));
+            (result, started.elapsed().as_millis())
+        })
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| (json!({ "error": "parallel tool panicked" }), 0)))
+        .collect::<Vec<_>>();
 
         for (invocation, (mut result, duration_ms)) in batch.into_iter().zip(results) {
             let loop_warning = tool_guard.after_call(&invocation.name, &invocation.args, &result);
@@ -983,17 +2103,13 @@ impl RunAgent {
             }
             let ok = result.get("error").is_none();
 
-            if let (Some(before), Some(after), Some(path)) = (
-                result.get("before").and_then(Value::as_str),
-                result.get("after").and_then(Value::as_str),
-                result.get("path").and_then(Value::as_str),
-            ) {
-                let _ = tx.send(AgentEvent::ToolDiff {
-                    tool: invocation.name.clone(),
-                    target: repo_relative_path(repo_root, path),
-                    before: before.to_string(),
-                    after: after.to_string(),
-                });
+            let mut diffs = Vec::new();
+            for (path, before, after) in result_diffs(&result) {
+                let target = repo_relative_path(repo_root, path);
+                diffs.push(FileDiff::new(&target, before, after));
+                // Parallel calls are read-only, so nothing was announced.
+                let change = tool_change(&invocation.name, target, before, after, &[], repo_root);
+                let _ = tx.send(tool_diff_event(change));
             }
 
             let mut summary = tool_result_summary(&invocation.name, &result);
@@ -1012,17 +2128,23 @@ impl RunAgent {
                 ),
             );
             let status = if ok { "ok" } else { "error" };
-            ledger.tool_finished(
-                &invocation.name,
+            ledger.tool_finished(ToolCallRecord {
+                iteration,
+                tool: invocation.name.clone(),
+                args: invocation.args.clone(),
                 status,
-                summary.clone(),
+                summary: summary.clone(),
                 duration_ms,
-                iteration,
-            );
+                diffs,
+            });
             send_run_status(
                 tx,
                 if ok { "tool_done" } else { "tool_error" },
-                format!("{} {status} in {duration_ms}ms", invocation.name),
+                format!(
+                    "{} {status} in {}",
+                    invocation.name,
+                    timefmt::duration_ms(duration_ms)
+                ),
                 iteration,
                 max_iterations,
             );
@@ -1039,25 +2161,70 @@ impl RunAgent {
             next_messages.push(json!({
                 "type": "function_call_output",
                 "call_id": invocation.call_id,
-                "output": serde_json::to_string(&result).unwrap()
+                "output": serde_json::to_string(
+                    &self.tools.budget_result(&invocation.call_id, result)
+                )
+                .unwrap()
             }));
         }
     }
 
+    /// Replayed or cached responses skip the API; fresh ones are stored.
     fn call_openai_with_retry(
         &self,
         api_key: &str,
         input: &Value,
         tx: &Sender<AgentEvent>,
-    ) -> Result<ModelResponse, String> {
+    ) -> Result<ModelResponse, ModelCallError> {
+        let adapted;
+        let input = if self.tool_profile.text_tools {
+            adapted = text_tools::adapt_input(input, &self.exposed_tools().0);
+            &adapted
+        } else {
+            input
+        };
+        let key = response_cache::key(&self.model_cfg.model, input);
+        if let Some(stored) = self.responses.lookup(&key) {
+            return stored
+                .map(|value| ModelResponse {
+                    value,
+                    output_streamed: false,
+                })
+                .map_err(ModelCallError::Final);
+        }
+        let result = self.call_model(api_key, input, tx);
+        if let Ok(response) = &result {
+            self.responses.store(&key, &response.value);
+        }
+        result
+    }
+
+    fn call_model(
+        &self,
+        api_key: &str,
+        input: &Value,
+        tx: &Sender<AgentEvent>,
+    ) -> Result<ModelResponse, ModelCallError> {
+        if let Some(provider) = self.test_provider.as_ref() {
+            return provider
+                .respond(input, tx)
+                .map(|response| ModelResponse {
+                    value: response.value,
+                    output_streamed: response.output_streamed,
+                })
+                .map_err(ModelCallError::Final);
+        }
+
+        let policy = RetryPolicy::from_env();
         let mut last_err = None;
 
-        for attempt in 1..=3 {
+        for attempt in 1..=policy.max_attempts {
             if self.cancel.is_cancelled() {
                 return Err("cancelled".into());
             }
 
             let streaming_disabled = env_truthy("OSMOGREP_NO_STREAM", false);
+            let started = Instant::now();
             let result = if streaming_disabled {
                 self.call_openai_blocking(api_key, input)
                     .map(|value| ModelResponse {
@@ -1067,78 +2234,168 @@ impl RunAgent {
             } else {
                 self.call_openai_streaming(api_key, input, tx)
             };
+            self.trace_model_call(&result, started, attempt, !streaming_disabled);
 
             match result {
                 Ok(response) => return Ok(response),
-                Err(e) => {
-                    last_err = Some(e.clone());
-                    if attempt < 3 {
-                        thread::sleep(Duration::from_millis(350 * attempt as u64));
+                Err(ModelCallError::Final(e)) => return Err(ModelCallError::Final(e)),
+                Err(ModelCallError::Transient {
+                    message,
+                    status,
+                    retry_after,
+                }) => {
+                    last_err = Some(message);
+                    if attempt < policy.max_attempts {
+                        let wait = policy.delay(attempt, retry_after);
+                        let _ = tx.send(AgentEvent::Retrying(RetryNotice {
+                            host: self.endpoint_host(),
+                            status,
+                            wait,
+                            attempt,
+                            max_attempts: policy.max_attempts,
+                        }));
+                        http_policy::sleep_cancellable(wait, &|| self.cancel.is_cancelled())?;
                     }
                 }
             }
         }
 
-        Err(last_err.unwrap_or_else(|| "unknown API error".into()))
+        Err(last_err
+            .unwrap_or_else(|| "unknown API error".to_string())
+            .into())
+    }
+
+    fn trace_model_call(
+        &self,
+        result: &Result<ModelResponse, ModelCallError>,
+        started: Instant,
+        attempt: u32,
+        streamed: bool,
+    ) {
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let model = &self.model_cfg.model;
+        match result {
+            Ok(response) => {
+                let usage = response.value.get("usage");
+                let tokens = |key: &str| usage.and_then(|u| u.get(key)).and_then(Value::as_u64);
+                tracing::info!(
+                    model = %model,
+                    latency_ms,
+                    attempt,
+                    streamed,
+                    input_tokens = tokens("input_tokens"),
+                    output_tokens = tokens("output_tokens"),
+                    "model call"
+                );
+            }
+            Err(ModelCallError::Final(e)) | Err(ModelCallError::Transient { message: e, .. }) => {
+                tracing::warn!(
+                    model = %model,
+                    latency_ms,
+                    attempt,
+                    streamed,
+                    error = %e,
+                    "model call failed"
+                );
+            }
+        }
     }
 
-    fn call_openai_blocking(&self, api_key: &str, input: &Value) -> Result<Value, String> {
+    /// Sends the request once, on a worker thread so a cancel does not wait
+    /// for it; `call_model` does the retrying.
+    fn call_openai_blocking(&self, api_key: &str, input: &Value) -> Result<Value, ModelCallError> {
         let payload = self.responses_payload(input, false);
 
-        let mut child = Command::new("curl")
-            .arg("-s")
-            .arg("-w")
-            .arg("\n%{http_code}")
-            .arg("-X")
-            .arg("POST")
-            .arg(self.responses_endpoint())
-            .arg("-H")
-            .arg("Content-Type: application/json")
-            .arg("-H")
-            .arg(format!("Authorization: Bearer {}", api_key))
-            .arg("--data-binary")
-            .arg(payload.to_string())
-            .stdout(Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?;
+        let client = net::client_builder()
+            .map_err(ModelCallError::Final)?
+            .timeout(Duration::from_secs(300))
+            .build()
+            .map_err(|e| ModelCallError::Final(e.to_string()))?;
+        let request = client
+            .post(self.responses_endpoint())
+            .bearer_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(&payload);
 
-        loop {
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = done_tx.send(request.send().and_then(ApiReply::read));
+        });
+
+        let reply = loop {
             if self.cancel.is_cancelled() {
-                let _ = child.kill();
-                return Err("cancelled".into());
+                return Err(ModelCallError::Final("cancelled".into()));
             }
-
-            match child.try_wait() {
-                Ok(Some(_status)) => break,
-                Ok(None) => thread::sleep(Duration::from_millis(100)),
-                Err(e) => return Err(e.to_string()),
+            match done_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(reply) => break reply,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("request thread exited".to_string().into())
+                }
             }
-        }
+        };
 
-        let mut out = String::new();
-        child
-            .stdout
-            .take()
-            .ok_or("missing stdout")?
-            .read_to_string(&mut out)
-            .map_err(|e| e.to_string())?;
+        // No response means the request never got through: worth another try.
+        reply.map_err(|e| e.to_string())?.into_result()
+    }
 
-        let (body, status) = out.rsplit_once('\n').ok_or("missing status")?;
-        if status != "200" {
-            return Err(format_api_error(status, body));
-        }
+    fn endpoint_host(&self) -> String {
+        Url::parse(&self.responses_endpoint())
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.model_cfg.provider.clone())
+    }
 
-        serde_json::from_str(body).map_err(|e| e.to_string())
+    /// Tool schemas for this run after the model's capability profile, and
+    /// the names the profile dropped.
+    fn exposed_tools(&self) -> (Vec<Value>, Vec<String>) {
+        let mut schemas = self.tools.scoped_schema(&self.tool_scope);
+        schemas.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        self.tool_profile.apply(schemas)
+    }
+
+    fn exposed_tool_names(&self) -> Vec<String> {
+        self.exposed_tools()
+            .0
+            .iter()
+            .filter_map(|schema| schema["name"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    fn report_tools(&self, ledger: &mut RunLedger, tx: &Sender<AgentEvent>) {
+        let dropped = self.exposed_tools().1;
+        let exposed = self.exposed_tool_names();
+        let mut detail = format!(
+            "profile {}: {} exposed ({})",
+            self.tool_profile_source,
+            exposed.len(),
+            exposed.join(", ")
+        );
+        if !dropped.is_empty() {
+            detail.push_str(&format!("; dropped {}", dropped.join(", ")));
+        }
+        if self.tool_profile.text_tools {
+            detail.push_str("; called through text");
+        }
+        ledger.status("tools", detail, 0);
+        let _ = tx.send(AgentEvent::ToolsExposed {
+            profile: self.tool_profile_source.clone(),
+            exposed,
+            dropped,
+        });
     }
 
     fn responses_payload(&self, input: &Value, stream: bool) -> Value {
         let mut payload = json!({
             "model": self.model_cfg.model,
             "input": input,
-            "tools": self.tools.scoped_schema(&self.tool_scope),
-            "tool_choice": "auto",
             "store": true
         });
+        // Text tools travel in the prompt instead.
+        if !self.tool_profile.text_tools {
+            payload["tools"] = json!(self.exposed_tools().0);
+            payload["tool_choice"] = json!("auto");
+        }
 
         if let Some(effort) = reasoning_effort_for(&self.model_cfg.model) {
             payload["reasoning"] = json!({ "effort": effort });
@@ -1151,31 +2408,40 @@ impl RunAgent {
         payload
     }
 
+    /// Connection failures and retryable statuses are already retried by the
+    /// HTTP policy, so only a stream that breaks mid-response is `Transient`.
     fn call_openai_streaming(
         &self,
         api_key: &str,
         input: &Value,
         tx: &Sender<AgentEvent>,
-    ) -> Result<ModelResponse, String> {
+    ) -> Result<ModelResponse, ModelCallError> {
         let payload = self.responses_payload(input, true);
 
-        let client = reqwest::blocking::Client::builder()
+        let client = net::client_builder()
+            .map_err(ModelCallError::Final)?
             .timeout(Duration::from_secs(120))
             .build()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| ModelCallError::Final(e.to_string()))?;
 
-        let mut resp = client
+        let request = client
             .post(self.responses_endpoint())
             .bearer_auth(api_key)
             .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .map_err(|e| e.to_string())?;
+            .json(&payload);
+        let mut resp =
+            http_policy::send_observed(request, &|| self.cancel.is_cancelled(), &|notice| {
+                let _ = tx.send(AgentEvent::Retrying(notice.clone()));
+            })
+            .map_err(ModelCallError::Final)?;
 
         let status = resp.status();
         if !status.is_success() {
             let body = resp.text().unwrap_or_default();
-            return Err(format_api_error(&status.to_string(), &body));
+            return Err(ModelCallError::Final(format_api_error(
+                &status.to_string(),
+                &body,
+            )));
         }
 
         let mut pending = String::new();
@@ -1228,6 +2494,9 @@ impl RunAgent {
                 };
 
                 match event.get("type").and_then(Value::as_str) {
+                    // Text tool calls arrive as text; the final reply is
+                    // shown once they have been parsed out.
+                    Some("response.output_text.delta") if self.tool_profile.text_tools => {}
                     Some("response.output_text.delta") => {
                         if let Some(delta) = event.get("delta").and_then(Value::as_str) {
                             if !delta.is_empty() {
@@ -1247,7 +2516,7 @@ impl RunAgent {
                             .and_then(|e| e.get("message"))
                             .and_then(Value::as_str)
                         {
-                            return Err(msg.to_string());
+                            return Err(msg.to_string().into());
                         }
                     }
                     _ => {}
@@ -1265,6 +2534,35 @@ impl RunAgent {
     }
 }
 
+fn turn_usage(model: &ModelConfig, input: &Value, resp: &Value) -> AgentEvent {
+    let output = resp.get("output").cloned().unwrap_or(Value::Null);
+    let tools = output
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|item| item.get("type").and_then(Value::as_str) == Some("function_call"))
+        .filter_map(|item| item.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect();
+    let (input_tokens, output_tokens, estimated) = match reported_tokens(resp.get("usage")) {
+        Some((input, output)) => (input as usize, output as usize, false),
+        // Four characters per token, like the context estimate.
+        None => (
+            input.to_string().len() / 4,
+            output.to_string().len() / 4,
+            true,
+        ),
+    };
+    AgentEvent::Usage {
+        provider: model.provider.clone(),
+        model: model.model.clone(),
+        input_tokens,
+        output_tokens,
+        estimated,
+        tools,
+    }
+}
+
 fn send_final_output_if_unstreamed(tx: &Sender<AgentEvent>, text: &str, output_streamed: bool) {
     if !output_streamed {
         let _ = tx.send(AgentEvent::OutputText(text.to_string()));
@@ -1272,6 +2570,68 @@ fn send_final_output_if_unstreamed(tx: &Sender<AgentEvent>, text: &str, output_s
 }
 
 impl RunAgent {
+    /// Runs the lint hooks over the files this run changed before it
+    /// answers; the follow-up for the model when one fails.
+    fn lint_follow_up(
+        &self,
+        repo_root: &Path,
+        repair: &mut RepairLoop,
+        changes: &RunChanges,
+        iteration: usize,
+        ledger: &mut RunLedger,
+        tx: &Sender<AgentEvent>,
+    ) -> Option<String> {
+        let changed = changes.paths();
+        let hooks = repair.hooks(repo_root, &changed);
+        if hooks.is_empty() {
+            return None;
+        }
+        let sandbox = PermissionPolicy::load(repo_root).ok().map(|p| p.sandbox);
+        let timeout = Duration::from_secs(repair.config.timeout_secs);
+        let mut runs = Vec::new();
+        for hook in hooks {
+            let _ = tx.send(AgentEvent::ValidationStart {
+                command: hook.command.clone(),
+                scope: changed.clone(),
+            });
+            let run =
+                match lint_hooks::run_hook(repo_root, &hook, timeout, sandbox.as_ref(), || {
+                    self.cancel.is_cancelled()
+                }) {
+                    Ok(run) => run,
+                    Err(e) => {
+                        ledger.status(
+                            "lint_hooks",
+                            format!("{} not run: {e}", hook.name),
+                            iteration,
+                        );
+                        continue;
+                    }
+                };
+            let verdict = if run.passed { "passed" } else { "failed" };
+            ledger.status("lint_hooks", format!("{} {verdict}", hook.name), iteration);
+            let _ = tx.send(AgentEvent::ValidationComplete {
+                command: hook.command.clone(),
+                exit_code: Some(run.exit_code.into()),
+                passed: run.passed,
+                summary: format!("lint hook {} {verdict}", hook.name),
+            });
+            runs.push(run);
+        }
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+        let follow_up = repair.follow_up(&runs);
+        if repair.exhausted {
+            ledger.status(
+                "lint_hooks",
+                format!("still failing after {} follow-ups", repair.attempts),
+                iteration,
+            );
+        }
+        follow_up
+    }
+
     fn responses_endpoint(&self) -> String {
         let base = self
             .model_cfg
@@ -1282,6 +2642,32 @@ impl RunAgent {
     }
 }
 
+/// Runs `f` over `items` on at most `workers` threads and returns the
+/// results in input order; `None` where `f` panicked.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    workers: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<Option<R>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(item)));
+                if let (Ok(result), Ok(mut slots)) = (result, results.lock()) {
+                    slots[i] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap_or_default()
+}
+
 fn collect_parallel_safe_batch(
     output: &[Value],
     start: usize,
@@ -1360,6 +2746,7 @@ fn summarize_args(tool: &str, args: &Value) -> String {
             .and_then(Value::as_str)
             .unwrap_or("")
             .to_string(),
+        "multi_edit" => crate::tools::multi_edit_targets(args).join(", "),
         _ => serde_json::to_string(args).unwrap_or_else(|_| "{}".to_string()),
     }
 }
@@ -1451,6 +2838,23 @@ fn pre_tool_events(name: &str, args: &Value, repo_root: &Path) -> Vec<AgentEvent
     events
 }
 
+fn record_rejected_test_edit(repo_root: &Path, name: &str, args: &Value, model: &str) {
+    if !matches!(name, "edit_file" | "write_file" | "notebook_edit") {
+        return;
+    }
+    let Some(path) = args.get("path").and_then(Value::as_str) else {
+        return;
+    };
+    let path = repo_relative_path(repo_root, path);
+    if testgen::is_test_path(&path) {
+        let edit = ["new", "content"]
+            .iter()
+            .find_map(|key| args.get(*key).and_then(Value::as_str))
+            .unwrap_or_default();
+        let _ = testgen::record_rejection(repo_root, &path, edit, model, PROMPT_VERSION);
+    }
+}
+
 fn post_tool_events(
     name: &str,
     args: &Value,
@@ -1828,16 +3232,47 @@ fn resolve_repo_path(repo_root: &Path, raw: &str) -> PathBuf {
     }
 }
 
+/// `path` with `.` and `..` folded away, without touching the filesystem.
+fn lexical_path(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
 fn repo_relative_path(repo_root: &Path, raw: &str) -> String {
-    let resolved = resolve_repo_path(repo_root, raw);
+    let resolved = lexical_path(&resolve_repo_path(repo_root, raw));
     resolved
-        .strip_prefix(repo_root)
+        .strip_prefix(lexical_path(repo_root))
         .ok()
         .unwrap_or(resolved.as_path())
         .display()
         .to_string()
 }
 
+/// `raw` as a normalized repo-relative path; Err when `..` or an absolute
+/// path leads out of `repo_root`.
+fn contained_repo_path(repo_root: &Path, raw: &str) -> Result<String, String> {
+    let resolved = lexical_path(&resolve_repo_path(repo_root, raw));
+    match resolved.strip_prefix(lexical_path(repo_root)) {
+        Ok(relative) if relative.as_os_str().is_empty() => {
+            Err(format!("{raw} is the repository root, not a file"))
+        }
+        Ok(relative) => Ok(relative.display().to_string()),
+        Err(_) => Err(format!(
+            "{raw} is outside the repository; writes must stay under {}",
+            repo_root.display()
+        )),
+    }
+}
+
 fn extract_patch_target(patch: &str) -> Option<String> {
     patch.lines().find_map(|line| {
         let path = if let Some(raw) = line.strip_prefix("+++ ") {
@@ -1851,6 +3286,124 @@ fn extract_patch_target(patch: &str) -> Option<String> {
     })
 }
 
+fn ask_change_budget(
+    tx: &Sender<AgentEvent>,
+    over: &str,
+    changes: &RunChanges,
+) -> Result<BudgetDecision, String> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let _ = tx.send(AgentEvent::ChangeBudgetExceeded {
+        summary: over.to_string(),
+        changes: changes.aggregate_diff(),
+        reply_tx,
+    });
+    reply_rx
+        .recv()
+        .map_err(|_| "change budget channel closed".to_string())
+}
+
+/// Ends a run stopped at the change budget, rolling back if asked, and
+/// returns the message shown as its final output.
+fn stop_at_change_budget(
+    decision: BudgetDecision,
+    over: &str,
+    changes: &RunChanges,
+    repo_root: &Path,
+    tx: &Sender<AgentEvent>,
+) -> String {
+    if decision != BudgetDecision::Rollback {
+        return format!(
+            "Stopped at the change budget ({over}). The edits so far are kept; \
+             continue the remaining work in a follow-up run."
+        );
+    }
+    match changes.rollback(repo_root) {
+        Ok(restored) => {
+            let files = restored.len();
+            for snap in restored {
+                let _ = tx.send(tool_diff_event(snap));
+            }
+            format!(
+                "Stopped at the change budget ({over}) and rolled back {files} file{}.",
+                if files == 1 { "" } else { "s" }
+            )
+        }
+        Err(e) => format!("Stopped at the change budget ({over}); rollback failed: {e}"),
+    }
+}
+
+/// Why an edit of `targets` leaves the approved plan, if it does.
+fn outside_plan(scope: &[String], targets: &[String]) -> Option<String> {
+    if scope.is_empty() {
+        return None;
+    }
+    let outside = targets.iter().find(|target| {
+        let target = target.trim_start_matches("./");
+        !scope
+            .iter()
+            .any(|pattern| pattern == target || protected_paths::matches(pattern, target))
+    })?;
+    Some(format!(
+        "{outside} is not in the approved plan (it covers {}); stay within the plan or ask the user to extend it with /plan files",
+        scope.join(", ")
+    ))
+}
+
+/// Repo-relative paths a write, edit or patch call would change, normalized
+/// so every policy check sees where the write really lands. Err when one
+/// leaves the repo.
+fn mutation_targets(name: &str, args: &Value, repo_root: &Path) -> Result<Vec<String>, String> {
+    let raw: Vec<String> = match name {
+        "edit_file" | "write_file" | "notebook_edit" => args
+            .get("path")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        "patch" => args
+            .get("patch")
+            .and_then(Value::as_str)
+            .map(patch_targets)
+            .unwrap_or_default(),
+        "rename_symbol" => crate::tools::rename_targets(repo_root, args),
+        "multi_edit" => crate::tools::multi_edit_targets(args),
+        _ => Vec::new(),
+    };
+    raw.iter()
+        .map(|path| contained_repo_path(repo_root, path))
+        .collect()
+}
+
+/// Every file a unified diff or `*** Begin Patch` block touches, including
+/// deletions and renames.
+fn patch_targets(patch: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for line in patch.lines() {
+        let path = if let Some(raw) = line.strip_prefix("+++ ") {
+            raw.strip_prefix("b/").unwrap_or(raw)
+        } else if let Some(raw) = line.strip_prefix("--- ") {
+            raw.strip_prefix("a/").unwrap_or(raw)
+        } else if let Some(raw) = [
+            "*** Update File: ",
+            "*** Add File: ",
+            "*** Delete File: ",
+            "*** Move to: ",
+        ]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        {
+            raw
+        } else {
+            continue;
+        };
+        let path = path.trim();
+        if !path.is_empty() && path != "/dev/null" && !paths.iter().any(|p| p == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
 fn extract_patch_start_line(patch: &str) -> Option<u64> {
     patch.lines().find_map(|line| {
         let rest = line.strip_prefix("@@ ")?;
@@ -1879,6 +3432,78 @@ fn patch_added_text(patch: &str) -> String {
     }
 }
 
+/// Whether each of `targets` exists under `repo_root`, taken before the
+/// tool call so its diffs can tell a created file from an edited empty one.
+fn target_existence(targets: &[String], repo_root: &Path) -> Vec<(String, bool)> {
+    targets
+        .iter()
+        .map(|target| (target.clone(), repo_root.join(target).exists()))
+        .collect()
+}
+
+/// The change a tool call made to `target`: created when the file was
+/// missing before the call, deleted when it is missing now. Targets the
+/// call did not announce count as created when they started empty.
+fn tool_change(
+    tool: &str,
+    target: String,
+    before: &str,
+    after: &str,
+    existed: &[(String, bool)],
+    repo_root: &Path,
+) -> DiffSnapshot {
+    let created = existed
+        .iter()
+        .find(|(t, _)| *t == target)
+        .map_or(before.is_empty(), |(_, exists)| !exists);
+    let deleted = !repo_root.join(&target).exists();
+    DiffSnapshot {
+        tool: tool.to_string(),
+        before: before.to_string(),
+        after: after.to_string(),
+        created,
+        deleted,
+        target,
+    }
+}
+
+fn tool_diff_event(change: DiffSnapshot) -> AgentEvent {
+    AgentEvent::ToolDiff {
+        tool: change.tool,
+        target: change.target,
+        before: change.before,
+        after: change.after,
+        created: change.created,
+        deleted: change.deleted,
+    }
+}
+
+/// `(path, before, after)` for each file a tool result changed: the
+/// top-level fields of single-file edits, or each entry of `changes`.
+fn result_diffs(result: &Value) -> Vec<(&str, &str, &str)> {
+    fn diff(value: &Value) -> Option<(&str, &str, &str)> {
+        Some((
+            value.get("path")?.as_str()?,
+            value.get("before")?.as_str()?,
+            value.get("after")?.as_str()?,
+        ))
+    }
+    match result.get("changes").and_then(Value::as_array) {
+        Some(changes) => changes.iter().filter_map(diff).collect(),
+        None => diff(result).into_iter().collect(),
+    }
+}
+
+/// The user message a `spawn_subagent` child starts from.
+fn subagent_prompt(task: &str) -> String {
+    format!(
+        "You are a sub-agent working for another agent. Do this task and nothing else:\n\n\
+         {task}\n\n\
+         Your final message is all the other agent sees: report what you found or changed, \
+         with file paths, concisely."
+    )
+}
+
 fn clip_event_text(text: &str) -> String {
     const MAX_EVENT_TEXT_CHARS: usize = 2_000;
     if text.chars().count() <= MAX_EVENT_TEXT_CHARS {
@@ -1917,13 +3542,18 @@ fn tool_result_summary(tool: &str, result: &Value) -> String {
                 .unwrap_or("")
                 .trim();
             let detail = if !stderr.is_empty() { stderr } else { stdout };
-            if detail.is_empty() {
+            let status = if result.get("running").and_then(Value::as_bool) == Some(true) {
+                "still running".to_string()
+            } else {
                 format!("exit={exit}")
+            };
+            if detail.is_empty() {
+                status
             } else {
-                format!("exit={exit}; {}", clip(detail))
+                format!("{status}; {}", clip(detail))
             }
         }
-        "read_file" => {
+        "read_file" | "read_tool_output" => {
             let lines = result
                 .get("lines")
                 .and_then(Value::as_u64)
@@ -1937,6 +3567,32 @@ fn tool_result_summary(tool: &str, result: &Value) -> String {
                 .unwrap_or_default();
             format!("wrote {bytes} bytes")
         }
+        "rename_symbol" => {
+            let count = |key: &str| result.get(key).and_then(Value::as_u64).unwrap_or_default();
+            format!(
+                "{} {} -> {}: {} occurrences in {} files",
+                if result.get("changes").is_some() {
+                    "renamed"
+                } else {
+                    "would rename"
+                },
+                result.get("symbol").and_then(Value::as_str).unwrap_or("?"),
+                result
+                    .get("new_name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("?"),
+                count("occurrences"),
+                count("files_changed")
+            )
+        }
+        "multi_edit" => {
+            let count = |key: &str| result.get(key).and_then(Value::as_u64).unwrap_or_default();
+            format!(
+                "{} edits in {} files",
+                count("edits"),
+                count("files_changed")
+            )
+        }
         "edit_file" => result
             .get("mode")
             .and_then(Value::as_str)
@@ -1970,6 +3626,19 @@ fn tool_result_summary(tool: &str, result: &Value) -> String {
                 }
             )
         }
+        "spawn_subagent" => {
+            let count = |key: &str| result.get(key).and_then(Value::as_u64).unwrap_or_default();
+            format!(
+                "sub-agent #{} {}: {} tool calls, {} tokens",
+                count("subagent"),
+                result
+                    .get("status")
+                    .and_then(Value::as_str)
+                    .unwrap_or("done"),
+                count("tool_calls"),
+                count("tokens")
+            )
+        }
         "dynamic_workflow" => {
             let kind = result
                 .pointer("/workflow/kind")
@@ -2027,10 +3696,22 @@ fn plan_items_from_result(tool: &str, result: &Value) -> Option<Vec<PlanItem>> {
                     .get("status")
                     .and_then(Value::as_str)
                     .unwrap_or("pending");
+                let files = item
+                    .get("files")
+                    .and_then(Value::as_array)
+                    .map(|files| {
+                        files
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
                 Some(PlanItem {
                     text: text.to_string(),
                     done: status == "completed",
                     active: status == "in_progress",
+                    files,
                 })
             })
             .collect(),
@@ -2104,6 +3785,12 @@ fn queue_verify_on_stop(
         "- verify-on-stop queued attempt {} before final response",
         *attempts
     ));
+    queue_follow_up(next_messages, final_text, nudge);
+    true
+}
+
+/// Sends the model back to work: its would-be final answer, then `nudge`.
+fn queue_follow_up(next_messages: &mut Vec<Value>, final_text: &str, nudge: String) {
     next_messages.push(json!({
         "role": "assistant",
         "content": final_text
@@ -2112,7 +3799,6 @@ fn queue_verify_on_stop(
         "role": "user",
         "content": nudge
     }));
-    true
 }
 
 fn assistant_memory_text(text: &str, run_notes: &[String], ledger: &RunLedger) -> String {
@@ -2148,6 +3834,15 @@ fn max_iterations() -> usize {
         .unwrap_or(90)
 }
 
+/// Upper bound on read-only tools run at once from one model response.
+fn max_parallel_tools() -> usize {
+    env::var("OSMOGREP_MAX_PARALLEL_TOOLS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(8)
+}
+
 fn send_run_status(
     tx: &Sender<AgentEvent>,
     phase: impl Into<String>,
@@ -2192,15 +3887,62 @@ fn reasoning_effort_for(model: &str) -> Option<String> {
         return None;
     }
 
-    if model.starts_with("gpt-5")
-        || model.starts_with("o1")
-        || model.starts_with("o3")
-        || model.starts_with("o4")
-    {
-        return Some("medium".to_string());
+    if model.starts_with("gpt-5")
+        || model.starts_with("o1")
+        || model.starts_with("o3")
+        || model.starts_with("o4")
+    {
+        return Some("medium".to_string());
+    }
+
+    None
+}
+
+/// A non-streamed response, read off the connection.
+struct ApiReply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl ApiReply {
+    fn read(resp: reqwest::blocking::Response) -> reqwest::Result<Self> {
+        let status = resp.status().as_u16();
+        let headers = resp
+            .headers()
+            .iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        Ok(Self {
+            status,
+            headers,
+            body: resp.text()?,
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
     }
 
-    None
+    /// Retryable statuses come back `Transient` with the server's wait;
+    /// any other failure status is `Final`.
+    fn into_result(self) -> Result<Value, ModelCallError> {
+        if self.status == 200 {
+            return serde_json::from_str(&self.body).map_err(|e| e.to_string().into());
+        }
+        let message = format_api_error(&self.status.to_string(), &self.body);
+        if !http_policy::is_retryable_status(self.status) {
+            return Err(ModelCallError::Final(message));
+        }
+        Err(ModelCallError::Transient {
+            message,
+            status: Some(self.status),
+            retry_after: http_policy::server_wait(self.status, |name| self.header(name)),
+        })
+    }
 }
 
 fn format_api_error(status: &str, body: &str) -> String {
@@ -2428,18 +4170,18 @@ fn one_shot_scoped_call(
         .clone()
         .unwrap_or_else(|| default_base_url_for(&model_cfg.provider));
     let endpoint = format!("{}/responses", base.trim_end_matches('/'));
-    let client = reqwest::blocking::Client::builder()
+    let client = net::client_builder()?
         .timeout(Duration::from_secs(120))
         .build()
         .map_err(|e| e.to_string())?;
 
-    let resp = client
-        .post(endpoint)
-        .bearer_auth(api_key)
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .map_err(|e| e.to_string())?;
+    let resp = http_policy::send(
+        client
+            .post(endpoint)
+            .bearer_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(&payload),
+    )?;
 
     let status = resp.status();
     if !status.is_success() {
@@ -2609,6 +4351,35 @@ mod tests {
     use super::*;
     use uuid::Uuid;
 
+    #[test]
+    fn api_reply_keeps_the_server_wait_for_retryable_statuses() {
+        let reply = |status: u16, body: &str| ApiReply {
+            status,
+            headers: vec![("Retry-After".to_string(), "7".to_string())],
+            body: body.to_string(),
+        };
+        match reply(429, "{\"error\":{\"message\":\"slow down\"}}").into_result() {
+            Err(ModelCallError::Transient {
+                message,
+                status,
+                retry_after,
+            }) => {
+                assert_eq!(message, "API error 429: slow down");
+                assert_eq!(status, Some(429));
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+            }
+            _ => panic!("429 should be retried"),
+        }
+        assert!(matches!(
+            reply(401, "bad key").into_result(),
+            Err(ModelCallError::Final(message)) if message == "API error 401: bad key"
+        ));
+        assert!(matches!(
+            reply(200, "{\"id\":\"r1\"}").into_result(),
+            Ok(value) if value["id"] == "r1"
+        ));
+    }
+
     #[test]
     fn final_output_event_is_sent_for_unstreamed_text() {
         let (tx, rx) = std::sync::mpsc::channel();
@@ -2863,7 +4634,7 @@ mod tests {
             &json!({
                 "items": [
                     { "id": 1, "text": "Inspect parser", "status": "completed" },
-                    { "id": 2, "text": "Patch parser", "status": "in_progress" },
+                    { "id": 2, "text": "Patch parser", "status": "in_progress", "files": ["src/parser.rs"] },
                     { "id": 3, "text": "Run tests", "status": "pending" }
                 ]
             }),
@@ -2875,6 +4646,7 @@ mod tests {
         assert!(!items[0].active);
         assert!(!items[1].done);
         assert!(items[1].active);
+        assert_eq!(items[1].files, ["src/parser.rs"]);
         assert!(!items[2].done);
         assert!(!items[2].active);
     }
@@ -2992,6 +4764,29 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn parallel_map_keeps_order_and_bounds_workers() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items: Vec<u64> = (0..12).collect();
+        let results = parallel_map(&items, 3, |n| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5 * (12 - n)));
+            running.fetch_sub(1, Ordering::SeqCst);
+            if *n == 7 {
+                panic!("tool failed");
+            }
+            n * 2
+        });
+
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(results.len(), 12);
+        assert_eq!(results[0], Some(0));
+        assert_eq!(results[7], None);
+        assert_eq!(results[11], Some(22));
+    }
+
     #[test]
     fn review_prompt_includes_findings_criteria_and_changes() {
         let changes = vec![DiffSnapshot {
@@ -2999,6 +4794,7 @@ mod tests {
             target: "src/lib.rs".to_string(),
             before: "fn value() -> i32 { 1 }".to_string(),
             after: "fn value() -> i32 { 2 }".to_string(),
+            ..DiffSnapshot::default()
         }];
 
         let prompt = build_review_prompt(&changes);
@@ -3017,6 +4813,7 @@ mod tests {
             target: "src/large.rs".to_string(),
             before: "a".repeat(REVIEW_FILE_BUDGET * 3),
             after: "b".repeat(REVIEW_FILE_BUDGET * 3),
+            ..DiffSnapshot::default()
         }];
 
         let prompt = build_review_prompt(&changes);
@@ -3026,6 +4823,798 @@ mod tests {
         assert!(prompt.contains("File: src/large.rs"));
     }
 
+    fn scripted_runner(
+        root: &Path,
+        turns: Value,
+        permission_profile: PermissionProfile,
+    ) -> (RunAgent, Arc<TestProvider>) {
+        let turns = serde_json::from_value(turns).unwrap();
+        let provider = Arc::new(TestProvider::new(turns));
+        let mut tool_scope = ToolScope::for_prompt("scripted");
+        if permission_profile == PermissionProfile::ReadOnly {
+            tool_scope = tool_scope.read_only();
+        }
+        let runner = RunAgent {
+            tools: Arc::new(ToolRegistry::with_root(root.to_path_buf())),
+            tool_scope,
+            tool_profile: ToolProfile::default(),
+            tool_profile_source: "test (built-in)".to_string(),
+            protected_paths: ProtectedPaths::default(),
+            change_budget: ChangeBudget::default(),
+            run_budget: RunBudget::default(),
+            lint_hooks: LintHooksConfig::default(),
+            hooks: LifecycleHooks::default(),
+            model_cfg: ModelConfig {
+                provider: "test".to_string(),
+                model: "scripted".to_string(),
+                api_key_env: None,
+                base_url: None,
+            },
+            api_key: None,
+            auto_approve: false,
+            permission_profile,
+            cancel: CancelToken::new(),
+            test_provider: Some(provider.clone()),
+            responses: ResponseStore::default(),
+            images: Vec::new(),
+            plan_scope: Vec::new(),
+            subagent: None,
+            depth: 0,
+        };
+        (runner, provider)
+    }
+
+    fn write_call(path: &str) -> Value {
+        json!({
+            "type": "function_call",
+            "name": "write_file",
+            "call_id": "call-write",
+            "arguments": json!({ "path": path, "content": "scripted\n" }).to_string(),
+        })
+    }
+
+    fn final_text(text: &str) -> Value {
+        json!({ "output": [{ "type": "output_text", "text": text }] })
+    }
+
+    fn call_output(request: &Value, call_id: &str) -> Option<String> {
+        request.as_array()?.iter().find_map(|item| {
+            (item.get("type").and_then(Value::as_str) == Some("function_call_output")
+                && item.get("call_id").and_then(Value::as_str) == Some(call_id))
+            .then(|| {
+                item.get("output")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .flatten()
+        })
+    }
+
+    #[test]
+    fn scripted_run_reports_denied_permission_to_model() {
+        let root = temp_root();
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("denied.txt")] }, final_text("gave up")]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        let result = thread::scope(|scope| {
+            let run =
+                scope.spawn(|| runner.run(root.clone(), "write", Vec::new(), None, steer_rx, &tx));
+            for event in rx.iter() {
+                if let AgentEvent::PermissionRequest { reply_tx, .. } = event {
+                    reply_tx.send(false).unwrap();
+                    break;
+                }
+            }
+            run.join().unwrap()
+        });
+
+        assert!(result.is_ok());
+        assert!(!root.join("denied.txt").exists());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(call_output(&requests[1], "call-write")
+            .unwrap()
+            .contains("user denied permission"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_refuses_edits_outside_approved_plan() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("notes.txt")] }, final_text("stopped")]),
+            PermissionProfile::FullAccess,
+        );
+        runner.plan_scope = vec!["src/**".to_string()];
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join("notes.txt").exists());
+        assert!(call_output(&provider.requests()[1], "call-write")
+            .unwrap()
+            .contains("notes.txt is not in the approved plan (it covers src/**)"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_delegates_to_a_scoped_subagent() {
+        let root = temp_root();
+        let spawn = json!({
+            "type": "function_call",
+            "name": "spawn_subagent",
+            "call_id": "call-spawn",
+            "arguments": json!({
+                "task": "Write child.txt",
+                "tools": ["write_file"],
+            })
+            .to_string(),
+        });
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [spawn] },
+                { "output": [write_call("child.txt")] },
+                final_text("wrote child.txt"),
+                final_text("delegated"),
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "delegate it", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(root.join("child.txt").exists());
+        let requests = provider.requests();
+        let child_input = requests[1].to_string();
+        assert!(child_input.contains("Write child.txt"));
+        assert!(!child_input.contains("delegate it"));
+        let output = call_output(&requests[3], "call-spawn").unwrap();
+        assert!(output.contains("wrote child.txt"));
+        assert!(output.contains("\"status\":\"done\""));
+
+        let events: Vec<AgentEvent> = rx.try_iter().collect();
+        let finished = events.iter().rev().find_map(|event| match event {
+            AgentEvent::Subagent(status) => Some(status),
+            _ => None,
+        });
+        assert!(matches!(
+            finished,
+            Some(SubagentStatus {
+                parent: None,
+                state: SubagentState::Done,
+                tool_calls: 1,
+                ..
+            })
+        ));
+        let outputs: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                AgentEvent::OutputText(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(outputs, ["delegated"]);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_stops_at_its_tool_call_budget() {
+        let root = temp_root();
+        let mut second = write_call("second.txt");
+        second["call_id"] = json!("call-second");
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("first.txt"), second] },
+                final_text("never reached"),
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        runner.run_budget = RunBudget::default().with_overrides("tools=1").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write both", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(root.join("first.txt").exists());
+        assert!(!root.join("second.txt").exists());
+        assert_eq!(provider.requests().len(), 1);
+        let output = rx
+            .try_iter()
+            .find_map(|event| match event {
+                AgentEvent::OutputText(text) => Some(text),
+                _ => None,
+            })
+            .unwrap();
+        assert!(output.starts_with("Stopped at the run budget: 1 tool call (limit 1)."));
+        assert!(output.contains("Changed first.txt."));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_checkpoints_its_messages_when_the_model_call_fails() {
+        let root = temp_root();
+        let (runner, _provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("kept.txt")] }]),
+            PermissionProfile::FullAccess,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        let err = runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap_err();
+
+        assert!(err.contains("test script exhausted"));
+        let (messages, transient) = rx
+            .try_iter()
+            .find_map(|event| match event {
+                AgentEvent::Checkpoint {
+                    messages,
+                    transient,
+                } => Some((messages, transient)),
+                _ => None,
+            })
+            .unwrap();
+        assert!(!transient);
+        let messages = Value::Array(messages);
+        assert!(call_output(&messages, "call-write").is_some());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_skips_prompt_for_remembered_approval() {
+        let root = temp_root();
+        approvals::remember(&root, "write_file", "**").unwrap();
+        let (runner, _provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("remembered.txt")] }, final_text("done")]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(root.join("remembered.txt").exists());
+        assert!(!rx
+            .try_iter()
+            .any(|event| matches!(event, AgentEvent::PermissionRequest { .. })));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_blocks_dangerous_tools_in_read_only_profile() {
+        let root = temp_root();
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("blocked.txt")] }, final_text("read only")]),
+            PermissionProfile::ReadOnly,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join("blocked.txt").exists());
+        assert!(!rx
+            .try_iter()
+            .any(|event| matches!(event, AgentEvent::PermissionRequest { .. })));
+        assert!(call_output(&provider.requests()[1], "call-write")
+            .unwrap()
+            .contains("permission profile is read-only"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_refuses_protected_paths_even_with_full_access() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("Cargo.lock")] },
+                { "output": [{
+                    "type": "function_call",
+                    "name": "write_file",
+                    "call_id": "call-climb",
+                    "arguments": json!({ "path": "./x/../.github/ci.yml", "content": "x" }).to_string(),
+                }] },
+                final_text("left it")
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        runner.protected_paths = ProtectedPaths {
+            patterns: vec!["*.lock".to_string(), ".github/**".to_string()],
+            mode: ProtectMode::Refuse,
+        };
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join("Cargo.lock").exists());
+        let output = call_output(&provider.requests()[1], "call-write").unwrap();
+        assert!(output.contains("protected path: Cargo.lock (matches *.lock)"));
+        assert!(output.contains("choose another approach"));
+        // Protected globs see the path the write lands on, not the spelling.
+        let climbed = call_output(&provider.requests()[2], "call-climb").unwrap();
+        assert!(climbed.contains("protected path: .github/ci.yml (matches .github/**)"));
+        assert!(!root.join(".github").exists());
+        assert_eq!(
+            patch_targets("--- a/old.rs\n+++ /dev/null\n*** Add File: new.rs\n"),
+            vec!["old.rs", "new.rs"]
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_follows_repo_permission_policy() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("README.md")] },
+                { "output": [{
+                    "type": "function_call",
+                    "name": "write_file",
+                    "call_id": "call-climb",
+                    "arguments": json!({ "path": "src/../README.md", "content": "x" }).to_string(),
+                }] },
+                { "output": [write_call("src/ok.txt")] },
+                final_text("done")
+            ]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let policy = PermissionPolicy::parse(
+            "[tools]\nallow = [\"write_file\"]\n[paths]\nwrite_allow = [\"src/**\"]\n",
+        )
+        .unwrap();
+        runner.tools = Arc::new(
+            ToolRegistry::builder(root.clone())
+                .permissions(policy)
+                .build(),
+        );
+        fs::create_dir_all(root.join("src")).unwrap();
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        // Neither call prompts: one is refused by the policy, the other
+        // pre-approved by it.
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join("README.md").exists());
+        let refused = call_output(&provider.requests()[1], "call-write").unwrap();
+        assert!(refused.contains("only src/** may be written"));
+        // `..` does not get a write past write_allow.
+        let climbed = call_output(&provider.requests()[2], "call-climb").unwrap();
+        assert!(climbed.contains("writing README.md is denied"));
+        assert_eq!(
+            fs::read_to_string(root.join("src/ok.txt")).unwrap(),
+            "scripted\n"
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_refuses_writes_that_climb_into_internal_dirs_or_out_of_the_repo() {
+        let root = temp_root();
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("src/../.osmogrep/permissions.toml")] },
+                { "output": [{
+                    "type": "function_call",
+                    "name": "write_file",
+                    "call_id": "call-outside",
+                    "arguments": json!({ "path": "src/../../outside.txt", "content": "x" }).to_string(),
+                }] },
+                final_text("done")
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        fs::create_dir_all(root.join("src")).unwrap();
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join(".osmogrep").exists());
+        let internal = call_output(&provider.requests()[1], "call-write").unwrap();
+        assert!(internal.contains("writing .osmogrep/permissions.toml is refused"));
+        let outside = call_output(&provider.requests()[2], "call-outside").unwrap();
+        assert!(outside.contains("is outside the repository"));
+        assert!(!root.parent().unwrap().join("outside.txt").exists());
+
+        assert_eq!(
+            mutation_targets(
+                "write_file",
+                &json!({ "path": "./src/./a/../lib.rs" }),
+                &root
+            ),
+            Ok(vec!["src/lib.rs".to_string()])
+        );
+        let absolute = root.join("src/x/../../Cargo.lock").display().to_string();
+        assert_eq!(
+            mutation_targets("write_file", &json!({ "path": absolute }), &root),
+            Ok(vec!["Cargo.lock".to_string()])
+        );
+        assert!(mutation_targets("write_file", &json!({ "path": "/etc/passwd" }), &root).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripted_run_runs_repo_lifecycle_hooks() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("secret.txt")] },
+                { "output": [write_call("ok.txt")] },
+                final_text("done")
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        runner.hooks = toml::from_str(
+            r#"
+[[pre_tool_call]]
+command = "grep -q secret && echo 'secrets stay put' >&2 && exit 1 || exit 0"
+
+[[post_edit]]
+command = "cat >> edits.jsonl"
+
+[[run_finished]]
+command = "cat > finished.json"
+"#,
+        )
+        .unwrap();
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert!(!root.join("secret.txt").exists());
+        let refused = call_output(&provider.requests()[1], "call-write").unwrap();
+        assert!(refused.contains("secrets stay put"));
+        let edit: Value =
+            serde_json::from_str(&fs::read_to_string(root.join("edits.jsonl")).unwrap()).unwrap();
+        assert_eq!(edit["event"], "post_edit");
+        assert_eq!(edit["files"], json!(["ok.txt"]));
+        let finished: Value =
+            serde_json::from_str(&fs::read_to_string(root.join("finished.json")).unwrap()).unwrap();
+        assert_eq!(finished["status"], "completed");
+        assert_eq!(finished["final_answer"], "done");
+        assert_eq!(finished["changed_files"], json!(["ok.txt"]));
+        assert_eq!(finished["run_id"], edit["run_id"]);
+        drop(tx);
+        let transcript = rx
+            .iter()
+            .find_map(|event| match event {
+                AgentEvent::Transcript(path) => Some(path),
+                _ => None,
+            })
+            .unwrap();
+        assert!(transcript.starts_with(root.join(crate::transcript::RUNS_DIR)));
+        assert!(transcript.with_extension("md").exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_calls_tools_through_text_when_the_profile_asks() {
+        let root = temp_root();
+        fs::write(root.join("notes.txt"), "from disk\n").unwrap();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                final_text(
+                    "<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"notes.txt\"}}\n</tool_call>"
+                ),
+                final_text("notes say: from disk")
+            ]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        runner.tool_profile.text_tools = true;
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "read notes", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        let requests = provider.requests();
+        assert!(requests[0][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("<tool_call>"));
+        let replayed = requests[1].as_array().unwrap();
+        assert!(replayed
+            .iter()
+            .all(|item| item.get("type").and_then(Value::as_str).is_none()));
+        let result = replayed.last().unwrap()["content"].as_str().unwrap();
+        assert!(result.starts_with("<tool_result name=\"read_file\">"));
+        assert!(result.contains("from disk"));
+        drop(tx);
+        assert!(rx.iter().any(
+            |event| matches!(event, AgentEvent::OutputText(text) if text == "notes say: from disk")
+        ));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_sends_lint_failures_back_until_attempts_run_out() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([
+                { "output": [write_call("notes.txt")] },
+                final_text("done"),
+                final_text("done again")
+            ]),
+            PermissionProfile::FullAccess,
+        );
+        runner.lint_hooks = LintHooksConfig {
+            max_attempts: 1,
+            commands: vec!["! grep -q scripted {files}".into()],
+            ..LintHooksConfig::default()
+        };
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "write notes", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        let follow_up = requests[2].as_array().unwrap().last().unwrap()["content"]
+            .as_str()
+            .unwrap();
+        assert!(follow_up.contains("lint hooks failed on your edits (attempt 1 of 1)"));
+        assert!(follow_up.contains("`! grep -q scripted notes.txt` exited with 1"));
+        drop(tx);
+        let failures = rx
+            .iter()
+            .filter(|event| matches!(event, AgentEvent::ValidationComplete { passed: false, .. }))
+            .count();
+        assert_eq!(failures, 2);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_rolls_back_when_change_budget_is_exceeded() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("big.txt")] }, final_text("unused")]),
+            PermissionProfile::FullAccess,
+        );
+        runner.change_budget = ChangeBudget {
+            max_lines: None,
+            max_files: Some(0),
+        };
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        let result = thread::scope(|scope| {
+            let run =
+                scope.spawn(|| runner.run(root.clone(), "write", Vec::new(), None, steer_rx, &tx));
+            for event in rx.iter() {
+                if let AgentEvent::ChangeBudgetExceeded {
+                    summary,
+                    changes,
+                    reply_tx,
+                } = event
+                {
+                    assert_eq!(summary, "1 line changed across 1 file (limit 0 files)");
+                    assert_eq!(changes[0].target, "big.txt");
+                    reply_tx.send(BudgetDecision::Rollback).unwrap();
+                    break;
+                }
+            }
+            run.join().unwrap()
+        });
+
+        assert!(result.is_ok());
+        assert!(!root.join("big.txt").exists());
+        assert_eq!(provider.requests().len(), 1);
+        assert!(rx.try_iter().any(|event| matches!(
+            event,
+            AgentEvent::OutputText(text) if text.contains("rolled back 1 file.")
+        )));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_injects_pending_steer_before_model_turn() {
+        let root = temp_root();
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([final_text("ok")]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let (tx, _rx) = mpsc::channel();
+        let (steer_tx, steer_rx) = mpsc::channel();
+        steer_tx.send("prefer small diffs".to_string()).unwrap();
+
+        runner
+            .run(root.clone(), "task", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        let first = provider.requests()[0].to_string();
+        assert!(first.contains("[steer-now]\\nprefer small diffs"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_sends_attached_images_with_the_prompt() {
+        let root = temp_root();
+        let (mut runner, provider) = scripted_runner(
+            &root,
+            json!([final_text("a red button")]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        runner.images = vec![ImageAttachment {
+            path: PathBuf::from("shot.png"),
+            mime: "image/png",
+            data: "iVBORw==".to_string(),
+        }];
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(
+                root.clone(),
+                "what is this?",
+                Vec::new(),
+                None,
+                steer_rx,
+                &tx,
+            )
+            .unwrap();
+
+        let input = provider.requests()[0].clone();
+        let user = input.as_array().unwrap().last().unwrap();
+        assert_eq!(user["content"][0]["text"], "what is this?");
+        assert_eq!(
+            user["content"][1]["image_url"],
+            "data:image/png;base64,iVBORw=="
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_stops_when_cancelled_mid_run() {
+        let root = temp_root();
+        let (runner, provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("cancelled.txt")] }, final_text("unreachable")]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+        let cancel = runner.cancel.clone();
+
+        let result = thread::scope(|scope| {
+            let run =
+                scope.spawn(|| runner.run(root.clone(), "write", Vec::new(), None, steer_rx, &tx));
+            for event in rx.iter() {
+                if let AgentEvent::PermissionRequest { reply_tx, .. } = event {
+                    cancel.cancel();
+                    reply_tx.send(false).unwrap();
+                    break;
+                }
+            }
+            run.join().unwrap()
+        });
+
+        assert_eq!(result, Err("cancelled".to_string()));
+        assert_eq!(provider.requests().len(), 1);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn scripted_run_assembles_streamed_output_without_replaying_final_text() {
+        let root = temp_root();
+        let (runner, _provider) = scripted_runner(
+            &root,
+            json!([{
+                "stream": ["Hel", "lo"],
+                "output": [{ "type": "output_text", "text": "Hello" }]
+            }]),
+            PermissionProfile::WorkspaceAuto,
+        );
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+
+        runner
+            .run(root.clone(), "greet", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        let events = rx.try_iter().collect::<Vec<_>>();
+        let streamed = events
+            .iter()
+            .filter_map(|event| match event {
+                AgentEvent::StreamDelta(delta) => Some(delta.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(streamed, "Hello");
+        assert!(events.iter().any(|e| matches!(e, AgentEvent::StreamDone)));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, AgentEvent::OutputText(_))));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn recorded_run_replays_without_the_model() {
+        let root = temp_root();
+        let recording = root.with_extension("json");
+        let (mut runner, _provider) = scripted_runner(
+            &root,
+            json!([{ "output": [write_call("replayed.txt")] }, final_text("wrote it")]),
+            PermissionProfile::FullAccess,
+        );
+        runner.responses = ResponseStore::default()
+            .with_recorder(Recorder::create(&recording, "scripted", "write").unwrap());
+        let (tx, _rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+        runner
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+        fs::remove_file(root.join("replayed.txt")).unwrap();
+
+        let (mut replayer, _provider) =
+            scripted_runner(&root, json!([]), PermissionProfile::FullAccess);
+        replayer.test_provider = None;
+        replayer.responses = ResponseStore::default().with_replay(Replay::new(
+            response_cache::Recording::load(&recording).unwrap(),
+        ));
+        let (tx, rx) = mpsc::channel();
+        let (_steer_tx, steer_rx) = mpsc::channel();
+        replayer
+            .run(root.clone(), "write", Vec::new(), None, steer_rx, &tx)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("replayed.txt")).unwrap(),
+            "scripted\n"
+        );
+        assert!(rx
+            .try_iter()
+            .any(|e| matches!(e, AgentEvent::OutputText(text) if text == "wrote it")));
+        let _ = fs::remove_file(recording);
+        let _ = fs::remove_dir_all(root);
+    }
+
     fn temp_root() -> PathBuf {
         let root = std::env::temp_dir().join(format!("osmogrep-agent-test-{}", Uuid::new_v4()));
         fs::create_dir_all(&root).unwrap();
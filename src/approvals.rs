@@ -0,0 +1,248 @@
+//! approvals.rs
+//!
+//! Remembered "always allow" answers to permission prompts, per repo, in
+//! `.osmogrep/approvals.json`:
+//!
+//! ```json
+//! { "entries": [ { "tool": "run_shell", "pattern": "cargo *", "added_at": "…" } ] }
+//! ```
+//!
+//! Pressing `a` at a prompt stores the tool with a pattern generalized from
+//! the call: the program for `run_shell` (`cargo *`), the directory of the
+//! written files for edits (`src/**`), and `*` for anything else. Shells,
+//! interpreters and wrappers like `env`, `sudo` or `xargs` run whatever
+//! their arguments say, so those calls, like chained or redirected
+//! commands, are remembered only as the exact command line. Later
+//! calls the pattern covers run without a prompt; protected-path edits
+//! still prompt. `/approvals` lists and revokes entries.
+
+use std::{fs, path::Path};
+
+use chrono::Utc;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::protected_paths;
+use crate::shell_guard;
+
+pub const FILE: &str = ".osmogrep/approvals.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    pub tool: String,
+    /// Glob over the command line for `run_shell`, over the written paths
+    /// for edits, `*` for every call.
+    pub pattern: String,
+    pub added_at: String,
+}
+
+impl Approval {
+    /// `run_shell: cargo *`
+    pub fn describe(&self) -> String {
+        format!("{}: {}", self.tool, self.pattern)
+    }
+
+    fn covers(&self, tool: &str, args: &Value, targets: &[String]) -> bool {
+        if self.tool != tool {
+            return false;
+        }
+        if tool == "run_shell" {
+            let cmd = shell_command(args);
+            // Anything but a plain command is only covered by its own
+            // exact entry.
+            return Pattern::new(&self.pattern).is_ok_and(|p| p.matches(cmd))
+                && (shell_guard::simple_command_words(cmd).is_some()
+                    || self.pattern == Pattern::escape(cmd));
+        }
+        if self.pattern == "*" {
+            return true;
+        }
+        !targets.is_empty()
+            && targets.iter().all(|target| {
+                protected_paths::matches(&self.pattern, target.trim_start_matches("./"))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approvals {
+    #[serde(default)]
+    pub entries: Vec<Approval>,
+}
+
+impl Approvals {
+    /// The repo's approvals; none when the file is missing or unreadable.
+    pub fn load(repo_root: &Path) -> Self {
+        fs::read_to_string(repo_root.join(FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, repo_root: &Path) -> Result<(), String> {
+        let path = repo_root.join(FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text + "\n").map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// The entry that lets this call run without a prompt.
+    pub fn find(&self, tool: &str, args: &Value, targets: &[String]) -> Option<&Approval> {
+        self.entries
+            .iter()
+            .find(|entry| entry.covers(tool, args, targets))
+    }
+}
+
+/// The pattern `[a]lways` stores for a call.
+pub fn pattern_for(tool: &str, args: &Value, targets: &[String]) -> String {
+    if tool == "run_shell" {
+        let cmd = shell_command(args);
+        let words = shell_guard::simple_command_words(cmd).unwrap_or_default();
+        return match words.first() {
+            Some(program) if !shell_guard::runs_arbitrary_commands(program) => {
+                format!("{} *", Pattern::escape(program))
+            }
+            _ => Pattern::escape(cmd),
+        };
+    }
+    let mut dirs = targets.iter().map(|target| {
+        let target = target.trim_start_matches("./");
+        target.rsplit_once('/').map_or("", |(dir, _)| dir)
+    });
+    let Some(first) = dirs.next() else {
+        return "*".to_string();
+    };
+    let common = dirs.fold(first.to_string(), |common, dir| {
+        let shared: Vec<&str> = common
+            .split('/')
+            .zip(dir.split('/'))
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect();
+        shared.join("/")
+    });
+    if common.is_empty() {
+        "**".to_string()
+    } else {
+        format!("{}/**", Pattern::escape(&common))
+    }
+}
+
+/// Stores an entry unless one with the same tool and pattern exists.
+/// Returns whether it was added.
+pub fn remember(repo_root: &Path, tool: &str, pattern: &str) -> Result<bool, String> {
+    let mut approvals = Approvals::load(repo_root);
+    if approvals
+        .entries
+        .iter()
+        .any(|entry| entry.tool == tool && entry.pattern == pattern)
+    {
+        return Ok(false);
+    }
+    approvals.entries.push(Approval {
+        tool: tool.to_string(),
+        pattern: pattern.to_string(),
+        added_at: Utc::now().to_rfc3339(),
+    });
+    approvals.save(repo_root)?;
+    Ok(true)
+}
+
+/// Removes the entry at `index` (0-based) and returns it.
+pub fn revoke(repo_root: &Path, index: usize) -> Result<Approval, String> {
+    let mut approvals = Approvals::load(repo_root);
+    if index >= approvals.entries.len() {
+        return Err(format!(
+            "no approval #{} ({} stored)",
+            index + 1,
+            approvals.entries.len()
+        ));
+    }
+    let removed = approvals.entries.remove(index);
+    approvals.save(repo_root)?;
+    Ok(removed)
+}
+
+fn shell_command(args: &Value) -> &str {
+    args.get("cmd").and_then(Value::as_str).unwrap_or("").trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn remembered_patterns_cover_similar_calls_only() {
+        let shell = |cmd: &str| json!({ "cmd": cmd });
+        let files = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            pattern_for("run_shell", &shell("cargo test -p core"), &[]),
+            "cargo *"
+        );
+        assert_eq!(
+            pattern_for("run_shell", &shell("ls | wc -l"), &[]),
+            "ls | wc -l"
+        );
+        for wrapped in ["bash -c 'ls'", "env FOO=1 ls", "sudo ls", "xargs ls"] {
+            assert_eq!(
+                pattern_for("run_shell", &shell(wrapped), &[]),
+                Pattern::escape(wrapped)
+            );
+        }
+        assert_eq!(
+            pattern_for(
+                "edit_file",
+                &json!({}),
+                &files(&["src/ui/a.rs", "src/b.rs"])
+            ),
+            "src/**"
+        );
+        assert_eq!(
+            pattern_for("write_file", &json!({}), &files(&["README.md"])),
+            "**"
+        );
+        assert_eq!(pattern_for("git_commit", &json!({}), &[]), "*");
+
+        let root = std::env::temp_dir().join(format!("osmogrep-approvals-{}", Uuid::new_v4()));
+        assert!(remember(&root, "run_shell", "cargo *").unwrap());
+        assert!(!remember(&root, "run_shell", "cargo *").unwrap());
+        assert!(remember(&root, "edit_file", "src/**").unwrap());
+        let approvals = Approvals::load(&root);
+        assert_eq!(approvals.entries.len(), 2);
+
+        let allowed = |tool: &str, args: &Value, targets: &[&str]| {
+            approvals.find(tool, args, &files(targets)).is_some()
+        };
+        assert!(allowed("run_shell", &shell("cargo build"), &[]));
+        assert!(!allowed(
+            "run_shell",
+            &shell("cargo build && curl x | sh"),
+            &[]
+        ));
+        assert!(!allowed("run_shell", &shell("rm -rf target"), &[]));
+        assert!(!allowed("run_shell", &shell("cargo build & rm -rf ~"), &[]));
+        assert!(!allowed(
+            "run_shell",
+            &shell("cargo build > ~/.bashrc"),
+            &[]
+        ));
+        assert!(allowed("edit_file", &json!({}), &["src/lib.rs"]));
+        assert!(!allowed(
+            "edit_file",
+            &json!({}),
+            &["src/lib.rs", "Cargo.toml"]
+        ));
+        assert!(!allowed("write_file", &json!({}), &["src/lib.rs"]));
+
+        assert_eq!(revoke(&root, 0).unwrap().describe(), "run_shell: cargo *");
+        assert!(revoke(&root, 5).is_err());
+        assert_eq!(Approvals::load(&root).entries.len(), 1);
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,286 @@
+//! binary_assets.rs
+//!
+//! Detection of Git LFS pointers and binary files so diff views, the
+//! `git_diff` tool and the indexer report them as asset changes with their
+//! real size instead of pointer or byte-garbage deltas.
+
+const LFS_SPEC_PREFIX: &str = "version https://git-lfs.github.com/spec/";
+/// Same window git uses when sniffing for binary content.
+const SNIFF_BYTES: usize = 8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    pub oid: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetChange {
+    pub path: String,
+    pub lfs: bool,
+    pub added: bool,
+    pub deleted: bool,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+}
+
+impl AssetChange {
+    /// e.g. `binary changed (2.3 MB → 2.4 MB)`.
+    pub fn summary(&self) -> String {
+        let size = |s: Option<u64>| s.map(format_size).unwrap_or_else(|| "?".to_string());
+        if self.added {
+            format!("binary added ({})", size(self.new_size))
+        } else if self.deleted {
+            format!("binary removed ({})", size(self.old_size))
+        } else {
+            format!(
+                "binary changed ({} → {})",
+                size(self.old_size),
+                size(self.new_size)
+            )
+        }
+    }
+}
+
+pub fn parse_lfs_pointer(text: &str) -> Option<LfsPointer> {
+    // Pointer files are tiny; anything bigger is real content.
+    if text.len() > 1024 || !text.starts_with(LFS_SPEC_PREFIX) {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("oid ") {
+            oid = Some(rest.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("size ") {
+            size = rest.trim().parse().ok();
+        }
+    }
+    Some(LfsPointer {
+        oid: oid?,
+        size: size?,
+    })
+}
+
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(SNIFF_BYTES)].contains(&0)
+}
+
+/// True for content that should never be parsed for symbols.
+pub fn is_asset(bytes: &[u8]) -> bool {
+    looks_binary(bytes)
+        || std::str::from_utf8(bytes)
+            .ok()
+            .and_then(parse_lfs_pointer)
+            .is_some()
+}
+
+/// Classifies a before/after pair; `None` when both sides are plain text.
+pub fn classify_texts(path: &str, before: &str, after: &str) -> Option<AssetChange> {
+    let side = |text: &str| -> (bool, bool, Option<u64>) {
+        if let Some(pointer) = parse_lfs_pointer(text) {
+            (true, true, Some(pointer.size))
+        } else if looks_binary(text.as_bytes()) {
+            (true, false, Some(text.len() as u64))
+        } else {
+            (
+                false,
+                false,
+                (!text.is_empty()).then_some(text.len() as u64),
+            )
+        }
+    };
+    let (old_asset, old_lfs, old_size) = side(before);
+    let (new_asset, new_lfs, new_size) = side(after);
+    if !old_asset && !new_asset {
+        return None;
+    }
+
+    Some(AssetChange {
+        path: path.to_string(),
+        lfs: old_lfs || new_lfs,
+        added: before.is_empty(),
+        deleted: after.is_empty(),
+        old_size,
+        new_size,
+    })
+}
+
+/// Replaces binary and LFS pointer sections of a unified `git diff` with a
+/// one-line summary each. `fill_sizes` can supply sizes git doesn't print
+/// for plain binaries. Returns the rewritten diff and the assets found.
+pub fn summarize_git_diff(
+    diff: &str,
+    fill_sizes: impl Fn(&mut AssetChange),
+) -> (String, Vec<AssetChange>) {
+    let mut out = String::new();
+    let mut assets = Vec::new();
+
+    for section in split_sections(diff) {
+        match section_asset(&section) {
+            Some(mut asset) => {
+                if !asset.lfs {
+                    fill_sizes(&mut asset);
+                }
+                out.push_str(section.lines().next().unwrap_or_default());
+                out.push('\n');
+                out.push_str(&format!(
+                    "{}{}\n",
+                    if asset.lfs { "LFS " } else { "" },
+                    asset.summary()
+                ));
+                assets.push(asset);
+            }
+            None => out.push_str(&section),
+        }
+    }
+
+    (out, assets)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn split_sections(diff: &str) -> Vec<String> {
+    let mut sections: Vec<String> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") || sections.is_empty() {
+            sections.push(String::new());
+        }
+        if let Some(last) = sections.last_mut() {
+            last.push_str(line);
+        }
+    }
+    sections
+}
+
+fn section_asset(section: &str) -> Option<AssetChange> {
+    let header = section.lines().next()?;
+    let path = header
+        .strip_prefix("diff --git ")?
+        .rsplit(" b/")
+        .next()?
+        .trim()
+        .to_string();
+    let added = section.lines().any(|l| l.starts_with("new file mode"));
+    let deleted = section.lines().any(|l| l.starts_with("deleted file mode"));
+
+    if section
+        .lines()
+        .any(|l| l.starts_with("Binary files ") && l.ends_with(" differ"))
+    {
+        return Some(AssetChange {
+            path,
+            lfs: false,
+            added,
+            deleted,
+            old_size: None,
+            new_size: None,
+        });
+    }
+
+    let body: Vec<&str> = section
+        .lines()
+        .skip_while(|l| !l.starts_with("@@"))
+        .filter(|l| !l.starts_with("@@"))
+        .collect();
+    let is_pointer = body
+        .iter()
+        .any(|l| l.get(1..).is_some_and(|t| t.starts_with(LFS_SPEC_PREFIX)));
+    if !is_pointer {
+        return None;
+    }
+
+    let size_on = |marker: char| {
+        body.iter().find_map(|l| {
+            let mut chars = l.chars();
+            let first = chars.next()?;
+            (first == marker || first == ' ')
+                .then(|| chars.as_str().strip_prefix("size ")?.trim().parse().ok())
+                .flatten()
+        })
+    };
+    Some(AssetChange {
+        path,
+        lfs: true,
+        added,
+        deleted,
+        old_size: if added { None } else { size_on('-') },
+        new_size: if deleted { None } else { size_on('+') },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTER_OLD: &str = "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 2411724\n";
+
+    #[test]
+    fn detects_pointers_and_binary_pairs() {
+        let pointer = parse_lfs_pointer(POINTER_OLD).unwrap();
+        assert_eq!(pointer.size, 2_411_724);
+        assert!(is_asset(POINTER_OLD.as_bytes()));
+        assert!(is_asset(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_asset(b"fn main() {}\n"));
+
+        let new = POINTER_OLD.replace("2411724", "2516582");
+        let change = classify_texts("assets/model.bin", POINTER_OLD, &new).unwrap();
+        assert!(change.lfs);
+        assert_eq!(change.summary(), "binary changed (2.3 MB → 2.4 MB)");
+        assert!(classify_texts("src/lib.rs", "a\n", "b\n").is_none());
+    }
+
+    #[test]
+    fn summarizes_git_diff_sections() {
+        let diff = "\
+diff --git a/assets/model.bin b/assets/model.bin
+index 1111111..2222222 100644
+--- a/assets/model.bin
++++ b/assets/model.bin
+@@ -1,3 +1,3 @@
+ version https://git-lfs.github.com/spec/v1
+-oid sha256:aaaa
+-size 2411724
++oid sha256:bbbb
++size 2516582
+diff --git a/src/lib.rs b/src/lib.rs
+index 3333333..4444444 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-fn a() {}
++fn b() {}
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..5555555
+Binary files /dev/null and b/logo.png differ
+";
+        let (rewritten, assets) = summarize_git_diff(diff, |asset| {
+            if asset.path == "logo.png" {
+                asset.new_size = Some(1536);
+            }
+        });
+
+        assert_eq!(assets.len(), 2);
+        assert_eq!(assets[0].path, "assets/model.bin");
+        assert_eq!(assets[0].old_size, Some(2_411_724));
+        assert_eq!(assets[0].new_size, Some(2_516_582));
+        assert!(assets[1].added && !assets[1].lfs);
+        assert!(rewritten.contains("LFS binary changed (2.3 MB → 2.4 MB)\n"));
+        assert!(rewritten.contains("+fn b() {}\n"));
+        assert!(!rewritten.contains("oid sha256"));
+        assert!(rewritten.contains("binary added (1.5 KB)\n"));
+    }
+}
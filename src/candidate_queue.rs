@@ -0,0 +1,188 @@
+//! candidate_queue.rs
+//!
+//! The `/testgen candidates` picker: the working tree's test candidates in
+//! ranked order. Each is picked to get tests written, or discarded with a
+//! reason typed in place; discards are recorded in the testgen ledger as
+//! soon as the reason is entered.
+
+use std::path::Path;
+
+use crate::testgen::{self, TestCandidate};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mark {
+    Picked,
+    /// Discarded, with the reason recorded in the ledger.
+    Discarded(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CandidateItem {
+    pub candidate: TestCandidate,
+    pub mark: Option<Mark>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CandidateQueue {
+    pub items: Vec<CandidateItem>,
+    pub selected: usize,
+    /// The discard reason being typed for the selected candidate.
+    pub reason: Option<String>,
+    /// Model the picked candidates go to, for the ledger.
+    pub model: String,
+}
+
+impl CandidateQueue {
+    /// A queue over `candidates`, or `None` when there are none.
+    pub fn new(candidates: Vec<TestCandidate>, model: &str) -> Option<Self> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let items = candidates
+            .into_iter()
+            .map(|candidate| CandidateItem {
+                candidate,
+                mark: None,
+            })
+            .collect();
+        Some(Self {
+            items,
+            selected: 0,
+            reason: None,
+            model: model.to_string(),
+        })
+    }
+
+    pub fn move_selection(&mut self, back: bool) {
+        self.selected = if back {
+            self.selected.saturating_sub(1)
+        } else {
+            (self.selected + 1).min(self.items.len() - 1)
+        };
+    }
+
+    /// Picks or unpicks the selected candidate; discarded ones stay so.
+    pub fn toggle_pick(&mut self) {
+        let item = &mut self.items[self.selected];
+        item.mark = match item.mark {
+            None => Some(Mark::Picked),
+            Some(Mark::Picked) => None,
+            Some(Mark::Discarded(_)) => return,
+        };
+    }
+
+    /// Starts typing a discard reason for the selected candidate.
+    pub fn start_discard(&mut self) {
+        if !matches!(self.items[self.selected].mark, Some(Mark::Discarded(_))) {
+            self.reason = Some(String::new());
+        }
+    }
+
+    /// Records the typed reason and marks the selected candidate discarded.
+    /// An empty reason is refused and keeps the prompt open.
+    pub fn finish_discard(&mut self, repo_root: &Path) -> Result<(), String> {
+        let reason = self
+            .reason
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if reason.is_empty() {
+            return Err("Type why this candidate needs no test, then press Enter.".to_string());
+        }
+        let item = &mut self.items[self.selected];
+        testgen::record_discard(
+            repo_root,
+            &item.candidate,
+            &reason,
+            &self.model,
+            crate::agent::PROMPT_VERSION,
+        )?;
+        item.mark = Some(Mark::Discarded(reason));
+        self.reason = None;
+        self.move_selection(false);
+        Ok(())
+    }
+
+    /// The picked candidates, or the selected one when none is picked and
+    /// it was not discarded.
+    pub fn picked(&self) -> Vec<&TestCandidate> {
+        let picked: Vec<&TestCandidate> = self
+            .items
+            .iter()
+            .filter(|item| item.mark == Some(Mark::Picked))
+            .map(|item| &item.candidate)
+            .collect();
+        if !picked.is_empty() {
+            return picked;
+        }
+        let selected = &self.items[self.selected];
+        match selected.mark {
+            Some(Mark::Discarded(_)) => Vec::new(),
+            _ => vec![&selected.candidate],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use uuid::Uuid;
+
+    fn candidate(symbol: &str) -> TestCandidate {
+        TestCandidate {
+            symbol: symbol.to_string(),
+            path: "src/lib.rs".to_string(),
+            line: 1,
+            covered_by: Vec::new(),
+            changed_lines_covered: None,
+        }
+    }
+
+    #[test]
+    fn picks_and_discards_with_a_recorded_reason() {
+        let root = env::temp_dir().join(format!("osmogrep-candidates-{}", Uuid::new_v4()));
+        assert!(CandidateQueue::new(Vec::new(), "gpt").is_none());
+        let mut queue = CandidateQueue::new(
+            vec![candidate("parse"), candidate("render"), candidate("load")],
+            "gpt",
+        )
+        .unwrap();
+        let symbols = |picked: Vec<&TestCandidate>| -> Vec<String> {
+            picked.into_iter().map(|c| c.symbol.clone()).collect()
+        };
+        assert_eq!(symbols(queue.picked()), ["parse"]);
+
+        queue.start_discard();
+        assert!(queue.finish_discard(&root).is_err());
+        queue.reason = Some(" generated code ".to_string());
+        queue.finish_discard(&root).unwrap();
+        assert_eq!(
+            queue.items[0].mark,
+            Some(Mark::Discarded("generated code".to_string()))
+        );
+        assert_eq!(queue.selected, 1);
+        assert_eq!(queue.reason, None);
+
+        queue.move_selection(true);
+        queue.toggle_pick();
+        assert!(matches!(queue.items[0].mark, Some(Mark::Discarded(_))));
+        assert!(queue.picked().is_empty());
+
+        queue.move_selection(false);
+        queue.move_selection(false);
+        queue.toggle_pick();
+        queue.move_selection(true);
+        queue.toggle_pick();
+        assert_eq!(symbols(queue.picked()), ["render", "load"]);
+        queue.toggle_pick();
+        assert_eq!(symbols(queue.picked()), ["load"]);
+
+        let entries = testgen::load(&root).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].symbols, ["parse"]);
+        assert_eq!(entries[0].reason.as_deref(), Some("generated code"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,248 @@
+//! change_budget.rs
+//!
+//! Caps on how much a single agent run may change before the user is asked
+//! whether to continue, stop and split the rest into a follow-up task, or
+//! roll the run's edits back:
+//!
+//! ```toml
+//! [change_budget]
+//! max_lines = 400   # added + removed lines across the run
+//! max_files = 10
+//! ```
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::state::DiffSnapshot;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeBudget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_lines: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetDecision {
+    /// Keep going; ask again once another budget's worth is spent.
+    Continue,
+    /// Stop here, keep the edits, and leave the rest for a follow-up run.
+    Split,
+    /// Stop and restore every file the run touched.
+    Rollback,
+}
+
+impl BudgetDecision {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BudgetDecision::Continue => "continue",
+            BudgetDecision::Split => "split",
+            BudgetDecision::Rollback => "rollback",
+        }
+    }
+}
+
+struct FileChange {
+    path: String,
+    original: String,
+    latest: String,
+    /// Missing before the run's first change to it.
+    created: bool,
+    /// Missing after its latest change.
+    deleted: bool,
+}
+
+impl FileChange {
+    fn changed(&self) -> bool {
+        self.original != self.latest || self.created != self.deleted
+    }
+}
+
+/// Edits made during one run, per file, from its first to its latest state.
+#[derive(Default)]
+pub struct RunChanges {
+    files: Vec<FileChange>,
+    /// Budgets the user has already let the run spend.
+    allowance: usize,
+}
+
+impl RunChanges {
+    pub fn record(&mut self, change: &DiffSnapshot) {
+        match self.files.iter_mut().find(|f| f.path == change.target) {
+            Some(file) => {
+                file.latest = change.after.clone();
+                file.deleted = change.deleted;
+            }
+            None => self.files.push(FileChange {
+                path: change.target.clone(),
+                original: change.before.clone(),
+                latest: change.after.clone(),
+                created: change.created,
+                deleted: change.deleted,
+            }),
+        }
+    }
+
+    /// Files that differ from before the run.
+    pub fn paths(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|f| f.changed())
+            .map(|f| f.path.clone())
+            .collect()
+    }
+
+    pub fn files_touched(&self) -> usize {
+        self.files.iter().filter(|f| f.changed()).count()
+    }
+
+    /// Added plus removed lines, net of edits that were later undone.
+    pub fn lines_changed(&self) -> usize {
+        self.files
+            .iter()
+            .map(|f| {
+                TextDiff::from_lines(&f.original, &f.latest)
+                    .iter_all_changes()
+                    .filter(|c| c.tag() != ChangeTag::Equal)
+                    .count()
+            })
+            .sum()
+    }
+
+    /// A description of the overrun, if the run has spent more than it was
+    /// allowed.
+    pub fn over_budget(&self, budget: &ChangeBudget) -> Option<String> {
+        let times = self.allowance + 1;
+        let lines = self.lines_changed();
+        let files = self.files_touched();
+        let over_lines = budget.max_lines.filter(|max| lines > max * times);
+        let over_files = budget.max_files.filter(|max| files > max * times);
+        if over_lines.is_none() && over_files.is_none() {
+            return None;
+        }
+        let limits: Vec<String> = [
+            over_lines.map(|max| count(max * times, "line")),
+            over_files.map(|max| count(max * times, "file")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        Some(format!(
+            "{} changed across {} (limit {})",
+            count(lines, "line"),
+            count(files, "file"),
+            limits.join(", ")
+        ))
+    }
+
+    pub fn allow_more(&mut self) {
+        self.allowance += 1;
+    }
+
+    /// One snapshot per touched file, original to latest.
+    pub fn aggregate_diff(&self) -> Vec<DiffSnapshot> {
+        self.files
+            .iter()
+            .filter(|f| f.changed())
+            .map(|f| DiffSnapshot {
+                tool: "run".to_string(),
+                target: f.path.clone(),
+                before: f.original.clone(),
+                after: f.latest.clone(),
+                created: f.created,
+                deleted: f.deleted,
+            })
+            .collect()
+    }
+
+    /// Restore every touched file, removing the ones the run created and
+    /// recreating the ones it deleted. Returns the restorations as snapshots.
+    pub fn rollback(&self, repo_root: &Path) -> Result<Vec<DiffSnapshot>, String> {
+        let mut restored = Vec::new();
+        for file in self.files.iter().filter(|f| f.changed()) {
+            let path = repo_root.join(&file.path);
+            let result = if file.created {
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                }
+            } else {
+                path.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, &file.original))
+            };
+            result.map_err(|e| format!("{}: {e}", file.path))?;
+            restored.push(DiffSnapshot {
+                tool: "rollback".to_string(),
+                target: file.path.clone(),
+                before: file.latest.clone(),
+                after: file.original.clone(),
+                created: file.deleted,
+                deleted: file.created,
+            });
+        }
+        Ok(restored)
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{n} {noun}{}", if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn change(path: &str, before: &str, after: &str, created: bool) -> DiffSnapshot {
+        DiffSnapshot {
+            tool: "write_file".to_string(),
+            target: path.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            created,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn tracks_net_changes_against_budget_and_rolls_back() {
+        let root = std::env::temp_dir().join(format!("osmogrep-budget-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(root.join("b.txt"), "new\n").unwrap();
+        fs::write(root.join("c.txt"), "filled\n").unwrap();
+
+        let budget = ChangeBudget {
+            max_lines: Some(3),
+            max_files: None,
+        };
+        let mut changes = RunChanges::default();
+        changes.record(&change("a.txt", "one\n", "one\ntwo\n", false));
+        assert_eq!(changes.lines_changed(), 1);
+        assert!(changes.over_budget(&budget).is_none());
+
+        changes.record(&change("b.txt", "", "new\n", true));
+        changes.record(&change("a.txt", "one\ntwo\n", "uno\ntwo\nthree\n", false));
+        assert_eq!(changes.files_touched(), 2);
+        assert_eq!(
+            changes.over_budget(&budget).as_deref(),
+            Some("5 lines changed across 2 files (limit 3 lines)")
+        );
+        changes.allow_more();
+        assert!(changes.over_budget(&budget).is_none());
+        // An existing empty file is edited, not created.
+        changes.record(&change("c.txt", "", "filled\n", false));
+        assert_eq!(changes.aggregate_diff().len(), 3);
+
+        let restored = changes.rollback(&root).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "one\n");
+        assert!(!root.join("b.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("c.txt")).unwrap(), "");
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,129 @@
+//! codeowners.rs
+//!
+//! Reads a CODEOWNERS file, in the format GitHub, GitLab and Gitea share,
+//! and answers who owns a repo-relative path. The last matching rule wins;
+//! a rule with no owners clears ownership. GitLab `[Section]` headers are
+//! skipped, so sectioned rules all count as one list.
+
+use std::{fs, path::Path};
+
+use glob::{MatchOptions, Pattern};
+
+/// Where forges look for the file, in the order they look.
+pub const LOCATIONS: &[&str] = &[
+    ".github/CODEOWNERS",
+    ".gitea/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+    "CODEOWNERS",
+    "docs/CODEOWNERS",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    patterns: Vec<Pattern>,
+    /// Logins and `org/team` names without the `@`, or email addresses.
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    pub fn load(repo_root: &Path) -> Option<Self> {
+        LOCATIONS
+            .iter()
+            .find_map(|loc| fs::read_to_string(repo_root.join(loc)).ok())
+            .map(|text| Self::parse(&text))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter(|line| !line.starts_with('[') && !line.starts_with("^["))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let patterns = compile(parts.next()?);
+                let owners = parts
+                    .take_while(|part| !part.starts_with('#'))
+                    .map(|owner| owner.trim_start_matches('@').to_string())
+                    .collect();
+                (!patterns.is_empty()).then_some(Rule { patterns, owners })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn owners(&self, path: &str) -> &[String] {
+        let path = path.trim_start_matches("./");
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.patterns.iter().any(|p| p.matches_with(path, options)))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+// gitignore-style: a leading or inner `/` anchors the pattern to the root,
+// otherwise it matches at any depth; a pattern naming a directory owns
+// everything below it, except `dir/*`, which only owns direct children.
+fn compile(raw: &str) -> Vec<Pattern> {
+    let trimmed = raw.trim_end_matches('/');
+    let anchored = trimmed.starts_with('/') || trimmed.contains('/');
+    let body = trimmed.trim_start_matches('/');
+    if body.is_empty() {
+        return Pattern::new("**").into_iter().collect();
+    }
+    let base = if anchored {
+        body.to_string()
+    } else {
+        format!("**/{body}")
+    };
+    let mut globs = vec![format!("{base}/**")];
+    if !raw.ends_with('/') {
+        globs.push(base.clone());
+    }
+    if body.ends_with("/*") {
+        globs.retain(|glob| glob == &base);
+    }
+    globs
+        .iter()
+        .filter_map(|glob| Pattern::new(glob).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let owners = CodeOwners::parse(
+            "# default\n\
+             *       @core\n\
+             *.rs    @rustaceans @acme/compiler # inline\n\
+             /docs/  docs@example.com\n\
+             [Build]\n\
+             build/* @ci\n\
+             /vendor/generated\n",
+        );
+        assert_eq!(owners.owners("README.md"), ["core"]);
+        assert_eq!(
+            owners.owners("src/deep/lib.rs"),
+            ["rustaceans", "acme/compiler"]
+        );
+        assert_eq!(owners.owners("docs/guide/intro.md"), ["docs@example.com"]);
+        assert_eq!(owners.owners("build/run.sh"), ["ci"]);
+        assert_eq!(owners.owners("build/nested/run.sh"), ["core"]);
+        assert!(owners.owners("vendor/generated/api.rs").is_empty());
+        assert!(CodeOwners::parse("").owners("a.rs").is_empty());
+    }
+}
@@ -0,0 +1,1113 @@
+//! command_docs.rs
+//!
+//! Slash command metadata. `/help`, `/help <command>` pages and the command
+//! palette are all generated from `COMMANDS`, so a command documented here
+//! is also one the palette offers.
+
+use std::sync::OnceLock;
+
+use crate::state::CommandItem;
+
+pub struct CommandDoc {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub details: &'static str,
+    pub args: &'static [(&'static str, &'static str)],
+    /// Fixed forms offered by the palette next to the bare command.
+    pub subcommands: &'static [(&'static str, &'static str)],
+    pub examples: &'static [&'static str],
+    pub related: &'static [&'static str],
+}
+
+const NONE: &[(&str, &str)] = &[];
+
+pub const COMMANDS: &[CommandDoc] = &[
+    CommandDoc {
+        name: "/help",
+        aliases: &[],
+        usage: "/help [command]",
+        summary: "Show available commands",
+        details: "Without an argument, lists every command. With one, shows its usage, \
+                  arguments, examples and related commands.",
+        args: &[("command", "Command name, with or without the leading /")],
+        subcommands: NONE,
+        examples: &["/help", "/help copy", "/help gh"],
+        related: &[],
+    },
+    CommandDoc {
+        name: "/clear",
+        aliases: &[],
+        usage: "/clear",
+        summary: "Clear logs",
+        details: "Empties the execution panel. The conversation and session state are kept.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/new", "/compact"],
+    },
+    CommandDoc {
+        name: "/key",
+        aliases: &[],
+        usage: "/key",
+        summary: "Set OpenAI API key",
+        details: "Switches the input to masked key entry. The key is saved to the config file.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/account", "/model"],
+    },
+    CommandDoc {
+        name: "/voice",
+        aliases: &[],
+        usage: "/voice [on|off|pause|resume|continuous|ptt [key]|wake <phrase>]",
+        summary: "Show voice status",
+        details: "Shows whether voice input is running, or starts and stops it. `ptt` only listens while the key is held (F5 by default) and commits on release; `wake` only keeps what follows the phrase.",
+        args: &[
+            ("key", "Push-to-talk key, e.g. f5, ctrl+space or alt+v"),
+            ("phrase", "Wake phrase, e.g. hey osmo"),
+        ],
+        subcommands: &[
+            ("/voice on", "Start voice input"),
+            ("/voice off", "Stop voice input"),
+            ("/voice pause", "Turn the mic off"),
+            ("/voice resume", "Turn the mic back on"),
+            ("/voice ptt", "Listen only while the push-to-talk key is held"),
+            ("/voice continuous", "Listen all the time"),
+        ],
+        examples: &["/voice on", "/voice ptt ctrl+space", "/voice wake hey osmo"],
+        related: &["/speak"],
+    },
+    CommandDoc {
+        name: "/speak",
+        aliases: &[],
+        usage: "/speak [on|off]",
+        summary: "Read final answers aloud",
+        details: "Synthesizes each final answer with the OpenAI speech API, or piper with `[voice] tts = \"piper\"`, and plays it. Code blocks and URLs are skipped; talking or holding push-to-talk cuts it off.",
+        args: NONE,
+        subcommands: &[
+            ("/speak on", "Read final answers aloud"),
+            ("/speak off", "Stop reading answers aloud"),
+        ],
+        examples: &["/speak on"],
+        related: &["/voice"],
+    },
+    CommandDoc {
+        name: "/status",
+        aliases: &[],
+        usage: "/status [system]",
+        summary: "Show session, run, model, and repo status",
+        details: "`system` shows outbound HTTP retries and circuit breaker state per host.",
+        args: NONE,
+        subcommands: &[(
+            "/status system",
+            "Show HTTP retry and circuit breaker stats",
+        )],
+        examples: &["/status", "/status system"],
+        related: &["/usage", "/metrics", "/lock"],
+    },
+    CommandDoc {
+        name: "/tab",
+        aliases: &["/tabs"],
+        usage: "/tab [new [path] | close | <n>]",
+        summary: "List, open, close or switch session tabs",
+        details: "Each tab is an independent session with its own conversation, log and running \
+                  agent; a background tab's agent keeps working and its output shows when the \
+                  tab is picked again. `new` opens a tab in this repo or in another directory. \
+                  Ctrl+1..9 (or Alt+1..9) switch tabs. A second tab on the same repo is not \
+                  saved between launches.",
+        args: &[
+            ("path", "Repo directory for the new tab; relative to this repo"),
+            ("n", "Tab number to switch to"),
+        ],
+        subcommands: &[
+            ("/tab new", "Open a new session tab in this repo"),
+            ("/tab close", "Close the current tab"),
+        ],
+        examples: &["/tab new ../docs-site", "/tab 2", "/tab close"],
+        related: &["/session", "/lock"],
+    },
+    CommandDoc {
+        name: "/lock",
+        aliases: &[],
+        usage: "/lock [take]",
+        summary: "Show the repo lock owner",
+        details: "Only one instance per repo can write. Others attach read-only until they \
+                  take the lock.",
+        args: NONE,
+        subcommands: &[("/lock take", "Take the repo lock from another instance")],
+        examples: &["/lock take"],
+        related: &["/profile", "/status"],
+    },
+    CommandDoc {
+        name: "/hooks",
+        aliases: &[],
+        usage: "/hooks [trust]",
+        summary: "Show the repo's lifecycle hooks",
+        details: "Lists what .osmogrep/hooks.toml runs and whether it is trusted. Agent runs \
+                  are refused until the file is trusted, and again after it changes.",
+        args: NONE,
+        subcommands: &[("/hooks trust", "Trust the hooks file as it is now")],
+        examples: &["/hooks trust"],
+        related: &["/lock", "/profile"],
+    },
+    CommandDoc {
+        name: "/account",
+        aliases: &[],
+        usage: "/account",
+        summary: "Show provider/account configuration",
+        details: "Shows the provider, base URL and a masked API key.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/key", "/model", "/providers"],
+    },
+    CommandDoc {
+        name: "/model",
+        aliases: &[],
+        usage: "/model [<provider> <model> [base_url]]",
+        summary: "Show active provider/model",
+        details: "Without arguments, shows the active model. With arguments, switches the \
+                  runtime model config and saves it.",
+        args: &[
+            ("provider", "One of the providers listed by /providers"),
+            ("model", "Model id for that provider"),
+            ("base_url", "Optional API base URL override"),
+        ],
+        subcommands: NONE,
+        examples: &[
+            "/model openai gpt-4.1",
+            "/model ollama qwen2.5-coder http://localhost:11434/v1",
+        ],
+        related: &["/providers", "/account"],
+    },
+    CommandDoc {
+        name: "/providers",
+        aliases: &[],
+        usage: "/providers",
+        summary: "Show available model providers",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/model"],
+    },
+    CommandDoc {
+        name: "/usage",
+        aliases: &[],
+        usage: "/usage",
+        summary: "Show token usage and estimated cost",
+        details: "Session totals from the provider's reported usage, the last run, a \
+                  breakdown per provider/model with its estimated cost, and per tool: the \
+                  tokens of the model turns that called it, drawn as block bars. Prices come from a built-in \
+                  table or `[pricing]` in config.toml; a `+` after the cost means some \
+                  model has no price.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/metrics", "/compact"],
+    },
+    CommandDoc {
+        name: "/metrics",
+        aliases: &[],
+        usage: "/metrics",
+        summary: "Show usage and queue metrics",
+        details: "Token usage as block bars, plus the active job count.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/usage", "/jobs"],
+    },
+    CommandDoc {
+        name: "/compact",
+        aliases: &[],
+        usage: "/compact",
+        summary: "Compress conversation context",
+        details: "Trims the conversation to two thirds of the token budget.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/usage", "/new"],
+    },
+    CommandDoc {
+        name: "/theme",
+        aliases: &[],
+        usage: "/theme [dark|light]",
+        summary: "Show or set dark/light theme",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/theme light"],
+        related: &["/color", "/type"],
+    },
+    CommandDoc {
+        name: "/color",
+        aliases: &[],
+        usage: "/color [orange|blue|green|violet|rose]",
+        summary: "Show or set accent color",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/color blue"],
+        related: &["/theme", "/type"],
+    },
+    CommandDoc {
+        name: "/type",
+        aliases: &[],
+        usage: "/type [compact|standard|spacious]",
+        summary: "Show or set UI density",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/type compact"],
+        related: &["/theme", "/color"],
+    },
+    CommandDoc {
+        name: "/timestamps",
+        aliases: &[],
+        usage: "/timestamps [off|absolute|relative]",
+        summary: "Cycle or set log timestamp prefixes",
+        details: "Absolute times use the local timezone; OSMOGREP_TIME_FORMAT sets a strftime \
+                  pattern. Saved transcripts always carry precise timestamps.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/timestamps relative"],
+        related: &["/type", "/copy"],
+    },
+    CommandDoc {
+        name: "/open",
+        aliases: &[],
+        usage: "/open [path[:line[:col]]]",
+        summary: "Open the latest path:line reference (Ctrl+G, or click one)",
+        details: "With a path, opens it in $VISUAL/$EDITOR at the line and suspends the TUI \
+                  until the editor exits (e or Alt+E opens the file in view). Without one, or \
+                  with no editor set, shows an excerpt around the line; an [editor] command \
+                  from the config file is copied or run instead.",
+        args: &[("path", "File relative to the repo root, or absolute")],
+        subcommands: NONE,
+        examples: &["/open", "/open src/main.rs:120"],
+        related: &["/diff", "/nv"],
+    },
+    CommandDoc {
+        name: "/split",
+        aliases: &[],
+        usage: "/split",
+        summary: "Show the session diff beside the execution log (Alt+S)",
+        details: "The right pane holds one diff per changed file, from before the session's \
+                  first change to now, and scrolls to the file the agent changed last. It \
+                  needs a terminal at least 100 columns wide.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/diff", "/minimap"],
+    },
+    CommandDoc {
+        name: "/minimap",
+        aliases: &[],
+        usage: "/minimap",
+        summary: "Toggle the diff minimap in the scroll gutter",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/diff"],
+    },
+    CommandDoc {
+        name: "/detach",
+        aliases: &[],
+        usage: "/detach <task>",
+        summary: "Run a task in a background process that outlives the TUI",
+        details: "Starts `osmogrep run` with the current permission profile; events go to \
+                  the session directory. Dangerous tools are denied unless /approve is on.",
+        args: &[("task", "Prompt for the agent")],
+        subcommands: NONE,
+        examples: &["/detach migrate the config loader to serde"],
+        related: &["/attach", "/jobs"],
+    },
+    CommandDoc {
+        name: "/attach",
+        aliases: &[],
+        usage: "/attach [id|stop|<image>|clear]",
+        summary: "List detached runs, or replay and follow one; or attach an image",
+        details: "Events written while nobody was watching are shown first, then new ones \
+                  as they arrive. `osmogrep attach <id>` does the same outside the TUI. \
+                  A png, jpeg, gif or webp path is sent with the next prompt instead, for \
+                  vision models; dropping the file on the terminal does the same.",
+        args: &[
+            ("id", "Run id or a unique prefix"),
+            ("image", "Image path, relative to the repo root"),
+        ],
+        subcommands: &[
+            ("/attach stop", "Stop following the current run"),
+            ("/attach clear", "Drop images and piped stdin attached to the next prompt"),
+        ],
+        examples: &["/attach", "/attach 3f9c", "/attach docs/screenshot.png"],
+        related: &["/detach"],
+    },
+    CommandDoc {
+        name: "/pr",
+        aliases: &[],
+        usage: "/pr [title <text>|create|cancel]",
+        summary: "Draft a pull request from the conversation and diff, then open it",
+        details: "`/pr` previews the title, branch and body without touching anything. \
+                  `/pr create` commits uncommitted changes (on a new osmogrep/ branch when on \
+                  the base branch), pushes, and opens the PR with gh, or the GitHub API when \
+                  GITHUB_TOKEN is set and gh is missing.",
+        args: NONE,
+        subcommands: &[
+            ("/pr title <text>", "Replace the drafted title"),
+            ("/pr create", "Commit, push and open the drafted PR"),
+            ("/pr cancel", "Drop the draft"),
+        ],
+        examples: &["/pr", "/pr title Fix flaky parser test", "/pr create"],
+        related: &["/gh", "/git"],
+    },
+    CommandDoc {
+        name: "/queue",
+        aliases: &[],
+        usage: "/queue [--sandbox] <task> | attach <id> | review <id>",
+        summary: "Queue agent tasks that run in the background",
+        details: "Each task runs as a detached run while the TUI stays free. Tasks in the \
+                  checkout run one after another; `--sandbox` tasks get their own worktree \
+                  and run in parallel. `/queue` alone lists the tasks.",
+        args: &[("task", "Prompt for the agent")],
+        subcommands: &[
+            (
+                "/queue attach <id>",
+                "Replay and follow a task's event stream",
+            ),
+            (
+                "/queue review <id>",
+                "Make a finished task's sandbox the active one for /sandbox diff and promote",
+            ),
+        ],
+        examples: &[
+            "/queue fix lint errors",
+            "/queue --sandbox add tests for parser",
+            "/queue attach 4",
+        ],
+        related: &["/jobs", "/detach", "/sandbox"],
+    },
+    CommandDoc {
+        name: "/keys",
+        aliases: &[],
+        usage: "/keys",
+        summary: "List key bindings",
+        details: "Shows every bindable action with its keys. Rebind them in the [keys] section \
+                  of config.toml, e.g. `page_up = [\"pageup\", \"ctrl+b\"]`; an entry \
+                  replaces that action's defaults.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/reload"],
+    },
+    CommandDoc {
+        name: "/commands",
+        aliases: &[],
+        usage: "/commands",
+        summary: "List custom commands",
+        details: "Lists the prompt templates in .osmogrep/commands/<name>.md, each offered as \
+                  /<name>. Running one sends the template to the agent with $ARGUMENTS \
+                  replaced by everything after the name and $1..$9 by its words. Optional \
+                  front matter sets `description:` and an `args:` hint.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/commands", "/release-notes v1.4 customers"],
+        related: &["/reload", "/help"],
+    },
+    CommandDoc {
+        name: "/reload",
+        aliases: &[],
+        usage: "/reload",
+        summary: "Re-read config.toml and apply changed settings",
+        details: "Validates the file first; on error the current settings stay. Model, API key \
+                  and proxy changes apply immediately, and every changed setting is listed. \
+                  Edits to the file are also picked up automatically. Custom commands in \
+                  .osmogrep/commands/ are read again too.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/model", "/status"],
+    },
+    CommandDoc {
+        name: "/copy",
+        aliases: &[],
+        usage: "/copy [N|all]",
+        summary: "Copy latest assistant response",
+        details: "Copies to the system clipboard, or over OSC 52 on SSH. Ctrl+O copies the \
+                  latest response.",
+        args: &[("N", "Copy the Nth most recent response instead")],
+        subcommands: &[("/copy all", "Copy visible transcript")],
+        examples: &["/copy 2", "/copy all"],
+        related: &[],
+    },
+    CommandDoc {
+        name: "/inspect",
+        aliases: &[],
+        usage: "/inspect",
+        summary: "Summarize uncommitted changes",
+        details: "Changed files with line counts, the indexed symbols the hunks touch and \
+                  their callers. Analyzed in the background while the app is idle, so it \
+                  is usually instant; the first prompt of a new task gets the same summary. \
+                  /diff staged, unstaged or branch changes what it compares against.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/inspect"],
+        related: &["/diff", "/git", "/impact", "/onboard"],
+    },
+    CommandDoc {
+        name: "/impact",
+        aliases: &[],
+        usage: "/impact [symbol]",
+        summary: "List the functions a change to a symbol can affect",
+        details: "Walks the call graph of the context index outwards from the symbol: its \
+                  callers, their callers and so on, up to six calls away, nearest first. \
+                  Without a symbol it reports every symbol the working tree changes. The \
+                  agent has the same walk as the impact_analysis tool.",
+        args: &[("symbol", "Function or method name as indexed")],
+        subcommands: NONE,
+        examples: &["/impact", "/impact parse_config"],
+        related: &["/inspect", "/find"],
+    },
+    CommandDoc {
+        name: "/find",
+        aliases: &[],
+        usage: "/find <query>",
+        summary: "Rank indexed functions and files by relevance to a query",
+        details: "BM25 over the context index: symbol names, parameters, doc comments and \
+                  the identifiers and comments in their bodies, plus file paths and \
+                  comments. Identifiers are split on case and underscores, so plain words \
+                  match camelCase and snake_case names. The agent has the same ranking as \
+                  the semantic_search tool.",
+        args: &[("query", "Words describing the code you are looking for")],
+        subcommands: NONE,
+        examples: &["/find retry backoff delay", "/find where is the config loaded"],
+        related: &["/impact", "/onboard"],
+    },
+    CommandDoc {
+        name: "/git",
+        aliases: &["/status git"],
+        usage: "/git [stage|ignore|include|exclude <path>]",
+        summary: "List staged, unstaged and untracked files",
+        details: "Untracked files are left out of the background diff analysis until \
+                  included; an included file counts as an all-new addition. `ignore` \
+                  appends the path to .gitignore.",
+        args: &[("path", "File path relative to the repo root")],
+        subcommands: &[
+            ("/git stage <path>", "Stage the file with git add"),
+            ("/git ignore <path>", "Add the path to .gitignore"),
+            (
+                "/git include <path>",
+                "Analyze an untracked file as a new addition",
+            ),
+            (
+                "/git exclude <path>",
+                "Leave an untracked file out of the analysis",
+            ),
+        ],
+        examples: &[
+            "/git",
+            "/git include src/new_module.rs",
+            "/git ignore scratch.log",
+        ],
+        related: &["/inspect", "/diff"],
+    },
+    CommandDoc {
+        name: "/export",
+        aliases: &[],
+        usage: "/export panel [path] [--ansi]",
+        summary: "Save the focused panel to a file",
+        details: "Writes what the panel shows: the diff view when it is open, otherwise the \
+                  visible part of the execution log. Ctrl+S saves it under .osmogrep/exports.",
+        args: &[
+            ("path", "Output file (.osmogrep/exports/panel-<time>.txt)"),
+            ("--ansi", "Keep colors as ANSI escape codes"),
+        ],
+        subcommands: NONE,
+        examples: &["/export panel", "/export panel diff.txt --ansi"],
+        related: &["/copy", "/diff"],
+    },
+    CommandDoc {
+        name: "/rename",
+        aliases: &["/session"],
+        usage: "/rename <session name>",
+        summary: "Rename current session",
+        details: "Without a name, shows the current session name.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/rename retry-policy"],
+        related: &["/new"],
+    },
+    CommandDoc {
+        name: "/test",
+        aliases: &[],
+        usage: "/test [target|--changed]",
+        summary: "Run auto-detected project tests",
+        details: "Runs the detected test command, optionally narrowed to a target. \
+                  `--changed` runs only the workspace packages (Cargo, npm/yarn/pnpm, \
+                  Python projects) that own uncommitted changes.",
+        args: &[("target", "Test filter or path passed to the test runner")],
+        subcommands: NONE,
+        examples: &["/test", "/test parser::tests", "/test --changed"],
+        related: &["/verify", "/job"],
+    },
+    CommandDoc {
+        name: "/coverage",
+        aliases: &[],
+        usage: "/coverage",
+        summary: "Collect line coverage and rate the changed lines",
+        details: "Runs `cargo llvm-cov` or `pytest --cov` and keeps the lcov report in \
+                  .context/coverage.lcov. `/inspect`, `osmogrep analyze` and the first \
+                  prompt of a task then show how many changed lines the tests execute, and \
+                  test candidates are ranked least covered first. The five least covered \
+                  files are drawn as block bars.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/coverage"],
+        related: &["/test", "/inspect"],
+    },
+    CommandDoc {
+        name: "/verify",
+        aliases: &[],
+        usage: "/verify",
+        summary: "Show verification ledger status",
+        details: "Whether the latest changes passed, failed or are stale.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/test", "/autofix"],
+    },
+    CommandDoc {
+        name: "/testgen",
+        aliases: &[],
+        usage: "/testgen [stats|maintain|strength|candidates [intent]]",
+        summary: "Show acceptance and gate pass rates of agent-written tests",
+        details: "Reads .osmogrep/testgen-ledger.json: commits with test files count as \
+                  accepted, denied test edits as rejected; gate pass is the verification \
+                  status at commit time. `maintain` runs the suite and asks the agent to \
+                  update generated tests that fail because a function they call changed \
+                  after they were committed. `strength` mutates the code the latest \
+                  generated tests call (swapped operators, negated conditions) in a sandbox \
+                  worktree and reports how many mutants those tests kill. `candidates` lists \
+                  the changed functions worth testing, those the intent (or the latest \
+                  prompt) names first, then untested ones, then by file risk; space picks, \
+                  w sends the picked ones to the agent, and x discards one with a reason \
+                  that is kept in the ledger.",
+        args: NONE,
+        subcommands: &[
+            ("/testgen stats", "Totals plus one row per week"),
+            (
+                "/testgen maintain",
+                "Update failing generated tests to changed code",
+            ),
+            (
+                "/testgen strength",
+                "Mutation score of the latest generated tests",
+            ),
+            (
+                "/testgen candidates",
+                "Pick which changed functions get tests written",
+            ),
+        ],
+        examples: &[
+            "/testgen stats",
+            "/testgen maintain",
+            "/testgen candidates retry logic",
+        ],
+        related: &["/verify", "/test"],
+    },
+    CommandDoc {
+        name: "/onboard",
+        aliases: &[],
+        usage: "/onboard [export [path]]",
+        summary: "Getting-started report for this repository",
+        details: "Entry points, how to run the tests, risky modules, conventions inferred \
+                  from existing tests, a dependency audit and a TODO scan, built from the \
+                  context index. `osmogrep onboard` prints the same report.",
+        args: &[("path", "Export path (.osmogrep/onboarding.md)")],
+        subcommands: &[("/onboard export [path]", "Also write it as markdown")],
+        examples: &["/onboard", "/onboard export docs/ONBOARDING.md"],
+        related: &["/test", "/status"],
+    },
+    CommandDoc {
+        name: "/autofix",
+        aliases: &[],
+        usage: "/autofix [on|off]",
+        summary: "Show or set auto-eval after agent runs",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/autofix on"],
+        related: &["/verify"],
+    },
+    CommandDoc {
+        name: "/mcp",
+        aliases: &[],
+        usage: "/mcp",
+        summary: "Show MCP status and servers",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/providers"],
+    },
+    CommandDoc {
+        name: "/undo",
+        aliases: &[],
+        usage: "/undo",
+        summary: "Revert the last agent file change",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/diff"],
+    },
+    CommandDoc {
+        name: "/review",
+        aliases: &[],
+        usage: "/review [all]",
+        summary: "Accept or reject the last run's changes file by file",
+        details: "Opens on its own after a run that changed files. ↑/↓ pick a file, a/r \
+                  accept or reject it (A/R all of them), d or Enter shows its diff, c \
+                  confirms and Esc closes without touching anything. Confirming restores \
+                  rejected files to how they were before the run and deletes files it \
+                  created.",
+        args: NONE,
+        subcommands: &[("/review all", "Review every change of the session instead")],
+        examples: &["/review", "/review all"],
+        related: &["/diff", "/undo"],
+    },
+    CommandDoc {
+        name: "/sandbox",
+        aliases: &[],
+        usage: "/sandbox [on|off|diff|promote|discard]",
+        summary: "Run the agent in a git worktree instead of the checkout",
+        details: "While on, every agent run reads, writes and runs shell commands in a \
+                  worktree seeded with the checkout's uncommitted and untracked files. \
+                  Nothing reaches your branch until `promote` applies the sandbox diff \
+                  with git apply; `off` refuses while unpromoted changes remain.",
+        args: NONE,
+        subcommands: &[
+            (
+                "/sandbox on",
+                "Create the worktree and route agent runs to it",
+            ),
+            ("/sandbox diff", "Open the sandbox changes in the diff view"),
+            (
+                "/sandbox promote",
+                "Apply the sandbox changes to the checkout",
+            ),
+            (
+                "/sandbox discard",
+                "Drop the changes since the last promote",
+            ),
+            (
+                "/sandbox off",
+                "Remove the worktree and edit the checkout again",
+            ),
+        ],
+        examples: &["/sandbox on", "/sandbox diff", "/sandbox promote"],
+        related: &["/diff", "/undo", "/git"],
+    },
+    CommandDoc {
+        name: "/rebase",
+        aliases: &[],
+        usage: "/rebase [<base>|continue|abort]",
+        summary: "Rebase the branch and let the agent resolve conflicts",
+        details: "Runs git rebase in the checkout. Each stop on conflicts queues an agent \
+                  run with the conflict regions and the lines around them; its edits are \
+                  previewed and need approval even with /approve on. The rebase continues \
+                  by itself once the run leaves no conflict markers. Without arguments, \
+                  shows what is still unresolved.",
+        args: &[("base", "Branch or commit to rebase onto, e.g. origin/main")],
+        subcommands: &[
+            (
+                "/rebase continue",
+                "Stage the resolved files and continue the rebase",
+            ),
+            ("/rebase abort", "Abort and restore the branch"),
+        ],
+        examples: &["/rebase origin/main", "/rebase continue"],
+        related: &["/git", "/diff", "/sandbox"],
+    },
+    CommandDoc {
+        name: "/diff",
+        aliases: &[],
+        usage: "/diff [baseline|head|staged|unstaged|branch|base <ref>]",
+        summary: "Show all file changes this session",
+        details: "One diff per file, from before the session first changed it to how it is \
+                  on disk now; files created or deleted along the way are marked. The \
+                  subcommands choose what /inspect and the first prompt of a task \
+                  compare against, remembered per branch. `branch` diffs from where the \
+                  branch forked off its base, origin/HEAD or main unless set with `base`.",
+        args: &[("ref", "Base branch or commit, e.g. origin/main")],
+        subcommands: &[
+            ("/diff baseline", "Show what /inspect compares against"),
+            ("/diff head", "Staged and unstaged changes against HEAD (default)"),
+            ("/diff staged", "Only staged changes"),
+            ("/diff unstaged", "Only changes not yet staged"),
+            ("/diff branch", "Everything since the branch forked off its base"),
+            ("/diff base <ref>", "Set the base branch and compare against it"),
+        ],
+        examples: &["/diff", "/diff staged", "/diff base origin/main"],
+        related: &["/undo", "/minimap", "/open"],
+    },
+    CommandDoc {
+        name: "/profile",
+        aliases: &[],
+        usage: "/profile [read-only|workspace-auto|full-access]",
+        summary: "Show or set permission profile",
+        details: "Writable profiles are refused while another instance holds the repo lock.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/profile read-only"],
+        related: &["/approve", "/lock"],
+    },
+    CommandDoc {
+        name: "/approve",
+        aliases: &[],
+        usage: "/approve",
+        summary: "Toggle dangerous tool auto-approve",
+        details: "When off, run_shell, write_file and edit_file ask before running.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/profile", "/approvals"],
+    },
+    CommandDoc {
+        name: "/approvals",
+        aliases: &[],
+        usage: "/approvals [revoke <n>]",
+        summary: "List or revoke remembered permission answers",
+        details: "Answering `a` at a permission prompt stores the tool with a pattern in \
+                  .osmogrep/approvals.json: `run_shell: cargo *` for commands, \
+                  `edit_file: src/**` for edits. Matching calls then run without a prompt; \
+                  protected paths always prompt.",
+        args: NONE,
+        subcommands: &[("/approvals revoke", "Forget an approval by its number")],
+        examples: &["/approvals", "/approvals revoke 2"],
+        related: &["/approve", "/profile"],
+    },
+    CommandDoc {
+        name: "/budget",
+        aliases: &[],
+        usage: "/budget [tools=<n>] [tokens=<n>] [time=<duration>] | reset",
+        summary: "Show or set the limits an agent run stops at",
+        details: "A run stops at its tool call, token or wall time limit and reports what it \
+                  did and which plan steps remain. Defaults come from [run_budget] in the \
+                  config; values set here apply to this session's runs. `off` lifts a limit.",
+        args: NONE,
+        subcommands: &[("/budget reset", "Go back to the configured limits")],
+        examples: &["/budget", "/budget tools=40 tokens=200k time=10m", "/budget time=off"],
+        related: &["/usage", "/plan"],
+    },
+    CommandDoc {
+        name: "/continue",
+        aliases: &[],
+        usage: "/continue",
+        summary: "Resume the last failed agent run",
+        details: "Restores the failed run's conversation, including the tool outputs of its \
+                  last turn, and asks the agent to carry on. A run that fails on a network \
+                  error or a 429/5xx response is retried this way once on its own; set \
+                  OSMOGREP_AUTO_CONTINUE=0 to turn that off.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/steer", "/budget"],
+    },
+    CommandDoc {
+        name: "/new",
+        aliases: &[],
+        usage: "/new",
+        summary: "Start a fresh conversation",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/clear", "/rename"],
+    },
+    CommandDoc {
+        name: "/plan",
+        aliases: &[],
+        usage: "/plan [add <text>|edit <id> <text>|files <id> <paths>|drop <id>|done <id>|approve|clear|mode [on|off]]",
+        summary: "Show/update plan items",
+        details: "Plan mode keeps the agent read-only while it writes a plan: steps with the \
+                  files each will change. Edit, retarget or drop steps, then `approve` leaves \
+                  plan mode and runs the plan step by step; edits outside the open steps' \
+                  files are refused until the plan is done or changed.",
+        args: NONE,
+        subcommands: &[
+            ("/plan add", "Add a plan item"),
+            ("/plan edit", "Reword a plan item"),
+            ("/plan files", "Set the files a plan item will change"),
+            ("/plan drop", "Remove a plan item"),
+            ("/plan done", "Mark a plan item complete"),
+            ("/plan approve", "Execute the plan"),
+            ("/plan clear", "Clear plan items"),
+            ("/plan mode", "Toggle plan-only read-only agent mode"),
+        ],
+        examples: &[
+            "/plan add wire retries into updater",
+            "/plan files 2 src/updater.rs src/net/**",
+            "/plan done 2",
+            "/plan mode on",
+            "/plan approve",
+        ],
+        related: &["/steer"],
+    },
+    CommandDoc {
+        name: "/steer",
+        aliases: &[],
+        usage: "/steer [<text>|<template>|now <text>|history|resend <n>|templates|clear]",
+        summary: "Set or show persistent steer instruction",
+        details: "The steer instruction is added to every run. `now` injects it into the \
+                  current run, or launches one with it. Steers sent during a run are listed \
+                  in the Steering panel and by `history`; `resend` sends one again. A \
+                  template name from `[steer_templates]` in config sets its text.",
+        args: NONE,
+        subcommands: &[
+            ("/steer history", "List steers sent during this run"),
+            ("/steer resend", "Send a steer from the history again"),
+            ("/steer templates", "List named steer templates"),
+        ],
+        examples: &[
+            "/steer prefer small diffs",
+            "/steer now stop editing tests",
+            "/steer tests-first",
+            "/steer resend 2",
+            "/steer clear",
+        ],
+        related: &["/plan"],
+    },
+    CommandDoc {
+        name: "/jobs",
+        aliases: &[],
+        usage: "/jobs",
+        summary: "Show background jobs",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/job", "/metrics"],
+    },
+    CommandDoc {
+        name: "/job",
+        aliases: &[],
+        usage: "/job test [target] | swarm <task> | resume <id> | cancel all",
+        summary: "Queue or manage a background job",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[
+            "/job test",
+            "/job swarm audit error handling",
+            "/job cancel all",
+        ],
+        related: &["/jobs", "/swarm"],
+    },
+    CommandDoc {
+        name: "/swarm",
+        aliases: &[],
+        usage: "/swarm <task>",
+        summary: "Run a task with parallel subagents now",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/swarm find unused config fields"],
+        related: &["/job"],
+    },
+    CommandDoc {
+        name: "/triage",
+        aliases: &[],
+        usage: "/triage [options]",
+        summary: "Run PR/issue triage for this repo",
+        details: "Options are free text passed to the triage agent as intent.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/triage", "/triage only open PRs from the last week"],
+        related: &["/gh"],
+    },
+    CommandDoc {
+        name: "/gh",
+        aliases: &[],
+        usage: "/gh [status] | prs [open|closed|merged] [limit] | issues [open|closed] [limit] \
+                | triage [flags]",
+        summary: "GitHub CLI status and PR/issue/triage views",
+        details: "Requires the gh CLI, logged in.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &["/gh prs open 20", "/gh issues closed"],
+        related: &["/triage"],
+    },
+    CommandDoc {
+        name: "/nv",
+        aliases: &[],
+        usage: "/nv [file|toggle|help]",
+        summary: "Open Neovim split (auto tmux bootstrap)",
+        details: "Opens nvim with a tree view at the repo root in a tmux split.",
+        args: &[("file", "File to open")],
+        subcommands: &[
+            ("/nv toggle", "Toggle nvim pane in current tmux window"),
+            ("/nv help", "Show nvim/tmux exit shortcuts"),
+        ],
+        examples: &["/nv src/main.rs"],
+        related: &["/open"],
+    },
+    CommandDoc {
+        name: "/quit",
+        aliases: &["/q"],
+        usage: "/quit",
+        summary: "Stop agent execution",
+        details: "Esc does the same while the agent is running.",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/exit"],
+    },
+    CommandDoc {
+        name: "/exit",
+        aliases: &[],
+        usage: "/exit",
+        summary: "Exit Osmogrep",
+        details: "",
+        args: NONE,
+        subcommands: NONE,
+        examples: &[],
+        related: &["/quit"],
+    },
+];
+
+/// Looks a command up by name or alias, with or without the leading `/`.
+pub fn find(name: &str) -> Option<&'static CommandDoc> {
+    let name = name.trim();
+    let name = name.strip_prefix('/').unwrap_or(name);
+    COMMANDS
+        .iter()
+        .find(|doc| doc.name[1..] == *name || doc.aliases.iter().any(|alias| alias[1..] == *name))
+}
+
+pub fn palette_items() -> &'static [CommandItem] {
+    static ITEMS: OnceLock<Vec<CommandItem>> = OnceLock::new();
+    ITEMS.get_or_init(|| {
+        COMMANDS
+            .iter()
+            .flat_map(|doc| {
+                std::iter::once((doc.name, doc.summary)).chain(doc.subcommands.iter().copied())
+            })
+            .map(|(cmd, desc)| CommandItem {
+                cmd: cmd.to_string(),
+                desc: desc.to_string(),
+            })
+            .collect()
+    })
+}
+
+/// The `/help` overview.
+pub fn overview() -> Vec<String> {
+    let mut lines = vec!["Available commands:".to_string()];
+    for doc in COMMANDS {
+        let mut name = doc.name.to_string();
+        for alias in doc.aliases {
+            name.push_str(" | ");
+            name.push_str(alias);
+        }
+        lines.push(format!("  {name:<16} {}", doc.summary));
+    }
+    lines.push(String::new());
+    lines.push("Use /help <command> for usage, arguments and examples.".to_string());
+    lines.push("Anything else is sent to the agent.".to_string());
+    lines.push(
+        "Testing, planning, review, triage, and subagents are model/harness workflows.".to_string(),
+    );
+    lines.push("!<cmd> runs a shell command directly.".to_string());
+    lines
+}
+
+/// Man-style page for one command.
+pub fn page(doc: &CommandDoc) -> Vec<String> {
+    let mut lines = vec![format!("{} — {}", doc.name, doc.summary)];
+    lines.push(format!("Usage: {}", doc.usage));
+    if !doc.details.is_empty() {
+        lines.push(doc.details.to_string());
+    }
+
+    let mut section = |title: &str, rows: Vec<String>| {
+        if !rows.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("{title}:"));
+            lines.extend(rows.into_iter().map(|row| format!("  {row}")));
+        }
+    };
+    section(
+        "Arguments",
+        doc.args
+            .iter()
+            .map(|(name, desc)| format!("{name:<12} {desc}"))
+            .collect(),
+    );
+    section(
+        "Subcommands",
+        doc.subcommands
+            .iter()
+            .map(|(cmd, desc)| format!("{cmd:<14} {desc}"))
+            .collect(),
+    );
+    section(
+        "Examples",
+        doc.examples.iter().map(|e| e.to_string()).collect(),
+    );
+    section(
+        "Aliases",
+        (!doc.aliases.is_empty())
+            .then(|| doc.aliases.join(", "))
+            .into_iter()
+            .collect(),
+    );
+    section(
+        "Related",
+        (!doc.related.is_empty())
+            .then(|| doc.related.join(", "))
+            .into_iter()
+            .collect(),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_commands_by_name_and_alias() {
+        assert_eq!(find("copy").unwrap().name, "/copy");
+        assert_eq!(find("/q").unwrap().name, "/quit");
+        assert_eq!(find("session").unwrap().name, "/rename");
+        assert!(find("nope").is_none());
+
+        let page = page(find("/copy").unwrap());
+        assert_eq!(page[0], "/copy — Copy latest assistant response");
+        assert_eq!(page[1], "Usage: /copy [N|all]");
+        assert!(page.contains(&"Examples:".to_string()));
+        assert!(page.contains(&"  /copy all      Copy visible transcript".to_string()));
+    }
+
+    #[test]
+    fn table_is_consistent() {
+        for doc in COMMANDS {
+            assert!(doc.usage.starts_with(doc.name), "{}", doc.name);
+            for related in doc.related {
+                assert!(find(related).is_some(), "{} -> {related}", doc.name);
+            }
+            for (sub, _) in doc.subcommands {
+                assert!(sub.starts_with(&format!("{} ", doc.name)), "{sub}");
+            }
+        }
+        let items = palette_items();
+        assert!(items.iter().any(|item| item.cmd == "/voice off"));
+        let mut names: Vec<_> = items.iter().map(|item| item.cmd.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), items.len());
+    }
+}
@@ -2,23 +2,41 @@
 
 use std::env;
 use std::fs;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{
+    collections::BTreeSet,
     io::Write,
     path::{Path, PathBuf},
 };
 
-use crate::agent::Agent;
+use crate::agent::{self, Agent};
+use crate::approvals::{self, Approvals};
+use crate::candidate_queue::CandidateQueue;
 use crate::clipboard;
+use crate::command_docs;
+use crate::context::{impact, indexer, search, working_diff};
+use crate::custom_commands::{self, CustomCommand};
+use crate::file_refs::{self, FileRef};
+use crate::git_status;
+use crate::http_policy;
+use crate::images::{self, ImageAttachment};
+use crate::instance;
+use crate::keymap::KeyAction;
 use crate::logger::{log, parse_user_input_log};
 use crate::persistence;
+use crate::platform;
+use crate::rebase::{self, RebaseOutcome};
+use crate::review_queue::ReviewQueue;
 use crate::state::{
-    AgentState, CommandItem, InputMode, JobKind, JobRecord, JobRequest, JobStatus, LogBuffer,
-    LogLevel, PermissionProfile, PlanItem, UiAccent, UiDensity, UiTheme, MAX_CONVERSATION_TOKENS,
+    AgentState, CommandItem, DiffSnapshot, InputMode, JobKind, JobRecord, JobRequest, JobStatus,
+    LogBuffer, LogLevel, LogTimestamps, PermissionProfile, PlanItem, SteerRecord, TabRequest,
+    UiAccent, UiDensity, UiTheme, MAX_CONVERSATION_TOKENS,
 };
 use crate::test_harness::run_tests;
-use crate::voice::VoiceCommand;
+use crate::timefmt;
+use crate::ui::chart;
+use crate::voice::{PttKey, VoiceActivation, VoiceCommand};
 use serde::Deserialize;
 use serde_json::Value;
 use std::sync::mpsc::Sender;
@@ -37,6 +55,18 @@ pub fn handle_command(
     let cmd_raw = raw.trim();
     let cmd = normalize_command_prefix(cmd_raw);
 
+    // Built-in names are refused when loading, so checking these first only
+    // keeps prefix matches like `/attach` from taking `/attach-logs`.
+    if let Some((custom, args)) = custom_commands::find(&state.ui.custom_commands, &cmd)
+        .map(|(custom, args)| (custom.clone(), args.to_string()))
+    {
+        run_custom_command(state, &custom, &args);
+        return;
+    }
+    if cmd.starts_with("/help ") {
+        help_page(state, &cmd);
+        return;
+    }
     if cmd.starts_with("/model ") {
         set_model(state, &cmd, agent);
         return;
@@ -45,10 +75,22 @@ pub fn handle_command(
         run_test(state, &cmd);
         return;
     }
+    if cmd == "/testgen candidates" || cmd.starts_with("/testgen candidates ") {
+        open_test_candidates(state, &cmd, agent);
+        return;
+    }
     if cmd.starts_with("/steer ") {
         set_steer(state, &cmd, steer_tx);
         return;
     }
+    if cmd.starts_with("/approvals") {
+        approvals_command(state, &cmd);
+        return;
+    }
+    if cmd == "/budget" || cmd.starts_with("/budget ") {
+        budget_command(state, &cmd);
+        return;
+    }
     if cmd.starts_with("/profile ") {
         set_profile(state, &cmd);
         return;
@@ -65,6 +107,10 @@ pub fn handle_command(
         set_type(state, &cmd);
         return;
     }
+    if cmd.starts_with("/timestamps ") {
+        set_timestamps(state, &cmd);
+        return;
+    }
     if cmd.starts_with("/copy ") {
         copy_output(state, &cmd);
         return;
@@ -93,6 +139,18 @@ pub fn handle_command(
         set_plan_mode(state, &cmd);
         return;
     }
+    if cmd.starts_with("/plan edit ") {
+        plan_edit(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/plan files ") {
+        plan_files(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/plan drop ") {
+        plan_drop(state, &cmd);
+        return;
+    }
     if cmd.starts_with("/autofix ") {
         set_autofix(state, &cmd);
         return;
@@ -109,6 +167,66 @@ pub fn handle_command(
         open_nv(state, &cmd);
         return;
     }
+    if cmd.starts_with("/detach ") {
+        detach_run(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/attach") {
+        attach_run(state, &cmd);
+        return;
+    }
+    if cmd == "/pr" || cmd.starts_with("/pr ") {
+        pr_command(state, &cmd);
+        return;
+    }
+    if cmd == "/queue" || cmd.starts_with("/queue ") {
+        queue_command(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/open ") {
+        open_file_ref_command(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/onboard") {
+        show_onboarding(state, &cmd);
+        return;
+    }
+    if cmd == "/git" || cmd.starts_with("/git ") || cmd == "/status git" {
+        git_status_command(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/diff ") {
+        diff_baseline_command(state, &cmd);
+        return;
+    }
+    if cmd == "/impact" || cmd.starts_with("/impact ") {
+        impact_command(state, &cmd);
+        return;
+    }
+    if cmd == "/find" || cmd.starts_with("/find ") {
+        find_command(state, &cmd);
+        return;
+    }
+    if cmd == "/sandbox" || cmd.starts_with("/sandbox ") {
+        sandbox_command(state, &cmd);
+        return;
+    }
+    if cmd == "/rebase" || cmd.starts_with("/rebase ") {
+        rebase_command(state, &cmd);
+        return;
+    }
+    if cmd.starts_with("/export") {
+        export_panel_command(state, &cmd);
+        return;
+    }
+    if cmd == "/tab" || cmd == "/tabs" || cmd.starts_with("/tab ") {
+        tab_command(state, &cmd);
+        return;
+    }
+    if cmd == "/voice ptt" || cmd.starts_with("/voice ptt ") || cmd.starts_with("/voice wake") {
+        set_voice_activation(state, &cmd, voice_tx);
+        return;
+    }
 
     match cmd.as_str() {
         "/help" => help(state),
@@ -116,6 +234,12 @@ pub fn handle_command(
         "/voice" => voice_status(state),
         "/voice on" => voice_on(state, voice_tx),
         "/voice off" => voice_off(state, voice_tx),
+        "/voice continuous" => set_voice_activation(state, &cmd, voice_tx),
+        "/voice pause" => voice_pause(state, voice_tx, true),
+        "/voice resume" => voice_pause(state, voice_tx, false),
+        "/speak" => speak_status(state),
+        "/speak on" => set_speak(state, true),
+        "/speak off" => set_speak(state, false),
 
         "/exit" => exit_app(state),
         "/quit" | "/q" => quit_agent(state),
@@ -124,15 +248,28 @@ pub fn handle_command(
         "/new" => new_conversation(state),
         "/approve" => toggle_auto_approve(state),
         "/status" => show_status(state, agent),
+        "/status system" => show_system_status(state),
+        "/lock" => show_lock(state),
+        "/lock take" => take_lock(state),
+        "/hooks" => show_hooks(state, false),
+        "/hooks trust" => show_hooks(state, true),
         "/account" => show_account(state, agent),
         "/model" => show_model(state, agent),
         "/test" => run_test(state, &cmd),
+        "/coverage" => run_coverage(state),
         "/verify" => show_verify(state),
+        "/testgen" | "/testgen stats" => show_testgen_stats(state),
+        "/testgen maintain" => maintain_generated_tests(state),
+        "/testgen strength" => measure_test_strength(state),
         "/mcp" => show_mcp(state),
         "/providers" => show_providers(state),
         "/undo" => undo_last_change(state),
+        "/review" => open_review(state, false),
+        "/review all" => open_review(state, true),
         "/diff" => show_session_diff(state),
+        "/inspect" => show_working_diff(state),
         "/steer" => show_steer(state),
+        "/continue" => continue_run(state),
         "/compact" => compact_context(state),
         "/usage" => show_usage(state),
         "/metrics" => show_metrics(state),
@@ -140,6 +277,16 @@ pub fn handle_command(
         "/theme" => show_theme(state),
         "/color" => show_color(state),
         "/type" => show_type(state),
+        "/timestamps" => cycle_timestamps(state),
+        "/minimap" => toggle_diff_minimap(state),
+        "/split" => toggle_split_diff(state),
+        "/keys" => show_keys(state),
+        "/reload" => {
+            load_custom_commands(state);
+            state.ui.config_reload_requested = true;
+        }
+        "/commands" => show_custom_commands(state),
+        "/open" => open_latest_file_ref(state),
         "/copy" => copy_output(state, &cmd),
         "/rename" | "/session" => show_session(state),
         "/jobs" => show_jobs(state),
@@ -149,6 +296,7 @@ pub fn handle_command(
         "/nv" => open_nv(state, &cmd),
         "/plan" => show_plan(state),
         "/plan clear" => plan_clear(state),
+        "/plan approve" => plan_approve(state),
         "/plan mode" => show_plan_mode(state),
 
         "" => {}
@@ -163,109 +311,224 @@ pub fn copy_latest_output(state: &mut AgentState) {
     copy_output(state, "/copy");
 }
 
+/// Opens the most recent `path:line` reference in the transcript.
+pub fn open_latest_file_ref(state: &mut AgentState) {
+    let texts: Vec<String> = state.logs.iter().map(|line| line.text.clone()).collect();
+    match file_refs::latest_ref(texts.iter().map(String::as_str)) {
+        Some(target) => open_file_ref(state, &target),
+        None => log(
+            state,
+            LogLevel::Warn,
+            "No file:line reference in the output.",
+        ),
+    }
+}
+
+fn open_file_ref_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/open").unwrap_or("").trim();
+    match FileRef::parse(arg) {
+        Some(target) => edit_file_ref(state, &target),
+        None => log(state, LogLevel::Warn, "Usage: /open [path[:line[:col]]]"),
+    }
+}
+
+/// Opens the file the user is looking at in the editor: the file selected
+/// in the review, else the first reference in view when the panel is
+/// scrolled back or shows a diff, else the latest one in the log. False
+/// when there is none.
+pub fn edit_current_file(state: &mut AgentState) -> bool {
+    let target = if let Some(review) = state.ui.review.as_ref() {
+        let change = &review.selected().change;
+        let diff =
+            crate::ui::diff::Diff::from_texts(change.target.clone(), &change.before, &change.after);
+        let line = diff
+            .lines
+            .iter()
+            .filter(|l| {
+                matches!(
+                    l.kind,
+                    crate::ui::diff::DiffLineKind::Added | crate::ui::diff::DiffLineKind::Removed
+                )
+            })
+            .find_map(|l| l.new_lineno.or(l.old_lineno))
+            .filter(|_| !diff.notebook);
+        Some(FileRef {
+            path: change.target.clone(),
+            line: line.unwrap_or(1),
+            column: None,
+        })
+    } else if state.ui.diff_active || !state.ui.follow_tail {
+        let in_view = state
+            .ui
+            .exec_refs
+            .borrow()
+            .iter()
+            .min_by_key(|hit| (hit.row, hit.start))
+            .map(|hit| hit.target.clone());
+        in_view.or_else(|| {
+            state.ui.diff_snapshot.first().map(|snap| FileRef {
+                path: snap.target.clone(),
+                line: 1,
+                column: None,
+            })
+        })
+    } else {
+        let texts: Vec<String> = state.logs.iter().map(|line| line.text.clone()).collect();
+        file_refs::latest_ref(texts.iter().map(String::as_str))
+    };
+    match target {
+        Some(target) => {
+            edit_file_ref(state, &target);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Hands the reference to the `[editor]` command when one is configured,
+/// else to `$VISUAL`/`$EDITOR` with the TUI suspended; without either it
+/// shows an excerpt.
+pub fn edit_file_ref(state: &mut AgentState, target: &FileRef) {
+    let configured = crate::agent::editor_config().is_some_and(|e| !e.command.trim().is_empty());
+    let editor = file_refs::terminal_editor().filter(|_| !configured);
+    let (Some(editor), Some(path)) = (editor, target.resolve(&state.repo_root)) else {
+        open_file_ref(state, target);
+        return;
+    };
+    state.ui.editor_request = Some(file_refs::terminal_editor_command(&editor, &path, target));
+}
+
+/// Shows an excerpt around the reference, or copies/runs the configured
+/// `[editor]` command for it.
+pub fn open_file_ref(state: &mut AgentState, target: &FileRef) {
+    let Some(path) = target.resolve(&state.repo_root) else {
+        log(
+            state,
+            LogLevel::Warn,
+            format!("{} is not a file in this repo.", target.path),
+        );
+        return;
+    };
+
+    if let Some(editor) = crate::agent::editor_config().filter(|e| !e.command.trim().is_empty()) {
+        let command = file_refs::editor_command(&editor.command, &path, target);
+        if editor.run {
+            match platform::shell_command(&command)
+                .current_dir(&state.repo_root)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(_) => log(state, LogLevel::Success, format!("Ran: {command}")),
+                Err(err) => log(state, LogLevel::Error, format!("{command}: {err}")),
+            }
+        } else {
+            match clipboard::copy_text_to_clipboard(&command) {
+                Ok(backend) => log(
+                    state,
+                    LogLevel::Success,
+                    format!("Copied `{command}` via {}.", backend.label()),
+                ),
+                Err(err) => log(state, LogLevel::Error, format!("Copy failed: {err}")),
+            }
+        }
+        return;
+    }
+
+    match file_refs::excerpt(&path, target.line, file_refs::EXCERPT_RADIUS) {
+        Ok(lines) => {
+            log(state, LogLevel::Info, format!("● {}", target.label()));
+            for line in lines {
+                log(state, LogLevel::Info, format!("  {line}"));
+            }
+        }
+        Err(err) => log(state, LogLevel::Error, err),
+    }
+    state.ui.follow_tail = true;
+    state.ui.exec_scroll = usize::MAX;
+}
+
+fn export_panel_command(state: &mut AgentState, cmd: &str) {
+    let usage = "Usage: /export panel [path] [--ansi]";
+    let mut args = cmd.strip_prefix("/export").unwrap_or("").split_whitespace();
+    if args.next() != Some("panel") {
+        log(state, LogLevel::Warn, usage);
+        return;
+    }
+    let (mut path, mut ansi) = (None, false);
+    for arg in args {
+        match arg {
+            "--ansi" => ansi = true,
+            _ if path.is_none() => path = Some(state.repo_root.join(arg)),
+            _ => {
+                log(state, LogLevel::Warn, usage);
+                return;
+            }
+        }
+    }
+    let path = path.unwrap_or_else(|| crate::ui::export::default_path(&state.repo_root));
+    save_panel(state, &path, ansi);
+}
+
+/// Writes the focused panel to a timestamped file under `.osmogrep/exports`.
+pub fn export_focused_panel(state: &mut AgentState) {
+    let path = crate::ui::export::default_path(&state.repo_root);
+    save_panel(state, &path, false);
+}
+
+fn save_panel(state: &mut AgentState, path: &Path, ansi: bool) {
+    match crate::ui::export::export_panel(state, path, ansi) {
+        Ok(count) => {
+            let shown = path.strip_prefix(&state.repo_root).unwrap_or(path);
+            log(
+                state,
+                LogLevel::Success,
+                format!("Exported {count} panel lines to {}.", shown.display()),
+            );
+        }
+        Err(err) => log(state, LogLevel::Error, format!("Export failed: {err}")),
+    }
+}
+
 fn help(state: &mut AgentState) {
-    use LogLevel::Info;
-
-    log(state, Info, "Available commands:");
-    log(state, Info, "  /help        Show this help");
-    log(state, Info, "  /clear       Clear logs");
-    log(state, Info, "  /key         Set OpenAI API key");
-    log(state, Info, "  /voice       Show voice status");
-    log(state, Info, "  /voice on    Start voice input");
-    log(state, Info, "  /voice off   Stop voice input");
-    log(
-        state,
-        Info,
-        "  /status      Show session, run, model, and repo status",
-    );
-    log(
-        state,
-        Info,
-        "  /account     Show provider/account configuration",
-    );
-    log(state, Info, "  /model       Show active provider/model");
-    log(state, Info, "  /model <provider> <model> [base_url]");
-    log(state, Info, "  /usage       Show token and context usage");
-    log(state, Info, "  /theme       Show or set theme (dark/light)");
-    log(state, Info, "  /color       Show or set accent color");
-    log(state, Info, "  /type        Show or set UI density");
-    log(state, Info, "  /copy [N]    Copy latest assistant response");
-    log(state, Info, "  /copy all    Copy the visible transcript");
-    log(state, Info, "  /rename      Rename current session");
-    log(
-        state,
-        Info,
-        "  /verify      Show verification ledger status",
-    );
-    log(
-        state,
-        Info,
-        "  /mcp         Show MCP status and configured servers",
-    );
-    log(state, Info, "  /providers   Show available model providers");
-    log(
-        state,
-        Info,
-        "  /undo        Revert the last agent file change",
-    );
-    log(
-        state,
-        Info,
-        "  /diff        Show all file changes this session",
-    );
-    log(state, Info, "  /compact     Compress conversation context");
-    log(state, Info, "  /metrics     Show usage and queue metrics");
-    log(
-        state,
-        Info,
-        "  /profile     Show permission profile (read-only/workspace-auto/full-access)",
-    );
-    log(state, Info, "  /profile <name>  Set permission profile");
-    log(
-        state,
-        Info,
-        "  /approve     Toggle dangerous tool auto-approve",
-    );
-    log(state, Info, "  /new         Start a fresh conversation");
-    log(state, Info, "  /steer       Show current steer instruction");
-    log(
-        state,
-        Info,
-        "  /steer <txt> Set persistent steer instruction",
-    );
-    log(
-        state,
-        Info,
-        "  /steer now <txt> Interrupt current run and relaunch with steer",
-    );
-    log(state, Info, "  /steer clear Remove steer instruction");
-    log(
-        state,
-        Info,
-        "  /plan mode   Show or set plan-only mode (on/off)",
-    );
-    log(state, Info, "  /jobs        Show background jobs");
-    log(
-        state,
-        Info,
-        "  /nv [file]   Open Neovim split at repo root with tree view",
-    );
-    log(
-        state,
-        Info,
-        "  /nv toggle   Toggle nvim pane in current tmux window",
-    );
-    log(state, Info, "  /nv help     Show nvim/tmux exit shortcuts");
-    log(state, Info, "  /quit | /q   Stop agent execution");
-    log(state, Info, "  /exit        Exit Osmogrep");
-    log(state, Info, "");
-    log(state, Info, "Anything else is sent to the agent.");
-    log(
-        state,
-        Info,
-        "Testing, planning, review, triage, and subagents are model/harness workflows.",
-    );
-    log(state, Info, "!<cmd> runs a shell command directly.");
+    for line in command_docs::overview() {
+        log(state, LogLevel::Info, line);
+    }
+}
+
+fn help_page(state: &mut AgentState, cmd: &str) {
+    let name = cmd.strip_prefix("/help").unwrap_or("").trim();
+    let custom = custom_commands::find(
+        &state.ui.custom_commands,
+        &format!("/{}", name.trim_start_matches('/')),
+    )
+    .map(|(custom, _)| custom.clone());
+    match (command_docs::find(name), custom) {
+        (Some(doc), _) => {
+            for line in command_docs::page(doc) {
+                log(state, LogLevel::Info, line);
+            }
+        }
+        (None, Some(custom)) => {
+            log(
+                state,
+                LogLevel::Info,
+                format!("{} — {}", custom.name, custom.description),
+            );
+            log(state, LogLevel::Info, format!("Usage: {}", custom.usage()));
+            log(
+                state,
+                LogLevel::Info,
+                format!("Defined in {}", custom.path.display()),
+            );
+        }
+        (None, None) => log(
+            state,
+            LogLevel::Warn,
+            format!("No command named {name}. Type /help for the list."),
+        ),
+    }
 }
 
 fn clear_logs(state: &mut AgentState) {
@@ -412,8 +675,10 @@ fn transcript_text(logs: &LogBuffer) -> String {
 }
 
 fn exit_app(state: &mut AgentState) {
-    log(state, LogLevel::Info, "Exiting Osmogrep.");
-    state.ui.should_exit = true;
+    state.request_exit();
+    if state.ui.should_exit {
+        log(state, LogLevel::Info, "Exiting Osmogrep.");
+    }
 }
 
 fn quit_agent(state: &mut AgentState) {
@@ -441,6 +706,140 @@ fn toggle_auto_approve(state: &mut AgentState) {
     );
 }
 
+/// `/continue` resumes the last failed run from its last model turn, with
+/// the tool calls and outputs it had made.
+fn continue_run(state: &mut AgentState) {
+    state.ui.auto_continued = false;
+    match resume_interrupted(state) {
+        Ok(()) => log(
+            state,
+            LogLevel::Info,
+            "Continuing the failed run from its last model turn.",
+        ),
+        Err(e) => log(state, LogLevel::Warn, e),
+    }
+}
+
+/// Restores a failed run's conversation and queues a prompt to carry on.
+pub fn resume_interrupted(state: &mut AgentState) -> Result<(), String> {
+    let run = state
+        .interrupted
+        .take()
+        .ok_or("No failed run to continue.")?;
+    state.conversation.set_messages(run.messages);
+    state.ui.queued_agent_prompt = Some(format!(
+        "Continue the task from where you stopped; the last attempt failed with: {}",
+        run.error
+    ));
+    Ok(())
+}
+
+/// `/budget` shows the limits runs stop at; `/budget tools=40 tokens=200k
+/// time=10m` replaces `[run_budget]` for the session and `/budget reset`
+/// goes back to it.
+fn budget_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/budget").unwrap_or("").trim();
+    let configured = agent::run_budget_config();
+    match arg {
+        "" => {}
+        "reset" => state.run_budget = None,
+        _ => match state.run_budget.unwrap_or(configured).with_overrides(arg) {
+            Ok(budget) => state.run_budget = Some(budget),
+            Err(e) => {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    format!("{e}. Usage: /budget [tools=N] [tokens=N] [time=10m] | reset"),
+                );
+                return;
+            }
+        },
+    }
+    let (budget, source) = match state.run_budget {
+        Some(budget) => (budget, "set with /budget"),
+        None => (configured, "from [run_budget]"),
+    };
+    log(
+        state,
+        LogLevel::Info,
+        format!("Run budget: {} ({source}).", budget.describe()),
+    );
+}
+
+/// `/approvals` lists the repo's remembered `[a]lways` answers;
+/// `/approvals revoke <n>` drops one.
+fn approvals_command(state: &mut AgentState, cmd: &str) {
+    let root = state.edit_root().to_path_buf();
+    let arg = cmd.strip_prefix("/approvals").unwrap_or("").trim();
+    if let Some(n) = arg.strip_prefix("revoke") {
+        let revoked = n
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| "Usage: /approvals revoke <n>".to_string())
+            .and_then(|n| approvals::revoke(&root, n.saturating_sub(1)));
+        match revoked {
+            Ok(approval) => log(
+                state,
+                LogLevel::Success,
+                format!("Revoked `{}`; it prompts again.", approval.describe()),
+            ),
+            Err(e) => log(state, LogLevel::Warn, e),
+        }
+        return;
+    }
+    if !arg.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /approvals [revoke <n>]");
+        return;
+    }
+
+    let entries = Approvals::load(&root).entries;
+    if entries.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            format!("No remembered approvals in {}.", approvals::FILE),
+        );
+        return;
+    }
+    log(
+        state,
+        LogLevel::Info,
+        format!("Always allowed in this repo ({}):", approvals::FILE),
+    );
+    for (i, entry) in entries.iter().enumerate() {
+        let added = entry.added_at.get(..10).unwrap_or(&entry.added_at);
+        log(
+            state,
+            LogLevel::Info,
+            format!("  {}. {}  (added {added})", i + 1, entry.describe()),
+        );
+    }
+}
+
+pub fn toggle_split_diff(state: &mut AgentState) {
+    state.ui.split_diff = !state.ui.split_diff;
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "Split diff pane: {}",
+            if state.ui.split_diff { "on" } else { "off" }
+        ),
+    );
+}
+
+pub fn toggle_diff_minimap(state: &mut AgentState) {
+    state.ui.diff_minimap = !state.ui.diff_minimap;
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "Diff minimap: {}",
+            if state.ui.diff_minimap { "on" } else { "off" }
+        ),
+    );
+}
+
 fn show_status(state: &mut AgentState, agent: Option<&mut Agent>) {
     let model = agent
         .map(|agent| {
@@ -450,7 +849,7 @@ fn show_status(state: &mut AgentState, agent: Option<&mut Agent>) {
         .unwrap_or_else(|| "unavailable".to_string());
     let session = state.session_name.as_deref().unwrap_or("untitled session");
     let branch = current_git_branch(&state.repo_root);
-    let uptime = state.started_at.elapsed().as_secs();
+    let uptime = timefmt::duration(state.started_at.elapsed());
     let jobs_active = state
         .jobs
         .iter()
@@ -461,7 +860,7 @@ fn show_status(state: &mut AgentState, agent: Option<&mut Agent>) {
         state,
         LogLevel::Info,
         format!(
-            "Status: session=\"{}\" run={} model={} repo={} branch={} profile={} approvals={} jobs_active={} uptime={}s",
+            "Status: session=\"{}\" run={} model={} repo={} branch={} diff=\"{}\" profile={} approvals={} jobs_active={} uptime={}",
             session,
             if state.ui.agent_running {
                 state.ui.run_phase.as_str()
@@ -471,6 +870,7 @@ fn show_status(state: &mut AgentState, agent: Option<&mut Agent>) {
             model,
             state.repo_root.display(),
             branch.unwrap_or_else(|| "unknown".to_string()),
+            working_diff::resolved_baseline(&state.repo_root).label(),
             state.permission_profile.as_str(),
             if state.ui.auto_approve { "auto" } else { "ask" },
             jobs_active,
@@ -479,19 +879,181 @@ fn show_status(state: &mut AgentState, agent: Option<&mut Agent>) {
     );
 }
 
-fn show_account(state: &mut AgentState, agent: Option<&mut Agent>) {
-    let Some(agent) = agent else {
-        log(state, LogLevel::Warn, "Agent unavailable.");
+fn show_system_status(state: &mut AgentState) {
+    let hosts = http_policy::host_stats();
+    if hosts.is_empty() {
+        log(state, LogLevel::Info, "HTTP: no outbound requests yet.");
         return;
-    };
-    let cfg = agent.model_config();
-    let key_status = agent
-        .api_key()
-        .as_deref()
-        .map(mask_secret)
-        .unwrap_or_else(|| "missing".to_string());
-    log(
-        state,
+    }
+
+    log(state, LogLevel::Info, "HTTP (per host):");
+    for (host, stats) in hosts {
+        let level = if stats.breaker_open() {
+            LogLevel::Warn
+        } else {
+            LogLevel::Info
+        };
+        log(
+            state,
+            level,
+            format!(
+                "  {host}: requests={} retries={} failures={} breaker_trips={} breaker={}",
+                stats.requests,
+                stats.retries,
+                stats.failures,
+                stats.breaker_trips,
+                if stats.breaker_open() {
+                    "open"
+                } else {
+                    "closed"
+                }
+            ),
+        );
+    }
+}
+
+fn tab_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd
+        .strip_prefix("/tabs")
+        .or_else(|| cmd.strip_prefix("/tab"))
+        .unwrap_or("")
+        .trim();
+    match arg {
+        "" => {
+            let lines = state
+                .ui
+                .tabs
+                .iter()
+                .enumerate()
+                .map(|(i, tab)| {
+                    let current = if i == state.ui.active_tab { "*" } else { " " };
+                    let running = if tab.running { "  (running)" } else { "" };
+                    format!("{current}{} {}{running}", i + 1, tab.title)
+                })
+                .collect::<Vec<_>>();
+            log(
+                state,
+                LogLevel::Info,
+                "Tabs (Ctrl+1..9 or Alt+1..9 to switch):",
+            );
+            for line in lines {
+                log(state, LogLevel::Info, line);
+            }
+        }
+        "close" => state.ui.tab_request = Some(TabRequest::Close),
+        "new" => state.ui.tab_request = Some(TabRequest::Open(None)),
+        _ => {
+            if let Some(path) = arg.strip_prefix("new ") {
+                let path = images::expand_home(path.trim());
+                let path = state.repo_root.join(path);
+                match path.canonicalize() {
+                    Ok(dir) if dir.is_dir() => {
+                        state.ui.tab_request = Some(TabRequest::Open(Some(dir)));
+                    }
+                    _ => log(
+                        state,
+                        LogLevel::Warn,
+                        format!("Not a directory: {}", path.display()),
+                    ),
+                }
+                return;
+            }
+            match arg.parse::<usize>() {
+                Ok(n) if (1..=state.ui.tabs.len()).contains(&n) => {
+                    state.ui.tab_request = Some(TabRequest::Switch(n - 1));
+                }
+                _ => log(
+                    state,
+                    LogLevel::Warn,
+                    "Usage: /tab [new [path] | close | <n>]",
+                ),
+            }
+        }
+    }
+}
+
+fn show_lock(state: &mut AgentState) {
+    if let Some(lock) = state.repo_lock.as_ref() {
+        let owner = lock.owner().describe();
+        log(
+            state,
+            LogLevel::Info,
+            format!("Repo lock: held by this instance, {owner}"),
+        );
+        return;
+    }
+
+    match instance::read_owner(&state.repo_root) {
+        Some(owner) => log(
+            state,
+            LogLevel::Warn,
+            format!(
+                "Repo lock: held by {}. This instance ({}) is read-only.",
+                owner.describe(),
+                instance::instance_id()
+            ),
+        ),
+        None => log(
+            state,
+            LogLevel::Info,
+            "Repo lock: free. Use /lock take to claim it.",
+        ),
+    }
+}
+
+fn show_hooks(state: &mut AgentState, trust: bool) {
+    match crate::lifecycle_hooks::report(&state.repo_root, trust) {
+        Ok(lines) => {
+            for line in lines {
+                log(state, LogLevel::Info, line);
+            }
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Hooks: {e}")),
+    }
+}
+
+fn take_lock(state: &mut AgentState) {
+    if state.repo_lock.is_some() {
+        log(
+            state,
+            LogLevel::Info,
+            "Repo lock already held by this instance.",
+        );
+        return;
+    }
+
+    match instance::take(&state.repo_root) {
+        Ok(lock) => {
+            state.repo_lock = Some(lock);
+            instance::set_secondary(false);
+            log(
+                state,
+                LogLevel::Success,
+                "Repo lock taken. Use /profile to enable writes.",
+            );
+            let _ = persistence::save(state);
+        }
+        Err(e) => log(
+            state,
+            LogLevel::Error,
+            format!("Could not take repo lock: {e}"),
+        ),
+    }
+}
+
+fn show_account(state: &mut AgentState, agent: Option<&mut Agent>) {
+    let Some(agent) = agent else {
+        log(state, LogLevel::Warn, "Agent unavailable.");
+        return;
+    };
+    let cfg = agent.model_config();
+    let key_status = agent
+        .api_key()
+        .as_deref()
+        .map(mask_secret)
+        .unwrap_or_else(|| "missing".to_string());
+    log(
+        state,
         LogLevel::Info,
         format!(
             "Account: provider={} model={} api_key={} env={} base_url={}",
@@ -580,7 +1142,12 @@ fn run_test(state: &mut AgentState, cmd: &str) {
         ),
     );
 
-    match run_tests(&state.repo_root, target) {
+    let result = if target == Some("--changed") {
+        crate::test_harness::run_affected_tests_cancellable(&state.repo_root, None, || false)
+    } else {
+        run_tests(&state.repo_root, target)
+    };
+    match result {
         Ok(run) => {
             log(
                 state,
@@ -590,15 +1157,34 @@ fn run_test(state: &mut AgentState, cmd: &str) {
                     LogLevel::Error
                 },
                 format!(
-                    "Test run [{}] exit={} passed={} failed={} duration={}ms timed_out={}",
+                    "Test run [{}] exit={} passed={} failed={} duration={} timed_out={}",
                     run.framework,
                     run.exit_code,
                     run.passed,
                     run.failed,
-                    run.duration_ms,
+                    timefmt::duration_ms(run.duration_ms),
                     run.timed_out
                 ),
             );
+            if !run.failures.is_empty() {
+                log(
+                    state,
+                    LogLevel::Error,
+                    format!("Failing: {}", run.failures.join(", ")),
+                );
+            }
+            if !run.slowest.is_empty() {
+                let slowest: Vec<String> = run
+                    .slowest
+                    .iter()
+                    .map(|t| format!("{} ({})", t.name, timefmt::duration_ms(t.duration_ms)))
+                    .collect();
+                log(
+                    state,
+                    LogLevel::Info,
+                    format!("Slowest: {}", slowest.join(", ")),
+                );
+            }
             for line in run
                 .output
                 .lines()
@@ -617,6 +1203,44 @@ fn run_test(state: &mut AgentState, cmd: &str) {
     }
 }
 
+fn run_coverage(state: &mut AgentState) {
+    log(state, LogLevel::Info, "Collecting coverage…");
+    match crate::coverage::collect(&state.repo_root) {
+        Ok((command, coverage)) => {
+            log(
+                state,
+                LogLevel::Success,
+                format!("Coverage [{command}]: {}", coverage.total()),
+            );
+            working_diff::invalidate();
+            let changed = working_diff::current(&state.repo_root)
+                .as_ref()
+                .and_then(working_diff::changed_line_coverage);
+            if let Some(changed) = changed {
+                log(
+                    state,
+                    LogLevel::Info,
+                    format!("Changed lines covered: {changed} (see /inspect)"),
+                );
+            }
+
+            // The log panel is a ratatui buffer, so charts here are always blocks.
+            let least = coverage
+                .least_covered(5)
+                .into_iter()
+                .map(|(path, lines)| (path, lines.percent(), lines.to_string()))
+                .collect::<Vec<_>>();
+            if !least.is_empty() {
+                log(state, LogLevel::Info, "Least covered files:");
+            }
+            for row in chart::captioned_bar_rows(&least, 100.0, 20) {
+                log(state, LogLevel::Info, format!("  {row}"));
+            }
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Coverage failed: {e}")),
+    }
+}
+
 fn show_mcp(state: &mut AgentState) {
     let enabled = crate::mcp::is_enabled();
     let servers = crate::mcp::list_servers();
@@ -652,7 +1276,7 @@ fn undo_last_change(state: &mut AgentState) {
         return;
     };
 
-    if let Err(e) = fs::write(&last.target, &last.before) {
+    if let Err(e) = fs::write(state.edit_root().join(&last.target), &last.before) {
         log(
             state,
             LogLevel::Error,
@@ -680,449 +1304,1842 @@ fn undo_last_change(state: &mut AgentState) {
     let _ = persistence::save(state);
 }
 
-fn show_session_diff(state: &mut AgentState) {
-    if state.session_changes.is_empty() {
-        log(state, LogLevel::Info, "No session changes to show.");
+/// Opens the review queue over the latest run's changes, or the whole
+/// session's with `all`.
+pub fn open_review(state: &mut AgentState, whole_session: bool) {
+    if state.ui.agent_running {
+        log(
+            state,
+            LogLevel::Warn,
+            "Wait for the agent run to finish before reviewing its changes.",
+        );
         return;
     }
-
-    state.ui.diff_active = true;
-    state.ui.diff_snapshot = state.session_changes.clone();
+    let from = if whole_session {
+        0
+    } else {
+        state.ui.run_changes_from.min(state.session_changes.len())
+    };
+    let files = crate::state::session_diff(&state.session_changes[from..], state.edit_root());
+    let Some(queue) = ReviewQueue::new(files) else {
+        log(state, LogLevel::Info, "No changes to review.");
+        return;
+    };
     log(
         state,
         LogLevel::Info,
         format!(
-            "Showing {} session change(s).",
-            state.ui.diff_snapshot.len()
+            "Reviewing {} changed file(s). Rejected files go back to how they were before the {}.",
+            queue.items.len(),
+            if whole_session { "session" } else { "run" }
         ),
     );
+    state.ui.diff_active = false;
+    state.ui.review = Some(queue);
 }
 
-fn show_verify(state: &mut AgentState) {
-    let status = crate::verification::latest_status(&state.repo_root);
-    let level = match status.status.as_str() {
-        "passed" => LogLevel::Success,
-        "failed" => LogLevel::Error,
-        "stale" => LogLevel::Warn,
-        _ => LogLevel::Info,
+/// Shows the diff of the file selected in the review queue.
+pub fn show_review_diff(state: &mut AgentState) {
+    let Some(review) = state.ui.review.as_ref() else {
+        return;
+    };
+    state.ui.diff_snapshot = vec![review.selected().change.clone()];
+    state.ui.diff_active = true;
+}
+
+/// Restores the rejected files and closes the review; refused while files
+/// are undecided.
+pub fn confirm_review(state: &mut AgentState) {
+    let Some(review) = state.ui.review.take() else {
+        return;
     };
+    let undecided = review.undecided();
+    if undecided > 0 {
+        log(
+            state,
+            LogLevel::Warn,
+            format!("{undecided} file(s) still need [a]ccept or [r]eject."),
+        );
+        state.ui.review = Some(review);
+        return;
+    }
+    let (restored, failed) = review.apply(state.edit_root());
+    let kept = review.items.len() - restored.len() - failed.len();
+    for snap in restored.iter() {
+        state.session_changes.push(snap.clone());
+        state.undo_stack.push(snap.clone());
+    }
+    working_diff::invalidate();
+    state.ui.diff_active = false;
+    for failure in failed {
+        log(
+            state,
+            LogLevel::Error,
+            format!("Restore failed for {failure}"),
+        );
+    }
     log(
         state,
-        level,
+        LogLevel::Success,
         format!(
-            "Verification status: {}{}",
-            status.status,
-            if status.needs_verification {
-                " (needs fresh passing evidence)"
-            } else {
-                ""
-            }
+            "Review done: kept {kept} file(s), restored {}.",
+            restored.len()
         ),
     );
+    let _ = persistence::save(state);
+}
 
-    if let Some(ev) = status.evidence.as_ref() {
+fn rebase_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/rebase").unwrap_or("").trim();
+    let root = state.repo_root.clone();
+    if state.ui.agent_running && !arg.is_empty() {
         log(
             state,
-            LogLevel::Info,
-            format!(
-                "Last evidence: {} [{}:{}:{}] exit={}",
-                ev.canonical_command, ev.kind, ev.scope, ev.status, ev.exit_code
-            ),
+            LogLevel::Warn,
+            "Wait for the agent run to finish before moving the rebase on.",
         );
-    } else {
-        log(state, LogLevel::Info, "Last evidence: none recorded");
+        return;
+    }
+    match arg {
+        "" => {
+            if !rebase::in_progress(&root) {
+                log(
+                    state,
+                    LogLevel::Info,
+                    "No rebase in progress. Usage: /rebase <base>|continue|abort",
+                );
+                return;
+            }
+            let unresolved = rebase::unresolved(&root);
+            let text = if unresolved.is_empty() {
+                "Rebase stopped; conflicts are resolved. Run /rebase continue.".to_string()
+            } else {
+                format!(
+                    "Rebase stopped; conflict markers remain in {}.",
+                    unresolved.join(", ")
+                )
+            };
+            log(state, LogLevel::Info, text);
+        }
+        "continue" => {
+            let outcome = rebase::continue_rebase(&root);
+            report_rebase_step(state, outcome);
+        }
+        "abort" => match rebase::abort(&root) {
+            Ok(()) => {
+                state.rebase = None;
+                log(
+                    state,
+                    LogLevel::Success,
+                    "Rebase aborted; the branch is unchanged.",
+                );
+            }
+            Err(e) => log(state, LogLevel::Error, format!("Rebase abort failed: {e}")),
+        },
+        base => {
+            if state.sandbox.is_some() {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    "/rebase rewrites the checkout's branch; promote or discard the sandbox and turn it off first.",
+                );
+                return;
+            }
+            let outcome = rebase::start(&root, base);
+            if outcome.is_ok() {
+                state.rebase = Some(base.to_string());
+            }
+            report_rebase_step(state, outcome);
+        }
     }
+}
 
-    if !status.verifiable_changed_paths.is_empty() {
+fn report_rebase_step(state: &mut AgentState, outcome: Result<RebaseOutcome, String>) {
+    match outcome {
+        Ok(RebaseOutcome::Finished) => {
+            let base = state.rebase.take().unwrap_or_else(|| "the base".into());
+            log(state, LogLevel::Success, format!("Rebased onto {base}."));
+        }
+        Ok(RebaseOutcome::Stopped { commit, conflicts }) => {
+            log(
+                state,
+                LogLevel::Warn,
+                format!(
+                    "Rebase stopped at {commit}: {} conflicted file(s).",
+                    conflicts.len()
+                ),
+            );
+            for conflict in &conflicts {
+                let text = format!("  {} ({})", conflict.path, conflict.kind);
+                log(state, LogLevel::Warn, text);
+            }
+            let base = state.rebase.clone().unwrap_or_else(|| "the base".into());
+            state.ui.queued_agent_prompt =
+                Some(rebase::resolution_prompt(&base, &commit, &conflicts));
+            log(
+                state,
+                LogLevel::Info,
+                "Queued conflict resolution; every edit asks for approval and the rebase continues once no markers remain.",
+            );
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Rebase: {e}")),
+    }
+}
+
+/// After an agent run during `/rebase`: continues the rebase when the run
+/// left no conflict markers behind.
+pub fn continue_rebase_after_run(state: &mut AgentState) {
+    if state.rebase.is_none() {
+        return;
+    }
+    let root = state.repo_root.clone();
+    if !rebase::in_progress(&root) {
+        state.rebase = None;
+        return;
+    }
+    let unresolved = rebase::unresolved(&root);
+    if !unresolved.is_empty() {
         log(
             state,
             LogLevel::Warn,
             format!(
-                "Changed code/config paths since evidence: {}",
-                format_path_list(&status.verifiable_changed_paths, 8)
-            ),
-        );
-    } else if !status.changed_paths.is_empty() {
-        log(
-            state,
-            LogLevel::Info,
-            format!(
-                "Changed paths since evidence are doc/data-only: {}",
-                format_path_list(&status.changed_paths, 8)
+                "Conflict markers remain in {}. Fix them and run /rebase continue, or /rebase abort.",
+                unresolved.join(", ")
             ),
         );
+        return;
     }
+    let outcome = rebase::continue_rebase(&root);
+    report_rebase_step(state, outcome);
 }
 
-fn format_path_list(paths: &[String], limit: usize) -> String {
-    let mut shown = paths
-        .iter()
-        .take(limit)
-        .map(|path| format!("`{path}`"))
-        .collect::<Vec<_>>();
-    let remaining = paths.len().saturating_sub(limit);
-    if remaining > 0 {
-        shown.push(format!("+{remaining} more"));
+fn sandbox_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/sandbox").unwrap_or("").trim();
+    if state.ui.agent_running && matches!(arg, "on" | "off" | "promote" | "discard") {
+        log(
+            state,
+            LogLevel::Warn,
+            "Wait for the agent run to finish before switching the sandbox.",
+        );
+        return;
     }
-    shown.join(", ")
-}
-
-fn show_steer(state: &mut AgentState) {
-    match state.steer.as_deref() {
-        Some(s) if !s.trim().is_empty() => {
-            log(state, LogLevel::Info, format!("Steer: {}", s.trim()));
-        }
-        _ => log(state, LogLevel::Info, "Steer: (not set)"),
-    }
-}
-
-fn set_steer(state: &mut AgentState, cmd: &str, steer_tx: Option<&Sender<String>>) {
-    let value = cmd.strip_prefix("/steer").map(str::trim).unwrap_or("");
-    if let Some(now_text) = value.strip_prefix("now ").map(str::trim) {
-        if now_text.is_empty() {
-            log(state, LogLevel::Warn, "Usage: /steer now <instruction>");
-            return;
-        }
-        state.steer = Some(now_text.to_string());
-        if state.ui.agent_running {
-            if let Some(tx) = steer_tx {
-                if tx.send(now_text.to_string()).is_ok() {
-                    log(
-                        state,
-                        LogLevel::Info,
-                        "Steer-now injected into running agent.",
-                    );
-                } else {
-                    state.ui.queued_agent_prompt = Some(now_text.to_string());
-                    log(
-                        state,
-                        LogLevel::Warn,
-                        "Live steer channel unavailable. Queued follow-up prompt instead.",
+    match arg {
+        "" => match state.sandbox.as_ref() {
+            Some(sandbox) => {
+                let text = format!(
+                    "Sandbox on: {} ({})",
+                    sandbox.path.display(),
+                    sandbox.branch
+                );
+                log(state, LogLevel::Info, text);
+            }
+            None => log(
+                state,
+                LogLevel::Info,
+                "Sandbox off: agent runs edit the checkout.",
+            ),
+        },
+        "on" => {
+            if state.sandbox.is_some() {
+                log(state, LogLevel::Info, "Sandbox is already on.");
+                return;
+            }
+            match crate::worktree::create_sandbox(&state.repo_root) {
+                Ok(sandbox) => {
+                    let text = format!(
+                        "Sandbox on: agent runs now edit {}. Review with /sandbox diff, then /sandbox promote.",
+                        sandbox.path.display()
                     );
+                    state.sandbox = Some(sandbox);
+                    state.ui.sandbox_changes_from = state.session_changes.len();
+                    log(state, LogLevel::Success, text);
                 }
-            } else {
-                state.ui.queued_agent_prompt = Some(now_text.to_string());
+                Err(e) => log(state, LogLevel::Error, format!("Sandbox failed: {e}")),
+            }
+        }
+        "off" => {
+            let Some(sandbox) = state.sandbox.as_ref() else {
+                log(state, LogLevel::Info, "Sandbox is already off.");
+                return;
+            };
+            let pending = sandbox.changes().map(|c| c.len()).unwrap_or(0);
+            if pending > 0 {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    format!(
+                        "Sandbox has {pending} unpromoted file(s). Run /sandbox promote or /sandbox discard first."
+                    ),
+                );
+                return;
+            }
+            if let Err(e) = sandbox.remove(&state.repo_root) {
                 log(
                     state,
                     LogLevel::Warn,
-                    "Live steer channel unavailable. Queued follow-up prompt instead.",
+                    format!("Sandbox worktree not removed: {e}"),
                 );
             }
-        } else {
-            state.ui.queued_agent_prompt = Some(now_text.to_string());
+            state.sandbox = None;
             log(
                 state,
-                LogLevel::Info,
-                "Steer-now queued for immediate launch.",
+                LogLevel::Success,
+                "Sandbox off: agent runs edit the checkout.",
             );
         }
-        let _ = persistence::save(state);
-        return;
+        "diff" => {
+            let Some(sandbox) = state.sandbox.as_ref() else {
+                log(state, LogLevel::Warn, "Sandbox is off. Use /sandbox on");
+                return;
+            };
+            match sandbox.changes() {
+                Ok(changes) if changes.is_empty() => {
+                    log(state, LogLevel::Info, "No sandbox changes to promote.")
+                }
+                Ok(changes) => {
+                    state.ui.diff_active = true;
+                    state.ui.diff_snapshot = changes
+                        .into_iter()
+                        .map(|c| DiffSnapshot {
+                            tool: "sandbox".into(),
+                            target: c.path,
+                            before: c.before,
+                            after: c.after,
+                            ..DiffSnapshot::default()
+                        })
+                        .collect();
+                    let text = format!(
+                        "Showing {} sandbox change(s). /sandbox promote applies them.",
+                        state.ui.diff_snapshot.len()
+                    );
+                    log(state, LogLevel::Info, text);
+                }
+                Err(e) => log(state, LogLevel::Error, format!("Sandbox diff failed: {e}")),
+            }
+        }
+        "promote" => {
+            let repo_root = state.repo_root.clone();
+            let Some(sandbox) = state.sandbox.as_mut() else {
+                log(state, LogLevel::Warn, "Sandbox is off. Use /sandbox on");
+                return;
+            };
+            match sandbox.promote(&repo_root) {
+                Ok(files) if files.is_empty() => {
+                    log(state, LogLevel::Info, "No sandbox changes to promote.")
+                }
+                Ok(files) => {
+                    working_diff::invalidate();
+                    state.ui.sandbox_changes_from = state.session_changes.len();
+                    log(
+                        state,
+                        LogLevel::Success,
+                        format!("Promoted {} file(s) to the checkout:", files.len()),
+                    );
+                    for file in files {
+                        log(state, LogLevel::Info, format!("  {file}"));
+                    }
+                }
+                Err(e) => log(state, LogLevel::Error, format!("Promote failed: {e}")),
+            }
+        }
+        "discard" => {
+            let Some(sandbox) = state.sandbox.as_ref() else {
+                log(state, LogLevel::Warn, "Sandbox is off. Use /sandbox on");
+                return;
+            };
+            match sandbox.discard() {
+                Ok(()) => {
+                    state.ui.sandbox_changes_from = state.session_changes.len();
+                    log(
+                        state,
+                        LogLevel::Success,
+                        "Discarded sandbox changes since the last promote.",
+                    );
+                }
+                Err(e) => log(state, LogLevel::Error, format!("Discard failed: {e}")),
+            }
+        }
+        _ => log(
+            state,
+            LogLevel::Warn,
+            "Usage: /sandbox [on|off|diff|promote|discard]",
+        ),
     }
+}
 
-    if value.eq_ignore_ascii_case("clear") || value.eq_ignore_ascii_case("off") {
-        state.steer = None;
-        log(state, LogLevel::Success, "Steer instruction cleared.");
-        let _ = persistence::save(state);
-        return;
+/// Reads `.osmogrep/commands/` again, warning about skipped files.
+pub fn load_custom_commands(state: &mut AgentState) {
+    let (commands, problems) = custom_commands::load(&state.repo_root);
+    for problem in problems {
+        log(
+            state,
+            LogLevel::Warn,
+            format!("Custom command ignored: {problem}"),
+        );
     }
+    state.ui.custom_commands = commands;
+}
 
-    if value.is_empty() {
-        show_steer(state);
+fn show_custom_commands(state: &mut AgentState) {
+    if state.ui.custom_commands.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            format!(
+                "No custom commands. Add prompt templates as {}/<name>.md.",
+                custom_commands::DIR
+            ),
+        );
         return;
     }
-
-    state.steer = Some(value.to_string());
-    log(state, LogLevel::Success, "Steer instruction updated.");
-    let _ = persistence::save(state);
+    let lines = state
+        .ui
+        .custom_commands
+        .iter()
+        .map(|custom| format!("{:<24} {}", custom.usage(), custom.description))
+        .collect::<Vec<_>>();
+    for line in lines {
+        log(state, LogLevel::Info, line);
+    }
 }
 
-fn compact_context(state: &mut AgentState) {
-    let before = state.conversation.token_estimate;
-    state
-        .conversation
-        .trim_to_budget(MAX_CONVERSATION_TOKENS.saturating_mul(2) / 3);
-    let after = state.conversation.token_estimate;
-    log(
-        state,
-        LogLevel::Info,
-        format!("Context compacted: {} -> {} tokens", before, after),
-    );
-    let _ = persistence::save(state);
+fn run_custom_command(state: &mut AgentState, custom: &CustomCommand, args: &str) {
+    match custom.expand(args) {
+        Ok(prompt) => {
+            state.ui.queued_agent_prompt = Some(prompt);
+            log(
+                state,
+                LogLevel::Info,
+                format!("Queued {} from {}/.", custom.name, custom_commands::DIR),
+            );
+        }
+        Err(usage) => log(state, LogLevel::Warn, usage),
+    }
 }
 
-fn show_usage(state: &mut AgentState) {
-    let total_tokens = state.usage.prompt_tokens + state.usage.completion_tokens;
-    let est_cost = estimated_usage_cost(state);
+fn show_keys(state: &mut AgentState) {
+    let lines = KeyAction::ALL
+        .into_iter()
+        .map(|action| {
+            format!(
+                "{:<15} {:<22} {}",
+                action.name(),
+                state.ui.keymap.label(action),
+                action.description()
+            )
+        })
+        .collect::<Vec<_>>();
+    for line in lines {
+        log(state, LogLevel::Info, line);
+    }
     log(
         state,
         LogLevel::Info,
         format!(
-            "Usage: prompt_tokens={} completion_tokens={} total_tokens={} context_tokens={} estimated_cost=${:.4}",
-            state.usage.prompt_tokens,
-            state.usage.completion_tokens,
-            total_tokens,
-            state.conversation.token_estimate,
-            est_cost
+            "Rebind in [keys] of {}.",
+            crate::agent::config_path().display()
         ),
     );
 }
 
-fn show_metrics(state: &mut AgentState) {
-    let total_tokens = state.usage.prompt_tokens + state.usage.completion_tokens;
-    let est_cost = estimated_usage_cost(state);
-    let queued = state
-        .jobs
-        .iter()
-        .filter(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
-        .count();
-    log(
-        state,
-        LogLevel::Info,
-        format!(
-            "tokens={} cost=${:.4} context_tokens={} jobs_active={}",
-            total_tokens, est_cost, state.conversation.token_estimate, queued
-        ),
-    );
+/// Closes the diff view, or opens it on the session's changes.
+pub fn toggle_session_diff(state: &mut AgentState) {
+    if state.ui.diff_active {
+        state.ui.diff_active = false;
+        return;
+    }
+    show_session_diff(state);
 }
 
-fn show_theme(state: &mut AgentState) {
-    log(
-        state,
-        LogLevel::Info,
-        format!("Theme: {}. Use /theme <dark|light>.", state.theme.as_str()),
-    );
-}
+fn show_session_diff(state: &mut AgentState) {
+    if state.session_changes.is_empty() {
+        log(state, LogLevel::Info, "No session changes to show.");
+        return;
+    }
 
-fn set_theme(state: &mut AgentState, cmd: &str) {
-    let value = cmd.strip_prefix("/theme").map(str::trim).unwrap_or("");
-    let Some(theme) = UiTheme::parse(value) else {
-        log(state, LogLevel::Warn, "Usage: /theme <dark|light>");
+    let files = crate::state::session_diff(&state.session_changes, state.edit_root());
+    if files.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            "Session changes cancel out: every file is back as it started.",
+        );
         return;
-    };
-    state.theme = theme;
-    log(
-        state,
-        LogLevel::Success,
-        format!("Theme set to {}", theme.as_str()),
-    );
-    let _ = persistence::save(state);
+    }
+    let created = files.iter().filter(|f| f.created).count();
+    let deleted = files.iter().filter(|f| f.deleted).count();
+    let mut summary = format!("Showing {} file(s) changed this session", files.len());
+    if created + deleted > 0 {
+        summary.push_str(&format!(" ({created} created, {deleted} deleted)"));
+    }
+    state.ui.diff_active = true;
+    state.ui.diff_snapshot = files;
+    log(state, LogLevel::Info, format!("{summary}."));
 }
 
-fn show_color(state: &mut AgentState) {
-    log(
-        state,
-        LogLevel::Info,
-        format!(
-            "Accent color: {}. Use /color <orange|blue|green|violet|rose>.",
-            state.accent.as_str()
+fn show_testgen_stats(state: &mut AgentState) {
+    match crate::testgen::load(&state.repo_root) {
+        Ok(entries) => {
+            for line in crate::testgen::stats_report(&entries) {
+                log(state, LogLevel::Info, line);
+            }
+        }
+        Err(e) => log(
+            state,
+            LogLevel::Error,
+            format!("Testgen ledger unreadable: {e}"),
         ),
-    );
+    }
 }
 
-fn set_color(state: &mut AgentState, cmd: &str) {
-    let value = cmd.strip_prefix("/color").map(str::trim).unwrap_or("");
-    let Some(accent) = UiAccent::parse(value) else {
+/// Opens the candidate picker on the working tree's changes, ranked by the
+/// given intent or, without one, the latest prompt.
+fn open_test_candidates(state: &mut AgentState, cmd: &str, agent: Option<&mut Agent>) {
+    if state.ui.agent_running {
         log(
             state,
             LogLevel::Warn,
-            "Usage: /color <orange|blue|green|violet|rose>",
+            "Wait for the agent run to finish before picking test candidates.",
+        );
+        return;
+    }
+    let Some(diff) = working_diff::current(&state.repo_root) else {
+        log(
+            state,
+            LogLevel::Info,
+            "Working tree is clean (or not a git repository).",
         );
         return;
     };
-    state.accent = accent;
-    log(
-        state,
-        LogLevel::Success,
-        format!("Accent color set to {}", accent.as_str()),
-    );
-    let _ = persistence::save(state);
-}
-
-fn show_type(state: &mut AgentState) {
+    let intent = match cmd.strip_prefix("/testgen candidates").unwrap_or("").trim() {
+        "" => state
+            .ui
+            .history
+            .iter()
+            .rev()
+            .find(|entry| !entry.starts_with('/'))
+            .cloned()
+            .unwrap_or_default(),
+        intent => intent.to_string(),
+    };
+    let mut candidates = crate::testgen::test_candidates(&state.repo_root, &diff);
+    crate::testgen::rank_candidates(&mut candidates, &diff.risk, &intent);
+    let model = agent.map_or_else(String::new, |agent| agent.model_config().model.clone());
+    let Some(queue) = CandidateQueue::new(candidates, &model) else {
+        log(
+            state,
+            LogLevel::Info,
+            "The changes touch no functions outside tests.",
+        );
+        return;
+    };
+    let untested = queue
+        .items
+        .iter()
+        .filter(|item| item.candidate.covered_by.is_empty())
+        .count();
     log(
         state,
         LogLevel::Info,
         format!(
-            "UI type: {}. Use /type <compact|standard|spacious>.",
-            state.density.as_str()
+            "{} test candidate(s), {untested} without tests. Discarded ones are logged in the testgen ledger with their reason.",
+            queue.items.len()
         ),
     );
+    state.ui.candidates = Some(queue);
 }
 
-fn set_type(state: &mut AgentState, cmd: &str) {
-    let value = cmd.strip_prefix("/type").map(str::trim).unwrap_or("");
-    let Some(density) = UiDensity::parse(value) else {
+/// Closes the picker and queues the agent to write tests for the picked
+/// candidates.
+pub fn materialize_test_candidates(state: &mut AgentState) {
+    let Some(queue) = state.ui.candidates.take() else {
+        return;
+    };
+    let picked = queue.picked();
+    if picked.is_empty() {
         log(
             state,
             LogLevel::Warn,
-            "Usage: /type <compact|standard|spacious>",
+            "Nothing picked: [space] picks candidates, [esc] closes the list.",
         );
+        state.ui.candidates = Some(queue);
         return;
-    };
-    state.density = density;
+    }
+    state.ui.queued_agent_prompt = Some(crate::testgen::candidates_prompt(&picked));
     log(
         state,
-        LogLevel::Success,
-        format!("UI type set to {}", density.as_str()),
+        LogLevel::Info,
+        format!(
+            "Queued tests for {} candidate(s); edits need approval and the commit is gated as usual.",
+            picked.len()
+        ),
     );
-    let _ = persistence::save(state);
 }
 
-fn show_session(state: &mut AgentState) {
+fn measure_test_strength(state: &mut AgentState) {
     log(
         state,
         LogLevel::Info,
-        format!(
-            "Session: {}",
-            state.session_name.as_deref().unwrap_or("untitled session")
-        ),
+        "Mutating the code under the latest generated tests in a sandbox worktree",
     );
-}
-
-fn rename_session(state: &mut AgentState, cmd: &str) {
-    let value = cmd
-        .strip_prefix("/session rename")
-        .or_else(|| cmd.strip_prefix("/rename"))
-        .map(str::trim)
-        .unwrap_or("");
-    if value.is_empty() {
-        log(state, LogLevel::Warn, "Usage: /rename <session name>");
-        return;
+    match crate::testgen::measure_strength(&state.repo_root) {
+        Ok((commit, strength)) => {
+            let short = &commit[..commit.len().min(8)];
+            let level = if strength.survivors.is_empty() {
+                LogLevel::Success
+            } else {
+                LogLevel::Warn
+            };
+            log(
+                state,
+                level,
+                format!("Test strength of {short}: {}", strength.label()),
+            );
+            for survivor in &strength.survivors {
+                log(state, LogLevel::Info, format!("  survived: {survivor}"));
+            }
+        }
+        Err(e) => log(
+            state,
+            LogLevel::Error,
+            format!("Test strength not measured: {e}"),
+        ),
     }
+}
 
-    state.session_name = Some(value.chars().take(80).collect());
+fn maintain_generated_tests(state: &mut AgentState) {
     log(
         state,
-        LogLevel::Success,
+        LogLevel::Info,
+        "Running tests to find generated tests broken by later changes",
+    );
+    let stale = match crate::testgen::stale_tests(&state.repo_root) {
+        Ok(stale) => stale,
+        Err(e) => {
+            log(
+                state,
+                LogLevel::Error,
+                format!("Test maintenance failed: {e}"),
+            );
+            return;
+        }
+    };
+    if stale.is_empty() {
+        log(
+            state,
+            LogLevel::Success,
+            "No failing generated tests with changed targets.",
+        );
+        return;
+    }
+    for test in &stale {
+        log(
+            state,
+            LogLevel::Warn,
+            format!(
+                "{} ({}) fails; changed since generation: {}",
+                test.test,
+                test.file,
+                test.changed.join(", ")
+            ),
+        );
+    }
+    state.ui.queued_agent_prompt = Some(crate::testgen::maintenance_prompt(&stale));
+    log(
+        state,
+        LogLevel::Info,
         format!(
-            "Session renamed to {}",
-            state.session_name.as_deref().unwrap_or("untitled session")
+            "Queued an update of {} test(s); edits need approval and the commit is gated as usual.",
+            stale.len()
         ),
     );
-    let _ = persistence::save(state);
 }
 
-fn show_profile(state: &mut AgentState) {
+fn show_verify(state: &mut AgentState) {
+    let status = crate::verification::latest_status(&state.repo_root);
+    let level = match status.status.as_str() {
+        "passed" => LogLevel::Success,
+        "failed" => LogLevel::Error,
+        "stale" => LogLevel::Warn,
+        _ => LogLevel::Info,
+    };
     log(
         state,
-        LogLevel::Info,
-        format!("Permission profile: {}", state.permission_profile.as_str()),
+        level,
+        format!(
+            "Verification status: {}{}",
+            status.status,
+            if status.needs_verification {
+                " (needs fresh passing evidence)"
+            } else {
+                ""
+            }
+        ),
     );
-}
 
-fn set_profile(state: &mut AgentState, cmd: &str) {
-    let value = cmd.strip_prefix("/profile").map(str::trim).unwrap_or("");
-    let Some(profile) = PermissionProfile::parse(value) else {
+    if let Some(ev) = status.evidence.as_ref() {
+        log(
+            state,
+            LogLevel::Info,
+            format!(
+                "Last evidence: {} [{}:{}:{}] exit={}",
+                ev.canonical_command, ev.kind, ev.scope, ev.status, ev.exit_code
+            ),
+        );
+    } else {
+        log(state, LogLevel::Info, "Last evidence: none recorded");
+    }
+
+    if !status.verifiable_changed_paths.is_empty() {
         log(
             state,
             LogLevel::Warn,
-            "Usage: /profile <read-only|workspace-auto|full-access>",
+            format!(
+                "Changed code/config paths since evidence: {}",
+                format_path_list(&status.verifiable_changed_paths, 8)
+            ),
         );
-        return;
-    };
-    state.permission_profile = profile;
-    state.ui.auto_approve = matches!(profile, PermissionProfile::FullAccess);
-    log(
-        state,
-        LogLevel::Success,
-        format!("Permission profile set to {}", profile.as_str()),
-    );
-    let _ = persistence::save(state);
+    } else if !status.changed_paths.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            format!(
+                "Changed paths since evidence are doc/data-only: {}",
+                format_path_list(&status.changed_paths, 8)
+            ),
+        );
+    }
 }
 
-fn run_swarm_now(state: &mut AgentState, cmd: &str, agent: Option<&mut Agent>) {
-    let prompt = cmd.strip_prefix("/swarm").map(str::trim).unwrap_or("");
-    if prompt.is_empty() {
-        log(state, LogLevel::Warn, "Usage: /swarm <task>");
-        return;
+fn format_path_list(paths: &[String], limit: usize) -> String {
+    let mut shown = paths
+        .iter()
+        .take(limit)
+        .map(|path| format!("`{path}`"))
+        .collect::<Vec<_>>();
+    let remaining = paths.len().saturating_sub(limit);
+    if remaining > 0 {
+        shown.push(format!("+{remaining} more"));
     }
-    let Some(agent) = agent else {
-        log(state, LogLevel::Warn, "Agent unavailable.");
-        return;
-    };
+    shown.join(", ")
+}
 
-    match agent.run_swarm(prompt) {
-        Ok(outputs) => {
-            log(state, LogLevel::Success, "Swarm completed.");
-            for (role, text) in outputs {
-                log(state, LogLevel::Info, format!("[{}]", role));
-                for line in text.lines().take(20) {
-                    log(state, LogLevel::Info, line.to_string());
-                }
-            }
+fn show_steer(state: &mut AgentState) {
+    match state.steer.as_deref() {
+        Some(s) if !s.trim().is_empty() => {
+            log(state, LogLevel::Info, format!("Steer: {}", s.trim()));
         }
-        Err(e) => log(state, LogLevel::Error, format!("Swarm failed: {}", e)),
+        _ => log(state, LogLevel::Info, "Steer: (not set)"),
     }
 }
 
-fn handle_job(state: &mut AgentState, cmd: &str) {
-    let rest = cmd.strip_prefix("/job").map(str::trim).unwrap_or("");
+fn set_steer(state: &mut AgentState, cmd: &str, steer_tx: Option<&Sender<String>>) {
+    let value = cmd.strip_prefix("/steer").map(str::trim).unwrap_or("");
+    let templates = crate::agent::steer_templates();
+    if let Some(now_text) = value.strip_prefix("now ").map(str::trim) {
+        if now_text.is_empty() {
+            log(state, LogLevel::Warn, "Usage: /steer now <instruction>");
+            return;
+        }
+        let text = templates
+            .get(now_text)
+            .cloned()
+            .unwrap_or_else(|| now_text.to_string());
+        steer_now(state, &text, steer_tx);
+        let _ = persistence::save(state);
+        return;
+    }
 
-    if rest == "cancel all" {
-        let mut ids = Vec::new();
-        for req in &state.job_queue {
-            ids.push(req.id);
+    if value == "history" {
+        show_steer_history(state);
+        return;
+    }
+    if value == "templates" {
+        log(state, LogLevel::Info, "Steer templates (/steer <name>):");
+        for (name, text) in &templates {
+            log(state, LogLevel::Info, format!("  {name}: {text}"));
         }
-        state.job_queue.clear();
-        for id in ids {
-            if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
-                job.status = JobStatus::Cancelled;
+        return;
+    }
+    if let Some(n) = value.strip_prefix("resend") {
+        let record = n
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| state.steer_history.get(i))
+            .cloned();
+        match record {
+            Some(record) => {
+                steer_now(state, &record.text, steer_tx);
+                let _ = persistence::save(state);
             }
+            None => log(
+                state,
+                LogLevel::Warn,
+                format!(
+                    "Usage: /steer resend <1-{}> (see /steer history)",
+                    state.steer_history.len()
+                ),
+            ),
+        }
+        return;
+    }
+
+    if value.eq_ignore_ascii_case("clear") || value.eq_ignore_ascii_case("off") {
+        state.steer = None;
+        log(state, LogLevel::Success, "Steer instruction cleared.");
+        let _ = persistence::save(state);
+        return;
+    }
+
+    if value.is_empty() {
+        show_steer(state);
+        return;
+    }
+
+    // A template also reaches a run that is already going.
+    if let Some(text) = templates.get(value) {
+        if state.ui.agent_running {
+            steer_now(state, text, steer_tx);
+        } else {
+            state.steer = Some(text.clone());
+        }
+        log(
+            state,
+            LogLevel::Success,
+            format!("Steer instruction set from template `{value}`."),
+        );
+        let _ = persistence::save(state);
+        return;
+    }
+
+    state.steer = Some(value.to_string());
+    log(state, LogLevel::Success, "Steer instruction updated.");
+    let _ = persistence::save(state);
+}
+
+/// Makes `text` the steer and hands it to the running agent, or launches a
+/// run with it when none is going.
+fn steer_now(state: &mut AgentState, text: &str, steer_tx: Option<&Sender<String>>) {
+    state.steer = Some(text.to_string());
+    if !state.ui.agent_running {
+        state.ui.queued_agent_prompt = Some(text.to_string());
+        record_steer(state, text, false);
+        log(
+            state,
+            LogLevel::Info,
+            "Steer-now queued for immediate launch.",
+        );
+        return;
+    }
+    if steer_tx.is_some_and(|tx| tx.send(text.to_string()).is_ok()) {
+        record_steer(state, text, true);
+        log(
+            state,
+            LogLevel::Info,
+            "Steer-now injected into running agent.",
+        );
+    } else {
+        state.ui.queued_agent_prompt = Some(text.to_string());
+        record_steer(state, text, false);
+        log(
+            state,
+            LogLevel::Warn,
+            "Live steer channel unavailable. Queued follow-up prompt instead.",
+        );
+    }
+}
+
+/// Adds `text` to the steering panel of the current run.
+pub fn record_steer(state: &mut AgentState, text: &str, live: bool) {
+    state.steer_history.push(SteerRecord {
+        text: text.trim().to_string(),
+        at: chrono::Local::now(),
+        live,
+    });
+}
+
+fn show_steer_history(state: &mut AgentState) {
+    if state.steer_history.is_empty() {
+        log(state, LogLevel::Info, "No steers sent during this run.");
+        return;
+    }
+    let lines: Vec<String> = state
+        .steer_history
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            format!(
+                "  {}. {} {} {}",
+                i + 1,
+                timefmt::clock(&record.at),
+                if record.live { "live  " } else { "queued" },
+                record.text
+            )
+        })
+        .collect();
+    log(
+        state,
+        LogLevel::Info,
+        "Steers this run (/steer resend <n> sends one again):",
+    );
+    for line in lines {
+        log(state, LogLevel::Info, line);
+    }
+}
+
+fn compact_context(state: &mut AgentState) {
+    let before = state.conversation.token_estimate;
+    state
+        .conversation
+        .trim_to_budget(MAX_CONVERSATION_TOKENS.saturating_mul(2) / 3);
+    let after = state.conversation.token_estimate;
+    log(
+        state,
+        LogLevel::Info,
+        format!("Context compacted: {} -> {} tokens", before, after),
+    );
+    let _ = persistence::save(state);
+}
+
+fn show_usage(state: &mut AgentState) {
+    let total_tokens = state.usage.prompt_tokens + state.usage.completion_tokens;
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "Usage: prompt_tokens={} completion_tokens={} total_tokens={} context_tokens={} estimated_cost={}",
+            state.usage.prompt_tokens,
+            state.usage.completion_tokens,
+            total_tokens,
+            state.conversation.token_estimate,
+            state.usage.cost_label()
+        ),
+    );
+
+    let run = state.usage.run.clone();
+    if run.model_calls > 0 {
+        log(
+            state,
+            LogLevel::Info,
+            format!(
+                "Last run: {} in / {} out over {} model call(s), ${:.4}",
+                run.input_tokens, run.output_tokens, run.model_calls, run.cost_usd
+            ),
+        );
+    }
+
+    let mut lines = Vec::new();
+    for (model, usage) in &state.usage.by_model {
+        let cost = if usage.unpriced_tokens > 0 {
+            format!("no price (add [pricing.\"{model}\"] to config.toml)")
+        } else {
+            format!("${:.4}", usage.cost_usd)
+        };
+        let estimated = if usage.estimated_tokens > 0 {
+            format!(", {} estimated", usage.estimated_tokens)
+        } else {
+            String::new()
+        };
+        lines.push(format!(
+            "  {model}: {} in / {} out over {} call(s){estimated}, {cost}",
+            usage.input_tokens, usage.output_tokens, usage.model_calls
+        ));
+    }
+    if !lines.is_empty() {
+        log(state, LogLevel::Info, "By model:");
+    }
+    for line in lines {
+        log(state, LogLevel::Info, line);
+    }
+
+    let mut tools: Vec<_> = state
+        .usage
+        .by_tool
+        .iter()
+        .map(|(name, usage)| (name.clone(), *usage))
+        .collect();
+    tools.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.tokens));
+    if !tools.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            "By tool (tokens of the turns that called it):",
+        );
+    }
+    let max = tools.first().map_or(0, |(_, usage)| usage.tokens) as f64;
+    let rows = tools
+        .into_iter()
+        .map(|(name, usage)| {
+            let caption = format!(
+                "{} call(s), {} tokens, {} per call",
+                usage.calls,
+                usage.tokens,
+                usage.tokens / usage.calls.max(1)
+            );
+            (name, usage.tokens as f64, caption)
+        })
+        .collect::<Vec<_>>();
+    for row in chart::captioned_bar_rows(&rows, max, 16) {
+        log(state, LogLevel::Info, format!("  {row}"));
+    }
+}
+
+fn show_metrics(state: &mut AgentState) {
+    let total_tokens = state.usage.prompt_tokens + state.usage.completion_tokens;
+    let est_cost = state.usage.cost_label();
+    let queued = state
+        .jobs
+        .iter()
+        .filter(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
+        .count();
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "tokens={} cost={} context_tokens={} jobs_active={}",
+            total_tokens, est_cost, state.conversation.token_estimate, queued
+        ),
+    );
+
+    // The log panel is a ratatui buffer, so charts here are always blocks.
+    let rows = chart::bar_rows(
+        &[
+            ("prompt ", state.usage.prompt_tokens as f64),
+            ("output ", state.usage.completion_tokens as f64),
+            ("context", state.conversation.token_estimate as f64),
+        ],
+        24,
+    );
+    for row in rows {
+        log(state, LogLevel::Info, format!("  {row}"));
+    }
+}
+
+fn show_theme(state: &mut AgentState) {
+    log(
+        state,
+        LogLevel::Info,
+        format!("Theme: {}. Use /theme <dark|light>.", state.theme.as_str()),
+    );
+}
+
+fn set_theme(state: &mut AgentState, cmd: &str) {
+    let value = cmd.strip_prefix("/theme").map(str::trim).unwrap_or("");
+    let Some(theme) = UiTheme::parse(value) else {
+        log(state, LogLevel::Warn, "Usage: /theme <dark|light>");
+        return;
+    };
+    state.theme = theme;
+    log(
+        state,
+        LogLevel::Success,
+        format!("Theme set to {}", theme.as_str()),
+    );
+    let _ = persistence::save(state);
+}
+
+fn show_color(state: &mut AgentState) {
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "Accent color: {}. Use /color <orange|blue|green|violet|rose>.",
+            state.accent.as_str()
+        ),
+    );
+}
+
+fn set_color(state: &mut AgentState, cmd: &str) {
+    let value = cmd.strip_prefix("/color").map(str::trim).unwrap_or("");
+    let Some(accent) = UiAccent::parse(value) else {
+        log(
+            state,
+            LogLevel::Warn,
+            "Usage: /color <orange|blue|green|violet|rose>",
+        );
+        return;
+    };
+    state.accent = accent;
+    log(
+        state,
+        LogLevel::Success,
+        format!("Accent color set to {}", accent.as_str()),
+    );
+    let _ = persistence::save(state);
+}
+
+fn show_type(state: &mut AgentState) {
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "UI type: {}. Use /type <compact|standard|spacious>.",
+            state.density.as_str()
+        ),
+    );
+}
+
+fn set_type(state: &mut AgentState, cmd: &str) {
+    let value = cmd.strip_prefix("/type").map(str::trim).unwrap_or("");
+    let Some(density) = UiDensity::parse(value) else {
+        log(
+            state,
+            LogLevel::Warn,
+            "Usage: /type <compact|standard|spacious>",
+        );
+        return;
+    };
+    state.density = density;
+    log(
+        state,
+        LogLevel::Success,
+        format!("UI type set to {}", density.as_str()),
+    );
+    let _ = persistence::save(state);
+}
+
+fn cycle_timestamps(state: &mut AgentState) {
+    let next = match state.timestamps {
+        LogTimestamps::Off => LogTimestamps::Absolute,
+        LogTimestamps::Absolute => LogTimestamps::Relative,
+        LogTimestamps::Relative => LogTimestamps::Off,
+    };
+    apply_timestamps(state, next);
+}
+
+fn set_timestamps(state: &mut AgentState, cmd: &str) {
+    let value = cmd.strip_prefix("/timestamps").map(str::trim).unwrap_or("");
+    let Some(mode) = LogTimestamps::parse(value) else {
+        log(
+            state,
+            LogLevel::Warn,
+            "Usage: /timestamps <off|absolute|relative>",
+        );
+        return;
+    };
+    apply_timestamps(state, mode);
+}
+
+fn apply_timestamps(state: &mut AgentState, mode: LogTimestamps) {
+    state.timestamps = mode;
+    log(
+        state,
+        LogLevel::Success,
+        format!("Log timestamps: {}", mode.as_str()),
+    );
+    let _ = persistence::save(state);
+}
+
+fn show_session(state: &mut AgentState) {
+    log(
+        state,
+        LogLevel::Info,
+        format!(
+            "Session: {}",
+            state.session_name.as_deref().unwrap_or("untitled session")
+        ),
+    );
+}
+
+fn rename_session(state: &mut AgentState, cmd: &str) {
+    let value = cmd
+        .strip_prefix("/session rename")
+        .or_else(|| cmd.strip_prefix("/rename"))
+        .map(str::trim)
+        .unwrap_or("");
+    if value.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /rename <session name>");
+        return;
+    }
+
+    state.session_name = Some(value.chars().take(80).collect());
+    log(
+        state,
+        LogLevel::Success,
+        format!(
+            "Session renamed to {}",
+            state.session_name.as_deref().unwrap_or("untitled session")
+        ),
+    );
+    let _ = persistence::save(state);
+}
+
+fn show_profile(state: &mut AgentState) {
+    log(
+        state,
+        LogLevel::Info,
+        format!("Permission profile: {}", state.permission_profile.as_str()),
+    );
+}
+
+fn set_profile(state: &mut AgentState, cmd: &str) {
+    let value = cmd.strip_prefix("/profile").map(str::trim).unwrap_or("");
+    let Some(profile) = PermissionProfile::parse(value) else {
+        log(
+            state,
+            LogLevel::Warn,
+            "Usage: /profile <read-only|workspace-auto|full-access>",
+        );
+        return;
+    };
+    if instance::is_secondary() && profile != PermissionProfile::ReadOnly {
+        log(
+            state,
+            LogLevel::Warn,
+            "Another instance owns this repo; use /lock take before enabling writes.",
+        );
+        return;
+    }
+    state.permission_profile = profile;
+    state.ui.auto_approve = matches!(profile, PermissionProfile::FullAccess);
+    log(
+        state,
+        LogLevel::Success,
+        format!("Permission profile set to {}", profile.as_str()),
+    );
+    let _ = persistence::save(state);
+}
+
+fn run_swarm_now(state: &mut AgentState, cmd: &str, agent: Option<&mut Agent>) {
+    let prompt = cmd.strip_prefix("/swarm").map(str::trim).unwrap_or("");
+    if prompt.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /swarm <task>");
+        return;
+    }
+    let Some(agent) = agent else {
+        log(state, LogLevel::Warn, "Agent unavailable.");
+        return;
+    };
+
+    match agent.run_swarm(prompt) {
+        Ok(outputs) => {
+            log(state, LogLevel::Success, "Swarm completed.");
+            for (role, text) in outputs {
+                log(state, LogLevel::Info, format!("[{}]", role));
+                for line in text.lines().take(20) {
+                    log(state, LogLevel::Info, line.to_string());
+                }
+            }
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Swarm failed: {}", e)),
+    }
+}
+
+fn handle_job(state: &mut AgentState, cmd: &str) {
+    let rest = cmd.strip_prefix("/job").map(str::trim).unwrap_or("");
+
+    if rest == "cancel all" {
+        let mut ids = Vec::new();
+        for req in &state.job_queue {
+            ids.push(req.id);
+        }
+        state.job_queue.clear();
+        for id in ids {
+            if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
+                job.status = JobStatus::Cancelled;
+            }
+        }
+        log(state, LogLevel::Info, "Cancelled queued jobs.");
+        let _ = persistence::save(state);
+        return;
+    }
+
+    if let Some(arg) = rest.strip_prefix("resume ") {
+        let Ok(id) = arg.trim().parse::<u64>() else {
+            log(state, LogLevel::Warn, "Usage: /job resume <id>");
+            return;
+        };
+        let Some(old) = state.jobs.iter().find(|j| j.id == id).cloned() else {
+            log(state, LogLevel::Warn, format!("Job #{} not found.", id));
+            return;
+        };
+        queue_job(state, old.kind, old.input);
+        return;
+    }
+
+    if let Some(arg) = rest.strip_prefix("swarm ") {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            log(state, LogLevel::Warn, "Usage: /job swarm <task>");
+            return;
+        }
+        queue_job(state, JobKind::Swarm, arg.to_string());
+        return;
+    }
+
+    if let Some(arg) = rest.strip_prefix("test") {
+        queue_job(state, JobKind::Test, arg.trim().to_string());
+        return;
+    }
+
+    log(
+        state,
+        LogLevel::Warn,
+        "Usage: /job test [target] | /job swarm <task> | /job resume <id> | /job cancel all",
+    );
+}
+
+fn queue_job(state: &mut AgentState, kind: JobKind, input: String) {
+    let id = state.next_job_id;
+    state.next_job_id += 1;
+    state.jobs.push(JobRecord {
+        id,
+        kind: kind.clone(),
+        input: input.clone(),
+        status: JobStatus::Queued,
+        output: None,
+        run_id: None,
+    });
+    state.job_queue.push(JobRequest { id, kind, input });
+    log(state, LogLevel::Info, format!("Queued job #{}", id));
+    let _ = persistence::save(state);
+}
+
+fn show_working_diff(state: &mut AgentState) {
+    let fresh = working_diff::cached(&state.repo_root).is_none();
+    match working_diff::current(&state.repo_root) {
+        Some(diff) => {
+            for line in working_diff::render(&diff, fresh) {
+                log(state, LogLevel::Info, line);
+            }
+        }
+        None => log(
+            state,
+            LogLevel::Info,
+            "Working tree is clean (or not a git repository).",
+        ),
+    }
+}
+
+fn impact_command(state: &mut AgentState, cmd: &str) {
+    let root = state.repo_root.clone();
+    let symbol = cmd.strip_prefix("/impact").unwrap_or("").trim();
+    let symbols: Vec<String> = if symbol.is_empty() {
+        let Some(diff) = working_diff::current(&root) else {
+            log(
+                state,
+                LogLevel::Info,
+                "Usage: /impact <symbol> (without one, the symbols the working tree changes)",
+            );
+            return;
+        };
+        let mut names: Vec<String> = diff.symbols.into_iter().map(|s| s.name).collect();
+        names.dedup();
+        names
+    } else {
+        vec![symbol.to_string()]
+    };
+    if symbols.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            "The changes touch no indexed symbols.",
+        );
+        return;
+    }
+    let ctx = indexer::load_or_build(&root);
+    for symbol in symbols {
+        match impact::analyze(&ctx, &root, &symbol, impact::DEFAULT_DEPTH) {
+            Ok(found) => {
+                for line in impact::render(&found) {
+                    log(state, LogLevel::Info, line);
+                }
+            }
+            Err(e) => log(state, LogLevel::Warn, e),
+        }
+    }
+}
+
+fn find_command(state: &mut AgentState, cmd: &str) {
+    let query = cmd.strip_prefix("/find").unwrap_or("").trim();
+    if query.is_empty() {
+        log(state, LogLevel::Info, "Usage: /find <query>");
+        return;
+    }
+    let root = state.repo_root.clone();
+    let ctx = indexer::load_or_build(&root);
+    let hits = search::search(&ctx, &root, query, search::DEFAULT_LIMIT);
+    for line in search::render(query, &hits) {
+        log(state, LogLevel::Info, line);
+    }
+}
+
+fn diff_baseline_command(state: &mut AgentState, cmd: &str) {
+    let root = state.repo_root.clone();
+    let rest = cmd.strip_prefix("/diff").unwrap_or("").trim();
+    let outcome = match rest.split_once(' ') {
+        Some(("base", base)) => working_diff::set_baseline(&root, None, Some(base.trim())),
+        None if rest == "baseline" => {
+            let setting = working_diff::resolved_baseline(&root);
+            log(
+                state,
+                LogLevel::Info,
+                format!("/inspect compares {}.", setting.label()),
+            );
+            return;
+        }
+        None => match working_diff::DiffBaseline::parse(rest) {
+            Some(baseline) => working_diff::set_baseline(&root, Some(baseline), None),
+            None => Err("Usage: /diff [baseline|head|staged|unstaged|branch|base <ref>]".into()),
+        },
+        Some(_) => Err("Usage: /diff [baseline|head|staged|unstaged|branch|base <ref>]".into()),
+    };
+    match outcome {
+        Ok(_) => {
+            let setting = working_diff::resolved_baseline(&root);
+            state.ui.diff_baseline = working_diff::baseline_label(&root);
+            let branch = current_git_branch(&root).unwrap_or_else(|| "HEAD".to_string());
+            if setting.baseline == working_diff::DiffBaseline::Branch && setting.base.is_none() {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    "No base branch found; set one with /diff base <ref>.",
+                );
+            }
+            log(
+                state,
+                LogLevel::Success,
+                format!("/inspect on {branch} now compares {}.", setting.label()),
+            );
+        }
+        Err(err) => log(state, LogLevel::Error, err),
+    }
+}
+
+fn git_status_command(state: &mut AgentState, cmd: &str) {
+    let root = state.repo_root.clone();
+    let rest = cmd.strip_prefix("/git").unwrap_or("").trim();
+    let (action, path) = rest.split_once(' ').unwrap_or((rest, ""));
+    let path = path.trim();
+    if !action.is_empty() && path.is_empty() {
+        log(
+            state,
+            LogLevel::Warn,
+            "Usage: /git [stage|ignore|include|exclude <path>]",
+        );
+        return;
+    }
+    let outcome = match action {
+        "" => {
+            match git_status::status(&root) {
+                Ok(entries) => {
+                    let included = working_diff::included_untracked(&root);
+                    for line in git_status::render(&entries, &included) {
+                        log(state, LogLevel::Info, line);
+                    }
+                }
+                Err(err) => log(state, LogLevel::Error, format!("git status failed: {err}")),
+            }
+            return;
+        }
+        "stage" => git_status::stage(&root, path).map(|_| format!("Staged {path}.")),
+        "ignore" => git_status::ignore(&root, path).map(|added| {
+            if added {
+                format!("Added {path} to .gitignore.")
+            } else {
+                format!("{path} is already in .gitignore.")
+            }
+        }),
+        "include" | "exclude" => {
+            let include = action == "include";
+            let untracked = git_status::status(&root)
+                .map(|entries| entries.iter().any(|e| e.untracked && e.path == path))
+                .unwrap_or(false);
+            if include && !untracked {
+                Err(format!("{path} is not an untracked file."))
+            } else {
+                let verb = if include {
+                    "is included in"
+                } else {
+                    "is left out of"
+                };
+                working_diff::set_included(&root, path, include)
+                    .map(|_| format!("{path} {verb} the diff analysis."))
+            }
+        }
+        other => Err(format!(
+            "Unknown /git action {other}. Use stage, ignore, include or exclude."
+        )),
+    };
+    match outcome {
+        Ok(message) => log(state, LogLevel::Success, message),
+        Err(err) => log(state, LogLevel::Error, err),
+    }
+}
+
+fn show_onboarding(state: &mut AgentState, cmd: &str) {
+    let rest = cmd.trim_start_matches("/onboard").trim();
+    let export = match rest.split_once(' ').unwrap_or((rest, "")) {
+        ("", _) => None,
+        ("export", path) if path.trim().is_empty() => Some(crate::onboard::DEFAULT_EXPORT),
+        ("export", path) => Some(path.trim()),
+        _ => {
+            log(state, LogLevel::Warn, "Usage: /onboard [export [path]]");
+            return;
+        }
+    };
+
+    let report = crate::onboard::build(&state.repo_root);
+    for line in crate::onboard::render(&report).lines() {
+        log(state, LogLevel::Info, line.to_string());
+    }
+    if let Some(path) = export {
+        match crate::onboard::export(&report, std::path::Path::new(path)) {
+            Ok(path) => log(
+                state,
+                LogLevel::Success,
+                format!("Onboarding report written to {}", path.display()),
+            ),
+            Err(e) => log(state, LogLevel::Error, format!("Export failed: {e}")),
+        }
+    }
+}
+
+fn detach_run(state: &mut AgentState, cmd: &str) {
+    let task = cmd.trim_start_matches("/detach").trim();
+    if task.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /detach <task>");
+        return;
+    }
+    match crate::detach::spawn(
+        &state.repo_root,
+        task,
+        state.permission_profile,
+        state.ui.auto_approve,
+    ) {
+        Ok(run) => {
+            log(
+                state,
+                LogLevel::Success,
+                format!("Detached run {} (pid {}).", run.id, run.pid),
+            );
+            log(
+                state,
+                LogLevel::Info,
+                format!(
+                    "It keeps running after Osmogrep exits. Follow it with /attach {0} or `osmogrep attach {0}`.",
+                    run.id
+                ),
+            );
+            if !state.ui.auto_approve {
+                log(
+                    state,
+                    LogLevel::Info,
+                    "Dangerous tools are denied in detached runs unless /approve is on.",
+                );
+            }
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Detach failed: {e}")),
+    }
+}
+
+fn attach_run(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.trim_start_matches("/attach").trim();
+    if arg == "stop" {
+        if let Some((id, _)) = state.ui.attached_run.take() {
+            log(
+                state,
+                LogLevel::Info,
+                format!("Stopped following run {id}."),
+            );
+        }
+        return;
+    }
+    if arg == "clear" {
+        let dropped = std::mem::take(&mut state.ui.images).len();
+        let piped = if state.ui.piped_input.take().is_some() {
+            " and the piped stdin"
+        } else {
+            ""
+        };
+        log(
+            state,
+            LogLevel::Info,
+            format!("Dropped {dropped} image(s){piped}."),
+        );
+        return;
+    }
+    let image_path = images::expand_home(arg.trim_matches(['\'', '"']));
+    if images::image_mime(&image_path).is_some() {
+        let image_path = state.repo_root.join(image_path);
+        attach_image(state, &image_path);
+        return;
+    }
+    if arg.is_empty() {
+        let pending = state
+            .ui
+            .images
+            .iter()
+            .map(|image| format!("Next prompt has image {}", image.path.display()))
+            .collect::<Vec<_>>();
+        for line in pending {
+            log(state, LogLevel::Info, line);
+        }
+        let runs = crate::detach::list();
+        if runs.is_empty() {
+            log(state, LogLevel::Info, "No detached runs.");
+        }
+        for run in runs.iter().take(15) {
+            let status = if run.is_finished() {
+                "finished"
+            } else {
+                "running"
+            };
+            log(
+                state,
+                LogLevel::Info,
+                format!(
+                    "{} [{}] started {} - {}",
+                    run.id,
+                    status,
+                    crate::timefmt::relative(
+                        run.started()
+                            .and_then(|at| (chrono::Local::now() - at).to_std().ok())
+                            .unwrap_or_default()
+                    ),
+                    compact_line(&run.task, 80)
+                ),
+            );
+        }
+        return;
+    }
+
+    match crate::detach::find(arg) {
+        Ok(run) => {
+            log(
+                state,
+                LogLevel::Info,
+                format!(
+                    "Attached to run {}: {}",
+                    run.id,
+                    compact_line(&run.task, 80)
+                ),
+            );
+            state.ui.attached_run = Some((
+                run.id.clone(),
+                crate::detach::EventTail::new(run.events_path()),
+            ));
+        }
+        Err(e) => log(state, LogLevel::Error, e),
+    }
+}
+
+fn attach_image(state: &mut AgentState, path: &std::path::Path) {
+    match ImageAttachment::load(path) {
+        Ok(image) => {
+            log(
+                state,
+                LogLevel::Info,
+                format!("Attached {} to the next prompt.", image.name()),
+            );
+            state.ui.images.push(image);
+        }
+        Err(e) => log(state, LogLevel::Error, format!("Attach failed: {e}")),
+    }
+}
+
+fn pr_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/pr").unwrap_or("").trim();
+    if let Some(title) = arg.strip_prefix("title ") {
+        let Some(draft) = state.ui.pending_pr.as_mut() else {
+            log(state, LogLevel::Warn, "No PR draft. Run /pr first.");
+            return;
+        };
+        draft.title = title.trim().to_string();
+        log(state, LogLevel::Info, format!("PR title: {}", title.trim()));
+        return;
+    }
+    match arg {
+        "" => {
+            if state.ui.agent_running {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    "Wait for the agent run to finish first.",
+                );
+                return;
+            }
+            let pending = state
+                .sandbox
+                .as_ref()
+                .and_then(|s| s.changes().ok())
+                .map_or(0, |c| c.len());
+            if pending > 0 {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    format!("The sandbox has {pending} unpromoted file(s); /sandbox promote them first."),
+                );
+            }
+            match crate::pull_request::draft(&state.repo_root, &state.conversation.messages) {
+                Ok(draft) => {
+                    for line in draft.preview() {
+                        log(state, LogLevel::Info, line);
+                    }
+                    log(
+                        state,
+                        LogLevel::Info,
+                        "Run /pr create to push and open it, /pr title <text> to retitle, /pr cancel to drop it.",
+                    );
+                    state.ui.pending_pr = Some(draft);
+                }
+                Err(e) => log(state, LogLevel::Error, format!("PR draft failed: {e}")),
+            }
+        }
+        "create" => {
+            let Some(draft) = state.ui.pending_pr.take() else {
+                log(state, LogLevel::Warn, "No PR draft. Run /pr first.");
+                return;
+            };
+            log(state, LogLevel::Info, format!("Pushing {}...", draft.head));
+            match crate::pull_request::create(&state.repo_root, &draft) {
+                Ok(url) => log(state, LogLevel::Success, format!("Opened PR: {url}")),
+                Err(e) => {
+                    log(state, LogLevel::Error, format!("PR failed: {e}"));
+                    state.ui.pending_pr = Some(draft);
+                }
+            }
+        }
+        "cancel" => {
+            if state.ui.pending_pr.take().is_some() {
+                log(state, LogLevel::Info, "Dropped the PR draft.");
+            }
+        }
+        _ => log(
+            state,
+            LogLevel::Warn,
+            "Usage: /pr | /pr title <text> | /pr create | /pr cancel",
+        ),
+    }
+}
+
+fn queue_command(state: &mut AgentState, cmd: &str) {
+    let arg = cmd.strip_prefix("/queue").unwrap_or("").trim();
+    let task_id = |prefix: &str| {
+        arg.strip_prefix(prefix)
+            .map(|rest| rest.trim().trim_start_matches('#'))
+            .and_then(|id| id.parse::<u64>().ok())
+    };
+
+    if arg.is_empty() {
+        let rows: Vec<String> = state
+            .jobs
+            .iter()
+            .filter(|job| matches!(job.kind, JobKind::Agent { .. }))
+            .rev()
+            .take(15)
+            .map(|job| {
+                let mut where_ = match job.kind {
+                    JobKind::Agent { sandboxed: true } => "sandbox".to_string(),
+                    _ => "checkout".to_string(),
+                };
+                if let Some(run) = &job.run_id {
+                    where_.push_str(&format!(", run {run}"));
+                }
+                format!(
+                    "#{} [{}] {} - {}",
+                    job.id,
+                    job_status_label(&job.status),
+                    where_,
+                    compact_line(&job.input, 80)
+                )
+            })
+            .collect();
+        if rows.is_empty() {
+            log(
+                state,
+                LogLevel::Info,
+                "No queued tasks. Usage: /queue [--sandbox] <task>",
+            );
+        }
+        for row in rows {
+            log(state, LogLevel::Info, row);
+        }
+        return;
+    }
+
+    if let Some(id) = task_id("attach") {
+        let run = state.jobs.iter().find(|j| j.id == id);
+        match run.map(|j| j.run_id.clone()) {
+            Some(Some(run_id)) => attach_run(state, &format!("/attach {run_id}")),
+            Some(None) => log(
+                state,
+                LogLevel::Info,
+                format!("Task #{id} has not started."),
+            ),
+            None => log(state, LogLevel::Warn, format!("Task #{id} not found.")),
         }
-        log(state, LogLevel::Info, "Cancelled queued jobs.");
-        let _ = persistence::save(state);
         return;
     }
 
-    if let Some(arg) = rest.strip_prefix("resume ") {
-        let Ok(id) = arg.trim().parse::<u64>() else {
-            log(state, LogLevel::Warn, "Usage: /job resume <id>");
+    if let Some(id) = task_id("review") {
+        if state.ui.agent_running || state.sandbox.is_some() {
+            log(
+                state,
+                LogLevel::Warn,
+                "Finish the agent run and close the current sandbox (/sandbox off) first.",
+            );
             return;
-        };
-        let Some(old) = state.jobs.iter().find(|j| j.id == id).cloned() else {
-            log(state, LogLevel::Warn, format!("Job #{} not found.", id));
+        }
+        let running = state
+            .jobs
+            .iter()
+            .any(|j| j.id == id && j.status == JobStatus::Running);
+        let sandboxes = &state.ui.task_sandboxes;
+        let Some(pos) = sandboxes.iter().position(|(job, _)| *job == id) else {
+            log(
+                state,
+                LogLevel::Warn,
+                format!("Task #{id} has no sandbox to review."),
+            );
             return;
         };
-        queue_job(state, old.kind, old.input);
-        return;
-    }
-
-    if let Some(arg) = rest.strip_prefix("swarm ") {
-        let arg = arg.trim();
-        if arg.is_empty() {
-            log(state, LogLevel::Warn, "Usage: /job swarm <task>");
+        if running {
+            log(
+                state,
+                LogLevel::Warn,
+                format!("Task #{id} is still running. Follow it with /queue attach {id}."),
+            );
             return;
         }
-        queue_job(state, JobKind::Swarm, arg.to_string());
+        let (_, sandbox) = state.ui.task_sandboxes.remove(pos);
+        state.sandbox = Some(sandbox);
+        log(
+            state,
+            LogLevel::Success,
+            format!("Task #{id}'s sandbox is now active. Use /sandbox diff, then /sandbox promote or /sandbox discard."),
+        );
         return;
     }
 
-    if let Some(arg) = rest.strip_prefix("test") {
-        queue_job(state, JobKind::Test, arg.trim().to_string());
+    let (sandboxed, task) = match arg.strip_prefix("--sandbox") {
+        Some(task) => (true, task.trim()),
+        None => (false, arg),
+    };
+    if task.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /queue [--sandbox] <task>");
         return;
     }
-
-    log(
-        state,
-        LogLevel::Warn,
-        "Usage: /job test [target] | /job swarm <task> | /job resume <id> | /job cancel all",
-    );
+    queue_job(state, JobKind::Agent { sandboxed }, task.to_string());
+    if !state.ui.auto_approve {
+        log(
+            state,
+            LogLevel::Info,
+            "Dangerous tools are denied in queued tasks unless /approve is on.",
+        );
+    }
 }
 
-fn queue_job(state: &mut AgentState, kind: JobKind, input: String) {
-    let id = state.next_job_id;
-    state.next_job_id += 1;
-    state.jobs.push(JobRecord {
-        id,
-        kind: kind.clone(),
-        input: input.clone(),
-        status: JobStatus::Queued,
-        output: None,
-    });
-    state.job_queue.push(JobRequest { id, kind, input });
-    log(state, LogLevel::Info, format!("Queued job #{}", id));
-    let _ = persistence::save(state);
+fn job_status_label(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Queued => "queued",
+        JobStatus::Running => "running",
+        JobStatus::Done => "done",
+        JobStatus::Failed => "failed",
+        JobStatus::Cancelled => "cancelled",
+    }
 }
 
 fn show_jobs(state: &mut AgentState) {
@@ -1137,18 +3154,11 @@ fn show_jobs(state: &mut AgentState) {
         .rev()
         .take(15)
         .map(|job| {
-            let status = match job.status {
-                JobStatus::Queued => "queued",
-                JobStatus::Running => "running",
-                JobStatus::Done => "done",
-                JobStatus::Failed => "failed",
-                JobStatus::Cancelled => "cancelled",
-            };
             format!(
                 "#{} [{}] {} - {}",
                 job.id,
                 job.kind.as_str(),
-                status,
+                job_status_label(&job.status),
                 compact_line(&job.input, 80)
             )
         })
@@ -1163,7 +3173,15 @@ fn show_plan(state: &mut AgentState) {
     log(
         state,
         LogLevel::Info,
-        format!("Plan mode: {}", if state.plan_mode { "on" } else { "off" }),
+        format!(
+            "Plan mode: {}{}",
+            if state.plan_mode { "on" } else { "off" },
+            if state.plan_approved {
+                " · plan approved"
+            } else {
+                ""
+            }
+        ),
     );
     if state.plan_items.is_empty() {
         log(state, LogLevel::Info, "Plan is empty.");
@@ -1174,8 +3192,13 @@ fn show_plan(state: &mut AgentState) {
         .iter()
         .enumerate()
         .map(|(idx, item)| {
+            let files = if item.files.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", item.files.join(", "))
+            };
             format!(
-                "{}. [{}] {}",
+                "{}. [{}] {}{files}",
                 idx + 1,
                 if item.done {
                     "x"
@@ -1215,6 +3238,9 @@ fn set_plan_mode(state: &mut AgentState, cmd: &str) {
         }
     };
     state.plan_mode = enabled;
+    if enabled {
+        state.plan_approved = false;
+    }
     log(
         state,
         LogLevel::Success,
@@ -1241,8 +3267,144 @@ fn plan_add(state: &mut AgentState, cmd: &str) {
         text: text.to_string(),
         done: false,
         active: state.plan_items.is_empty(),
+        files: Vec::new(),
     });
     log(state, LogLevel::Success, "Plan item added.");
+    plan_changed(state);
+    let _ = persistence::save(state);
+}
+
+/// Parses the step number after `prefix`, returning its index and the rest
+/// of the command.
+fn plan_step<'a>(state: &mut AgentState, cmd: &'a str, prefix: &str) -> Option<(usize, &'a str)> {
+    let rest = cmd.strip_prefix(prefix).map(str::trim).unwrap_or("");
+    let (n, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    match n.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= state.plan_items.len() => Some((n - 1, tail.trim())),
+        _ => {
+            log(state, LogLevel::Warn, "Plan item out of range.");
+            None
+        }
+    }
+}
+
+fn plan_edit(state: &mut AgentState, cmd: &str) {
+    let Some((idx, text)) = plan_step(state, cmd, "/plan edit") else {
+        return;
+    };
+    if text.is_empty() {
+        log(state, LogLevel::Warn, "Usage: /plan edit <id> <text>");
+        return;
+    }
+    state.plan_items[idx].text = text.to_string();
+    log(
+        state,
+        LogLevel::Success,
+        format!("Plan item {} updated.", idx + 1),
+    );
+    plan_changed(state);
+    let _ = persistence::save(state);
+}
+
+fn plan_files(state: &mut AgentState, cmd: &str) {
+    let Some((idx, files)) = plan_step(state, cmd, "/plan files") else {
+        return;
+    };
+    state.plan_items[idx].files = files
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|f| !f.is_empty())
+        .map(|f| f.trim_start_matches("./").to_string())
+        .collect();
+    let targets = if state.plan_items[idx].files.is_empty() {
+        "no files".to_string()
+    } else {
+        state.plan_items[idx].files.join(", ")
+    };
+    log(
+        state,
+        LogLevel::Success,
+        format!("Plan item {} targets {targets}.", idx + 1),
+    );
+    plan_changed(state);
+    let _ = persistence::save(state);
+}
+
+fn plan_drop(state: &mut AgentState, cmd: &str) {
+    let Some((idx, _)) = plan_step(state, cmd, "/plan drop") else {
+        return;
+    };
+    let removed = state.plan_items.remove(idx);
+    if removed.active {
+        if let Some(next) = state.plan_items.iter_mut().find(|item| !item.done) {
+            next.active = true;
+        }
+    }
+    log(
+        state,
+        LogLevel::Success,
+        format!("Plan item {} dropped: {}", idx + 1, removed.text),
+    );
+    plan_changed(state);
+    let _ = persistence::save(state);
+}
+
+/// An edit to an approved plan needs approving again.
+fn plan_changed(state: &mut AgentState) {
+    if state.plan_approved {
+        state.plan_approved = false;
+        log(
+            state,
+            LogLevel::Info,
+            "Plan changed; /plan approve to execute it.",
+        );
+    }
+}
+
+/// Approves the plan for execution: leaves plan mode, resumes at the first
+/// open step and launches a run that works through it.
+fn plan_approve(state: &mut AgentState) {
+    if state.plan_items.iter().all(|item| item.done) {
+        log(state, LogLevel::Warn, "No open plan steps to approve.");
+        return;
+    }
+    if state.ui.agent_running {
+        log(
+            state,
+            LogLevel::Warn,
+            "Wait for the current run to finish before approving the plan.",
+        );
+        return;
+    }
+    state.plan_mode = false;
+    state.plan_approved = true;
+    let first_open = state.plan_items.iter().position(|item| !item.done);
+    for (idx, item) in state.plan_items.iter_mut().enumerate() {
+        item.active = Some(idx) == first_open;
+    }
+    if let Err(e) = crate::tools::seed_plan(state.edit_root(), &state.plan_items) {
+        log(
+            state,
+            LogLevel::Warn,
+            format!("Could not store the plan: {e}"),
+        );
+    }
+    let files: BTreeSet<&str> = state
+        .plan_items
+        .iter()
+        .filter(|item| !item.done)
+        .flat_map(|item| item.files.iter().map(String::as_str))
+        .collect();
+    let scope = if files.is_empty() {
+        "any file".to_string()
+    } else {
+        files.into_iter().collect::<Vec<_>>().join(", ")
+    };
+    log(
+        state,
+        LogLevel::Success,
+        format!("Plan approved. Executing it; edits are limited to {scope}."),
+    );
+    state.ui.queued_agent_prompt = Some("Execute the approved plan.".to_string());
     let _ = persistence::save(state);
 }
 
@@ -1277,6 +3439,7 @@ fn plan_done(state: &mut AgentState, cmd: &str) {
 
 fn plan_clear(state: &mut AgentState) {
     state.plan_items.clear();
+    state.plan_approved = false;
     log(state, LogLevel::Info, "Plan cleared.");
     let _ = persistence::save(state);
 }
@@ -1444,7 +3607,7 @@ fn handle_gh_command(state: &mut AgentState, cmd: &str) {
 }
 
 fn gh_status(state: &mut AgentState) {
-    if !has_cmd("gh") {
+    if !platform::has_program("gh") {
         log(
             state,
             LogLevel::Error,
@@ -1739,8 +3902,14 @@ fn gh_run_triage(state: &mut AgentState, arg: &str) {
     let repo_slug = repo.replace('/', "_");
     let context_dir = state.repo_root.join(".context");
     let _ = fs::create_dir_all(&context_dir);
-    let report_json_path = context_dir.join(format!("triage-report-{}.json", repo_slug));
-    let report_md_path = context_dir.join(format!("triage-brief-{}.md", repo_slug));
+    let report_json_path = context_dir.join(instance::artifact_name(
+        &format!("triage-report-{repo_slug}"),
+        "json",
+    ));
+    let report_md_path = context_dir.join(instance::artifact_name(
+        &format!("triage-brief-{repo_slug}"),
+        "md",
+    ));
     let vision_default = state.repo_root.join("VISION.md");
 
     let mut args: Vec<String> = vec!["triage".to_string()];
@@ -1764,7 +3933,10 @@ fn gh_run_triage(state: &mut AgentState, arg: &str) {
     }
     if !has_state_file {
         args.push("--state-file".to_string());
-        args.push(format!(".context/triage-state-{}.json", repo_slug));
+        args.push(format!(
+            ".context/{}",
+            instance::artifact_name(&format!("triage-state-{repo_slug}"), "json")
+        ));
     }
     if !has_out {
         args.push("--out".to_string());
@@ -2086,7 +4258,7 @@ fn write_triage_brief(report: &Value, repo: &str, path: &PathBuf) -> Result<(),
 }
 
 fn ensure_gh_ready(state: &mut AgentState) -> bool {
-    if !has_cmd("gh") {
+    if !platform::has_program("gh") {
         log(
             state,
             LogLevel::Error,
@@ -2156,10 +4328,14 @@ fn open_nv(state: &mut AgentState, cmd: &str) {
         return;
     }
 
-    if !has_cmd("tmux") || !has_cmd("nvim") {
+    if !platform::has_program("tmux") || !platform::has_program("nvim") {
         let missing = [
-            if has_cmd("tmux") { None } else { Some("tmux") },
-            if has_cmd("nvim") {
+            if platform::has_program("tmux") {
+                None
+            } else {
+                Some("tmux")
+            },
+            if platform::has_program("nvim") {
                 None
             } else {
                 Some("neovim (nvim)")
@@ -2396,15 +4572,6 @@ fn shell_escape(s: &str) -> String {
     format!("'{}'", escaped)
 }
 
-fn has_cmd(name: &str) -> bool {
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("command -v {} >/dev/null 2>&1", name))
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-}
-
 fn ensure_nvim_ux_bootstrap(state: &mut AgentState) {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("~"));
     let cfg_dir = home.join(".config").join("nvim");
@@ -2714,16 +4881,129 @@ fn voice_status(state: &mut AgentState) {
         state,
         LogLevel::Info,
         format!(
-            "Voice: {} (connected: {}, url: {}, model: {})",
+            "Voice: {} (connected: {}, backend: {}, url: {}, model: {})",
             if state.voice.enabled { "on" } else { "off" },
             state.voice.connected,
+            state.voice.backend.label(),
             state.voice.url,
             state.voice.model
         ),
     );
+    let activation = voice_activation_label(&state.voice.activation, &state.voice.ptt_key);
+    log(state, LogLevel::Info, format!("Activation: {activation}"));
+    log(state, LogLevel::Info, status);
+}
+
+fn voice_activation_label(activation: &VoiceActivation, key: &PttKey) -> String {
+    match activation {
+        VoiceActivation::Continuous => "continuous".into(),
+        VoiceActivation::PushToTalk => format!("push-to-talk (hold {})", key.label()),
+        VoiceActivation::WakeWord(phrase) => format!("wake word \"{phrase}\""),
+    }
+}
+
+fn set_voice_activation(
+    state: &mut AgentState,
+    cmd: &str,
+    voice_tx: Option<&Sender<VoiceCommand>>,
+) {
+    state.voice.visible = true;
+    let rest = cmd.trim_start_matches("/voice").trim();
+    let activation = if let Some(key) = rest.strip_prefix("ptt") {
+        let key = key.trim();
+        if !key.is_empty() {
+            let Some(parsed) = PttKey::parse(key) else {
+                log(
+                    state,
+                    LogLevel::Error,
+                    format!("Unknown key: {key} (try f5, ctrl+space or alt+v)"),
+                );
+                return;
+            };
+            state.voice.ptt_key = parsed;
+        }
+        VoiceActivation::PushToTalk
+    } else if let Some(phrase) = rest.strip_prefix("wake") {
+        let phrase = phrase.trim();
+        if phrase.is_empty() {
+            log(state, LogLevel::Error, "Usage: /voice wake <phrase>");
+            return;
+        }
+        VoiceActivation::WakeWord(phrase.to_string())
+    } else {
+        VoiceActivation::Continuous
+    };
+
+    state.voice.ptt_held = false;
+    state.voice.ptt_last_key = None;
+    state.voice.ptt_transition = None;
+    state.voice.wake_armed = None;
+    if state.voice.connected {
+        if let Some(tx) = voice_tx {
+            let _ = tx.send(if activation == VoiceActivation::PushToTalk {
+                VoiceCommand::Pause
+            } else {
+                VoiceCommand::Resume
+            });
+        }
+    }
+    let label = voice_activation_label(&activation, &state.voice.ptt_key);
+    state.voice.activation = activation;
+    log(state, LogLevel::Info, format!("Voice activation: {label}"));
+}
+
+fn speak_status(state: &mut AgentState) {
+    let status = match (state.voice.speak, state.voice.speaking) {
+        (_, true) => "Read-back: speaking",
+        (true, false) => "Read-back: on",
+        (false, false) => "Read-back: off",
+    };
     log(state, LogLevel::Info, status);
 }
 
+fn set_speak(state: &mut AgentState, on: bool) {
+    state.voice.speak = on;
+    if !on {
+        state.voice.speech_stop_requested = true;
+    }
+    log(
+        state,
+        LogLevel::Info,
+        if on {
+            "Final answers will be read aloud."
+        } else {
+            "Read-back off."
+        },
+    );
+}
+
+fn voice_pause(state: &mut AgentState, voice_tx: Option<&Sender<VoiceCommand>>, pause: bool) {
+    state.voice.visible = true;
+    if !state.voice.connected {
+        log(state, LogLevel::Info, "Voice not connected.");
+        return;
+    }
+    if state.voice.push_to_talk() {
+        let key = state.voice.ptt_key.label();
+        log(
+            state,
+            LogLevel::Info,
+            format!("Push-to-talk controls the mic; hold {key} to talk."),
+        );
+        return;
+    }
+
+    if let Some(tx) = voice_tx {
+        let _ = tx.send(if pause {
+            VoiceCommand::Pause
+        } else {
+            VoiceCommand::Resume
+        });
+    } else {
+        log(state, LogLevel::Error, "Voice channel unavailable.");
+    }
+}
+
 fn voice_on(state: &mut AgentState, voice_tx: Option<&Sender<VoiceCommand>>) {
     state.voice.visible = true;
     if state.voice.enabled {
@@ -2735,6 +5015,7 @@ fn voice_on(state: &mut AgentState, voice_tx: Option<&Sender<VoiceCommand>>) {
         let _ = tx.send(VoiceCommand::Start {
             url: state.voice.url.clone(),
             model: state.voice.model.clone(),
+            paused: state.voice.push_to_talk(),
         });
         state.voice.enabled = true;
         log(state, LogLevel::Info, "Starting voice input...");
@@ -2761,38 +5042,49 @@ fn voice_off(state: &mut AgentState, voice_tx: Option<&Sender<VoiceCommand>>) {
 pub fn update_command_hints(state: &mut AgentState) {
     let input = normalize_command_prefix(state.ui.input.trim());
     let prev_selected = state.ui.command_selected;
-    let (items, selected) = command_hints_for(&input, prev_selected);
+    let (items, selected) = command_hints_for(&input, prev_selected, &state.ui.custom_commands);
 
     state.ui.command_items = items;
     state.ui.command_selected = selected;
 }
 
-fn command_hints_for(input: &str, prev_selected: usize) -> (Vec<CommandItem>, usize) {
+fn command_hints_for(
+    input: &str,
+    prev_selected: usize,
+    custom: &[CustomCommand],
+) -> (Vec<CommandItem>, usize) {
     if !input.starts_with('/') {
         return (Vec::new(), 0);
     }
 
-    let all = command_palette_items();
+    let all = command_palette_items()
+        .iter()
+        .cloned()
+        .chain(custom.iter().map(|custom| CommandItem {
+            cmd: custom.name.clone(),
+            desc: custom.description.clone(),
+        }))
+        .collect::<Vec<_>>();
     if all.iter().any(|item| item.cmd == input) {
         return (Vec::new(), 0);
     }
 
     let mut items = Vec::new();
-    for item in all {
+    for item in &all {
         if input == "/" || item.cmd.starts_with(input) {
-            items.push(*item);
+            items.push(item.clone());
         }
     }
 
     if items.is_empty() {
-        for item in all {
+        for item in &all {
             if item.cmd.contains(input)
                 || item
                     .desc
                     .to_ascii_lowercase()
                     .contains(&input[1..].to_ascii_lowercase())
             {
-                items.push(*item);
+                items.push(item.clone());
             }
         }
     }
@@ -2807,152 +5099,7 @@ fn command_hints_for(input: &str, prev_selected: usize) -> (Vec<CommandItem>, us
 }
 
 fn command_palette_items() -> &'static [CommandItem] {
-    &[
-        CommandItem {
-            cmd: "/help",
-            desc: "Show available commands",
-        },
-        CommandItem {
-            cmd: "/clear",
-            desc: "Clear logs",
-        },
-        CommandItem {
-            cmd: "/key",
-            desc: "Set OpenAI API key",
-        },
-        CommandItem {
-            cmd: "/voice",
-            desc: "Show voice status",
-        },
-        CommandItem {
-            cmd: "/voice on",
-            desc: "Start voice input",
-        },
-        CommandItem {
-            cmd: "/voice off",
-            desc: "Stop voice input",
-        },
-        CommandItem {
-            cmd: "/status",
-            desc: "Show session, run, model, and repo status",
-        },
-        CommandItem {
-            cmd: "/account",
-            desc: "Show provider/account configuration",
-        },
-        CommandItem {
-            cmd: "/model",
-            desc: "Show active provider/model",
-        },
-        CommandItem {
-            cmd: "/usage",
-            desc: "Show token and context usage",
-        },
-        CommandItem {
-            cmd: "/theme",
-            desc: "Show or set dark/light theme",
-        },
-        CommandItem {
-            cmd: "/color",
-            desc: "Show or set accent color",
-        },
-        CommandItem {
-            cmd: "/type",
-            desc: "Show or set UI density",
-        },
-        CommandItem {
-            cmd: "/copy",
-            desc: "Copy latest assistant response",
-        },
-        CommandItem {
-            cmd: "/copy all",
-            desc: "Copy visible transcript",
-        },
-        CommandItem {
-            cmd: "/rename",
-            desc: "Rename current session",
-        },
-        CommandItem {
-            cmd: "/mcp",
-            desc: "Show MCP status and servers",
-        },
-        CommandItem {
-            cmd: "/providers",
-            desc: "Show available model providers",
-        },
-        CommandItem {
-            cmd: "/undo",
-            desc: "Revert the last agent file change",
-        },
-        CommandItem {
-            cmd: "/diff",
-            desc: "Show all file changes this session",
-        },
-        CommandItem {
-            cmd: "/compact",
-            desc: "Compress conversation context",
-        },
-        CommandItem {
-            cmd: "/metrics",
-            desc: "Show usage and queue metrics",
-        },
-        CommandItem {
-            cmd: "/profile",
-            desc: "Show or set permission profile",
-        },
-        CommandItem {
-            cmd: "/approve",
-            desc: "Toggle dangerous tool auto-approve",
-        },
-        CommandItem {
-            cmd: "/new",
-            desc: "Start a fresh conversation",
-        },
-        CommandItem {
-            cmd: "/plan",
-            desc: "Show/update plan items",
-        },
-        CommandItem {
-            cmd: "/plan add",
-            desc: "Add a plan item",
-        },
-        CommandItem {
-            cmd: "/plan done",
-            desc: "Mark a plan item complete",
-        },
-        CommandItem {
-            cmd: "/plan clear",
-            desc: "Clear plan items",
-        },
-        CommandItem {
-            cmd: "/plan mode",
-            desc: "Toggle plan-only read-only agent mode",
-        },
-        CommandItem {
-            cmd: "/steer",
-            desc: "Set or show persistent steer instruction",
-        },
-        CommandItem {
-            cmd: "/jobs",
-            desc: "Show background jobs",
-        },
-        CommandItem {
-            cmd: "/nv",
-            desc: "Open Neovim split (auto tmux bootstrap)",
-        },
-        CommandItem {
-            cmd: "/quit",
-            desc: "Stop agent execution",
-        },
-        CommandItem {
-            cmd: "/q",
-            desc: "Stop agent execution",
-        },
-        CommandItem {
-            cmd: "/exit",
-            desc: "Exit Osmogrep",
-        },
-    ]
+    command_docs::palette_items()
 }
 
 fn normalize_command_prefix(input: &str) -> String {
@@ -2969,13 +5116,14 @@ fn normalize_command_prefix(input: &str) -> String {
 mod tests {
     use super::{
         assistant_response_blocks, command_hints_for, latest_assistant_response, parse_copy_target,
-        transcript_text, CopyTarget,
+        transcript_text, CopyTarget, CustomCommand,
     };
     use crate::state::{LogBuffer, LogLevel};
+    use std::path::PathBuf;
 
     #[test]
     fn command_hints_include_plan_while_typing() {
-        let (items, selected) = command_hints_for("/pla", 0);
+        let (items, selected) = command_hints_for("/pla", 0, &[]);
 
         assert_eq!(selected, 0);
         assert!(items.iter().any(|item| item.cmd == "/plan"));
@@ -2984,7 +5132,7 @@ mod tests {
 
     #[test]
     fn command_hints_hide_after_exact_plan_command() {
-        let (items, selected) = command_hints_for("/plan", 4);
+        let (items, selected) = command_hints_for("/plan", 4, &[]);
 
         assert!(items.is_empty());
         assert_eq!(selected, 0);
@@ -2992,12 +5140,30 @@ mod tests {
 
     #[test]
     fn command_hints_include_copy_commands() {
-        let (items, _) = command_hints_for("/cop", 0);
+        let (items, _) = command_hints_for("/cop", 0, &[]);
 
         assert!(items.iter().any(|item| item.cmd == "/copy"));
         assert!(items.iter().any(|item| item.cmd == "/copy all"));
     }
 
+    #[test]
+    fn command_hints_offer_custom_commands() {
+        let custom = CustomCommand {
+            name: "/release-notes".to_string(),
+            description: "Draft release notes".to_string(),
+            args: String::new(),
+            template: "Write release notes.".to_string(),
+            path: PathBuf::from(".osmogrep/commands/release-notes.md"),
+        };
+        let (items, _) = command_hints_for("/rel", 0, std::slice::from_ref(&custom));
+
+        assert!(items.iter().any(|item| item.cmd == "/release-notes"));
+        assert!(items.iter().any(|item| item.cmd == "/reload"));
+        assert!(command_hints_for("/release-notes", 0, &[custom])
+            .0
+            .is_empty());
+    }
+
     #[test]
     fn copy_target_parses_latest_number_and_transcript() {
         assert!(matches!(
@@ -3053,11 +5219,6 @@ mod tests {
     }
 }
 
-fn estimated_usage_cost(state: &AgentState) -> f64 {
-    ((state.usage.prompt_tokens as f64) * 0.0000025)
-        + ((state.usage.completion_tokens as f64) * 0.0000100)
-}
-
 fn mask_secret(value: &str) -> String {
     let trimmed = value.trim();
     if trimmed.is_empty() {
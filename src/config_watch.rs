@@ -0,0 +1,218 @@
+//! config_watch.rs
+//!
+//! Notices edits to `config.toml` while the TUI is running and works out
+//! which settings changed, so `/reload` and the watcher in the main loop
+//! can apply them without a restart and say exactly what moved.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use toml::Value;
+
+/// How often the main loop may stat the config file.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const SECRET_MARKERS: &[&str] = &["key", "token", "secret", "password"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub key: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Change {
+    pub fn describe(&self) -> String {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => format!("{}: {before} → {after}", self.key),
+            (None, Some(after)) => format!("{}: set to {after}", self.key),
+            (Some(before), None) => format!("{}: removed (was {before})", self.key),
+            (None, None) => self.key.clone(),
+        }
+    }
+}
+
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    snapshot: BTreeMap<String, String>,
+    last_poll: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let snapshot = read_flat(&path).unwrap_or_default();
+        Self {
+            modified: modified(&path),
+            path,
+            snapshot,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file was written since the last check. Stats the file at
+    /// most once per `POLL_INTERVAL`.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let current = modified(&self.path);
+        if current == self.modified {
+            return false;
+        }
+        self.modified = current;
+        true
+    }
+
+    /// Re-read and validate the file. On success the new version becomes
+    /// the baseline and the changed settings are returned; on failure the
+    /// previous settings stay in effect.
+    pub fn reload(
+        &mut self,
+        validate: impl Fn(&str) -> Result<(), String>,
+    ) -> Result<Vec<Change>, String> {
+        self.modified = modified(&self.path);
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {e}", self.path.display())),
+        };
+        let flat = flatten(&text)?;
+        validate(&text)?;
+        let changes = diff(&self.snapshot, &flat);
+        self.snapshot = flat;
+        Ok(changes)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_flat(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    flatten(&fs::read_to_string(path).map_err(|e| e.to_string())?)
+}
+
+/// Dotted keys (`model.provider`, `mcp.servers.git.cmd`) to display values.
+/// Secrets are masked; proxy credentials are redacted.
+pub fn flatten(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let value: Value = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut out = BTreeMap::new();
+    flatten_into(&mut out, String::new(), &value);
+    Ok(out)
+}
+
+fn flatten_into(out: &mut BTreeMap<String, String>, prefix: String, value: &Value) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_into(out, key, value);
+            }
+        }
+        Value::String(s) => {
+            let leaf = prefix
+                .rsplit('.')
+                .next()
+                .unwrap_or(&prefix)
+                .to_ascii_lowercase();
+            let shown =
+                if SECRET_MARKERS.iter().any(|m| leaf.contains(m)) && !leaf.ends_with("_env") {
+                    format!("*** ({} chars)", s.chars().count())
+                } else if s.contains("://") {
+                    crate::net::redact_proxy(s)
+                } else {
+                    format!("{s:?}")
+                };
+            out.insert(prefix, shown);
+        }
+        other => {
+            out.insert(prefix, other.to_string());
+        }
+    }
+}
+
+pub fn diff(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<Change> {
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| Change {
+            key: key.clone(),
+            before: old.get(key).cloned(),
+            after: new.get(key).cloned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn diffs_flattened_settings_and_masks_secrets() {
+        let old = flatten(
+            "api_key = \"sk-old\"\n[model]\nprovider = \"openai\"\nmodel = \"gpt-5.2\"\n\
+             api_key_env = \"OPENAI_API_KEY\"\n",
+        )
+        .unwrap();
+        let new = flatten(
+            "api_key = \"sk-newer\"\n[model]\nprovider = \"openai\"\nmodel = \"gpt-5.3\"\n\
+             api_key_env = \"OPENAI_API_KEY\"\n[network]\nproxy = \"http://u:p@proxy:3128\"\n",
+        )
+        .unwrap();
+
+        let lines: Vec<String> = diff(&old, &new).iter().map(Change::describe).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "api_key: *** (6 chars) → *** (8 chars)",
+                "model.model: \"gpt-5.2\" → \"gpt-5.3\"",
+                "network.proxy: set to http://***@proxy:3128/",
+            ]
+        );
+        assert!(flatten("[model\n").is_err());
+    }
+
+    #[test]
+    fn reload_keeps_baseline_on_invalid_config() {
+        let path = std::env::temp_dir().join(format!("osmogrep-config-{}.toml", Uuid::new_v4()));
+        fs::write(&path, "[editor]\ncommand = \"vim +{line} {path}\"\n").unwrap();
+        let mut watcher = ConfigWatcher::new(path.clone());
+        assert!(!watcher.changed());
+
+        fs::write(&path, "[editor]\ncommand = 3\n").unwrap();
+        let err = watcher
+            .reload(|_| Err("editor.command must be a string".into()))
+            .unwrap_err();
+        assert!(err.contains("editor.command"));
+
+        fs::write(
+            &path,
+            "[editor]\ncommand = \"hx {path}:{line}\"\nrun = true\n",
+        )
+        .unwrap();
+        let changes = watcher.reload(|_| Ok(())).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key, "editor.command");
+        assert_eq!(changes[0].before.as_deref(), Some("\"vim +{line} {path}\""));
+        assert_eq!(changes[1].describe(), "editor.run: set to true");
+        assert!(watcher.reload(|_| Ok(())).unwrap().is_empty());
+        let _ = fs::remove_file(path);
+    }
+}
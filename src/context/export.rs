@@ -0,0 +1,364 @@
+//! context/export.rs
+//!
+//! `.context/context.json`, the index as the agent reads it. Next to the
+//! files and symbols it carries repo facts (indexed languages, manifests,
+//! test frameworks and commands) and the import graph between indexed
+//! files. Rewritten whenever the index is built or refreshed.
+//!
+//! The export is kept under `MAX_BYTES`: past it, docs, library calls and
+//! raw imports are dropped first, then the symbols with the fewest callers,
+//! and `budget` says what was left out.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::context::indexer::{self, Context, FileInfo, RepoStats, Symbol};
+use crate::test_harness;
+
+pub const FILE: &str = ".context/context.json";
+/// Bump when the exported shape changes (and `indexer::INDEX_VERSION` with
+/// it, so cached checkouts export again).
+pub const SCHEMA_VERSION: u32 = 1;
+pub const MAX_BYTES: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoFacts {
+    /// Indexed files per language.
+    pub languages: BTreeMap<String, usize>,
+    pub manifests: Vec<String>,
+    pub test_frameworks: Vec<String>,
+    pub test_commands: Vec<String>,
+}
+
+impl RepoFacts {
+    pub fn detect(repo_root: &Path, ctx: &Context) -> Self {
+        let mut languages = BTreeMap::new();
+        for file in &ctx.files {
+            *languages.entry(file.language.clone()).or_default() += 1;
+        }
+        Self {
+            languages,
+            manifests: crate::agent::detect_manifests(repo_root),
+            test_frameworks: test_harness::detect_frameworks(repo_root)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            test_commands: crate::agent::detect_verify_commands(repo_root),
+        }
+    }
+}
+
+/// What the size budget left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Budget {
+    pub max_bytes: usize,
+    /// Symbol docs, `calls`, `lang_calls` and file `imports` were dropped.
+    pub details_dropped: bool,
+    pub symbols_omitted: usize,
+}
+
+#[derive(Serialize)]
+struct Export<'a> {
+    schema_version: u32,
+    facts: &'a RepoFacts,
+    stats: &'a RepoStats,
+    files: &'a [FileInfo],
+    /// Indexed file -> indexed files it imports.
+    import_graph: &'a BTreeMap<String, Vec<String>>,
+    symbols: &'a [Symbol],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget: Option<Budget>,
+}
+
+/// Writes the export of `ctx` to `FILE` under `repo_root`.
+pub fn write(repo_root: &Path, ctx: &Context) -> Result<(), String> {
+    let json = render(repo_root, ctx, MAX_BYTES)?;
+    let path = repo_root.join(FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    indexer::write_atomic(&path, json.as_bytes())
+}
+
+/// The export as pretty JSON, trimmed to `max_bytes` where possible.
+pub fn render(repo_root: &Path, ctx: &Context, max_bytes: usize) -> Result<String, String> {
+    let facts = RepoFacts::detect(repo_root, ctx);
+    let graph = import_graph(repo_root, &ctx.files);
+    let to_json = |files: &[FileInfo], symbols: &[Symbol], budget: Option<Budget>| {
+        serde_json::to_string_pretty(&Export {
+            schema_version: SCHEMA_VERSION,
+            facts: &facts,
+            stats: &ctx.stats,
+            files,
+            import_graph: &graph,
+            symbols,
+            budget,
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    let full = to_json(&ctx.files, &ctx.symbols, None)?;
+    if full.len() <= max_bytes {
+        return Ok(full);
+    }
+
+    let files: Vec<FileInfo> = ctx
+        .files
+        .iter()
+        .map(|f| FileInfo {
+            imports: Vec::new(),
+            ..f.clone()
+        })
+        .collect();
+    let mut symbols: Vec<Symbol> = ctx
+        .symbols
+        .iter()
+        .map(|s| Symbol {
+            doc: None,
+            calls: Vec::new(),
+            lang_calls: Vec::new(),
+            ..s.clone()
+        })
+        .collect();
+    // Most-called symbols are kept longest.
+    symbols.sort_by_key(|s| std::cmp::Reverse(s.called_by.len()));
+    let budget = |kept: usize| Budget {
+        max_bytes,
+        details_dropped: true,
+        symbols_omitted: symbols.len() - kept,
+    };
+
+    let slim = to_json(&files, &symbols, Some(budget(symbols.len())))?;
+    if slim.len() <= max_bytes {
+        return Ok(slim);
+    }
+    // Largest symbol count that fits; with none kept it may still not fit.
+    let (mut low, mut high) = (0, symbols.len());
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if to_json(&files, &symbols[..mid], Some(budget(mid)))?.len() <= max_bytes {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    to_json(&files, &symbols[..low], Some(budget(low)))
+}
+
+/// Edges from each file to the indexed files its imports resolve to.
+/// External crates and packages have no file and are left out.
+pub fn import_graph(repo_root: &Path, files: &[FileInfo]) -> BTreeMap<String, Vec<String>> {
+    let relative = |path: &str| {
+        Path::new(path)
+            .strip_prefix(repo_root)
+            .unwrap_or(Path::new(path))
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let modules: HashMap<(&str, Vec<String>), &str> = files
+        .iter()
+        .map(|f| {
+            (
+                (f.language.as_str(), module_path(&relative(&f.path))),
+                f.path.as_str(),
+            )
+        })
+        .collect();
+
+    let mut graph = BTreeMap::new();
+    for file in files {
+        let own = module_path(&relative(&file.path));
+        let mut targets: Vec<String> = file
+            .imports
+            .iter()
+            .filter_map(|import| {
+                let mut segments = match file.language.as_str() {
+                    "rust" => rust_target(&own, import)?,
+                    "python" => python_target(&file.path, &own, import),
+                    _ => return None,
+                };
+                // `crate::a::b::Item` lives in the longest prefix with a file.
+                loop {
+                    match modules.get(&(file.language.as_str(), segments.clone())) {
+                        Some(target) if *target != file.path => return Some(target.to_string()),
+                        _ => segments.pop()?,
+                    };
+                }
+            })
+            .collect();
+        targets.sort();
+        targets.dedup();
+        if !targets.is_empty() {
+            graph.insert(file.path.clone(), targets);
+        }
+    }
+    graph
+}
+
+/// Module segments of a repo-relative source path: `src/a/mod.rs` and
+/// `src/a.rs` are `a`, `pkg/__init__.py` is `pkg`, `src/lib.rs` is the root.
+fn module_path(relative: &str) -> Vec<String> {
+    let stem = relative.rsplit_once('.').map_or(relative, |(stem, _)| stem);
+    let mut segments: Vec<String> = stem.split('/').map(String::from).collect();
+    if segments.len() > 1 && segments[0] == "src" {
+        segments.remove(0);
+    }
+    if matches!(
+        segments.last().map(String::as_str),
+        Some("mod" | "__init__")
+    ) || segments == ["lib"]
+        || segments == ["main"]
+    {
+        segments.pop();
+    }
+    segments
+}
+
+fn is_package(path: &str) -> bool {
+    ["mod.rs", "__init__.py", "lib.rs", "main.rs"]
+        .iter()
+        .any(|name| path == *name || path.ends_with(&format!("/{name}")))
+}
+
+/// Absolute module segments of a Rust `use` path inside `own`; `None` for
+/// other crates.
+fn rust_target(own: &[String], import: &str) -> Option<Vec<String>> {
+    let mut parts = import.split("::");
+    let mut base = match parts.next()? {
+        "crate" => Vec::new(),
+        "self" => own.to_vec(),
+        "super" => own[..own.len().saturating_sub(1)].to_vec(),
+        _ => return None,
+    };
+    for part in parts {
+        match part {
+            "super" => {
+                base.pop();
+            }
+            _ => base.push(part.to_string()),
+        }
+    }
+    Some(base)
+}
+
+/// Absolute module segments of a Python import; leading dots are relative
+/// to the importing file's package.
+fn python_target(path: &str, own: &[String], import: &str) -> Vec<String> {
+    let dots = import.len() - import.trim_start_matches('.').len();
+    let mut base = if dots == 0 {
+        Vec::new()
+    } else if is_package(path) {
+        own.to_vec()
+    } else {
+        own[..own.len().saturating_sub(1)].to_vec()
+    };
+    for _ in 1..dots.max(1) {
+        base.pop();
+    }
+    base.extend(
+        import
+            .trim_start_matches('.')
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .map(String::from),
+    );
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    #[test]
+    fn exports_facts_and_import_graph_within_the_budget() {
+        let root = std::env::temp_dir().join(format!("osmogrep-export-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src/store")).unwrap();
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "mod store;\nuse crate::store::{self, disk::Disk};\nuse std::fs;\nfn open() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/store/mod.rs"), "pub mod disk;\n").unwrap();
+        fs::write(
+            root.join("src/store/disk.rs"),
+            "use super::super::open;\n/// Writes.\nfn save() { open() }\n",
+        )
+        .unwrap();
+        fs::write(root.join("app/__init__.py"), "").unwrap();
+        fs::write(root.join("app/db.py"), "def query():\n    pass\n").unwrap();
+        fs::write(
+            root.join("app/views.py"),
+            "import os\nfrom .db import query\nfrom app import db\n\ndef index():\n    query()\n",
+        )
+        .unwrap();
+
+        let ctx = indexer::load_or_build(&root);
+        let key = |rel: &str| root.join(rel).display().to_string();
+        let lib = ctx
+            .files
+            .iter()
+            .find(|f| f.path == key("src/lib.rs"))
+            .unwrap();
+        assert_eq!(
+            lib.imports,
+            [
+                "self::store",
+                "crate::store",
+                "crate::store::disk::Disk",
+                "std::fs"
+            ]
+        );
+
+        let export: Value =
+            serde_json::from_str(&fs::read_to_string(root.join(FILE)).unwrap()).unwrap();
+        assert_eq!(export["schema_version"], SCHEMA_VERSION);
+        assert_eq!(export["facts"]["languages"]["rust"], 3);
+        assert_eq!(export["facts"]["manifests"][0], "Cargo.toml");
+        assert_eq!(export["facts"]["test_frameworks"][0], "cargo");
+        let graph = &export["import_graph"];
+        assert_eq!(
+            graph[key("src/lib.rs")],
+            serde_json::json!([key("src/store/disk.rs"), key("src/store/mod.rs")])
+        );
+        assert_eq!(
+            graph[key("src/store/disk.rs")],
+            serde_json::json!([key("src/lib.rs")])
+        );
+        assert_eq!(
+            graph[key("app/views.py")],
+            serde_json::json!([key("app/__init__.py"), key("app/db.py")])
+        );
+        assert!(export.get("budget").is_none());
+
+        let full = render(&root, &ctx, MAX_BYTES).unwrap();
+        let slim = render(&root, &ctx, full.len() - 1).unwrap();
+        let slim_json: Value = serde_json::from_str(&slim).unwrap();
+        assert_eq!(slim_json["budget"]["details_dropped"], true);
+        assert_eq!(slim_json["budget"]["symbols_omitted"], 0);
+        assert!(slim_json["symbols"][0]["doc"].is_null());
+
+        // Symbols nothing calls are the first to go.
+        let tight = render(&root, &ctx, slim.len() - 1).unwrap();
+        assert!(tight.len() < slim.len());
+        let tight_json: Value = serde_json::from_str(&tight).unwrap();
+        assert_eq!(tight_json["budget"]["symbols_omitted"], 1);
+        let kept: Vec<&str> = tight_json["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(kept.len(), 3);
+        assert!(kept.contains(&"open") && kept.contains(&"query"));
+        let _ = fs::remove_dir_all(root);
+    }
+}
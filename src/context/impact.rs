@@ -0,0 +1,190 @@
+//! context/impact.rs
+//!
+//! Transitive impact of a symbol over the call graph in the context index.
+//! The indexer records each function's callees (`calls`, resolved to
+//! `custom_calls`) and the reverse `called_by` edges; walking `called_by`
+//! outwards gives every function whose behavior may change with the
+//! symbol. Edges are by name, so a name defined twice counts as both.
+//! Used by `/impact` and the `impact_analysis` tool.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::context::indexer::Context;
+
+pub const DEFAULT_DEPTH: usize = 6;
+/// Affected functions reported before the walk stops.
+pub const MAX_AFFECTED: usize = 200;
+
+/// A function that reaches the symbol through `depth` calls.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Affected {
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+    pub depth: usize,
+    /// The function it calls on the way to the symbol.
+    pub via: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Impact {
+    pub symbol: String,
+    /// `path:line` of each definition of the symbol.
+    pub defined_at: Vec<String>,
+    pub affected: Vec<Affected>,
+    /// Stopped at `MAX_AFFECTED`, or callers remained past the depth limit.
+    pub truncated: bool,
+}
+
+/// Functions that call `symbol` directly or through up to `max_depth`
+/// calls, nearest first.
+pub fn analyze(
+    ctx: &Context,
+    repo_root: &Path,
+    symbol: &str,
+    max_depth: usize,
+) -> Result<Impact, String> {
+    let relative = |file: &str| {
+        let path = Path::new(file);
+        path.strip_prefix(repo_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+    let defined_at: Vec<String> = ctx
+        .symbols
+        .iter()
+        .filter(|s| s.name == symbol)
+        .map(|s| format!("{}:{}", relative(&s.file), s.line_start))
+        .collect();
+    if defined_at.is_empty() {
+        return Err(format!("`{symbol}` is not in the symbol index"));
+    }
+
+    let mut seen: BTreeSet<&str> = BTreeSet::from([symbol]);
+    let mut queue: VecDeque<(&str, usize)> = VecDeque::from([(symbol, 0)]);
+    let mut affected = Vec::new();
+    let mut truncated = false;
+    while let Some((name, depth)) = queue.pop_front() {
+        let callers: BTreeSet<&str> = ctx
+            .symbols
+            .iter()
+            .filter(|s| s.name == name)
+            .flat_map(|s| s.called_by.iter().map(String::as_str))
+            .filter(|caller| !seen.contains(caller))
+            .collect();
+        if depth == max_depth {
+            truncated |= !callers.is_empty();
+            continue;
+        }
+        for caller in callers {
+            seen.insert(caller);
+            queue.push_back((caller, depth + 1));
+            for definition in ctx.symbols.iter().filter(|s| s.name == caller) {
+                if affected.len() == MAX_AFFECTED {
+                    truncated = true;
+                    break;
+                }
+                affected.push(Affected {
+                    name: caller.to_string(),
+                    file: relative(&definition.file),
+                    line: definition.line_start,
+                    depth: depth + 1,
+                    via: name.to_string(),
+                });
+            }
+        }
+        if affected.len() == MAX_AFFECTED {
+            break;
+        }
+    }
+    Ok(Impact {
+        symbol: symbol.to_string(),
+        defined_at,
+        affected,
+        truncated,
+    })
+}
+
+/// Report lines for `/impact`, one per affected function.
+pub fn render(impact: &Impact) -> Vec<String> {
+    let mut lines = vec![format!(
+        "● {} ({}): {} affected function(s)",
+        impact.symbol,
+        impact.defined_at.join(", "),
+        impact.affected.len()
+    )];
+    for affected in &impact.affected {
+        lines.push(format!(
+            "  {}{} ({}:{}) via {}",
+            "  ".repeat(affected.depth - 1),
+            affected.name,
+            affected.file,
+            affected.line,
+            affected.via
+        ));
+    }
+    if impact.truncated {
+        lines.push("  … more callers past the limit".to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::indexer;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn walks_callers_transitively_nearest_first() {
+        let root = std::env::temp_dir().join(format!("osmogrep-impact-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "fn parse() {}\n\
+             fn load() { parse() }\n\
+             fn run() { load(); parse() }\n\
+             fn main() { run() }\n\
+             fn unrelated() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/cli.py"), "def cli():\n    main()\n").unwrap();
+        let ctx = indexer::load_or_build(&root);
+
+        let impact = analyze(&ctx, &root, "parse", DEFAULT_DEPTH).unwrap();
+        assert_eq!(impact.defined_at, ["src/lib.rs:1"]);
+        let walked: Vec<(&str, usize, &str)> = impact
+            .affected
+            .iter()
+            .map(|a| (a.name.as_str(), a.depth, a.via.as_str()))
+            .collect();
+        assert_eq!(
+            walked,
+            [
+                ("load", 1, "parse"),
+                ("run", 1, "parse"),
+                ("main", 2, "run"),
+                ("cli", 3, "main"),
+            ]
+        );
+        assert!(!impact.truncated);
+        assert_eq!(render(&impact)[4], "      cli (src/cli.py:1) via main");
+
+        let shallow = analyze(&ctx, &root, "parse", 1).unwrap();
+        assert_eq!(shallow.affected.len(), 2);
+        assert!(shallow.truncated);
+        assert!(analyze(&ctx, &root, "unrelated", DEFAULT_DEPTH)
+            .unwrap()
+            .affected
+            .is_empty());
+        assert!(analyze(&ctx, &root, "missing", DEFAULT_DEPTH).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
@@ -11,15 +12,20 @@ use tree_sitter_rust as rust;
 
 use blake3::Hasher;
 
+use crate::binary_assets;
+use crate::context::export;
+
 /* ======================= CONFIG ======================= */
 
 const CONTEXT_DIR: &str = ".context";
-const CONTEXT_FILE: &str = "context.json";
-const META_FILE: &str = "meta.json";
+const INDEX_FILE: &str = "index.bin";
+// Bump when `Index` or anything inside `Context` changes shape, or with
+// `export::SCHEMA_VERSION` so existing checkouts write the new export.
+const INDEX_VERSION: u32 = 2;
 
 /* ======================= DATA MODEL ======================= */
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct RepoStats {
     pub file_count: usize,
     pub total_bytes: u64,
@@ -32,6 +38,10 @@ pub struct FileInfo {
     pub language: String,
     pub bytes: u64,
     pub lines: usize,
+    /// Module paths as written: `use` paths (braces expanded) and `mod x;`
+    /// as `self::x` in Rust, `import`/`from` modules in Python.
+    #[serde(default)]
+    pub imports: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -62,10 +72,28 @@ pub struct Context {
     pub symbols: Vec<Symbol>,
 }
 
+/// What a file looked like when it was last hashed. Files whose size and
+/// mtime still match are not read again on the next launch.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct FileStamp {
+    hash: String,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
 #[derive(Serialize, Deserialize)]
-struct Meta {
-    stats: RepoStats,
-    file_hashes: HashMap<String, String>,
+struct Index {
+    version: u32,
+    files: HashMap<String, FileStamp>,
+    context: Context,
+}
+
+// Same layout as `Index`, so the context need not be cloned to save it.
+#[derive(Serialize)]
+struct IndexRef<'a> {
+    version: u32,
+    files: &'a HashMap<String, FileStamp>,
+    context: &'a Context,
 }
 
 /* ======================= PUBLIC ENTRY ======================= */
@@ -73,60 +101,110 @@ struct Meta {
 pub fn load_or_build(root: impl AsRef<Path>) -> Context {
     let root = root.as_ref();
     let ctx_dir = root.join(CONTEXT_DIR);
-    let ctx_path = ctx_dir.join(CONTEXT_FILE);
-    let meta_path = ctx_dir.join(META_FILE);
-
-    let current_stats = compute_repo_stats(root);
-    let current_hashes = compute_file_hashes(root);
-
-    if let (Ok(ctx_raw), Ok(meta_raw)) = (
-        fs::read_to_string(&ctx_path),
-        fs::read_to_string(&meta_path),
-    ) {
-        if let (Ok(mut ctx), Ok(meta)) = (
-            serde_json::from_str::<Context>(&ctx_raw),
-            serde_json::from_str::<Meta>(&meta_raw),
-        ) {
-            if meta.stats == current_stats {
-                incremental_update(root, &mut ctx, &meta.file_hashes, &current_hashes);
-
-                fs::write(&ctx_path, serde_json::to_string_pretty(&ctx).unwrap()).unwrap();
-
-                fs::write(
-                    &meta_path,
-                    serde_json::to_string_pretty(&Meta {
-                        stats: current_stats,
-                        file_hashes: current_hashes,
-                    })
-                    .unwrap(),
-                )
-                .unwrap();
-
-                return ctx;
-            }
+    let ctx_path = root.join(export::FILE);
+
+    let cached = read_index(&ctx_dir);
+    let stamps = compute_file_stamps(root, cached.as_ref().map(|i| &i.files));
+    let hashes = hashes_of(&stamps);
+
+    let ctx = match cached {
+        Some(index) if index.files == stamps && ctx_path.exists() => return index.context,
+        Some(mut index) => {
+            // Only files whose hash moved are re-parsed, however much changed.
+            incremental_update(root, &mut index.context, &hashes_of(&index.files), &hashes);
+            index.context.stats = stats_of(&index.context.files);
+            index.context
         }
+        None => build_context(root, &hashes),
+    };
+
+    fs::create_dir_all(&ctx_dir).ok();
+    export::write(root, &ctx).unwrap();
+    write_index(&ctx_dir, &stamps, &ctx).unwrap();
+
+    ctx
+}
+
+/// Re-parses just `paths` (as keyed by the index) into the cached context,
+/// dropping the ones that no longer exist. Returns how many changed.
+pub fn refresh_paths(root: impl AsRef<Path>, paths: &[String]) -> Result<usize, String> {
+    let ctx_dir = root.as_ref().join(CONTEXT_DIR);
+    let mut index = read_index(&ctx_dir)
+        .ok_or_else(|| format!("{} is missing or stale", ctx_dir.join(INDEX_FILE).display()))?;
+
+    let old = hashes_of(&index.files);
+    for path in paths {
+        match stamp_file(Path::new(path), None) {
+            Some(stamp) => index.files.insert(path.clone(), stamp),
+            None => index.files.remove(path),
+        };
+    }
+    let hashes = hashes_of(&index.files);
+    let changed = paths
+        .iter()
+        .filter(|p| old.get(*p) != hashes.get(*p))
+        .count();
+    if changed == 0 {
+        return Ok(0);
     }
 
-    let ctx = build_context(root, current_stats.clone(), &current_hashes);
+    incremental_update(root.as_ref(), &mut index.context, &old, &hashes);
+    index.context.stats = stats_of(&index.context.files);
 
-    fs::create_dir_all(&ctx_dir).ok();
-    fs::write(&ctx_path, serde_json::to_string_pretty(&ctx).unwrap()).unwrap();
-    fs::write(
-        &meta_path,
-        serde_json::to_string_pretty(&Meta {
-            stats: current_stats,
-            file_hashes: current_hashes,
+    export::write(root.as_ref(), &index.context)?;
+    write_index(&ctx_dir, &index.files, &index.context)?;
+
+    Ok(changed)
+}
+
+/// Modification times of every indexable file, keyed like the index.
+pub fn source_mtimes(root: impl AsRef<Path>) -> HashMap<String, SystemTime> {
+    WalkDir::new(root.as_ref())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file() && !should_ignore(e.path()))
+        .filter(|e| detect_language(e.path()).is_some())
+        .filter_map(|e| {
+            let modified = e.metadata().ok()?.modified().ok()?;
+            Some((e.path().display().to_string(), modified))
         })
-        .unwrap(),
-    )
-    .unwrap();
+        .collect()
+}
 
-    ctx
+/// The cached index, or None when it is missing, unreadable or was written
+/// by a build with a different layout.
+fn read_index(ctx_dir: &Path) -> Option<Index> {
+    let raw = fs::read(ctx_dir.join(INDEX_FILE)).ok()?;
+    bincode::deserialize::<Index>(&raw)
+        .ok()
+        .filter(|index| index.version == INDEX_VERSION)
+}
+
+fn write_index(
+    ctx_dir: &Path,
+    files: &HashMap<String, FileStamp>,
+    context: &Context,
+) -> Result<(), String> {
+    let raw = bincode::serialize(&IndexRef {
+        version: INDEX_VERSION,
+        files,
+        context,
+    })
+    .map_err(|e| e.to_string())?;
+    write_atomic(&ctx_dir.join(INDEX_FILE), &raw)
+}
+
+// The agent reads context.json while the watcher rewrites it.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("{}: {e}", path.display()))
 }
 
 /* ======================= IGNORE RULES ======================= */
 
-fn should_ignore(path: &Path) -> bool {
+pub fn should_ignore(path: &Path) -> bool {
     path.components().any(|c| {
         matches!(
             c.as_os_str().to_string_lossy().as_ref(),
@@ -160,12 +238,34 @@ fn detect_language(path: &Path) -> Option<&'static str> {
 
 fn hash_file(path: &Path) -> Option<String> {
     let data = fs::read(path).ok()?;
+    // LFS pointers and binaries are never parsed for symbols.
+    if binary_assets::is_asset(&data) {
+        return None;
+    }
     let mut h = Hasher::new();
     h.update(&data);
     Some(h.finalize().to_hex().to_string())
 }
 
-fn compute_file_hashes(root: &Path) -> HashMap<String, String> {
+fn stamp_file(path: &Path, previous: Option<&FileStamp>) -> Option<FileStamp> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok();
+    if let Some(prev) = previous {
+        if prev.len == meta.len() && modified.is_some() && prev.modified == modified {
+            return Some(prev.clone());
+        }
+    }
+    Some(FileStamp {
+        hash: hash_file(path)?,
+        len: meta.len(),
+        modified,
+    })
+}
+
+fn compute_file_stamps(
+    root: &Path,
+    previous: Option<&HashMap<String, FileStamp>>,
+) -> HashMap<String, FileStamp> {
     let mut out = HashMap::new();
 
     for e in WalkDir::new(root).into_iter().filter_map(Result::ok) {
@@ -176,40 +276,30 @@ fn compute_file_hashes(root: &Path) -> HashMap<String, String> {
         if detect_language(p).is_none() {
             continue;
         }
-        if let Some(h) = hash_file(p) {
-            out.insert(p.display().to_string(), h);
+        let key = p.display().to_string();
+        if let Some(stamp) = stamp_file(p, previous.and_then(|prev| prev.get(&key))) {
+            out.insert(key, stamp);
         }
     }
 
     out
 }
 
-/* ======================= STATS ======================= */
-
-fn compute_repo_stats(root: &Path) -> RepoStats {
-    let mut stats = RepoStats {
-        file_count: 0,
-        total_bytes: 0,
-        total_lines: 0,
-    };
+fn hashes_of(stamps: &HashMap<String, FileStamp>) -> HashMap<String, String> {
+    stamps
+        .iter()
+        .map(|(path, stamp)| (path.clone(), stamp.hash.clone()))
+        .collect()
+}
 
-    for e in WalkDir::new(root).into_iter().filter_map(Result::ok) {
-        if !e.file_type().is_file() || should_ignore(e.path()) {
-            continue;
-        }
+/* ======================= STATS ======================= */
 
-        if detect_language(e.path()).is_some() {
-            if let Ok(meta) = e.metadata() {
-                stats.file_count += 1;
-                stats.total_bytes += meta.len();
-                if let Ok(src) = fs::read_to_string(e.path()) {
-                    stats.total_lines += src.lines().count();
-                }
-            }
-        }
+fn stats_of(files: &[FileInfo]) -> RepoStats {
+    RepoStats {
+        file_count: files.len(),
+        total_bytes: files.iter().map(|f| f.bytes).sum(),
+        total_lines: files.iter().map(|f| f.lines).sum(),
     }
-
-    stats
 }
 
 /* ======================= INCREMENTAL ======================= */
@@ -247,11 +337,11 @@ fn incremental_update(
             continue;
         };
 
-        match lang {
+        let imports = match lang {
             "python" => extract_python(&src, path, &mut ctx.symbols),
             "rust" => extract_rust(&src, path, &mut ctx.symbols),
-            _ => {}
-        }
+            _ => Vec::new(),
+        };
 
         let bytes = fs::metadata(p).map(|m| m.len()).unwrap_or(0);
         let lines = src.lines().count();
@@ -261,6 +351,7 @@ fn incremental_update(
             language: lang.into(),
             bytes,
             lines,
+            imports,
         });
     }
 
@@ -325,7 +416,7 @@ fn rust_doc(node: Node, src: &str) -> Option<String> {
 
 /* ======================= BUILD ======================= */
 
-fn build_context(_root: &Path, stats: RepoStats, hashes: &HashMap<String, String>) -> Context {
+fn build_context(_root: &Path, hashes: &HashMap<String, String>) -> Context {
     let mut files = Vec::new();
     let mut symbols = Vec::new();
 
@@ -338,11 +429,11 @@ fn build_context(_root: &Path, stats: RepoStats, hashes: &HashMap<String, String
             continue;
         };
 
-        match lang {
+        let imports = match lang {
             "python" => extract_python(&src, path, &mut symbols),
             "rust" => extract_rust(&src, path, &mut symbols),
-            _ => {}
-        }
+            _ => Vec::new(),
+        };
 
         let bytes = fs::metadata(p).map(|m| m.len()).unwrap_or(0);
         let lines = src.lines().count();
@@ -352,13 +443,14 @@ fn build_context(_root: &Path, stats: RepoStats, hashes: &HashMap<String, String
             language: lang.into(),
             bytes,
             lines,
+            imports,
         });
     }
 
     finalize_calls(&mut symbols);
 
     Context {
-        stats,
+        stats: stats_of(&files),
         files,
         symbols,
     }
@@ -366,21 +458,44 @@ fn build_context(_root: &Path, stats: RepoStats, hashes: &HashMap<String, String
 
 /* ======================= PYTHON EXTRACTION ======================= */
 
-fn extract_python(src: &str, file: &str, out: &mut Vec<Symbol>) {
+/// Pushes the file's symbols to `out` and returns its imports.
+fn extract_python(src: &str, file: &str, out: &mut Vec<Symbol>) -> Vec<String> {
     let mut p = Parser::new();
     p.set_language(&python::language()).ok();
-    let Some(t) = p.parse(src, None) else { return };
+    let Some(t) = p.parse(src, None) else {
+        return Vec::new();
+    };
 
     let root = t.root_node();
     let mut cursor = root.walk();
+    let mut imports = Vec::new();
 
     for node in root.children(&mut cursor) {
         match node.kind() {
             "function_definition" => out.push(extract_python_fn(node, src, file)),
             "class_definition" => out.push(extract_python_class(node, src, file)),
+            "import_statement" => {
+                let mut c = node.walk();
+                for name in node.children_by_field_name("name", &mut c) {
+                    // `import a.b as c` names the module in the alias.
+                    let module = name.child_by_field_name("name").unwrap_or(name);
+                    if let Ok(text) = module.utf8_text(src.as_bytes()) {
+                        imports.push(text.to_string());
+                    }
+                }
+            }
+            "import_from_statement" => {
+                if let Some(text) = node
+                    .child_by_field_name("module_name")
+                    .and_then(|m| m.utf8_text(src.as_bytes()).ok())
+                {
+                    imports.push(text.to_string());
+                }
+            }
             _ => {}
         }
     }
+    imports
 }
 
 fn extract_python_fn(n: Node, src: &str, file: &str) -> Symbol {
@@ -469,17 +584,41 @@ fn extract_python_class(n: Node, src: &str, file: &str) -> Symbol {
 
 /* ======================= RUST EXTRACTION ======================= */
 
-fn extract_rust(src: &str, file: &str, out: &mut Vec<Symbol>) {
+/// Pushes the file's functions to `out` and returns its imports.
+fn extract_rust(src: &str, file: &str, out: &mut Vec<Symbol>) -> Vec<String> {
     let mut p = Parser::new();
     p.set_language(&rust::language()).ok();
-    let Some(t) = p.parse(src, None) else { return };
+    let Some(t) = p.parse(src, None) else {
+        return Vec::new();
+    };
 
     let root = t.root_node();
     let mut cursor = root.walk();
+    let mut imports = Vec::new();
 
     for n in root.children(&mut cursor) {
-        if n.kind() != "function_item" {
-            continue;
+        match n.kind() {
+            "function_item" => {}
+            "use_declaration" => {
+                if let Some(text) = n
+                    .child_by_field_name("argument")
+                    .and_then(|a| a.utf8_text(src.as_bytes()).ok())
+                {
+                    flatten_use("", text, &mut imports);
+                }
+                continue;
+            }
+            // Only `mod x;` points at another file; inline modules do not.
+            "mod_item" if n.child_by_field_name("body").is_none() => {
+                if let Some(name) = n
+                    .child_by_field_name("name")
+                    .and_then(|x| x.utf8_text(src.as_bytes()).ok())
+                {
+                    imports.push(format!("self::{name}"));
+                }
+                continue;
+            }
+            _ => continue,
         }
 
         let name = n
@@ -530,6 +669,45 @@ fn extract_rust(src: &str, file: &str, out: &mut Vec<Symbol>) {
             line_end: n.end_position().row + 1,
         });
     }
+    imports
+}
+
+/// Expands a `use` tree such as `crate::a::{b, c::{self, D as E}}` into one
+/// path per imported item, without aliases, `self` or globs.
+fn flatten_use(prefix: &str, tree: &str, out: &mut Vec<String>) {
+    let tree = tree.trim();
+    let tree = tree
+        .split_once(" as ")
+        .map_or(tree, |(path, _)| path)
+        .trim();
+    if let Some(open) = tree.find('{').filter(|_| tree.ends_with('}')) {
+        let prefix = format!("{prefix}{}", &tree[..open]);
+        let mut depth = 0;
+        let mut start = open + 1;
+        for (i, ch) in tree.char_indices().filter(|(i, _)| *i > open) {
+            match ch {
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                ',' | '}' if depth == 0 => {
+                    flatten_use(&prefix, &tree[start..i], out);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        return;
+    }
+    if tree.is_empty() {
+        return;
+    }
+    let path = format!("{prefix}{tree}");
+    let path = path
+        .trim_end_matches("::*")
+        .trim_end_matches("::self")
+        .replace(char::is_whitespace, "");
+    if !out.contains(&path) {
+        out.push(path);
+    }
 }
 
 /* ======================= POST PROCESS ======================= */
@@ -539,6 +717,10 @@ fn finalize_calls(symbols: &mut Vec<Symbol>) {
     let mut reverse: HashMap<String, Vec<String>> = HashMap::new();
 
     for s in symbols.iter_mut() {
+        // Incremental updates run this again over symbols kept from the cache.
+        s.custom_calls.clear();
+        s.lang_calls.clear();
+        s.called_by.clear();
         for c in &s.calls {
             if names.contains(c) {
                 s.custom_calls.push(c.clone());
@@ -560,3 +742,76 @@ fn finalize_calls(symbols: &mut Vec<Symbol>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn refresh_paths_reparses_only_the_given_files() {
+        let root = std::env::temp_dir().join(format!("osmogrep-indexer-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let a = root.join("a.py");
+        let b = root.join("b.py");
+        fs::write(&a, "def alpha():\n    return beta()\n").unwrap();
+        fs::write(&b, "def beta():\n    return 1\n").unwrap();
+        let ctx = load_or_build(&root);
+        assert_eq!(ctx.files.len(), 2);
+
+        fs::write(
+            &a,
+            "def alpha():\n    return gamma()\n\ndef gamma():\n    return 2\n",
+        )
+        .unwrap();
+        fs::remove_file(&b).unwrap();
+        let keys = [a.display().to_string(), b.display().to_string()];
+        assert_eq!(refresh_paths(&root, &keys).unwrap(), 2);
+        assert_eq!(refresh_paths(&root, &keys).unwrap(), 0);
+
+        let ctx: Context =
+            serde_json::from_str(&fs::read_to_string(root.join(export::FILE)).unwrap()).unwrap();
+        let mut names: Vec<_> = ctx.symbols.iter().map(|s| s.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["alpha", "gamma"]);
+        let alpha = ctx.symbols.iter().find(|s| s.name == "alpha").unwrap();
+        assert_eq!(alpha.custom_calls, ["gamma"]);
+        assert_eq!(ctx.stats.file_count, 1);
+        assert_eq!(source_mtimes(&root).len(), 1);
+        assert_eq!(load_or_build(&root).symbols.len(), 2);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn warm_load_reuses_the_index_and_picks_up_edits() {
+        let root = std::env::temp_dir().join(format!("osmogrep-indexer-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let a = root.join("a.rs");
+        fs::write(&a, "fn alpha() {}\n").unwrap();
+        load_or_build(&root);
+        let index = read_index(&root.join(CONTEXT_DIR)).unwrap();
+        assert_eq!(index.context.symbols.len(), 1);
+
+        // Cached symbols are served without re-parsing unchanged files.
+        let mut index = index;
+        index.context.symbols[0].doc = Some("from cache".into());
+        write_index(&root.join(CONTEXT_DIR), &index.files, &index.context).unwrap();
+        assert_eq!(
+            load_or_build(&root).symbols[0].doc.as_deref(),
+            Some("from cache")
+        );
+
+        fs::write(&a, "fn alpha() {}\nfn beta() { alpha() }\n").unwrap();
+        let ctx = load_or_build(&root);
+        let beta = ctx.symbols.iter().find(|s| s.name == "beta").unwrap();
+        assert_eq!(beta.custom_calls, ["alpha"]);
+        assert_eq!(ctx.stats.total_lines, 2);
+
+        fs::write(root.join(CONTEXT_DIR).join(INDEX_FILE), b"junk").unwrap();
+        assert!(read_index(&root.join(CONTEXT_DIR)).is_none());
+        assert_eq!(load_or_build(&root).symbols.len(), 2);
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,15 +1,26 @@
+pub mod export;
+pub mod impact;
 pub mod indexer;
+pub mod risk;
+pub mod search;
+pub mod working_diff;
 pub enum ContextEvent {
     Started,
     Finished,
     Error(String),
+    /// The watcher saw `n` source files change and is re-parsing them.
+    Refreshing(usize),
+    /// `n` files were re-indexed in place; 0 when nothing changed.
+    Refreshed(usize),
+    RefreshFailed(String),
 }
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
 use std::thread;
+use std::time::Duration;
 
-use crate::context::indexer::load_or_build;
+use crate::context::indexer::{load_or_build, refresh_paths, source_mtimes};
 
 pub fn spawn_indexer(root: PathBuf, tx: Sender<ContextEvent>) {
     thread::spawn(move || {
@@ -23,7 +34,61 @@ pub fn spawn_indexer(root: PathBuf, tx: Sender<ContextEvent>) {
             }
             Err(_) => {
                 let _ = tx.send(ContextEvent::Error("context indexing panicked".into()));
+                return;
             }
         }
+
+        if let Some(interval) = watch_interval() {
+            watch(&root, interval, &tx);
+        }
     });
 }
+
+/// Poll interval for the re-index watcher; `OSMOGREP_INDEX_WATCH_SECS=0`
+/// turns it off.
+fn watch_interval() -> Option<Duration> {
+    let secs = std::env::var("OSMOGREP_INDEX_WATCH_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(2);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// Polls modification times and re-parses only the files that moved. Ends
+// once the receiver is gone.
+fn watch(root: &Path, interval: Duration, tx: &Sender<ContextEvent>) {
+    let mut seen = source_mtimes(root);
+    loop {
+        thread::sleep(interval);
+
+        let current = source_mtimes(root);
+        let mut changed: Vec<String> = current
+            .iter()
+            .filter(|(path, modified)| seen.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .chain(seen.keys().filter(|p| !current.contains_key(*p)).cloned())
+            .collect();
+        seen = current;
+
+        if changed.is_empty() {
+            // Probe the channel so the thread exits with the UI.
+            if tx.send(ContextEvent::Refreshed(0)).is_err() {
+                return;
+            }
+            continue;
+        }
+        changed.sort();
+
+        if tx.send(ContextEvent::Refreshing(changed.len())).is_err() {
+            return;
+        }
+        let event = match std::panic::catch_unwind(|| refresh_paths(root, &changed)) {
+            Ok(Ok(n)) => ContextEvent::Refreshed(n),
+            Ok(Err(e)) => ContextEvent::RefreshFailed(e),
+            Err(_) => ContextEvent::RefreshFailed("context re-indexing panicked".into()),
+        };
+        if tx.send(event).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! context/risk.rs
+//!
+//! Risk score of the analyzed diff, from rules a team can tune in
+//! `.osmogrep/risk.toml`:
+//!
+//! ```toml
+//! medium = 25   # score at which a diff counts as medium risk
+//! high = 60
+//!
+//! # Each pattern scores once when any changed file matches it.
+//! [[paths]]
+//! pattern = "migrations/**"
+//! weight = 40
+//!
+//! # Each kind of file the diff changes scores once.
+//! [surfaces]
+//! source = 10
+//! config = 15
+//! test = 0
+//! docs = 0
+//!
+//! # Callers of the touched symbols that were not changed themselves.
+//! [fan_in]
+//! per_caller = 3
+//! max = 30
+//! ```
+//!
+//! Missing keys keep their defaults. Path globs follow `[protected_paths]`.
+//! `/inspect` and `osmogrep analyze` show the score with each contribution.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+use crate::context::working_diff::ChangedFile;
+use crate::protected_paths;
+
+pub const RULES_FILE: &str = ".osmogrep/risk.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathRule {
+    pub pattern: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FanIn {
+    pub per_caller: u32,
+    pub max: u32,
+}
+
+impl Default for FanIn {
+    fn default() -> Self {
+        Self {
+            per_caller: 3,
+            max: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskRules {
+    pub medium: u32,
+    pub high: u32,
+    pub paths: Vec<PathRule>,
+    /// Weight per surface kind; kinds left out keep their default.
+    pub surfaces: BTreeMap<String, u32>,
+    pub fan_in: FanIn,
+}
+
+impl Default for RiskRules {
+    fn default() -> Self {
+        Self {
+            medium: 25,
+            high: 60,
+            paths: Vec::new(),
+            surfaces: BTreeMap::new(),
+            fan_in: FanIn::default(),
+        }
+    }
+}
+
+/// What part of the project a changed file belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Surface {
+    Source,
+    Config,
+    Test,
+    Docs,
+}
+
+impl Surface {
+    pub const ALL: [Surface; 4] = [
+        Surface::Source,
+        Surface::Config,
+        Surface::Test,
+        Surface::Docs,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Surface::Source => "source",
+            Surface::Config => "config",
+            Surface::Test => "test",
+            Surface::Docs => "docs",
+        }
+    }
+
+    fn default_weight(self) -> u32 {
+        match self {
+            Surface::Source => 10,
+            Surface::Config => 15,
+            Surface::Test | Surface::Docs => 0,
+        }
+    }
+
+    pub fn of(path: &str) -> Self {
+        let lower = path.to_ascii_lowercase();
+        let ext = Path::new(&lower)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        if crate::testgen::is_test_path(path) {
+            Surface::Test
+        } else if matches!(ext, "md" | "rst" | "adoc")
+            || lower.starts_with("docs/")
+            || lower.contains("/docs/")
+        {
+            Surface::Docs
+        } else if crate::test_harness::is_manifest(path)
+            || lower.starts_with(".github/")
+            || matches!(
+                ext,
+                "toml" | "yaml" | "yml" | "json" | "ini" | "cfg" | "lock" | "env"
+            )
+            || lower
+                .rsplit('/')
+                .next()
+                .is_some_and(|name| matches!(name, "dockerfile" | "makefile" | ".gitlab-ci.yml"))
+        {
+            Surface::Config
+        } else {
+            Surface::Source
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl RiskLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        }
+    }
+}
+
+/// One rule's contribution to the score.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Factor {
+    /// e.g. `path migrations/**`, `surface config`, `fan-in 4 callers`.
+    pub rule: String,
+    pub points: u32,
+    /// Changed files the rule matched; empty for fan-in.
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RiskScore {
+    pub score: u32,
+    pub level: RiskLevel,
+    pub factors: Vec<Factor>,
+    /// Why `.osmogrep/risk.toml` was ignored in favor of the defaults.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_error: Option<String>,
+}
+
+impl RiskScore {
+    /// `high (72): path migrations/** +40, surface config +15, …`
+    pub fn summary(&self) -> String {
+        let factors: Vec<String> = self
+            .factors
+            .iter()
+            .map(|f| format!("{} +{}", f.rule, f.points))
+            .collect();
+        if factors.is_empty() {
+            return format!("{} ({})", self.level.as_str(), self.score);
+        }
+        format!(
+            "{} ({}): {}",
+            self.level.as_str(),
+            self.score,
+            factors.join(", ")
+        )
+    }
+
+    /// Points of the rules that matched `path`; fan-in counts for no file.
+    pub fn points_for(&self, path: &str) -> u32 {
+        self.factors
+            .iter()
+            .filter(|f| f.files.iter().any(|file| file == path))
+            .map(|f| f.points)
+            .sum()
+    }
+}
+
+impl RiskRules {
+    /// The repo's rules, or the defaults when the file does not exist.
+    pub fn load(repo_root: &Path) -> Result<Self, String> {
+        match fs::read_to_string(repo_root.join(RULES_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{RULES_FILE}: {e}")),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let rules: Self = toml::from_str(text).map_err(|e| format!("{RULES_FILE}: {e}"))?;
+        if let Some(rule) = rules
+            .paths
+            .iter()
+            .find(|rule| Pattern::new(&rule.pattern).is_err())
+        {
+            return Err(format!("{RULES_FILE}: invalid pattern {:?}", rule.pattern));
+        }
+        let kinds: Vec<&str> = Surface::ALL.iter().map(|s| s.as_str()).collect();
+        if let Some(kind) = rules.surfaces.keys().find(|k| !kinds.contains(&k.as_str())) {
+            return Err(format!(
+                "{RULES_FILE}: unknown surface {kind:?} (expected one of {})",
+                kinds.join(", ")
+            ));
+        }
+        if rules.medium > rules.high {
+            return Err(format!("{RULES_FILE}: medium is above high"));
+        }
+        Ok(rules)
+    }
+
+    fn surface_weight(&self, surface: Surface) -> u32 {
+        self.surfaces
+            .get(surface.as_str())
+            .copied()
+            .unwrap_or_else(|| surface.default_weight())
+    }
+
+    /// Score of a diff changing `files` whose touched symbols have
+    /// `callers` unchanged callers.
+    pub fn assess(&self, files: &[ChangedFile], callers: usize) -> RiskScore {
+        let mut factors = Vec::new();
+        for rule in &self.paths {
+            let matched: Vec<String> = files
+                .iter()
+                .filter(|f| protected_paths::matches(&rule.pattern, &f.path))
+                .map(|f| f.path.clone())
+                .collect();
+            if !matched.is_empty() && rule.weight > 0 {
+                factors.push(Factor {
+                    rule: format!("path {}", rule.pattern),
+                    points: rule.weight,
+                    files: matched,
+                });
+            }
+        }
+        for surface in Surface::ALL {
+            let matched: Vec<String> = files
+                .iter()
+                .filter(|f| Surface::of(&f.path) == surface)
+                .map(|f| f.path.clone())
+                .collect();
+            let points = self.surface_weight(surface);
+            if !matched.is_empty() && points > 0 {
+                factors.push(Factor {
+                    rule: format!("surface {}", surface.as_str()),
+                    points,
+                    files: matched,
+                });
+            }
+        }
+        let fan_in = (callers as u32)
+            .saturating_mul(self.fan_in.per_caller)
+            .min(self.fan_in.max);
+        if fan_in > 0 {
+            factors.push(Factor {
+                rule: format!("fan-in {callers} callers"),
+                points: fan_in,
+                files: Vec::new(),
+            });
+        }
+        let score = factors.iter().map(|f| f.points).sum();
+        let level = if score >= self.high {
+            RiskLevel::High
+        } else if score >= self.medium {
+            RiskLevel::Medium
+        } else {
+            RiskLevel::Low
+        };
+        RiskScore {
+            score,
+            level,
+            factors,
+            rules_error: None,
+        }
+    }
+}
+
+/// Scores `files` with the repo's rules, falling back to the defaults (and
+/// saying so) when the rules file is invalid.
+pub fn assess(repo_root: &Path, files: &[ChangedFile], callers: usize) -> RiskScore {
+    match RiskRules::load(repo_root) {
+        Ok(rules) => rules.assess(files, callers),
+        Err(e) => RiskScore {
+            rules_error: Some(e),
+            ..RiskRules::default().assess(files, callers)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(path: &str) -> ChangedFile {
+        ChangedFile {
+            path: path.into(),
+            added: 1,
+            removed: 0,
+            untracked: false,
+            coverage: None,
+        }
+    }
+
+    #[test]
+    fn weighs_paths_surfaces_and_fan_in_into_a_level() {
+        assert_eq!(Surface::of("src/lib.rs"), Surface::Source);
+        assert_eq!(Surface::of("Cargo.toml"), Surface::Config);
+        assert_eq!(Surface::of(".github/workflows/ci.yml"), Surface::Config);
+        assert_eq!(Surface::of("tests/cli.rs"), Surface::Test);
+        assert_eq!(Surface::of("docs/guide.md"), Surface::Docs);
+
+        let files = [
+            changed("db/migrations/001_init.sql"),
+            changed("src/db.rs"),
+            changed("tests/db.rs"),
+        ];
+        let defaults = RiskRules::default().assess(&files, 2);
+        assert_eq!(defaults.score, 16);
+        assert_eq!(defaults.level, RiskLevel::Low);
+        assert_eq!(
+            defaults.summary(),
+            "low (16): surface source +10, fan-in 2 callers +6"
+        );
+
+        let rules = RiskRules::parse(
+            "high = 50\n\
+             [[paths]]\npattern = \"**/migrations/**\"\nweight = 40\n\
+             [surfaces]\ntest = 5\n\
+             [fan_in]\nper_caller = 10\nmax = 15\n",
+        )
+        .unwrap();
+        let score = rules.assess(&files, 4);
+        assert_eq!(score.level, RiskLevel::High);
+        assert_eq!(score.score, 40 + 10 + 5 + 15);
+        assert_eq!(score.factors[0].files, ["db/migrations/001_init.sql"]);
+        assert_eq!(score.factors[3].rule, "fan-in 4 callers");
+        assert_eq!(score.points_for("db/migrations/001_init.sql"), 40 + 10);
+        assert_eq!(score.points_for("tests/db.rs"), 5);
+        assert_eq!(score.points_for("README.md"), 0);
+
+        assert!(RiskRules::parse("[surfaces]\nvendor = 3\n")
+            .unwrap_err()
+            .contains("unknown surface \"vendor\""));
+        assert!(RiskRules::parse("medium = 80\n").is_err());
+        assert!(RiskRules::parse("[[paths]]\npattern = \"[\"\nweight = 1\n").is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! context/search.rs
+//!
+//! Ranked search over the context index for natural-language queries. Each
+//! indexed symbol is a document of its name, parameters, doc comment and
+//! the identifiers and comments in its body; each indexed file is one of
+//! its path and comments. Identifiers are split on case and `_`, so
+//! "parse config" finds `parseConfig` and `parse_config`. Ranking is BM25,
+//! with the name and doc counted several times over the body.
+//! Used by `/find` and the `semantic_search` tool.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::context::indexer::Context;
+
+pub const DEFAULT_LIMIT: usize = 10;
+
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+const NAME_BOOST: usize = 3;
+const DOC_BOOST: usize = 2;
+
+/// A ranked symbol or file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hit {
+    /// `function`, `class` or `file`.
+    pub kind: String,
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+    pub score: f64,
+    /// First line of the doc comment, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+struct Document {
+    hit: Hit,
+    terms: HashMap<String, usize>,
+    len: usize,
+}
+
+/// The `limit` documents most relevant to `query`, best first. Documents
+/// sharing no term with the query are left out.
+pub fn search(ctx: &Context, repo_root: &Path, query: &str, limit: usize) -> Vec<Hit> {
+    let query: HashSet<String> = tokens(query).into_iter().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let docs = documents(ctx, repo_root);
+    if docs.is_empty() {
+        return Vec::new();
+    }
+
+    let n = docs.len() as f64;
+    let avg_len = docs.iter().map(|d| d.len).sum::<usize>() as f64 / n;
+    let idf: HashMap<&str, f64> = query
+        .iter()
+        .map(|term| {
+            let df = docs.iter().filter(|d| d.terms.contains_key(term)).count() as f64;
+            (term.as_str(), ((n - df + 0.5) / (df + 0.5) + 1.0).ln())
+        })
+        .collect();
+
+    let mut hits: Vec<Hit> = docs
+        .into_iter()
+        .filter_map(|doc| {
+            let norm = K1 * (1.0 - B + B * doc.len as f64 / avg_len.max(1.0));
+            let score: f64 = idf
+                .iter()
+                .filter_map(|(term, idf)| {
+                    let tf = *doc.terms.get(*term)? as f64;
+                    Some(idf * tf * (K1 + 1.0) / (tf + norm))
+                })
+                .sum();
+            (score > 0.0).then_some(Hit { score, ..doc.hit })
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.file.cmp(&b.file))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+/// Report lines for `/find`.
+pub fn render(query: &str, hits: &[Hit]) -> Vec<String> {
+    if hits.is_empty() {
+        return vec![format!("Nothing in the index matches \"{query}\".")];
+    }
+    let mut lines = vec![format!("● {} match(es) for \"{query}\"", hits.len())];
+    for hit in hits {
+        let location = if hit.kind == "file" {
+            hit.file.clone()
+        } else {
+            format!("{} {} ({}:{})", hit.kind, hit.name, hit.file, hit.line)
+        };
+        match &hit.doc {
+            Some(doc) => lines.push(format!("  {:>5.2}  {location} — {doc}", hit.score)),
+            None => lines.push(format!("  {:>5.2}  {location}", hit.score)),
+        }
+    }
+    lines
+}
+
+fn documents(ctx: &Context, repo_root: &Path) -> Vec<Document> {
+    let relative = |file: &str| {
+        let path = Path::new(file);
+        path.strip_prefix(repo_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let mut docs = Vec::new();
+    for file in &ctx.files {
+        let source = fs::read_to_string(&file.path).unwrap_or_default();
+        let lines: Vec<&str> = source.lines().collect();
+        let path = relative(&file.path);
+
+        let mut terms = Vec::new();
+        for _ in 0..NAME_BOOST {
+            terms.extend(tokens(&path));
+        }
+        terms.extend(lines.iter().filter_map(|l| comment(l)).flat_map(tokens));
+        docs.push(document(
+            Hit {
+                kind: "file".into(),
+                name: path.clone(),
+                file: path.clone(),
+                line: 1,
+                score: 0.0,
+                doc: None,
+            },
+            terms,
+        ));
+
+        for symbol in ctx.symbols.iter().filter(|s| s.file == file.path) {
+            let start = symbol.line_start.saturating_sub(1).min(lines.len());
+            let doc = symbol
+                .doc
+                .clone()
+                .or_else(|| leading_comment(&lines[..start]));
+            let mut terms = Vec::new();
+            for _ in 0..NAME_BOOST {
+                terms.extend(tokens(&symbol.name));
+            }
+            for _ in 0..DOC_BOOST {
+                terms.extend(doc.iter().flat_map(|d| tokens(d)));
+            }
+            terms.extend(symbol.inputs.iter().flat_map(|i| tokens(i)));
+            let end = symbol.line_end.min(lines.len()).max(start);
+            terms.extend(lines[start..end].iter().flat_map(|l| tokens(l)));
+            docs.push(document(
+                Hit {
+                    kind: symbol.kind.clone(),
+                    name: symbol.name.clone(),
+                    file: path.clone(),
+                    line: symbol.line_start,
+                    score: 0.0,
+                    doc: doc
+                        .as_deref()
+                        .and_then(|d| d.lines().map(str::trim).find(|l| !l.is_empty()))
+                        .map(str::to_string),
+                },
+                terms,
+            ));
+        }
+    }
+    docs
+}
+
+fn document(hit: Hit, words: Vec<String>) -> Document {
+    let mut terms = HashMap::new();
+    for word in &words {
+        *terms.entry(word.clone()).or_default() += 1;
+    }
+    Document {
+        hit,
+        terms,
+        len: words.len(),
+    }
+}
+
+/// The comment block right above a definition (`///` docs, `#` or `//`
+/// comments), skipping attributes and decorators.
+fn leading_comment(above: &[&str]) -> Option<String> {
+    let mut block = Vec::new();
+    for line in above.iter().rev() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#[") || trimmed.starts_with('@') {
+            continue;
+        }
+        match comment(trimmed) {
+            Some(text) => block.push(text.trim_start_matches(['/', '!']).trim()),
+            None => break,
+        }
+    }
+    block.reverse();
+    (!block.is_empty()).then(|| block.join("\n"))
+}
+
+/// The text of a `//` or `#` comment line.
+fn comment(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    line.strip_prefix("//")
+        .or_else(|| line.strip_prefix('#').filter(|rest| !rest.starts_with('[')))
+}
+
+/// Lowercase terms of `text`: words and identifier parts split on case
+/// changes, `_` and digits, with a plural `s` dropped.
+pub fn tokens(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = word.chars().collect();
+        let mut start = 0;
+        for i in 1..=chars.len() {
+            let boundary = i == chars.len()
+                || (chars[i].is_uppercase()
+                    && (chars[i - 1].is_lowercase()
+                        || chars.get(i + 1).is_some_and(|c| c.is_lowercase())))
+                || chars[i].is_ascii_digit() != chars[i - 1].is_ascii_digit();
+            if boundary {
+                let part: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                start = i;
+                if part.len() < 2 || part.chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                let stem = match part.strip_suffix('s') {
+                    Some(stem) if stem.len() > 2 && !stem.ends_with('s') => stem.to_string(),
+                    _ => part,
+                };
+                out.push(stem);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::indexer;
+    use uuid::Uuid;
+
+    #[test]
+    fn ranks_symbols_by_names_docs_and_bodies() {
+        assert_eq!(
+            tokens("parseHTTPHeaders load_config2 v2"),
+            ["parse", "http", "header", "load", "config"]
+        );
+
+        let root = std::env::temp_dir().join(format!("osmogrep-search-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/config.rs"),
+            "// Settings loaded from disk.\n\n\
+             /// Reads the TOML settings file.\n\
+             #[inline]\n\
+             fn load_config(path: &str) -> u8 { read_settings(path) }\n\
+             fn read_settings(p: &str) -> u8 { 0 }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/retry.py"),
+            "def backoff_delay(attempt):\n    \"\"\"Exponential delay between retries.\"\"\"\n    return 2 ** attempt\n",
+        )
+        .unwrap();
+        let ctx = indexer::load_or_build(&root);
+
+        let hits = search(&ctx, &root, "load the config", DEFAULT_LIMIT);
+        assert_eq!(hits[0].name, "load_config");
+        assert_eq!(hits[0].file, "src/config.rs");
+        assert_eq!(
+            hits[0].doc.as_deref(),
+            Some("Reads the TOML settings file.")
+        );
+
+        let hits = search(&ctx, &root, "how long to wait between retries", 2);
+        assert_eq!(hits[0].name, "backoff_delay");
+        assert!(hits.len() <= 2);
+        assert!(search(&ctx, &root, "settings", DEFAULT_LIMIT)
+            .iter()
+            .any(|h| h.kind == "file" && h.file == "src/config.rs"));
+        assert!(search(&ctx, &root, "kubernetes", DEFAULT_LIMIT).is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,873 @@
+//! context/working_diff.rs
+//!
+//! Pre-analysis of uncommitted changes. While the TUI is idle and the
+//! working tree is dirty, `poll` analyzes the diff in a background thread:
+//! changed files with line counts, the indexed symbols the hunks touch, and
+//! the callers of those symbols. `/inspect` and the first prompt of an agent
+//! task read the result from the cache, which is only served while the
+//! working tree still matches the fingerprint it was computed for.
+//!
+//! Untracked files are left out unless selected with `/git include <path>`;
+//! selected ones count as all-new additions with every symbol touched.
+//!
+//! In a monorepo the workspace packages owning the changed files are
+//! recorded too, so tests can run in just those packages.
+//!
+//! When `/coverage` has collected line coverage, each changed file carries
+//! the share of its changed lines the suite executes.
+//!
+//! What the analysis compares against is chosen per branch with `/diff
+//! staged|unstaged|branch|head` and `/diff base <ref>`: everything
+//! uncommitted (the default), only staged or only unstaged changes, or the
+//! whole branch since it forked from a base branch.
+//!
+//! Each analysis carries a risk score from the rules in
+//! `.osmogrep/risk.toml`; see [`crate::context::risk`].
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use std::collections::BTreeMap;
+
+use blake3::Hasher;
+use serde::{Deserialize, Serialize};
+
+use crate::context::indexer;
+use crate::context::risk::{self, RiskScore};
+use crate::coverage::{Coverage, LineCoverage};
+use crate::workspace::{self, Package};
+
+/// How often an idle TUI may start a background check.
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_LISTED: usize = 12;
+/// Untracked files selected for analysis, relative to the repo root.
+const INCLUDE_FILE: &str = ".context/diff-include.json";
+/// Baseline chosen for each branch, relative to the repo root.
+const BASELINE_FILE: &str = ".context/diff-baseline.json";
+/// Tried in order when a branch has no base set.
+const DEFAULT_BASES: &[&str] = &[
+    "origin/HEAD",
+    "origin/main",
+    "origin/master",
+    "main",
+    "master",
+];
+
+/// What the analysis compares the working tree against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffBaseline {
+    /// Staged and unstaged changes against HEAD.
+    #[default]
+    Head,
+    /// Changes in the index only.
+    Staged,
+    /// Working tree changes not yet staged.
+    Unstaged,
+    /// Everything since the branch forked from its base, committed or not.
+    Branch,
+}
+
+impl DiffBaseline {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "head" | "uncommitted" => Some(Self::Head),
+            "staged" | "cached" => Some(Self::Staged),
+            "unstaged" => Some(Self::Unstaged),
+            "branch" => Some(Self::Branch),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Head => "head",
+            Self::Staged => "staged",
+            Self::Unstaged => "unstaged",
+            Self::Branch => "branch",
+        }
+    }
+}
+
+/// The baseline of one branch; `base` is only used by `Branch`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineSetting {
+    #[serde(default)]
+    pub baseline: DiffBaseline,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+}
+
+impl BaselineSetting {
+    /// What is being compared, e.g. `staged changes` or `branch vs origin/main`.
+    pub fn label(&self) -> String {
+        match self.baseline {
+            DiffBaseline::Head => "uncommitted changes".to_string(),
+            DiffBaseline::Staged => "staged changes".to_string(),
+            DiffBaseline::Unstaged => "unstaged changes".to_string(),
+            DiffBaseline::Branch => format!(
+                "branch vs {}",
+                self.base.as_deref().unwrap_or("default base")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedFile {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+    pub untracked: bool,
+    /// Changed lines executed by the last `/coverage` run.
+    pub coverage: Option<LineCoverage>,
+}
+
+/// An indexed symbol whose body overlaps a changed hunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TouchedSymbol {
+    pub name: String,
+    pub path: String,
+    pub line: usize,
+    pub end: usize,
+}
+
+impl TouchedSymbol {
+    fn label(&self) -> String {
+        format!("{} ({}:{})", self.name, self.path, self.line)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkingDiff {
+    pub fingerprint: String,
+    /// The baseline compared against, with the base resolved.
+    pub baseline: BaselineSetting,
+    pub files: Vec<ChangedFile>,
+    pub symbols: Vec<TouchedSymbol>,
+    /// Functions that call a touched symbol and were not changed themselves.
+    pub callers: Vec<String>,
+    /// New-side line ranges of each hunk, as `(path, start, end)`.
+    pub hunks: Vec<(String, usize, usize)>,
+    /// Workspace packages owning the changed files.
+    pub packages: Vec<Package>,
+    pub risk: RiskScore,
+    pub analyzed_at: Instant,
+    pub took: Duration,
+}
+
+struct Cache {
+    root: PathBuf,
+    diff: WorkingDiff,
+}
+
+fn cache() -> &'static Mutex<Option<Cache>> {
+    static CACHE: OnceLock<Mutex<Option<Cache>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+static IN_FLIGHT: AtomicBool = AtomicBool::new(false);
+
+/// Called from the main loop while nothing else is running. Starts at most
+/// one background analysis every few seconds; it returns early when the
+/// cached result still matches the working tree.
+pub fn poll(repo_root: &Path, last_poll: &mut Instant) {
+    if last_poll.elapsed() < POLL_INTERVAL || IN_FLIGHT.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    *last_poll = Instant::now();
+    let root = repo_root.to_path_buf();
+    thread::spawn(move || {
+        let _ = std::panic::catch_unwind(|| refresh(&root));
+        IN_FLIGHT.store(false, Ordering::SeqCst);
+    });
+}
+
+fn refresh(root: &Path) {
+    let Some(fingerprint) = fingerprint(root) else {
+        store(root, None);
+        return;
+    };
+    let unchanged = cache().lock().ok().is_some_and(|slot| {
+        slot.as_ref()
+            .is_some_and(|c| c.root == root && c.diff.fingerprint == fingerprint)
+    });
+    if !unchanged {
+        store(root, analyze_baseline(root, fingerprint));
+    }
+}
+
+fn store(root: &Path, diff: Option<WorkingDiff>) {
+    if let Ok(mut slot) = cache().lock() {
+        *slot = diff.map(|diff| Cache {
+            root: root.to_path_buf(),
+            diff,
+        });
+    }
+}
+
+/// The cached analysis, if the working tree has not changed since.
+pub fn cached(repo_root: &Path) -> Option<WorkingDiff> {
+    let fingerprint = fingerprint(repo_root)?;
+    let slot = cache().lock().ok()?;
+    let cached = slot.as_ref()?;
+    (cached.root == repo_root && cached.diff.fingerprint == fingerprint)
+        .then(|| cached.diff.clone())
+}
+
+/// The cached analysis, or a fresh one computed now. `None` when the tree
+/// is clean or not a git repository.
+pub fn current(repo_root: &Path) -> Option<WorkingDiff> {
+    if let Some(diff) = cached(repo_root) {
+        return Some(diff);
+    }
+    let diff = analyze_baseline(repo_root, fingerprint(repo_root)?)?;
+    store(repo_root, Some(diff.clone()));
+    Some(diff)
+}
+
+/// Uncached analysis of everything that differs from `base`, committed or
+/// not, for `osmogrep analyze` in CI. `None` when nothing differs.
+pub fn analyze_against(repo_root: &Path, base: &str) -> Option<WorkingDiff> {
+    let baseline = BaselineSetting {
+        baseline: DiffBaseline::Branch,
+        base: Some(base.to_string()),
+    };
+    analyze(
+        repo_root,
+        fingerprint(repo_root).unwrap_or_default(),
+        baseline,
+        &[base.to_string()],
+    )
+}
+
+fn analyze_baseline(root: &Path, fingerprint: String) -> Option<WorkingDiff> {
+    let baseline = resolved_baseline(root);
+    let args = diff_args(root, &baseline)?;
+    analyze(root, fingerprint, baseline, &args)
+}
+
+fn current_branch(repo_root: &Path) -> String {
+    git(repo_root, &["branch", "--show-current"])
+        .map(|out| out.trim().to_string())
+        .filter(|branch| !branch.is_empty())
+        .unwrap_or_else(|| "HEAD".to_string())
+}
+
+fn load_baselines(repo_root: &Path) -> BTreeMap<String, BaselineSetting> {
+    fs::read_to_string(repo_root.join(BASELINE_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// The current branch's baseline as set with `/diff`.
+pub fn baseline(repo_root: &Path) -> BaselineSetting {
+    load_baselines(repo_root)
+        .remove(&current_branch(repo_root))
+        .unwrap_or_default()
+}
+
+/// The current branch's resolved baseline label for the header; `None`
+/// for the default.
+pub fn baseline_label(repo_root: &Path) -> Option<String> {
+    let setting = resolved_baseline(repo_root);
+    (setting.baseline != DiffBaseline::Head).then(|| setting.label())
+}
+
+/// `baseline` with a `Branch` base filled in from `DEFAULT_BASES`.
+pub fn resolved_baseline(repo_root: &Path) -> BaselineSetting {
+    let mut setting = baseline(repo_root);
+    if setting.baseline == DiffBaseline::Branch && setting.base.is_none() {
+        setting.base = DEFAULT_BASES
+            .iter()
+            .find(|base| resolves(repo_root, base))
+            .map(|base| base.to_string());
+    }
+    setting
+}
+
+fn resolves(repo_root: &Path, rev: &str) -> bool {
+    git(
+        repo_root,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+    .is_some()
+}
+
+/// Sets the current branch's baseline; a new `base` also selects
+/// `Branch`. The fingerprint covers the baseline, so the cached analysis
+/// goes stale by itself.
+pub fn set_baseline(
+    repo_root: &Path,
+    baseline: Option<DiffBaseline>,
+    base: Option<&str>,
+) -> Result<BaselineSetting, String> {
+    if let Some(base) = base {
+        if !resolves(repo_root, base) {
+            return Err(format!(
+                "{base} is not a branch or commit in this repository."
+            ));
+        }
+    }
+    let branch = current_branch(repo_root);
+    let mut all = load_baselines(repo_root);
+    let setting = all.entry(branch).or_default();
+    if let Some(base) = base {
+        setting.base = Some(base.to_string());
+        setting.baseline = DiffBaseline::Branch;
+    }
+    if let Some(baseline) = baseline {
+        setting.baseline = baseline;
+    }
+    let setting = setting.clone();
+
+    let file = repo_root.join(BASELINE_FILE);
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let text = serde_json::to_string_pretty(&all).map_err(|e| e.to_string())?;
+    fs::write(&file, text).map_err(|e| format!("{BASELINE_FILE}: {e}"))?;
+    Ok(setting)
+}
+
+/// Arguments to `git diff` for `baseline`; `None` when its base does not
+/// resolve.
+fn diff_args(repo_root: &Path, baseline: &BaselineSetting) -> Option<Vec<String>> {
+    match baseline.baseline {
+        DiffBaseline::Head => Some(vec!["HEAD".to_string()]),
+        DiffBaseline::Staged => Some(vec!["--cached".to_string()]),
+        DiffBaseline::Unstaged => Some(Vec::new()),
+        DiffBaseline::Branch => {
+            let base = baseline.base.as_deref()?;
+            let fork = git(repo_root, &["merge-base", base, "HEAD"])?;
+            Some(vec![fork.trim().to_string()])
+        }
+    }
+}
+
+/// Untracked files the analysis includes, sorted.
+pub fn included_untracked(repo_root: &Path) -> Vec<String> {
+    fs::read_to_string(repo_root.join(INCLUDE_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str::<BTreeSet<String>>(&text).ok())
+        .map(|set| set.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Adds or removes `path` from the selection and drops the cached analysis.
+/// Returns false if nothing changed.
+pub fn set_included(repo_root: &Path, path: &str, include: bool) -> Result<bool, String> {
+    let mut set: BTreeSet<String> = included_untracked(repo_root).into_iter().collect();
+    let changed = if include {
+        set.insert(path.to_string())
+    } else {
+        set.remove(path)
+    };
+    if changed {
+        let file = repo_root.join(INCLUDE_FILE);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let text = serde_json::to_string_pretty(&set).map_err(|e| e.to_string())?;
+        fs::write(&file, text).map_err(|e| format!("{INCLUDE_FILE}: {e}"))?;
+        if cache()
+            .lock()
+            .is_ok_and(|slot| slot.as_ref().is_some_and(|c| c.root == repo_root))
+        {
+            store(repo_root, None);
+        }
+    }
+    Ok(changed)
+}
+
+/// Hash of `git status` plus the size and mtime of every dirty path; `None`
+/// for a clean tree. Osmogrep's own state directories are left out so
+/// writing the index does not invalidate the analysis. With a `Branch`
+/// baseline the commits since the fork point count too.
+pub fn fingerprint(repo_root: &Path) -> Option<String> {
+    let status = git(
+        repo_root,
+        &["status", "--porcelain", "-z", "--untracked-files=all"],
+    )?;
+    let mut hasher = Hasher::new();
+    let baseline = resolved_baseline(repo_root);
+    hasher.update(
+        serde_json::to_string(&baseline)
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    // Tuning the risk rules re-scores the same diff.
+    hasher.update(&fs::read(repo_root.join(risk::RULES_FILE)).unwrap_or_default());
+    let mut dirty = false;
+    if baseline.baseline == DiffBaseline::Branch {
+        let fork = diff_args(repo_root, &baseline).unwrap_or_default().concat();
+        let head = git(repo_root, &["rev-parse", "HEAD"]).unwrap_or_default();
+        dirty = !fork.is_empty() && fork != head.trim();
+        hasher.update(format!("{fork}..{}", head.trim()).as_bytes());
+    }
+    for entry in status.split('\0').filter(|e| e.len() > 3) {
+        let path = &entry[3..];
+        if is_own_state(path) {
+            continue;
+        }
+        dirty = true;
+        hasher.update(entry.as_bytes());
+        if let Ok(meta) = fs::metadata(repo_root.join(path)) {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            hasher.update(format!("{}:{mtime}", meta.len()).as_bytes());
+        }
+    }
+    dirty.then(|| hasher.finalize().to_hex().to_string())
+}
+
+fn is_own_state(path: &str) -> bool {
+    path.starts_with(".context/") || path.starts_with(".osmogrep/")
+}
+
+fn analyze(
+    root: &Path,
+    fingerprint: String,
+    baseline: BaselineSetting,
+    diff_args: &[String],
+) -> Option<WorkingDiff> {
+    let started = Instant::now();
+    let git_diff = |extra: &[&str]| {
+        let mut args = vec!["diff"];
+        args.extend(diff_args.iter().map(String::as_str));
+        args.extend(extra);
+        git(root, &args)
+    };
+    let mut files: Vec<ChangedFile> = git_diff(&["--numstat"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?.parse().unwrap_or(0);
+            let removed = parts.next()?.parse().unwrap_or(0);
+            Some(ChangedFile {
+                path: parts.next()?.to_string(),
+                added,
+                removed,
+                untracked: false,
+                coverage: None,
+            })
+        })
+        .collect();
+    // Untracked files are never staged.
+    let included = if baseline.baseline == DiffBaseline::Staged {
+        Vec::new()
+    } else {
+        included_untracked(root)
+    };
+    for path in git(root, &["ls-files", "--others", "--exclude-standard"])
+        .unwrap_or_default()
+        .lines()
+        .filter(|p| included.iter().any(|i| i == p))
+    {
+        let added = fs::read_to_string(root.join(path)).map_or(0, |t| t.lines().count());
+        files.push(ChangedFile {
+            path: path.to_string(),
+            added,
+            removed: 0,
+            untracked: true,
+            coverage: None,
+        });
+    }
+    files.retain(|f| !is_own_state(&f.path));
+    if files.is_empty() {
+        return None;
+    }
+
+    let hunks = changed_ranges(&git_diff(&["-U0", "--no-color"]).unwrap_or_default());
+    if let Some(coverage) = Coverage::load(root) {
+        for file in &mut files {
+            let ranges: Vec<(usize, usize)> = if file.untracked {
+                vec![(1, usize::MAX)]
+            } else {
+                hunks
+                    .iter()
+                    .filter(|(path, _, _)| *path == file.path)
+                    .map(|(_, start, end)| (*start, *end))
+                    .collect()
+            };
+            file.coverage = coverage.lines_in(&file.path, &ranges);
+        }
+    }
+    let ctx = indexer::load_or_build(root);
+    let relative = |file: &str| {
+        let path = Path::new(file);
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+    let touched: Vec<&indexer::Symbol> = ctx
+        .symbols
+        .iter()
+        .filter(|s| {
+            let file = relative(&s.file);
+            files.iter().any(|f| f.path == file && f.untracked)
+                || hunks.iter().any(|(path, start, end)| {
+                    *path == file && *start <= s.line_end && s.line_start <= *end
+                })
+        })
+        .collect();
+    let changed: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    let packages = workspace::affected(&workspace::packages(root), &changed);
+    let names: BTreeSet<&str> = touched.iter().map(|s| s.name.as_str()).collect();
+    let callers: BTreeSet<String> = touched
+        .iter()
+        .flat_map(|s| s.called_by.iter())
+        .filter(|caller| !names.contains(caller.as_str()))
+        .cloned()
+        .collect();
+
+    let risk = risk::assess(root, &files, callers.len());
+    Some(WorkingDiff {
+        fingerprint,
+        baseline,
+        files,
+        symbols: touched
+            .iter()
+            .map(|s| TouchedSymbol {
+                name: s.name.clone(),
+                path: relative(&s.file),
+                line: s.line_start,
+                end: s.line_end,
+            })
+            .collect(),
+        callers: callers.into_iter().collect(),
+        hunks,
+        packages,
+        risk,
+        analyzed_at: Instant::now(),
+        took: started.elapsed(),
+    })
+}
+
+/// Drops the cached analysis, e.g. after new coverage was collected.
+pub fn invalidate() {
+    if let Ok(mut cached) = cache().lock() {
+        *cached = None;
+    }
+}
+
+/// New-side line ranges of each hunk in a `-U0` diff, as `(path, start, end)`.
+pub(crate) fn changed_ranges(diff: &str) -> Vec<(String, usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut file = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            file = path.strip_prefix("b/").map(str::to_string);
+        } else if let (Some(hunk), Some(path)) = (line.strip_prefix("@@ "), file.as_ref()) {
+            let Some(new) = hunk.split_whitespace().find_map(|p| p.strip_prefix('+')) else {
+                continue;
+            };
+            let mut parts = new.splitn(2, ',');
+            let start: usize = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+            let len: usize = parts.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+            ranges.push((path.clone(), start, start + len.saturating_sub(1)));
+        }
+    }
+    ranges
+}
+
+fn git(root: &Path, args: &[&str]) -> Option<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// Report lines for `/inspect`.
+pub fn render(diff: &WorkingDiff, fresh: bool) -> Vec<String> {
+    let added: usize = diff.files.iter().map(|f| f.added).sum();
+    let removed: usize = diff.files.iter().map(|f| f.removed).sum();
+    let source = if fresh {
+        format!("analyzed now in {}", crate::timefmt::duration(diff.took))
+    } else {
+        format!(
+            "from cache, analyzed {} ago",
+            crate::timefmt::duration(diff.analyzed_at.elapsed())
+        )
+    };
+    let mut lines = vec![format!(
+        "● {}: {} files changed (+{added} -{removed}), {source}",
+        heading(&diff.baseline),
+        diff.files.len()
+    )];
+    for file in diff.files.iter().take(MAX_LISTED) {
+        let mark = if file.untracked { "?" } else { "M" };
+        let covered = file
+            .coverage
+            .map(|c| format!("  changed lines covered: {c}"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {mark} {}  +{} -{}{covered}",
+            file.path, file.added, file.removed
+        ));
+    }
+    if diff.files.len() > MAX_LISTED {
+        lines.push(format!("  … {} more", diff.files.len() - MAX_LISTED));
+    }
+    if !diff.symbols.is_empty() {
+        lines.push(format!("Touched symbols: {}", list(&labels(diff))));
+    }
+    if !diff.callers.is_empty() {
+        lines.push(format!("Callers to re-check: {}", list(&diff.callers)));
+    }
+    if diff.packages.iter().any(|p| p.root != ".") {
+        lines.push(format!("Packages: {}", list(&package_labels(diff))));
+    }
+    if let Some(total) = changed_line_coverage(diff) {
+        lines.push(format!("Changed lines covered: {total}"));
+    }
+    lines.push(format!("Risk: {}", diff.risk.summary()));
+    if let Some(error) = &diff.risk.rules_error {
+        lines.push(format!("  default risk rules used: {error}"));
+    }
+    lines
+}
+
+/// Coverage of all changed lines in instrumented files.
+pub fn changed_line_coverage(diff: &WorkingDiff) -> Option<LineCoverage> {
+    diff.files
+        .iter()
+        .filter_map(|f| f.coverage)
+        .reduce(|a, b| LineCoverage {
+            covered: a.covered + b.covered,
+            instrumented: a.instrumented + b.instrumented,
+        })
+}
+
+/// Compact context appended to the first prompt of an agent task.
+pub fn prompt_context(diff: &WorkingDiff) -> String {
+    let files: Vec<&str> = diff.files.iter().map(|f| f.path.as_str()).collect();
+    let mut text = format!("{}: {}.", heading(&diff.baseline), list(&files));
+    if !diff.symbols.is_empty() {
+        text.push_str(&format!(" Touched symbols: {}.", list(&labels(diff))));
+    }
+    if !diff.callers.is_empty() {
+        text.push_str(&format!(" Their callers: {}.", list(&diff.callers)));
+    }
+    if diff.packages.iter().any(|p| p.root != ".") {
+        text.push_str(&format!(
+            " Affected packages: {}.",
+            list(&package_labels(diff))
+        ));
+    }
+    let uncovered: Vec<String> = diff
+        .files
+        .iter()
+        .filter_map(|f| Some((f, f.coverage?)))
+        .filter(|(_, c)| c.covered < c.instrumented)
+        .map(|(f, c)| format!("{} ({:.0}%)", f.path, c.percent()))
+        .collect();
+    if !uncovered.is_empty() {
+        text.push_str(&format!(
+            " Changed lines the tests do not execute: {}.",
+            list(&uncovered)
+        ));
+    }
+    text
+}
+
+fn heading(baseline: &BaselineSetting) -> String {
+    match baseline.baseline {
+        DiffBaseline::Head => "Uncommitted changes in the working tree".to_string(),
+        DiffBaseline::Staged => "Staged changes".to_string(),
+        DiffBaseline::Unstaged => "Unstaged changes".to_string(),
+        DiffBaseline::Branch => format!(
+            "Changes on this branch since {}",
+            baseline.base.as_deref().unwrap_or("its base")
+        ),
+    }
+}
+
+fn package_labels(diff: &WorkingDiff) -> Vec<String> {
+    diff.packages
+        .iter()
+        .map(|p| format!("{} ({})", p.name, p.root))
+        .collect()
+}
+
+fn labels(diff: &WorkingDiff) -> Vec<String> {
+    diff.symbols.iter().map(TouchedSymbol::label).collect()
+}
+
+fn list<S: AsRef<str>>(items: &[S]) -> String {
+    let shown: Vec<&str> = items.iter().take(MAX_LISTED).map(AsRef::as_ref).collect();
+    let more = items.len().saturating_sub(MAX_LISTED);
+    if more > 0 {
+        format!("{} and {more} more", shown.join(", "))
+    } else {
+        shown.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn run_git(root: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    #[test]
+    fn analyzes_dirty_tree_and_invalidates_on_change() {
+        let root = std::env::temp_dir().join(format!("osmogrep-wdiff-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        run_git(&root, &["init", "-q"]);
+        run_git(&root, &["config", "user.email", "test@example.com"]);
+        run_git(&root, &["config", "user.name", "Osmogrep Test"]);
+        fs::write(root.join(".gitignore"), ".context/\n").unwrap();
+        let lib = "fn helper() -> u32 {\n    1\n}\n\nfn caller() -> u32 {\n    helper()\n}\n";
+        fs::write(root.join("src/lib.rs"), lib).unwrap();
+        run_git(&root, &["add", "."]);
+        run_git(&root, &["commit", "-q", "-m", "init"]);
+        assert!(fingerprint(&root).is_none());
+        assert!(current(&root).is_none());
+
+        fs::write(root.join("src/lib.rs"), lib.replace("    1\n", "    2\n")).unwrap();
+        fs::write(root.join("notes.txt"), "a\nb\n").unwrap();
+        assert_eq!(current(&root).unwrap().files.len(), 1);
+        assert!(set_included(&root, "notes.txt", true).unwrap());
+        assert!(cached(&root).is_none());
+        let diff = current(&root).unwrap();
+        assert_eq!(
+            diff.files,
+            vec![
+                ChangedFile {
+                    path: "src/lib.rs".into(),
+                    added: 1,
+                    removed: 1,
+                    untracked: false,
+                    coverage: None,
+                },
+                ChangedFile {
+                    path: "notes.txt".into(),
+                    added: 2,
+                    removed: 0,
+                    untracked: true,
+                    coverage: None,
+                },
+            ]
+        );
+        assert_eq!(labels(&diff), vec!["helper (src/lib.rs:1)"]);
+        assert_eq!(diff.callers, vec!["caller"]);
+        assert_eq!(
+            render(&diff, true).last().unwrap(),
+            "Risk: low (13): surface source +10, fan-in 1 callers +3"
+        );
+        assert!(cached(&root).is_some());
+        fs::create_dir_all(root.join(".osmogrep")).unwrap();
+        fs::write(
+            root.join(risk::RULES_FILE),
+            "[[paths]]\npattern = \"src/**\"\nweight = 20\n",
+        )
+        .unwrap();
+        assert!(cached(&root).is_none());
+        assert_eq!(current(&root).unwrap().risk.level, risk::RiskLevel::Medium);
+
+        fs::write(
+            root.join(crate::coverage::LCOV_FILE),
+            "SF:src/lib.rs\nDA:1,1\nDA:2,0\nDA:6,1\nend_of_record\n",
+        )
+        .unwrap();
+        invalidate();
+        let diff = current(&root).unwrap();
+        let changed = diff.files[0].coverage.unwrap();
+        assert_eq!((changed.covered, changed.instrumented), (0, 1));
+        assert_eq!(diff.files[1].coverage, None);
+        assert!(prompt_context(&diff).contains("do not execute: src/lib.rs (0%)"));
+
+        fs::write(root.join("notes.txt"), "a\nb\nc\n").unwrap();
+        assert!(cached(&root).is_none());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn baseline_picks_staged_unstaged_or_branch_changes_per_branch() {
+        let root = std::env::temp_dir().join(format!("osmogrep-wdiff-base-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        run_git(&root, &["init", "-q", "-b", "main"]);
+        run_git(&root, &["config", "user.email", "test@example.com"]);
+        run_git(&root, &["config", "user.name", "Osmogrep Test"]);
+        fs::write(root.join(".gitignore"), ".context/\n").unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(root.join(name), "1\n").unwrap();
+        }
+        run_git(&root, &["add", "."]);
+        run_git(&root, &["commit", "-q", "-m", "init"]);
+        run_git(&root, &["checkout", "-q", "-b", "feature"]);
+        fs::write(root.join("a.txt"), "2\n").unwrap();
+        run_git(&root, &["commit", "-q", "-am", "a"]);
+        fs::write(root.join("b.txt"), "2\n").unwrap();
+        run_git(&root, &["add", "b.txt"]);
+        fs::write(root.join("c.txt"), "2\n").unwrap();
+
+        let paths = |root: &Path| -> Vec<String> {
+            analyze_baseline(root, fingerprint(root).unwrap())
+                .unwrap()
+                .files
+                .into_iter()
+                .map(|file| file.path)
+                .collect()
+        };
+        assert_eq!(paths(&root), ["b.txt", "c.txt"]);
+        let head = fingerprint(&root);
+        set_baseline(&root, Some(DiffBaseline::Staged), None).unwrap();
+        assert_ne!(fingerprint(&root), head);
+        assert_eq!(paths(&root), ["b.txt"]);
+        set_baseline(&root, Some(DiffBaseline::Unstaged), None).unwrap();
+        assert_eq!(paths(&root), ["c.txt"]);
+
+        assert!(set_baseline(&root, None, Some("nope")).is_err());
+        let setting = set_baseline(&root, None, Some("main")).unwrap();
+        assert_eq!(setting.label(), "branch vs main");
+        assert_eq!(paths(&root), ["a.txt", "b.txt", "c.txt"]);
+        run_git(&root, &["commit", "-q", "-am", "bc"]);
+        assert!(
+            fingerprint(&root).is_some(),
+            "branch commits count as changes"
+        );
+        assert_eq!(baseline_label(&root).as_deref(), Some("branch vs main"));
+
+        run_git(&root, &["checkout", "-q", "main"]);
+        assert_eq!(baseline(&root), BaselineSetting::default());
+        assert_eq!(baseline_label(&root), None);
+        let _ = fs::remove_dir_all(root);
+    }
+}
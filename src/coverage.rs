@@ -0,0 +1,224 @@
+//! coverage.rs
+//!
+//! Line coverage from `cargo llvm-cov` or `pytest --cov`, written as lcov to
+//! `.context/coverage.lcov` by `/coverage`. The diff analysis reads it back
+//! to say how much of each changed file's new lines the suite executes, and
+//! test candidates are ranked so uncovered changes come first.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{process_runner, test_harness};
+
+pub const LCOV_FILE: &str = ".context/coverage.lcov";
+
+/// Hit counts per instrumented line, keyed by repo-relative path.
+#[derive(Debug, Default, Clone)]
+pub struct Coverage {
+    files: HashMap<String, BTreeMap<usize, u64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineCoverage {
+    pub covered: usize,
+    pub instrumented: usize,
+}
+
+impl LineCoverage {
+    pub fn percent(&self) -> f64 {
+        if self.instrumented == 0 {
+            return 100.0;
+        }
+        self.covered as f64 * 100.0 / self.instrumented as f64
+    }
+}
+
+impl std::fmt::Display for LineCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}% of {} lines", self.percent(), self.instrumented)
+    }
+}
+
+impl Coverage {
+    /// Parses `SF:`/`DA:` records; absolute source paths under `repo_root`
+    /// are made relative.
+    pub fn parse_lcov(text: &str, repo_root: &Path) -> Self {
+        let mut files: HashMap<String, BTreeMap<usize, u64>> = HashMap::new();
+        let mut current: Option<String> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(path) = line.strip_prefix("SF:") {
+                let path = Path::new(path);
+                let relative = path.strip_prefix(repo_root).unwrap_or(path);
+                current = Some(
+                    relative
+                        .to_string_lossy()
+                        .trim_start_matches("./")
+                        .to_string(),
+                );
+            } else if let (Some(da), Some(file)) = (line.strip_prefix("DA:"), current.as_ref()) {
+                let mut parts = da.split(',');
+                let (Some(Ok(line_no)), Some(Ok(hits))) = (
+                    parts.next().map(str::parse::<usize>),
+                    parts.next().map(str::parse::<u64>),
+                ) else {
+                    continue;
+                };
+                *files
+                    .entry(file.clone())
+                    .or_default()
+                    .entry(line_no)
+                    .or_default() += hits;
+            } else if line == "end_of_record" {
+                current = None;
+            }
+        }
+        Self { files }
+    }
+
+    /// The last collected coverage, if any.
+    pub fn load(repo_root: &Path) -> Option<Self> {
+        let text = fs::read_to_string(repo_root.join(LCOV_FILE)).ok()?;
+        Some(Self::parse_lcov(&text, repo_root))
+    }
+
+    pub fn total(&self) -> LineCoverage {
+        let lines = self.files.values().flat_map(|lines| lines.values());
+        LineCoverage {
+            covered: lines.clone().filter(|hits| **hits > 0).count(),
+            instrumented: lines.count(),
+        }
+    }
+
+    /// Up to `limit` instrumented files, least covered first.
+    pub fn least_covered(&self, limit: usize) -> Vec<(String, LineCoverage)> {
+        let mut files = self
+            .files
+            .iter()
+            .map(|(path, lines)| {
+                let coverage = LineCoverage {
+                    covered: lines.values().filter(|hits| **hits > 0).count(),
+                    instrumented: lines.len(),
+                };
+                (path.clone(), coverage)
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.1.percent().total_cmp(&b.1.percent()).then(a.0.cmp(&b.0)));
+        files.truncate(limit);
+        files
+    }
+
+    /// Coverage of the instrumented lines of `path` inside the inclusive
+    /// `ranges`; None when the file was not instrumented at all.
+    pub fn lines_in(&self, path: &str, ranges: &[(usize, usize)]) -> Option<LineCoverage> {
+        let lines = self.files.get(path)?;
+        let mut result = LineCoverage {
+            covered: 0,
+            instrumented: 0,
+        };
+        for (start, end) in ranges {
+            for (_, hits) in lines.range(*start..=*end) {
+                result.instrumented += 1;
+                result.covered += usize::from(*hits > 0);
+            }
+        }
+        Some(result)
+    }
+}
+
+/// The coverage command for a test framework, writing lcov to `LCOV_FILE`.
+pub fn command(framework: &str) -> Option<String> {
+    match framework {
+        "cargo" => Some(format!(
+            "cargo llvm-cov --color never --lcov --output-path {LCOV_FILE}"
+        )),
+        "pytest" => Some(format!("pytest -q --cov=. --cov-report=lcov:{LCOV_FILE}")),
+        _ => None,
+    }
+}
+
+/// Runs the suite of the first detected framework that can report coverage.
+pub fn collect(repo_root: &Path) -> Result<(String, Coverage), String> {
+    let command = test_harness::detect_frameworks(repo_root)
+        .into_iter()
+        .find_map(command)
+        .ok_or("No coverage tool for this repo (cargo llvm-cov or pytest-cov).")?;
+    fs::create_dir_all(repo_root.join(".context")).map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(repo_root.join(LCOV_FILE));
+
+    let timeout = process_runner::timeout_from_env("OSMOGREP_TEST_TIMEOUT_SECS", 300);
+    let out = process_runner::run_shell_command(&command, Some(repo_root), timeout)?;
+    match Coverage::load(repo_root) {
+        Some(coverage) => Ok((command, coverage)),
+        None => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+            let tail: Vec<&str> = tail.into_iter().rev().collect();
+            Err(format!(
+                "`{command}` wrote no coverage (exit {}): {}",
+                out.exit_code,
+                tail.join(" | ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_changed_lines_the_suite_executes() {
+        let root = Path::new("/work/repo");
+        let lcov = "\
+SF:/work/repo/src/cart.rs
+DA:1,3
+DA:2,0
+DA:5,1
+DA:9,0
+end_of_record
+SF:src/other.rs
+DA:1,1
+end_of_record
+";
+        let coverage = Coverage::parse_lcov(lcov, root);
+        assert_eq!(
+            coverage.lines_in("src/cart.rs", &[(2, 5)]),
+            Some(LineCoverage {
+                covered: 1,
+                instrumented: 2
+            })
+        );
+        assert_eq!(
+            coverage
+                .lines_in("src/cart.rs", &[(1, 1), (9, 12)])
+                .unwrap()
+                .to_string(),
+            "50% of 2 lines"
+        );
+        assert_eq!(coverage.lines_in("src/missing.rs", &[(1, 9)]), None);
+        assert_eq!(
+            coverage.total(),
+            LineCoverage {
+                covered: 3,
+                instrumented: 5
+            }
+        );
+        assert_eq!(
+            coverage.least_covered(1),
+            vec![(
+                "src/cart.rs".to_string(),
+                LineCoverage {
+                    covered: 2,
+                    instrumented: 4
+                }
+            )]
+        );
+        assert_eq!(command("go"), None);
+    }
+}
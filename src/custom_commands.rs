@@ -0,0 +1,259 @@
+//! custom_commands.rs
+//!
+//! Prompt templates in `.osmogrep/commands/<name>.md`, offered as `/<name>`
+//! by the command palette. Running one sends the expanded template to the
+//! agent:
+//!
+//! ```markdown
+//! ---
+//! description: Draft release notes since a tag
+//! args: <tag> <audience>
+//! ---
+//! Write release notes for the changes since $1 for $2 readers.
+//! Extra instructions: $ARGUMENTS
+//! ```
+//!
+//! `$ARGUMENTS` is everything after the command name and `$1`..`$9` are its
+//! words (quotes group words). The front matter is optional; without a
+//! description the first line of the template is shown. Files whose name
+//! clashes with a built-in command are skipped.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command_docs;
+
+pub const DIR: &str = ".osmogrep/commands";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomCommand {
+    /// `/release-notes`
+    pub name: String,
+    pub description: String,
+    /// Argument hint from the front matter, e.g. `<tag> <audience>`.
+    pub args: String,
+    pub template: String,
+    pub path: PathBuf,
+}
+
+impl CustomCommand {
+    fn parse(name: String, path: PathBuf, text: &str) -> Self {
+        let (front, body) = split_front_matter(text);
+        let field = |key: &str| {
+            front.iter().find_map(|line| {
+                let (k, v) = line.split_once(':')?;
+                (k.trim() == key).then(|| v.trim().trim_matches('"').to_string())
+            })
+        };
+        let template = body.trim().to_string();
+        let description = field("description").unwrap_or_else(|| {
+            template
+                .lines()
+                .map(|line| line.trim().trim_start_matches('#').trim())
+                .find(|line| !line.is_empty())
+                .unwrap_or("Custom prompt")
+                .to_string()
+        });
+        Self {
+            name,
+            description,
+            args: field("args").unwrap_or_default(),
+            template,
+            path,
+        }
+    }
+
+    /// `/release-notes <tag> <audience>`
+    pub fn usage(&self) -> String {
+        if self.args.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} {}", self.name, self.args)
+        }
+    }
+
+    /// The prompt for `/name <args>`; fails when the template uses a
+    /// positional argument that was not given.
+    pub fn expand(&self, args: &str) -> Result<String, String> {
+        let words = split_words(args);
+        let mut out = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(idx) = rest.find('$') {
+            out.push_str(&rest[..idx]);
+            let after = &rest[idx + 1..];
+            if let Some(tail) = after.strip_prefix("ARGUMENTS") {
+                out.push_str(args.trim());
+                rest = tail;
+            } else if let Some(n) = after.chars().next().and_then(|c| c.to_digit(10)) {
+                if n == 0 {
+                    out.push('$');
+                    rest = after;
+                    continue;
+                }
+                let word = words
+                    .get(n as usize - 1)
+                    .ok_or_else(|| format!("Usage: {}", self.usage()))?;
+                out.push_str(word);
+                rest = &after[1..];
+            } else {
+                out.push('$');
+                rest = after;
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// The repo's custom commands sorted by name, and a note for each file that
+/// was skipped.
+pub fn load(repo_root: &Path) -> (Vec<CustomCommand>, Vec<String>) {
+    let mut commands = Vec::new();
+    let mut problems = Vec::new();
+    let Ok(entries) = fs::read_dir(repo_root.join(DIR)) else {
+        return (commands, problems);
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let file = format!("{DIR}/{stem}.md");
+        if stem.is_empty()
+            || !stem
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            problems.push(format!("{file}: use letters, digits, - and _ in the name"));
+            continue;
+        }
+        let name = format!("/{}", stem.to_ascii_lowercase());
+        if command_docs::find(&name).is_some() {
+            problems.push(format!("{file}: {name} is a built-in command"));
+            continue;
+        }
+        match fs::read_to_string(&path) {
+            Ok(text) => commands.push(CustomCommand::parse(name, path, &text)),
+            Err(e) => problems.push(format!("{file}: {e}")),
+        }
+    }
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+    commands.dedup_by(|a, b| a.name == b.name);
+    (commands, problems)
+}
+
+/// The command `input` (`/name args...`) invokes, and its arguments.
+pub fn find<'a, 'c>(
+    commands: &'c [CustomCommand],
+    input: &'a str,
+) -> Option<(&'c CustomCommand, &'a str)> {
+    let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    commands
+        .iter()
+        .find(|cmd| cmd.name.eq_ignore_ascii_case(name))
+        .map(|cmd| (cmd, args.trim()))
+}
+
+fn split_front_matter(text: &str) -> (Vec<&str>, &str) {
+    let text = text.trim_start_matches('\u{feff}');
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (Vec::new(), text);
+    };
+    let mut front = Vec::new();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return (front, &rest[offset..]);
+        }
+        front.push(line.trim_end());
+    }
+    (Vec::new(), text)
+}
+
+/// Whitespace-separated words; single or double quotes keep a phrase
+/// together.
+fn split_words(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut in_word = false;
+    for c in args.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use uuid::Uuid;
+
+    #[test]
+    fn loads_templates_and_expands_arguments() {
+        let root = env::temp_dir().join(format!("osmogrep-commands-{}", Uuid::new_v4()));
+        let dir = root.join(DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("release-notes.md"),
+            "---\ndescription: Draft release notes\nargs: <tag> <audience>\n---\n\
+             Notes since $1 for $2 readers in $USD.\nAll: $ARGUMENTS\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("security-review.md"),
+            "# Review for security\nCheck it.\n",
+        )
+        .unwrap();
+        fs::write(dir.join("help.md"), "shadowed").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let (commands, problems) = load(&root);
+        let names: Vec<_> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["/release-notes", "/security-review"]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("/help is a built-in command"));
+        assert_eq!(commands[1].description, "Review for security");
+
+        let (cmd, args) = find(&commands, "/release-notes v1.2 \"core team\"").unwrap();
+        assert_eq!(cmd.usage(), "/release-notes <tag> <audience>");
+        assert_eq!(cmd.description, "Draft release notes");
+        assert_eq!(
+            cmd.expand(args).unwrap(),
+            "Notes since v1.2 for core team readers in $USD.\nAll: v1.2 \"core team\""
+        );
+        assert_eq!(
+            cmd.expand("v1.2").unwrap_err(),
+            "Usage: /release-notes <tag> <audience>"
+        );
+        assert!(find(&commands, "/release").is_none());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,381 @@
+//! detach.rs
+//!
+//! Agent runs that outlive the TUI. `/detach <task>` starts
+//! `osmogrep run --json-events` as a background process whose event stream
+//! is written to `<session dir>/runs/<id>.jsonl`; `osmogrep attach <id>` or
+//! `/attach <id>` replays the events missed so far and then follows the
+//! rest until the run finishes. `/queue` schedules tasks as detached runs
+//! and waits on their outcome.
+
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::state::{LogLevel, PermissionProfile};
+
+/// Lets a detached run write to a repo whose lock is held by the instance
+/// that started it.
+pub const LOCK_DELEGATE_ENV: &str = "OSMOGREP_LOCK_DELEGATE";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetachedRun {
+    pub id: String,
+    pub pid: u32,
+    pub task: String,
+    pub repo_root: PathBuf,
+    /// RFC 3339.
+    pub started_at: String,
+}
+
+impl DetachedRun {
+    pub fn started(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(&self.started_at)
+            .ok()
+            .map(|at| at.with_timezone(&Local))
+    }
+
+    pub fn events_path(&self) -> PathBuf {
+        runs_dir().join(format!("{}.jsonl", self.id))
+    }
+
+    pub fn stderr_path(&self) -> PathBuf {
+        runs_dir().join(format!("{}.stderr", self.id))
+    }
+
+    /// Whether the run ended: a terminal event was written, or the process
+    /// is gone.
+    pub fn is_finished(&self) -> bool {
+        let ended = fs::read_to_string(self.events_path())
+            .map(|text| {
+                text.lines()
+                    .rev()
+                    .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+                    .any(|event| is_terminal(&event))
+            })
+            .unwrap_or(false);
+        ended || !crate::instance::pid_alive(self.pid)
+    }
+
+    /// None while the run is going; then its final answer, or why it did
+    /// not finish.
+    pub fn outcome(&self) -> Option<Result<String, String>> {
+        let events = fs::read_to_string(self.events_path()).unwrap_or_default();
+        if let Some(outcome) = outcome_of(&events) {
+            return Some(outcome);
+        }
+        if crate::instance::pid_alive(self.pid) {
+            return None;
+        }
+        Some(Err(format!(
+            "run exited without finishing; see {}",
+            self.stderr_path().display()
+        )))
+    }
+}
+
+/// Final answer or failure recorded in an event log, if it has ended.
+fn outcome_of(events: &str) -> Option<Result<String, String>> {
+    let mut answer = String::new();
+    let mut streamed = String::new();
+    for event in events
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+    {
+        let text = event
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match event
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+        {
+            "stream_delta" => streamed.push_str(text),
+            "stream_done" => answer = std::mem::take(&mut streamed),
+            "output_text" => answer = text.to_string(),
+            "done" => return Some(Ok(answer)),
+            "error" => {
+                let message = event.get("message").and_then(Value::as_str);
+                return Some(Err(message.unwrap_or("run failed").to_string()));
+            }
+            "cancelled" => return Some(Err("run cancelled".to_string())),
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn runs_dir() -> PathBuf {
+    crate::persistence::session_dir().join("runs")
+}
+
+pub fn spawn(
+    repo_root: &Path,
+    task: &str,
+    profile: PermissionProfile,
+    auto_approve: bool,
+) -> Result<DetachedRun, String> {
+    let dir = runs_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    let id = Uuid::new_v4().simple().to_string()[..8].to_string();
+    let prompt_path = dir.join(format!("{id}.prompt"));
+    fs::write(&prompt_path, task).map_err(|e| e.to_string())?;
+    let stdout = File::create(dir.join(format!("{id}.jsonl"))).map_err(|e| e.to_string())?;
+    let stderr = File::create(dir.join(format!("{id}.stderr"))).map_err(|e| e.to_string())?;
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut cmd = Command::new(exe);
+    cmd.arg("run")
+        .arg("--repo-root")
+        .arg(repo_root)
+        .arg("--prompt-file")
+        .arg(&prompt_path)
+        .arg("--json-events")
+        .arg("--permission-profile")
+        .arg(profile.as_str())
+        .env(LOCK_DELEGATE_ENV, crate::instance::instance_id())
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr);
+    if auto_approve {
+        cmd.arg("--auto-approve");
+    }
+    // Its own process group keeps the run alive when the terminal closes.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("failed to start run: {e}"))?;
+    let run = DetachedRun {
+        id,
+        pid: child.id(),
+        task: task.to_string(),
+        repo_root: repo_root.to_path_buf(),
+        started_at: Local::now().to_rfc3339(),
+    };
+    let meta = serde_json::to_string_pretty(&run).map_err(|e| e.to_string())?;
+    fs::write(dir.join(format!("{}.json", run.id)), meta).map_err(|e| e.to_string())?;
+    // Reap the child if it finishes while we are still running.
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(run)
+}
+
+/// Detached runs, newest first.
+pub fn list() -> Vec<DetachedRun> {
+    let mut runs: Vec<DetachedRun> = fs::read_dir(runs_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|text| serde_json::from_str(&text).ok())
+        .collect();
+    runs.sort_by_key(|run| std::cmp::Reverse(run.started()));
+    runs
+}
+
+/// Run whose id starts with `id`.
+pub fn find(id: &str) -> Result<DetachedRun, String> {
+    let id = id.trim();
+    let mut matches: Vec<DetachedRun> = list()
+        .into_iter()
+        .filter(|run| !id.is_empty() && run.id.starts_with(id))
+        .collect();
+    match matches.len() {
+        0 => Err(format!("no detached run {id}")),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{n} detached runs match {id}; use more characters")),
+    }
+}
+
+pub fn is_terminal(event: &Value) -> bool {
+    matches!(
+        event.get("type").and_then(Value::as_str),
+        Some("done" | "error" | "cancelled")
+    )
+}
+
+/// Reads events appended to a run's event file since the last poll.
+pub struct EventTail {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+    streamed: String,
+}
+
+impl EventTail {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            partial: String::new(),
+            streamed: String::new(),
+        }
+    }
+
+    /// Complete events written since the last call.
+    pub fn poll(&mut self) -> Vec<Value> {
+        let Ok(mut file) = File::open(&self.path) else {
+            return Vec::new();
+        };
+        if file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return Vec::new();
+        }
+        let mut chunk = String::new();
+        let Ok(read) = file.read_to_string(&mut chunk) else {
+            return Vec::new();
+        };
+        self.offset += read as u64;
+        self.partial.push_str(&chunk);
+
+        let Some(end) = self.partial.rfind('\n') else {
+            return Vec::new();
+        };
+        let complete: String = self.partial.drain(..=end).collect();
+        complete
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Log lines for `event`. Streamed text is buffered until the stream
+    /// ends so it shows as whole lines.
+    pub fn render(&mut self, event: &Value) -> Vec<(LogLevel, String)> {
+        let text = |key: &str| {
+            event
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let info = |line: String| vec![(LogLevel::Info, line)];
+
+        match event
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+        {
+            "run_start" => info(format!("▶ {}", text("task"))),
+            "tool_call" => info(format!(
+                "→ {} {}",
+                text("name"),
+                event.get("args").map(Value::to_string).unwrap_or_default()
+            )),
+            "tool_result" => info(format!("  {}", text("summary"))),
+            "edit_complete" => info(format!("✎ {}", text("path"))),
+            "validation_complete" => {
+                let passed = event.get("passed").and_then(Value::as_bool) == Some(true);
+                vec![(
+                    if passed {
+                        LogLevel::Success
+                    } else {
+                        LogLevel::Warn
+                    },
+                    format!("{} {}", if passed { "✓" } else { "✗" }, text("command")),
+                )]
+            }
+            "permission_request" => {
+                let approved = event.get("approved").and_then(Value::as_bool) == Some(true);
+                vec![(
+                    if approved {
+                        LogLevel::Info
+                    } else {
+                        LogLevel::Warn
+                    },
+                    format!(
+                        "{} {} {}",
+                        if approved { "Approved" } else { "Denied" },
+                        text("tool_name"),
+                        text("args_summary")
+                    ),
+                )]
+            }
+            "stream_delta" => {
+                self.streamed.push_str(&text("text"));
+                Vec::new()
+            }
+            "stream_done" => std::mem::take(&mut self.streamed)
+                .lines()
+                .map(|line| (LogLevel::Info, line.to_string()))
+                .collect(),
+            "output_text" => text("text")
+                .lines()
+                .map(|line| (LogLevel::Info, line.to_string()))
+                .collect(),
+            "error" => vec![(LogLevel::Error, format!("Run failed: {}", text("message")))],
+            "cancelled" => vec![(LogLevel::Warn, "Run cancelled.".to_string())],
+            "done" => vec![(LogLevel::Success, "Run finished.".to_string())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn tail_reads_only_complete_new_events() {
+        let path = std::env::temp_dir().join(format!("osmogrep-run-{}.jsonl", Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, r#"{{"type":"run_start","task":"refactor"}}"#).unwrap();
+        write!(file, r#"{{"type":"stream_delta","text":"Hel"#).unwrap();
+        file.flush().unwrap();
+
+        let mut tail = EventTail::new(path.clone());
+        let events = tail.poll();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            tail.render(&events[0]),
+            vec![(LogLevel::Info, "▶ refactor".to_string())]
+        );
+
+        writeln!(file, r#"lo\nworld"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"stream_done"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"done"}}"#).unwrap();
+        file.flush().unwrap();
+
+        let events = tail.poll();
+        assert_eq!(events.len(), 3);
+        assert!(tail.render(&events[0]).is_empty());
+        let lines: Vec<String> = tail.render(&events[1]).into_iter().map(|l| l.1).collect();
+        assert_eq!(lines, vec!["Hello", "world"]);
+        assert!(is_terminal(&events[2]));
+        assert!(tail.poll().is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn outcome_is_the_last_answer_once_the_run_ends() {
+        let mut events = [
+            r#"{"type":"output_text","text":"Planning."}"#,
+            r#"{"type":"stream_delta","text":"Done "}"#,
+            r#"{"type":"stream_delta","text":"refactoring."}"#,
+        ]
+        .join("\n");
+        assert_eq!(outcome_of(&events), None);
+
+        events.push_str("\n{\"type\":\"stream_done\"}\n{\"type\":\"done\"}\n");
+        assert_eq!(
+            outcome_of(&events),
+            Some(Ok("Done refactoring.".to_string()))
+        );
+        assert_eq!(
+            outcome_of(r#"{"type":"error","message":"no key"}"#),
+            Some(Err("no key".to_string()))
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! file_log.rs
+//!
+//! Structured logs that outlive the TUI, as JSON lines under
+//! `~/.local/state/osmogrep/logs/`:
+//!
+//! ```toml
+//! [logging]
+//! level = "info"       # off, error, warn, info, debug or trace
+//! rotation = "daily"   # hourly, daily or never
+//! max_files = 7        # older files are deleted on rotation
+//! dir = "~/logs/osmogrep"
+//! ```
+//!
+//! `OSMOGREP_LOG=debug` overrides the level. Runs and their tool calls,
+//! permission answers and errors come from the run ledger; model calls and
+//! HTTP requests carry their latency; warnings shown in the TUI are copied.
+
+use std::env;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_appender::rolling::{self, RollingFileAppender};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_level")]
+    pub level: String,
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            rotation: Rotation::default(),
+            max_files: default_max_files(),
+            dir: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// `OSMOGREP_LOG`, else the configured level.
+    pub fn level_filter(&self) -> Result<LevelFilter, String> {
+        let level = env::var("OSMOGREP_LOG").unwrap_or_else(|_| self.level.clone());
+        level.trim().parse().map_err(|_| {
+            format!("unknown log level `{level}` (off, error, warn, info, debug or trace)")
+        })
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => crate::images::expand_home(&dir.to_string_lossy()),
+            None => dirs::state_dir()
+                .or_else(dirs::data_local_dir)
+                .unwrap_or_else(env::temp_dir)
+                .join("osmogrep")
+                .join("logs"),
+        }
+    }
+
+    fn appender(&self) -> Result<RollingFileAppender, String> {
+        let rotation = match self.rotation {
+            Rotation::Hourly => rolling::Rotation::HOURLY,
+            Rotation::Daily => rolling::Rotation::DAILY,
+            Rotation::Never => rolling::Rotation::NEVER,
+        };
+        let dir = self.dir();
+        RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix("osmogrep")
+            .filename_suffix("jsonl")
+            .max_log_files(self.max_files.max(1))
+            .build(&dir)
+            .map_err(|e| format!("{}: {e}", dir.display()))
+    }
+}
+
+/// The JSON-lines subscriber for `cfg`; `None` when logging is off.
+pub fn subscriber(cfg: &LoggingConfig) -> Result<Option<impl Subscriber + Send + Sync>, String> {
+    let level = cfg.level_filter()?;
+    if level == LevelFilter::OFF {
+        return Ok(None);
+    }
+    Ok(Some(
+        tracing_subscriber::fmt()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(false)
+            .with_ansi(false)
+            .with_max_level(level)
+            .with_writer(cfg.appender()?)
+            .finish(),
+    ))
+}
+
+/// Installs the file logger for the process. Returns the log directory,
+/// or `None` when logging is off.
+pub fn init(cfg: &LoggingConfig) -> Result<Option<PathBuf>, String> {
+    let Some(subscriber) = subscriber(cfg)? else {
+        return Ok(None);
+    };
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| e.to_string())?;
+    Ok(Some(cfg.dir()))
+}
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+fn default_max_files() -> usize {
+    7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn writes_json_lines_at_the_configured_level() {
+        let dir = env::temp_dir().join(format!("osmogrep-logs-{}", Uuid::new_v4()));
+        let cfg: LoggingConfig = toml::from_str(&format!(
+            "level = \"info\"\nrotation = \"never\"\ndir = {:?}",
+            dir.display().to_string()
+        ))
+        .unwrap();
+        assert_eq!(cfg.max_files, 7);
+        assert!(LoggingConfig {
+            level: "chatty".into(),
+            ..cfg.clone()
+        }
+        .level_filter()
+        .is_err());
+
+        let logger = subscriber(&cfg).unwrap().unwrap();
+        tracing::subscriber::with_default(logger, || {
+            tracing::info!(tool = "run_shell", duration_ms = 42u64, "tool finished");
+            tracing::debug!("too detailed");
+        });
+
+        let text = fs::read_to_string(dir.join("osmogrep.jsonl")).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "tool finished");
+        assert_eq!(lines[0]["tool"], "run_shell");
+        assert_eq!(lines[0]["duration_ms"], 42);
+        let off = LoggingConfig {
+            level: "off".into(),
+            ..cfg
+        };
+        assert!(subscriber(&off).unwrap().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
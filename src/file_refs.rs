@@ -0,0 +1,267 @@
+//! file_refs.rs
+//!
+//! `path:line[:col]` references in log and diff output, and what to do
+//! with one: show an excerpt in the execution panel, or hand it to the
+//! editor configured under `[editor]`:
+//!
+//! ```toml
+//! [editor]
+//! command = "code -g {path}:{line}:{col}"
+//! run = true   # default false: copy the command to the clipboard
+//! ```
+//!
+//! Without `[editor]`, `/open` and `e` open the file in `$VISUAL` or
+//! `$EDITOR` with the TUI suspended until the editor exits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthStr;
+
+/// Lines shown on each side of the target line in an excerpt.
+pub const EXCERPT_RADIUS: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditorConfig {
+    pub command: String,
+    /// Run the command instead of copying it.
+    #[serde(default)]
+    pub run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRef {
+    pub path: String,
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+impl FileRef {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let mut parts = text.splitn(3, ':');
+        let path = parts.next()?.trim();
+        if path.is_empty() {
+            return None;
+        }
+        let line = match parts.next() {
+            Some(raw) => raw.trim().parse().ok().filter(|n| *n > 0)?,
+            None => 1,
+        };
+        let column = parts.next().and_then(|raw| raw.trim().parse().ok());
+        Some(Self {
+            path: path.to_string(),
+            line,
+            column,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        match self.column {
+            Some(col) => format!("{}:{}:{col}", self.path, self.line),
+            None => format!("{}:{}", self.path, self.line),
+        }
+    }
+
+    /// Existing file this points at, relative paths taken from `repo_root`.
+    pub fn resolve(&self, repo_root: &Path) -> Option<PathBuf> {
+        let path = Path::new(&self.path);
+        let full = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            repo_root.join(path)
+        };
+        full.is_file().then_some(full)
+    }
+}
+
+/// A reference on screen; `start..end` are terminal columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefHit {
+    pub row: u16,
+    pub start: u16,
+    pub end: u16,
+    pub target: FileRef,
+}
+
+/// References in `text` with their display-column spans.
+pub fn find_refs(text: &str) -> Vec<(usize, usize, FileRef)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?:^|[\s(\[`'\x22=>])",
+            r"((?:[\w.\-]+/)*[\w.\-]*\w\.[A-Za-z][A-Za-z0-9]*)",
+            r":(\d+)(?::(\d+))?"
+        ))
+        .expect("file ref regex")
+    });
+
+    re.captures_iter(text)
+        .filter_map(|caps| {
+            let path = caps.get(1)?;
+            let line = caps.get(2)?.as_str().parse().ok().filter(|n| *n > 0)?;
+            let column = caps.get(3).and_then(|c| c.as_str().parse().ok());
+            let end = caps.get(3).or(caps.get(2))?.end();
+            Some((
+                text[..path.start()].width(),
+                text[..end].width(),
+                FileRef {
+                    path: path.as_str().to_string(),
+                    line,
+                    column,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Most recent reference in `texts`, scanning from the end.
+pub fn latest_ref<'a>(texts: impl DoubleEndedIterator<Item = &'a str>) -> Option<FileRef> {
+    texts
+        .rev()
+        .find_map(|text| find_refs(text).pop().map(|(_, _, target)| target))
+}
+
+/// `{path}`, `{line}` and `{col}` substituted; the path is shell-quoted.
+pub fn editor_command(template: &str, path: &Path, target: &FileRef) -> String {
+    template
+        .replace("{path}", &shell_quote(&path.display().to_string()))
+        .replace("{line}", &target.line.to_string())
+        .replace("{col}", &target.column.unwrap_or(1).to_string())
+}
+
+/// `$VISUAL`, else `$EDITOR`, when set.
+pub fn terminal_editor() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|editor| editor.trim().to_string())
+        .find(|editor| !editor.is_empty())
+}
+
+/// The shell command opening `path` at `target`'s line in `editor`, an
+/// `$EDITOR`-style command. Editors that take `path:line:col` get that;
+/// the rest get the `+line path` most terminal editors understand.
+pub fn terminal_editor_command(editor: &str, path: &Path, target: &FileRef) -> String {
+    let program = editor
+        .split_whitespace()
+        .next()
+        .and_then(|word| Path::new(word).file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let located = format!(
+        "{}:{}:{}",
+        path.display(),
+        target.line,
+        target.column.unwrap_or(1)
+    );
+    match program {
+        "code" | "code-insiders" | "codium" | "cursor" => {
+            format!("{editor} -g {}", shell_quote(&located))
+        }
+        "subl" | "hx" | "helix" | "zed" => format!("{editor} {}", shell_quote(&located)),
+        _ => format!(
+            "{editor} +{} {}",
+            target.line,
+            shell_quote(&path.display().to_string())
+        ),
+    }
+}
+
+/// Numbered lines around `line`, the target marked with `>`.
+pub fn excerpt(path: &Path, line: usize, radius: usize) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.is_empty() {
+        return Ok(vec!["(empty file)".to_string()]);
+    }
+    let line = line.clamp(1, lines.len());
+    let first = line.saturating_sub(radius).max(1);
+    let last = (line + radius).min(lines.len());
+    let width = last.to_string().len();
+
+    Ok((first..=last)
+        .map(|n| {
+            let marker = if n == line { '>' } else { ' ' };
+            format!("{marker}{n:>width$} │ {}", lines[n - 1])
+        })
+        .collect())
+}
+
+pub(crate) fn shell_quote(raw: &str) -> String {
+    if raw
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-+".contains(c))
+    {
+        raw.to_string()
+    } else {
+        format!("'{}'", raw.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn finds_refs_in_compiler_and_log_output() {
+        let refs = find_refs("error: --> src/agent.rs:42:7 (see tests/cli.rs:3)");
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].0, 11);
+        assert_eq!(refs[0].1, 28);
+        assert_eq!(
+            refs[0].2,
+            FileRef {
+                path: "src/agent.rs".into(),
+                line: 42,
+                column: Some(7),
+            }
+        );
+        assert_eq!(refs[1].2.label(), "tests/cli.rs:3");
+
+        assert!(find_refs("listening on 127.0.0.1:8080 at 12:30").is_empty());
+        assert_eq!(
+            latest_ref(["a.rs:1", "nothing", "b.py:9 then"].into_iter()),
+            FileRef::parse("b.py:9")
+        );
+    }
+
+    #[test]
+    fn builds_editor_command_and_excerpt() {
+        let target = FileRef::parse("src/my file.rs:12:4").unwrap();
+        assert_eq!(
+            editor_command(
+                "code -g {path}:{line}:{col}",
+                Path::new("/r/src/my file.rs"),
+                &target
+            ),
+            "code -g '/r/src/my file.rs':12:4"
+        );
+        let path = Path::new("/r/src/my file.rs");
+        assert_eq!(
+            terminal_editor_command("nvim", path, &target),
+            "nvim +12 '/r/src/my file.rs'"
+        );
+        assert_eq!(
+            terminal_editor_command("/usr/bin/code --reuse-window", path, &target),
+            "/usr/bin/code --reuse-window -g '/r/src/my file.rs:12:4'"
+        );
+        assert_eq!(
+            terminal_editor_command("hx", path, &target),
+            "hx '/r/src/my file.rs:12:4'"
+        );
+
+        let path = std::env::temp_dir().join(format!("osmogrep-ref-{}.txt", Uuid::new_v4()));
+        let body: String = (1..=30).map(|n| format!("line {n}\n")).collect();
+        fs::write(&path, body).unwrap();
+        let lines = excerpt(&path, 3, 2).unwrap();
+        assert_eq!(lines.first().unwrap(), " 1 │ line 1");
+        assert_eq!(lines[2], ">3 │ line 3");
+        assert_eq!(lines.len(), 5);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,322 @@
+//! fix_tests.rs
+//!
+//! `osmogrep fix-tests`: a repair loop over a failing suite. Failing tests
+//! are grouped by the file that defines them; each group goes to the agent
+//! with the part of the runner output about its tests, and after the patch
+//! only that group's tests run again. Rounds repeat until the suite is
+//! green or the round budget is spent.
+
+use std::{fs, path::Path};
+
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::context::indexer;
+use crate::file_refs::shell_quote;
+use crate::test_harness::{self, TestRun};
+
+/// Characters of runner output given to the agent per group.
+const EXCERPT_LIMIT: usize = 3_000;
+/// Lines after a bare mention of a test when the runner prints no block.
+const MENTION_LINES: usize = 25;
+
+/// Failing tests defined in one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureGroup {
+    /// `None` when the runner did not name the tests or no file defines them.
+    pub file: Option<String>,
+    pub tests: Vec<String>,
+    pub excerpt: String,
+}
+
+impl FailureGroup {
+    pub fn label(&self) -> String {
+        self.file
+            .clone()
+            .unwrap_or_else(|| "unlocated tests".to_string())
+    }
+}
+
+/// The failures of `run`, one group per defining file in first-failure
+/// order. A run that names no failing tests is one group with the tail of
+/// its output.
+pub fn group_failures(repo_root: &Path, run: &TestRun) -> Vec<FailureGroup> {
+    if run.success {
+        return Vec::new();
+    }
+    if run.failures.is_empty() {
+        return vec![FailureGroup {
+            file: None,
+            tests: Vec::new(),
+            excerpt: tail(&run.output),
+        }];
+    }
+    let extensions = test_harness::runner(&run.framework)
+        .map(|runner| runner.extensions())
+        .unwrap_or_default();
+    let mut groups: Vec<FailureGroup> = Vec::new();
+    for test in &run.failures {
+        let file = locate_test(repo_root, test, extensions);
+        let excerpt = failure_excerpt(&run.output, test).unwrap_or_default();
+        match groups.iter_mut().find(|g| g.file == file) {
+            Some(group) => {
+                group.tests.push(test.clone());
+                if !excerpt.is_empty() {
+                    group.excerpt = clip(&format!("{}\n\n{excerpt}", group.excerpt));
+                }
+            }
+            None => groups.push(FailureGroup {
+                file,
+                tests: vec![test.clone()],
+                excerpt: clip(&excerpt),
+            }),
+        }
+    }
+    groups
+}
+
+/// Groups of a scoped rerun of `group`; keeps the group's file and tests
+/// when the rerun names no failures.
+pub fn regroup(repo_root: &Path, group: &FailureGroup, rerun: &TestRun) -> Vec<FailureGroup> {
+    let groups = group_failures(repo_root, rerun);
+    if groups.iter().any(|g| !g.tests.is_empty()) {
+        return groups;
+    }
+    groups
+        .into_iter()
+        .map(|g| FailureGroup {
+            excerpt: g.excerpt,
+            ..group.clone()
+        })
+        .collect()
+}
+
+/// The file defining `test`: the path prefix of pytest-style ids, or the
+/// source file with a matching definition, preferring paths that mention
+/// the test's module segments.
+pub fn locate_test(repo_root: &Path, test: &str, extensions: &[&str]) -> Option<String> {
+    if let Some((path, _)) = test.split_once("::") {
+        if repo_root.join(path).is_file() {
+            return Some(path.to_string());
+        }
+    }
+    let segments: Vec<&str> = test
+        .split(|c: char| c == ':' || c == '.' || c == '/' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let name = regex::escape(segments.last()?);
+    let definition = Regex::new(&format!(
+        r#"(?m)(\b(fn|def|func)\s+{name}\b|\b(test|it)\(\s*['"`]{name}['"`]|\bvoid\s+{name}\s*\()"#
+    ))
+    .ok()?;
+    let mut found: Vec<String> = WalkDir::new(repo_root)
+        .into_iter()
+        .filter_entry(|e| {
+            let rel = e.path().strip_prefix(repo_root).unwrap_or(e.path());
+            !indexer::should_ignore(rel)
+        })
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.is_empty() || extensions.contains(&ext))
+        })
+        .filter(|e| fs::read_to_string(e.path()).is_ok_and(|text| definition.is_match(&text)))
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(repo_root).ok()?;
+            Some(rel.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    found.sort();
+    let modules = &segments[..segments.len() - 1];
+    let best = found
+        .iter()
+        .position(|path| modules.iter().any(|m| *m != "tests" && path.contains(m)))
+        .unwrap_or(0);
+    (!found.is_empty()).then(|| found.swap_remove(best))
+}
+
+/// The part of `output` about `test`: cargo's `---- test stdout ----`
+/// block, pytest's `____ test ____` section, or the lines after the first
+/// mention.
+pub fn failure_excerpt(output: &str, test: &str) -> Option<String> {
+    let lines: Vec<&str> = output.lines().collect();
+    let short = test.rsplit("::").next().unwrap_or(test);
+    let cargo_header = format!("---- {test} stdout ----");
+    let pytest_header =
+        |line: &str| line.starts_with("___") && line.trim_matches('_').trim().ends_with(short);
+    let (start, limit) = match lines
+        .iter()
+        .position(|line| line.trim() == cargo_header || pytest_header(line))
+    {
+        Some(start) => (start, lines.len()),
+        None => {
+            let start = lines.iter().position(|line| line.contains(short))?;
+            (start, (start + MENTION_LINES).min(lines.len()))
+        }
+    };
+    // A block ends where the next test's block or the summary starts.
+    let end = lines[start + 1..limit]
+        .iter()
+        .position(|line| {
+            line.starts_with("---- ")
+                || line.starts_with("___")
+                || line.starts_with("===")
+                || line.trim() == "failures:"
+        })
+        .map_or(limit, |offset| start + 1 + offset);
+    Some(clip(lines[start..end].join("\n").trim_end()))
+}
+
+/// Test command arguments that rerun only `group`, for the runner named
+/// `framework`; `None` runs the whole suite.
+pub fn rerun_target(framework: &str, group: &FailureGroup) -> Option<String> {
+    let quoted = || {
+        group
+            .tests
+            .iter()
+            .map(|t| shell_quote(t))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    match framework {
+        "cargo" if !group.tests.is_empty() => Some(format!("-- {}", quoted())),
+        "go" if !group.tests.is_empty() => Some(format!(
+            "-run {}",
+            shell_quote(&format!("^({})$", group.tests.join("|")))
+        )),
+        "pytest" | "jest" => group.file.as_deref().map(shell_quote),
+        _ => None,
+    }
+}
+
+/// Agent task for one group in `round` of `rounds`.
+pub fn repair_prompt(group: &FailureGroup, framework: &str, round: usize, rounds: usize) -> String {
+    let mut prompt = match &group.file {
+        Some(file) => {
+            format!("These {framework} tests in {file} fail (repair round {round} of {rounds}):\n")
+        }
+        None => format!("The {framework} test suite fails (repair round {round} of {rounds}):\n"),
+    };
+    for test in &group.tests {
+        prompt.push_str(&format!("- `{test}`\n"));
+    }
+    if group.excerpt.is_empty() {
+        prompt.push_str("\nThe runner output about them was cut off; run them to see it.\n");
+    } else {
+        prompt.push_str(&format!("\nRunner output:\n```\n{}\n```\n", group.excerpt));
+    }
+    prompt.push_str(
+        "\nRules:\n\
+         1) Find the root cause in the code under test before touching the test; change an \
+            assertion only when the test expects behavior that was deliberately changed.\n\
+         2) Keep the patch focused on these failures. Do not delete, skip or weaken tests.\n\
+         3) Run these tests before finishing; the loop reruns them after your answer.\n",
+    );
+    prompt
+}
+
+fn tail(output: &str) -> String {
+    let skip = output.chars().count().saturating_sub(EXCERPT_LIMIT);
+    output.chars().skip(skip).collect()
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_LIMIT {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(EXCERPT_LIMIT).collect();
+    out.push_str("\n...truncated...");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn failing_run(framework: &str, failures: &[&str], output: &str) -> TestRun {
+        TestRun {
+            framework: framework.to_string(),
+            command: String::new(),
+            exit_code: 101,
+            duration_ms: 0,
+            success: false,
+            passed: 1,
+            failed: failures.len(),
+            failures: failures.iter().map(|f| f.to_string()).collect(),
+            slowest: Vec::new(),
+            output: output.to_string(),
+            timed_out: false,
+            cancelled: false,
+            verification: None,
+        }
+    }
+
+    #[test]
+    fn groups_failures_by_file_with_their_output_and_scoped_reruns() {
+        let root = std::env::temp_dir().join(format!("osmogrep-fixtests-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src/parse")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "#[test]\nfn adds() {}\n#[test]\nfn subtracts() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/parse/mod.rs"), "#[test]\nfn adds() {}\n").unwrap();
+        let output = "running 3 tests\n\
+            test tests::adds ... FAILED\n\
+            test parse::tests::adds ... FAILED\n\
+            test tests::subtracts ... FAILED\n\n\
+            failures:\n\n\
+            ---- tests::adds stdout ----\n\
+            assertion `left == right` failed\n  left: 3\n right: 4\n\n\
+            ---- parse::tests::adds stdout ----\n\
+            parse panicked\n\n\
+            failures:\n    tests::adds\n";
+        let run = failing_run(
+            "cargo",
+            &["tests::adds", "parse::tests::adds", "tests::subtracts"],
+            output,
+        );
+        let groups = group_failures(&root, &run);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].file.as_deref(), Some("src/lib.rs"));
+        assert_eq!(groups[0].tests, ["tests::adds", "tests::subtracts"]);
+        assert!(groups[0]
+            .excerpt
+            .starts_with("---- tests::adds stdout ----"));
+        assert!(groups[0].excerpt.contains("right: 4"));
+        assert!(!groups[0].excerpt.contains("parse panicked"));
+        assert_eq!(groups[1].file.as_deref(), Some("src/parse/mod.rs"));
+        assert_eq!(
+            rerun_target("cargo", &groups[0]).as_deref(),
+            Some("-- 'tests::adds' 'tests::subtracts'")
+        );
+        let prompt = repair_prompt(&groups[1], "cargo", 1, 3);
+        assert!(prompt.starts_with("These cargo tests in src/parse/mod.rs fail"));
+        assert!(prompt.contains("parse panicked"));
+
+        fs::write(
+            root.join("test_api.py"),
+            "def test_get():\n    assert False\n",
+        )
+        .unwrap();
+        let pytest = "____ test_get ____\n    def test_get():\n>       assert False\nE       assert False\n==== 1 failed ====\n";
+        let run = failing_run("pytest", &["test_api.py::test_get"], pytest);
+        let groups = group_failures(&root, &run);
+        assert_eq!(groups[0].file.as_deref(), Some("test_api.py"));
+        assert!(groups[0].excerpt.ends_with("E       assert False"));
+        assert_eq!(
+            rerun_target("pytest", &groups[0]).as_deref(),
+            Some("test_api.py")
+        );
+
+        let rerun = failing_run("pytest", &[], "1 failed");
+        let regrouped = regroup(&root, &groups[0], &rerun);
+        assert_eq!(regrouped[0].tests, ["test_api.py::test_get"]);
+        assert_eq!(regrouped[0].excerpt, "1 failed");
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,27 @@
+//! git.rs
+//!
+//! Runs git in a repository for the modules that read or change its state
+//! (`/git`, `/pr`, `/rebase`, the testgen ledger).
+
+use std::path::Path;
+use std::process::Command;
+
+/// Stdout of `git -C <repo_root> <args>`, or what git printed when it
+/// failed: stderr, or stdout when stderr is empty.
+pub fn git(repo_root: &Path, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo_root)
+        .args(args)
+        // Commands that would open an editor (`rebase --continue`) keep the
+        // message they were given instead.
+        .env("GIT_EDITOR", "true")
+        .output()
+        .map_err(|e| format!("git: {e}"))?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        return Err(if stderr.is_empty() { stdout } else { stderr });
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
@@ -0,0 +1,154 @@
+//! git_status.rs
+//!
+//! `/git` (and `/status git`): staged, unstaged and untracked files, with
+//! per-file actions to stage a file, add it to `.gitignore`, or include an
+//! untracked file in the background diff analysis as an all-new addition.
+
+use std::{fs, path::Path};
+
+use crate::git::git;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: String,
+    /// Index status letter from `git status --porcelain`, if staged.
+    pub staged: Option<char>,
+    /// Worktree status letter, if changed since staging.
+    pub unstaged: Option<char>,
+    pub untracked: bool,
+}
+
+pub fn status(repo_root: &Path) -> Result<Vec<StatusEntry>, String> {
+    let out = git(
+        repo_root,
+        &["status", "--porcelain=v1", "-z", "--untracked-files=all"],
+    )?;
+    Ok(parse_status(&out))
+}
+
+fn parse_status(out: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    let mut fields = out.split('\0').filter(|f| !f.is_empty());
+    while let Some(field) = fields.next() {
+        let mut chars = field.chars();
+        let (Some(x), Some(y)) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        let path = field.get(3..).unwrap_or("").to_string();
+        // Renames and copies are followed by the original path.
+        if matches!(x, 'R' | 'C') {
+            fields.next();
+        }
+        let letter = |c: char| (c != ' ' && c != '?').then_some(c);
+        entries.push(StatusEntry {
+            path,
+            staged: letter(x),
+            unstaged: letter(y),
+            untracked: x == '?',
+        });
+    }
+    entries
+}
+
+pub fn stage(repo_root: &Path, path: &str) -> Result<(), String> {
+    git(repo_root, &["add", "--", path]).map(|_| ())
+}
+
+/// Appends `path` to the repo's `.gitignore`. Returns false if it was
+/// already listed.
+pub fn ignore(repo_root: &Path, path: &str) -> Result<bool, String> {
+    let gitignore = repo_root.join(".gitignore");
+    let mut text = fs::read_to_string(&gitignore).unwrap_or_default();
+    if text.lines().any(|line| line.trim() == path) {
+        return Ok(false);
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(path);
+    text.push('\n');
+    fs::write(&gitignore, text).map_err(|e| format!(".gitignore: {e}"))?;
+    Ok(true)
+}
+
+/// Report lines for `/git`; `included` are the untracked files that the
+/// diff analysis treats as new additions.
+pub fn render(entries: &[StatusEntry], included: &[String]) -> Vec<String> {
+    if entries.is_empty() {
+        return vec!["Working tree is clean.".to_string()];
+    }
+    let staged: Vec<&StatusEntry> = entries.iter().filter(|e| e.staged.is_some()).collect();
+    let unstaged: Vec<&StatusEntry> = entries.iter().filter(|e| e.unstaged.is_some()).collect();
+    let untracked: Vec<&StatusEntry> = entries.iter().filter(|e| e.untracked).collect();
+
+    let mut lines = vec![format!(
+        "● Git status: {} staged, {} unstaged, {} untracked",
+        staged.len(),
+        unstaged.len(),
+        untracked.len()
+    )];
+    if !staged.is_empty() {
+        lines.push("Staged:".to_string());
+        for entry in staged {
+            lines.push(format!("  {} {}", entry.staged.unwrap_or(' '), entry.path));
+        }
+    }
+    if !unstaged.is_empty() {
+        lines.push("Unstaged:".to_string());
+        for entry in unstaged {
+            lines.push(format!(
+                "  {} {}",
+                entry.unstaged.unwrap_or(' '),
+                entry.path
+            ));
+        }
+    }
+    if !untracked.is_empty() {
+        lines.push("Untracked:".to_string());
+        for entry in untracked {
+            let mark = if included.contains(&entry.path) {
+                "  [in analysis]"
+            } else {
+                ""
+            };
+            lines.push(format!("  ? {}{mark}", entry.path));
+        }
+    }
+    lines.push(
+        "Actions: /git stage <path> · /git ignore <path> · /git include|exclude <path>".to_string(),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_porcelain_entries() {
+        let out =
+            "M  src/staged.rs\0 M src/edited.rs\0MM src/both.rs\0R  new.rs\0old.rs\0?? notes.txt\0";
+        let entries = parse_status(out);
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[3].path, "new.rs");
+        assert!(entries[4].untracked);
+
+        let lines = render(&entries, &["notes.txt".to_string()]);
+        assert_eq!(
+            lines,
+            vec![
+                "● Git status: 3 staged, 2 unstaged, 1 untracked",
+                "Staged:",
+                "  M src/staged.rs",
+                "  M src/both.rs",
+                "  R new.rs",
+                "Unstaged:",
+                "  M src/edited.rs",
+                "  M src/both.rs",
+                "Untracked:",
+                "  ? notes.txt  [in analysis]",
+                "Actions: /git stage <path> · /git ignore <path> · /git include|exclude <path>",
+            ]
+        );
+    }
+}
@@ -8,13 +8,22 @@ use chrono::Utc;
 use serde_json::{json, Value};
 use uuid::Uuid;
 
-use crate::{agent::ModelConfig, state::PermissionProfile};
+use crate::{
+    agent::ModelConfig,
+    state::PermissionProfile,
+    transcript::{ToolCallRecord, Transcript},
+};
 
 const MAX_PREVIEW_CHARS: usize = 1200;
 
 pub struct RunLedger {
     run_id: String,
     path: Option<PathBuf>,
+    repo_root: PathBuf,
+    /// Written to `.context/runs/` by `finish`, or when the ledger is
+    /// dropped.
+    transcript: Transcript,
+    written: bool,
 }
 
 impl RunLedger {
@@ -27,7 +36,31 @@ impl RunLedger {
     ) -> Self {
         let run_id = Uuid::new_v4().to_string();
         let path = prepare_ledger_path(repo_root, &run_id);
-        let mut ledger = Self { run_id, path };
+        let transcript = Transcript {
+            run_id: run_id.clone(),
+            started_at: Utc::now().to_rfc3339(),
+            status: "incomplete".into(),
+            prompt: prompt.to_string(),
+            provider: model.provider.clone(),
+            model: model.model.clone(),
+            permission_profile: permission_profile.as_str().to_string(),
+            ..Transcript::default()
+        };
+        tracing::info!(
+            run_id = %run_id,
+            provider = %model.provider,
+            model = %model.model,
+            permission_profile = permission_profile.as_str(),
+            prompt_chars = prompt.chars().count(),
+            "run started"
+        );
+        let mut ledger = Self {
+            run_id,
+            path,
+            repo_root: repo_root.to_path_buf(),
+            transcript,
+            written: false,
+        };
         ledger.record(json!({
             "type": "run_started",
             "prompt_preview": clip(prompt),
@@ -47,43 +80,66 @@ impl RunLedger {
         self.path.as_deref()
     }
 
+    /// The run so far; written out when the ledger is dropped.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
     pub fn status(&mut self, phase: &str, detail: impl Into<String>, iteration: usize) {
+        let detail = clip(&detail.into());
+        tracing::debug!(run_id = %self.run_id, phase, detail = %detail, iteration, "status");
         self.record(json!({
             "type": "status",
             "phase": phase,
-            "detail": clip(&detail.into()),
+            "detail": detail,
             "iteration": iteration
         }));
     }
 
     pub fn tool_started(&mut self, tool: &str, args_summary: impl Into<String>, iteration: usize) {
+        let args_summary = clip(&args_summary.into());
+        tracing::debug!(
+            run_id = %self.run_id,
+            tool,
+            args = %args_summary,
+            iteration,
+            "tool started"
+        );
         self.record(json!({
             "type": "tool_started",
             "tool": tool,
-            "args_summary": clip(&args_summary.into()),
+            "args_summary": args_summary,
             "iteration": iteration
         }));
     }
 
-    pub fn tool_finished(
-        &mut self,
-        tool: &str,
-        status: &str,
-        summary: impl Into<String>,
-        duration_ms: u128,
-        iteration: usize,
-    ) {
+    pub fn tool_finished(&mut self, call: ToolCallRecord) {
+        tracing::info!(
+            run_id = %self.run_id,
+            tool = %call.tool,
+            status = call.status,
+            duration_ms = call.duration_ms as u64,
+            iteration = call.iteration,
+            "tool finished"
+        );
         self.record(json!({
             "type": "tool_finished",
-            "tool": tool,
-            "status": status,
-            "summary": clip(&summary.into()),
-            "duration_ms": duration_ms,
-            "iteration": iteration
+            "tool": call.tool,
+            "status": call.status,
+            "summary": clip(&call.summary),
+            "duration_ms": call.duration_ms,
+            "iteration": call.iteration
         }));
+        self.transcript.tool_calls.push(call);
+    }
+
+    /// Adds one model response's token usage.
+    pub fn usage(&mut self, usage: Option<&Value>) {
+        self.transcript.usage.add(usage);
     }
 
     pub fn permission(&mut self, tool: &str, decision: &str, iteration: usize) {
+        tracing::info!(run_id = %self.run_id, tool, decision, iteration, "permission");
         self.record(json!({
             "type": "permission",
             "tool": tool,
@@ -93,6 +149,9 @@ impl RunLedger {
     }
 
     pub fn final_text(&mut self, text: &str, iteration: usize) {
+        self.transcript.status = "completed".into();
+        self.transcript.final_answer = Some(text.to_string());
+        tracing::info!(run_id = %self.run_id, iteration, "run finished");
         self.record(json!({
             "type": "final",
             "text_preview": clip(text),
@@ -101,9 +160,13 @@ impl RunLedger {
     }
 
     pub fn error(&mut self, message: impl Into<String>, iteration: usize) {
+        let message = message.into();
+        self.transcript.status = "failed".into();
+        self.transcript.error = Some(message.clone());
+        tracing::error!(run_id = %self.run_id, error = %message, iteration, "run failed");
         self.record(json!({
             "type": "error",
-            "message": clip(&message.into()),
+            "message": clip(&message),
             "iteration": iteration
         }));
     }
@@ -124,6 +187,25 @@ impl RunLedger {
         };
         let _ = writeln!(file, "{}", value);
     }
+
+    /// Writes the transcript and returns the path of its JSON file.
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        self.write_transcript()
+    }
+
+    fn write_transcript(&mut self) -> Result<PathBuf, String> {
+        self.written = true;
+        self.transcript.finished_at = Some(Utc::now().to_rfc3339());
+        self.transcript.write(&self.repo_root)
+    }
+}
+
+impl Drop for RunLedger {
+    fn drop(&mut self) {
+        if !self.written {
+            let _ = self.write_transcript();
+        }
+    }
 }
 
 fn prepare_ledger_path(repo_root: &Path, run_id: &str) -> Option<PathBuf> {
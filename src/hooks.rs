@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 
 use serde::Deserialize;
 
@@ -48,9 +47,7 @@ pub fn run_hook(name: &str, vars: &[(&str, &str)]) -> Result<Option<String>, Str
     };
 
     let cmd = expand_template(&template, vars);
-    let out = Command::new("sh")
-        .arg("-lc")
-        .arg(&cmd)
+    let out = crate::platform::login_shell_command(&cmd)
         .output()
         .map_err(|e| e.to_string())?;
 
@@ -0,0 +1,632 @@
+//! http_policy.rs
+//!
+//! Shared policy for outbound HTTP: retries with exponential backoff and
+//! jitter, `Retry-After` support (or the `x-ratelimit-reset-*` headers on a
+//! 429 without one), and a per-host circuit breaker. A response reporting
+//! an exhausted quota (`x-ratelimit-remaining: 0`, as GitHub and GitLab
+//! send) pauses every request to that host until the quota resets, so
+//! concurrent callers back off together. Every request sent through `send`
+//! is counted; `/status system` shows the totals, and `send_observed`
+//! reports each wait so the UI can show it.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{RequestBuilder, Response};
+
+use crate::timefmt;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const MAX_ATTEMPTS_LIMIT: u32 = 10;
+const BASE_DELAY: Duration = Duration::from_millis(400);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+const BREAKER_THRESHOLD: u32 = 5;
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: BASE_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Default policy, with `OSMOGREP_HTTP_MAX_ATTEMPTS` overriding the
+    /// attempt count (1 disables retries).
+    pub fn from_env() -> Self {
+        let max_attempts = env::var("OSMOGREP_HTTP_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .map(|n| n.clamp(1, MAX_ATTEMPTS_LIMIT))
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retrying after failed attempt `attempt` (1-based). A
+    /// server-provided `Retry-After` wins over the computed backoff.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(wait) = retry_after {
+            return wait.min(MAX_RETRY_AFTER);
+        }
+
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        // Equal jitter: half fixed, half random, so concurrent clients spread
+        // out without ever retrying immediately.
+        let half = exp / 2;
+        half + half.mul_f64(jitter_fraction())
+    }
+}
+
+/// A retry about to happen, reported to `send_observed` callers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryNotice {
+    pub host: String,
+    /// Status that failed the attempt; None when no response arrived.
+    pub status: Option<u16>,
+    pub wait: Duration,
+    /// The failed attempt (1-based) out of `max_attempts`.
+    pub attempt: u32,
+    pub max_attempts: u32,
+}
+
+impl std::fmt::Display for RetryNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            Some(429) => write!(f, "rate limited by {}", self.host)?,
+            Some(status) => write!(f, "{} returned {status}", self.host)?,
+            None => write!(f, "request to {} failed", self.host)?,
+        }
+        write!(
+            f,
+            ", retrying in {} (attempt {}/{})",
+            timefmt::duration(self.wait),
+            self.attempt + 1,
+            self.max_attempts
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HostStats {
+    pub requests: u64,
+    pub retries: u64,
+    pub failures: u64,
+    pub breaker_trips: u64,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    paused_until: Option<Instant>,
+}
+
+impl HostStats {
+    pub fn breaker_open(&self) -> bool {
+        self.open_until.is_some_and(|until| until > Instant::now())
+    }
+}
+
+/// Sends `request` under the shared retry policy.
+pub fn send(request: RequestBuilder) -> Result<Response, String> {
+    send_cancellable(request, &|| false)
+}
+
+/// Like `send`, but stops waiting between attempts once `is_cancelled`
+/// returns true. Responses with a non-retryable status are returned as-is;
+/// after the last attempt a retryable status is returned as well so callers
+/// can report the server's error body.
+pub fn send_cancellable(
+    request: RequestBuilder,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Result<Response, String> {
+    send_observed(request, is_cancelled, &|_| {})
+}
+
+/// Like `send_cancellable`, calling `on_retry` before each wait.
+pub fn send_observed(
+    request: RequestBuilder,
+    is_cancelled: &dyn Fn() -> bool,
+    on_retry: &dyn Fn(&RetryNotice),
+) -> Result<Response, String> {
+    let policy = RetryPolicy::from_env();
+    let host = request_host(&request);
+    let mut attempt = 1;
+
+    loop {
+        if is_cancelled() {
+            return Err("cancelled".into());
+        }
+        wait_for_quota(&host, is_cancelled)?;
+        admit(&host)?;
+
+        // Streaming bodies cannot be replayed; those get a single attempt.
+        let Some(current) = request.try_clone() else {
+            return finish(&host, timed(&host, attempt, || request.send()));
+        };
+
+        let (status, retry_after) = match timed(&host, attempt, || current.send()) {
+            Ok(resp) if should_retry(&resp) => {
+                let status = resp.status().as_u16();
+                let header = |name: &str| resp.headers().get(name)?.to_str().ok();
+                let retry_after = server_wait(status, header);
+                if let Some(wait) = retry_after {
+                    pause(&host, wait);
+                }
+                if attempt >= policy.max_attempts {
+                    return finish(&host, Ok(resp));
+                }
+                record(&host, false);
+                (Some(status), retry_after)
+            }
+            Ok(resp) => {
+                let header = |name: &str| resp.headers().get(name)?.to_str().ok();
+                if let Some(wait) = quota_wait(header, Utc::now()) {
+                    pause(&host, wait);
+                }
+                return finish(&host, Ok(resp));
+            }
+            Err(e) if is_transient(&e) && attempt < policy.max_attempts => {
+                record(&host, false);
+                (None, None)
+            }
+            Err(e) => return finish(&host, Err(e)),
+        };
+
+        note_retry(&host);
+        let wait = policy.delay(attempt, retry_after);
+        on_retry(&RetryNotice {
+            host: host.clone(),
+            status,
+            wait,
+            attempt,
+            max_attempts: policy.max_attempts,
+        });
+        sleep_cancellable(wait, is_cancelled)?;
+        attempt += 1;
+    }
+}
+
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 425 | 429 | 500 | 502 | 503 | 504)
+}
+
+/// Whether a 403 is a rate limit rather than a permission error: GitHub
+/// answers both primary (quota used up) and secondary limits with 403.
+pub fn is_rate_limited<'a>(status: u16, header: impl Fn(&str) -> Option<&'a str>) -> bool {
+    status == 403 && (header("retry-after").is_some() || quota_wait(header, Utc::now()).is_some())
+}
+
+/// How long the server asked a failed `status` response to wait: its
+/// `Retry-After`, the reset of an exhausted quota, or for a 429 without
+/// either the later of the `x-ratelimit-reset-requests`/`-tokens` windows.
+pub fn server_wait<'a>(status: u16, header: impl Fn(&str) -> Option<&'a str>) -> Option<Duration> {
+    if let Some(wait) = header("retry-after").and_then(|v| parse_retry_after(v, Utc::now())) {
+        return Some(wait);
+    }
+    if let Some(wait) = quota_wait(&header, Utc::now()) {
+        return Some(wait);
+    }
+    if status != 429 {
+        return None;
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .into_iter()
+        .filter_map(|name| header(name).and_then(parse_reset))
+        .max()
+}
+
+/// Time until an exhausted quota resets: `x-ratelimit-remaining: 0` (or
+/// GitLab's `ratelimit-remaining`) with the reset given as a Unix time.
+/// None while requests remain.
+pub fn quota_wait<'a>(
+    header: impl Fn(&str) -> Option<&'a str>,
+    now: DateTime<Utc>,
+) -> Option<Duration> {
+    let remaining = header("x-ratelimit-remaining").or_else(|| header("ratelimit-remaining"))?;
+    if remaining.trim().parse::<u64>().ok()? > 0 {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")
+        .or_else(|| header("ratelimit-reset"))?
+        .trim()
+        .parse::<i64>()
+        .ok()?;
+    let wait = DateTime::from_timestamp(reset, 0)? - now;
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Parses a reset window like `1m30s`, `6.5s` or `250ms`.
+pub fn parse_reset(value: &str) -> Option<Duration> {
+    let mut rest = value.trim();
+    let mut total = 0.0f64;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter(|i| *i > 0)?;
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let (scale, unit_len) = if rest.starts_with("ms") {
+            (0.001, 2)
+        } else if rest.starts_with('h') {
+            (3600.0, 1)
+        } else if rest.starts_with('m') {
+            (60.0, 1)
+        } else if rest.starts_with('s') {
+            (1.0, 1)
+        } else {
+            return None;
+        };
+        total += number * scale;
+        rest = &rest[unit_len..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+/// Parses a `Retry-After` value given either as seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&Utc) - now;
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Per-host counters, sorted by host.
+pub fn host_stats() -> Vec<(String, HostStats)> {
+    let Ok(hosts) = hosts().lock() else {
+        return Vec::new();
+    };
+    let mut out = hosts
+        .iter()
+        .map(|(host, stats)| (host.clone(), stats.clone()))
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    out
+}
+
+/// Sends one attempt, logging its status and latency.
+fn timed(
+    host: &str,
+    attempt: u32,
+    send: impl FnOnce() -> reqwest::Result<Response>,
+) -> reqwest::Result<Response> {
+    let started = Instant::now();
+    let result = send();
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(resp) => tracing::debug!(
+            host,
+            attempt,
+            status = resp.status().as_u16(),
+            latency_ms,
+            "http request"
+        ),
+        Err(e) => tracing::debug!(host, attempt, latency_ms, error = %e, "http request failed"),
+    }
+    result
+}
+
+fn should_retry(resp: &Response) -> bool {
+    let status = resp.status().as_u16();
+    is_retryable_status(status)
+        || is_rate_limited(status, |name| resp.headers().get(name)?.to_str().ok())
+}
+
+fn finish(host: &str, result: reqwest::Result<Response>) -> Result<Response, String> {
+    let failed = match &result {
+        Ok(resp) => should_retry(resp),
+        Err(e) => is_transient(e),
+    };
+    record(host, !failed);
+    result.map_err(|e| e.to_string())
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_request()
+}
+
+fn request_host(request: &RequestBuilder) -> String {
+    request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .and_then(|r| {
+            let url = r.url();
+            let host = url.host_str()?.to_string();
+            Some(match url.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host,
+            })
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn hosts() -> &'static Mutex<HashMap<String, HostStats>> {
+    static HOSTS: OnceLock<Mutex<HashMap<String, HostStats>>> = OnceLock::new();
+    HOSTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn admit(host: &str) -> Result<(), String> {
+    let Ok(mut hosts) = hosts().lock() else {
+        return Ok(());
+    };
+    let stats = hosts.entry(host.to_string()).or_default();
+    if let Some(until) = stats.open_until {
+        let now = Instant::now();
+        if until > now {
+            return Err(format!(
+                "circuit open for {host} after {} consecutive failures; retry in {}s",
+                stats.consecutive_failures,
+                (until - now).as_secs().max(1)
+            ));
+        }
+        // Half-open: let this request probe the host. One more failure
+        // re-opens the breaker immediately.
+        stats.open_until = None;
+        stats.consecutive_failures = BREAKER_THRESHOLD - 1;
+    }
+    stats.requests += 1;
+    Ok(())
+}
+
+fn record(host: &str, success: bool) {
+    let Ok(mut hosts) = hosts().lock() else {
+        return;
+    };
+    let stats = hosts.entry(host.to_string()).or_default();
+    if success {
+        stats.consecutive_failures = 0;
+        return;
+    }
+
+    stats.failures += 1;
+    stats.consecutive_failures += 1;
+    if stats.consecutive_failures >= BREAKER_THRESHOLD && stats.open_until.is_none() {
+        stats.open_until = Some(Instant::now() + BREAKER_COOLDOWN);
+        stats.breaker_trips += 1;
+    }
+}
+
+/// Holds every request to `host` for `wait`, capped like `Retry-After`.
+fn pause(host: &str, wait: Duration) {
+    if wait.is_zero() {
+        return;
+    }
+    if let Ok(mut hosts) = hosts().lock() {
+        let until = Instant::now() + wait.min(MAX_RETRY_AFTER);
+        let stats = hosts.entry(host.to_string()).or_default();
+        stats.paused_until = stats.paused_until.max(Some(until));
+    }
+}
+
+/// Waits out a pause another request to `host` set.
+fn wait_for_quota(host: &str, is_cancelled: &dyn Fn() -> bool) -> Result<(), String> {
+    let until = match hosts().lock() {
+        Ok(hosts) => hosts.get(host).and_then(|stats| stats.paused_until),
+        Err(_) => None,
+    };
+    match until {
+        Some(until) if until > Instant::now() => {
+            sleep_cancellable(until - Instant::now(), is_cancelled)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn note_retry(host: &str) {
+    if let Ok(mut hosts) = hosts().lock() {
+        hosts.entry(host.to_string()).or_default().retries += 1;
+    }
+}
+
+/// Sleeps for `delay`, returning early with `cancelled` once `is_cancelled`
+/// does.
+pub fn sleep_cancellable(delay: Duration, is_cancelled: &dyn Fn() -> bool) -> Result<(), String> {
+    let deadline = Instant::now() + delay;
+    loop {
+        if is_cancelled() {
+            return Err("cancelled".into());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(());
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+}
+
+fn jitter_fraction() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() as u32) as f64 / u32::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Serves one canned response per accepted connection.
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for body in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(body.as_bytes());
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    #[test]
+    fn retries_retryable_status_honoring_retry_after() {
+        let url = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let client = reqwest::blocking::Client::new();
+
+        let notices = Mutex::new(Vec::new());
+        let resp = send_observed(client.get(&url), &|| false, &|notice| {
+            notices.lock().unwrap().push(notice.clone())
+        })
+        .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.text().unwrap(), "ok");
+        let notices = notices.into_inner().unwrap();
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].status, Some(503));
+        assert_eq!(notices[0].wait, Duration::ZERO);
+
+        let host = url.trim_start_matches("http://").trim_end_matches('/');
+        let (_, stats) = host_stats().into_iter().find(|(h, _)| h == host).unwrap();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.failures, 1);
+        assert!(!stats.breaker_open());
+    }
+
+    #[test]
+    fn exhausted_quota_is_retried_but_other_forbidden_responses_are_not() {
+        let url = serve(vec![
+            "HTTP/1.1 403 Forbidden\r\nX-RateLimit-Remaining: 0\r\nX-RateLimit-Reset: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+        let client = reqwest::blocking::Client::new();
+
+        let notices = Mutex::new(Vec::new());
+        let resp = send_observed(client.get(&url), &|| false, &|notice| {
+            notices.lock().unwrap().push(notice.clone())
+        })
+        .unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+        let notices = notices.into_inner().unwrap();
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].status, Some(403));
+        assert_eq!(notices[0].wait, Duration::ZERO);
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures_and_half_opens() {
+        let host = format!("breaker-{}", uuid::Uuid::new_v4());
+        for _ in 0..BREAKER_THRESHOLD {
+            admit(&host).unwrap();
+            record(&host, false);
+        }
+        let err = admit(&host).unwrap_err();
+        assert!(err.contains("circuit open"));
+
+        hosts().lock().unwrap().get_mut(&host).unwrap().open_until = Some(Instant::now());
+        admit(&host).unwrap();
+        record(&host, false);
+        assert!(admit(&host).is_err());
+
+        let (_, stats) = host_stats().into_iter().find(|(h, _)| *h == host).unwrap();
+        assert_eq!(stats.breaker_trips, 2);
+    }
+
+    #[test]
+    fn backoff_grows_with_jitter_and_respects_retry_after() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..=6 {
+            let exp = (BASE_DELAY * (1 << (attempt - 1))).min(MAX_DELAY);
+            let delay = policy.delay(attempt, None);
+            assert!(
+                delay >= exp / 2 && delay <= exp,
+                "attempt {attempt}: {delay:?}"
+            );
+        }
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3600))),
+            MAX_RETRY_AFTER
+        );
+
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after(" 12 ", now),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn rate_limits_wait_for_the_reset_window_without_retry_after() {
+        let headers = [
+            ("x-ratelimit-reset-requests", "1m30s"),
+            ("x-ratelimit-reset-tokens", "6.5s"),
+        ];
+        let header = |name: &str| headers.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+        assert_eq!(server_wait(429, header), Some(Duration::from_secs(90)));
+        assert_eq!(server_wait(503, header), None);
+        assert_eq!(
+            server_wait(503, |name| (name == "retry-after").then_some("4")),
+            Some(Duration::from_secs(4))
+        );
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let quota = |remaining: &'static str| {
+            move |name: &str| match name {
+                "ratelimit-remaining" => Some(remaining),
+                "ratelimit-reset" => Some("1700000030"),
+                _ => None,
+            }
+        };
+        assert_eq!(quota_wait(quota("0"), now), Some(Duration::from_secs(30)));
+        assert_eq!(quota_wait(quota("12"), now), None);
+        assert!(!is_rate_limited(403, quota("12")));
+        assert!(is_rate_limited(403, quota("0")));
+        assert!(!is_rate_limited(403, |_| None));
+        assert_eq!(parse_reset("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_reset("1h2m"), Some(Duration::from_secs(3720)));
+        assert_eq!(parse_reset("soon"), None);
+        assert_eq!(parse_reset("5"), None);
+
+        let notice = RetryNotice {
+            host: "api.openai.com".into(),
+            status: Some(429),
+            wait: Duration::from_secs(12),
+            attempt: 1,
+            max_attempts: 3,
+        };
+        assert_eq!(
+            notice.to_string(),
+            "rate limited by api.openai.com, retrying in 12.0s (attempt 2/3)"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! images.rs
+//!
+//! Image attachments for vision models. An attached screenshot rides along
+//! with the next prompt as a base64 data URL in an `input_image` part of the
+//! user message.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+/// The Responses API refuses larger images.
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageAttachment {
+    pub path: PathBuf,
+    pub mime: &'static str,
+    /// Base64 of the file contents.
+    pub data: String,
+}
+
+impl ImageAttachment {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mime = image_mime(path)
+            .ok_or_else(|| format!("{} is not a png, jpeg, gif or webp image", path.display()))?;
+        let meta = fs::metadata(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        if meta.len() > MAX_IMAGE_BYTES {
+            return Err(format!(
+                "{} is over {} MB",
+                path.display(),
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            ));
+        }
+        let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mime,
+            data: B64.encode(bytes),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime, self.data)
+    }
+}
+
+/// Formats vision models accept.
+pub fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// The image a paste refers to, when the paste is a single dropped file.
+/// Terminals paste a dropped file as its path, quoted or with escaped
+/// spaces, and some as a `file://` URL.
+pub fn dropped_image(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    if text.is_empty() || text.contains('\n') {
+        return None;
+    }
+    let unquoted = text
+        .strip_prefix('\'')
+        .and_then(|t| t.strip_suffix('\''))
+        .or_else(|| text.strip_prefix('"').and_then(|t| t.strip_suffix('"')))
+        .map(str::to_string)
+        .unwrap_or_else(|| text.replace("\\ ", " "));
+    let unquoted = unquoted.strip_prefix("file://").unwrap_or(&unquoted);
+    let path = expand_home(unquoted);
+    (path.is_absolute() && image_mime(&path).is_some() && path.is_file()).then_some(path)
+}
+
+/// `~/x` as a path under the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// User message content: plain text, or text followed by image parts.
+pub fn user_content(text: &str, images: &[ImageAttachment]) -> Value {
+    if images.is_empty() {
+        return json!(text);
+    }
+    let mut parts = vec![json!({ "type": "input_text", "text": text })];
+    parts.extend(
+        images
+            .iter()
+            .map(|image| json!({ "type": "input_image", "image_url": image.data_url() })),
+    );
+    Value::Array(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn dropped_paths_are_unquoted_and_must_be_images() {
+        let dir = std::env::temp_dir().join(format!("osmogrep-images-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let shot = dir.join("Screen Shot.png");
+        fs::write(&shot, [0x89, b'P', b'N', b'G']).unwrap();
+        fs::write(dir.join("notes.txt"), "x").unwrap();
+        let shown = shot.display().to_string();
+
+        assert_eq!(dropped_image(&format!("'{shown}' ")), Some(shot.clone()));
+        assert_eq!(
+            dropped_image(&shown.replace(' ', "\\ ")),
+            Some(shot.clone())
+        );
+        assert_eq!(
+            dropped_image(&format!("file://{shown}")),
+            Some(shot.clone())
+        );
+        assert_eq!(dropped_image(&format!("look at {shown}")), None);
+        assert_eq!(
+            dropped_image(&dir.join("notes.txt").display().to_string()),
+            None
+        );
+
+        let image = ImageAttachment::load(&shot).unwrap();
+        assert_eq!(image.name(), "Screen Shot.png");
+        assert_eq!(image.data_url(), "data:image/png;base64,iVBORw==");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn images_follow_the_prompt_text() {
+        assert_eq!(user_content("fix it", &[]), json!("fix it"));
+        let image = ImageAttachment {
+            path: PathBuf::from("ui.jpg"),
+            mime: "image/jpeg",
+            data: "AAAA".into(),
+        };
+        assert_eq!(
+            user_content("what is wrong here?", &[image]),
+            json!([
+                { "type": "input_text", "text": "what is wrong here?" },
+                { "type": "input_image", "image_url": "data:image/jpeg;base64,AAAA" }
+            ])
+        );
+    }
+}
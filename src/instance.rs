@@ -0,0 +1,315 @@
+//! instance.rs
+//!
+//! Advisory per-repo lock so concurrent osmogrep instances don't clobber
+//! each other. The first instance owns `.context/osmogrep.lock`; later ones
+//! attach read-only and write session state and artifacts under
+//! instance-suffixed names.
+//!
+//! The lock file appears complete or not at all: the owner is written to a
+//! temp file first and hard-linked into place, which fails if a lock exists.
+//! A lock is only cleared when its owner provably died on this host, or
+//! when it has been unreadable for longer than a writer could need.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::{Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use uuid::Uuid;
+
+const LOCK_FILE: &str = "osmogrep.lock";
+
+/// How long an unreadable lock is taken to be another instance's, before
+/// it counts as a crashed writer's leftover.
+const UNREADABLE_GRACE: Duration = Duration::from_secs(10);
+
+static SECONDARY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub instance: String,
+    pub pid: u32,
+    pub host: String,
+    /// Unix seconds.
+    pub started_at: i64,
+    pub version: String,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            instance: instance_id().to_string(),
+            pid: std::process::id(),
+            host: host_name(),
+            started_at: Utc::now().timestamp(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// A lock is stale when its owner ran on this host and is gone.
+    pub fn is_stale(&self) -> bool {
+        self.host == host_name() && !pid_alive(self.pid)
+    }
+
+    pub fn describe(&self) -> String {
+        let since = Local
+            .timestamp_opt(self.started_at, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        format!(
+            "pid {} on {} (instance {}, since {})",
+            self.pid, self.host, self.instance, since
+        )
+    }
+}
+
+pub enum LockAttempt {
+    Acquired(RepoLock),
+    Held(LockOwner),
+}
+
+/// Write lock on a repo. Released on drop if this instance still owns it.
+#[derive(Debug)]
+pub struct RepoLock {
+    path: PathBuf,
+    owner: LockOwner,
+}
+
+impl RepoLock {
+    pub fn owner(&self) -> &LockOwner {
+        &self.owner
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        if read_owner_at(&self.path).is_some_and(|owner| owner.instance == self.owner.instance) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Tries to take the repo's write lock, replacing it if the owner is stale.
+pub fn acquire(repo_root: &Path) -> Result<LockAttempt, String> {
+    let path = lock_path(repo_root);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let owner = LockOwner::current();
+    let staged = path.with_file_name(format!("{LOCK_FILE}.{}.tmp", owner.instance));
+    let text = serde_json::to_string_pretty(&owner).map_err(|e| e.to_string())?;
+    fs::write(&staged, text).map_err(|e| format!("lock {}: {e}", staged.display()))?;
+    let attempt = claim(&staged, &path, owner);
+    let _ = fs::remove_file(&staged);
+    attempt
+}
+
+fn claim(staged: &Path, path: &Path, owner: LockOwner) -> Result<LockAttempt, String> {
+    for _ in 0..2 {
+        match fs::hard_link(staged, path) {
+            Ok(()) => {
+                return Ok(LockAttempt::Acquired(RepoLock {
+                    path: path.to_path_buf(),
+                    owner,
+                }))
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let existing = read_owner_at(path);
+                match &existing {
+                    Some(existing) if !existing.is_stale() => {
+                        return Ok(LockAttempt::Held(existing.clone()));
+                    }
+                    None if !older_than(path, UNREADABLE_GRACE) => {
+                        return Err(format!(
+                            "{} is unreadable; another instance may be writing it",
+                            path.display()
+                        ));
+                    }
+                    // Dead owner, or unreadable for too long: clear it and
+                    // retry once.
+                    _ => clear(path, existing.as_ref(), &owner.instance),
+                }
+            }
+            Err(e) => return Err(format!("lock {}: {e}", path.display())),
+        }
+    }
+
+    Err(format!("could not acquire {}", path.display()))
+}
+
+/// Moves the lock aside and deletes it if it is still the one found stale;
+/// a lock another instance took meanwhile is put back.
+fn clear(path: &Path, stale: Option<&LockOwner>, instance: &str) {
+    let aside = path.with_file_name(format!("{LOCK_FILE}.{instance}.stale"));
+    if fs::rename(path, &aside).is_err() {
+        // Already cleared by someone else.
+        return;
+    }
+    if read_owner_at(&aside).as_ref() != stale {
+        let _ = fs::hard_link(&aside, path);
+    }
+    let _ = fs::remove_file(&aside);
+}
+
+fn older_than(path: &Path, age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|elapsed| elapsed >= age)
+}
+
+/// Takes the lock regardless of its current owner.
+pub fn take(repo_root: &Path) -> Result<RepoLock, String> {
+    let _ = fs::remove_file(lock_path(repo_root));
+    match acquire(repo_root)? {
+        LockAttempt::Acquired(lock) => Ok(lock),
+        LockAttempt::Held(owner) => Err(format!("lock re-taken by {}", owner.describe())),
+    }
+}
+
+pub fn read_owner(repo_root: &Path) -> Option<LockOwner> {
+    read_owner_at(&lock_path(repo_root))
+}
+
+/// Short random id for this process.
+pub fn instance_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| Uuid::new_v4().simple().to_string()[..8].to_string())
+}
+
+/// True when this process attached to a repo another instance has locked.
+pub fn is_secondary() -> bool {
+    SECONDARY.load(Ordering::SeqCst)
+}
+
+pub fn set_secondary(secondary: bool) {
+    SECONDARY.store(secondary, Ordering::SeqCst);
+}
+
+/// `stem.ext` for the lock owner, `stem-<instance>.ext` for secondaries.
+pub fn artifact_name(stem: &str, ext: &str) -> String {
+    if is_secondary() {
+        format!("{stem}-{}.{ext}", instance_id())
+    } else {
+        format!("{stem}.{ext}")
+    }
+}
+
+fn lock_path(repo_root: &Path) -> PathBuf {
+    repo_root.join(".context").join(LOCK_FILE)
+}
+
+fn read_owner_at(path: &Path) -> Option<LockOwner> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+}
+
+fn host_name() -> String {
+    System::host_name().unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn pid_alive(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut sys = System::new();
+    sys.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    sys.process(pid).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_repo() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("osmogrep-lock-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn second_acquire_sees_owner_and_drop_releases() {
+        let root = temp_repo();
+
+        let LockAttempt::Acquired(lock) = acquire(&root).unwrap() else {
+            panic!("first acquire should own the lock");
+        };
+        let owner = lock.owner().clone();
+        assert_eq!(owner.pid, std::process::id());
+
+        match acquire(&root).unwrap() {
+            LockAttempt::Held(held) => assert_eq!(held, owner),
+            LockAttempt::Acquired(_) => panic!("lock should be held"),
+        }
+
+        drop(lock);
+        assert!(read_owner(&root).is_none());
+        let LockAttempt::Acquired(_lock) = acquire(&root).unwrap() else {
+            panic!("lock should be free after drop");
+        };
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn stale_lock_is_replaced() {
+        let root = temp_repo();
+        let stale = LockOwner {
+            instance: "deadbeef".to_string(),
+            pid: u32::MAX - 1,
+            host: host_name(),
+            started_at: 0,
+            version: "0.0.0".to_string(),
+        };
+        fs::create_dir_all(root.join(".context")).unwrap();
+        fs::write(lock_path(&root), serde_json::to_string(&stale).unwrap()).unwrap();
+        assert!(stale.is_stale());
+
+        let LockAttempt::Acquired(lock) = acquire(&root).unwrap() else {
+            panic!("stale lock should be replaced");
+        };
+        assert_eq!(lock.owner().instance, instance_id());
+
+        drop(lock);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn unreadable_lock_is_only_cleared_after_the_grace_period() {
+        let root = temp_repo();
+        fs::create_dir_all(root.join(".context")).unwrap();
+        let path = lock_path(&root);
+        fs::write(&path, "").unwrap();
+
+        let err = acquire(&root).err().expect("fresh unreadable lock is kept");
+        assert!(err.contains("unreadable"), "{err}");
+        assert!(path.exists());
+
+        let old = std::time::SystemTime::now() - UNREADABLE_GRACE * 2;
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let LockAttempt::Acquired(lock) = acquire(&root).unwrap() else {
+            panic!("old unreadable lock should be replaced");
+        };
+        assert_eq!(read_owner(&root).as_ref(), Some(lock.owner()));
+        let leftovers: Vec<_> = fs::read_dir(root.join(".context"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, [LOCK_FILE]);
+
+        drop(lock);
+        let _ = fs::remove_dir_all(root);
+    }
+}
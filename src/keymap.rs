@@ -0,0 +1,399 @@
+//! keymap.rs
+//!
+//! Key bindings for TUI actions, from the `[keys]` section of the config
+//! file. Each entry maps an action to one key or a list of keys and
+//! replaces that action's defaults:
+//!
+//! ```toml
+//! [keys]
+//! page_up = ["pageup", "ctrl+b"]
+//! page_down = ["pagedown", "ctrl+f"]
+//! toggle_diff = "alt+d"
+//! ```
+//!
+//! Text editing keys stay with the input box; plain printable keys cannot
+//! be bound, since they would stop typing.
+
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyAction {
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollBottom,
+    Cancel,
+    CopyOutput,
+    ExportPanel,
+    OpenFileRef,
+    EditFile,
+    PrevTool,
+    NextTool,
+    ToggleTool,
+    ToggleDiff,
+    ToggleSplit,
+    ToggleMinimap,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 16] = [
+        KeyAction::ScrollUp,
+        KeyAction::ScrollDown,
+        KeyAction::PageUp,
+        KeyAction::PageDown,
+        KeyAction::ScrollBottom,
+        KeyAction::Cancel,
+        KeyAction::CopyOutput,
+        KeyAction::ExportPanel,
+        KeyAction::OpenFileRef,
+        KeyAction::EditFile,
+        KeyAction::PrevTool,
+        KeyAction::NextTool,
+        KeyAction::ToggleTool,
+        KeyAction::ToggleDiff,
+        KeyAction::ToggleSplit,
+        KeyAction::ToggleMinimap,
+    ];
+
+    /// The name used in `[keys]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::ScrollUp => "scroll_up",
+            KeyAction::ScrollDown => "scroll_down",
+            KeyAction::PageUp => "page_up",
+            KeyAction::PageDown => "page_down",
+            KeyAction::ScrollBottom => "scroll_bottom",
+            KeyAction::Cancel => "cancel",
+            KeyAction::CopyOutput => "copy_output",
+            KeyAction::ExportPanel => "export_panel",
+            KeyAction::OpenFileRef => "open_file_ref",
+            KeyAction::EditFile => "edit_file",
+            KeyAction::PrevTool => "prev_tool",
+            KeyAction::NextTool => "next_tool",
+            KeyAction::ToggleTool => "toggle_tool",
+            KeyAction::ToggleDiff => "toggle_diff",
+            KeyAction::ToggleSplit => "toggle_split",
+            KeyAction::ToggleMinimap => "toggle_minimap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::ScrollUp => "Scroll the execution log up a line",
+            KeyAction::ScrollDown => "Scroll the execution log down a line",
+            KeyAction::PageUp => "Scroll the execution log up a page",
+            KeyAction::PageDown => "Scroll the execution log down a page",
+            KeyAction::ScrollBottom => "Follow the latest output (empty prompt)",
+            KeyAction::Cancel => "Cancel the running agent",
+            KeyAction::CopyOutput => "Copy the latest agent output",
+            KeyAction::ExportPanel => "Save the focused panel to a file",
+            KeyAction::OpenFileRef => "Open the latest path:line reference",
+            KeyAction::EditFile => "Open the file in view in $EDITOR (e when scrolled back)",
+            KeyAction::PrevTool => "Select the previous tool-call block",
+            KeyAction::NextTool => "Select the next tool-call block",
+            KeyAction::ToggleTool => "Expand or collapse a tool-call block (empty prompt)",
+            KeyAction::ToggleDiff => "Open or close the session diff view",
+            KeyAction::ToggleSplit => "Show the session diff beside the execution log",
+            KeyAction::ToggleMinimap => "Toggle the diff minimap",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            KeyAction::ScrollUp => &["ctrl+up"],
+            KeyAction::ScrollDown => &["ctrl+down"],
+            KeyAction::PageUp => &["pageup"],
+            KeyAction::PageDown => &["pagedown"],
+            KeyAction::ScrollBottom => &["end"],
+            KeyAction::Cancel => &["esc"],
+            KeyAction::CopyOutput => &["ctrl+o"],
+            KeyAction::ExportPanel => &["ctrl+s"],
+            KeyAction::OpenFileRef => &["ctrl+g"],
+            KeyAction::EditFile => &["alt+e"],
+            KeyAction::PrevTool => &["alt+up"],
+            KeyAction::NextTool => &["alt+down"],
+            KeyAction::ToggleTool => &["enter"],
+            KeyAction::ToggleSplit => &["alt+s"],
+            KeyAction::ToggleDiff | KeyAction::ToggleMinimap => &[],
+        }
+    }
+}
+
+/// A key with modifiers, parsed from `ctrl+o`, `alt+up`, `pagedown`, `f2`
+/// and the like. Letters keep their case, so `alt+G` is Alt+Shift+g.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let (mods, key) = match raw.rsplit_once('+') {
+            // `ctrl++` binds the plus key.
+            Some((mods, "")) => (mods.strip_suffix('+')?, "+"),
+            Some(split) => split,
+            None => ("", raw),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for part in mods.split('+').filter(|p| !p.is_empty()) {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "option" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next()?),
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            lower if lower.len() > 1 && lower.starts_with('f') => {
+                KeyCode::F(lower[1..].parse().ok()?)
+            }
+            _ => return None,
+        };
+        Some(Self { code, modifiers })
+    }
+
+    /// Shift is ignored, since terminals disagree on reporting it; the case
+    /// of a letter already says whether it was held, except with Ctrl,
+    /// where terminals cannot tell.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        if key.modifiers & relevant != self.modifiers & relevant {
+            return false;
+        }
+        match (key.code, self.code) {
+            (KeyCode::Char(a), KeyCode::Char(b))
+                if self.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                a.eq_ignore_ascii_case(&b)
+            }
+            (code, own) => code == own,
+        }
+    }
+
+    /// Whether the chord would swallow typed text.
+    fn is_typing(&self) -> bool {
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        matches!(self.code, KeyCode::Char(_)) && (self.modifiers & relevant).is_empty()
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("Shift+");
+        }
+        match self.code {
+            KeyCode::F(n) => label.push_str(&format!("F{n}")),
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                label.push(c.to_ascii_uppercase())
+            }
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::Tab => label.push_str("Tab"),
+            KeyCode::Enter => label.push_str("Enter"),
+            KeyCode::Esc => label.push_str("Esc"),
+            KeyCode::Backspace => label.push_str("Backspace"),
+            KeyCode::Delete => label.push_str("Delete"),
+            KeyCode::Insert => label.push_str("Insert"),
+            KeyCode::Up => label.push('↑'),
+            KeyCode::Down => label.push('↓'),
+            KeyCode::Left => label.push('←'),
+            KeyCode::Right => label.push('→'),
+            KeyCode::PageUp => label.push_str("PageUp"),
+            KeyCode::PageDown => label.push_str("PageDown"),
+            KeyCode::Home => label.push_str("Home"),
+            KeyCode::End => label.push_str("End"),
+            _ => label.push('?'),
+        }
+        label
+    }
+}
+
+/// One `[keys]` entry: a key or a list of keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    fn keys(&self) -> &[String] {
+        match self {
+            KeySpec::One(key) => std::slice::from_ref(key),
+            KeySpec::Many(keys) => keys,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<KeyAction, Vec<KeyChord>>,
+    /// Entries that were skipped, as `keys.<action>: why`.
+    pub problems: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = KeyAction::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .filter_map(|key| KeyChord::parse(key))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self {
+            bindings,
+            problems: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The defaults with `keys` applied. Unknown actions and keys that do
+    /// not parse are reported in `problems` and leave the default in place.
+    pub fn from_config(keys: &BTreeMap<String, KeySpec>) -> Self {
+        let mut keymap = Self::default();
+        for (name, spec) in keys {
+            let Some(action) = KeyAction::from_name(name) else {
+                keymap.problems.push(format!("keys.{name}: unknown action"));
+                continue;
+            };
+            let mut chords = Vec::new();
+            for raw in spec.keys() {
+                match KeyChord::parse(raw) {
+                    Some(chord) if chord.is_typing() => keymap.problems.push(format!(
+                        "keys.{name}: {raw:?} needs Ctrl or Alt, or it would stop typing"
+                    )),
+                    Some(chord) => chords.push(chord),
+                    None => keymap
+                        .problems
+                        .push(format!("keys.{name}: unknown key {raw:?}")),
+                }
+            }
+            if chords.len() == spec.keys().len() {
+                keymap.bindings.insert(action, chords);
+            }
+        }
+        keymap
+    }
+
+    /// The first action, in `KeyAction::ALL` order, bound to `key`.
+    pub fn action(&self, key: &KeyEvent) -> Option<KeyAction> {
+        KeyAction::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).iter().any(|chord| chord.matches(key)))
+    }
+
+    pub fn keys(&self, action: KeyAction) -> &[KeyChord] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// `Ctrl+o, Alt+o`, or `unbound`.
+    pub fn label(&self, action: KeyAction) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return "unbound".to_string();
+        }
+        keys.iter()
+            .map(KeyChord::label)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn chords_parse_and_match() {
+        let chord = KeyChord::parse("Ctrl+PageDown").unwrap();
+        assert_eq!(chord.label(), "Ctrl+PageDown");
+        assert!(chord.matches(&press(KeyCode::PageDown, KeyModifiers::CONTROL)));
+        assert!(!chord.matches(&press(KeyCode::PageDown, KeyModifiers::NONE)));
+
+        let g = KeyChord::parse("alt+G").unwrap();
+        assert!(g.matches(&press(
+            KeyCode::Char('G'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT
+        )));
+        assert!(!g.matches(&press(KeyCode::Char('g'), KeyModifiers::ALT)));
+
+        assert_eq!(KeyChord::parse("ctrl++").unwrap().code, KeyCode::Char('+'));
+        assert_eq!(KeyChord::parse("f12").unwrap().code, KeyCode::F(12));
+        assert!(KeyChord::parse("hyper+x").is_none());
+        assert!(KeyChord::parse("pagesideways").is_none());
+    }
+
+    #[test]
+    fn config_replaces_defaults_per_action() {
+        let keys: BTreeMap<String, KeySpec> = toml::from_str(
+            r#"
+            page_up = ["pageup", "ctrl+b"]
+            toggle_diff = "alt+d"
+            scroll_down = "j"
+            warp = "ctrl+w"
+            "#,
+        )
+        .unwrap();
+        let keymap = Keymap::from_config(&keys);
+
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('b'), KeyModifiers::CONTROL)),
+            Some(KeyAction::PageUp)
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('d'), KeyModifiers::ALT)),
+            Some(KeyAction::ToggleDiff)
+        );
+        assert_eq!(keymap.label(KeyAction::ScrollDown), "Ctrl+↓");
+        assert_eq!(keymap.label(KeyAction::ToggleMinimap), "unbound");
+        assert_eq!(
+            keymap.problems,
+            [
+                "keys.scroll_down: \"j\" needs Ctrl or Alt, or it would stop typing",
+                "keys.warp: unknown action",
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! osmogrep as a library.
+//!
+//! The binary is a thin shell over these modules. The tool registration API
+//! is the supported surface for embedding and follows semver:
+//!
+//! - [`tools::Tool`], [`tools::ToolSafety`], [`tools::ToolResult`]
+//! - [`tools::ToolRegistry`] and [`tools::ToolRegistryBuilder`]
+//! - [`tools::OutputBudget`]
+//! - [`Agent::with_tools`]
+//!
+//! New `Tool` methods only ship with default implementations. Everything
+//! else is shared with the binary and may change in any release.
+
+#[doc(hidden)]
+pub mod agent;
+#[doc(hidden)]
+pub mod approvals;
+#[doc(hidden)]
+pub mod binary_assets;
+#[doc(hidden)]
+pub mod candidate_queue;
+#[doc(hidden)]
+pub mod change_budget;
+#[doc(hidden)]
+pub mod clipboard;
+#[doc(hidden)]
+pub mod codeowners;
+#[doc(hidden)]
+pub mod command_docs;
+#[doc(hidden)]
+pub mod commands;
+#[doc(hidden)]
+pub mod config_watch;
+#[doc(hidden)]
+pub mod context;
+#[doc(hidden)]
+pub mod coverage;
+#[doc(hidden)]
+pub mod custom_commands;
+#[doc(hidden)]
+pub mod detach;
+#[doc(hidden)]
+pub mod file_log;
+#[doc(hidden)]
+pub mod file_refs;
+#[doc(hidden)]
+pub mod fix_tests;
+#[doc(hidden)]
+pub mod git;
+#[doc(hidden)]
+pub mod git_status;
+#[doc(hidden)]
+pub mod harness;
+#[doc(hidden)]
+pub mod hooks;
+#[doc(hidden)]
+pub mod http_policy;
+#[doc(hidden)]
+pub mod images;
+#[doc(hidden)]
+pub mod instance;
+#[doc(hidden)]
+pub mod keymap;
+#[doc(hidden)]
+pub mod lifecycle_hooks;
+#[doc(hidden)]
+pub mod lint_hooks;
+#[doc(hidden)]
+pub mod logger;
+#[doc(hidden)]
+pub mod mcp;
+#[doc(hidden)]
+pub mod mutation;
+#[doc(hidden)]
+pub mod net;
+#[doc(hidden)]
+pub mod notify;
+#[doc(hidden)]
+pub mod onboard;
+#[doc(hidden)]
+pub mod permissions;
+#[doc(hidden)]
+pub mod persistence;
+#[doc(hidden)]
+pub mod piped_input;
+#[doc(hidden)]
+pub mod platform;
+#[doc(hidden)]
+pub mod pricing;
+#[doc(hidden)]
+pub mod process_runner;
+#[doc(hidden)]
+pub mod protected_paths;
+#[doc(hidden)]
+pub mod pull_request;
+#[doc(hidden)]
+pub mod rebase;
+#[doc(hidden)]
+pub mod response_cache;
+#[doc(hidden)]
+pub mod review_queue;
+#[doc(hidden)]
+pub mod run_budget;
+#[doc(hidden)]
+pub mod sandbox;
+#[doc(hidden)]
+pub mod shell_guard;
+#[doc(hidden)]
+pub mod speech;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod test_harness;
+#[doc(hidden)]
+pub mod test_provider;
+#[doc(hidden)]
+pub mod testgen;
+#[doc(hidden)]
+pub mod text_tools;
+#[doc(hidden)]
+pub mod timefmt;
+#[doc(hidden)]
+pub mod tool_budget;
+#[doc(hidden)]
+pub mod tool_guard;
+pub mod tools;
+#[doc(hidden)]
+pub mod transcript;
+#[doc(hidden)]
+pub mod triage;
+#[doc(hidden)]
+pub mod ui;
+#[doc(hidden)]
+pub mod updater;
+#[doc(hidden)]
+pub mod verification;
+#[doc(hidden)]
+pub mod verify_stop;
+#[doc(hidden)]
+pub mod voice;
+#[doc(hidden)]
+pub mod whisper_local;
+#[doc(hidden)]
+pub mod workspace;
+#[doc(hidden)]
+pub mod worktree;
+
+pub use agent::{Agent, ToolSetup};
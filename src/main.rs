@@ -1,27 +1,3 @@
-mod agent;
-mod clipboard;
-mod commands;
-mod context;
-mod harness;
-mod hooks;
-mod logger;
-mod mcp;
-mod persistence;
-mod process_runner;
-mod shell_guard;
-mod state;
-mod test_harness;
-mod tool_budget;
-mod tool_guard;
-mod tools;
-mod triage;
-mod ui;
-mod updater;
-mod verification;
-mod verify_stop;
-mod voice;
-mod worktree;
-
 use std::{
     error::Error,
     fs, io,
@@ -37,27 +13,52 @@ use crossterm::event;
 use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal};
 
 use clap::{Args, Parser, Subcommand};
+use url::Url;
 use uuid::Uuid;
 
-use crate::{
-    agent::{Agent, AgentEvent, CancelToken, RunControl},
-    context::ContextEvent,
+use osmogrep::{
+    agent,
+    agent::{Agent, AgentEvent, CancelToken, RunControl, SubagentState, UserPrompt},
+    change_budget::BudgetDecision,
+    commands,
+    config_watch::ConfigWatcher,
+    context,
+    context::{working_diff, ContextEvent},
+    detach, file_log, fix_tests,
+    instance::{self, LockAttempt},
+    keymap::Keymap,
+    lifecycle_hooks,
     logger::{
         flush_streaming_log, log, log_agent_output, log_status, log_tool_call, log_tool_result,
         log_user_input, update_streaming_log,
     },
+    net, notify, onboard, persistence,
+    piped_input::PipedInput,
+    platform,
+    pricing::ModelPrice,
+    response_cache::{self, Recorder, Replay},
+    speech,
     state::{
-        AgentState, DiffSnapshot, InputMode, JobKind, JobStatus, LogLevel, PermissionProfile,
-        MAX_CONVERSATION_TOKENS,
+        AgentState, DiffSnapshot, InputMode, InterruptedRun, JobKind, JobStatus, LogLevel,
+        PermissionProfile, PlanItem, TabLabel, TabRequest, MAX_CONVERSATION_TOKENS,
     },
+    test_harness, testgen, timefmt, triage,
     ui::{
         main_ui::handle_event,
         runtime::TuiRuntime,
         terminal::{setup_terminal, teardown_terminal},
     },
+    updater, voice,
+    worktree::{self, Sandbox},
 };
 
 enum JobEvent {
+    /// An agent task's detached run started.
+    Started {
+        id: u64,
+        run_id: String,
+        sandbox: Option<Sandbox>,
+    },
     Finished {
         id: u64,
         ok: bool,
@@ -98,7 +99,102 @@ enum CliCommand {
     /// Run the coding agent headlessly and print events to stdout
     Run(RunArgs),
     /// Analyze GitHub PRs/issues for duplicates, ranking, and scope drift
-    Triage(triage::TriageArgs),
+    Triage(Box<triage::TriageArgs>),
+    /// Inspect the ledger of agent-written tests
+    Testgen(TestgenArgs),
+    /// Show the repo's lifecycle hooks and whether they are trusted
+    Hooks {
+        /// Repository whose .osmogrep/hooks.toml to read
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+
+        /// Trust the hooks file as it is now, so agent runs may use it
+        #[arg(long, default_value_t = false)]
+        trust: bool,
+    },
+    /// Analyze the diff against a base revision and print it as JSON
+    Analyze {
+        /// Repository to analyze
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+
+        /// Revision to diff the working tree against
+        #[arg(long, default_value = "HEAD")]
+        base: String,
+
+        /// Exit with status 1 when a touched symbol has no test calling it
+        #[arg(long, default_value_t = false)]
+        fail_on_untested: bool,
+    },
+    /// Print a getting-started report for the repository
+    Onboard {
+        /// Repository to describe
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+
+        /// Also write the report as markdown to this file
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Print the report as JSON instead of markdown
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List detached runs, or replay and follow one by id
+    Attach {
+        /// Run id (a unique prefix is enough)
+        id: Option<String>,
+    },
+    /// Run the tests, hand each failing file to the agent and rerun it until green
+    FixTests {
+        /// Repository root for tool execution
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+
+        /// Repair rounds before giving up
+        #[arg(long, default_value_t = 3)]
+        max_rounds: usize,
+
+        /// Approve dangerous workspace actions without an interactive prompt
+        #[arg(long, default_value_t = false)]
+        auto_approve: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+struct TestgenArgs {
+    #[command(subcommand)]
+    command: TestgenCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum TestgenCommand {
+    /// Summarize acceptance and gate pass rates per week
+    Stats {
+        /// Repository whose .osmogrep/testgen-ledger.json to read
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+    },
+    /// Update failing generated tests whose target code changed since
+    Maintain {
+        /// Repository root for tool execution
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+
+        /// List the stale tests without starting an agent run
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Approve dangerous workspace actions without an interactive prompt
+        #[arg(long, default_value_t = false)]
+        auto_approve: bool,
+    },
+    /// Mutation-test the code the latest generated tests call
+    Strength {
+        /// Repository whose latest generated tests to score
+        #[arg(long, default_value = ".")]
+        repo_root: PathBuf,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -118,6 +214,10 @@ struct RunArgs {
     #[arg(long, default_value = ".")]
     repo_root: PathBuf,
 
+    /// Task to send to the agent, e.g. `osmogrep run "fix the failing test"`
+    #[arg(value_name = "TASK", conflicts_with_all = ["prompt", "prompt_file"])]
+    task: Option<String>,
+
     /// Prompt text to send to the agent
     #[arg(long, conflicts_with = "prompt_file")]
     prompt: Option<String>,
@@ -127,7 +227,7 @@ struct RunArgs {
     prompt_file: Option<PathBuf>,
 
     /// Emit newline-delimited JSON events for non-TUI callers
-    #[arg(long, default_value_t = false)]
+    #[arg(long, visible_alias = "json", default_value_t = false)]
     json_events: bool,
 
     /// Permission profile: read-only, workspace-auto, or full-access
@@ -137,6 +237,15 @@ struct RunArgs {
     /// Approve dangerous workspace actions without an interactive prompt
     #[arg(long, default_value_t = false)]
     auto_approve: bool,
+
+    /// Write every model response of the run to this file
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Run against the model responses recorded in this file instead of the
+    /// API; the recorded prompt is used unless one is given
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
 }
 
 fn env_truthy(key: &str, default: bool) -> bool {
@@ -160,13 +269,13 @@ fn agent_iteration_limit() -> usize {
 fn run_shell(state: &mut AgentState, cmd: &str) {
     log(state, LogLevel::Info, &format!("SHELL : $ {}", cmd));
 
-    if let Err(e) = crate::shell_guard::check_shell_command(cmd) {
+    if let Err(e) = osmogrep::shell_guard::check_shell_command(cmd) {
         log(state, LogLevel::Error, e);
         return;
     }
 
-    let timeout = crate::process_runner::timeout_from_env("OSMOGREP_SHELL_TIMEOUT_SECS", 120);
-    match crate::process_runner::run_shell_command(cmd, Some(&state.repo_root), timeout) {
+    let timeout = osmogrep::process_runner::timeout_from_env("OSMOGREP_SHELL_TIMEOUT_SECS", 120);
+    match osmogrep::process_runner::run_shell_command(cmd, Some(&state.repo_root), timeout) {
         Ok(out) => {
             let mut combined = String::new();
             for line in String::from_utf8_lossy(&out.stdout).lines() {
@@ -179,9 +288,12 @@ fn run_shell(state: &mut AgentState, cmd: &str) {
                 combined.push('\n');
                 log(state, LogLevel::Error, line);
             }
-            if let Some(ev) =
-                crate::verification::record_command(&state.repo_root, cmd, out.exit_code, &combined)
-            {
+            if let Some(ev) = osmogrep::verification::record_command(
+                &state.repo_root,
+                cmd,
+                out.exit_code,
+                &combined,
+            ) {
                 log(
                     state,
                     if ev.status == "passed" {
@@ -199,7 +311,10 @@ fn run_shell(state: &mut AgentState, cmd: &str) {
                 log(
                     state,
                     LogLevel::Error,
-                    format!("Shell command timed out after {}ms", out.duration_ms),
+                    format!(
+                        "Shell command timed out after {}",
+                        timefmt::duration_ms(out.duration_ms)
+                    ),
                 );
             }
         }
@@ -209,12 +324,19 @@ fn run_shell(state: &mut AgentState, cmd: &str) {
     }
 }
 
+/// Notifies when the terminal is in the background.
+fn notify_if_away(state: &AgentState, message: &str) {
+    if state.ui.focus_lost {
+        notify::send(&agent::notifications_config(), message);
+    }
+}
+
 fn warn_if_verification_needed(state: &mut AgentState) {
     if state.session_changes.is_empty() {
         return;
     }
 
-    let status = crate::verification::latest_status(&state.repo_root);
+    let status = osmogrep::verification::latest_status(&state.repo_root);
     if !status.needs_verification {
         return;
     }
@@ -271,14 +393,15 @@ fn queue_auto_review_if_needed(state: &mut AgentState) {
 
     let id = state.next_job_id;
     state.next_job_id += 1;
-    state.jobs.push(crate::state::JobRecord {
+    state.jobs.push(osmogrep::state::JobRecord {
         id,
         kind: JobKind::Review,
         input: format!("{} change(s)", changes.len()),
         status: JobStatus::Queued,
         output: None,
+        run_id: None,
     });
-    state.job_queue.push(crate::state::JobRequest {
+    state.job_queue.push(osmogrep::state::JobRequest {
         id,
         kind: JobKind::Review,
         input,
@@ -312,11 +435,26 @@ fn start_agent_run(
     if text.trim().is_empty() {
         return;
     }
+    let text = &state.expand_pastes(text);
+    // The steering panel covers one run; a queued steer-now launching this
+    // run stays listed.
+    let launched_by_steer = state
+        .steer_history
+        .last()
+        .is_some_and(|record| !record.live && record.text == text.trim());
+    if !launched_by_steer {
+        state.steer_history.clear();
+    }
+    state.ui.subagents.clear();
+    state.interrupted = None;
+    state.ui.review = None;
+    state.ui.run_changes_from = state.session_changes.len();
 
     let (tx, rx) = mpsc::channel();
     let repo_root = state.repo_root.clone();
     let prior_messages = state.conversation.messages.clone();
-    let auto_approve = if state.plan_mode {
+    // Conflict resolutions during /rebase are always previewed and approved.
+    let auto_approve = if state.plan_mode || state.rebase.is_some() {
         false
     } else {
         state.ui.auto_approve
@@ -324,18 +462,61 @@ fn start_agent_run(
     let steer = state.steer.clone();
     let permission_profile = if state.plan_mode {
         PermissionProfile::ReadOnly
+    } else if state.rebase.is_some() && state.permission_profile == PermissionProfile::FullAccess {
+        PermissionProfile::WorkspaceAuto
     } else {
         state.permission_profile
     };
-    let user_text = if state.plan_mode {
+    let executing_plan = state.plan_approved && !state.plan_mode && !state.plan_items.is_empty();
+    let plan_scope = if executing_plan {
+        state
+            .plan_items
+            .iter()
+            .filter(|item| !item.done)
+            .flat_map(|item| item.files.iter().cloned())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut user_text = if state.plan_mode {
         plan_mode_prompt(text)
+    } else if executing_plan {
+        approved_plan_prompt(text, &state.plan_items)
     } else {
         text.to_string()
     };
+    if prior_messages.is_empty() {
+        if let Some(diff) = working_diff::cached(&repo_root) {
+            user_text = format!("{user_text}\n\n{}", working_diff::prompt_context(&diff));
+        }
+    }
+
+    if let Some(piped) = state.ui.piped_input.take() {
+        log(state, LogLevel::Info, format!("Sending {}.", piped.label()));
+        user_text = piped.attach_to(&user_text);
+    }
+
+    let images = std::mem::take(&mut state.ui.images);
+    if !images.is_empty() {
+        log(
+            state,
+            LogLevel::Info,
+            format!(
+                "Sending {} image{}.",
+                images.len(),
+                if images.len() == 1 { "" } else { "s" }
+            ),
+        );
+    }
 
     let RunControl { cancel, steer_tx } = agent.spawn(
-        repo_root,
-        user_text,
+        state.edit_root().to_path_buf(),
+        UserPrompt {
+            text: user_text,
+            images,
+            plan_scope,
+            budget: state.run_budget,
+        },
         prior_messages,
         steer,
         permission_profile,
@@ -366,6 +547,8 @@ fn start_agent_run(
     };
     state.ui.run_detail = Some(if state.plan_mode {
         "plan mode read-only".to_string()
+    } else if executing_plan {
+        "executing approved plan".to_string()
     } else {
         "building request".to_string()
     });
@@ -376,11 +559,11 @@ fn start_agent_run(
     state.ui.last_tool_status = None;
     reset_streaming_output(&mut state.ui);
     state.ui.active_edit_target = None;
-    state.usage.prompt_tokens += (text.len() / 4).max(1);
+    state.usage.run = Default::default();
     let _ = persistence::save(state);
 }
 
-fn reset_streaming_output(ui: &mut crate::state::UiState) {
+fn reset_streaming_output(ui: &mut osmogrep::state::UiState) {
     ui.streaming_active = false;
     ui.streaming_buffer.clear();
     ui.streaming_transcript.clear();
@@ -422,17 +605,45 @@ fn plan_mode_prompt(text: &str) -> String {
         "PLAN MODE ACTIVE.\n\
          Do not edit files, write files, apply patches, commit, install dependencies, run mutating shell commands, or take destructive actions.\n\
          You may inspect the repository with safe read/search tools and ask concise clarifying questions if needed.\n\
-         Use `update_plan` to set the concrete checklist so it appears in the UI.\n\
+         Use `update_plan` with action=set to record the steps, each with the files it will change, so the checklist appears in the UI.\n\
          Produce a concrete implementation plan with risks, verification commands to run later, and the exact files likely to change.\n\
-         Stop after the plan unless the user exits plan mode.\n\n\
+         Stop after the plan: the user reviews it and runs /plan approve to execute it.\n\n\
          User task:\n{text}"
     )
 }
 
+fn approved_plan_prompt(text: &str, items: &[PlanItem]) -> String {
+    let steps: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let mark = if item.done { "x" } else { " " };
+            if item.files.is_empty() {
+                format!("{}. [{mark}] {}", idx + 1, item.text)
+            } else {
+                format!(
+                    "{}. [{mark}] {} (files: {})",
+                    idx + 1,
+                    item.text,
+                    item.files.join(", ")
+                )
+            }
+        })
+        .collect();
+    format!(
+        "APPROVED PLAN.\n\
+         The user approved this plan. Work through the open steps in order and call `update_plan` with action=done and the step number as each one is finished.\n\
+         Only edit the files the open steps list; edits elsewhere are refused. If another file has to change, stop and explain why.\n\n\
+         {}\n\n\
+         User task:\n{text}",
+        steps.join("\n")
+    )
+}
+
 fn handle_update_event(state: &mut AgentState, evt: updater::UpdateEvent) {
     match evt {
         updater::UpdateEvent::Available(info) => {
-            state.ui.pending_update = Some(crate::state::PendingUpdate {
+            state.ui.pending_update = Some(osmogrep::state::PendingUpdate {
                 current_version: info.current_version.clone(),
                 latest_version: info.latest_version.clone(),
                 asset_url: info.asset_url,
@@ -520,9 +731,148 @@ fn deny_update(state: &mut AgentState) {
     }
 }
 
+/// Runs a `/queue` task as a detached run, in a fresh sandbox worktree when
+/// `sandboxed`, and waits for it to end.
+fn run_agent_task(
+    repo_root: &Path,
+    req: &osmogrep::state::JobRequest,
+    sandboxed: bool,
+    profile: PermissionProfile,
+    auto_approve: bool,
+    tx: &mpsc::Sender<JobEvent>,
+) -> (bool, String) {
+    let sandbox = if sandboxed {
+        match worktree::create_sandbox(repo_root) {
+            Ok(sandbox) => Some(sandbox),
+            Err(e) => return (false, format!("Sandbox failed: {e}")),
+        }
+    } else {
+        None
+    };
+    let root = sandbox.as_ref().map_or(repo_root, |s| s.path.as_path());
+    let run = match detach::spawn(root, &req.input, profile, auto_approve) {
+        Ok(run) => run,
+        Err(e) => {
+            if let Some(sandbox) = &sandbox {
+                let _ = sandbox.remove(repo_root);
+            }
+            return (false, e);
+        }
+    };
+    let _ = tx.send(JobEvent::Started {
+        id: req.id,
+        run_id: run.id.clone(),
+        sandbox,
+    });
+    loop {
+        match run.outcome() {
+            Some(Ok(answer)) => return (true, answer),
+            Some(Err(e)) => return (false, e),
+            None => std::thread::sleep(Duration::from_millis(500)),
+        }
+    }
+}
+
+/// Log events the followed detached run wrote since the last check.
+/// Returns whether anything was logged.
+fn follow_attached_run(state: &mut AgentState) -> bool {
+    let Some((id, tail)) = state.ui.attached_run.as_mut() else {
+        return false;
+    };
+    let id = id.clone();
+    let mut lines = Vec::new();
+    let mut ended = false;
+    for event in tail.poll() {
+        lines.extend(tail.render(&event));
+        ended |= detach::is_terminal(&event);
+    }
+    if ended {
+        state.ui.attached_run = None;
+    }
+    let logged = !lines.is_empty();
+    for (level, line) in lines {
+        log(state, level, line);
+    }
+    if ended {
+        log_status(state, format!("Detached run {id} ended."));
+    }
+    logged
+}
+
+fn load_keymap(state: &mut AgentState) {
+    let keymap = Keymap::from_config(&agent::keys_config());
+    for problem in &keymap.problems {
+        log(
+            state,
+            LogLevel::Warn,
+            format!("Key binding ignored: {problem}"),
+        );
+    }
+    state.ui.keymap = keymap;
+}
+
+/// `explicit` is a `/reload`; watcher-triggered reloads stay quiet when
+/// nothing relevant changed.
+fn reload_config(
+    state: &mut AgentState,
+    agent: &mut Agent,
+    watcher: &mut ConfigWatcher,
+    explicit: bool,
+) {
+    let changes = match watcher.reload(agent::validate_config) {
+        Ok(changes) => changes,
+        Err(e) => {
+            log(
+                state,
+                LogLevel::Error,
+                format!(
+                    "Config not reloaded, keeping current settings: {}: {e}",
+                    watcher.path().display()
+                ),
+            );
+            return;
+        }
+    };
+    if changes.is_empty() {
+        if explicit {
+            log(state, LogLevel::Info, "Config reloaded: no changes.");
+        }
+        return;
+    }
+
+    agent.reload_config();
+    net::reload_settings();
+    load_keymap(state);
+    log(
+        state,
+        LogLevel::Success,
+        format!(
+            "Config reloaded: {} setting{} changed.",
+            changes.len(),
+            if changes.len() == 1 { "" } else { "s" }
+        ),
+    );
+    for change in &changes {
+        log(state, LogLevel::Info, format!("  {}", change.describe()));
+    }
+    if changes
+        .iter()
+        .any(|c| c.key.starts_with("network.ca_bundles"))
+    {
+        log(
+            state,
+            LogLevel::Warn,
+            "network.ca_bundles takes effect after a restart.",
+        );
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
     let session_name = selected_session_name(&cli);
+    if let Err(e) = file_log::init(&agent::logging_config()) {
+        eprintln!("osmogrep: file logging disabled: {e}");
+    }
     match cli.command {
         Some(CliCommand::Version) => {
             println!("osmogrep {}", env!("CARGO_PKG_VERSION"));
@@ -537,16 +887,117 @@ fn main() -> Result<(), Box<dyn Error>> {
             uninstall_current_binary(args)?;
         }
         Some(CliCommand::Run(args)) => {
-            let code = run_headless(args)?;
+            let code = run_headless(args, PipedInput::read_stdin()?)?;
             if code != 0 {
                 std::process::exit(code);
             }
         }
         Some(CliCommand::Triage(args)) => {
-            triage::run(args)?;
+            triage::run(*args)?;
+        }
+        Some(CliCommand::Attach { id }) => {
+            attach_detached_run(id.as_deref())?;
+        }
+        Some(CliCommand::Hooks { repo_root, trust }) => {
+            for line in lifecycle_hooks::report(&repo_root, trust)? {
+                println!("{line}");
+            }
+        }
+        Some(CliCommand::Analyze {
+            repo_root,
+            base,
+            fail_on_untested,
+        }) => {
+            let report = testgen::diff_report(&repo_root, &base);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if fail_on_untested && report.requires_tests {
+                std::process::exit(1);
+            }
+        }
+        Some(CliCommand::Onboard {
+            repo_root,
+            output,
+            json,
+        }) => {
+            let report = onboard::build(&repo_root);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", onboard::render(&report));
+            }
+            if let Some(path) = output {
+                let path = onboard::export(&report, &path)?;
+                eprintln!("wrote {}", path.display());
+            }
+        }
+        Some(CliCommand::FixTests {
+            repo_root,
+            max_rounds,
+            auto_approve,
+        }) => {
+            let code = run_fix_tests(repo_root, max_rounds, auto_approve)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
         }
+        Some(CliCommand::Testgen(args)) => match args.command {
+            TestgenCommand::Stats { repo_root } => {
+                for line in testgen::stats_report(&testgen::load(&repo_root)?) {
+                    println!("{line}");
+                }
+            }
+            TestgenCommand::Maintain {
+                repo_root,
+                dry_run,
+                auto_approve,
+            } => {
+                let stale = testgen::stale_tests(&repo_root)?;
+                if stale.is_empty() {
+                    println!("No failing generated tests with changed targets.");
+                    return Ok(());
+                }
+                for test in &stale {
+                    println!(
+                        "{} ({}): changed since generation: {}",
+                        test.test,
+                        test.file,
+                        test.changed.join(", ")
+                    );
+                }
+                if !dry_run {
+                    let code = run_headless(
+                        RunArgs {
+                            repo_root,
+                            task: None,
+                            prompt: Some(testgen::maintenance_prompt(&stale)),
+                            prompt_file: None,
+                            json_events: false,
+                            permission_profile: "workspace-auto".to_string(),
+                            auto_approve,
+                            record: None,
+                            replay: None,
+                        },
+                        None,
+                    )?;
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                }
+            }
+            TestgenCommand::Strength { repo_root } => {
+                let (commit, strength) = testgen::measure_strength(&repo_root)?;
+                println!(
+                    "{}: test strength {}",
+                    &commit[..commit.len().min(8)],
+                    strength.label()
+                );
+                for survivor in &strength.survivors {
+                    println!("  survived: {survivor}");
+                }
+            }
+        },
         None => {
-            run_tui(session_name)?;
+            run_tui(session_name, PipedInput::read_stdin()?)?;
         }
     }
     Ok(())
@@ -579,6 +1030,11 @@ fn print_doctor() -> Result<(), Box<dyn Error>> {
     println!("config: {}", config_dir.display());
     println!("sessions: {}", session_dir.display());
     println!("saved_sessions: {}", sessions.len());
+    let logging = agent::logging_config();
+    match logging.level_filter() {
+        Ok(level) => println!("logs: {} ({level})", logging.dir().display()),
+        Err(e) => println!("logs: {e}"),
+    }
     println!("repo: {}", std::env::current_dir()?.display());
     println!(
         "openai_api_key: {}",
@@ -588,6 +1044,20 @@ fn print_doctor() -> Result<(), Box<dyn Error>> {
             "missing"
         }
     );
+
+    let network = net::settings();
+    println!("network: {}", network.describe());
+    let base_url = Agent::new().base_url();
+    for url in [base_url.as_str(), "https://api.github.com"] {
+        let route = Url::parse(url)
+            .ok()
+            .and_then(|u| network.proxy_for(&u).map(net::redact_proxy))
+            .unwrap_or_else(|| "direct".to_string());
+        match net::probe(url) {
+            Ok(status) => println!("reach {url} via {route}: HTTP {status}"),
+            Err(e) => println!("reach {url} via {route}: FAILED ({e})"),
+        }
+    }
     Ok(())
 }
 
@@ -673,12 +1143,137 @@ fn confirm_uninstall(path: &Path) -> Result<bool, Box<dyn Error>> {
     Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "YES"))
 }
 
-fn run_headless(args: RunArgs) -> Result<i32, Box<dyn Error>> {
-    let prompt = match (args.prompt, args.prompt_file) {
+fn attach_detached_run(id: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let Some(id) = id else {
+        let runs = detach::list();
+        if runs.is_empty() {
+            println!("No detached runs in {}", detach::runs_dir().display());
+        }
+        for run in runs {
+            let status = if run.is_finished() {
+                "finished"
+            } else {
+                "running"
+            };
+            println!(
+                "{}  {:<8}  {}  {}",
+                run.id,
+                status,
+                run.started()
+                    .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default(),
+                run.task.lines().next().unwrap_or_default()
+            );
+        }
+        return Ok(());
+    };
+
+    let run = detach::find(id)?;
+    let mut tail = detach::EventTail::new(run.events_path());
+    loop {
+        // Check before reading so events written just before exit are shown.
+        let finished = run.is_finished();
+        for event in tail.poll() {
+            for (_, line) in tail.render(&event) {
+                println!("{line}");
+            }
+            if detach::is_terminal(&event) {
+                return Ok(());
+            }
+        }
+        if finished {
+            let stderr = fs::read_to_string(run.stderr_path()).unwrap_or_default();
+            if !stderr.trim().is_empty() {
+                eprint!("{stderr}");
+            }
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+// Repair rounds over the failing tests: one headless run per failing file,
+// then a rerun of only that file's tests. Exits 1 while anything still fails.
+fn run_fix_tests(
+    repo_root: PathBuf,
+    max_rounds: usize,
+    auto_approve: bool,
+) -> Result<i32, Box<dyn Error>> {
+    let run = test_harness::run_tests(&repo_root, None)?;
+    let framework = run.framework.clone();
+    let mut groups = fix_tests::group_failures(&repo_root, &run);
+    for round in 1..=max_rounds {
+        if groups.is_empty() {
+            let run = test_harness::run_tests(&repo_root, None)?;
+            groups = fix_tests::group_failures(&repo_root, &run);
+            if groups.is_empty() {
+                break;
+            }
+        }
+        let mut failing = Vec::new();
+        for group in groups {
+            println!(
+                "round {round}/{max_rounds}: {} ({} failing)",
+                group.label(),
+                group.tests.len()
+            );
+            let code = run_headless(
+                RunArgs {
+                    repo_root: repo_root.clone(),
+                    task: None,
+                    prompt: Some(fix_tests::repair_prompt(
+                        &group, &framework, round, max_rounds,
+                    )),
+                    prompt_file: None,
+                    json_events: false,
+                    permission_profile: "workspace-auto".to_string(),
+                    auto_approve,
+                    record: None,
+                    replay: None,
+                },
+                None,
+            )?;
+            if code != 0 {
+                eprintln!("agent run for {} exited with {code}", group.label());
+                failing.push(group);
+                continue;
+            }
+            let target = fix_tests::rerun_target(&framework, &group);
+            let rerun = test_harness::run_tests(&repo_root, target.as_deref())?;
+            if rerun.success {
+                println!("fixed {}", group.label());
+            } else {
+                failing.extend(fix_tests::regroup(&repo_root, &group, &rerun));
+            }
+        }
+        groups = failing;
+    }
+
+    let run = test_harness::run_tests(&repo_root, None)?;
+    if run.success {
+        println!("All {} tests pass.", run.passed);
+        return Ok(0);
+    }
+    println!(
+        "{} failing after {max_rounds} rounds: {}",
+        run.failed,
+        run.failures.join(", ")
+    );
+    Ok(1)
+}
+
+fn run_headless(args: RunArgs, piped: Option<PipedInput>) -> Result<i32, Box<dyn Error>> {
+    let replay = args
+        .replay
+        .as_deref()
+        .map(response_cache::Recording::load)
+        .transpose()?;
+    let prompt = match (args.task.or(args.prompt), args.prompt_file) {
         (Some(prompt), None) => prompt,
         (None, Some(path)) => fs::read_to_string(path)?,
+        (None, None) if replay.is_some() => replay.as_ref().unwrap().prompt.clone(),
         (None, None) => {
-            return Err("provide --prompt or --prompt-file".into());
+            return Err("provide a task, --prompt or --prompt-file".into());
         }
         (Some(_), Some(_)) => {
             return Err("use only one of --prompt or --prompt-file".into());
@@ -689,22 +1284,59 @@ fn run_headless(args: RunArgs) -> Result<i32, Box<dyn Error>> {
     if prompt.is_empty() {
         return Err("prompt is empty".into());
     }
+    let task = prompt.clone();
+    let prompt = match &piped {
+        Some(piped) => {
+            eprintln!("note: attached {}", piped.label());
+            piped.attach_to(&prompt)
+        }
+        None => prompt,
+    };
 
     let repo_root = fs::canonicalize(&args.repo_root).unwrap_or(args.repo_root);
     let permission_profile = PermissionProfile::parse(&args.permission_profile)
         .ok_or("permission profile must be read-only, workspace-auto, or full-access")?;
-    let agent = Agent::new();
+    let mut agent = Agent::new();
+    if let Some(recording) = replay {
+        agent = agent.with_replay(Replay::new(recording));
+    }
     if !agent.is_configured() {
         return Err("OPENAI_API_KEY is not set".into());
     }
+    if let Some(path) = &args.record {
+        let recorder = Recorder::create(path, &agent.model_config().model, &prompt)?;
+        agent = agent.with_recorder(recorder);
+    }
+
+    let _repo_lock = match instance::acquire(&repo_root)? {
+        LockAttempt::Acquired(lock) => Some(lock),
+        // A run detached from the TUI shares that instance's lock.
+        LockAttempt::Held(owner)
+            if std::env::var(detach::LOCK_DELEGATE_ENV).is_ok_and(|id| id == owner.instance) =>
+        {
+            None
+        }
+        LockAttempt::Held(owner) if permission_profile == PermissionProfile::ReadOnly => {
+            instance::set_secondary(true);
+            eprintln!("note: repository is locked by {}", owner.describe());
+            None
+        }
+        LockAttempt::Held(owner) => {
+            return Err(format!(
+                "repository is locked by {}; stop it or use --permission-profile read-only",
+                owner.describe()
+            )
+            .into());
+        }
+    };
 
     let mut emitter = HeadlessEmitter::new(args.json_events, args.auto_approve);
-    emitter.emit_run_start(&repo_root, &prompt);
+    emitter.emit_run_start(&repo_root, &task);
 
     let (tx, rx) = mpsc::channel();
     let _control = agent.spawn(
         repo_root,
-        prompt,
+        prompt.into(),
         Vec::new(),
         None,
         permission_profile,
@@ -872,12 +1504,16 @@ fn headless_json_value(evt: AgentEvent, auto_approve: bool) -> serde_json::Value
             target,
             before,
             after,
+            created,
+            deleted,
         } => serde_json::json!({
             "type": "tool_diff",
             "tool": tool,
             "target": target,
             "before": before,
-            "after": after
+            "after": after,
+            "created": created,
+            "deleted": deleted
         }),
         AgentEvent::PreviewDiff {
             tool,
@@ -910,23 +1546,91 @@ fn headless_json_value(evt: AgentEvent, auto_approve: bool) -> serde_json::Value
             "iteration": iteration,
             "max_iterations": max_iterations
         }),
+        AgentEvent::Retrying(notice) => serde_json::json!({
+            "type": "retrying",
+            "host": notice.host,
+            "status": notice.status,
+            "wait_ms": notice.wait.as_millis() as u64,
+            "attempt": notice.attempt,
+            "max_attempts": notice.max_attempts,
+            "message": notice.to_string()
+        }),
         AgentEvent::PermissionRequest {
             tool_name,
             args_summary,
+            protected,
             reply_tx,
+            ..
         } => {
-            let approved = auto_approve;
+            // Protected edits need a person to confirm them.
+            let approved = auto_approve && protected.is_none();
             let _ = reply_tx.send(approved);
             serde_json::json!({
                 "type": "permission_request",
                 "tool_name": tool_name,
                 "args_summary": args_summary,
+                "protected": protected,
                 "approved": approved
             })
         }
+        AgentEvent::ToolsExposed {
+            profile,
+            exposed,
+            dropped,
+        } => serde_json::json!({
+            "type": "tools_exposed",
+            "profile": profile,
+            "exposed": exposed,
+            "dropped": dropped
+        }),
+        AgentEvent::ChangeBudgetExceeded {
+            summary,
+            changes,
+            reply_tx,
+        } => {
+            // Nobody to ask: keep the edits and stop.
+            let _ = reply_tx.send(BudgetDecision::Split);
+            serde_json::json!({
+                "type": "change_budget_exceeded",
+                "summary": summary,
+                "files": changes.iter().map(|c| c.target.as_str()).collect::<Vec<_>>(),
+                "decision": BudgetDecision::Split.as_str()
+            })
+        }
         AgentEvent::ConversationUpdate(_) => {
             serde_json::json!({ "type": "conversation_update" })
         }
+        AgentEvent::Usage {
+            provider,
+            model,
+            input_tokens,
+            output_tokens,
+            estimated,
+            tools,
+        } => serde_json::json!({
+            "type": "usage",
+            "provider": provider,
+            "model": model,
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "estimated": estimated,
+            "tools": tools
+        }),
+        AgentEvent::Subagent(status) => {
+            serde_json::json!({ "type": "subagent", "subagent": status })
+        }
+        AgentEvent::Checkpoint {
+            messages,
+            transient,
+        } => serde_json::json!({
+            "type": "checkpoint",
+            "messages": messages,
+            "transient": transient
+        }),
+        AgentEvent::Transcript(path) => serde_json::json!({
+            "type": "transcript",
+            "path": path.display().to_string(),
+        }),
         AgentEvent::Cancelled => serde_json::json!({ "type": "cancelled" }),
         AgentEvent::Error(message) => {
             serde_json::json!({ "type": "error", "message": message })
@@ -1078,12 +1782,15 @@ fn emit_headless_text(evt: AgentEvent, auto_approve: bool) {
         } => {
             println!("[status] {phase} {iteration}/{max_iterations} {detail}");
         }
+        AgentEvent::Retrying(notice) => println!("[retry] {notice}"),
         AgentEvent::PermissionRequest {
             tool_name,
             args_summary,
+            protected,
             reply_tx,
+            ..
         } => {
-            let approved = auto_approve;
+            let approved = auto_approve && protected.is_none();
             let _ = reply_tx.send(approved);
             println!(
                 "[permission] {} {} {}",
@@ -1091,8 +1798,44 @@ fn emit_headless_text(evt: AgentEvent, auto_approve: bool) {
                 tool_name,
                 args_summary
             );
+            if let Some(protected) = protected {
+                println!("[permission] protected path {protected}");
+            }
+        }
+        AgentEvent::ToolsExposed {
+            profile,
+            exposed,
+            dropped,
+        } => {
+            println!("[tools] {profile}: {}", exposed.join(", "));
+            if !dropped.is_empty() {
+                println!("[tools] dropped {}", dropped.join(", "));
+            }
+        }
+        AgentEvent::ChangeBudgetExceeded {
+            summary, reply_tx, ..
+        } => {
+            let _ = reply_tx.send(BudgetDecision::Split);
+            println!("[change budget] {summary}; stopping");
+        }
+        AgentEvent::ConversationUpdate(_)
+        | AgentEvent::Usage { .. }
+        | AgentEvent::Checkpoint { .. } => {}
+        // Progress updates in between would repeat the line per tool call.
+        AgentEvent::Subagent(status)
+            if status.state != SubagentState::Running || status.tokens == 0 =>
+        {
+            println!(
+                "[subagent] #{} {}: {} ({} tool calls, {} tokens)",
+                status.id,
+                status.state.as_str(),
+                status.task,
+                status.tool_calls,
+                status.tokens
+            );
         }
-        AgentEvent::ConversationUpdate(_) => {}
+        AgentEvent::Subagent(_) => {}
+        AgentEvent::Transcript(path) => println!("[transcript] {}", path.display()),
         AgentEvent::Cancelled => println!("[cancelled]"),
         AgentEvent::Error(message) => eprintln!("[error] {}", message),
         AgentEvent::Done => println!("[done]"),
@@ -1103,17 +1846,20 @@ fn compact_json(value: &serde_json::Value) -> String {
     serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string())
 }
 
-fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
+fn run_tui(session_name: Option<String>, piped: Option<PipedInput>) -> Result<(), Box<dyn Error>> {
     let mut terminal_session = setup_terminal()?;
 
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    let mut state = init_state();
-    persistence::load(&mut state);
-    if let Some(name) = session_name {
-        state.session_name = Some(name);
-        let _ = persistence::save(&state);
+    let mut state = open_session(std::env::current_dir()?, session_name, None);
+    if let Some(piped) = piped {
+        log(
+            &mut state,
+            LogLevel::Info,
+            format!("Attached {} to the next prompt.", piped.label()),
+        );
+        state.ui.piped_input = Some(piped);
     }
     let mut agent = Agent::new();
     if env_truthy("OSMOGREP_NV_TIPS", false) {
@@ -1131,7 +1877,14 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
 
     let (voice_cmd_tx, voice_cmd_rx) = mpsc::channel();
     let (voice_evt_tx, voice_evt_rx) = mpsc::channel();
-    let _voice_handle = voice::spawn_voice_worker(voice_cmd_rx, voice_evt_tx.clone());
+    let voice_config = agent::voice_config();
+    state.voice.backend = voice_config.backend;
+    state.voice.speak = voice_config.speak;
+    let (speech_cmd_tx, speech_cmd_rx) = mpsc::channel();
+    let (speech_evt_tx, speech_evt_rx) = mpsc::channel();
+    let _speech_handle =
+        speech::spawn_speech_worker(speech_cmd_rx, speech_evt_tx, voice_config.clone());
+    let _voice_handle = voice::spawn_voice_worker(voice_cmd_rx, voice_evt_tx.clone(), voice_config);
     let proxy_listen = std::env::var("VLLM_REALTIME_PROXY_LISTEN").ok();
     if let Some(listen_addr) = proxy_listen.clone() {
         let _proxy_handle = voice::spawn_voice_proxy_worker(
@@ -1146,20 +1899,15 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
         let _ = voice_cmd_tx.send(voice::VoiceCommand::Start {
             url: state.voice.url.clone(),
             model: state.voice.model.clone(),
+            paused: state.voice.push_to_talk(),
         });
         state.voice.visible = true;
         state.voice.enabled = true;
         log_status(&mut state, "Connecting voice input...");
     }
 
-    let mut agent_rx: Option<mpsc::Receiver<AgentEvent>> = None;
-    let mut agent_cancel: Option<CancelToken> = None;
-    let mut agent_steer_tx: Option<mpsc::Sender<String>> = None;
-    let (job_tx, job_rx) = mpsc::channel::<JobEvent>();
     let (update_tx, update_rx) = mpsc::channel::<updater::UpdateEvent>();
     updater::spawn_update_check(update_tx.clone());
-    let mut running_jobs = 0usize;
-    let mut context_rx: Option<mpsc::Receiver<ContextEvent>>;
     let voice_silence_ms: u64 = std::env::var("VLLM_REALTIME_SILENCE_MS")
         .ok()
         .and_then(|v| v.parse::<u64>().ok())
@@ -1167,25 +1915,23 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
 
     /* ---------- SPAWN CONTEXT INDEXER ---------- */
 
-    {
-        let (tx, rx) = mpsc::channel();
-        let root = state.repo_root.clone();
-
-        context::spawn_indexer(root, tx);
-        context_rx = Some(rx);
-    }
+    let mut links = SessionLinks::start(&state.repo_root);
+    // Every tab's session; `None` marks the one in front.
+    let mut tabs: Vec<Option<ParkedTab>> = vec![None];
 
     /* ---------- MAIN LOOP ---------- */
 
     let mut runtime = TuiRuntime::default();
+    let mut config_watcher = ConfigWatcher::new(agent::config_path());
+    let mut diff_poll = Instant::now();
 
     loop {
         runtime.draw_if_due(&mut terminal, &state)?;
 
         if event::poll(runtime.poll_timeout(tui_live_activity(
             &state,
-            agent_rx.is_some(),
-            running_jobs,
+            links.agent_rx.is_some(),
+            links.running_jobs,
         )))? {
             let ev = event::read()?;
             handle_event(
@@ -1198,11 +1944,60 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
             runtime.mark_dirty();
         }
 
+        if follow_attached_run(&mut state) {
+            runtime.mark_dirty();
+        }
+
+        if let Some(request) = state.ui.tab_request.take() {
+            apply_tab_request(request, &mut state, &mut links, &mut tabs);
+            runtime.mark_dirty();
+        }
+        refresh_tab_labels(&mut state, &tabs);
+
+        if let Some(command) = state.ui.editor_request.take() {
+            terminal_session.suspend()?;
+            let status = platform::shell_command(&command)
+                .current_dir(&state.repo_root)
+                .status();
+            terminal_session.resume()?;
+            terminal.clear()?;
+            match status {
+                Ok(status) if status.success() => {}
+                Ok(status) => log(
+                    &mut state,
+                    LogLevel::Warn,
+                    format!("Editor exited with {status}: {command}"),
+                ),
+                Err(e) => log(
+                    &mut state,
+                    LogLevel::Error,
+                    format!("Could not start the editor: {e}"),
+                ),
+            }
+            runtime.mark_dirty();
+        }
+
+        if state.ui.exit_when_idle && state.exit_blockers().is_empty() {
+            state.ui.should_exit = true;
+        }
         if state.ui.should_exit {
             let _ = persistence::save(&state);
+            for tab in tabs.iter().flatten() {
+                let _ = persistence::save(&tab.state);
+            }
             break;
         }
 
+        if !tui_live_activity(&state, links.agent_rx.is_some(), links.running_jobs) {
+            working_diff::poll(&state.repo_root, &mut diff_poll);
+        }
+
+        if state.ui.config_reload_requested || config_watcher.changed() {
+            let explicit = std::mem::take(&mut state.ui.config_reload_requested);
+            reload_config(&mut state, &mut agent, &mut config_watcher, explicit);
+            runtime.mark_dirty();
+        }
+
         if state.ui.update_install_requested {
             state.ui.update_install_requested = false;
             state.ui.update_skip_requested = false;
@@ -1216,163 +2011,37 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
         }
 
         loop {
-            match job_rx.try_recv() {
-                Ok(JobEvent::Finished {
-                    id,
-                    ok,
-                    output,
-                    kind,
-                }) => {
+            match update_rx.try_recv() {
+                Ok(evt) => {
+                    handle_update_event(&mut state, evt);
                     runtime.mark_dirty();
-                    running_jobs = running_jobs.saturating_sub(1);
-                    if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
-                        job.status = if ok {
-                            JobStatus::Done
-                        } else {
-                            JobStatus::Failed
-                        };
-                        job.output = Some(output.clone());
-                    }
-                    log(
-                        &mut state,
-                        if ok {
-                            LogLevel::Success
-                        } else {
-                            LogLevel::Error
-                        },
-                        format!(
-                            "Job #{} [{}] {}",
-                            id,
-                            kind.as_str(),
-                            if ok { "completed" } else { "failed" }
-                        ),
-                    );
-                    for line in output.lines().take(24) {
-                        log(&mut state, LogLevel::Info, line.to_string());
-                    }
                 }
                 Err(mpsc::TryRecvError::Empty) => break,
                 Err(mpsc::TryRecvError::Disconnected) => break,
             }
         }
 
-        loop {
-            match update_rx.try_recv() {
-                Ok(evt) => {
-                    handle_update_event(&mut state, evt);
-                    runtime.mark_dirty();
-                }
-                Err(mpsc::TryRecvError::Empty) => break,
-                Err(mpsc::TryRecvError::Disconnected) => break,
-            }
-        }
-
-        while running_jobs < 2 && !state.job_queue.is_empty() {
-            runtime.mark_dirty();
-            let req = state.job_queue.remove(0);
-            if let Some(job) = state.jobs.iter_mut().find(|j| j.id == req.id) {
-                job.status = JobStatus::Running;
-            }
-
-            let tx = job_tx.clone();
-            let model_cfg = agent.model_config().clone();
-            let api_key = agent.api_key();
-            let repo_root = state.repo_root.clone();
-            running_jobs += 1;
-
-            std::thread::spawn(move || {
-                let (ok, output, kind) = match req.kind {
-                    JobKind::Swarm => match api_key {
-                        Some(k) => match agent::run_swarm_job(model_cfg, k, req.input.clone()) {
-                            Ok(s) => (true, s, JobKind::Swarm),
-                            Err(e) => (false, e, JobKind::Swarm),
-                        },
-                        None => (false, "OPENAI_API_KEY not set".to_string(), JobKind::Swarm),
-                    },
-                    JobKind::Review => match api_key {
-                        Some(k) => match serde_json::from_str::<Vec<DiffSnapshot>>(&req.input) {
-                            Ok(changes) => match agent::run_review_job(model_cfg, k, changes) {
-                                Ok(s) => (true, s, JobKind::Review),
-                                Err(e) => (false, e, JobKind::Review),
-                            },
-                            Err(e) => (false, e.to_string(), JobKind::Review),
-                        },
-                        None => (false, "OPENAI_API_KEY not set".to_string(), JobKind::Review),
-                    },
-                    JobKind::Test => {
-                        let target = if req.input.trim().is_empty() {
-                            None
-                        } else {
-                            Some(req.input.as_str())
-                        };
-                        match test_harness::run_tests(&repo_root, target) {
-                            Ok(run) => (
-                                run.success,
-                                format!(
-                                    "framework={} exit={} passed={} failed={}\n{}",
-                                    run.framework,
-                                    run.exit_code,
-                                    run.passed,
-                                    run.failed,
-                                    run.output
-                                ),
-                                JobKind::Test,
-                            ),
-                            Err(e) => (false, e, JobKind::Test),
-                        }
-                    }
-                };
-                let _ = tx.send(JobEvent::Finished {
-                    id: req.id,
-                    ok,
-                    output,
-                    kind,
-                });
+        // Releases are only requested from the terminal while push-to-talk
+        // is live; otherwise a release is assumed once key repeat stops.
+        let ptt_live = state.voice.connected && state.voice.push_to_talk();
+        state.voice.ptt_release_events = terminal_session.set_key_release_reporting(ptt_live);
+        if state.voice.ptt_held
+            && !state.voice.ptt_release_events
+            && state
+                .voice
+                .ptt_last_key
+                .is_some_and(|at| at.elapsed() >= voice::PTT_RELEASE_TIMEOUT)
+        {
+            state.voice.ptt_release();
+        }
+        if let Some(resume) = state.voice.ptt_transition.take() {
+            state.voice.speech_stop_requested |= resume && state.voice.speaking;
+            let _ = voice_cmd_tx.send(if resume {
+                voice::VoiceCommand::Resume
+            } else {
+                voice::VoiceCommand::Pause
             });
-        }
-
-        if let Some(rx) = context_rx.take() {
-            let mut done = false;
-
-            loop {
-                match rx.try_recv() {
-                    Ok(evt) => match evt {
-                        ContextEvent::Started => {
-                            runtime.mark_dirty();
-                            state.ui.indexing = true;
-                            state.ui.indexed = false;
-                            state.ui.spinner_started_at = Some(Instant::now());
-                        }
-
-                        ContextEvent::Finished => {
-                            runtime.mark_dirty();
-                            state.ui.indexing = false;
-                            state.ui.indexed = true;
-                            state.ui.spinner_started_at = None;
-                            done = true;
-                        }
-
-                        ContextEvent::Error(_e) => {
-                            runtime.mark_dirty();
-                            state.ui.indexing = false;
-                            state.ui.spinner_started_at = None;
-                            done = true;
-                        }
-                    },
-
-                    Err(mpsc::TryRecvError::Empty) => break,
-                    Err(mpsc::TryRecvError::Disconnected) => {
-                        runtime.mark_dirty();
-                        state.ui.indexing = false;
-                        state.ui.spinner_started_at = None;
-                        done = true;
-                        break;
-                    }
-                }
-            }
-            if !done {
-                context_rx = Some(rx);
-            }
+            runtime.mark_dirty();
         }
 
         loop {
@@ -1398,17 +2067,38 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
                         state.voice.buffer.clear();
                         state.voice.last_activity = None;
                         state.voice.last_inserted = None;
+                        state.voice.paused = false;
+                        state.voice.ptt_held = false;
+                        state.voice.ptt_last_key = None;
+                        state.voice.ptt_transition = None;
+                        state.voice.wake_armed = None;
+                    }
+                    voice::VoiceEvent::Paused => {
+                        runtime.mark_dirty();
+                        state.voice.paused = true;
+                    }
+                    voice::VoiceEvent::Resumed => {
+                        runtime.mark_dirty();
+                        state.voice.paused = false;
                     }
                     voice::VoiceEvent::Partial(delta) => {
                         runtime.mark_dirty();
+                        // Talking over a read-back cuts it off.
+                        state.voice.speech_stop_requested |= state.voice.speaking;
                         state.voice.partial = Some(delta);
                         state.voice.last_final = None;
                         if let Some(delta) = state.voice.partial.as_deref() {
                             state.voice.buffer.push_str(delta);
                         }
                         state.voice.last_activity = Some(Instant::now());
-                        if state.ui.input.is_empty()
-                            || state.voice.last_inserted.as_deref() == Some(state.ui.input.as_str())
+                        // Until the wake phrase is heard, nothing is meant for the input.
+                        let gated =
+                            matches!(state.voice.activation, voice::VoiceActivation::WakeWord(_))
+                                && !state.voice.wake_armed();
+                        if !gated
+                            && (state.ui.input.is_empty()
+                                || state.voice.last_inserted.as_deref()
+                                    == Some(state.ui.input.as_str()))
                         {
                             state.ui.input = state.voice.buffer.clone();
                             state.voice.last_inserted = Some(state.ui.input.clone());
@@ -1416,11 +2106,18 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
                     }
                     voice::VoiceEvent::Final(text) => {
                         runtime.mark_dirty();
+                        state.voice.speech_stop_requested |= state.voice.speaking;
                         let final_text = if text.trim().is_empty() {
                             state.voice.buffer.clone()
                         } else {
                             text
                         };
+                        let Some(final_text) = gate_voice_text(&mut state, final_text) else {
+                            state.voice.partial = None;
+                            state.voice.buffer.clear();
+                            state.voice.last_activity = Some(Instant::now());
+                            continue;
+                        };
                         state.ui.input_mode = InputMode::AgentText;
                         state.ui.input_masked = false;
                         state.ui.input_placeholder = None;
@@ -1469,12 +2166,35 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
             }
         }
 
-        if state.voice.connected {
+        if std::mem::take(&mut state.voice.speech_stop_requested) {
+            let _ = speech_cmd_tx.send(speech::SpeechCommand::Stop);
+        }
+        loop {
+            match speech_evt_rx.try_recv() {
+                Ok(speech::SpeechEvent::Started) => {
+                    runtime.mark_dirty();
+                    state.voice.speaking = true;
+                }
+                Ok(speech::SpeechEvent::Finished) => {
+                    runtime.mark_dirty();
+                    state.voice.speaking = false;
+                }
+                Ok(speech::SpeechEvent::Error(msg)) => {
+                    log(&mut state, LogLevel::Warn, format!("Speech: {msg}"));
+                }
+                Err(_) => break,
+            }
+        }
+
+        // While the push-to-talk key is held, only the release commits.
+        let ptt_capturing = state.voice.push_to_talk() && !state.voice.paused;
+        if state.voice.connected && !ptt_capturing {
             if let Some(last) = state.voice.last_activity {
                 if !state.voice.buffer.is_empty()
                     && last.elapsed() >= Duration::from_millis(voice_silence_ms)
                 {
-                    let final_text = state.voice.buffer.clone();
+                    let buffered = state.voice.buffer.clone();
+                    let final_text = gate_voice_text(&mut state, buffered).unwrap_or_default();
                     if !final_text.trim().is_empty() {
                         state.ui.input_mode = InputMode::AgentText;
                         state.ui.input_masked = false;
@@ -1504,7 +2224,7 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
         }
 
         if state.ui.cancel_requested {
-            if let Some(token) = agent_cancel.as_ref() {
+            if let Some(token) = links.agent_cancel.as_ref() {
                 token.cancel();
                 log_status(&mut state, "Cancellation requested.");
                 runtime.mark_dirty();
@@ -1512,337 +2232,33 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
             state.ui.cancel_requested = false;
         }
 
-        if let Some(rx) = agent_rx.as_ref() {
-            loop {
-                match rx.try_recv() {
-                    Ok(evt) => match evt {
-                        AgentEvent::ToolCall { name, args } => {
-                            runtime.mark_dirty();
-                            let cmd = match args {
-                                serde_json::Value::Object(ref map) => map
-                                    .values()
-                                    .filter_map(|v| v.as_str())
-                                    .collect::<Vec<_>>()
-                                    .join(" "),
-                                _ => String::new(),
-                            };
-
-                            log_tool_call(&mut state, &name, cmd);
-                            state.ui.run_phase = "tool".to_string();
-                            state.ui.current_tool = Some(name.clone());
-                            state.ui.current_tool_detail = Some(compact_json(&args));
-                            state.ui.active_edit_target = tool_target_path(&name, &args);
-                        }
-
-                        AgentEvent::ToolResult { summary } => {
-                            runtime.mark_dirty();
-                            state.ui.last_tool_status = Some(summary.clone());
-                            log_tool_result(&mut state, summary);
-                        }
-
-                        AgentEvent::PlanUpdate { items } => {
-                            runtime.mark_dirty();
-                            let completed = items.iter().filter(|item| item.done).count();
-                            let total = items.len();
-                            state.plan_items = items;
-                            log_status(
-                                &mut state,
-                                format!("plan updated: {completed}/{total} complete"),
-                            );
-                            let _ = persistence::save(&state);
-                        }
-
-                        AgentEvent::FileFocus {
-                            phase,
-                            path,
-                            line,
-                            reason,
-                            ..
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.run_phase = phase.clone();
-                            state.ui.active_edit_target = Some(path.clone());
-                            state.ui.run_detail = Some(reason.unwrap_or_else(|| {
-                                line.map(|line| format!("{path}:{line}"))
-                                    .unwrap_or_else(|| path.clone())
-                            }));
-                        }
-
-                        AgentEvent::EditStart {
-                            path,
-                            operation,
-                            summary,
-                            ..
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.run_phase = "editing".to_string();
-                            state.ui.active_edit_target = Some(path.clone());
-                            state.ui.current_tool_detail = Some(summary.clone());
-                            log_status(&mut state, format!("{operation} {path}"));
-                        }
-
-                        AgentEvent::EditDelta {
-                            path,
-                            text,
-                            delta_kind,
-                            ..
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.diff_active = true;
-                            state.ui.active_edit_target = Some(path.clone());
-                            state.ui.diff_snapshot = vec![DiffSnapshot {
-                                tool: format!("live:{delta_kind}"),
-                                target: path,
-                                before: String::new(),
-                                after: text,
-                            }];
-                        }
-
-                        AgentEvent::EditComplete {
-                            path,
-                            changed,
-                            summary,
-                            ..
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.active_edit_target = Some(path.clone());
-                            state.ui.last_tool_status = Some(summary.clone());
-                            log_tool_result(
-                                &mut state,
-                                format!(
-                                    "{} {}",
-                                    if changed { "edited" } else { "unchanged" },
-                                    path
-                                ),
-                            );
-                        }
-
-                        AgentEvent::ValidationStart { command, .. } => {
-                            runtime.mark_dirty();
-                            state.ui.run_phase = "validating".to_string();
-                            state.ui.run_detail = Some(command.clone());
-                            log_status(&mut state, format!("validating: {command}"));
-                        }
-
-                        AgentEvent::ValidationComplete {
-                            command,
-                            passed,
-                            summary,
-                            ..
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.run_phase = if passed {
-                                "validated".to_string()
-                            } else {
-                                "validation_failed".to_string()
-                            };
-                            state.ui.last_tool_status = Some(summary.clone());
-                            log_tool_result(
-                                &mut state,
-                                format!(
-                                    "{}: {command}",
-                                    if passed {
-                                        "validation passed"
-                                    } else {
-                                        "validation failed"
-                                    }
-                                ),
-                            );
-                        }
-
-                        AgentEvent::ToolDiff {
-                            tool,
-                            target,
-                            before,
-                            after,
-                        } => {
-                            runtime.mark_dirty();
-                            let snap = DiffSnapshot {
-                                tool,
-                                target,
-                                before,
-                                after,
-                            };
-
-                            state.session_changes.push(snap.clone());
-                            state.undo_stack.push(snap.clone());
-                            state.ui.diff_active = true;
-                            state.ui.diff_snapshot = vec![snap];
-                            state.ui.active_edit_target =
-                                state.ui.diff_snapshot.first().map(|d| d.target.clone());
-                            let _ = persistence::save(&state);
-                        }
-
-                        AgentEvent::PreviewDiff {
-                            tool,
-                            target,
-                            before,
-                            after,
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.diff_active = true;
-                            state.ui.diff_snapshot = vec![DiffSnapshot {
-                                tool: format!("preview:{tool}"),
-                                target,
-                                before,
-                                after,
-                            }];
-                        }
-
-                        AgentEvent::OutputText(text) => {
-                            runtime.mark_dirty();
-                            if log_final_output_once(&mut state, &text) {
-                                state.usage.completion_tokens += (text.len() / 4).max(1);
-                            }
-                            let _ = persistence::save(&state);
-                        }
-
-                        AgentEvent::StreamDelta(delta) => {
-                            runtime.mark_dirty();
-                            state.ui.streaming_active = true;
-                            state.ui.follow_tail = true;
-                            state.ui.streaming_transcript.push_str(&delta);
-                            state.ui.streaming_buffer.push_str(&delta);
-                            update_streaming_log(&mut state);
-                        }
-
-                        AgentEvent::StreamDone => {
-                            runtime.mark_dirty();
-                            finish_streaming_output(&mut state);
-                        }
-
-                        AgentEvent::RunStatus {
-                            phase,
-                            detail,
-                            iteration,
-                            max_iterations,
-                        } => {
-                            runtime.mark_dirty();
-                            state.ui.run_phase = phase;
-                            state.ui.run_detail = Some(detail);
-                            state.ui.run_iteration = iteration;
-                            state.ui.run_iteration_limit = max_iterations;
-                        }
-
-                        AgentEvent::PermissionRequest {
-                            tool_name,
-                            args_summary,
-                            reply_tx,
-                        } => {
-                            runtime.mark_dirty();
-                            if state.ui.auto_approve {
-                                let _ = reply_tx.send(true);
-                                log_status(
-                                    &mut state,
-                                    format!("Auto-approved {} ({})", tool_name, args_summary),
-                                );
-                            } else {
-                                state.ui.pending_permission =
-                                    Some(crate::state::PendingPermission {
-                                        tool_name,
-                                        args_summary,
-                                        reply_tx,
-                                    });
-                            }
-                        }
-
-                        AgentEvent::ConversationUpdate(messages) => {
-                            runtime.mark_dirty();
-                            state.conversation.set_messages(messages);
-                            state.conversation.trim_to_budget(MAX_CONVERSATION_TOKENS);
-                            let _ = persistence::save(&state);
-                        }
-
-                        AgentEvent::Cancelled => {
-                            runtime.mark_dirty();
-                            finish_streaming_output(&mut state);
-                            log(&mut state, LogLevel::Warn, "Agent cancelled.");
-                            state.ui.spinner_started_at = None;
-                            state.ui.agent_running = false;
-                            state.ui.run_phase = "cancelled".to_string();
-                            state.ui.current_tool = None;
-                            state.ui.current_tool_detail = None;
-                            state.ui.pending_permission = None;
-                            state.ui.active_edit_target = None;
-                            agent_cancel = None;
-                            agent_steer_tx = None;
-                            agent_rx = None;
-                            break;
-                        }
-
-                        AgentEvent::Error(e) => {
-                            runtime.mark_dirty();
-                            log(&mut state, LogLevel::Error, e);
-                            finish_streaming_output(&mut state);
-                            state.ui.spinner_started_at = None;
-                            state.ui.agent_running = false;
-                            state.ui.run_phase = "error".to_string();
-                            state.ui.current_tool = None;
-                            state.ui.current_tool_detail = None;
-                            state.ui.pending_permission = None;
-                            state.ui.active_edit_target = None;
-                            agent_cancel = None;
-                            agent_steer_tx = None;
-                            agent_rx = None;
-                            break;
-                        }
-
-                        AgentEvent::Done => {
-                            runtime.mark_dirty();
-                            state.ui.spinner_started_at = None;
-                            state.ui.agent_running = false;
-                            state.ui.run_phase = "idle".to_string();
-                            state.ui.current_tool = None;
-                            state.ui.current_tool_detail = None;
-                            state.ui.pending_permission = None;
-                            state.ui.active_edit_target = None;
-                            warn_if_verification_needed(&mut state);
-                            queue_auto_review_if_needed(&mut state);
-                            if state.auto_eval && !state.session_changes.is_empty() {
-                                let id = state.next_job_id;
-                                state.next_job_id += 1;
-                                state.jobs.push(crate::state::JobRecord {
-                                    id,
-                                    kind: JobKind::Test,
-                                    input: String::new(),
-                                    status: JobStatus::Queued,
-                                    output: None,
-                                });
-                                state.job_queue.push(crate::state::JobRequest {
-                                    id,
-                                    kind: JobKind::Test,
-                                    input: String::new(),
-                                });
-                                log(
-                                    &mut state,
-                                    LogLevel::Info,
-                                    format!("Auto-eval queued as job #{}", id),
-                                );
-                            }
-                            agent_cancel = None;
-                            agent_steer_tx = None;
-                            agent_rx = None;
-                            break;
-                        }
-                    },
-
-                    Err(mpsc::TryRecvError::Empty) => break,
-                    Err(mpsc::TryRecvError::Disconnected) => {
-                        runtime.mark_dirty();
-                        finish_streaming_output(&mut state);
-                        state.ui.spinner_started_at = None;
-                        state.ui.agent_running = false;
-                        state.ui.run_phase = "disconnected".to_string();
-                        state.ui.current_tool = None;
-                        state.ui.current_tool_detail = None;
-                        state.ui.pending_permission = None;
-                        state.ui.active_edit_target = None;
-                        agent_cancel = None;
-                        agent_steer_tx = None;
-                        agent_rx = None;
-                        break;
-                    }
-                }
+        if pump_session(&mut state, &mut links, &agent, Some(&speech_cmd_tx)) {
+            runtime.mark_dirty();
+        }
+        // Parked tabs keep going too: their prompts, results and queued
+        // work are handled while another tab is in front.
+        for (index, tab) in tabs.iter_mut().enumerate() {
+            let Some(tab) = tab.as_mut() else {
+                continue;
+            };
+            let waiting = tab.state.ui.pending_permission.is_some();
+            if pump_session(&mut tab.state, &mut tab.links, &agent, None) {
+                runtime.mark_dirty();
+            }
+            if let Some(prompt) = tab
+                .state
+                .ui
+                .pending_permission
+                .as_ref()
+                .filter(|_| !waiting)
+            {
+                let text = format!(
+                    "Tab {}: {} needs permission; Alt+{} switches to it.",
+                    index + 1,
+                    prompt.tool_name,
+                    index + 1
+                );
+                log_status(&mut state, text);
             }
         }
 
@@ -1876,22 +2292,25 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
                         text,
                         Some(&voice_cmd_tx),
                         Some(&mut agent),
-                        agent_steer_tx.as_ref(),
+                        links.agent_steer_tx.as_ref(),
                     );
                 }
 
                 InputMode::Shell => {
                     if !text.is_empty() {
-                        run_shell(&mut state, text);
+                        let command = state.expand_pastes(text);
+                        run_shell(&mut state, &command);
                     }
                     continue;
                 }
 
                 InputMode::AgentText => {
                     if !text.is_empty() {
-                        if agent_rx.is_some() {
-                            if let Some(tx) = agent_steer_tx.as_ref() {
-                                let _ = tx.send(text.to_string());
+                        if links.agent_rx.is_some() {
+                            if let Some(tx) = links.agent_steer_tx.as_ref() {
+                                let steer = state.expand_pastes(text);
+                                let _ = tx.send(steer.clone());
+                                commands::record_steer(&mut state, &steer, true);
                                 log_status(&mut state, "Steer sent to running agent.");
                             } else {
                                 state.ui.queued_agent_prompt = Some(text.to_string());
@@ -1902,9 +2321,9 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
                                 &mut state,
                                 &agent,
                                 text,
-                                &mut agent_rx,
-                                &mut agent_cancel,
-                                &mut agent_steer_tx,
+                                &mut links.agent_rx,
+                                &mut links.agent_cancel,
+                                &mut links.agent_steer_tx,
                             );
                         }
                     }
@@ -1923,27 +2342,12 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
             }
         }
 
-        if agent_rx.is_none() {
-            if let Some(next_prompt) = state.ui.queued_agent_prompt.take() {
-                runtime.mark_dirty();
-                log_user_input(&mut state, &next_prompt);
-                start_agent_run(
-                    &mut state,
-                    &agent,
-                    &next_prompt,
-                    &mut agent_rx,
-                    &mut agent_cancel,
-                    &mut agent_steer_tx,
-                );
-            }
-        }
-
-        if !state.ui.execution_pending && agent_rx.is_none() {
+        if !state.ui.execution_pending && links.agent_rx.is_none() {
             let prev_commands = state
                 .ui
                 .command_items
                 .iter()
-                .map(|item| item.cmd)
+                .map(|item| item.cmd.clone())
                 .collect::<Vec<_>>();
             let prev_selected = state.ui.command_selected;
             commands::update_command_hints(&mut state);
@@ -1984,6 +2388,965 @@ fn run_tui(session_name: Option<String>) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Takes in what a tab's agent run, background jobs and indexer sent since
+/// the last tick, and starts its queued jobs and follow-up prompt. Speech
+/// only goes with the tab in front. Returns whether anything changed.
+fn pump_session(
+    state: &mut AgentState,
+    links: &mut SessionLinks,
+    agent: &Agent,
+    speech_cmd_tx: Option<&mpsc::Sender<speech::SpeechCommand>>,
+) -> bool {
+    let mut dirty = false;
+    loop {
+        match links.job_rx.try_recv() {
+            Ok(JobEvent::Finished {
+                id,
+                ok,
+                output,
+                kind,
+            }) => {
+                dirty = true;
+                links.running_jobs = links.running_jobs.saturating_sub(1);
+                if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
+                    job.status = if ok {
+                        JobStatus::Done
+                    } else {
+                        JobStatus::Failed
+                    };
+                    job.output = Some(output.clone());
+                }
+                log(
+                    state,
+                    if ok {
+                        LogLevel::Success
+                    } else {
+                        LogLevel::Error
+                    },
+                    format!(
+                        "Job #{} [{}] {}",
+                        id,
+                        kind.as_str(),
+                        if ok { "completed" } else { "failed" }
+                    ),
+                );
+                for line in output.lines().take(24) {
+                    log(state, LogLevel::Info, line.to_string());
+                }
+                if ok && matches!(kind, JobKind::Agent { sandboxed: true }) {
+                    log(
+                        state,
+                        LogLevel::Info,
+                        format!("Review its changes with /queue review {id}."),
+                    );
+                }
+            }
+            Ok(JobEvent::Started {
+                id,
+                run_id,
+                sandbox,
+            }) => {
+                dirty = true;
+                if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
+                    job.run_id = Some(run_id.clone());
+                }
+                if let Some(sandbox) = sandbox {
+                    state.ui.task_sandboxes.push((id, sandbox));
+                }
+                log(
+                    state,
+                    LogLevel::Info,
+                    format!(
+                        "Task #{id} started as run {run_id}; follow it with /queue attach {id}."
+                    ),
+                );
+            }
+            Err(mpsc::TryRecvError::Empty) => break,
+            Err(mpsc::TryRecvError::Disconnected) => break,
+        }
+    }
+
+    while links.running_jobs < 2 {
+        // Agent tasks outside a sandbox share the checkout, so they run
+        // one at a time.
+        let checkout_busy = state.jobs.iter().any(|j| {
+            j.status == JobStatus::Running && matches!(j.kind, JobKind::Agent { sandboxed: false })
+        });
+        let Some(next) = state.job_queue.iter().position(|req| {
+            !checkout_busy || !matches!(req.kind, JobKind::Agent { sandboxed: false })
+        }) else {
+            break;
+        };
+        dirty = true;
+        let req = state.job_queue.remove(next);
+        if let Some(job) = state.jobs.iter_mut().find(|j| j.id == req.id) {
+            job.status = JobStatus::Running;
+        }
+
+        let tx = links.job_tx.clone();
+        let model_cfg = agent.model_config().clone();
+        let api_key = agent.api_key();
+        let repo_root = state.repo_root.clone();
+        let permission_profile = state.permission_profile;
+        let auto_approve = state.ui.auto_approve;
+        links.running_jobs += 1;
+
+        std::thread::spawn(move || {
+            let (ok, output, kind) = match req.kind {
+                JobKind::Swarm => match api_key {
+                    Some(k) => match agent::run_swarm_job(model_cfg, k, req.input.clone()) {
+                        Ok(s) => (true, s, JobKind::Swarm),
+                        Err(e) => (false, e, JobKind::Swarm),
+                    },
+                    None => (false, "OPENAI_API_KEY not set".to_string(), JobKind::Swarm),
+                },
+                JobKind::Review => match api_key {
+                    Some(k) => match serde_json::from_str::<Vec<DiffSnapshot>>(&req.input) {
+                        Ok(changes) => match agent::run_review_job(model_cfg, k, changes) {
+                            Ok(s) => (true, s, JobKind::Review),
+                            Err(e) => (false, e, JobKind::Review),
+                        },
+                        Err(e) => (false, e.to_string(), JobKind::Review),
+                    },
+                    None => (false, "OPENAI_API_KEY not set".to_string(), JobKind::Review),
+                },
+                JobKind::Test => {
+                    let target = if req.input.trim().is_empty() {
+                        None
+                    } else {
+                        Some(req.input.as_str())
+                    };
+                    match test_harness::run_tests(&repo_root, target) {
+                        Ok(run) => (
+                            run.success,
+                            format!(
+                                "framework={} exit={} passed={} failed={}\n{}",
+                                run.framework, run.exit_code, run.passed, run.failed, run.output
+                            ),
+                            JobKind::Test,
+                        ),
+                        Err(e) => (false, e, JobKind::Test),
+                    }
+                }
+                JobKind::Agent { sandboxed } => {
+                    let (ok, output) = run_agent_task(
+                        &repo_root,
+                        &req,
+                        sandboxed,
+                        permission_profile,
+                        auto_approve,
+                        &tx,
+                    );
+                    (ok, output, JobKind::Agent { sandboxed })
+                }
+            };
+            let _ = tx.send(JobEvent::Finished {
+                id: req.id,
+                ok,
+                output,
+                kind,
+            });
+        });
+    }
+
+    if let Some(rx) = links.context_rx.take() {
+        let mut done = false;
+
+        loop {
+            match rx.try_recv() {
+                Ok(evt) => match evt {
+                    ContextEvent::Started => {
+                        dirty = true;
+                        state.ui.indexing = true;
+                        state.ui.indexed = false;
+                        state.ui.spinner_started_at = Some(Instant::now());
+                    }
+
+                    ContextEvent::Finished => {
+                        dirty = true;
+                        state.ui.indexing = false;
+                        state.ui.indexed = true;
+                        state.ui.index_updated_at = Some(chrono::Local::now());
+                        state.ui.spinner_started_at = None;
+                    }
+
+                    ContextEvent::Refreshing(files) => {
+                        dirty = true;
+                        state.ui.index_refreshing = files;
+                    }
+
+                    ContextEvent::Refreshed(0) => {
+                        if state.ui.index_refreshing > 0 {
+                            dirty = true;
+                            state.ui.index_refreshing = 0;
+                        }
+                    }
+
+                    ContextEvent::Refreshed(_) => {
+                        dirty = true;
+                        state.ui.index_refreshing = 0;
+                        state.ui.index_updated_at = Some(chrono::Local::now());
+                    }
+
+                    ContextEvent::RefreshFailed(e) => {
+                        dirty = true;
+                        state.ui.index_refreshing = 0;
+                        log(state, LogLevel::Warn, format!("Re-indexing failed: {e}"));
+                    }
+
+                    ContextEvent::Error(_e) => {
+                        dirty = true;
+                        state.ui.indexing = false;
+                        state.ui.spinner_started_at = None;
+                        done = true;
+                    }
+                },
+
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    dirty = true;
+                    state.ui.indexing = false;
+                    state.ui.spinner_started_at = None;
+                    done = true;
+                    break;
+                }
+            }
+        }
+        if !done {
+            links.context_rx = Some(rx);
+        }
+    }
+
+    if let Some(rx) = links.agent_rx.as_ref() {
+        loop {
+            match rx.try_recv() {
+                Ok(evt) => match evt {
+                    AgentEvent::ToolCall { name, args } => {
+                        dirty = true;
+                        let cmd = match args {
+                            serde_json::Value::Object(ref map) => map
+                                .values()
+                                .filter_map(|v| v.as_str())
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                            _ => String::new(),
+                        };
+
+                        log_tool_call(state, &name, cmd);
+                        state.ui.run_phase = "tool".to_string();
+                        state.ui.current_tool = Some(name.clone());
+                        state.ui.current_tool_detail = Some(compact_json(&args));
+                        state.ui.active_edit_target = tool_target_path(&name, &args);
+                    }
+
+                    AgentEvent::ToolResult { summary } => {
+                        dirty = true;
+                        state.ui.last_tool_status = Some(summary.clone());
+                        log_tool_result(state, summary);
+                    }
+
+                    AgentEvent::PlanUpdate { items } => {
+                        dirty = true;
+                        let completed = items.iter().filter(|item| item.done).count();
+                        let total = items.len();
+                        state.plan_items = items;
+                        log_status(state, format!("plan updated: {completed}/{total} complete"));
+                        if state.plan_approved && total > 0 && completed == total {
+                            state.plan_approved = false;
+                            log(state, LogLevel::Success, "Approved plan complete.");
+                        }
+                        let _ = persistence::save(state);
+                    }
+
+                    AgentEvent::FileFocus {
+                        phase,
+                        path,
+                        line,
+                        reason,
+                        ..
+                    } => {
+                        dirty = true;
+                        state.ui.run_phase = phase.clone();
+                        state.ui.active_edit_target = Some(path.clone());
+                        state.ui.run_detail = Some(reason.unwrap_or_else(|| {
+                            line.map(|line| format!("{path}:{line}"))
+                                .unwrap_or_else(|| path.clone())
+                        }));
+                    }
+
+                    AgentEvent::EditStart {
+                        path,
+                        operation,
+                        summary,
+                        ..
+                    } => {
+                        dirty = true;
+                        state.ui.run_phase = "editing".to_string();
+                        state.ui.active_edit_target = Some(path.clone());
+                        state.ui.current_tool_detail = Some(summary.clone());
+                        log_status(state, format!("{operation} {path}"));
+                    }
+
+                    AgentEvent::EditDelta {
+                        path,
+                        text,
+                        delta_kind,
+                        ..
+                    } => {
+                        dirty = true;
+                        state.ui.diff_active = true;
+                        state.ui.active_edit_target = Some(path.clone());
+                        state.ui.diff_snapshot = vec![DiffSnapshot {
+                            tool: format!("live:{delta_kind}"),
+                            target: path,
+                            before: String::new(),
+                            after: text,
+                            ..DiffSnapshot::default()
+                        }];
+                    }
+
+                    AgentEvent::EditComplete {
+                        path,
+                        changed,
+                        summary,
+                        ..
+                    } => {
+                        dirty = true;
+                        state.ui.active_edit_target = Some(path.clone());
+                        state.ui.last_tool_status = Some(summary.clone());
+                        log_tool_result(
+                            state,
+                            format!("{} {}", if changed { "edited" } else { "unchanged" }, path),
+                        );
+                    }
+
+                    AgentEvent::ValidationStart { command, .. } => {
+                        dirty = true;
+                        state.ui.run_phase = "validating".to_string();
+                        state.ui.run_detail = Some(command.clone());
+                        log_status(state, format!("validating: {command}"));
+                    }
+
+                    AgentEvent::ValidationComplete {
+                        command,
+                        passed,
+                        summary,
+                        ..
+                    } => {
+                        dirty = true;
+                        state.ui.run_phase = if passed {
+                            "validated".to_string()
+                        } else {
+                            "validation_failed".to_string()
+                        };
+                        state.ui.last_tool_status = Some(summary.clone());
+                        log_tool_result(
+                            state,
+                            format!(
+                                "{}: {command}",
+                                if passed {
+                                    "validation passed"
+                                } else {
+                                    "validation failed"
+                                }
+                            ),
+                        );
+                    }
+
+                    AgentEvent::ToolDiff {
+                        tool,
+                        target,
+                        before,
+                        after,
+                        created,
+                        deleted,
+                    } => {
+                        dirty = true;
+                        let snap = DiffSnapshot {
+                            tool,
+                            target,
+                            before,
+                            after,
+                            created,
+                            deleted,
+                        };
+
+                        if test_harness::is_manifest(&snap.target) {
+                            refresh_languages(state);
+                        }
+                        state.session_changes.push(snap.clone());
+                        state.undo_stack.push(snap.clone());
+                        state.ui.active_edit_target = Some(snap.target.clone());
+                        // The split pane already shows it with the rest.
+                        if !state.ui.split_diff {
+                            state.ui.diff_active = true;
+                            state.ui.diff_snapshot = vec![snap];
+                        }
+                        let _ = persistence::save(state);
+                    }
+
+                    AgentEvent::PreviewDiff {
+                        tool,
+                        target,
+                        before,
+                        after,
+                    } => {
+                        dirty = true;
+                        let snap = DiffSnapshot {
+                            tool: format!("preview:{tool}"),
+                            target,
+                            before,
+                            after,
+                            ..DiffSnapshot::default()
+                        };
+                        // Multi-file previews (rename_symbol) arrive back
+                        // to back, one event per file.
+                        let same_call = state.ui.diff_active
+                            && state
+                                .ui
+                                .diff_snapshot
+                                .iter()
+                                .all(|s| s.tool == snap.tool && s.target != snap.target);
+                        if !same_call {
+                            state.ui.diff_snapshot.clear();
+                        }
+                        state.ui.diff_active = true;
+                        state.ui.diff_snapshot.push(snap);
+                    }
+
+                    AgentEvent::OutputText(text) => {
+                        dirty = true;
+                        let spoken = log_final_output_once(state, &text) && state.voice.speak;
+                        if let Some(tx) = speech_cmd_tx.filter(|_| spoken) {
+                            let _ = tx.send(speech::SpeechCommand::Speak {
+                                text,
+                                api_key: speech_api_key(agent),
+                            });
+                        }
+                        let _ = persistence::save(state);
+                    }
+
+                    AgentEvent::StreamDelta(delta) => {
+                        dirty = true;
+                        state.ui.streaming_active = true;
+                        state.ui.follow_tail = true;
+                        state.ui.streaming_transcript.push_str(&delta);
+                        state.ui.streaming_buffer.push_str(&delta);
+                        update_streaming_log(state);
+                    }
+
+                    AgentEvent::StreamDone => {
+                        dirty = true;
+                        finish_streaming_output(state);
+                    }
+
+                    AgentEvent::RunStatus {
+                        phase,
+                        detail,
+                        iteration,
+                        max_iterations,
+                    } => {
+                        dirty = true;
+                        state.ui.run_phase = phase;
+                        state.ui.run_detail = Some(detail);
+                        state.ui.run_iteration = iteration;
+                        state.ui.run_iteration_limit = max_iterations;
+                    }
+
+                    AgentEvent::Subagent(status) => {
+                        dirty = true;
+                        let subagents = &mut state.ui.subagents;
+                        match subagents.iter_mut().find(|s| s.id == status.id) {
+                            Some(known) => *known = status,
+                            None => subagents.push(status),
+                        }
+                    }
+
+                    AgentEvent::Retrying(notice) => {
+                        dirty = true;
+                        let text = notice.to_string();
+                        state.ui.run_phase = "retrying".to_string();
+                        state.ui.run_detail = Some(text.clone());
+                        log(state, LogLevel::Warn, text);
+                    }
+
+                    AgentEvent::PermissionRequest {
+                        tool_name,
+                        args_summary,
+                        protected,
+                        always,
+                        reply_tx,
+                    } => {
+                        dirty = true;
+                        if state.ui.auto_approve && state.rebase.is_none() && protected.is_none() {
+                            let _ = reply_tx.send(true);
+                            log_status(
+                                state,
+                                format!("Auto-approved {} ({})", tool_name, args_summary),
+                            );
+                        } else {
+                            notify_if_away(
+                                state,
+                                &format!("{tool_name} needs permission ({args_summary})"),
+                            );
+                            state.ui.pending_permission =
+                                Some(osmogrep::state::PendingPermission {
+                                    tool_name,
+                                    args_summary,
+                                    protected,
+                                    always,
+                                    confirmed_once: false,
+                                    reply_tx,
+                                });
+                        }
+                    }
+
+                    AgentEvent::ToolsExposed {
+                        profile,
+                        exposed,
+                        dropped,
+                    } => {
+                        // Only worth a line when the profile trimmed the set.
+                        if !dropped.is_empty() {
+                            dirty = true;
+                            log_status(
+                                state,
+                                format!(
+                                    "Tool profile {profile}: {} tools, dropped {}",
+                                    exposed.len(),
+                                    dropped.join(", ")
+                                ),
+                            );
+                        }
+                    }
+
+                    AgentEvent::ChangeBudgetExceeded {
+                        summary,
+                        changes,
+                        reply_tx,
+                    } => {
+                        dirty = true;
+                        log(
+                            state,
+                            LogLevel::Warn,
+                            format!("Change budget exceeded: {summary}."),
+                        );
+                        state.ui.diff_active = true;
+                        state.ui.diff_snapshot = changes;
+                        state.ui.pending_budget =
+                            Some(osmogrep::state::PendingBudget { summary, reply_tx });
+                    }
+
+                    AgentEvent::ConversationUpdate(messages) => {
+                        dirty = true;
+                        state.conversation.set_messages(messages);
+                        state.conversation.trim_to_budget(MAX_CONVERSATION_TOKENS);
+                        let _ = persistence::save(state);
+                    }
+
+                    AgentEvent::Usage {
+                        provider,
+                        model,
+                        input_tokens,
+                        output_tokens,
+                        estimated,
+                        tools,
+                    } => {
+                        dirty = true;
+                        let price =
+                            ModelPrice::resolve(&provider, &model, &agent::pricing_overrides());
+                        state.usage.record(
+                            &format!("{provider}/{model}"),
+                            input_tokens,
+                            output_tokens,
+                            estimated,
+                            &tools,
+                            price,
+                        );
+                    }
+
+                    AgentEvent::Cancelled => {
+                        dirty = true;
+                        finish_streaming_output(state);
+                        log(state, LogLevel::Warn, "Agent cancelled.");
+                        state.ui.spinner_started_at = None;
+                        state.ui.agent_running = false;
+                        state.ui.run_phase = "cancelled".to_string();
+                        state.ui.current_tool = None;
+                        state.ui.current_tool_detail = None;
+                        state.ui.pending_permission = None;
+                        state.ui.active_edit_target = None;
+                        links.agent_cancel = None;
+                        links.agent_steer_tx = None;
+                        links.agent_rx = None;
+                        break;
+                    }
+
+                    // Listed under .context/runs; only headless runs print it.
+                    AgentEvent::Transcript(_) => {}
+
+                    AgentEvent::Checkpoint {
+                        messages,
+                        transient,
+                    } => {
+                        state.interrupted = Some(InterruptedRun {
+                            messages,
+                            error: String::new(),
+                            transient,
+                        });
+                    }
+
+                    AgentEvent::Error(e) => {
+                        dirty = true;
+                        notify_if_away(state, &format!("Run failed: {e}"));
+                        log(state, LogLevel::Error, e.clone());
+                        if let Some(run) = state.interrupted.as_mut() {
+                            run.error = e;
+                            let retry = run.transient
+                                && env_truthy("OSMOGREP_AUTO_CONTINUE", true)
+                                && !state.ui.auto_continued;
+                            if retry && commands::resume_interrupted(state).is_ok() {
+                                state.ui.auto_continued = true;
+                                log(
+                                    state,
+                                    LogLevel::Warn,
+                                    "Transient failure; continuing from the last model turn.",
+                                );
+                            } else {
+                                log(
+                                    state,
+                                    LogLevel::Info,
+                                    "/continue resumes the run from its last model turn.",
+                                );
+                            }
+                            let _ = persistence::save(state);
+                        }
+                        finish_streaming_output(state);
+                        state.ui.spinner_started_at = None;
+                        state.ui.agent_running = false;
+                        state.ui.run_phase = "error".to_string();
+                        state.ui.current_tool = None;
+                        state.ui.current_tool_detail = None;
+                        state.ui.pending_permission = None;
+                        state.ui.active_edit_target = None;
+                        links.agent_cancel = None;
+                        links.agent_steer_tx = None;
+                        links.agent_rx = None;
+                        break;
+                    }
+
+                    AgentEvent::Done => {
+                        dirty = true;
+                        notify_if_away(state, "Run finished");
+                        state.ui.spinner_started_at = None;
+                        state.ui.agent_running = false;
+                        state.ui.run_phase = "idle".to_string();
+                        state.ui.auto_continued = false;
+                        state.ui.current_tool = None;
+                        state.ui.current_tool_detail = None;
+                        state.ui.pending_permission = None;
+                        state.ui.active_edit_target = None;
+                        // Shell commands can add manifests too.
+                        refresh_languages(state);
+                        warn_if_verification_needed(state);
+                        queue_auto_review_if_needed(state);
+                        commands::continue_rebase_after_run(state);
+                        if !state.ui.agent_running
+                            && state.rebase.is_none()
+                            && state.session_changes.len() > state.ui.run_changes_from
+                        {
+                            commands::open_review(state, false);
+                        }
+                        if state.plan_mode && !state.plan_items.is_empty() {
+                            log(
+                                state,
+                                LogLevel::Info,
+                                "Plan ready: /plan edit, /plan files or /plan drop <n> to change it, /plan approve to execute it.",
+                            );
+                        }
+                        if state.auto_eval && !state.session_changes.is_empty() {
+                            let id = state.next_job_id;
+                            state.next_job_id += 1;
+                            state.jobs.push(osmogrep::state::JobRecord {
+                                id,
+                                kind: JobKind::Test,
+                                input: String::new(),
+                                status: JobStatus::Queued,
+                                output: None,
+                                run_id: None,
+                            });
+                            state.job_queue.push(osmogrep::state::JobRequest {
+                                id,
+                                kind: JobKind::Test,
+                                input: String::new(),
+                            });
+                            log(
+                                state,
+                                LogLevel::Info,
+                                format!("Auto-eval queued as job #{}", id),
+                            );
+                        }
+                        links.agent_cancel = None;
+                        links.agent_steer_tx = None;
+                        links.agent_rx = None;
+                        break;
+                    }
+                },
+
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    dirty = true;
+                    finish_streaming_output(state);
+                    state.ui.spinner_started_at = None;
+                    state.ui.agent_running = false;
+                    state.ui.run_phase = "disconnected".to_string();
+                    state.ui.current_tool = None;
+                    state.ui.current_tool_detail = None;
+                    state.ui.pending_permission = None;
+                    state.ui.active_edit_target = None;
+                    links.agent_cancel = None;
+                    links.agent_steer_tx = None;
+                    links.agent_rx = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    if links.agent_rx.is_none() {
+        if let Some(next_prompt) = state.ui.queued_agent_prompt.take() {
+            dirty = true;
+            log_user_input(state, &next_prompt);
+            start_agent_run(
+                state,
+                agent,
+                &next_prompt,
+                &mut links.agent_rx,
+                &mut links.agent_cancel,
+                &mut links.agent_steer_tx,
+            );
+        }
+    }
+    dirty
+}
+
+/// What a tab's session is wired to besides its state: the running agent,
+/// the context indexer and its background jobs.
+struct SessionLinks {
+    agent_rx: Option<mpsc::Receiver<AgentEvent>>,
+    agent_cancel: Option<CancelToken>,
+    agent_steer_tx: Option<mpsc::Sender<String>>,
+    context_rx: Option<mpsc::Receiver<ContextEvent>>,
+    job_tx: mpsc::Sender<JobEvent>,
+    job_rx: mpsc::Receiver<JobEvent>,
+    running_jobs: usize,
+}
+
+impl SessionLinks {
+    fn start(repo_root: &Path) -> Self {
+        let (context_tx, context_rx) = mpsc::channel();
+        context::spawn_indexer(repo_root.to_path_buf(), context_tx);
+        let (job_tx, job_rx) = mpsc::channel();
+        Self {
+            agent_rx: None,
+            agent_cancel: None,
+            agent_steer_tx: None,
+            context_rx: Some(context_rx),
+            job_tx,
+            job_rx,
+            running_jobs: 0,
+        }
+    }
+}
+
+/// A tab that is not in front. Its agent and jobs keep running and are
+/// pumped every tick like the front tab's; a permission prompt waits in
+/// its state until the tab is picked again.
+struct ParkedTab {
+    state: AgentState,
+    links: SessionLinks,
+}
+
+/// The tab's state on its own repo: the repo lock, the saved session and
+/// per-repo settings. A `sibling` tab already on the same repo keeps the
+/// lock and the saved session, so this one starts fresh and is not saved.
+fn open_session(
+    repo_root: PathBuf,
+    session_name: Option<String>,
+    sibling: Option<&AgentState>,
+) -> AgentState {
+    let mut state = init_state_at(repo_root);
+    let lock_owner = match sibling {
+        Some(sibling) => {
+            state.ui.scratch_tab = true;
+            if sibling.repo_lock.is_none() {
+                state.permission_profile = PermissionProfile::ReadOnly;
+            }
+            None
+        }
+        None => match instance::acquire(&state.repo_root) {
+            Ok(LockAttempt::Acquired(lock)) => {
+                state.repo_lock = Some(lock);
+                None
+            }
+            Ok(LockAttempt::Held(owner)) => {
+                instance::set_secondary(true);
+                Some(owner)
+            }
+            Err(e) => {
+                log(
+                    &mut state,
+                    LogLevel::Warn,
+                    format!("Repo lock unavailable: {e}"),
+                );
+                None
+            }
+        },
+    };
+    if sibling.is_none() {
+        persistence::load(&mut state);
+    }
+    if let Some(owner) = lock_owner {
+        state.permission_profile = PermissionProfile::ReadOnly;
+        state.ui.auto_approve = false;
+        log(
+            &mut state,
+            LogLevel::Warn,
+            format!(
+                "Another osmogrep instance owns this repo: {}",
+                owner.describe()
+            ),
+        );
+        log(
+            &mut state,
+            LogLevel::Info,
+            format!(
+                "Attached read-only as instance {}. Use /lock take to take over.",
+                instance::instance_id()
+            ),
+        );
+    }
+    if let Some(name) = session_name {
+        state.session_name = Some(name);
+        let _ = persistence::save(&state);
+    }
+
+    load_keymap(&mut state);
+    commands::load_custom_commands(&mut state);
+    state
+}
+
+/// Swaps tab `index` to the front. Voice input and terminal focus belong
+/// to the window, so they move with the front tab.
+fn show_tab(
+    index: usize,
+    state: &mut AgentState,
+    links: &mut SessionLinks,
+    tabs: &mut [Option<ParkedTab>],
+) {
+    let Some(front) = tabs.iter().position(Option::is_none) else {
+        return;
+    };
+    let Some(mut next) = tabs.get_mut(index).and_then(Option::take) else {
+        return;
+    };
+    std::mem::swap(&mut next.state.voice, &mut state.voice);
+    next.state.ui.focus_lost = state.ui.focus_lost;
+    std::mem::swap(state, &mut next.state);
+    std::mem::swap(links, &mut next.links);
+    // A parked tab is out of sight, so its prompts and results notify.
+    next.state.ui.focus_lost = true;
+    tabs[front] = Some(next);
+}
+
+fn apply_tab_request(
+    request: TabRequest,
+    state: &mut AgentState,
+    links: &mut SessionLinks,
+    tabs: &mut Vec<Option<ParkedTab>>,
+) {
+    let front = tabs.iter().position(Option::is_none).unwrap_or(0);
+    match request {
+        TabRequest::Switch(index) if index < tabs.len() => show_tab(index, state, links, tabs),
+        TabRequest::Switch(index) => log(
+            state,
+            LogLevel::Warn,
+            format!("There is no tab {}; /tab lists them.", index + 1),
+        ),
+        TabRequest::Open(dir) => {
+            if tabs.len() >= 9 {
+                log(
+                    state,
+                    LogLevel::Warn,
+                    "Nine tabs are open; /tab close one first.",
+                );
+                return;
+            }
+            let repo_root = dir.unwrap_or_else(|| state.repo_root.clone());
+            let sibling = std::iter::once(&*state)
+                .chain(tabs.iter().flatten().map(|tab| &tab.state))
+                .find(|other| other.repo_root == repo_root && !other.ui.scratch_tab);
+            let opened = open_session(repo_root, None, sibling);
+            let links_for_tab = SessionLinks::start(&opened.repo_root);
+            tabs.push(Some(ParkedTab {
+                state: opened,
+                links: links_for_tab,
+            }));
+            show_tab(tabs.len() - 1, state, links, tabs);
+            let title = tab_title(state);
+            log_status(state, format!("Opened tab {}: {title}", tabs.len()));
+        }
+        TabRequest::Close if tabs.len() == 1 => {
+            log(state, LogLevel::Warn, "This is the only tab; /quit exits.");
+        }
+        TabRequest::Close if links.agent_rx.is_some() || links.running_jobs > 0 => {
+            log(
+                state,
+                LogLevel::Warn,
+                "This tab has a run or jobs in progress; cancel them before closing it.",
+            );
+        }
+        TabRequest::Close => {
+            let _ = persistence::save(state);
+            let neighbour = if front == 0 { 1 } else { front - 1 };
+            show_tab(neighbour, state, links, tabs);
+            // The closed session is parked where it was; drop it.
+            tabs.remove(front);
+        }
+    }
+}
+
+/// The tab bar entry for a session: its name, else its repo directory.
+fn tab_title(state: &AgentState) -> String {
+    state.session_name.clone().unwrap_or_else(|| {
+        state
+            .repo_root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| state.repo_root.display().to_string())
+    })
+}
+
+fn refresh_tab_labels(state: &mut AgentState, tabs: &[Option<ParkedTab>]) {
+    let labels = tabs
+        .iter()
+        .map(|tab| match tab {
+            Some(tab) => TabLabel {
+                title: tab_title(&tab.state),
+                running: tab.state.ui.agent_running,
+                exit_blockers: tab.state.session_exit_blockers(),
+            },
+            None => TabLabel {
+                title: tab_title(state),
+                running: state.ui.agent_running,
+                exit_blockers: Vec::new(),
+            },
+        })
+        .collect();
+    state.ui.tabs = labels;
+    state.ui.active_tab = tabs.iter().position(Option::is_none).unwrap_or(0);
+}
+
 fn tui_live_activity(state: &AgentState, agent_active: bool, running_jobs: usize) -> bool {
     agent_active
         || running_jobs > 0
@@ -1998,24 +3361,84 @@ fn tui_live_activity(state: &AgentState, agent_active: bool, running_jobs: usize
             .is_some_and(|update| update.installing)
 }
 
-fn init_state() -> AgentState {
+/// Re-run framework detection after a manifest changed, so the header and
+/// test routing follow a repo that became polyglot.
+fn refresh_languages(state: &mut AgentState) {
+    let languages = test_harness::detect_languages(&state.repo_root);
+    if languages != state.ui.repo_languages {
+        log_status(
+            state,
+            format!("Detected languages: {}", languages.join(", ")),
+        );
+        state.ui.repo_languages = languages;
+    }
+}
+
+/// Speech always goes to OpenAI, whichever provider runs the agent.
+fn speech_api_key(agent: &Agent) -> Option<String> {
+    std::env::var("OPENAI_API_KEY")
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+        .or_else(|| {
+            agent
+                .api_key()
+                .filter(|_| agent.model_config().provider == "openai")
+        })
+}
+
+/// Runs a finished utterance through the wake-word gate. None when it is
+/// not meant for the input, including the wake phrase on its own.
+fn gate_voice_text(state: &mut AgentState, text: String) -> Option<String> {
+    let voice::VoiceActivation::WakeWord(phrase) = &state.voice.activation else {
+        return Some(text);
+    };
+    match voice::wake_gate(&text, phrase, state.voice.wake_armed()) {
+        voice::WakeGate::Drop => None,
+        voice::WakeGate::Arm => {
+            state.voice.wake_armed = Some(Instant::now());
+            None
+        }
+        voice::WakeGate::Pass(text) => {
+            state.voice.wake_armed = None;
+            Some(text)
+        }
+    }
+}
+
+fn init_state_at(repo_root: PathBuf) -> AgentState {
     let voice_url = std::env::var("VLLM_REALTIME_URL")
         .unwrap_or_else(|_| "ws://127.0.0.1:8000/v1/realtime".into());
     let voice_model = std::env::var("VLLM_REALTIME_MODEL")
         .unwrap_or_else(|_| "mistralai/Voxtral-Mini-4B-Realtime-2602".into());
-    let repo_root = std::env::current_dir().unwrap();
-    let mut ui = crate::state::UiState::default();
-    ui.repo_branch = crate::ui::helper::git_branch(&repo_root);
+    // A push-to-talk key wins over a wake word when both are set.
+    let ptt_key = std::env::var("VLLM_REALTIME_PTT_KEY")
+        .ok()
+        .and_then(|raw| voice::PttKey::parse(&raw));
+    let wake_word = std::env::var("VLLM_REALTIME_WAKE_WORD")
+        .ok()
+        .filter(|phrase| !phrase.trim().is_empty());
+    let voice_activation = match (ptt_key, wake_word) {
+        (Some(_), _) => voice::VoiceActivation::PushToTalk,
+        (None, Some(phrase)) => voice::VoiceActivation::WakeWord(phrase.trim().to_string()),
+        (None, None) => voice::VoiceActivation::Continuous,
+    };
+    let mut ui = osmogrep::state::UiState::default();
+    ui.repo_branch = osmogrep::ui::helper::git_branch(&repo_root);
+    ui.diff_baseline = working_diff::baseline_label(&repo_root);
+    ui.repo_languages = test_harness::detect_languages(&repo_root);
 
     AgentState {
         ui,
 
-        logs: crate::state::LogBuffer::new(),
+        logs: osmogrep::state::LogBuffer::new(),
         session_changes: Vec::new(),
         reviewed_change_count: 0,
         undo_stack: Vec::new(),
-        usage: crate::state::UsageStats::default(),
+        usage: osmogrep::state::UsageStats::default(),
         steer: None,
+        steer_history: Vec::new(),
+        run_budget: None,
+        interrupted: None,
         auto_eval: true,
         permission_profile: PermissionProfile::WorkspaceAuto,
         jobs: Vec::new(),
@@ -2023,18 +3446,25 @@ fn init_state() -> AgentState {
         next_job_id: 1,
         plan_items: Vec::new(),
         session_name: None,
-        theme: crate::state::UiTheme::default(),
-        accent: crate::state::UiAccent::default(),
-        density: crate::state::UiDensity::default(),
+        theme: osmogrep::state::UiTheme::default(),
+        accent: osmogrep::state::UiAccent::default(),
+        density: osmogrep::state::UiDensity::default(),
+        timestamps: osmogrep::state::LogTimestamps::default(),
         plan_mode: false,
+        plan_approved: false,
         started_at: Instant::now(),
         repo_root,
-        voice: crate::state::VoiceState {
+        repo_lock: None,
+        sandbox: None,
+        rebase: None,
+        voice: osmogrep::state::VoiceState {
             url: voice_url,
             model: voice_model,
-            ..crate::state::VoiceState::default()
+            activation: voice_activation,
+            ptt_key: ptt_key.unwrap_or_default(),
+            ..osmogrep::state::VoiceState::default()
         },
-        conversation: crate::state::ConversationHistory::new(),
+        conversation: osmogrep::state::ConversationHistory::new(),
     }
 }
 fn tool_target_path(name: &str, args: &serde_json::Value) -> Option<String> {
@@ -2107,6 +3537,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_positional_run_task() {
+        let cli = Cli::try_parse_from(["osmogrep", "run", "fix the build", "--json"]).unwrap();
+
+        match cli.command {
+            Some(CliCommand::Run(args)) => {
+                assert_eq!(args.task.as_deref(), Some("fix the build"));
+                assert!(args.json_events);
+            }
+            _ => panic!("expected run command"),
+        }
+        assert!(Cli::try_parse_from(["osmogrep", "run", "a", "--prompt", "b"]).is_err());
+    }
+
     #[test]
     fn normalizes_session_name() {
         assert_eq!(
@@ -2145,7 +3589,7 @@ mod tests {
 
     #[test]
     fn final_output_is_not_logged_twice_after_stream_flush() {
-        let mut state = init_state();
+        let mut state = init_state_at(std::env::current_dir().unwrap());
         state.ui.streaming_active = true;
         state.ui.streaming_buffer = "Hi. What do you want to work on?\n".to_string();
         state.ui.streaming_transcript = state.ui.streaming_buffer.clone();
@@ -2162,7 +3606,7 @@ mod tests {
 
     #[test]
     fn repeated_final_output_is_deduped_within_run() {
-        let mut state = init_state();
+        let mut state = init_state_at(std::env::current_dir().unwrap());
 
         assert!(log_final_output_once(&mut state, "same answer"));
         assert!(!log_final_output_once(&mut state, "same answer\n"));
@@ -2265,14 +3709,38 @@ mod tests {
         assert!(prompt.contains("User task:\nrefactor the parser"));
     }
 
+    #[test]
+    fn approved_plan_prompt_lists_steps_with_files() {
+        let step = |text: &str, done: bool, files: &[&str]| PlanItem {
+            text: text.to_string(),
+            done,
+            active: false,
+            files: files.iter().map(|f| f.to_string()).collect(),
+        };
+        let prompt = approved_plan_prompt(
+            "Execute the approved plan.",
+            &[
+                step("Inspect parser", true, &[]),
+                step("Patch parser", false, &["src/parser.rs"]),
+            ],
+        );
+
+        assert!(prompt.starts_with("APPROVED PLAN."));
+        assert!(
+            prompt.contains("1. [x] Inspect parser\n2. [ ] Patch parser (files: src/parser.rs)")
+        );
+        assert!(prompt.contains("edits elsewhere are refused"));
+    }
+
     #[test]
     fn serializes_plan_update_headless_event() {
         let event = headless_json_value(
             AgentEvent::PlanUpdate {
-                items: vec![crate::state::PlanItem {
+                items: vec![osmogrep::state::PlanItem {
                     text: "Inspect parser".to_string(),
                     done: false,
                     active: true,
+                    files: Vec::new(),
                 }],
             },
             false,
@@ -2302,6 +3770,7 @@ mod tests {
             target: target.to_string(),
             before: "before".to_string(),
             after: "after".to_string(),
+            ..DiffSnapshot::default()
         }
     }
 }
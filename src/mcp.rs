@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 use std::time::Duration;
 
 use serde::Deserialize;
@@ -72,10 +71,8 @@ pub fn call(server: Option<&str>, method: &str, args: &Value) -> Result<Value, S
 
     // Scaffold protocol: run configured command with method/args available as env vars.
     // This keeps integration simple while allowing external MCP bridges.
-    let mut cmd = Command::new("sh");
-    cmd.arg("-lc")
-        .arg(&scfg.cmd)
-        .env("OSMOGREP_MCP_SERVER", &target)
+    let mut cmd = crate::platform::login_shell_command(&scfg.cmd);
+    cmd.env("OSMOGREP_MCP_SERVER", &target)
         .env("OSMOGREP_MCP_METHOD", method)
         .env(
             "OSMOGREP_MCP_ARGS",
@@ -0,0 +1,284 @@
+//! mutation.rs
+//!
+//! Quick mutation pass that measures how strong a set of tests is. Inside
+//! the functions under test, tree-sitter finds comparison, boolean and
+//! arithmetic operators to swap and `if` conditions to negate (Rust and
+//! Python). Each mutant is applied in a sandbox worktree and the tests run
+//! once; a mutant the tests still pass on survived. The share of killed
+//! mutants is the test strength shown by `/testgen strength`.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Parser};
+
+use crate::{test_harness, worktree};
+
+/// Mutants tried per measurement; each costs one test run.
+pub const MAX_MUTANTS: usize = 12;
+
+const SWAPS: &[(&str, &str)] = &[
+    ("==", "!="),
+    ("!=", "=="),
+    ("<", ">="),
+    ("<=", ">"),
+    (">", "<="),
+    (">=", "<"),
+    ("&&", "||"),
+    ("||", "&&"),
+    ("and", "or"),
+    ("or", "and"),
+    ("+", "-"),
+    ("-", "+"),
+    ("*", "/"),
+];
+
+/// One source edit that should make a good test fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutant {
+    pub path: String,
+    pub line: usize,
+    /// What changed, e.g. `` `<` -> `>=` ``.
+    pub description: String,
+    start_byte: usize,
+    end_byte: usize,
+    replacement: String,
+}
+
+impl Mutant {
+    pub fn apply(&self, source: &str) -> String {
+        format!(
+            "{}{}{}",
+            &source[..self.start_byte],
+            self.replacement,
+            &source[self.end_byte..]
+        )
+    }
+}
+
+impl std::fmt::Display for Mutant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{} {}", self.path, self.line, self.description)
+    }
+}
+
+/// Killed and surviving mutants of one measurement.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Strength {
+    pub killed: usize,
+    pub total: usize,
+    /// Mutants the tests did not notice, as `path:line change`.
+    #[serde(default)]
+    pub survivors: Vec<String>,
+}
+
+impl Strength {
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.killed as f64 * 100.0 / self.total as f64
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "{:.0}% ({}/{} mutants killed)",
+            self.percent(),
+            self.killed,
+            self.total
+        )
+    }
+}
+
+/// Mutants of `source` (the file at `path`) between 1-based lines `start`
+/// and `end`, in source order. Files other than Rust and Python have none.
+pub fn mutants(path: &str, source: &str, start: usize, end: usize) -> Vec<Mutant> {
+    let (language, negate): (_, fn(&str) -> String) =
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => (tree_sitter_rust::language(), |c| format!("!({c})")),
+            Some("py") => (tree_sitter_python::language(), |c| format!("not ({c})")),
+            _ => return Vec::new(),
+        };
+    let mut parser = Parser::new();
+    if parser.set_language(&language).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    collect(
+        tree.root_node(),
+        source,
+        path,
+        (start, end),
+        negate,
+        &mut out,
+    );
+    out
+}
+
+fn collect(
+    node: Node,
+    source: &str,
+    path: &str,
+    lines: (usize, usize),
+    negate: fn(&str) -> String,
+    out: &mut Vec<Mutant>,
+) {
+    let line = node.start_position().row + 1;
+    if node.end_position().row + 1 < lines.0 || line > lines.1 {
+        return;
+    }
+    match node.kind() {
+        "binary_expression" | "binary_operator" | "boolean_operator" | "comparison_operator" => {
+            let mut cursor = node.walk();
+            for op in node.children(&mut cursor).filter(|c| !c.is_named()) {
+                if let Some((from, to)) = SWAPS.iter().find(|(from, _)| *from == op.kind()) {
+                    out.push(Mutant {
+                        path: path.to_string(),
+                        line: op.start_position().row + 1,
+                        description: format!("`{from}` -> `{to}`"),
+                        start_byte: op.start_byte(),
+                        end_byte: op.end_byte(),
+                        replacement: to.to_string(),
+                    });
+                }
+            }
+        }
+        "if_expression" | "if_statement" | "elif_clause" => {
+            // `if let` conditions are patterns, not booleans.
+            if let Some(condition) = node
+                .child_by_field_name("condition")
+                .filter(|c| !matches!(c.kind(), "let_condition" | "let_chain"))
+            {
+                let text = &source[condition.byte_range()];
+                out.push(Mutant {
+                    path: path.to_string(),
+                    line: condition.start_position().row + 1,
+                    description: format!("negate `{}`", text.lines().next().unwrap_or(text)),
+                    start_byte: condition.start_byte(),
+                    end_byte: condition.end_byte(),
+                    replacement: negate(text),
+                });
+            }
+        }
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, source, path, lines, negate, out);
+    }
+}
+
+/// Applies each mutant under `root` in turn, asks `passes` whether the
+/// tests still pass, and restores the file.
+pub fn score(
+    root: &Path,
+    mutants: &[Mutant],
+    mut passes: impl FnMut() -> Result<bool, String>,
+) -> Result<Strength, String> {
+    let mut strength = Strength::default();
+    for mutant in mutants {
+        let file = root.join(&mutant.path);
+        let original = fs::read_to_string(&file).map_err(|e| format!("{}: {e}", mutant.path))?;
+        fs::write(&file, mutant.apply(&original)).map_err(|e| format!("{}: {e}", mutant.path))?;
+        let passed = passes();
+        fs::write(&file, original).map_err(|e| format!("{}: {e}", mutant.path))?;
+        strength.total += 1;
+        if passed? {
+            strength.survivors.push(mutant.to_string());
+        } else {
+            strength.killed += 1;
+        }
+    }
+    Ok(strength)
+}
+
+/// Mutates the line ranges `targets` (`(path, start, end)`) and runs the
+/// tests selected by `test_target` against each mutant in a sandbox
+/// worktree, leaving the checkout untouched.
+pub fn measure(
+    repo_root: &Path,
+    targets: &[(String, usize, usize)],
+    test_target: Option<&str>,
+) -> Result<Strength, String> {
+    let mut all = Vec::new();
+    for (path, start, end) in targets {
+        let source =
+            fs::read_to_string(repo_root.join(path)).map_err(|e| format!("{path}: {e}"))?;
+        all.extend(mutants(path, &source, *start, *end));
+    }
+    all.truncate(MAX_MUTANTS);
+    if all.is_empty() {
+        return Err("no operators or conditions to mutate in the tested code".into());
+    }
+
+    let sandbox = worktree::create_sandbox(repo_root)?;
+    let result = (|| {
+        let baseline = test_harness::run_tests(&sandbox.path, test_target)?;
+        if !baseline.success {
+            return Err(format!(
+                "the tests fail before mutation ({} failed)",
+                baseline.failed
+            ));
+        }
+        score(&sandbox.path, &all, || {
+            test_harness::run_tests(&sandbox.path, test_target).map(|run| run.success)
+        })
+    })();
+    let _ = sandbox.remove(repo_root);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn swaps_operators_and_negates_conditions_within_the_range() {
+        let rust = "fn outside(a: i32) -> bool { a == 1 }\n\
+                    fn clamp(v: i32, max: i32) -> i32 {\n    if v > max && max >= 0 { max } else { v + 1 }\n}\n\
+                    fn pick(v: Option<i32>) -> i32 { if let Some(v) = v { v } else { 0 } }\n";
+        let found = mutants("src/lib.rs", rust, 2, 5);
+        let described: Vec<String> = found.iter().map(|m| m.to_string()).collect();
+        assert_eq!(
+            described,
+            [
+                "src/lib.rs:3 negate `v > max && max >= 0`",
+                "src/lib.rs:3 `&&` -> `||`",
+                "src/lib.rs:3 `>` -> `<=`",
+                "src/lib.rs:3 `>=` -> `<`",
+                "src/lib.rs:3 `+` -> `-`",
+            ]
+        );
+        assert!(found[0]
+            .apply(rust)
+            .contains("if !(v > max && max >= 0) { max }"));
+
+        let python = "def is_adult(age):\n    if age >= 18 and age < 130:\n        return True\n    return False\n";
+        let found = mutants("app.py", python, 1, 4);
+        assert_eq!(found.len(), 4);
+        assert!(found[0]
+            .apply(python)
+            .contains("if not (age >= 18 and age < 130):"));
+        assert!(mutants("app.js", "if (a > b) {}", 1, 1).is_empty());
+
+        let root = std::env::temp_dir().join(format!("osmogrep-mutation-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("app.py"), python).unwrap();
+        let mut runs = 0;
+        let strength = score(&root, &found, || {
+            runs += 1;
+            let text = fs::read_to_string(root.join("app.py")).unwrap();
+            Ok(text.contains("age < 130"))
+        })
+        .unwrap();
+        assert_eq!(runs, 4);
+        assert_eq!((strength.killed, strength.total), (1, 4));
+        assert_eq!(strength.label(), "25% (1/4 mutants killed)");
+        assert_eq!(fs::read_to_string(root.join("app.py")).unwrap(), python);
+        let _ = fs::remove_dir_all(root);
+    }
+}
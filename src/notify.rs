@@ -0,0 +1,105 @@
+//! notify.rs
+//!
+//! Terminal notifications for a run that finishes, fails or waits on a
+//! permission prompt while the terminal is in the background. They are an
+//! OSC 9 desktop notification (iTerm2, kitty, WezTerm, Windows Terminal,
+//! foot) followed by a bell, which most other terminals turn into an
+//! urgency hint. Set in the `[notifications]` section of the config file:
+//!
+//! ```toml
+//! [notifications]
+//! enabled = true
+//! bell = true
+//! ```
+//!
+//! Focus comes from the terminal's focus reports; a terminal that does not
+//! send them counts as focused, so it never notifies.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Characters of the message kept in a notification.
+const MESSAGE_LIMIT: usize = 200;
+
+/// `[notifications]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub enabled: bool,
+    /// Ring the bell after the OSC 9 notification.
+    pub bell: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bell: true,
+        }
+    }
+}
+
+/// The escape sequence notifying `message`; empty when disabled.
+pub fn sequence(cfg: &NotificationsConfig, message: &str) -> String {
+    if !cfg.enabled {
+        return String::new();
+    }
+    let mut text: String = message
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MESSAGE_LIMIT)
+        .collect();
+    if message.chars().count() > MESSAGE_LIMIT {
+        text.push('…');
+    }
+    let mut out = format!("\x1b]9;osmogrep: {}\x07", text.trim());
+    if cfg.bell {
+        out.push('\x07');
+    }
+    out
+}
+
+/// Writes the notification to the controlling terminal.
+pub fn send(cfg: &NotificationsConfig, message: &str) {
+    let sequence = sequence(cfg, message);
+    if sequence.is_empty() {
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        if let Ok(mut tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+            let _ = tty.write_all(sequence.as_bytes()).and_then(|_| tty.flush());
+            return;
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    let _ = stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_is_one_line_osc9_with_an_optional_bell() {
+        let cfg = NotificationsConfig::default();
+        assert_eq!(
+            sequence(&cfg, "Run failed:\nboom\x1b[0m"),
+            "\x1b]9;osmogrep: Run failed: boom [0m\x07\x07"
+        );
+        let quiet = NotificationsConfig { bell: false, ..cfg };
+        assert_eq!(sequence(&quiet, "done"), "\x1b]9;osmogrep: done\x07");
+        let off = NotificationsConfig {
+            enabled: false,
+            ..cfg
+        };
+        assert!(sequence(&off, "done").is_empty());
+        let long = "x".repeat(MESSAGE_LIMIT + 10);
+        assert!(sequence(&quiet, &long).contains(&format!("{}…", "x".repeat(MESSAGE_LIMIT))));
+    }
+}
@@ -0,0 +1,516 @@
+//! onboard.rs
+//!
+//! A "getting started" report for an unfamiliar repository: entry points,
+//! how to run the tests, risky modules, conventions inferred from the
+//! existing tests, a dependency audit and a TODO scan. Built from the
+//! context index plus a single walk of the tree.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::context::indexer::{self, RepoStats};
+use crate::{test_harness, testgen};
+
+/// Export path used when none is given.
+pub const DEFAULT_EXPORT: &str = ".osmogrep/onboarding.md";
+
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "rb", "c", "h", "cc", "cpp", "hpp",
+    "cs", "swift",
+];
+const TODO_MARKERS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+const TOP: usize = 8;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub root: PathBuf,
+    pub index: RepoStats,
+    pub languages: Vec<String>,
+    pub manifests: Vec<String>,
+    pub entry_points: Vec<String>,
+    pub test_commands: Vec<String>,
+    pub test_files: usize,
+    pub conventions: Vec<String>,
+    pub risky_modules: Vec<RiskyModule>,
+    pub dependencies: usize,
+    pub dependency_findings: Vec<String>,
+    pub todo_total: usize,
+    pub todos: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskyModule {
+    pub path: String,
+    pub lines: usize,
+    pub todos: usize,
+    pub callers: usize,
+}
+
+struct SourceFile {
+    path: String,
+    lines: usize,
+    todos: usize,
+    text: String,
+}
+
+pub fn build(repo_root: &Path) -> Report {
+    let ctx = indexer::load_or_build(repo_root);
+    let sources = scan_sources(repo_root);
+
+    // Fan-in per file from the index call graph.
+    let mut callers: HashMap<String, usize> = HashMap::new();
+    for symbol in &ctx.symbols {
+        let file = relative(repo_root, Path::new(&symbol.file));
+        *callers.entry(file).or_default() += symbol.called_by.len();
+    }
+
+    let tests: Vec<&SourceFile> = sources
+        .iter()
+        .filter(|file| testgen::is_test_path(&file.path) || has_inline_tests(file))
+        .collect();
+
+    // Rust files with inline tests are still code.
+    let mut risky_modules: Vec<RiskyModule> = sources
+        .iter()
+        .filter(|file| !testgen::is_test_path(&file.path))
+        .map(|file| RiskyModule {
+            path: file.path.clone(),
+            lines: file.lines,
+            todos: file.todos,
+            callers: callers.get(&file.path).copied().unwrap_or(0),
+        })
+        .filter(|module| module.lines >= 200 || module.todos > 0)
+        .collect();
+    risky_modules.sort_by_key(|m| std::cmp::Reverse(m.lines + 50 * m.todos + 10 * m.callers));
+    risky_modules.truncate(TOP / 2 + 1);
+
+    let mut todos: Vec<(String, usize)> = sources
+        .iter()
+        .filter(|file| file.todos > 0)
+        .map(|file| (file.path.clone(), file.todos))
+        .collect();
+    todos.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let todo_total = todos.iter().map(|(_, n)| n).sum();
+    todos.truncate(TOP);
+
+    let (dependencies, dependency_findings) = audit_dependencies(repo_root);
+    let paths: Vec<&str> = sources.iter().map(|file| file.path.as_str()).collect();
+
+    Report {
+        root: repo_root.to_path_buf(),
+        index: ctx.stats,
+        languages: test_harness::detect_languages(repo_root),
+        manifests: crate::agent::detect_manifests(repo_root),
+        entry_points: entry_points(repo_root, &paths),
+        test_commands: crate::agent::detect_verify_commands(repo_root),
+        test_files: tests.len(),
+        conventions: conventions(&tests),
+        risky_modules,
+        dependencies,
+        dependency_findings,
+        todo_total,
+        todos,
+    }
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn scan_sources(root: &Path) -> Vec<SourceFile> {
+    let mut files: Vec<SourceFile> = WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file() && !indexer::should_ignore(e.path()))
+        .filter(|e| {
+            e.path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext))
+        })
+        .filter_map(|e| {
+            let text = fs::read_to_string(e.path()).ok()?;
+            Some(SourceFile {
+                path: relative(root, e.path()),
+                lines: text.lines().count(),
+                todos: count_todos(&text),
+                text,
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn count_todos(text: &str) -> usize {
+    text.lines()
+        .filter(|line| {
+            TODO_MARKERS.iter().any(|marker| {
+                line.match_indices(marker).any(|(at, _)| {
+                    let before = line[..at].chars().next_back();
+                    let after = line[at + marker.len()..].chars().next();
+                    !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                        && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                })
+            })
+        })
+        .count()
+}
+
+fn has_inline_tests(file: &SourceFile) -> bool {
+    file.path.ends_with(".rs") && file.text.contains("#[cfg(test)]")
+}
+
+fn entry_points(root: &Path, paths: &[&str]) -> Vec<String> {
+    let mut entries: Vec<String> = paths
+        .iter()
+        .filter(|path| {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            matches!(
+                name,
+                "main.rs" | "lib.rs" | "main.py" | "__main__.py" | "manage.py" | "main.go"
+            ) || path.starts_with("src/bin/")
+        })
+        .map(|path| path.to_string())
+        .collect();
+
+    if let Ok(text) = fs::read_to_string(root.join("package.json")) {
+        if let Ok(pkg) = serde_json::from_str::<Value>(&text) {
+            if let Some(main) = pkg.get("main").and_then(Value::as_str) {
+                entries.push(format!("{main} (package.json main)"));
+            }
+            match pkg.get("bin") {
+                Some(Value::String(bin)) => entries.push(format!("{bin} (package.json bin)")),
+                Some(Value::Object(bins)) => entries.extend(
+                    bins.iter()
+                        .filter_map(|(name, path)| Some(format!("{} ({name})", path.as_str()?))),
+                ),
+                _ => {}
+            }
+        }
+    }
+    if let Some(scripts) = read_toml(&root.join("pyproject.toml"))
+        .as_ref()
+        .and_then(|doc| doc.get("project")?.get("scripts")?.as_table().cloned())
+    {
+        entries.extend(
+            scripts
+                .iter()
+                .filter_map(|(name, target)| Some(format!("{} ({name})", target.as_str()?))),
+        );
+    }
+    entries.dedup();
+    entries
+}
+
+fn conventions(tests: &[&SourceFile]) -> Vec<String> {
+    let count = |pred: &dyn Fn(&SourceFile) -> bool| tests.iter().filter(|f| pred(f)).count();
+    let mut out = Vec::new();
+
+    let inline = count(&|f| has_inline_tests(f));
+    if inline > 0 {
+        out.push(format!(
+            "Rust unit tests live in-file in `#[cfg(test)] mod tests` ({inline} files)."
+        ));
+    }
+    let integration = count(&|f| f.path.starts_with("tests/") && f.path.ends_with(".rs"));
+    if integration > 0 {
+        out.push(format!(
+            "Rust integration tests live under tests/ ({integration} files)."
+        ));
+    }
+    if count(&|f| f.text.contains("insta::assert")) > 0 {
+        out.push("Snapshot tests use insta; review changes with `cargo insta review`.".into());
+    }
+    let pytest = count(&|f| f.path.ends_with(".py"));
+    if pytest > 0 {
+        let unittest = count(&|f| f.path.ends_with(".py") && f.text.contains("unittest.TestCase"));
+        let style = if unittest * 2 > pytest {
+            "unittest.TestCase classes"
+        } else {
+            "plain pytest functions"
+        };
+        out.push(format!("Python tests ({pytest} files) are {style}."));
+        if count(&|f| f.path.ends_with("conftest.py")) > 0 {
+            out.push("Shared pytest fixtures live in conftest.py.".into());
+        }
+    }
+    let spec = count(&|f| f.path.contains(".spec."));
+    let test = count(&|f| f.path.contains(".test."));
+    if spec + test > 0 {
+        let suffix = if spec > test { ".spec" } else { ".test" };
+        out.push(format!(
+            "JavaScript/TypeScript tests use the `{suffix}.*` suffix."
+        ));
+    }
+    let go = count(&|f| f.path.ends_with("_test.go"));
+    if go > 0 {
+        out.push(format!(
+            "Go tests sit next to the code in *_test.go ({go} files)."
+        ));
+    }
+
+    let names: Vec<String> = tests
+        .iter()
+        .flat_map(|f| testgen::test_symbols(&f.text))
+        .collect();
+    if !names.is_empty() {
+        let prefixed = names
+            .iter()
+            .filter(|n| n.starts_with("test_") || n.starts_with("Test"))
+            .count();
+        out.push(if prefixed * 2 > names.len() {
+            format!(
+                "Test names are prefixed (`test_…`), {} tests seen.",
+                names.len()
+            )
+        } else {
+            format!(
+                "Test names describe behaviour without a `test_` prefix, {} tests seen.",
+                names.len()
+            )
+        });
+    }
+    out
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Total declared dependencies and the ones worth a second look.
+fn audit_dependencies(root: &Path) -> (usize, Vec<String>) {
+    let mut total = 0;
+    let mut findings = Vec::new();
+
+    if let Some(cargo) = read_toml(&root.join("Cargo.toml")) {
+        for section in ["dependencies", "dev-dependencies", "build-dependencies"] {
+            let Some(deps) = cargo.get(section).and_then(toml::Value::as_table) else {
+                continue;
+            };
+            for (name, spec) in deps {
+                total += 1;
+                let field = |key: &str| spec.get(key).and_then(toml::Value::as_str);
+                let version = spec.as_str().or(field("version"));
+                if field("git").is_some() && field("rev").is_none() && field("tag").is_none() {
+                    findings.push(format!("Cargo.toml {name}: git dependency without rev/tag"));
+                } else if field("path").is_some() {
+                    findings.push(format!("Cargo.toml {name}: path dependency"));
+                } else if version == Some("*") {
+                    findings.push(format!("Cargo.toml {name}: wildcard version"));
+                }
+            }
+        }
+    }
+
+    if let Some(pkg) = fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+    {
+        for section in ["dependencies", "devDependencies"] {
+            let Some(deps) = pkg.get(section).and_then(Value::as_object) else {
+                continue;
+            };
+            for (name, spec) in deps {
+                total += 1;
+                let spec = spec.as_str().unwrap_or_default();
+                if spec == "*" || spec == "latest" {
+                    findings.push(format!("package.json {name}: unpinned ({spec})"));
+                } else if ["git", "github:", "http"]
+                    .iter()
+                    .any(|p| spec.starts_with(p))
+                {
+                    findings.push(format!("package.json {name}: fetched from {spec}"));
+                } else if spec.starts_with("file:") {
+                    findings.push(format!("package.json {name}: local {spec}"));
+                }
+            }
+        }
+    }
+
+    let mut requirements: Vec<(String, String)> = read_toml(&root.join("pyproject.toml"))
+        .as_ref()
+        .and_then(|doc| doc.get("project")?.get("dependencies")?.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|dep| Some(("pyproject.toml".to_string(), dep.as_str()?.to_string())))
+        .collect();
+    if let Ok(text) = fs::read_to_string(root.join("requirements.txt")) {
+        requirements.extend(
+            text.lines()
+                .map(|line| line.split('#').next().unwrap_or("").trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('-'))
+                .map(|line| ("requirements.txt".to_string(), line.to_string())),
+        );
+    }
+    for (file, dep) in requirements {
+        total += 1;
+        if dep.contains("git+") {
+            findings.push(format!("{file} {dep}: fetched from git"));
+        } else if !dep.contains(['=', '<', '>', '~', '!']) {
+            findings.push(format!("{file} {dep}: no version constraint"));
+        }
+    }
+
+    (total, findings)
+}
+
+/// Markdown rendering, used both for the TUI log and for export.
+pub fn render(report: &Report) -> String {
+    let mut out = vec![format!("# Getting started: {}", report.root.display())];
+    let bullets = |out: &mut Vec<String>, title: &str, items: &[String], empty: &str| {
+        out.push(String::new());
+        out.push(format!("## {title}"));
+        if items.is_empty() {
+            out.push(format!("- {empty}"));
+        }
+        out.extend(items.iter().map(|item| format!("- {item}")));
+    };
+
+    let languages = if report.languages.is_empty() {
+        "unknown".to_string()
+    } else {
+        report.languages.join(", ")
+    };
+    bullets(
+        &mut out,
+        "Overview",
+        &[
+            format!("Languages: {languages}"),
+            format!("Manifests: {}", report.manifests.join(", ")),
+            format!(
+                "Indexed: {} files, {} lines",
+                report.index.file_count, report.index.total_lines
+            ),
+        ],
+        "",
+    );
+    bullets(&mut out, "Entry points", &report.entry_points, "none found");
+
+    let mut testing: Vec<String> = report
+        .test_commands
+        .iter()
+        .map(|cmd| format!("`{cmd}`"))
+        .collect();
+    testing.push(format!("{} test files", report.test_files));
+    bullets(&mut out, "Running tests", &testing, "");
+    bullets(
+        &mut out,
+        "Conventions from existing tests",
+        &report.conventions,
+        "no tests found",
+    );
+
+    let risky: Vec<String> = report
+        .risky_modules
+        .iter()
+        .map(|m| {
+            format!(
+                "{}: {} lines, {} TODOs, {} callers",
+                m.path, m.lines, m.todos, m.callers
+            )
+        })
+        .collect();
+    bullets(&mut out, "Risky modules", &risky, "none stand out");
+
+    let mut deps = vec![format!("{} declared", report.dependencies)];
+    deps.extend(report.dependency_findings.iter().cloned());
+    bullets(&mut out, "Dependencies", &deps, "");
+
+    let mut todos = vec![format!(
+        "{} markers (TODO/FIXME/HACK/XXX)",
+        report.todo_total
+    )];
+    todos.extend(
+        report
+            .todos
+            .iter()
+            .map(|(path, count)| format!("{path}: {count}")),
+    );
+    bullets(&mut out, "TODOs", &todos, "");
+
+    out.join("\n") + "\n"
+}
+
+/// Write the rendered report to `path` (relative to the repo root).
+pub fn export(report: &Report, path: &Path) -> Result<PathBuf, String> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        report.root.join(path)
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, render(report)).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn reports_entry_points_tests_dependencies_and_todos() {
+        let root = std::env::temp_dir().join(format!("osmogrep-onboard-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1\"\n\
+             anyhow = \"*\"\nforked = { git = \"https://example.com/forked\" }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "// TODO: split this up\nfn main() {}\n\n#[cfg(test)]\nmod tests {\n    \
+             #[test]\n    fn parses_args() {}\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/util.rs"),
+            "// FIXME(io) TODOS are not TODOs\n",
+        )
+        .unwrap();
+        fs::write(root.join("tests/cli.rs"), "#[test]\nfn runs() {}\n").unwrap();
+
+        let report = build(&root);
+        assert_eq!(report.languages, vec!["rust"]);
+        assert_eq!(report.entry_points, vec!["src/main.rs"]);
+        assert_eq!(report.test_files, 2);
+        assert_eq!(report.dependencies, 3);
+        assert_eq!(
+            report.dependency_findings,
+            vec![
+                "Cargo.toml anyhow: wildcard version",
+                "Cargo.toml forked: git dependency without rev/tag",
+            ]
+        );
+        assert_eq!(report.todo_total, 2);
+        let risky: Vec<&str> = report
+            .risky_modules
+            .iter()
+            .map(|m| m.path.as_str())
+            .collect();
+        assert_eq!(risky, vec!["src/main.rs", "src/util.rs"]);
+        assert!(report.conventions[0].contains("in-file"));
+        assert!(report.conventions[1].contains("tests/"));
+
+        let text = render(&report);
+        assert!(text.contains("## Running tests\n- `cargo test --color never`"));
+        let path = export(&report, Path::new(DEFAULT_EXPORT)).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), text);
+        let _ = fs::remove_dir_all(root);
+    }
+}
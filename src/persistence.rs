@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
@@ -6,9 +7,13 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sha2::{Digest, Sha256};
 
+use crate::instance;
+use crate::logger::parse_user_input_log;
 use crate::state::{
-    AgentState, DiffSnapshot, JobRecord, PermissionProfile, PlanItem, UiAccent, UiDensity, UiTheme,
+    AgentState, DiffSnapshot, InterruptedRun, JobRecord, JobStatus, LogBuffer, LogTimestamps,
+    ModelUsage, PermissionProfile, PlanItem, ToolUsage, UiAccent, UiDensity, UiTheme,
 };
+use crate::timefmt;
 
 #[derive(Serialize, Deserialize)]
 struct PersistedState {
@@ -37,6 +42,10 @@ struct PersistedState {
     #[serde(default)]
     completion_tokens: usize,
     #[serde(default)]
+    usage_by_model: BTreeMap<String, ModelUsage>,
+    #[serde(default)]
+    usage_by_tool: BTreeMap<String, ToolUsage>,
+    #[serde(default)]
     session_name: Option<String>,
     #[serde(default)]
     theme: UiTheme,
@@ -45,11 +54,23 @@ struct PersistedState {
     #[serde(default)]
     density: UiDensity,
     #[serde(default)]
+    timestamps: LogTimestamps,
+    #[serde(default)]
     plan_mode: bool,
+    #[serde(default)]
+    plan_approved: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interrupted: Option<InterruptedRun>,
 }
 
 pub fn load(state: &mut AgentState) {
-    let path = state_file(&state.repo_root);
+    // A secondary instance starts from the lock owner's session.
+    let own = state_file(&state.repo_root);
+    let path = if own.exists() {
+        own
+    } else {
+        session_dir().join(format!("{}.json", repo_hash(&state.repo_root)))
+    };
     let Ok(raw) = fs::read_to_string(path) else {
         return;
     };
@@ -64,6 +85,12 @@ pub fn load(state: &mut AgentState) {
     state.auto_eval = saved.auto_eval;
     state.permission_profile = saved.permission_profile;
     state.jobs = saved.jobs;
+    // Job threads do not survive a restart.
+    for job in &mut state.jobs {
+        if matches!(job.status, JobStatus::Queued | JobStatus::Running) {
+            job.status = JobStatus::Cancelled;
+        }
+    }
     state.next_job_id = saved.next_job_id.max(1);
     state.plan_items = saved.plan_items;
     state.session_changes = saved.session_changes;
@@ -71,14 +98,22 @@ pub fn load(state: &mut AgentState) {
     state.undo_stack = saved.undo_stack;
     state.usage.prompt_tokens = saved.prompt_tokens;
     state.usage.completion_tokens = saved.completion_tokens;
+    state.usage.by_model = saved.usage_by_model;
+    state.usage.by_tool = saved.usage_by_tool;
     state.session_name = saved.session_name;
     state.theme = saved.theme;
     state.accent = saved.accent;
     state.density = saved.density;
+    state.timestamps = saved.timestamps;
     state.plan_mode = saved.plan_mode;
+    state.plan_approved = saved.plan_approved;
+    state.interrupted = saved.interrupted;
 }
 
 pub fn save(state: &AgentState) -> Result<(), String> {
+    if state.ui.scratch_tab {
+        return Ok(());
+    }
     let path = state_file(&state.repo_root);
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
@@ -97,15 +132,38 @@ pub fn save(state: &AgentState) -> Result<(), String> {
         undo_stack: state.undo_stack.clone(),
         prompt_tokens: state.usage.prompt_tokens,
         completion_tokens: state.usage.completion_tokens,
+        usage_by_model: state.usage.by_model.clone(),
+        usage_by_tool: state.usage.by_tool.clone(),
         session_name: state.session_name.clone(),
         theme: state.theme,
         accent: state.accent,
         density: state.density,
+        timestamps: state.timestamps,
         plan_mode: state.plan_mode,
+        plan_approved: state.plan_approved,
+        interrupted: state.interrupted.clone(),
     };
 
     let text = serde_json::to_string_pretty(&payload).map_err(|e| e.to_string())?;
-    fs::write(path, text).map_err(|e| e.to_string())
+    fs::write(&path, text).map_err(|e| e.to_string())?;
+    fs::write(path.with_extension("log"), transcript(&state.logs)).map_err(|e| e.to_string())
+}
+
+/// The session log with precise timestamps, whatever the display setting.
+fn transcript(logs: &LogBuffer) -> String {
+    logs.iter()
+        .map(|line| {
+            let text = parse_user_input_log(&line.text)
+                .map(|input| format!("> {input}"))
+                .unwrap_or_else(|| line.text.clone());
+            format!(
+                "{} {:<5} {}\n",
+                timefmt::precise(&line.wall),
+                line.level.as_str(),
+                text
+            )
+        })
+        .collect()
 }
 
 pub struct SessionSummary {
@@ -170,11 +228,13 @@ fn default_auto_eval() -> bool {
 }
 
 fn state_file(repo_root: &Path) -> PathBuf {
+    session_dir().join(instance::artifact_name(&repo_hash(repo_root), "json"))
+}
+
+fn repo_hash(repo_root: &Path) -> String {
     let mut hasher = Sha256::new();
     hasher.update(repo_root.to_string_lossy().as_bytes());
-    let hash = format!("{:x}", hasher.finalize());
-
-    session_dir().join(format!("{}.json", hash))
+    format!("{:x}", hasher.finalize())
 }
 
 pub fn config_dir() -> PathBuf {
@@ -189,10 +249,26 @@ pub fn session_dir() -> PathBuf {
 
 #[cfg(test)]
 mod tests {
-    use super::list_sessions_in;
+    use super::{list_sessions_in, transcript};
+    use crate::state::{LogBuffer, LogLevel};
     use std::fs;
     use uuid::Uuid;
 
+    #[test]
+    fn transcript_lines_carry_precise_timestamps() {
+        let mut logs = LogBuffer::new();
+        logs.push(LogLevel::Info, "USER|fix the parser");
+        logs.push(LogLevel::Error, "build failed");
+
+        let text = transcript(&logs);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let (stamp, rest) = lines[0].split_once(' ').unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(stamp).is_ok());
+        assert_eq!(rest, "info  > fix the parser");
+        assert!(lines[1].ends_with(" error build failed"));
+    }
+
     #[test]
     fn lists_saved_session_summaries() {
         let dir = std::env::temp_dir().join(format!("osmogrep-sessions-{}", Uuid::new_v4()));
@@ -0,0 +1,136 @@
+//! piped_input.rs
+//!
+//! Text piped into osmogrep, as in `cat build.log | osmogrep run "explain
+//! this failure"`. When stdin is not a terminal it is read once at startup
+//! and rides along with the next prompt as a fenced document under an
+//! `[attached: stdin ...]` marker. Input longer than `MAX_CHARS` keeps its
+//! head and tail, where logs usually say what went wrong, and notes how
+//! much was cut from the middle.
+
+use std::io::{self, IsTerminal, Read};
+
+use crate::platform;
+
+/// Characters of piped input sent to the model.
+pub const MAX_CHARS: usize = 60_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipedInput {
+    /// What is sent, already truncated.
+    pub text: String,
+    /// Lines and characters of the whole input.
+    pub lines: usize,
+    pub chars: usize,
+}
+
+impl PipedInput {
+    /// Reads stdin to the end; `None` when it is a terminal or sends
+    /// nothing but whitespace.
+    pub fn read_stdin() -> Result<Option<Self>, String> {
+        let mut stdin = io::stdin();
+        if stdin.is_terminal() {
+            return Ok(None);
+        }
+        let mut bytes = Vec::new();
+        stdin
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("could not read stdin: {e}"))?;
+        Ok(Self::from_text(&platform::decode_text(&bytes)))
+    }
+
+    pub fn from_text(raw: &str) -> Option<Self> {
+        if raw.trim().is_empty() {
+            return None;
+        }
+        let chars = raw.chars().count();
+        let text = if chars <= MAX_CHARS {
+            raw.to_string()
+        } else {
+            let keep = MAX_CHARS / 2;
+            let head: String = raw.chars().take(keep).collect();
+            let tail: String = raw.chars().skip(chars - keep).collect();
+            format!(
+                "{head}\n... [{} characters omitted] ...\n{tail}",
+                chars - 2 * keep
+            )
+        };
+        Some(Self {
+            text,
+            lines: raw.lines().count(),
+            chars,
+        })
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.chars > MAX_CHARS
+    }
+
+    /// `stdin, 120 lines`
+    pub fn label(&self) -> String {
+        let mut label = format!(
+            "stdin, {} line{}",
+            self.lines,
+            if self.lines == 1 { "" } else { "s" }
+        );
+        if self.truncated() {
+            label.push_str(", truncated");
+        }
+        label
+    }
+
+    /// `prompt` followed by the attachment marker and the fenced input.
+    pub fn attach_to(&self, prompt: &str) -> String {
+        let mut marker = format!("[attached: {}", self.label());
+        if self.truncated() {
+            marker.push_str(&format!(
+                " to the first and last {} of {} characters",
+                MAX_CHARS / 2,
+                self.chars
+            ));
+        }
+        marker.push(']');
+        let fence = "`".repeat(longest_backtick_run(&self.text).max(2) + 1);
+        format!(
+            "{prompt}\n\n{marker}\n{fence}text\n{}\n{fence}",
+            self.text.trim_end()
+        )
+    }
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attaches_input_under_a_marker_and_keeps_head_and_tail() {
+        assert!(PipedInput::from_text(" \n\t").is_none());
+
+        let piped = PipedInput::from_text("error: boom\n```rust\nfn main() {}\n```\n").unwrap();
+        assert_eq!(piped.label(), "stdin, 4 lines");
+        assert_eq!(
+            piped.attach_to("explain this failure"),
+            "explain this failure\n\n[attached: stdin, 4 lines]\n````text\nerror: boom\n\
+             ```rust\nfn main() {}\n```\n````"
+        );
+
+        let long = format!("{}{}", "a".repeat(MAX_CHARS), "z".repeat(10));
+        let piped = PipedInput::from_text(&long).unwrap();
+        assert!(piped.truncated());
+        assert!(piped.text.starts_with(&"a".repeat(MAX_CHARS / 2)));
+        assert!(piped.text.ends_with(&format!(
+            "{}{}",
+            "a".repeat(MAX_CHARS / 2 - 10),
+            "z".repeat(10)
+        )));
+        assert!(piped.text.contains("\n... [10 characters omitted] ...\n"));
+        assert!(piped.attach_to("why").contains(&format!(
+            "[attached: stdin, 1 line, truncated to the first and last {} of {} characters]",
+            MAX_CHARS / 2,
+            MAX_CHARS + 10
+        )));
+    }
+}
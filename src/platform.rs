@@ -0,0 +1,232 @@
+//! platform.rs
+//!
+//! What differs between Unix and Windows when osmogrep runs programs:
+//! one-off commands go through `sh -c` or `cmd /C`, and `run_shell`'s
+//! persistent session is bash (or sh) or PowerShell, each with its own way
+//! of sourcing a script and reporting the exit code and directory after it.
+//! Programs are looked up on `PATH`, with `PATHEXT` on Windows.
+
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Unix,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Unix
+        }
+    }
+
+    /// `sh -c <script>` or `cmd /C <script>`.
+    pub fn shell_argv(self, script: &str) -> Vec<String> {
+        let (shell, flag) = match self {
+            Self::Unix => ("sh", "-c"),
+            Self::Windows => ("cmd", "/C"),
+        };
+        vec![shell.to_string(), flag.to_string(), script.to_string()]
+    }
+
+    /// Shells `run_shell` tries for its session, in order.
+    pub fn session_shells(self) -> &'static [&'static [&'static str]] {
+        match self {
+            Self::Unix => &[&["bash", "--noprofile", "--norc"], &["sh"]],
+            Self::Windows => &[
+                &[
+                    "pwsh",
+                    "-NoLogo",
+                    "-NoProfile",
+                    "-ExecutionPolicy",
+                    "Bypass",
+                ],
+                &[
+                    "powershell",
+                    "-NoLogo",
+                    "-NoProfile",
+                    "-ExecutionPolicy",
+                    "Bypass",
+                ],
+            ],
+        }
+    }
+
+    /// Extension of the file each `run_shell` command is written to.
+    pub fn script_ext(self) -> &'static str {
+        match self {
+            Self::Unix => "sh",
+            Self::Windows => "ps1",
+        }
+    }
+
+    /// First command of a new session: no echo, prompt or history, then
+    /// `limits` (the sandbox's `ulimit`s, ignored on Windows).
+    pub fn session_setup(self, limits: &str) -> String {
+        match self {
+            Self::Unix => format!("stty -echo -onlcr 2>/dev/null; unset HISTFILE; {limits}"),
+            Self::Windows => "function prompt { ' ' }; \
+                 Remove-Module PSReadLine -ErrorAction SilentlyContinue"
+                .to_string(),
+        }
+    }
+
+    /// The line that runs `script` in the session with stderr going to
+    /// `stderr`. It prints `<marker><nonce>+` on a line of its own before
+    /// the script and `\n<marker><nonce> <exit code> <cwd>` after it; the
+    /// marker is spliced together at run time, so a terminal echoing the
+    /// line back never shows either. On Unix it first drops the aliases,
+    /// functions, traps and `PROMPT_COMMAND` earlier commands left, so
+    /// each command runs the programs its text names.
+    pub fn session_line(self, script: &Path, stderr: &Path, marker: &str, nonce: &str) -> String {
+        match self {
+            Self::Unix => format!(
+                "\\unalias -a 2>/dev/null; \\unset -f $(compgen -A function 2>/dev/null) 2>/dev/null; \
+                 \\trap - DEBUG RETURN 2>/dev/null; \\unset PROMPT_COMMAND; \
+                 \\printf '%s%s+\\n' '{marker}' '{nonce}'; \\. {} 2>{}; \
+                 \\printf '\\n%s%s %s %s\\n' '{marker}' '{nonce}' \"$?\" \"$PWD\"\n",
+                sh_quote(script),
+                sh_quote(stderr)
+            ),
+            Self::Windows => format!(
+                "[Console]::Out.Write('{marker}' + '{nonce}' + \"+`n\"); \
+                 $global:LASTEXITCODE = 0; . {} 2>{}; $ok = $?; \
+                 $code = if ($LASTEXITCODE) {{ $LASTEXITCODE }} elseif ($ok) {{ 0 }} else {{ 1 }}; \
+                 [Console]::Out.Write(\"`n\" + '{marker}' + '{nonce}' + \" $code $($PWD.Path)`n\")\r\n",
+                ps_quote(script),
+                ps_quote(stderr)
+            ),
+        }
+    }
+
+    /// File names `name` may have on `PATH`: itself, and on Windows each
+    /// `PATHEXT` extension added when it has none.
+    pub fn executable_names(self, name: &str, pathext: Option<&str>) -> Vec<String> {
+        if self == Self::Unix || Path::new(name).extension().is_some() {
+            return vec![name.to_string()];
+        }
+        pathext
+            .unwrap_or(".COM;.EXE;.BAT;.CMD")
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(|ext| format!("{name}{}", ext.to_ascii_lowercase()))
+            .collect()
+    }
+}
+
+/// A `Command` for `argv`. The script after `cmd /C` is passed verbatim,
+/// since cmd does not understand the quoting Rust would add.
+pub fn command(argv: &[String]) -> Command {
+    let mut command = Command::new(&argv[0]);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        if argv.len() == 3 && argv[1] == "/C" {
+            command.arg("/C").raw_arg(&argv[2]);
+            return command;
+        }
+    }
+    command.args(&argv[1..]);
+    command
+}
+
+/// `script` through the platform shell.
+pub fn shell_command(script: &str) -> Command {
+    command(&Platform::current().shell_argv(script))
+}
+
+/// Like `shell_command`, through a login shell on Unix so profile `PATH`
+/// additions apply.
+pub fn login_shell_command(script: &str) -> Command {
+    match Platform::current() {
+        Platform::Unix => command(&["sh".into(), "-lc".into(), script.into()]),
+        Platform::Windows => shell_command(script),
+    }
+}
+
+/// Whether `name` is an executable file on `PATH`.
+pub fn has_program(name: &str) -> bool {
+    let Some(path) = env::var_os("PATH") else {
+        return false;
+    };
+    let pathext = env::var("PATHEXT").ok();
+    let names = Platform::current().executable_names(name, pathext.as_deref());
+    env::split_paths(&path).any(|dir| names.iter().any(|name| dir.join(name).is_file()))
+}
+
+/// Text a shell wrote to a file: UTF-8, or UTF-16 when it starts with a
+/// byte order mark, as Windows PowerShell redirections do.
+pub fn decode_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+fn sh_quote(path: &Path) -> String {
+    quote(path.as_os_str(), "'\\''")
+}
+
+fn ps_quote(path: &Path) -> String {
+    quote(path.as_os_str(), "''")
+}
+
+fn quote(raw: &OsStr, escaped_quote: &str) -> String {
+    format!("'{}'", raw.to_string_lossy().replace('\'', escaped_quote))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shells_and_program_names_follow_the_platform() {
+        assert_eq!(Platform::Unix.shell_argv("ls"), ["sh", "-c", "ls"]);
+        assert_eq!(
+            Platform::Windows.shell_argv("dir /b"),
+            ["cmd", "/C", "dir /b"]
+        );
+        assert_eq!(
+            Platform::Windows.executable_names("tmux", Some(".EXE;.CMD")),
+            ["tmux.exe", "tmux.cmd"]
+        );
+        assert_eq!(
+            Platform::Windows.executable_names("nvim.exe", None),
+            ["nvim.exe"]
+        );
+        assert_eq!(Platform::Unix.executable_names("tmux", None), ["tmux"]);
+
+        let script = Path::new("/tmp/it's.sh");
+        let line = Platform::Unix.session_line(script, Path::new("/tmp/e"), "MARK_", "n1");
+        assert!(line.contains("\\. '/tmp/it'\\''s.sh' 2>'/tmp/e';"));
+        assert!(line.starts_with("\\unalias -a"));
+        assert!(!line.contains("MARK_n1"));
+        let line = Platform::Windows.session_line(
+            Path::new(r"C:\Temp\it's.ps1"),
+            Path::new(r"C:\Temp\e"),
+            "MARK_",
+            "n1",
+        );
+        assert!(line.contains(r". 'C:\Temp\it''s.ps1' 2>'C:\Temp\e';"));
+        assert!(!line.contains("MARK_n1"));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("héllo".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_text(&utf16), "héllo");
+        assert_eq!(decode_text("plain".as_bytes()), "plain");
+    }
+}
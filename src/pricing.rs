@@ -0,0 +1,127 @@
+//! pricing.rs
+//!
+//! Per-token prices for turning reported usage into an estimated cost.
+//! Built-in prices cover the OpenAI models and local providers;
+//! `[pricing]` in the config file adds or overrides them:
+//!
+//! ```toml
+//! [pricing.groq]
+//! input_per_mtok = 0.59
+//! output_per_mtok = 0.79
+//!
+//! [pricing."mistral/codestral-latest"]   # provider/model wins over provider
+//! input_per_mtok = 0.3
+//! output_per_mtok = 0.9
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+// Longest matching prefix wins, so `gpt-4o-mini` is not priced as `gpt-4o`.
+const OPENAI: &[(&str, f64, f64)] = &[
+    ("gpt-5-nano", 0.05, 0.40),
+    ("gpt-5-mini", 0.25, 2.00),
+    ("gpt-5", 1.25, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("o4-mini", 1.10, 4.40),
+    ("o3-mini", 1.10, 4.40),
+    ("o3", 2.00, 8.00),
+];
+
+impl ModelPrice {
+    /// Most specific match: `provider/model` in `overrides`, then
+    /// `provider`, then the built-in table. None when the model has no
+    /// known price.
+    pub fn resolve(
+        provider: &str,
+        model: &str,
+        overrides: &BTreeMap<String, ModelPrice>,
+    ) -> Option<Self> {
+        overrides
+            .get(&format!("{provider}/{model}"))
+            .or_else(|| overrides.get(provider))
+            .copied()
+            .or_else(|| Self::builtin(provider, model))
+    }
+
+    fn builtin(provider: &str, model: &str) -> Option<Self> {
+        match provider {
+            "ollama" | "test" => Some(Self {
+                input_per_mtok: 0.0,
+                output_per_mtok: 0.0,
+            }),
+            "openai" => OPENAI
+                .iter()
+                .filter(|(prefix, _, _)| model.starts_with(prefix))
+                .max_by_key(|(prefix, _, _)| prefix.len())
+                .map(|&(_, input, output)| Self {
+                    input_per_mtok: input,
+                    output_per_mtok: output,
+                }),
+            _ => None,
+        }
+    }
+
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input_per_mtok + output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_overrides_before_the_builtin_table() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(
+            "groq".to_string(),
+            ModelPrice {
+                input_per_mtok: 1.0,
+                output_per_mtok: 2.0,
+            },
+        );
+        overrides.insert(
+            "openai/gpt-4o".to_string(),
+            ModelPrice {
+                input_per_mtok: 5.0,
+                output_per_mtok: 5.0,
+            },
+        );
+
+        let mini = ModelPrice::resolve("openai", "gpt-4o-mini-2024-07-18", &overrides).unwrap();
+        assert_eq!(mini.input_per_mtok, 0.15);
+        assert_eq!(
+            ModelPrice::resolve("openai", "gpt-4o", &overrides)
+                .unwrap()
+                .input_per_mtok,
+            5.0
+        );
+        let routed = ModelPrice::resolve("groq", "llama-3.3-70b", &overrides).unwrap();
+        assert_eq!(routed.cost(1_000_000, 500_000), 2.0);
+        assert_eq!(
+            ModelPrice::resolve("ollama", "llama3", &overrides)
+                .unwrap()
+                .cost(10_000, 10_000),
+            0.0
+        );
+        assert_eq!(
+            ModelPrice::resolve("mistral", "codestral", &overrides),
+            None
+        );
+        assert_eq!(ModelPrice::resolve("openai", "davinci", &overrides), None);
+    }
+}
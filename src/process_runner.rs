@@ -32,8 +32,7 @@ pub fn run_shell_command_cancellable(
     timeout: Duration,
     is_cancelled: impl Fn() -> bool,
 ) -> Result<ProcessRun, String> {
-    let mut command = Command::new("sh");
-    command.arg("-c").arg(cmd);
+    let mut command = crate::platform::shell_command(cmd);
     if let Some(cwd) = cwd {
         command.current_dir(cwd);
     }
@@ -0,0 +1,156 @@
+//! protected_paths.rs
+//!
+//! Files the agent must not edit casually. Write, edit and patch calls that
+//! touch a path matching `[protected_paths]` are refused outright, or need a
+//! second, distinct confirmation that auto-approve does not answer:
+//!
+//! ```toml
+//! [protected_paths]
+//! patterns = ["migrations/**", "*.lock", ".github/workflows/**"]
+//! mode = "confirm"   # or "refuse"
+//! ```
+//!
+//! A pattern without `/` matches the file name anywhere in the repo; one
+//! with `/` matches the repo-relative path, and `**` spans directories.
+
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectMode {
+    /// Ask twice, even with auto-approve on; headless runs deny.
+    #[default]
+    Confirm,
+    /// Never allow the edit.
+    Refuse,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedPaths {
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub mode: ProtectMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub pattern: String,
+}
+
+impl Violation {
+    pub fn describe(&self) -> String {
+        format!("{} (matches {})", self.path, self.pattern)
+    }
+}
+
+impl ProtectedPaths {
+    /// Patterns that do not parse, for config validation.
+    pub fn invalid_patterns(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .filter(|pattern| Pattern::new(pattern).is_err())
+            .cloned()
+            .collect()
+    }
+
+    /// Repo-relative `paths` that match a protected pattern. Callers pass
+    /// them normalized (see `agent::mutation_targets`), so `x/../Cargo.lock`
+    /// is checked as `Cargo.lock`.
+    pub fn check(&self, paths: &[String]) -> Vec<Violation> {
+        paths
+            .iter()
+            .filter_map(|path| {
+                let path = path.trim_start_matches("./");
+                let pattern = self.patterns.iter().find(|p| matches(p, path))?;
+                Some(Violation {
+                    path: path.to_string(),
+                    pattern: pattern.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let Ok(compiled) = Pattern::new(pattern) else {
+        return false;
+    };
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    if pattern.contains('/') {
+        compiled.matches_with(path, options)
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        compiled.matches_with(name, options)
+    }
+}
+
+/// What the model is told when a protected edit is refused or denied.
+pub fn refusal_message(violations: &[Violation], mode: ProtectMode) -> String {
+    let paths: Vec<String> = violations.iter().map(Violation::describe).collect();
+    let why = match mode {
+        ProtectMode::Refuse => "edits to protected paths are refused",
+        ProtectMode::Confirm => "the user did not confirm the protected edit",
+    };
+    format!(
+        "protected path: {}; {why}. Leave these files unchanged and choose another \
+         approach, or ask the user to make this change.",
+        paths.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_names_anywhere_and_paths_from_root() {
+        let protected = ProtectedPaths {
+            patterns: vec![
+                "migrations/**".into(),
+                "*.lock".into(),
+                ".github/workflows/**".into(),
+            ],
+            mode: ProtectMode::Confirm,
+        };
+        let paths: Vec<String> = [
+            "Cargo.lock",
+            "web/yarn.lock",
+            "./migrations/2024/001_init.sql",
+            ".github/workflows/ci.yml",
+            "src/migrations.rs",
+            "app/migrations/001.sql",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        let hits: Vec<String> = protected
+            .check(&paths)
+            .iter()
+            .map(Violation::describe)
+            .collect();
+        assert_eq!(
+            hits,
+            vec![
+                "Cargo.lock (matches *.lock)",
+                "web/yarn.lock (matches *.lock)",
+                "migrations/2024/001_init.sql (matches migrations/**)",
+                ".github/workflows/ci.yml (matches .github/workflows/**)",
+            ]
+        );
+        assert!(ProtectedPaths::default().check(&paths).is_empty());
+        assert_eq!(
+            ProtectedPaths {
+                patterns: vec!["[".into()],
+                ..ProtectedPaths::default()
+            }
+            .invalid_patterns(),
+            vec!["["]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! pull_request.rs
+//!
+//! `/pr`: a pull request drafted from the conversation and the branch diff.
+//! The draft is previewed first; creating it commits pending changes (on a
+//! new `osmogrep/` branch when the checkout is on the base branch), pushes,
+//! and opens the PR with `gh pr create`, or through the GitHub API with
+//! `GITHUB_TOKEN` when `gh` is not installed.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use serde_json::Value;
+
+use crate::git::git;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrDraft {
+    pub title: String,
+    pub body: String,
+    pub base: String,
+    pub head: String,
+    /// `head` does not exist yet and is created from the current branch.
+    pub new_branch: bool,
+    /// Uncommitted changes are committed with the title as the message.
+    pub commit_changes: bool,
+    pub files: Vec<ChangedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub path: String,
+    /// None for binary files.
+    pub added: Option<usize>,
+    pub removed: Option<usize>,
+}
+
+const TITLE_CHARS: usize = 72;
+const SUMMARY_CHARS: usize = 2000;
+
+/// Drafts a PR for everything on the current branch and in the working tree
+/// that is not on the base branch yet.
+pub fn draft(repo_root: &Path, messages: &[Value]) -> Result<PrDraft, String> {
+    let current = git(repo_root, &["rev-parse", "--abbrev-ref", "HEAD"])?
+        .trim()
+        .to_string();
+    if current == "HEAD" {
+        return Err("HEAD is detached; check out a branch first".into());
+    }
+    let base = base_branch(repo_root);
+    let remote_base = format!("origin/{base}");
+    let base_ref = match git(
+        repo_root,
+        &["rev-parse", "--verify", "--quiet", &remote_base],
+    ) {
+        Ok(_) => remote_base,
+        Err(_) => base.clone(),
+    };
+    let fork_point = git(repo_root, &["merge-base", &base_ref, "HEAD"])?
+        .trim()
+        .to_string();
+
+    let mut files = parse_numstat(&git(
+        repo_root,
+        &[
+            "diff",
+            "--numstat",
+            &fork_point,
+            "--",
+            ".",
+            ":(exclude).context",
+        ],
+    )?);
+    let untracked = git(
+        repo_root,
+        &[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "--",
+            ".",
+            ":(exclude).context",
+        ],
+    )?;
+    files.extend(untracked.lines().map(|path| ChangedFile {
+        path: path.to_string(),
+        added: None,
+        removed: None,
+    }));
+    if files.is_empty() {
+        return Err(format!(
+            "nothing to open a PR for: no changes since {base_ref}"
+        ));
+    }
+    let dirty = !git(
+        repo_root,
+        &["status", "--porcelain", "--", ".", ":(exclude).context"],
+    )?
+    .trim()
+    .is_empty();
+
+    let prompt = last_text(messages, "user");
+    let answer = last_text(messages, "assistant");
+    let title = match prompt.as_deref() {
+        Some(prompt) => title_from_prompt(prompt),
+        None => git(repo_root, &["log", "-1", "--pretty=%s"])?
+            .trim()
+            .to_string(),
+    };
+    let new_branch = current == base;
+    let head = if new_branch {
+        branch_name(&title)
+    } else {
+        current
+    };
+
+    Ok(PrDraft {
+        body: render_body(prompt.as_deref(), answer.as_deref(), &files),
+        title,
+        base,
+        head,
+        new_branch,
+        commit_changes: dirty,
+        files,
+    })
+}
+
+impl PrDraft {
+    /// What `/pr` shows before anything is pushed.
+    pub fn preview(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("PR: {}", self.title),
+            format!(
+                "{} -> {}{}",
+                self.head,
+                self.base,
+                if self.new_branch { " (new branch)" } else { "" }
+            ),
+        ];
+        if self.commit_changes {
+            lines.push(
+                "Uncommitted changes will be committed with the title as the message.".into(),
+            );
+        }
+        lines.push(String::new());
+        lines.extend(self.body.lines().map(str::to_string));
+        lines
+    }
+}
+
+/// Commits, pushes and opens the PR; returns its URL.
+pub fn create(repo_root: &Path, draft: &PrDraft) -> Result<String, String> {
+    if draft.new_branch {
+        git(repo_root, &["switch", "-c", &draft.head])?;
+    }
+    if draft.commit_changes {
+        git(repo_root, &["add", "-A", "--", ".", ":(exclude).context"])?;
+        git(repo_root, &["commit", "-q", "-m", &draft.title])?;
+    }
+    git(repo_root, &["push", "-u", "origin", &draft.head])?;
+
+    if has_gh() {
+        return gh_create(repo_root, draft);
+    }
+    let token = std::env::var("GITHUB_TOKEN").map_err(|_| {
+        "install the GitHub CLI (gh) or set GITHUB_TOKEN to open the PR".to_string()
+    })?;
+    let remote = git(repo_root, &["remote", "get-url", "origin"])?;
+    let repo = github_repo(remote.trim())
+        .ok_or_else(|| format!("origin is not a GitHub remote: {}", remote.trim()))?;
+    crate::triage::create_pull(token, &repo, draft).map_err(|e| e.to_string())
+}
+
+fn gh_create(repo_root: &Path, draft: &PrDraft) -> Result<String, String> {
+    let mut child = Command::new("gh")
+        .current_dir(repo_root)
+        .args(["pr", "create", "--title", &draft.title, "--body-file", "-"])
+        .args(["--base", &draft.base, "--head", &draft.head])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("gh: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(draft.body.as_bytes())
+            .map_err(|e| format!("gh: {e}"))?;
+    }
+    let out = child.wait_with_output().map_err(|e| format!("gh: {e}"))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    Ok(stdout.lines().last().unwrap_or_default().trim().to_string())
+}
+
+/// First line of the prompt as a sentence-case title.
+pub fn title_from_prompt(prompt: &str) -> String {
+    let line = prompt.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    let line = line.trim().trim_end_matches(['.', ':', '!']);
+    let mut chars = line.chars();
+    let mut title: String = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => return "Update from Osmogrep".to_string(),
+    };
+    if title.chars().count() > TITLE_CHARS {
+        title = title.chars().take(TITLE_CHARS - 3).collect::<String>();
+        title = format!("{}...", title.trim_end());
+    }
+    title
+}
+
+pub fn render_body(prompt: Option<&str>, answer: Option<&str>, files: &[ChangedFile]) -> String {
+    let mut out = String::from("## Summary\n\n");
+    match answer.map(str::trim).filter(|a| !a.is_empty()) {
+        Some(answer) if answer.chars().count() > SUMMARY_CHARS => {
+            let clipped: String = answer.chars().take(SUMMARY_CHARS).collect();
+            out.push_str(clipped.trim_end());
+            out.push_str("\n...\n");
+        }
+        Some(answer) => {
+            out.push_str(answer);
+            out.push('\n');
+        }
+        None => out.push_str("_No summary._\n"),
+    }
+
+    out.push_str(&format!("\n## Changes ({} files)\n\n", files.len()));
+    for file in files {
+        match (file.added, file.removed) {
+            (Some(added), Some(removed)) => {
+                out.push_str(&format!("- `{}` (+{added} -{removed})\n", file.path))
+            }
+            _ => out.push_str(&format!("- `{}`\n", file.path)),
+        }
+    }
+
+    if let Some(prompt) = prompt.map(str::trim).filter(|p| !p.is_empty()) {
+        out.push_str("\n## Request\n\n");
+        for line in prompt.lines() {
+            out.push_str(&format!("> {line}\n"));
+        }
+    }
+    out
+}
+
+/// `owner/repo` of a GitHub remote URL.
+pub fn github_repo(remote: &str) -> Option<String> {
+    let path = remote
+        .strip_prefix("git@github.com:")
+        .or_else(|| remote.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| remote.strip_prefix("https://github.com/"))
+        .or_else(|| remote.strip_prefix("http://github.com/"))?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, repo) = path.split_once('/')?;
+    (!owner.is_empty() && !repo.is_empty() && !repo.contains('/')).then(|| path.to_string())
+}
+
+fn last_text(messages: &[Value], role: &str) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .filter(|m| m.get("role").and_then(Value::as_str) == Some(role))
+        .find_map(|m| m.get("content").and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+fn base_branch(repo_root: &Path) -> String {
+    git(
+        repo_root,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .ok()
+    .and_then(|r| r.trim().strip_prefix("origin/").map(str::to_string))
+    .unwrap_or_else(|| "main".to_string())
+}
+
+// `osmogrep/fix-the-flaky-parser-test`
+fn branch_name(title: &str) -> String {
+    let slug: String = title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let words: Vec<&str> = slug.split('-').filter(|w| !w.is_empty()).take(6).collect();
+    format!("osmogrep/{}", words.join("-"))
+}
+
+fn parse_numstat(out: &str) -> Vec<ChangedFile> {
+    out.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?.parse().ok();
+            let removed = parts.next()?.parse().ok();
+            Some(ChangedFile {
+                path: parts.next()?.to_string(),
+                added,
+                removed,
+            })
+        })
+        .collect()
+}
+
+fn has_gh() -> bool {
+    Command::new("gh")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn drafts_a_branch_title_and_body_from_the_conversation_and_diff() {
+        let root = std::env::temp_dir().join(format!("osmogrep-pr-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let git = |args: &[&str]| {
+            let ok = Command::new("git")
+                .current_dir(&root)
+                .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+                .args(args)
+                .status()
+                .unwrap()
+                .success();
+            assert!(ok, "git {args:?}");
+        };
+        git(&["init", "-q", "-b", "main"]);
+        fs::write(root.join("parser.rs"), "fn parse() {}\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "init"]);
+        fs::write(
+            root.join("parser.rs"),
+            "fn parse() -> bool {\n    true\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("parser_test.rs"), "#[test]\nfn parses() {}\n").unwrap();
+
+        let messages = vec![
+            json!({ "role": "user", "content": "fix the flaky parser test." }),
+            json!({ "role": "assistant", "content": "Made `parse` return a result and added a test." }),
+        ];
+        let draft = draft(&root, &messages).unwrap();
+        assert_eq!(draft.title, "Fix the flaky parser test");
+        assert_eq!(draft.base, "main");
+        assert_eq!(draft.head, "osmogrep/fix-the-flaky-parser-test");
+        assert!(draft.new_branch && draft.commit_changes);
+        assert_eq!(
+            draft.body,
+            "## Summary\n\nMade `parse` return a result and added a test.\n\n\
+             ## Changes (2 files)\n\n- `parser.rs` (+3 -1)\n- `parser_test.rs`\n\n\
+             ## Request\n\n> fix the flaky parser test.\n"
+        );
+        assert_eq!(
+            draft.preview()[1],
+            "osmogrep/fix-the-flaky-parser-test -> main (new branch)"
+        );
+
+        assert_eq!(
+            github_repo("git@github.com:kaushal07wick/OsmoGrep.git").as_deref(),
+            Some("kaushal07wick/OsmoGrep")
+        );
+        assert_eq!(
+            github_repo("https://github.com/owner/repo").as_deref(),
+            Some("owner/repo")
+        );
+        assert_eq!(github_repo("https://gitlab.com/owner/repo.git"), None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,285 @@
+//! rebase.rs
+//!
+//! `/rebase <base>`: rebases the current branch and, each time git stops on
+//! conflicts, hands the conflicted files to the agent. Every file comes with
+//! its conflict regions and the lines around them, so the agent resolves
+//! them with the edit tools (each edit previewed and approved) instead of
+//! rewriting whole files. Once no conflict markers remain the rebase
+//! continues, until it finishes or stops on the next commit.
+
+use std::{fs, path::Path};
+
+use crate::git::git;
+use crate::git_status;
+
+/// Lines shown before and after each conflict region.
+const CONTEXT_LINES: usize = 6;
+
+/// A file git could not merge while replaying a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: String,
+    /// How the two sides differ, e.g. `both modified`.
+    pub kind: &'static str,
+    /// Each conflict region with its surrounding lines, numbered.
+    pub hunks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseOutcome {
+    Finished,
+    /// Stopped replaying `commit` (short hash and subject).
+    Stopped {
+        commit: String,
+        conflicts: Vec<Conflict>,
+    },
+}
+
+/// Whether a rebase is stopped in `repo_root`.
+pub fn in_progress(repo_root: &Path) -> bool {
+    ["rebase-merge", "rebase-apply"].iter().any(|dir| {
+        git(repo_root, &["rev-parse", "--git-path", dir])
+            .map(|path| repo_root.join(path.trim()).exists())
+            .unwrap_or(false)
+    })
+}
+
+/// Starts `git rebase <base>`. Refuses while another rebase is stopped;
+/// git itself refuses with uncommitted changes.
+pub fn start(repo_root: &Path, base: &str) -> Result<RebaseOutcome, String> {
+    if in_progress(repo_root) {
+        return Err(
+            "A rebase is already in progress. Use /rebase continue or /rebase abort.".into(),
+        );
+    }
+    step(repo_root, &["rebase", base])
+}
+
+/// Stages the resolved files and runs `git rebase --continue`. Refuses
+/// while a conflicted file still has conflict markers.
+pub fn continue_rebase(repo_root: &Path) -> Result<RebaseOutcome, String> {
+    if !in_progress(repo_root) {
+        return Err("No rebase in progress.".into());
+    }
+    let unresolved = unresolved(repo_root);
+    if !unresolved.is_empty() {
+        return Err(format!(
+            "Conflict markers remain in {}.",
+            unresolved.join(", ")
+        ));
+    }
+    for conflict in conflicted(repo_root) {
+        git(repo_root, &["add", "-A", "--", &conflict.0])?;
+    }
+    step(repo_root, &["rebase", "--continue"])
+}
+
+pub fn abort(repo_root: &Path) -> Result<(), String> {
+    git(repo_root, &["rebase", "--abort"]).map(|_| ())
+}
+
+/// Conflicted files that still contain conflict markers.
+pub fn unresolved(repo_root: &Path) -> Vec<String> {
+    conflicted(repo_root)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| {
+            fs::read_to_string(repo_root.join(path))
+                .is_ok_and(|text| text.lines().any(|line| line.starts_with("<<<<<<<")))
+        })
+        .collect()
+}
+
+// Runs a rebase command; conflicts are an outcome, any other failure an
+// error.
+fn step(repo_root: &Path, args: &[&str]) -> Result<RebaseOutcome, String> {
+    match git(repo_root, args) {
+        Ok(_) if !in_progress(repo_root) => Ok(RebaseOutcome::Finished),
+        result => {
+            let conflicts = conflicts(repo_root);
+            if conflicts.is_empty() {
+                return Err(result.err().unwrap_or_else(|| {
+                    "git stopped the rebase without conflicts; see git status.".into()
+                }));
+            }
+            let commit = git(repo_root, &["log", "-1", "--format=%h %s", "REBASE_HEAD"])
+                .map(|out| out.trim().to_string())
+                .unwrap_or_default();
+            Ok(RebaseOutcome::Stopped { commit, conflicts })
+        }
+    }
+}
+
+fn conflicted(repo_root: &Path) -> Vec<(String, &'static str)> {
+    git_status::status(repo_root)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| {
+            // In a rebase "us" is the base and "them" the replayed commit.
+            let kind = match (entry.staged?, entry.unstaged?) {
+                ('U', 'U') => "both modified",
+                ('A', 'A') => "both added",
+                ('D', 'D') => "both deleted",
+                ('A', 'U') => "added on the base",
+                ('U', 'A') => "added by the commit",
+                ('D', 'U') => "deleted on the base, modified by the commit",
+                ('U', 'D') => "deleted by the commit, modified on the base",
+                _ => return None,
+            };
+            Some((entry.path, kind))
+        })
+        .collect()
+}
+
+/// The conflicted files of the stopped rebase with their conflict regions.
+pub fn conflicts(repo_root: &Path) -> Vec<Conflict> {
+    conflicted(repo_root)
+        .into_iter()
+        .map(|(path, kind)| {
+            let text = fs::read_to_string(repo_root.join(&path)).unwrap_or_default();
+            Conflict {
+                hunks: conflict_hunks(&text),
+                path,
+                kind,
+            }
+        })
+        .collect()
+}
+
+// Conflict regions widened by `CONTEXT_LINES`, overlapping ones merged,
+// each line prefixed with its 1-based number.
+fn conflict_hunks(text: &str) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut open = None;
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with("<<<<<<<") {
+            open = Some(i);
+        } else if let (true, Some(start)) = (line.starts_with(">>>>>>>"), open) {
+            open = None;
+            let from = start.saturating_sub(CONTEXT_LINES);
+            let to = (i + CONTEXT_LINES).min(lines.len() - 1);
+            match ranges.last_mut() {
+                Some(last) if last.1 + 1 >= from => last.1 = to,
+                _ => ranges.push((from, to)),
+            }
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(from, to)| {
+            (from..=to)
+                .map(|i| format!("{:>5} | {}", i + 1, lines[i]))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect()
+}
+
+/// Instructions for the agent run that resolves one stop of the rebase.
+pub fn resolution_prompt(base: &str, commit: &str, conflicts: &[Conflict]) -> String {
+    let mut prompt = format!(
+        "A rebase onto {base} stopped on conflicts while replaying commit {commit}.\n\
+         Resolve every conflict below. Between `<<<<<<<` and `=======` is the code on {base} \
+         (the base); between `=======` and `>>>>>>>` is the change from the commit being replayed.\n\
+         \n\
+         Requirements:\n\
+         1) Keep the intent of both sides: the commit's change applied on top of what {base} has now.\n\
+         2) Edit only the conflict regions with the edit tools, one file at a time; remove every \
+         conflict marker. Read more of a file if the context shown is not enough.\n\
+         3) For a file deleted on one side, delete it or keep it with the other side's change, \
+         whichever the commit intends.\n\
+         4) Do not run git add, git commit or git rebase; the rebase continues once no markers remain.\n\
+         5) Finish with one line per file saying how it was resolved.\n"
+    );
+    for conflict in conflicts {
+        prompt.push_str(&format!("\n## {} ({})\n", conflict.path, conflict.kind));
+        for hunk in &conflict.hunks {
+            prompt.push_str(&format!("```\n{hunk}\n```\n"));
+        }
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use uuid::Uuid;
+
+    fn run_git(root: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    #[test]
+    fn stops_on_conflicts_with_context_and_continues_once_resolved() {
+        let root = std::env::temp_dir().join(format!("osmogrep-rebase-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        run_git(&root, &["init", "-q", "-b", "main"]);
+        run_git(&root, &["config", "user.email", "test@example.com"]);
+        run_git(&root, &["config", "user.name", "Osmogrep Test"]);
+        let numbered = |middle: &str| {
+            (1..=20)
+                .map(|n| {
+                    if n == 10 {
+                        middle.to_string()
+                    } else {
+                        n.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                + "\n"
+        };
+        fs::write(root.join("a.txt"), numbered("10")).unwrap();
+        run_git(&root, &["add", "."]);
+        run_git(&root, &["commit", "-q", "-m", "init"]);
+        run_git(&root, &["checkout", "-q", "-b", "feature"]);
+        fs::write(root.join("a.txt"), numbered("ten")).unwrap();
+        run_git(&root, &["commit", "-q", "-am", "spell out ten"]);
+        run_git(&root, &["checkout", "-q", "main"]);
+        fs::write(root.join("a.txt"), numbered("10!")).unwrap();
+        run_git(&root, &["commit", "-q", "-am", "stress ten"]);
+        run_git(&root, &["checkout", "-q", "feature"]);
+
+        let RebaseOutcome::Stopped { commit, conflicts } = start(&root, "main").unwrap() else {
+            panic!("expected a conflict");
+        };
+        assert!(commit.ends_with(" spell out ten"));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            (conflicts[0].path.as_str(), conflicts[0].kind),
+            ("a.txt", "both modified")
+        );
+        let hunk = &conflicts[0].hunks[0];
+        assert!(hunk.starts_with("    4 | 4\n"));
+        assert!(hunk.contains("   11 | 10!\n"));
+        assert!(hunk.ends_with("   20 | 16"));
+        let prompt = resolution_prompt("main", &commit, &conflicts);
+        assert!(prompt.contains("## a.txt (both modified)"));
+
+        assert!(start(&root, "main").is_err());
+        assert_eq!(unresolved(&root), ["a.txt"]);
+        assert!(continue_rebase(&root).unwrap_err().contains("a.txt"));
+        fs::write(root.join("a.txt"), numbered("ten!")).unwrap();
+        assert_eq!(continue_rebase(&root).unwrap(), RebaseOutcome::Finished);
+        assert!(!in_progress(&root));
+        let log = Command::new("git")
+            .arg("-C")
+            .arg(&root)
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout),
+            "spell out ten\nstress ten\ninit\n"
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,283 @@
+//! response_cache.rs
+//!
+//! Model responses keyed by model and a hash of the request input. Three
+//! users share the key:
+//!
+//! - an optional on-disk cache, so an identical request is answered locally:
+//!
+//!   ```toml
+//!   [response_cache]
+//!   enabled = true
+//!   dir = "~/.cache/osmogrep/responses"   # default
+//!   ```
+//!
+//! - `osmogrep run --record run.json`, which writes every response of the
+//!   run (sub-agents included) to one file;
+//! - `osmogrep run --replay run.json`, which runs the agent loop again with
+//!   those responses instead of the API. Tools really execute, so a replay
+//!   reproduces what the agent did to the workspace.
+//!
+//! A replayed request whose input no longer matches the recording (tool
+//! output changed, another machine) gets the next unused response in
+//! recorded order.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const RECORDING_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+impl ResponseCacheConfig {
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => crate::images::expand_home(&dir.to_string_lossy()),
+            None => dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("osmogrep")
+                .join("responses"),
+        }
+    }
+}
+
+/// Hex digest of `model` and the exact request input.
+pub fn key(model: &str, input: &Value) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(model.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(input.to_string().as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub model: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub responses: Vec<RecordedResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub key: String,
+    pub response: Value,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw =
+            fs::read_to_string(path).map_err(|e| format!("recording {}: {e}", path.display()))?;
+        let recording: Self =
+            serde_json::from_str(&raw).map_err(|e| format!("recording {}: {e}", path.display()))?;
+        if recording.version != RECORDING_VERSION {
+            return Err(format!(
+                "recording {}: version {} is not supported",
+                path.display(),
+                recording.version
+            ));
+        }
+        Ok(recording)
+    }
+}
+
+/// Appends each response to a recording file, rewriting it every time so a
+/// run that crashes still leaves what it got.
+pub struct Recorder {
+    path: PathBuf,
+    recording: Mutex<Recording>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, model: &str, prompt: &str) -> Result<Self, String> {
+        let recorder = Self {
+            path: path.to_path_buf(),
+            recording: Mutex::new(Recording {
+                version: RECORDING_VERSION,
+                model: model.to_string(),
+                prompt: prompt.to_string(),
+                responses: Vec::new(),
+            }),
+        };
+        let recording = recorder.recording.lock().map_err(|_| "recorder poisoned")?;
+        recorder.write(&recording)?;
+        drop(recording);
+        Ok(recorder)
+    }
+
+    fn record(&self, key: &str, response: &Value) -> Result<(), String> {
+        let mut recording = self.recording.lock().map_err(|_| "recorder poisoned")?;
+        recording.responses.push(RecordedResponse {
+            key: key.to_string(),
+            response: response.clone(),
+        });
+        self.write(&recording)
+    }
+
+    fn write(&self, recording: &Recording) -> Result<(), String> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let text = serde_json::to_string_pretty(recording).map_err(|e| e.to_string())?;
+        fs::write(&self.path, text).map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+}
+
+/// Serves a recording's responses, each at most once.
+pub struct Replay {
+    recording: Recording,
+    used: Mutex<Vec<bool>>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        let used = vec![false; recording.responses.len()];
+        Self {
+            recording,
+            used: Mutex::new(used),
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.recording.model
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.recording.prompt
+    }
+
+    /// The unused response recorded for `key`, else the next unused one.
+    pub fn respond(&self, key: &str) -> Result<Value, String> {
+        let mut used = self.used.lock().map_err(|_| "replay poisoned")?;
+        let unused = |i: &usize| !used[*i];
+        let responses = &self.recording.responses;
+        let matched = (0..responses.len())
+            .filter(unused)
+            .find(|&i| responses[i].key == key);
+        let index = match matched {
+            Some(index) => index,
+            None => {
+                let index = (0..responses.len()).find(unused).ok_or_else(|| {
+                    format!(
+                        "replay exhausted: the recording has {} responses",
+                        responses.len()
+                    )
+                })?;
+                tracing::warn!(index, "replayed request differs from the recording");
+                index
+            }
+        };
+        used[index] = true;
+        Ok(responses[index].response.clone())
+    }
+}
+
+/// Where a run's model responses come from besides the API, and where they
+/// are kept.
+#[derive(Clone, Default)]
+pub struct ResponseStore {
+    cache: Option<PathBuf>,
+    recorder: Option<Arc<Recorder>>,
+    replay: Option<Arc<Replay>>,
+}
+
+impl ResponseStore {
+    pub fn from_config(cfg: &ResponseCacheConfig) -> Self {
+        Self {
+            cache: cfg.enabled.then(|| cfg.dir()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    pub fn with_replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(Arc::new(replay));
+        self
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// A replayed or cached response for `key`; `None` means ask the API.
+    pub fn lookup(&self, key: &str) -> Option<Result<Value, String>> {
+        if let Some(replay) = &self.replay {
+            return Some(replay.respond(key));
+        }
+        let path = self.cache.as_ref()?.join(format!("{key}.json"));
+        let value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        tracing::debug!(key, "response cache hit");
+        Some(Ok(value))
+    }
+
+    /// Caches and records a response the API returned.
+    pub fn store(&self, key: &str, response: &Value) {
+        if let Some(dir) = &self.cache {
+            let written = fs::create_dir_all(dir)
+                .and_then(|_| fs::write(dir.join(format!("{key}.json")), response.to_string()));
+            if let Err(e) = written {
+                tracing::warn!(dir = %dir.display(), error = %e, "response cache write failed");
+            }
+        }
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record(key, response) {
+                tracing::warn!(error = %e, "recording write failed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::env;
+    use uuid::Uuid;
+
+    #[test]
+    fn replay_prefers_matching_keys_then_recorded_order() {
+        let path = env::temp_dir().join(format!("osmogrep-recording-{}.json", Uuid::new_v4()));
+        let recorder = Recorder::create(&path, "gpt-test", "fix it").unwrap();
+        let first = key("gpt-test", &json!([{ "role": "user", "content": "a" }]));
+        let second = key("gpt-test", &json!([{ "role": "user", "content": "b" }]));
+        assert_ne!(first, second);
+        recorder.record(&first, &json!({ "n": 1 })).unwrap();
+        recorder.record(&second, &json!({ "n": 2 })).unwrap();
+
+        let replay = Replay::new(Recording::load(&path).unwrap());
+        assert_eq!(replay.model(), "gpt-test");
+        assert_eq!(replay.prompt(), "fix it");
+        assert_eq!(replay.respond(&second).unwrap()["n"], 2);
+        assert_eq!(replay.respond("changed input").unwrap()["n"], 1);
+        assert!(replay.respond(&first).unwrap_err().contains("exhausted"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn cache_answers_a_stored_key() {
+        let dir = env::temp_dir().join(format!("osmogrep-cache-{}", Uuid::new_v4()));
+        let store = ResponseStore::from_config(&ResponseCacheConfig {
+            enabled: true,
+            dir: Some(dir.clone()),
+        });
+        assert!(store.lookup("k").is_none());
+        store.store("k", &json!({ "output": [] }));
+        assert_eq!(store.lookup("k").unwrap().unwrap(), json!({ "output": [] }));
+        assert!(ResponseStore::default().lookup("k").is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,192 @@
+//! review_queue.rs
+//!
+//! Per-file review of what an agent run changed. `/review` (or the end of a
+//! run that changed files) lists every file with its diff against the file
+//! before the run. Each file is accepted or rejected; confirming restores
+//! the rejected ones to their pre-run content, deleting files the run
+//! created, so only the accepted changes remain.
+
+use std::fs;
+use std::path::Path;
+
+use crate::state::DiffSnapshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Accept,
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewItem {
+    /// The file before the run and as it is now.
+    pub change: DiffSnapshot,
+    pub decision: Option<Decision>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewQueue {
+    pub items: Vec<ReviewItem>,
+    pub selected: usize,
+}
+
+impl ReviewQueue {
+    /// A queue over `changes`, or `None` when there is nothing to review.
+    pub fn new(changes: Vec<DiffSnapshot>) -> Option<Self> {
+        if changes.is_empty() {
+            return None;
+        }
+        let items = changes
+            .into_iter()
+            .map(|change| ReviewItem {
+                change,
+                decision: None,
+            })
+            .collect();
+        Some(Self { items, selected: 0 })
+    }
+
+    pub fn selected(&self) -> &ReviewItem {
+        &self.items[self.selected]
+    }
+
+    pub fn move_selection(&mut self, back: bool) {
+        self.selected = if back {
+            self.selected.saturating_sub(1)
+        } else {
+            (self.selected + 1).min(self.items.len() - 1)
+        };
+    }
+
+    /// Decides the selected file and moves on to the next undecided one.
+    pub fn decide(&mut self, decision: Decision) {
+        self.items[self.selected].decision = Some(decision);
+        let len = self.items.len();
+        if let Some(next) = (1..len)
+            .map(|step| (self.selected + step) % len)
+            .find(|&i| self.items[i].decision.is_none())
+        {
+            self.selected = next;
+        }
+    }
+
+    pub fn decide_all(&mut self, decision: Decision) {
+        for item in &mut self.items {
+            item.decision = Some(decision);
+        }
+    }
+
+    pub fn undecided(&self) -> usize {
+        self.items.iter().filter(|i| i.decision.is_none()).count()
+    }
+
+    /// Restores every rejected file under `root`. Returns one snapshot per
+    /// restored file, from its current content back to the pre-run one,
+    /// and the files that could not be restored.
+    pub fn apply(&self, root: &Path) -> (Vec<DiffSnapshot>, Vec<String>) {
+        let mut restored = Vec::new();
+        let mut failed = Vec::new();
+        for item in &self.items {
+            if item.decision != Some(Decision::Reject) {
+                continue;
+            }
+            let change = &item.change;
+            let path = root.join(&change.target);
+            let result = if change.created {
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                }
+            } else {
+                path.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, &change.before))
+            };
+            match result {
+                Ok(()) => restored.push(DiffSnapshot {
+                    tool: "review".to_string(),
+                    target: change.target.clone(),
+                    before: change.after.clone(),
+                    after: change.before.clone(),
+                    created: change.deleted,
+                    deleted: change.created,
+                }),
+                Err(e) => failed.push(format!("{}: {e}", change.target)),
+            }
+        }
+        (restored, failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use uuid::Uuid;
+
+    fn snap(target: &str, before: &str, after: &str) -> DiffSnapshot {
+        DiffSnapshot {
+            tool: "write_file".to_string(),
+            target: target.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            created: false,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn confirming_restores_only_rejected_files() {
+        let root = env::temp_dir().join(format!("osmogrep-review-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/kept.rs"), "new").unwrap();
+        fs::write(root.join("src/edited.rs"), "new").unwrap();
+        fs::write(root.join("src/created.rs"), "new").unwrap();
+        fs::write(root.join("src/was_empty.rs"), "new").unwrap();
+
+        let mut queue = ReviewQueue::new(vec![
+            snap("src/kept.rs", "old", "new"),
+            snap("src/edited.rs", "old", "new"),
+            DiffSnapshot {
+                created: true,
+                ..snap("src/created.rs", "", "new")
+            },
+            DiffSnapshot {
+                deleted: true,
+                ..snap("src/deleted.rs", "old", "")
+            },
+            snap("src/was_empty.rs", "", "new"),
+        ])
+        .unwrap();
+        queue.decide(Decision::Accept);
+        assert_eq!(queue.selected, 1);
+        queue.decide(Decision::Reject);
+        queue.decide(Decision::Reject);
+        assert_eq!(queue.undecided(), 2);
+        queue.decide(Decision::Reject);
+        queue.decide(Decision::Reject);
+        assert_eq!(queue.undecided(), 0);
+
+        let (restored, failed) = queue.apply(&root);
+        assert!(failed.is_empty(), "{failed:?}");
+        assert_eq!(restored.len(), 4);
+        assert!(restored[1].deleted && restored[2].created);
+        assert_eq!(fs::read_to_string(root.join("src/kept.rs")).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(root.join("src/edited.rs")).unwrap(),
+            "old"
+        );
+        assert!(!root.join("src/created.rs").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("src/deleted.rs")).unwrap(),
+            "old"
+        );
+        // An existing empty file goes back to empty rather than away.
+        assert_eq!(
+            fs::read_to_string(root.join("src/was_empty.rs")).unwrap(),
+            ""
+        );
+        assert!(ReviewQueue::new(Vec::new()).is_none());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,275 @@
+//! run_budget.rs
+//!
+//! Limits on what one agent run may spend before it stops on its own:
+//!
+//! ```toml
+//! [run_budget]
+//! max_tool_calls = 60
+//! max_tokens = 500000   # input + output, summed over model turns
+//! max_seconds = 1800
+//! ```
+//!
+//! `/budget tools=40 tokens=200k time=10m` overrides them for the next runs
+//! of the session. A run at a limit makes no further tool calls or model
+//! turns; it ends with a report of what it did and which plan steps remain.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::PlanItem;
+use crate::timefmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunBudget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tool_calls: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_seconds: Option<u64>,
+}
+
+impl RunBudget {
+    /// Applies `tools=40 tokens=200k time=10m`; `off` lifts a limit.
+    pub fn with_overrides(mut self, args: &str) -> Result<Self, String> {
+        for arg in args.split_whitespace() {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got `{arg}`"))?;
+            match key {
+                "tools" => self.max_tool_calls = limit(value, parse_count)?,
+                "tokens" => self.max_tokens = limit(value, parse_count)?,
+                "time" => self.max_seconds = limit(value, parse_seconds)?,
+                _ => return Err(format!("unknown limit `{key}` (tools, tokens or time)")),
+            }
+        }
+        Ok(self)
+    }
+
+    /// `40 tool calls, 200k tokens, 10m 0s`
+    pub fn describe(&self) -> String {
+        let limits: Vec<String> = [
+            self.max_tool_calls.map(|n| count(n, "tool call")),
+            self.max_tokens.map(|n| format!("{} tokens", tokens(n))),
+            self.max_seconds
+                .map(|secs| timefmt::duration(Duration::from_secs(secs))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if limits.is_empty() {
+            "no limits".to_string()
+        } else {
+            limits.join(", ")
+        }
+    }
+}
+
+/// What a run has spent so far.
+pub struct RunSpend {
+    started: Instant,
+    pub tool_calls: usize,
+    pub tokens: usize,
+}
+
+impl RunSpend {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            tool_calls: 0,
+            tokens: 0,
+        }
+    }
+
+    /// The limit the run has reached, e.g. `40 tool calls (limit 40)`.
+    pub fn over(&self, budget: &RunBudget) -> Option<String> {
+        if let Some(max) = budget.max_tool_calls.filter(|max| self.tool_calls >= *max) {
+            return Some(format!(
+                "{} (limit {max})",
+                count(self.tool_calls, "tool call")
+            ));
+        }
+        if let Some(max) = budget.max_tokens.filter(|max| self.tokens >= *max) {
+            return Some(format!(
+                "{} tokens (limit {})",
+                tokens(self.tokens),
+                tokens(max)
+            ));
+        }
+        let elapsed = self.started.elapsed();
+        let max = budget.max_seconds.map(Duration::from_secs)?;
+        (elapsed >= max).then(|| {
+            format!(
+                "{} elapsed (limit {})",
+                timefmt::duration(elapsed),
+                timefmt::duration(max)
+            )
+        })
+    }
+
+    /// `12 tool calls, 48.2k tokens in 3m 5s`
+    pub fn describe(&self) -> String {
+        format!(
+            "{}, {} tokens in {}",
+            count(self.tool_calls, "tool call"),
+            tokens(self.tokens),
+            timefmt::duration(self.started.elapsed())
+        )
+    }
+}
+
+/// The final message of a run stopped at its budget: what it spent and
+/// changed, and the plan steps still open.
+pub fn stop_summary(over: &str, spend: &RunSpend, changed: &[String], plan: &[PlanItem]) -> String {
+    let mut out = format!(
+        "Stopped at the run budget: {over}.\n\nDone: {}.",
+        spend.describe()
+    );
+    if changed.is_empty() {
+        out.push_str(" No files changed.");
+    } else {
+        out.push_str(&format!(" Changed {}.", changed.join(", ")));
+    }
+    let remaining: Vec<&PlanItem> = plan.iter().filter(|item| !item.done).collect();
+    if !remaining.is_empty() {
+        out.push_str("\n\nRemaining:");
+        for item in remaining {
+            out.push_str(&format!("\n- {}", item.text));
+        }
+    }
+    out.push_str("\n\nAsk to continue for another run, or raise the limit with /budget.");
+    out
+}
+
+fn limit<T>(value: &str, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+    if matches!(value, "off" | "none") {
+        Ok(None)
+    } else {
+        parse(value).map(Some)
+    }
+}
+
+/// `40`, `200k`, `1.5m`
+fn parse_count(value: &str) -> Result<usize, String> {
+    let lower = value.to_ascii_lowercase();
+    let (number, scale) = match lower.strip_suffix('k') {
+        Some(number) => (number, 1_000.0),
+        None => match lower.strip_suffix('m') {
+            Some(number) => (number, 1_000_000.0),
+            None => (lower.as_str(), 1.0),
+        },
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * scale) as usize)
+        .ok_or_else(|| format!("`{value}` is not a count"))
+}
+
+/// `90s`, `10m`, `1h`; a bare number is seconds.
+fn parse_seconds(value: &str) -> Result<u64, String> {
+    let (number, scale) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 3_600),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|n| *n > 0)
+        .map(|n| n * scale)
+        .ok_or_else(|| format!("`{value}` is not a duration (e.g. 90s, 10m, 1h)"))
+}
+
+fn tokens(n: usize) -> String {
+    if n < 1_000 {
+        n.to_string()
+    } else if n.is_multiple_of(1_000) {
+        format!("{}k", n / 1_000)
+    } else {
+        format!("{:.1}k", n as f64 / 1_000.0)
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{n} {noun}{}", if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_limits_and_reports_the_one_reached() {
+        let config = RunBudget {
+            max_tool_calls: Some(60),
+            max_tokens: None,
+            max_seconds: Some(1_800),
+        };
+        let budget = config
+            .with_overrides("tools=40 tokens=200k time=off")
+            .unwrap();
+        assert_eq!(
+            budget,
+            RunBudget {
+                max_tool_calls: Some(40),
+                max_tokens: Some(200_000),
+                max_seconds: None,
+            }
+        );
+        assert_eq!(budget.describe(), "40 tool calls, 200k tokens");
+        assert_eq!(
+            RunBudget::default()
+                .with_overrides("time=10m")
+                .unwrap()
+                .max_seconds,
+            Some(600)
+        );
+        assert!(config.with_overrides("turns=3").is_err());
+        assert!(config.with_overrides("tools=many").is_err());
+
+        let mut spend = RunSpend::start();
+        spend.tool_calls = 39;
+        spend.tokens = 150_500;
+        assert!(spend.over(&budget).is_none());
+        spend.tool_calls = 40;
+        assert_eq!(
+            spend.over(&budget).as_deref(),
+            Some("40 tool calls (limit 40)")
+        );
+        spend.tool_calls = 3;
+        spend.tokens = 200_100;
+        assert_eq!(
+            spend.over(&budget).as_deref(),
+            Some("200.1k tokens (limit 200k)")
+        );
+
+        let plan = [
+            PlanItem {
+                text: "parse input".into(),
+                done: true,
+                active: false,
+                files: Vec::new(),
+            },
+            PlanItem {
+                text: "wire the command".into(),
+                done: false,
+                active: true,
+                files: Vec::new(),
+            },
+        ];
+        let summary = stop_summary(
+            "3 tool calls (limit 3)",
+            &spend,
+            &["src/a.rs".into()],
+            &plan,
+        );
+        assert!(summary.starts_with("Stopped at the run budget: 3 tool calls (limit 3)."));
+        assert!(summary.contains("Changed src/a.rs."));
+        assert!(summary.contains("Remaining:\n- wire the command"));
+        assert!(!summary.contains("parse input"));
+    }
+}
@@ -0,0 +1,304 @@
+//! sandbox.rs
+//!
+//! Confinement for the commands the agent runs through `run_shell` and
+//! `run_tests`, set in the `[sandbox]` section of
+//! `.osmogrep/permissions.toml`:
+//!
+//! ```toml
+//! [sandbox]
+//! cpu_secs = 600           # CPU time per process
+//! memory_mb = 4096         # address space per process
+//! open_files = 1024
+//! network = false          # no network access
+//! confine_writes = true    # only the repo root and temp dirs are writable
+//! writable = ["~/.cargo"]  # more writable paths under confine_writes
+//! ```
+//!
+//! Under `confine_writes` the repo's `.osmogrep/` and `.context/` stay
+//! read-only unless `[paths] internal_writes` is set, so a command cannot
+//! rewrite the policy that confines it.
+//!
+//! Limits are `ulimit`s in the command's shell. Turning off the network or
+//! confining writes needs bubblewrap (`bwrap`) on Linux or `sandbox-exec`
+//! on macOS; without one the command is refused rather than run
+//! unconfined.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::permissions::INTERNAL_DIRS;
+use crate::platform::Platform;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxPolicy {
+    pub cpu_secs: Option<u64>,
+    pub memory_mb: Option<u64>,
+    pub open_files: Option<u64>,
+    pub network: bool,
+    pub confine_writes: bool,
+    pub writable: Vec<String>,
+    /// Copied from `[paths] internal_writes`; see [`crate::permissions`].
+    #[serde(skip)]
+    pub internal_writes: bool,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            cpu_secs: None,
+            memory_mb: None,
+            open_files: None,
+            network: true,
+            confine_writes: false,
+            writable: Vec::new(),
+            internal_writes: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Isolator {
+    Bwrap(PathBuf),
+    SandboxExec,
+}
+
+impl SandboxPolicy {
+    /// `ulimit` commands for the configured limits, each ending in `; `.
+    /// Windows shells have no `ulimit`, so there it is empty.
+    pub fn ulimits(&self) -> String {
+        if Platform::current() == Platform::Windows {
+            return String::new();
+        }
+        [
+            ("-t", self.cpu_secs),
+            ("-v", self.memory_mb.map(|mb| mb * 1024)),
+            ("-n", self.open_files),
+        ]
+        .iter()
+        .filter_map(|(flag, value)| value.map(|value| format!("ulimit {flag} {value}; ")))
+        .collect()
+    }
+
+    fn isolated(&self) -> bool {
+        !self.network || self.confine_writes
+    }
+
+    /// `argv` as run inside the sandbox; unchanged when neither the network
+    /// nor writes are restricted.
+    pub fn wrap_argv(&self, repo_root: &Path, argv: Vec<String>) -> Result<Vec<String>, String> {
+        if !self.isolated() {
+            return Ok(argv);
+        }
+        let isolator = find_isolator().ok_or(
+            "[sandbox] network = false and confine_writes need bwrap (Linux) or sandbox-exec (macOS)",
+        )?;
+        // bwrap can only keep existing directories read-only; without them
+        // a command could create the policy file.
+        for dir in self.read_only_paths(repo_root) {
+            fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        }
+        Ok(self.isolated_argv(&isolator, repo_root, argv))
+    }
+
+    /// The platform shell running `cmd` under the limits and inside the
+    /// sandbox.
+    pub fn shell_argv(&self, repo_root: &Path, cmd: &str) -> Result<Vec<String>, String> {
+        let script = format!("{}{cmd}", self.ulimits());
+        self.wrap_argv(repo_root, Platform::current().shell_argv(&script))
+    }
+
+    fn writable_paths(&self, repo_root: &Path) -> Vec<String> {
+        let mut paths = vec![
+            repo_root.display().to_string(),
+            std::env::temp_dir().display().to_string(),
+        ];
+        paths.extend(
+            self.writable
+                .iter()
+                .map(|path| crate::images::expand_home(path).display().to_string()),
+        );
+        paths
+    }
+
+    /// Repo directories kept read-only inside a `confine_writes` sandbox.
+    fn read_only_paths(&self, repo_root: &Path) -> Vec<PathBuf> {
+        if !self.confine_writes || self.internal_writes {
+            return Vec::new();
+        }
+        INTERNAL_DIRS
+            .iter()
+            .map(|dir| repo_root.join(dir))
+            .collect()
+    }
+
+    fn isolated_argv(
+        &self,
+        isolator: &Isolator,
+        repo_root: &Path,
+        argv: Vec<String>,
+    ) -> Vec<String> {
+        let mut out = Vec::new();
+        match isolator {
+            Isolator::Bwrap(bwrap) => {
+                out.push(bwrap.display().to_string());
+                out.push("--die-with-parent".into());
+                if self.confine_writes {
+                    out.extend(
+                        ["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]
+                            .map(String::from),
+                    );
+                    for path in self.writable_paths(repo_root) {
+                        if Path::new(&path).exists() {
+                            out.extend(["--bind".to_string(), path.clone(), path]);
+                        }
+                    }
+                    for path in self.read_only_paths(repo_root) {
+                        if path.exists() {
+                            let path = path.display().to_string();
+                            out.extend(["--ro-bind".to_string(), path.clone(), path]);
+                        }
+                    }
+                } else {
+                    out.extend(["--dev-bind", "/", "/"].map(String::from));
+                }
+                if !self.network {
+                    out.push("--unshare-net".into());
+                }
+            }
+            Isolator::SandboxExec => {
+                let mut profile = String::from("(version 1)(allow default)");
+                if !self.network {
+                    profile.push_str("(deny network*)");
+                }
+                if self.confine_writes {
+                    profile.push_str("(deny file-write*)(allow file-write* (subpath \"/dev\")");
+                    for path in self.writable_paths(repo_root) {
+                        // /tmp and /var are symlinks into /private.
+                        let real = fs::canonicalize(&path)
+                            .map(|p| p.display().to_string())
+                            .unwrap_or(path);
+                        profile.push_str(&format!(" (subpath {real:?})"));
+                    }
+                    profile.push(')');
+                    let read_only = self.read_only_paths(repo_root);
+                    if !read_only.is_empty() {
+                        profile.push_str("(deny file-write*");
+                        for path in read_only {
+                            let real = fs::canonicalize(&path).unwrap_or(path);
+                            let real = real.display().to_string();
+                            profile.push_str(&format!(" (subpath {real:?})"));
+                        }
+                        profile.push(')');
+                    }
+                }
+                out.extend(["sandbox-exec".to_string(), "-p".to_string(), profile]);
+            }
+        }
+        out.extend(argv);
+        out
+    }
+}
+
+fn find_isolator() -> Option<Isolator> {
+    if cfg!(target_os = "macos") {
+        return Path::new("/usr/bin/sandbox-exec")
+            .exists()
+            .then_some(Isolator::SandboxExec);
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join("bwrap"))
+        .find(|bwrap| bwrap.is_file())
+        .map(Isolator::Bwrap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn limits_become_ulimits() {
+        let policy: SandboxPolicy =
+            toml::from_str("cpu_secs = 60\nmemory_mb = 512\nopen_files = 64").unwrap();
+        let argv = policy
+            .shell_argv(&std::env::temp_dir(), "ulimit -n")
+            .unwrap();
+        assert_eq!(
+            argv,
+            [
+                "sh",
+                "-c",
+                "ulimit -t 60; ulimit -v 524288; ulimit -n 64; ulimit -n"
+            ]
+        );
+        let out = std::process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "64");
+    }
+
+    #[test]
+    fn isolation_wraps_the_command() {
+        let root = std::env::temp_dir();
+        let policy: SandboxPolicy = toml::from_str("network = false").unwrap();
+        let argv = policy.isolated_argv(
+            &Isolator::Bwrap("/usr/bin/bwrap".into()),
+            &root,
+            vec!["true".into()],
+        );
+        assert_eq!(
+            argv,
+            [
+                "/usr/bin/bwrap",
+                "--die-with-parent",
+                "--dev-bind",
+                "/",
+                "/",
+                "--unshare-net",
+                "true"
+            ]
+        );
+
+        let policy = SandboxPolicy {
+            confine_writes: true,
+            ..SandboxPolicy::default()
+        };
+        let argv = policy.isolated_argv(&Isolator::SandboxExec, &root, vec!["true".into()]);
+        assert_eq!(argv[..2], ["sandbox-exec", "-p"]);
+        assert!(argv[2].starts_with("(version 1)(allow default)(deny file-write*)"));
+        let real = |dir: &str| {
+            let path = root.join(dir);
+            fs::canonicalize(&path)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        assert!(argv[2].ends_with(&format!(
+            "(deny file-write* (subpath {:?}) (subpath {:?}))",
+            real(".osmogrep"),
+            real(".context")
+        )));
+        assert!(!argv[2].contains("network"));
+
+        let repo = root.join(format!("osmogrep-sandbox-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(repo.join(".osmogrep")).unwrap();
+        let argv = policy.isolated_argv(&Isolator::Bwrap("bwrap".into()), &repo, vec![]);
+        let config = repo.join(".osmogrep").display().to_string();
+        let at = argv.iter().position(|arg| arg == &config).unwrap();
+        assert_eq!(argv[at - 1], "--ro-bind");
+        assert!(!argv.contains(&repo.join(".context").display().to_string()));
+        let lifted = SandboxPolicy {
+            internal_writes: true,
+            ..policy.clone()
+        };
+        let argv = lifted.isolated_argv(&Isolator::Bwrap("bwrap".into()), &repo, vec![]);
+        assert!(!argv.contains(&config));
+        let _ = fs::remove_dir_all(repo);
+        assert!(toml::from_str::<SandboxPolicy>("netwrok = false").is_err());
+    }
+}
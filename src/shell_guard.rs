@@ -1,3 +1,9 @@
+//! shell_guard.rs
+//!
+//! Shell commands the agent may never run, and [`simple_command_words`],
+//! which decides whether a command is plain enough for an allow rule or a
+//! remembered approval to cover it.
+
 use regex::Regex;
 
 pub fn check_shell_command(cmd: &str) -> Result<(), String> {
@@ -22,6 +28,89 @@ pub fn check_shell_command(cmd: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// The words of `cmd` when it is one plain command a glob over the command
+/// line fully describes: no chaining, pipes, background `&`, redirects,
+/// subshells, substitutions or `$` expansions. None otherwise, so allow
+/// rules and remembered approvals never pre-approve what they cannot read.
+pub fn simple_command_words(cmd: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = cmd.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() && c != '\n' => {
+                words.extend(word.take());
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '$' | '`' => return None,
+                        '\\' => word.push(chars.next()?),
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+            ';' | '&' | '|' | '<' | '>' | '(' | ')' | '`' | '$' | '\n' => return None,
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    (!words.is_empty()).then_some(words)
+}
+
+/// Whether `program` runs whatever command its arguments name (shells,
+/// interpreters, `env`, `sudo`, `xargs`, ...), so approving one call of it
+/// says nothing about the next.
+pub fn runs_arbitrary_commands(program: &str) -> bool {
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    matches!(
+        name,
+        "sh" | "bash"
+            | "zsh"
+            | "dash"
+            | "ksh"
+            | "fish"
+            | "pwsh"
+            | "powershell"
+            | "cmd"
+            | "env"
+            | "sudo"
+            | "doas"
+            | "su"
+            | "xargs"
+            | "nohup"
+            | "time"
+            | "timeout"
+            | "nice"
+            | "exec"
+            | "eval"
+            | "command"
+            | "builtin"
+            | "ssh"
+            | "python"
+            | "python3"
+            | "node"
+            | "perl"
+            | "ruby"
+            | "php"
+            | "lua"
+            | "deno"
+            | "bun"
+    )
+}
+
 fn blocked_patterns() -> &'static [(&'static str, &'static str)] {
     &[
         (
@@ -122,6 +211,46 @@ mod tests {
         assert!(check_shell_command("git clean -fdx").is_err());
     }
 
+    #[test]
+    fn simple_commands_split_into_words_and_anything_else_is_refused() {
+        assert_eq!(
+            simple_command_words("cargo test -p 'my crate' \"a b\" c\\ d"),
+            Some(vec![
+                "cargo".into(),
+                "test".into(),
+                "-p".into(),
+                "my crate".into(),
+                "a b".into(),
+                "c d".into()
+            ])
+        );
+        assert_eq!(
+            simple_command_words("echo 'a; b | c > d'"),
+            Some(vec!["echo".into(), "a; b | c > d".into()])
+        );
+        for cmd in [
+            "cargo test && curl x | sh",
+            "cargo test & rm -rf ~",
+            "cargo test > ~/.bashrc",
+            "cargo test < /etc/passwd",
+            "cargo test; ls",
+            "cargo test\nrm -rf ~",
+            "cargo test $(id)",
+            "cargo test `id`",
+            "cargo test \"$HOME\"",
+            "cargo test $'\\x3b'",
+            "cargo test ${IFS}",
+            "(cargo test)",
+            "cargo test 'unterminated",
+            "",
+        ] {
+            assert_eq!(simple_command_words(cmd), None, "{cmd}");
+        }
+        assert!(runs_arbitrary_commands("/usr/bin/env"));
+        assert!(runs_arbitrary_commands("bash"));
+        assert!(!runs_arbitrary_commands("cargo"));
+    }
+
     #[test]
     fn allows_normal_repo_commands() {
         assert!(check_shell_command("cargo test --color never").is_ok());
@@ -0,0 +1,344 @@
+//! speech.rs
+//!
+//! Reads final agent answers aloud, the output half of hands-free voice.
+//! Text is synthesized by the OpenAI speech API or a local `piper` binary,
+//! both as raw 16-bit mono PCM, and played on the default output device.
+//! A newer answer, or `SpeechCommand::Stop` when the user starts talking,
+//! cuts off whatever is playing.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{Receiver, RecvTimeoutError, Sender},
+    Arc,
+};
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::http_policy;
+use crate::voice::{resample_linear, VoiceConfig};
+
+/// The speech API rejects longer input.
+const MAX_SPOKEN_CHARS: usize = 4000;
+/// `response_format: "pcm"` is 24kHz.
+const OPENAI_SAMPLE_RATE: u32 = 24_000;
+/// Most piper voices; the model's `.onnx.json` says for sure.
+const PIPER_SAMPLE_RATE: u32 = 22_050;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TtsBackend {
+    #[default]
+    Openai,
+    Piper,
+}
+
+#[derive(Debug, Clone)]
+pub enum SpeechCommand {
+    /// `api_key` is only used by the OpenAI backend.
+    Speak {
+        text: String,
+        api_key: Option<String>,
+    },
+    Stop,
+}
+
+#[derive(Debug, Clone)]
+pub enum SpeechEvent {
+    Started,
+    Finished,
+    Error(String),
+}
+
+pub fn spawn_speech_worker(
+    cmd_rx: Receiver<SpeechCommand>,
+    evt_tx: Sender<SpeechEvent>,
+    config: VoiceConfig,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut next = None;
+        loop {
+            let cmd = match next.take() {
+                Some(cmd) => cmd,
+                None => match cmd_rx.recv() {
+                    Ok(cmd) => cmd,
+                    Err(_) => break,
+                },
+            };
+            let SpeechCommand::Speak { text, api_key } = cmd else {
+                continue;
+            };
+            let spoken = speakable_text(&text);
+            if spoken.is_empty() {
+                continue;
+            }
+
+            let audio = match config.tts {
+                TtsBackend::Openai => synthesize_openai(&spoken, api_key.as_deref(), &config),
+                TtsBackend::Piper => synthesize_piper(&spoken, &config),
+            };
+            let result = audio.and_then(|(samples, rate)| {
+                let _ = evt_tx.send(SpeechEvent::Started);
+                play(&samples, rate, &cmd_rx)
+            });
+            match result {
+                Ok(interrupted_by) => next = interrupted_by,
+                Err(e) => {
+                    let _ = evt_tx.send(SpeechEvent::Error(e));
+                }
+            }
+            let _ = evt_tx.send(SpeechEvent::Finished);
+        }
+    })
+}
+
+/// Markdown as it should sound: code blocks and URLs are skipped, link
+/// text and inline code are kept, and markup characters are dropped.
+pub fn speakable_text(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || trimmed.starts_with('|') {
+            continue;
+        }
+        let trimmed = trimmed
+            .trim_start_matches('#')
+            .trim_start_matches('>')
+            .trim_start();
+        let trimmed = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .unwrap_or(trimmed);
+        if !trimmed.is_empty() {
+            lines.push(strip_inline_markup(trimmed));
+        }
+    }
+
+    let mut text = lines.join("\n");
+    if text.chars().count() > MAX_SPOKEN_CHARS {
+        let cut = text
+            .char_indices()
+            .nth(MAX_SPOKEN_CHARS)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len());
+        // End on a sentence rather than mid-word where possible.
+        let end = text[..cut].rfind(['.', '\n']).map(|i| i + 1).unwrap_or(cut);
+        text.truncate(end);
+    }
+    text.trim().to_string()
+}
+
+fn strip_inline_markup(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        // [text](url) reads as its text.
+        if c == '[' {
+            if let Some((label, after)) = rest[1..].split_once("](") {
+                if let Some(close) = after.find(')') {
+                    out.push_str(label);
+                    rest = &after[close + 1..];
+                    continue;
+                }
+            }
+        }
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = &rest[end..];
+            continue;
+        }
+        if !matches!(c, '*' | '_' | '`' | '~') {
+            out.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn synthesize_openai(
+    text: &str,
+    api_key: Option<&str>,
+    config: &VoiceConfig,
+) -> Result<(Vec<f32>, u32), String> {
+    let api_key = api_key.ok_or("Speech needs OPENAI_API_KEY")?;
+    let body = json!({
+        "model": config.tts_model.as_deref().unwrap_or("gpt-4o-mini-tts"),
+        "voice": config.tts_voice.as_deref().unwrap_or("alloy"),
+        "input": text,
+        "response_format": "pcm",
+    });
+    let client = crate::net::client_builder()?
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let bytes = http_policy::send(
+        client
+            .post("https://api.openai.com/v1/audio/speech")
+            .bearer_auth(api_key)
+            .json(&body),
+    )?
+    .error_for_status()
+    .map_err(|e| format!("Speech request failed: {e}"))?
+    .bytes()
+    .map_err(|e| e.to_string())?;
+    Ok((pcm_samples(&bytes), OPENAI_SAMPLE_RATE))
+}
+
+fn synthesize_piper(text: &str, config: &VoiceConfig) -> Result<(Vec<f32>, u32), String> {
+    let model = config
+        .piper_model
+        .as_ref()
+        .ok_or("voice.tts = \"piper\" needs voice.piper_model")?;
+    let mut child = Command::new("piper")
+        .arg("--model")
+        .arg(model)
+        .arg("--output-raw")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run piper: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to run piper: {e}"))?;
+    }
+    let out = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run piper: {e}"))?;
+    if !out.status.success() {
+        return Err(format!("piper exited with {}", out.status));
+    }
+    Ok((pcm_samples(&out.stdout), piper_sample_rate(model)))
+}
+
+fn piper_sample_rate(model: &Path) -> u32 {
+    let mut config = PathBuf::from(model).into_os_string();
+    config.push(".json");
+    fs::read_to_string(config)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        .and_then(|cfg| cfg["audio"]["sample_rate"].as_u64())
+        .map(|rate| rate as u32)
+        .unwrap_or(PIPER_SAMPLE_RATE)
+}
+
+/// Little-endian signed 16-bit mono, as both backends emit it.
+fn pcm_samples(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32)
+        .collect()
+}
+
+/// Plays until the audio ends or a command arrives. A new `Speak` is
+/// handed back so it plays next.
+fn play(
+    samples: &[f32],
+    sample_rate: u32,
+    cmd_rx: &Receiver<SpeechCommand>,
+) -> Result<Option<SpeechCommand>, String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("No output audio device found")?;
+    let default_config = device
+        .default_output_config()
+        .map_err(|e: cpal::DefaultStreamConfigError| e.to_string())?;
+    let config = default_config.config();
+    let samples = Arc::new(resample_linear(samples, sample_rate, config.sample_rate.0));
+    let position = Arc::new(AtomicUsize::new(0));
+
+    let stream = match default_config.sample_format() {
+        SampleFormat::F32 => build_output::<f32>(&device, &config, &samples, &position, |s| s),
+        SampleFormat::I16 => build_output::<i16>(&device, &config, &samples, &position, |s| {
+            (s * i16::MAX as f32) as i16
+        }),
+        SampleFormat::U16 => build_output::<u16>(&device, &config, &samples, &position, |s| {
+            ((s + 1.0) * 0.5 * u16::MAX as f32) as u16
+        }),
+        _ => Err("Unsupported sample format".into()),
+    }?;
+    stream
+        .play()
+        .map_err(|e| format!("Audio stream failed to start: {e}"))?;
+
+    while position.load(Ordering::Relaxed) < samples.len() {
+        match cmd_rx.recv_timeout(Duration::from_millis(20)) {
+            Ok(SpeechCommand::Stop) | Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            Ok(speak) => return Ok(Some(speak)),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+    // Let the device drain its last buffer.
+    thread::sleep(Duration::from_millis(150));
+    Ok(None)
+}
+
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: &Arc<Vec<f32>>,
+    position: &Arc<AtomicUsize>,
+    convert: fn(f32) -> T,
+) -> Result<Stream, String>
+where
+    T: cpal::SizedSample + Send + 'static,
+{
+    let channels = config.channels as usize;
+    let samples = samples.clone();
+    let position = position.clone();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                for frame in data.chunks_mut(channels) {
+                    let i = position.fetch_add(1, Ordering::Relaxed);
+                    let value = convert(samples.get(i).copied().unwrap_or(0.0));
+                    frame.fill(value);
+                }
+            },
+            |err| eprintln!("Audio stream error: {err}"),
+            None,
+        )
+        .map_err(|e: cpal::BuildStreamError| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speakable_text_drops_code_and_markup() {
+        let answer = "## Fixed\n\
+                      The **parser** now handles `None`; see [the docs](https://example.com/p).\n\
+                      ```rust\nfn parse() {}\n```\n\
+                      - Ran `cargo test` at https://ci.example.com/run/1\n\
+                      | file | lines |\n";
+        assert_eq!(
+            speakable_text(answer),
+            "Fixed\nThe parser now handles None; see the docs.\nRan cargo test at"
+        );
+        assert_eq!(pcm_samples(&[0xff, 0x7f, 0x00, 0x00, 0x01]), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn long_answers_are_cut_at_a_sentence() {
+        let answer = "Done. ".repeat(1000);
+        let spoken = speakable_text(&answer);
+        assert!(spoken.chars().count() <= MAX_SPOKEN_CHARS);
+        assert!(spoken.ends_with("Done."));
+    }
+}
@@ -1,11 +1,35 @@
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
 use std::time::Instant;
 
+use crate::agent::SubagentStatus;
+use crate::candidate_queue::CandidateQueue;
+use crate::change_budget::BudgetDecision;
+use crate::custom_commands::CustomCommand;
+use crate::detach::EventTail;
+use crate::file_refs::RefHit;
+use crate::images::{self, ImageAttachment};
+use crate::instance::RepoLock;
+use crate::keymap::Keymap;
+use crate::logger::is_tool_call_log;
+use crate::piped_input::PipedInput;
+use crate::pricing::ModelPrice;
+use crate::pull_request::PrDraft;
+use crate::review_queue::ReviewQueue;
+use crate::run_budget::RunBudget;
+use crate::ui::scroll::ScrollMetrics;
+use crate::voice::{PttKey, VoiceActivation, VoiceBackend, WAKE_WINDOW};
+use crate::worktree::Sandbox;
+
 pub const MAX_LOGS: usize = 1000;
+/// Pastes longer than this go in as a `[pasted N lines]` placeholder.
+pub const PASTE_ATTACH_LINES: usize = 20;
+pub const PASTE_ATTACH_CHARS: usize = 2000;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InputMode {
@@ -23,21 +47,44 @@ pub enum LogLevel {
     Error,
 }
 
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Success => "ok",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LogLine {
+    /// Unique for the session, unlike the position, which shifts as old
+    /// lines are dropped.
+    pub id: u64,
     pub level: LogLevel,
     pub text: String,
     pub at: Instant,
+    pub wall: DateTime<Local>,
 }
 
 pub struct LogBuffer {
     logs: VecDeque<LogLine>,
+    next_id: u64,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LogBuffer {
     pub fn new() -> Self {
         Self {
             logs: VecDeque::with_capacity(MAX_LOGS),
+            next_id: 0,
         }
     }
 
@@ -50,10 +97,13 @@ impl LogBuffer {
             self.logs.pop_front();
         }
 
+        self.next_id += 1;
         self.logs.push_back(LogLine {
+            id: self.next_id,
             level,
             text: text.into(),
             at: Instant::now(),
+            wall: Local::now(),
         });
     }
 
@@ -62,18 +112,107 @@ impl LogBuffer {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiffSnapshot {
     pub tool: String,
     pub target: String,
     pub before: String,
     pub after: String,
+    /// The file did not exist before the change.
+    #[serde(default)]
+    pub created: bool,
+    /// The file no longer exists after the change.
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+impl DiffSnapshot {
+    /// Whether the file ends up different from how it started: other
+    /// content, or created or deleted. A file created and deleted again is
+    /// unchanged.
+    pub fn changed(&self) -> bool {
+        self.before != self.after || self.created != self.deleted
+    }
+}
+
+/// One snapshot per file for a run of changes: the file before its first
+/// change and after its last, in the order files were first touched.
+/// Files that ended up as they started are left out.
+pub fn merge_changes(changes: &[DiffSnapshot]) -> Vec<DiffSnapshot> {
+    let mut merged = merge_per_file(changes);
+    merged.retain(DiffSnapshot::changed);
+    merged
+}
+
+/// The session's changes as one snapshot per file: the file before the
+/// session first changed it and as it is under `root` now, marked deleted
+/// when it is gone.
+pub fn session_diff(changes: &[DiffSnapshot], root: &Path) -> Vec<DiffSnapshot> {
+    let mut merged = merge_per_file(changes);
+    for snap in &mut merged {
+        match std::fs::read_to_string(root.join(&snap.target)) {
+            Ok(text) => {
+                snap.after = text;
+                snap.deleted = false;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                snap.after.clear();
+                snap.deleted = true;
+            }
+            // Unreadable or not UTF-8: the last snapshot is the best we have.
+            Err(_) => {}
+        }
+    }
+    merged.retain(DiffSnapshot::changed);
+    merged
+}
+
+fn merge_per_file(changes: &[DiffSnapshot]) -> Vec<DiffSnapshot> {
+    let mut merged: Vec<DiffSnapshot> = Vec::new();
+    for change in changes {
+        match merged.iter_mut().find(|m| m.target == change.target) {
+            Some(m) => {
+                m.tool = change.tool.clone();
+                m.after = change.after.clone();
+                m.deleted = change.deleted;
+            }
+            None => merged.push(change.clone()),
+        }
+    }
+    merged
 }
 
-#[derive(Clone, Copy)]
+/// A tab in the status line's tab bar; each is an independent session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabLabel {
+    pub title: String,
+    pub running: bool,
+    /// What in a tab behind this one would be cut off by exiting.
+    pub exit_blockers: Vec<String>,
+}
+
+/// A tab change asked for by a key or `/tab`, applied by the main loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TabRequest {
+    /// Zero-based tab index.
+    Switch(usize),
+    /// A new session in this repo, or in the given one.
+    Open(Option<PathBuf>),
+    Close,
+}
+
+/// A large paste, kept out of the input box. `label` stands in for it in
+/// the input until the prompt is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PastedText {
+    pub label: String,
+    pub text: String,
+}
+
+#[derive(Clone)]
 pub struct CommandItem {
-    pub cmd: &'static str,
-    pub desc: &'static str,
+    pub cmd: String,
+    pub desc: String,
 }
 
 pub struct UiState {
@@ -81,7 +220,23 @@ pub struct UiState {
     pub input: String,
     pub input_cursor: usize,
     pub input_clipboard: String,
+    /// The last key killed text, so the next kill extends the same yank.
+    pub input_kill_chain: bool,
+    /// Every large paste this session, so recalled history still expands.
+    pub pastes: Vec<PastedText>,
+    /// Images to send with the next prompt.
+    pub images: Vec<ImageAttachment>,
+    /// Text piped to stdin at startup, sent with the next prompt.
+    pub piped_input: Option<PipedInput>,
     pub input_all_selected: bool,
+    /// Tool-call blocks in the execution log showing all of their output,
+    /// by header log id. The rest show one line.
+    pub expanded_tools: HashSet<u64>,
+    /// The tool-call block Enter toggles; the latest one when unset.
+    pub selected_tool: Option<u64>,
+    pub keymap: Keymap,
+    /// Prompt templates from `.osmogrep/commands/`, offered as `/name`.
+    pub custom_commands: Vec<CustomCommand>,
     pub input_mode: InputMode,
     pub input_masked: bool,
     pub input_placeholder: Option<String>,
@@ -89,6 +244,9 @@ pub struct UiState {
     pub should_exit: bool,
     pub indexing: bool,
     pub indexed: bool,
+    /// Files the index watcher is re-parsing right now.
+    pub index_refreshing: usize,
+    pub index_updated_at: Option<DateTime<Local>>,
     pub history: Vec<String>,
     pub history_index: Option<usize>,
     pub hint: Option<String>,
@@ -100,6 +258,13 @@ pub struct UiState {
     pub last_activity: Instant,
     pub exec_scroll: usize,
     pub follow_tail: bool,
+    /// Execution panel scroll extent from the last frame, for click-to-jump.
+    pub exec_metrics: Cell<ScrollMetrics>,
+    /// `path:line` references visible in the execution panel last frame.
+    pub exec_refs: RefCell<Vec<RefHit>>,
+    pub diff_minimap: bool,
+    /// Show the session's changes beside the execution log.
+    pub split_diff: bool,
     pub active_spinner: Option<String>,
     pub spinner_started_at: Option<Instant>,
     pub agent_running: bool,
@@ -110,13 +275,40 @@ pub struct UiState {
     pub current_tool: Option<String>,
     pub current_tool_detail: Option<String>,
     pub last_tool_status: Option<String>,
+    /// `spawn_subagent` children of the current run, in start order.
+    pub subagents: Vec<SubagentStatus>,
+    /// A transient failure was already continued automatically; the next
+    /// one waits for `/continue`.
+    pub auto_continued: bool,
+    /// The terminal reported losing focus; notifications only go out then.
+    pub focus_lost: bool,
     pub cancel_requested: bool,
     pub auto_approve: bool,
     pub pending_permission: Option<PendingPermission>,
+    pub pending_budget: Option<PendingBudget>,
     pub pending_update: Option<PendingUpdate>,
     pub update_check_status: Option<String>,
     pub update_install_requested: bool,
     pub update_skip_requested: bool,
+    pub config_reload_requested: bool,
+    pub pending_exit: Option<PendingExit>,
+    /// `/pr` draft awaiting `/pr create`.
+    pub pending_pr: Option<PrDraft>,
+    /// Per-file accept/reject of a run's changes, open until confirmed.
+    pub review: Option<ReviewQueue>,
+    /// `/testgen candidates` picker, open until materialized or closed.
+    pub candidates: Option<CandidateQueue>,
+    /// Index into `session_changes` where the latest run's changes start.
+    pub run_changes_from: usize,
+    /// Index into `session_changes` where edits not yet promoted from the
+    /// sandbox start.
+    pub sandbox_changes_from: usize,
+    /// Exit as soon as the work that held up an exit is done.
+    pub exit_when_idle: bool,
+    /// Detached run being followed, by id.
+    pub attached_run: Option<(String, EventTail)>,
+    /// Worktrees of sandboxed `/queue` tasks, by job id, until reviewed.
+    pub task_sandboxes: Vec<(u64, Sandbox)>,
     pub streaming_buffer: String,
     pub streaming_transcript: String,
     pub last_rendered_output: Option<String>,
@@ -126,6 +318,20 @@ pub struct UiState {
     pub active_edit_target: Option<String>,
     pub queued_agent_prompt: Option<String>,
     pub repo_branch: Option<String>,
+    /// What `/inspect` compares against when it is not the uncommitted
+    /// changes, e.g. `branch vs origin/main`.
+    pub diff_baseline: Option<String>,
+    /// Languages of the detected test frameworks; refreshed when the agent
+    /// touches a manifest.
+    pub repo_languages: Vec<String>,
+    /// Every open tab, in order; the main loop refreshes it.
+    pub tabs: Vec<TabLabel>,
+    pub active_tab: usize,
+    pub tab_request: Option<TabRequest>,
+    /// Editor command for the main loop to run with the TUI suspended.
+    pub editor_request: Option<String>,
+    /// Another tab already has this repo open; this session is not saved.
+    pub scratch_tab: bool,
 }
 
 impl Default for UiState {
@@ -134,7 +340,15 @@ impl Default for UiState {
             input: String::new(),
             input_cursor: 0,
             input_clipboard: String::new(),
+            input_kill_chain: false,
+            pastes: Vec::new(),
+            images: Vec::new(),
+            piped_input: None,
             input_all_selected: false,
+            expanded_tools: HashSet::new(),
+            selected_tool: None,
+            keymap: Keymap::default(),
+            custom_commands: Vec::new(),
             input_mode: InputMode::AgentText,
             input_masked: false,
             input_placeholder: None,
@@ -142,6 +356,8 @@ impl Default for UiState {
             should_exit: false,
             indexing: false,
             indexed: false,
+            index_refreshing: 0,
+            index_updated_at: None,
             history: Vec::new(),
             history_index: None,
             hint: None,
@@ -153,6 +369,10 @@ impl Default for UiState {
             last_activity: Instant::now(),
             exec_scroll: usize::MAX,
             follow_tail: true,
+            exec_metrics: Cell::new(ScrollMetrics::default()),
+            exec_refs: RefCell::new(Vec::new()),
+            diff_minimap: true,
+            split_diff: false,
             active_spinner: None,
             spinner_started_at: None,
             agent_running: false,
@@ -163,13 +383,27 @@ impl Default for UiState {
             current_tool: None,
             current_tool_detail: None,
             last_tool_status: None,
+            subagents: Vec::new(),
+            auto_continued: false,
+            focus_lost: false,
             cancel_requested: false,
             auto_approve: false,
             pending_permission: None,
+            pending_budget: None,
             pending_update: None,
             update_check_status: None,
             update_install_requested: false,
             update_skip_requested: false,
+            config_reload_requested: false,
+            pending_exit: None,
+            pending_pr: None,
+            review: None,
+            candidates: None,
+            run_changes_from: 0,
+            sandbox_changes_from: 0,
+            exit_when_idle: false,
+            attached_run: None,
+            task_sandboxes: Vec::new(),
             streaming_buffer: String::new(),
             streaming_transcript: String::new(),
             last_rendered_output: None,
@@ -179,6 +413,13 @@ impl Default for UiState {
             active_edit_target: None,
             queued_agent_prompt: None,
             repo_branch: None,
+            diff_baseline: None,
+            repo_languages: Vec::new(),
+            tabs: Vec::new(),
+            active_tab: 0,
+            tab_request: None,
+            editor_request: None,
+            scratch_tab: false,
         }
     }
 }
@@ -191,6 +432,12 @@ pub struct AgentState {
     pub undo_stack: Vec<DiffSnapshot>,
     pub usage: UsageStats,
     pub steer: Option<String>,
+    /// Steers of the current run, oldest first; a new run starts a new list.
+    pub steer_history: Vec<SteerRecord>,
+    /// `/budget` limits replacing `[run_budget]` for this session's runs.
+    pub run_budget: Option<RunBudget>,
+    /// The last run, if it failed; `/continue` resumes it.
+    pub interrupted: Option<InterruptedRun>,
     pub auto_eval: bool,
     pub permission_profile: PermissionProfile,
     pub jobs: Vec<JobRecord>,
@@ -201,10 +448,20 @@ pub struct AgentState {
     pub theme: UiTheme,
     pub accent: UiAccent,
     pub density: UiDensity,
+    pub timestamps: LogTimestamps,
     pub plan_mode: bool,
+    /// The plan was approved with `/plan approve`; runs execute it and may
+    /// only edit the files its unfinished steps target.
+    pub plan_approved: bool,
 
     pub started_at: Instant,
     pub repo_root: PathBuf,
+    /// Held while this instance owns the repo; `None` when attached read-only.
+    pub repo_lock: Option<RepoLock>,
+    /// Worktree the agent edits while `/sandbox on`.
+    pub sandbox: Option<Sandbox>,
+    /// Base of the `/rebase` in progress; its runs ask before every edit.
+    pub rebase: Option<String>,
     pub voice: VoiceState,
     pub conversation: ConversationHistory,
 }
@@ -311,6 +568,34 @@ impl Default for UiDensity {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTimestamps {
+    #[default]
+    Off,
+    Absolute,
+    Relative,
+}
+
+impl LogTimestamps {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogTimestamps::Off => "off",
+            LogTimestamps::Absolute => "absolute",
+            LogTimestamps::Relative => "relative",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "hide" => Some(Self::Off),
+            "absolute" | "abs" | "clock" | "on" => Some(Self::Absolute),
+            "relative" | "rel" | "ago" => Some(Self::Relative),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PermissionProfile {
@@ -344,6 +629,11 @@ pub enum JobKind {
     Swarm,
     Test,
     Review,
+    /// A `/queue` task: an agent run in the checkout, or in its own sandbox
+    /// worktree so several can run at once.
+    Agent {
+        sandboxed: bool,
+    },
 }
 
 impl JobKind {
@@ -352,6 +642,7 @@ impl JobKind {
             JobKind::Swarm => "swarm",
             JobKind::Test => "test",
             JobKind::Review => "review",
+            JobKind::Agent { .. } => "agent",
         }
     }
 }
@@ -373,6 +664,30 @@ pub struct JobRecord {
     pub input: String,
     pub status: JobStatus,
     pub output: Option<String>,
+    /// Detached run of an agent task, once it started.
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+/// An instruction sent to the current run while it works: `/steer now`,
+/// a template or text typed mid-run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SteerRecord {
+    pub text: String,
+    pub at: DateTime<Local>,
+    /// Injected into the running agent; `false` when it launched a run.
+    pub live: bool,
+}
+
+/// A run that stopped on an error, as of its last model turn.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InterruptedRun {
+    /// The model input of that turn: the conversation plus the run's tool
+    /// calls and outputs so far.
+    pub messages: Vec<Value>,
+    pub error: String,
+    /// The model request failed in a way worth retrying.
+    pub transient: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -388,9 +703,133 @@ pub struct PlanItem {
     pub done: bool,
     #[serde(default)]
     pub active: bool,
+    /// Files or globs the step expects to change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
 }
 
 impl AgentState {
+    /// Where agent runs read and write: the sandbox when one is active,
+    /// otherwise the checkout.
+    pub fn edit_root(&self) -> &Path {
+        self.sandbox
+            .as_ref()
+            .map_or(self.repo_root.as_path(), |s| s.path.as_path())
+    }
+
+    /// Work that exiting right now would cut off, in this tab's session
+    /// or in any other tab.
+    pub fn exit_blockers(&self) -> Vec<String> {
+        let mut reasons = self.session_exit_blockers();
+        for (i, tab) in self.ui.tabs.iter().enumerate() {
+            if i == self.ui.active_tab {
+                continue;
+            }
+            for reason in &tab.exit_blockers {
+                reasons.push(format!("in tab {} ({}), {reason}", i + 1, tab.title));
+            }
+        }
+        reasons
+    }
+
+    /// Work in this tab's session alone that exiting would cut off.
+    pub fn session_exit_blockers(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.ui.agent_running {
+            reasons.push("an agent run is in progress".to_string());
+        }
+        if self.ui.pending_permission.is_some() {
+            reasons.push("a permission prompt is waiting".to_string());
+        }
+        if self.ui.pending_budget.is_some() {
+            reasons.push("a change-budget prompt is waiting".to_string());
+        }
+        if let Some(review) = &self.ui.review {
+            let files = review.items.len();
+            reasons.push(format!(
+                "the review of {files} changed file{} is not confirmed",
+                if files == 1 { "" } else { "s" }
+            ));
+        }
+        let unpromoted = self
+            .session_changes
+            .len()
+            .saturating_sub(self.ui.sandbox_changes_from);
+        if self.sandbox.is_some() && unpromoted > 0 {
+            reasons.push(format!(
+                "{unpromoted} sandbox edit{} not promoted",
+                if unpromoted == 1 { " is" } else { "s are" }
+            ));
+        }
+        let jobs = self
+            .jobs
+            .iter()
+            .filter(|job| matches!(job.status, JobStatus::Queued | JobStatus::Running))
+            .count();
+        if jobs > 0 {
+            reasons.push(format!(
+                "{jobs} background job{} not finished",
+                if jobs == 1 { " is" } else { "s are" }
+            ));
+        }
+        reasons
+    }
+
+    /// Exit now, or ask first when that would lose a run, prompt, job or
+    /// edits still waiting on a review or a sandbox promote.
+    pub fn request_exit(&mut self) {
+        let reasons = self.exit_blockers();
+        if reasons.is_empty() {
+            self.ui.should_exit = true;
+        } else {
+            self.ui.pending_exit = Some(PendingExit { reasons });
+        }
+    }
+
+    /// Moves the tool-call block selection one block back or forward,
+    /// starting from the latest block.
+    pub fn select_tool_block(&mut self, back: bool) {
+        let ids = self.tool_block_ids();
+        let Some(&latest) = ids.last() else {
+            return;
+        };
+        let current = self
+            .ui
+            .selected_tool
+            .and_then(|id| ids.iter().position(|&i| i == id));
+        self.ui.selected_tool = match (current, back) {
+            (None, _) => Some(latest),
+            (Some(i), true) => Some(ids[i.saturating_sub(1)]),
+            (Some(i), false) => ids.get(i + 1).copied(),
+        };
+    }
+
+    /// Expands or collapses the selected tool-call block. False when the log
+    /// has no tool calls.
+    pub fn toggle_tool_block(&mut self) -> bool {
+        let ids = self.tool_block_ids();
+        let Some(id) = self
+            .ui
+            .selected_tool
+            .filter(|id| ids.contains(id))
+            .or(ids.last().copied())
+        else {
+            return false;
+        };
+        if !self.ui.expanded_tools.remove(&id) {
+            self.ui.expanded_tools.insert(id);
+        }
+        true
+    }
+
+    fn tool_block_ids(&self) -> Vec<u64> {
+        self.logs
+            .iter()
+            .filter(|log| is_tool_call_log(&log.text))
+            .map(|log| log.id)
+            .collect()
+    }
+
     pub fn push_char(&mut self, c: char) {
         let mut buf = [0; 4];
         self.insert_text(c.encode_utf8(&mut buf));
@@ -413,6 +852,55 @@ impl AgentState {
         self.ui.history_index = None;
     }
 
+    /// Inserts a paste, or a placeholder for it when it is long enough to
+    /// flood the input box. A dropped image file is attached instead.
+    pub fn insert_paste(&mut self, text: &str) {
+        if self.ui.input_mode == InputMode::AgentText {
+            if let Some(image) =
+                images::dropped_image(text).and_then(|path| ImageAttachment::load(&path).ok())
+            {
+                self.ui.images.push(image);
+                return;
+            }
+        }
+        let lines = text.lines().count();
+        let large = lines > PASTE_ATTACH_LINES || text.chars().count() > PASTE_ATTACH_CHARS;
+        if !large || self.ui.input_mode == InputMode::ApiKey {
+            self.insert_text(text);
+            return;
+        }
+
+        let base = if lines > 1 {
+            format!("pasted {lines} lines")
+        } else {
+            format!("pasted {} chars", text.chars().count())
+        };
+        let taken = |label: &str| self.ui.pastes.iter().any(|p| p.label == label);
+        let mut label = format!("[{base}]");
+        let mut n = 2;
+        while taken(&label) {
+            label = format!("[{base} #{n}]");
+            n += 1;
+        }
+        self.ui.pastes.push(PastedText {
+            label: label.clone(),
+            text: text.to_string(),
+        });
+        self.insert_text(&label);
+    }
+
+    /// `text` with paste placeholders swapped back for what was pasted.
+    pub fn expand_pastes(&self, text: &str) -> String {
+        let mut expanded = text.to_string();
+        // Newest first, so `[pasted 3 lines #2]` is not hit by `[pasted 3 lines]`.
+        for paste in self.ui.pastes.iter().rev() {
+            if expanded.contains(&paste.label) {
+                expanded = expanded.replace(&paste.label, &paste.text);
+            }
+        }
+        expanded
+    }
+
     pub fn backspace(&mut self) {
         if self.ui.input_all_selected {
             self.ui.input.clear();
@@ -462,6 +950,16 @@ impl AgentState {
         self.ui.input_cursor = next_char_boundary(&self.ui.input, cursor);
     }
 
+    pub fn move_cursor_word_left(&mut self) {
+        self.ui.input_all_selected = false;
+        self.ui.input_cursor = previous_word_start(&self.ui.input, self.ui.input_cursor);
+    }
+
+    pub fn move_cursor_word_right(&mut self) {
+        self.ui.input_all_selected = false;
+        self.ui.input_cursor = next_word_end(&self.ui.input, self.ui.input_cursor);
+    }
+
     pub fn move_cursor_line_start(&mut self) {
         self.ui.input_all_selected = false;
         let cursor = clamp_char_boundary(&self.ui.input, self.ui.input_cursor);
@@ -547,10 +1045,7 @@ impl AgentState {
             return false;
         }
 
-        self.ui.input_clipboard = self.ui.input[cursor..kill_end].to_string();
-        self.ui.input.drain(cursor..kill_end);
-        self.ui.input_cursor = cursor;
-        self.ui.history_index = None;
+        self.kill_range(cursor, kill_end);
         true
     }
 
@@ -586,11 +1081,58 @@ impl AgentState {
             return false;
         }
 
-        self.ui.input_clipboard = self.ui.input[start..cursor].to_string();
-        self.ui.input.drain(start..cursor);
+        self.kill_range(start, cursor);
+        true
+    }
+
+    /// Kills back to the start of the word, stopping at punctuation,
+    /// unlike `delete_previous_word`, which only stops at whitespace.
+    pub fn kill_word_backward(&mut self) -> bool {
+        if self.ui.input_all_selected {
+            return self.cut_input();
+        }
+
+        let cursor = clamp_char_boundary(&self.ui.input, self.ui.input_cursor);
+        let start = previous_word_start(&self.ui.input, cursor);
+        if start == cursor {
+            return false;
+        }
+
+        self.kill_range(start, cursor);
+        true
+    }
+
+    pub fn kill_word_forward(&mut self) -> bool {
+        if self.ui.input_all_selected {
+            return self.cut_input();
+        }
+
+        let cursor = clamp_char_boundary(&self.ui.input, self.ui.input_cursor);
+        let end = next_word_end(&self.ui.input, cursor);
+        if end == cursor {
+            return false;
+        }
+
+        self.kill_range(cursor, end);
+        true
+    }
+
+    /// Removes `start..end` into the clipboard. Back-to-back kills join up,
+    /// in reading order, so one yank restores them all.
+    fn kill_range(&mut self, start: usize, end: usize) {
+        let killed = &self.ui.input[start..end];
+        if !self.ui.input_kill_chain {
+            self.ui.input_clipboard.clear();
+        }
+        if start < self.ui.input_cursor {
+            self.ui.input_clipboard.insert_str(0, killed);
+        } else {
+            self.ui.input_clipboard.push_str(killed);
+        }
+        self.ui.input.drain(start..end);
         self.ui.input_cursor = start;
+        self.ui.input_kill_chain = true;
         self.ui.history_index = None;
-        true
     }
 
     pub fn paste_input(&mut self) -> bool {
@@ -721,6 +1263,36 @@ fn next_char_boundary(value: &str, index: usize) -> usize {
         .unwrap_or(value.len())
 }
 
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Start of the word before `cursor`, skipping the separators in between.
+fn previous_word_start(value: &str, cursor: usize) -> usize {
+    let cursor = clamp_char_boundary(value, cursor);
+    let mut chars = value[..cursor].char_indices().rev().peekable();
+    while chars.next_if(|&(_, c)| !is_word_char(c)).is_some() {}
+    let mut start = chars.peek().map(|&(i, _)| i).unwrap_or(0);
+    for (i, c) in chars {
+        if !is_word_char(c) {
+            break;
+        }
+        start = i;
+    }
+    start
+}
+
+/// End of the word after `cursor`, skipping the separators before it.
+fn next_word_end(value: &str, cursor: usize) -> usize {
+    let cursor = clamp_char_boundary(value, cursor);
+    let mut chars = value[cursor..].char_indices().peekable();
+    while chars.next_if(|&(_, c)| !is_word_char(c)).is_some() {}
+    chars
+        .find(|&(_, c)| !is_word_char(c))
+        .map(|(i, _)| cursor + i)
+        .unwrap_or(value.len())
+}
+
 fn current_line_bounds(value: &str, cursor: usize) -> (usize, usize) {
     let cursor = clamp_char_boundary(value, cursor);
     let start = value[..cursor]
@@ -786,6 +1358,51 @@ pub struct VoiceState {
     pub last_inserted: Option<String>,
     pub url: String,
     pub model: String,
+    pub backend: VoiceBackend,
+    pub activation: VoiceActivation,
+    pub ptt_key: PttKey,
+    /// The mic is off; set from the worker's Paused/Resumed events.
+    pub paused: bool,
+    pub ptt_held: bool,
+    /// Last press or repeat of the push-to-talk key.
+    pub ptt_last_key: Option<Instant>,
+    /// The terminal reports key releases, so no release timeout is needed.
+    pub ptt_release_events: bool,
+    /// Pending resume (true) or pause (false), consumed by the main loop.
+    pub ptt_transition: Option<bool>,
+    /// When the wake phrase was heard on its own.
+    pub wake_armed: Option<Instant>,
+    /// Read final answers aloud.
+    pub speak: bool,
+    pub speaking: bool,
+    /// Cut off the read-back in progress; consumed by the main loop.
+    pub speech_stop_requested: bool,
+}
+
+impl VoiceState {
+    pub fn push_to_talk(&self) -> bool {
+        self.activation == VoiceActivation::PushToTalk
+    }
+
+    pub fn wake_armed(&self) -> bool {
+        self.wake_armed.is_some_and(|at| at.elapsed() < WAKE_WINDOW)
+    }
+
+    pub fn ptt_press(&mut self) {
+        self.ptt_last_key = Some(Instant::now());
+        if !self.ptt_held {
+            self.ptt_held = true;
+            self.ptt_transition = Some(true);
+        }
+    }
+
+    pub fn ptt_release(&mut self) {
+        self.ptt_last_key = None;
+        if self.ptt_held {
+            self.ptt_held = false;
+            self.ptt_transition = Some(false);
+        }
+    }
 }
 
 impl Default for VoiceState {
@@ -802,22 +1419,154 @@ impl Default for VoiceState {
             last_inserted: None,
             url: String::new(),
             model: String::new(),
+            backend: VoiceBackend::Vllm,
+            activation: VoiceActivation::Continuous,
+            ptt_key: PttKey::default(),
+            paused: false,
+            ptt_held: false,
+            ptt_last_key: None,
+            ptt_release_events: false,
+            ptt_transition: None,
+            wake_armed: None,
+            speak: false,
+            speaking: false,
+            speech_stop_requested: false,
         }
     }
 }
 
+/// Token usage of the session. `prompt_tokens` and `completion_tokens` are
+/// the session totals; the maps break them down.
 #[derive(Default)]
 pub struct UsageStats {
     pub prompt_tokens: usize,
     pub completion_tokens: usize,
+    /// Keyed by `provider/model`.
+    pub by_model: BTreeMap<String, ModelUsage>,
+    /// Tokens of the model turns that requested each tool, split evenly
+    /// between the calls of a turn.
+    pub by_tool: BTreeMap<String, ToolUsage>,
+    /// The current or last agent run.
+    pub run: ModelUsage,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub model_calls: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Estimated USD for the tokens of priced models.
+    pub cost_usd: f64,
+    /// Tokens of models without a known price, left out of `cost_usd`.
+    #[serde(default)]
+    pub unpriced_tokens: usize,
+    /// Tokens estimated from text because the provider reported none.
+    #[serde(default)]
+    pub estimated_tokens: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolUsage {
+    pub calls: usize,
+    pub tokens: usize,
+}
+
+impl ModelUsage {
+    pub fn total_tokens(&self) -> usize {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(
+        &mut self,
+        input_tokens: usize,
+        output_tokens: usize,
+        estimated: bool,
+        price: Option<ModelPrice>,
+    ) {
+        self.model_calls += 1;
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+        match price {
+            Some(price) => self.cost_usd += price.cost(input_tokens, output_tokens),
+            None => self.unpriced_tokens += input_tokens + output_tokens,
+        }
+        if estimated {
+            self.estimated_tokens += input_tokens + output_tokens;
+        }
+    }
+}
+
+impl UsageStats {
+    /// Adds one model turn of `model` (`provider/model`); `price` is None
+    /// for models without a known price.
+    pub fn record(
+        &mut self,
+        model: &str,
+        input_tokens: usize,
+        output_tokens: usize,
+        estimated: bool,
+        tools: &[String],
+        price: Option<ModelPrice>,
+    ) {
+        self.prompt_tokens += input_tokens;
+        self.completion_tokens += output_tokens;
+        self.by_model.entry(model.to_string()).or_default().add(
+            input_tokens,
+            output_tokens,
+            estimated,
+            price,
+        );
+        self.run.add(input_tokens, output_tokens, estimated, price);
+
+        let Some(share) = (input_tokens + output_tokens).checked_div(tools.len()) else {
+            return;
+        };
+        for tool in tools {
+            let usage = self.by_tool.entry(tool.clone()).or_default();
+            usage.calls += 1;
+            usage.tokens += share;
+        }
+    }
+
+    pub fn cost_usd(&self) -> f64 {
+        self.by_model.values().fold(0.0, |sum, m| sum + m.cost_usd)
+    }
+
+    /// Whether some tokens went to models without a known price.
+    pub fn has_unpriced(&self) -> bool {
+        self.by_model.values().any(|m| m.unpriced_tokens > 0)
+    }
+
+    /// `$0.0123`, with a `+` when unpriced tokens are missing from it.
+    pub fn cost_label(&self) -> String {
+        format!(
+            "${:.4}{}",
+            self.cost_usd(),
+            if self.has_unpriced() { "+" } else { "" }
+        )
+    }
 }
 
 pub struct PendingPermission {
     pub tool_name: String,
     pub args_summary: String,
+    /// Protected paths the call would change; these need a second `y`.
+    pub protected: Option<String>,
+    /// Approval pattern stored when the answer is `[a]lways`.
+    pub always: String,
+    pub confirmed_once: bool,
     pub reply_tx: Sender<bool>,
 }
 
+pub struct PendingBudget {
+    pub summary: String,
+    pub reply_tx: Sender<BudgetDecision>,
+}
+
+pub struct PendingExit {
+    pub reasons: Vec<String>,
+}
+
 pub struct PendingUpdate {
     pub current_version: String,
     pub latest_version: String,
@@ -834,6 +1583,12 @@ pub struct ConversationHistory {
     pub token_estimate: usize,
 }
 
+impl Default for ConversationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConversationHistory {
     pub fn new() -> Self {
         let messages = vec![json!({
@@ -842,7 +1597,7 @@ impl ConversationHistory {
                 "You are Osmogrep, an AI coding agent working inside this repository.\n\
                 \n\
                 This repository may include a machine-generated index stored as `.context/context.json` at the repository root.\n\
-                This file, if present, describes the structure of the codebase: files, symbols, and call relationships.\n\
+                This file, if present, describes the structure of the codebase: repo facts (languages, manifests, test commands), files, the import graph between them, symbols, and call relationships.\n\
                 \n\
                 The index is a regular file and must be discovered and read using tools.\n\
                 It may or may not exist.\n\
@@ -1005,6 +1760,9 @@ mod tests {
             undo_stack: Vec::new(),
             usage: UsageStats::default(),
             steer: None,
+            steer_history: Vec::new(),
+            run_budget: None,
+            interrupted: None,
             auto_eval: false,
             permission_profile: PermissionProfile::WorkspaceAuto,
             jobs: Vec::new(),
@@ -1015,14 +1773,103 @@ mod tests {
             theme: UiTheme::default(),
             accent: UiAccent::default(),
             density: UiDensity::default(),
+            timestamps: LogTimestamps::default(),
             plan_mode: false,
+            plan_approved: false,
             started_at: Instant::now(),
             repo_root: PathBuf::from("."),
+            repo_lock: None,
+            sandbox: None,
+            rebase: None,
             voice: VoiceState::default(),
             conversation: ConversationHistory::new(),
         }
     }
 
+    #[test]
+    fn merge_changes_keeps_first_before_and_last_after_per_file() {
+        let snap = |target: &str, before: &str, after: &str| DiffSnapshot {
+            tool: "edit_file".to_string(),
+            target: target.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            ..DiffSnapshot::default()
+        };
+        let merged = merge_changes(&[
+            snap("a.rs", "1", "2"),
+            snap("b.rs", "x", "y"),
+            snap("a.rs", "2", "3"),
+            snap("b.rs", "y", "x"),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(
+            (merged[0].target.as_str(), merged[0].before.as_str()),
+            ("a.rs", "1")
+        );
+        assert_eq!(merged[0].after, "3");
+    }
+
+    #[test]
+    fn session_diff_compares_first_snapshot_with_the_disk() {
+        let root =
+            std::env::temp_dir().join(format!("osmogrep-session-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("kept.rs"), "3").unwrap();
+        std::fs::write(root.join("new.rs"), "fresh").unwrap();
+        std::fs::write(root.join("was_empty.rs"), "filled").unwrap();
+        std::fs::write(root.join("emptied.rs"), "").unwrap();
+        let snap = |target: &str, before: &str, after: &str| DiffSnapshot {
+            tool: "write_file".to_string(),
+            target: target.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            ..DiffSnapshot::default()
+        };
+        let created = |target: &str, after: &str| DiffSnapshot {
+            created: true,
+            ..snap(target, "", after)
+        };
+        let deleted = |target: &str, before: &str| DiffSnapshot {
+            deleted: true,
+            ..snap(target, before, "")
+        };
+        let diff = session_diff(
+            &[
+                snap("kept.rs", "1", "2"),
+                created("new.rs", "fresh"),
+                snap("gone.rs", "old", "older"),
+                snap("was_empty.rs", "", "filled"),
+                snap("emptied.rs", "full", ""),
+                created("scratch.rs", "tmp"),
+                deleted("scratch.rs", "tmp"),
+            ],
+            &root,
+        );
+        let files: Vec<_> = diff
+            .iter()
+            .map(|d| {
+                (
+                    d.target.as_str(),
+                    d.before.as_str(),
+                    d.after.as_str(),
+                    d.created,
+                    d.deleted,
+                )
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("kept.rs", "1", "3", false, false),
+                ("new.rs", "", "fresh", true, false),
+                ("gone.rs", "old", "", false, true),
+                ("was_empty.rs", "", "filled", false, false),
+                ("emptied.rs", "full", "", false, false),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(root);
+    }
+
     #[test]
     fn select_all_replaces_input_on_next_character() {
         let mut state = agent_state_with_input("large pasted prompt");
@@ -1107,6 +1954,82 @@ mod tests {
         assert_eq!(state.ui.input_cursor, "alpha  ".len());
     }
 
+    #[test]
+    fn word_motion_stops_at_punctuation_and_consecutive_kills_yank_together() {
+        let mut state = agent_state_with_input("fix src/main.rs, then 界test");
+        state.ui.input_cursor = state.ui.input.len();
+
+        state.move_cursor_word_left();
+        assert_eq!(state.ui.input_cursor, "fix src/main.rs, then ".len());
+        state.move_cursor_word_left();
+        state.move_cursor_word_left();
+        assert_eq!(state.ui.input_cursor, "fix src/main.".len());
+        state.move_cursor_word_right();
+        assert_eq!(state.ui.input_cursor, "fix src/main.rs".len());
+
+        state.ui.input_cursor = "fix src/".len();
+        assert!(state.kill_word_forward());
+        assert!(state.kill_word_forward());
+        assert_eq!(state.ui.input, "fix src/, then 界test");
+        assert!(state.kill_word_backward());
+        assert_eq!(state.ui.input_clipboard, "src/main.rs");
+        assert_eq!(state.ui.input, "fix , then 界test");
+
+        state.ui.input_kill_chain = false;
+        state.ui.input_cursor = state.ui.input.len();
+        assert!(state.kill_word_backward());
+        assert_eq!(state.ui.input_clipboard, "界test");
+        assert!(!state.kill_word_forward());
+    }
+
+    #[test]
+    fn large_pastes_become_placeholders_that_expand_on_send() {
+        let mut state = agent_state_with_input("why does this fail: ");
+        state.ui.input_cursor = state.ui.input.len();
+        let trace = (1..=30)
+            .map(|n| format!("  at frame {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        state.insert_paste(&trace);
+        state.insert_text(" and ");
+        state.insert_paste(&trace);
+        state.insert_paste("short");
+        assert_eq!(
+            state.ui.input,
+            "why does this fail: [pasted 30 lines] and [pasted 30 lines #2]short"
+        );
+        assert_eq!(
+            state.expand_pastes(&state.ui.input),
+            format!("why does this fail: {trace} and {trace}short")
+        );
+
+        state.ui.input_mode = InputMode::ApiKey;
+        state.clear_input();
+        state.insert_paste(&"k".repeat(3000));
+        assert_eq!(state.ui.input.len(), 3000);
+    }
+
+    #[test]
+    fn dropped_image_files_are_attached_not_typed() {
+        let dir = std::env::temp_dir().join(format!("osmogrep-drop-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shot = dir.join("shot.png");
+        std::fs::write(&shot, b"png").unwrap();
+        let mut state = agent_state_with_input("");
+
+        state.insert_paste(&format!("'{}'", shot.display()));
+        assert_eq!(state.ui.input, "");
+        assert_eq!(state.ui.images.len(), 1);
+        assert_eq!(state.ui.images[0].name(), "shot.png");
+
+        // A missing file is just text.
+        state.insert_paste("/nowhere/shot.png");
+        assert_eq!(state.ui.input, "/nowhere/shot.png");
+        assert_eq!(state.ui.images.len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
     #[test]
     fn inserts_and_deletes_at_cursor_without_splitting_multibyte_chars() {
         let mut state = agent_state_with_input("a界c");
@@ -1160,4 +2083,32 @@ mod tests {
         assert_eq!(UiDensity::parse("roomy"), Some(UiDensity::Spacious));
         assert_eq!(UiDensity::parse("huge"), None);
     }
+
+    #[test]
+    fn usage_is_priced_per_model_and_split_across_tool_calls() {
+        let mut usage = UsageStats::default();
+        let price = Some(ModelPrice {
+            input_per_mtok: 2.0,
+            output_per_mtok: 10.0,
+        });
+        let tools = ["read_file".to_string(), "search".to_string()];
+        usage.record("openai/gpt-4.1", 1_000, 200, false, &tools, price);
+        usage.record("openai/gpt-4.1", 500, 100, false, &tools[..1], price);
+        assert_eq!(usage.cost_label(), "$0.0060");
+
+        usage.record("mistral/codestral", 300, 0, true, &[], None);
+        assert_eq!(usage.prompt_tokens, 1_800);
+        assert_eq!(usage.completion_tokens, 300);
+        assert_eq!(usage.cost_label(), "$0.0060+");
+        assert_eq!(usage.by_model["mistral/codestral"].estimated_tokens, 300);
+        assert_eq!(usage.run.model_calls, 3);
+        assert_eq!(
+            usage.by_tool["read_file"],
+            ToolUsage {
+                calls: 2,
+                tokens: 1_200
+            }
+        );
+        assert_eq!(usage.by_tool["search"].tokens, 600);
+    }
 }
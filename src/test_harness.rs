@@ -1,9 +1,11 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use regex::Regex;
 use serde::Serialize;
 
+use crate::sandbox::SandboxPolicy;
 use crate::verification::VerificationEvidence;
 
 const OUTPUT_LIMIT: usize = 10_000;
@@ -17,30 +19,99 @@ pub struct TestRun {
     pub success: bool,
     pub passed: usize,
     pub failed: usize,
+    /// Names of the failing tests, when the runner reports them.
+    pub failures: Vec<String>,
+    /// Slowest tests first; only runners that time each test fill this.
+    pub slowest: Vec<TestTiming>,
     pub output: String,
     pub timed_out: bool,
     pub cancelled: bool,
     pub verification: Option<VerificationEvidence>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestTiming {
+    pub name: String,
+    pub duration_ms: u128,
+}
+
+/// What a runner reported, from its console output or JUnit XML.
+#[derive(Debug, Default, PartialEq)]
+pub struct TestSuiteResult {
+    pub passed: usize,
+    pub failed: usize,
+    pub failures: Vec<String>,
+    pub timings: Vec<TestTiming>,
+}
+
+impl TestSuiteResult {
+    fn counts((passed, failed): (usize, usize)) -> Self {
+        Self {
+            passed,
+            failed,
+            ..Self::default()
+        }
+    }
+}
+
+const SLOWEST_LIMIT: usize = 5;
+
 pub fn run_tests(repo_root: &Path, target: Option<&str>) -> Result<TestRun, String> {
-    run_tests_cancellable(repo_root, target, || false)
+    run_tests_cancellable(repo_root, target, None, || false)
 }
 
+/// `sandbox` confines the test command, as for the agent's `run_tests`.
 pub fn run_tests_cancellable(
     repo_root: &Path,
     target: Option<&str>,
+    sandbox: Option<&SandboxPolicy>,
     is_cancelled: impl Fn() -> bool,
 ) -> Result<TestRun, String> {
-    let (framework, command) = detect_framework_and_command(repo_root, target)?;
+    let (runner, command) = detect_runner_and_command(repo_root, target)?;
+    execute(repo_root, runner, command, sandbox, is_cancelled)
+}
 
+/// Runs only the tests of the workspace packages that own uncommitted
+/// changes. Falls back to the whole suite when the tree is clean, only the
+/// root package changed, or the packages need different runners.
+pub fn run_affected_tests_cancellable(
+    repo_root: &Path,
+    sandbox: Option<&SandboxPolicy>,
+    is_cancelled: impl Fn() -> bool,
+) -> Result<TestRun, String> {
+    let scoped = crate::context::working_diff::current(repo_root).and_then(|diff| {
+        let (name, command) = crate::workspace::test_command(repo_root, &diff.packages)?;
+        Some((runner(name)?, command))
+    });
+    match scoped {
+        Some((runner, command)) => execute(repo_root, runner, command, sandbox, is_cancelled),
+        None => run_tests_cancellable(repo_root, None, sandbox, is_cancelled),
+    }
+}
+
+fn execute(
+    repo_root: &Path,
+    runner: &'static dyn TestRunner,
+    command: String,
+    sandbox: Option<&SandboxPolicy>,
+    is_cancelled: impl Fn() -> bool,
+) -> Result<TestRun, String> {
+    let started = SystemTime::now();
     let timeout = crate::process_runner::timeout_from_env("OSMOGREP_TEST_TIMEOUT_SECS", 300);
-    let out = crate::process_runner::run_shell_command_cancellable(
-        &command,
-        Some(repo_root),
-        timeout,
-        is_cancelled,
-    )?;
+    let out = match sandbox {
+        Some(sandbox) => {
+            let argv = sandbox.shell_argv(repo_root, &command)?;
+            let mut sandboxed = crate::platform::command(&argv);
+            sandboxed.current_dir(repo_root);
+            crate::process_runner::run_command_cancellable(sandboxed, timeout, is_cancelled)?
+        }
+        None => crate::process_runner::run_shell_command_cancellable(
+            &command,
+            Some(repo_root),
+            timeout,
+            is_cancelled,
+        )?,
+    };
     let duration_ms = out.duration_ms;
 
     let mut text = String::new();
@@ -54,17 +125,24 @@ pub fn run_tests_cancellable(
 
     let output = truncate_output(&text);
     let exit_code = out.exit_code;
-    let (passed, failed) = parse_counts(&framework, &text);
+    let mut report =
+        junit_result(runner, repo_root, started).unwrap_or_else(|| runner.parse(&text));
+    report
+        .timings
+        .sort_by_key(|t| std::cmp::Reverse(t.duration_ms));
+    report.timings.truncate(SLOWEST_LIMIT);
     let verification = crate::verification::record_command(repo_root, &command, exit_code, &text);
 
     Ok(TestRun {
-        framework,
+        framework: runner.name().to_string(),
         command,
         exit_code,
         duration_ms,
         success: exit_code == 0 && !out.timed_out && !out.cancelled,
-        passed,
-        failed,
+        passed: report.passed,
+        failed: report.failed,
+        failures: report.failures,
+        slowest: report.timings,
         output,
         timed_out: out.timed_out,
         cancelled: out.cancelled,
@@ -72,71 +150,424 @@ pub fn run_tests_cancellable(
     })
 }
 
-fn detect_framework_and_command(
+/// Files whose creation or removal can change the detected frameworks.
+pub const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "go.mod",
+    "pyproject.toml",
+    "pytest.ini",
+    "package.json",
+    "pom.xml",
+];
+
+pub fn is_manifest(path: &str) -> bool {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| MANIFESTS.contains(&name))
+}
+
+/// A test framework: how to spot it in a repo, how to run it and how to
+/// read what it prints. Runners that also write JUnit XML name the files in
+/// `junit_reports`; those are read in preference to the console output.
+pub trait TestRunner: Sync {
+    fn name(&self) -> &'static str;
+    fn language(&self) -> &'static str;
+    /// Source extensions whose file targets route to this runner.
+    fn extensions(&self) -> &'static [&'static str];
+    fn detect(&self, repo_root: &Path) -> bool;
+    fn invocation(&self, repo_root: &Path) -> String;
+    fn parse(&self, raw: &str) -> TestSuiteResult;
+    fn junit_reports(&self, _repo_root: &Path) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Known runners in routing priority order.
+pub static RUNNERS: &[&dyn TestRunner] = &[&Cargo, &Pytest, &Jest, &Go, &Maven];
+
+pub fn runner(name: &str) -> Option<&'static dyn TestRunner> {
+    RUNNERS.iter().copied().find(|r| r.name() == name)
+}
+
+struct Cargo;
+
+impl TestRunner for Cargo {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn language(&self) -> &'static str {
+        "rust"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rs"]
+    }
+
+    fn detect(&self, repo_root: &Path) -> bool {
+        repo_root.join("Cargo.toml").exists()
+    }
+
+    fn invocation(&self, repo_root: &Path) -> String {
+        if repo_root.join(".config/nextest.toml").exists() {
+            "cargo nextest run --color never".into()
+        } else {
+            "cargo test --color never".into()
+        }
+    }
+
+    fn parse(&self, raw: &str) -> TestSuiteResult {
+        parse_cargo_report(raw)
+    }
+}
+
+struct Pytest;
+
+impl TestRunner for Pytest {
+    fn name(&self) -> &'static str {
+        "pytest"
+    }
+
+    fn language(&self) -> &'static str {
+        "python"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["py"]
+    }
+
+    fn detect(&self, repo_root: &Path) -> bool {
+        repo_root.join("pyproject.toml").exists()
+            || repo_root.join("pytest.ini").exists()
+            || has_python_tests(&repo_root.join("tests"))
+    }
+
+    fn invocation(&self, _repo_root: &Path) -> String {
+        "pytest -q".into()
+    }
+
+    fn parse(&self, raw: &str) -> TestSuiteResult {
+        TestSuiteResult::counts(parse_pytest_counts(raw))
+    }
+}
+
+struct Jest;
+
+const JEST_JUNIT: &str = ".context/junit-jest.xml";
+
+impl Jest {
+    fn package_json(repo_root: &Path) -> String {
+        fs::read_to_string(repo_root.join("package.json")).unwrap_or_default()
+    }
+}
+
+impl TestRunner for Jest {
+    fn name(&self) -> &'static str {
+        "jest"
+    }
+
+    fn language(&self) -> &'static str {
+        "javascript"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["js", "jsx", "ts", "tsx", "mjs", "cjs"]
+    }
+
+    fn detect(&self, repo_root: &Path) -> bool {
+        let pkg = Self::package_json(repo_root);
+        pkg.contains("jest") || pkg.contains("vitest") || pkg.contains("\"test\"")
+    }
+
+    fn invocation(&self, repo_root: &Path) -> String {
+        if Self::package_json(repo_root).contains("jest-junit") {
+            format!(
+                "JEST_JUNIT_OUTPUT_FILE={JEST_JUNIT} npm test -- --runInBand \
+                 --reporters=default --reporters=jest-junit"
+            )
+        } else {
+            "npm test -- --runInBand".into()
+        }
+    }
+
+    fn parse(&self, raw: &str) -> TestSuiteResult {
+        TestSuiteResult::counts(parse_jest_counts(raw))
+    }
+
+    fn junit_reports(&self, repo_root: &Path) -> Vec<PathBuf> {
+        vec![repo_root.join(JEST_JUNIT)]
+    }
+}
+
+struct Go;
+
+const GO_JUNIT: &str = ".context/junit-go.xml";
+
+impl TestRunner for Go {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn language(&self) -> &'static str {
+        "go"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["go"]
+    }
+
+    fn detect(&self, repo_root: &Path) -> bool {
+        repo_root.join("go.mod").exists()
+    }
+
+    fn invocation(&self, _repo_root: &Path) -> String {
+        if on_path("gotestsum") {
+            format!("gotestsum --junitfile {GO_JUNIT} -- ./...")
+        } else {
+            "go test ./...".into()
+        }
+    }
+
+    fn parse(&self, raw: &str) -> TestSuiteResult {
+        TestSuiteResult::counts(parse_go_counts(raw))
+    }
+
+    fn junit_reports(&self, repo_root: &Path) -> Vec<PathBuf> {
+        vec![repo_root.join(GO_JUNIT)]
+    }
+}
+
+struct Maven;
+
+impl TestRunner for Maven {
+    fn name(&self) -> &'static str {
+        "maven"
+    }
+
+    fn language(&self) -> &'static str {
+        "java"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["java", "kt"]
+    }
+
+    fn detect(&self, repo_root: &Path) -> bool {
+        repo_root.join("pom.xml").exists()
+    }
+
+    fn invocation(&self, _repo_root: &Path) -> String {
+        "mvn -q -B test".into()
+    }
+
+    fn parse(&self, raw: &str) -> TestSuiteResult {
+        let re = Regex::new(r"Tests run: (\d+), Failures: (\d+), Errors: (\d+), Skipped: (\d+)")
+            .unwrap();
+        let Some(c) = re.captures_iter(raw).last() else {
+            return TestSuiteResult::default();
+        };
+        let n = |i: usize| c[i].parse::<usize>().unwrap_or(0);
+        let failed = n(2) + n(3);
+        TestSuiteResult::counts((n(1).saturating_sub(failed + n(4)), failed))
+    }
+
+    fn junit_reports(&self, repo_root: &Path) -> Vec<PathBuf> {
+        fs::read_dir(repo_root.join("target/surefire-reports"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "xml"))
+            .collect()
+    }
+}
+
+pub(crate) fn on_path(bin: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(bin).is_file()))
+}
+
+/// Test frameworks present in the repo, in routing priority order. Polyglot
+/// repos list several.
+pub fn detect_frameworks(repo_root: &Path) -> Vec<&'static str> {
+    RUNNERS
+        .iter()
+        .filter(|r| r.detect(repo_root))
+        .map(|r| r.name())
+        .collect()
+}
+
+/// A bare `tests/` directory means pytest only when it holds Python files;
+/// Rust integration tests live there too.
+fn has_python_tests(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|entry| entry.path().extension().is_some_and(|ext| ext == "py"))
+}
+
+pub fn language_of(framework: &str) -> &'static str {
+    runner(framework).map_or("unknown", |r| r.language())
+}
+
+/// Languages of the detected frameworks, e.g. `["rust", "python"]`.
+pub fn detect_languages(repo_root: &Path) -> Vec<String> {
+    detect_frameworks(repo_root)
+        .into_iter()
+        .map(|framework| language_of(framework).to_string())
+        .collect()
+}
+
+/// Framework that owns a target naming a source file, if its extension says.
+fn framework_for_target(target: &str) -> Option<&'static str> {
+    let path = target.split("::").next().unwrap_or(target);
+    let ext = Path::new(path).extension()?.to_str()?;
+    RUNNERS
+        .iter()
+        .find(|r| r.extensions().contains(&ext))
+        .map(|r| r.name())
+}
+
+fn detect_runner_and_command(
     repo_root: &Path,
     target: Option<&str>,
-) -> Result<(String, String), String> {
+) -> Result<(&'static dyn TestRunner, String), String> {
     let target = target.unwrap_or("").trim();
+    let frameworks = detect_frameworks(repo_root);
+    // In a polyglot repo a file target goes to its own language's runner.
+    let framework = framework_for_target(target)
+        .filter(|framework| frameworks.contains(framework))
+        .or_else(|| frameworks.first().copied())
+        .ok_or("No supported test framework detected (cargo/pytest/jest/go/maven).")?;
+    let runner = runner(framework).ok_or("unknown test framework")?;
 
-    let cargo = repo_root.join("Cargo.toml").exists();
-    let go = repo_root.join("go.mod").exists();
-    let py = repo_root.join("pyproject.toml").exists()
-        || repo_root.join("pytest.ini").exists()
-        || repo_root.join("tests").exists();
-    let pkg = repo_root.join("package.json");
-    let js = if pkg.exists() {
-        fs::read_to_string(pkg)
-            .ok()
-            .map(|s| s.contains("jest") || s.contains("vitest") || s.contains("\"test\""))
-            .unwrap_or(false)
-    } else {
-        false
-    };
-
-    let (framework, mut cmd) = if cargo {
-        ("cargo", "cargo test --color never".to_string())
-    } else if py {
-        ("pytest", "pytest -q".to_string())
-    } else if js {
-        ("jest", "npm test -- --runInBand".to_string())
-    } else if go {
-        ("go", "go test ./...".to_string())
-    } else {
-        return Err("No supported test framework detected (cargo/pytest/jest/go).".to_string());
-    };
-
+    let mut cmd = runner.invocation(repo_root);
     if !target.is_empty() {
         cmd.push(' ');
         cmd.push_str(target);
     }
 
-    Ok((framework.to_string(), cmd))
+    Ok((runner, cmd))
 }
 
-fn parse_counts(framework: &str, output: &str) -> (usize, usize) {
-    match framework {
-        "cargo" => parse_cargo_counts(output),
-        "pytest" => parse_pytest_counts(output),
-        "jest" => parse_jest_counts(output),
-        "go" => parse_go_counts(output),
-        _ => (0, 0),
+/// JUnit XML written by this run: the runner's own reports plus any matching
+/// `OSMOGREP_JUNIT_REPORT` (a glob relative to the repo root). Files older
+/// than `since` are leftovers from an earlier run and are skipped.
+fn junit_result(
+    runner: &dyn TestRunner,
+    repo_root: &Path,
+    since: SystemTime,
+) -> Option<TestSuiteResult> {
+    let mut paths = runner.junit_reports(repo_root);
+    if let Ok(pattern) = std::env::var("OSMOGREP_JUNIT_REPORT") {
+        let pattern = repo_root.join(pattern.trim());
+        if let Ok(found) = glob::glob(&pattern.to_string_lossy()) {
+            paths.extend(found.flatten());
+        }
     }
+
+    let mut merged: Option<TestSuiteResult> = None;
+    for path in paths {
+        let fresh = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified >= since);
+        if !fresh {
+            continue;
+        }
+        let Some(result) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|xml| parse_junit(&xml).ok())
+        else {
+            continue;
+        };
+        let total = merged.get_or_insert_with(TestSuiteResult::default);
+        total.passed += result.passed;
+        total.failed += result.failed;
+        total.failures.extend(result.failures);
+        total.timings.extend(result.timings);
+    }
+    merged
 }
 
-fn parse_cargo_counts(output: &str) -> (usize, usize) {
-    let re = Regex::new(r"test result:\s+\w+\.\s+(\d+) passed;\s+(\d+) failed;").unwrap();
-    if let Some(c) = re.captures_iter(output).last() {
-        let p = c
-            .get(1)
-            .and_then(|m| m.as_str().parse::<usize>().ok())
-            .unwrap_or(0);
-        let f = c
-            .get(2)
-            .and_then(|m| m.as_str().parse::<usize>().ok())
+/// Reads a JUnit XML report (`<testsuites>` or a bare `<testsuite>`). A
+/// `<testcase>` with a `<failure>` or `<error>` child failed, one with
+/// `<skipped>` is not counted, and the rest passed.
+pub fn parse_junit(xml: &str) -> Result<TestSuiteResult, String> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| format!("junit report: {e}"))?;
+    let mut result = TestSuiteResult::default();
+
+    for case in doc.descendants().filter(|n| n.has_tag_name("testcase")) {
+        let name = match (case.attribute("classname"), case.attribute("name")) {
+            (Some(class), Some(name)) if !class.is_empty() => format!("{class}.{name}"),
+            (_, name) => name.unwrap_or("").to_string(),
+        };
+        let has = |tag: &str| case.children().any(|c| c.has_tag_name(tag));
+        if has("skipped") {
+            continue;
+        }
+        if has("failure") || has("error") {
+            result.failed += 1;
+            result.failures.push(name.clone());
+        } else {
+            result.passed += 1;
+        }
+        if let Some(secs) = case.attribute("time").and_then(|t| t.parse::<f64>().ok()) {
+            result.timings.push(TestTiming {
+                name,
+                duration_ms: (secs * 1000.0).round() as u128,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// `cargo test` prints one `test result:` footer per test binary, so counts
+/// are summed; `cargo nextest` prints a single `Summary` line and a timed
+/// `PASS`/`FAIL` line per test.
+fn parse_cargo_report(output: &str) -> TestSuiteResult {
+    let mut report = TestSuiteResult::default();
+
+    let footer = Regex::new(r"test result:\s+\w+\.\s+(\d+) passed;\s+(\d+) failed;").unwrap();
+    for c in footer.captures_iter(output) {
+        report.passed += c[1].parse::<usize>().unwrap_or(0);
+        report.failed += c[2].parse::<usize>().unwrap_or(0);
+    }
+    let failed_line = Regex::new(r"(?m)^test (\S+) \.\.\. FAILED").unwrap();
+    report.failures = failed_line
+        .captures_iter(output)
+        .map(|c| c[1].to_string())
+        .collect();
+
+    let summary = Regex::new(r"(?m)^\s*Summary \[.*\] \d+ tests? run: (\d+) passed.*$").unwrap();
+    if let Some(c) = summary.captures_iter(output).last() {
+        report.passed = c[1].parse().unwrap_or(0);
+        report.failed = Regex::new(r"(\d+) failed")
+            .unwrap()
+            .captures(&c[0])
+            .and_then(|f| f[1].parse().ok())
             .unwrap_or(0);
-        return (p, f);
+
+        // `PASS [   0.012s] crate module::tests::name`
+        let timed =
+            Regex::new(r"(?m)^\s*(PASS|FAIL|TIMEOUT|SIGSEGV|SIGABRT) \[\s*([\d.]+)s\] (.+)$")
+                .unwrap();
+        for c in timed.captures_iter(output) {
+            let name = c[3].split_whitespace().last().unwrap_or("").to_string();
+            let secs: f64 = c[2].parse().unwrap_or(0.0);
+            if &c[1] != "PASS" && !report.failures.contains(&name) {
+                report.failures.push(name.clone());
+            }
+            report.timings.push(TestTiming {
+                name,
+                duration_ms: (secs * 1000.0).round() as u128,
+            });
+        }
     }
-    (0, 0)
+
+    report
 }
 
 fn parse_pytest_counts(output: &str) -> (usize, usize) {
@@ -204,3 +635,118 @@ fn truncate_output(s: &str) -> String {
 
     format!("...truncated...\n{}", tail)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn routes_targets_by_language_in_polyglot_repos() {
+        let root = std::env::temp_dir().join(format!("osmogrep-polyglot-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(root.join("tests/cli.rs"), "#[test]\nfn runs() {}\n").unwrap();
+        assert_eq!(detect_languages(&root), vec!["rust"]);
+
+        fs::write(root.join("pyproject.toml"), "[project]\nname = \"x\"\n").unwrap();
+        assert_eq!(detect_languages(&root), vec!["rust", "python"]);
+        assert!(is_manifest("sub/pyproject.toml"));
+        assert!(!is_manifest("src/lib.rs"));
+
+        let (runner, cmd) = detect_runner_and_command(&root, Some("tests/test_api.py")).unwrap();
+        assert_eq!(runner.name(), "pytest");
+        assert_eq!(cmd, "pytest -q tests/test_api.py");
+        let (runner, _) = detect_runner_and_command(&root, Some("parser")).unwrap();
+        assert_eq!(runner.name(), "cargo");
+        let (runner, _) = detect_runner_and_command(&root, Some("web/app.test.ts")).unwrap();
+        assert_eq!(runner.name(), "cargo");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn cargo_reports_sum_binaries_and_name_failures() {
+        let libtest = "\
+running 2 tests
+test parser::tests::parses ... ok
+test parser::tests::rejects ... FAILED
+
+failures:
+    parser::tests::rejects
+
+test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.01s
+
+running 3 tests
+test cli ... ok
+test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+        let report = Cargo.parse(libtest);
+        assert_eq!((report.passed, report.failed), (4, 1));
+        assert_eq!(report.failures, ["parser::tests::rejects"]);
+        assert!(report.timings.is_empty());
+
+        let nextest = "\
+    Starting 3 tests across 2 binaries
+        PASS [   0.004s] app parser::tests::parses
+        FAIL [   1.250s] app parser::tests::rejects
+        PASS [   0.020s] app::cli cli
+------------
+     Summary [   1.260s] 3 tests run: 2 passed, 1 failed, 0 skipped
+";
+        let report = Cargo.parse(nextest);
+        assert_eq!((report.passed, report.failed), (2, 1));
+        assert_eq!(report.failures, ["parser::tests::rejects"]);
+        assert_eq!(
+            report.timings[1],
+            TestTiming {
+                name: "parser::tests::rejects".into(),
+                duration_ms: 1250
+            }
+        );
+    }
+
+    #[test]
+    fn junit_reports_win_over_console_output() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="com.acme.CartTest" tests="3">
+    <testcase classname="com.acme.CartTest" name="adds" time="0.012"/>
+    <testcase classname="com.acme.CartTest" name="totals" time="1.5">
+      <failure message="expected 3">stack</failure>
+    </testcase>
+    <testcase classname="com.acme.CartTest" name="later"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+        let result = parse_junit(xml).unwrap();
+        assert_eq!((result.passed, result.failed), (1, 1));
+        assert_eq!(result.failures, ["com.acme.CartTest.totals"]);
+        assert_eq!(result.timings.len(), 2);
+        assert!(parse_junit("<testsuite").is_err());
+
+        let root = std::env::temp_dir().join(format!("osmogrep-junit-{}", Uuid::new_v4()));
+        let reports = root.join("target/surefire-reports");
+        fs::create_dir_all(&reports).unwrap();
+        fs::write(root.join("pom.xml"), "<project/>").unwrap();
+        let before = SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::write(reports.join("TEST-com.acme.CartTest.xml"), xml).unwrap();
+
+        let (runner, cmd) = detect_runner_and_command(&root, Some("CartTest.java")).unwrap();
+        assert_eq!(
+            (runner.name(), cmd.as_str()),
+            ("maven", "mvn -q -B test CartTest.java")
+        );
+        assert_eq!(language_of("maven"), "java");
+        let from_xml = junit_result(runner, &root, before).unwrap();
+        assert_eq!(from_xml.failures, ["com.acme.CartTest.totals"]);
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        assert!(junit_result(runner, &root, later).is_none());
+        assert_eq!(
+            runner.parse("Tests run: 3, Failures: 1, Errors: 0, Skipped: 1"),
+            TestSuiteResult::counts((1, 1))
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
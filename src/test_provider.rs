@@ -0,0 +1,133 @@
+//! test_provider.rs
+//!
+//! Scripted model backend for deterministic agent-loop runs. Selected with
+//! provider `test`; the script path comes from `OSMOGREP_TEST_SCRIPT` or the
+//! model config's `base_url`.
+//!
+//! Script format (turn N is served by `turns[N - 1]`):
+//! ```json
+//! { "turns": [
+//!     { "output": [{ "type": "function_call", "name": "read_file",
+//!                    "call_id": "c1", "arguments": "{\"path\":\"a.rs\"}" }] },
+//!     { "stream": ["Do", "ne"], "output": [{ "type": "output_text", "text": "Done" }] }
+//! ] }
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{mpsc::Sender, Mutex};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::agent::{AgentEvent, ModelConfig};
+
+pub const TEST_PROVIDER: &str = "test";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedTurn {
+    #[serde(default)]
+    pub stream: Vec<String>,
+    #[serde(default)]
+    pub output: Vec<Value>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Script {
+    turns: Vec<ScriptedTurn>,
+}
+
+pub struct TestProvider {
+    turns: Vec<ScriptedTurn>,
+    state: Mutex<ProviderState>,
+}
+
+#[derive(Default)]
+struct ProviderState {
+    served: usize,
+    requests: Vec<Value>,
+}
+
+pub struct TestResponse {
+    pub value: Value,
+    pub output_streamed: bool,
+}
+
+impl TestProvider {
+    pub fn new(turns: Vec<ScriptedTurn>) -> Self {
+        Self {
+            turns,
+            state: Mutex::new(ProviderState::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw =
+            fs::read_to_string(path).map_err(|e| format!("test script {}: {e}", path.display()))?;
+        let script: Script = serde_json::from_str(&raw)
+            .map_err(|e| format!("test script {}: {e}", path.display()))?;
+        Ok(Self::new(script.turns))
+    }
+
+    /// Returns the provider for `cfg` when it selects the `test` backend.
+    pub fn from_config(cfg: &ModelConfig) -> Option<Result<Self, String>> {
+        if !is_test_provider(cfg) {
+            return None;
+        }
+        let path = env::var("OSMOGREP_TEST_SCRIPT")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+            .or_else(|| cfg.base_url.clone());
+        Some(match path {
+            Some(path) => Self::load(Path::new(path.trim())),
+            None => Err("test provider needs OSMOGREP_TEST_SCRIPT or base_url".to_string()),
+        })
+    }
+
+    /// Serves the next scripted turn, emitting stream deltas the same way the
+    /// SSE client does.
+    pub fn respond(&self, input: &Value, tx: &Sender<AgentEvent>) -> Result<TestResponse, String> {
+        let turn = {
+            let mut state = self.state.lock().map_err(|_| "test provider poisoned")?;
+            state.requests.push(input.clone());
+            state.served += 1;
+            state.served
+        };
+
+        let scripted = self
+            .turns
+            .get(turn - 1)
+            .ok_or_else(|| format!("test script exhausted at turn {turn}"))?;
+        if let Some(error) = scripted.error.as_ref() {
+            return Err(error.clone());
+        }
+
+        for delta in &scripted.stream {
+            let _ = tx.send(AgentEvent::StreamDelta(delta.clone()));
+        }
+        if !scripted.stream.is_empty() {
+            let _ = tx.send(AgentEvent::StreamDone);
+        }
+
+        Ok(TestResponse {
+            value: json!({ "output": scripted.output }),
+            output_streamed: scripted.stream.iter().any(|d| !d.is_empty()),
+        })
+    }
+
+    /// Model inputs received so far, one per served turn.
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<Value> {
+        self.state
+            .lock()
+            .map(|state| state.requests.clone())
+            .unwrap_or_default()
+    }
+}
+
+pub fn is_test_provider(cfg: &ModelConfig) -> bool {
+    cfg.provider.eq_ignore_ascii_case(TEST_PROVIDER)
+}
@@ -0,0 +1,979 @@
+//! testgen.rs
+//!
+//! Provenance ledger for tests the agent writes. Every agent commit that
+//! touches test files is recorded as accepted, and every denied edit to a
+//! test file as rejected, in `.osmogrep/testgen-ledger.json`:
+//!
+//! ```json
+//! { "version": 1, "entries": [ { "outcome": "accepted", "diff_hash": "…", … } ] }
+//! ```
+//!
+//! `osmogrep testgen stats` and `/testgen stats` summarize acceptance and
+//! gate pass rates per week to judge generator quality over time.
+//!
+//! `testgen maintain` uses the same ledger to find generated tests that now
+//! fail because a function they call changed after they were committed, and
+//! hands them to the agent to update with their original intent kept.
+//!
+//! `osmogrep analyze` reports the symbols a diff touches and which of them
+//! no test calls yet, so CI can flag changes that need tests.
+//!
+//! `/testgen candidates` lists those symbols ranked by what the user asked
+//! for and how risky their file is. Picked ones go to the agent to write
+//! tests for; discarded ones land in the ledger with the reason given, so
+//! the ranking can be tuned against them later.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use chrono::{DateTime, Datelike, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::context::risk::RiskScore;
+use crate::context::{indexer, working_diff};
+use crate::coverage::{Coverage, LineCoverage};
+use crate::git::git;
+use crate::mutation::{self, Strength};
+
+const LEDGER_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Accepted,
+    Rejected,
+    /// A test candidate the user turned down before any test was written.
+    Discarded,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub recorded_at: String,
+    pub outcome: Outcome,
+    /// Commit hash for accepted entries.
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// SHA-256 of the committed patch, or of the denied edit's arguments.
+    pub diff_hash: String,
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    pub model: String,
+    pub prompt_version: u32,
+    /// Verification status when the commit was made: `passed`, `failed`,
+    /// `stale` or `unverified`. Absent for rejections.
+    #[serde(default)]
+    pub gate: Option<String>,
+    /// Mutation score of the added tests, once `testgen strength` ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strength: Option<Strength>,
+    /// Why a candidate was discarded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Ledger {
+    version: u32,
+    #[serde(default)]
+    entries: Vec<LedgerEntry>,
+}
+
+pub fn ledger_path(repo_root: &Path) -> PathBuf {
+    repo_root.join(".osmogrep").join("testgen-ledger.json")
+}
+
+pub fn load(repo_root: &Path) -> Result<Vec<LedgerEntry>, String> {
+    let path = ledger_path(repo_root);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str::<Ledger>(&text)
+            .map(|ledger| ledger.entries)
+            .map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+fn append(repo_root: &Path, entry: LedgerEntry) -> Result<(), String> {
+    let mut entries = load(repo_root)?;
+    entries.push(entry);
+    store(repo_root, entries)
+}
+
+fn store(repo_root: &Path, entries: Vec<LedgerEntry>) -> Result<(), String> {
+    let path = ledger_path(repo_root);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let ledger = Ledger {
+        version: LEDGER_VERSION,
+        entries,
+    };
+    let text = serde_json::to_string_pretty(&ledger).map_err(|e| e.to_string())?;
+    fs::write(&path, text + "\n").map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Record HEAD as accepted when it touches test files. Returns the entry,
+/// or `None` when the commit has no tests in it.
+pub fn record_commit(
+    repo_root: &Path,
+    model: &str,
+    prompt_version: u32,
+) -> Result<Option<LedgerEntry>, String> {
+    let commit = git(repo_root, &["rev-parse", "HEAD"])?.trim().to_string();
+    let patch = git(repo_root, &["show", "--format=", "--no-color", "HEAD"])?;
+    let files: Vec<String> = git(repo_root, &["show", "--format=", "--name-only", "HEAD"])?
+        .lines()
+        .map(str::trim)
+        .filter(|path| is_test_path(path))
+        .map(str::to_string)
+        .collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let entry = LedgerEntry {
+        recorded_at: Utc::now().to_rfc3339(),
+        outcome: Outcome::Accepted,
+        commit: Some(commit),
+        diff_hash: hex::encode(Sha256::digest(patch.as_bytes())),
+        files,
+        symbols: test_symbols(&patch),
+        model: model.to_string(),
+        prompt_version,
+        gate: Some(crate::verification::latest_status(repo_root).status),
+        strength: None,
+        reason: None,
+    };
+    append(repo_root, entry.clone())?;
+    Ok(Some(entry))
+}
+
+/// Record a denied edit to a test file.
+pub fn record_rejection(
+    repo_root: &Path,
+    path: &str,
+    edit: &str,
+    model: &str,
+    prompt_version: u32,
+) -> Result<(), String> {
+    append(
+        repo_root,
+        LedgerEntry {
+            recorded_at: Utc::now().to_rfc3339(),
+            outcome: Outcome::Rejected,
+            commit: None,
+            diff_hash: hex::encode(Sha256::digest(edit.as_bytes())),
+            files: vec![path.to_string()],
+            symbols: test_symbols(edit),
+            model: model.to_string(),
+            prompt_version,
+            gate: None,
+            strength: None,
+            reason: None,
+        },
+    )
+}
+
+/// Record a test candidate the user discarded, with their reason.
+pub fn record_discard(
+    repo_root: &Path,
+    candidate: &TestCandidate,
+    reason: &str,
+    model: &str,
+    prompt_version: u32,
+) -> Result<(), String> {
+    let key = format!("{}\0{}", candidate.path, candidate.symbol);
+    append(
+        repo_root,
+        LedgerEntry {
+            recorded_at: Utc::now().to_rfc3339(),
+            outcome: Outcome::Discarded,
+            commit: None,
+            diff_hash: hex::encode(Sha256::digest(key.as_bytes())),
+            files: vec![candidate.path.clone()],
+            symbols: vec![candidate.symbol.clone()],
+            model: model.to_string(),
+            prompt_version,
+            gate: None,
+            strength: None,
+            reason: Some(reason.to_string()),
+        },
+    )
+}
+
+pub fn is_test_path(path: &str) -> bool {
+    let path = path.replace('\\', "/").to_ascii_lowercase();
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let stem = name.split('.').next().unwrap_or(name);
+    path.split('/')
+        .any(|dir| matches!(dir, "tests" | "test" | "__tests__" | "spec"))
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_tests")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+/// Test names introduced by added lines of `patch` (or of raw source).
+pub fn test_symbols(patch: &str) -> Vec<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(concat!(
+            r#"(?:\bfn\s+(\w+)\s*\(|\bdef\s+(test\w*)\s*\(|\bfunc\s+(Test\w*)\s*\("#,
+            r#"|\b(?:it|test)\(\s*["'`]([^"'`]+)["'`])"#
+        ))
+        .expect("test symbol regex")
+    });
+
+    let is_patch = patch.lines().any(|line| line.starts_with("@@"));
+    let mut symbols = Vec::new();
+    let mut after_test_attr = false;
+    for line in patch.lines() {
+        let line = match line.strip_prefix('+') {
+            Some(added) if !line.starts_with("+++") => added,
+            _ if is_patch => continue,
+            _ => line,
+        };
+        let trimmed = line.trim();
+        if trimmed.starts_with("#[") && trimmed.contains("test") {
+            after_test_attr = true;
+            continue;
+        }
+        if let Some(caps) = re.captures(line) {
+            let rust_fn = caps.get(1).map(|m| m.as_str());
+            let name = match rust_fn {
+                Some(name) if after_test_attr || name.starts_with("test") => Some(name),
+                Some(_) => None,
+                None => (2..=4).find_map(|i| caps.get(i)).map(|m| m.as_str()),
+            };
+            if let Some(name) = name {
+                if !symbols.iter().any(|s| s == name) {
+                    symbols.push(name.to_string());
+                }
+            }
+        }
+        if !trimmed.is_empty() && !trimmed.starts_with("#[") {
+            after_test_attr = false;
+        }
+    }
+    symbols
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub discarded: usize,
+    pub gate_passed: usize,
+}
+
+impl Stats {
+    pub fn acceptance_rate(&self) -> Option<f64> {
+        let total = self.accepted + self.rejected;
+        (total > 0).then(|| self.accepted as f64 / total as f64)
+    }
+
+    pub fn gate_pass_rate(&self) -> Option<f64> {
+        (self.accepted > 0).then(|| self.gate_passed as f64 / self.accepted as f64)
+    }
+
+    fn add(&mut self, entry: &LedgerEntry) {
+        match entry.outcome {
+            Outcome::Accepted => {
+                self.accepted += 1;
+                if entry.gate.as_deref() == Some("passed") {
+                    self.gate_passed += 1;
+                }
+            }
+            Outcome::Rejected => self.rejected += 1,
+            Outcome::Discarded => self.discarded += 1,
+        }
+    }
+}
+
+/// Totals plus one row per ISO week, oldest first.
+pub fn stats(entries: &[LedgerEntry]) -> (Stats, Vec<(String, Stats)>) {
+    let mut total = Stats::default();
+    let mut weekly: BTreeMap<String, Stats> = BTreeMap::new();
+    for entry in entries {
+        total.add(entry);
+        let week = DateTime::parse_from_rfc3339(&entry.recorded_at)
+            .map(|at| {
+                let week = at.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            })
+            .unwrap_or_else(|_| "unknown".to_string());
+        weekly.entry(week).or_default().add(entry);
+    }
+    (total, weekly.into_iter().collect())
+}
+
+pub fn stats_report(entries: &[LedgerEntry]) -> Vec<String> {
+    if entries.is_empty() {
+        return vec!["No generated tests recorded yet.".to_string()];
+    }
+    let rate = |r: Option<f64>| r.map_or("-".to_string(), |r| format!("{:.0}%", r * 100.0));
+    let (total, weekly) = stats(entries);
+
+    let mut summary = format!(
+        "accepted {}  rejected {}  acceptance {}  gate pass {}",
+        total.accepted,
+        total.rejected,
+        rate(total.acceptance_rate()),
+        rate(total.gate_pass_rate())
+    );
+    if total.discarded > 0 {
+        summary.push_str(&format!("  discarded candidates {}", total.discarded));
+    }
+    let mut lines = vec![
+        summary,
+        String::new(),
+        format!(
+            "{:<10} {:>8} {:>8} {:>10} {:>9}",
+            "week", "accepted", "rejected", "acceptance", "gate pass"
+        ),
+    ];
+    lines.extend(weekly.iter().map(|(week, s)| {
+        format!(
+            "{:<10} {:>8} {:>8} {:>10} {:>9}",
+            week,
+            s.accepted,
+            s.rejected,
+            rate(s.acceptance_rate()),
+            rate(s.gate_pass_rate())
+        )
+    }));
+    let scored: Vec<&Strength> = entries.iter().filter_map(|e| e.strength.as_ref()).collect();
+    if !scored.is_empty() {
+        let average = scored.iter().map(|s| s.percent()).sum::<f64>() / scored.len() as f64;
+        lines.push(String::new());
+        lines.push(format!(
+            "test strength {average:.0}% on average over {} measured commit(s)",
+            scored.len()
+        ));
+    }
+    lines
+}
+
+/// A generated test whose callees changed after the commit that added it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleTest {
+    pub test: String,
+    pub file: String,
+    pub commit: String,
+    /// Non-test functions the test calls whose bodies changed since `commit`.
+    pub changed: Vec<String>,
+}
+
+/// Accepted tests from the ledger that call a function changed since they
+/// were committed, by their latest entry. Tests that no longer exist in the
+/// index are skipped.
+pub fn changed_since_generation(repo_root: &Path, entries: &[LedgerEntry]) -> Vec<StaleTest> {
+    let mut origins: BTreeMap<&str, (&str, &[String])> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.outcome == Outcome::Accepted) {
+        if let Some(commit) = entry.commit.as_deref() {
+            for test in &entry.symbols {
+                origins.insert(test, (commit, &entry.files));
+            }
+        }
+    }
+    if origins.is_empty() {
+        return Vec::new();
+    }
+
+    let ctx = indexer::load_or_build(repo_root);
+    let relative = |file: &str| {
+        let path = Path::new(file);
+        path.strip_prefix(repo_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+    let mut diffs: BTreeMap<&str, Vec<(String, usize, usize)>> = BTreeMap::new();
+    let mut stale = Vec::new();
+    for (test, (commit, files)) in origins {
+        let Some(symbol) = ctx
+            .symbols
+            .iter()
+            .find(|s| s.name == test && files.contains(&relative(&s.file)))
+        else {
+            continue;
+        };
+        let hunks = diffs.entry(commit).or_insert_with(|| {
+            git(repo_root, &["diff", "-U0", "--no-color", commit])
+                .map(|diff| working_diff::changed_ranges(&diff))
+                .unwrap_or_default()
+        });
+        let changed: BTreeSet<String> = ctx
+            .symbols
+            .iter()
+            .filter(|s| s.name != test && symbol.custom_calls.contains(&s.name))
+            .filter(|s| {
+                let file = relative(&s.file);
+                !is_test_path(&file)
+                    && hunks.iter().any(|(path, start, end)| {
+                        *path == file && *start <= s.line_end && s.line_start <= *end
+                    })
+            })
+            .map(|s| s.name.clone())
+            .collect();
+        if !changed.is_empty() {
+            stale.push(StaleTest {
+                test: test.to_string(),
+                file: relative(&symbol.file),
+                commit: commit.to_string(),
+                changed: changed.into_iter().collect(),
+            });
+        }
+    }
+    stale
+}
+
+/// Whether `output` from a test run reports `test` as failed, going by the
+/// failure markers of cargo, pytest, jest and go.
+pub fn reported_failing(output: &str, test: &str) -> bool {
+    output.lines().any(|line| {
+        (line.contains("FAIL") || line.contains('✕'))
+            && line.match_indices(test).any(|(at, _)| {
+                let word = |c: char| c.is_alphanumeric() || c == '_';
+                !line[..at].ends_with(word) && !line[at + test.len()..].starts_with(word)
+            })
+    })
+}
+
+/// Runs the suite and returns the generated tests that fail and whose
+/// callees changed since they were committed.
+pub fn stale_tests(repo_root: &Path) -> Result<Vec<StaleTest>, String> {
+    let candidates = changed_since_generation(repo_root, &load(repo_root)?);
+    if candidates.is_empty() {
+        return Ok(candidates);
+    }
+    let run = crate::test_harness::run_tests(repo_root, None)?;
+    if run.success {
+        return Ok(Vec::new());
+    }
+    Ok(candidates
+        .into_iter()
+        .filter(|c| reported_failing(&run.output, &c.test))
+        .collect())
+}
+
+/// Agent task that updates each stale test. Edits and the final commit go
+/// through the usual approval flow, and the commit lands in the ledger with
+/// its gate status like any other generated test.
+pub fn maintenance_prompt(stale: &[StaleTest]) -> String {
+    let mut prompt = String::from(
+        "Some tests written earlier by this agent now fail because the code they exercise \
+         changed. Update each one to the new behavior while keeping what it was meant to \
+         check.\n\n",
+    );
+    for test in stale {
+        let short = &test.commit[..test.commit.len().min(12)];
+        prompt.push_str(&format!(
+            "- `{}` in {} (added in {short}); changed since: {}. Original intent: \
+             `git show {short} -- {}`; what changed: `git diff {short} -- <file>`.\n",
+            test.test,
+            test.file,
+            test.changed.join(", "),
+            test.file
+        ));
+    }
+    prompt.push_str(
+        "\nRules:\n\
+         1) Read the original test and the change before editing; if the new behavior looks \
+            like a regression rather than an intended change, say so and leave the test alone.\n\
+         2) Keep the test's name and the property it asserts; only adjust inputs, expected \
+            values or setup that the change legitimately affects. Do not delete or skip tests.\n\
+         3) Run the updated tests, then commit them with a message naming the tests.\n",
+    );
+    prompt
+}
+
+/// Line ranges `(path, start, end)` of the non-test functions that the
+/// tests of `entry` call.
+pub fn tested_code(repo_root: &Path, entry: &LedgerEntry) -> Vec<(String, usize, usize)> {
+    let ctx = indexer::load_or_build(repo_root);
+    let relative = |file: &str| {
+        let path = Path::new(file);
+        path.strip_prefix(repo_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+    let callees: BTreeSet<&str> = ctx
+        .symbols
+        .iter()
+        .filter(|s| entry.symbols.contains(&s.name) && entry.files.contains(&relative(&s.file)))
+        .flat_map(|s| s.custom_calls.iter().map(String::as_str))
+        .collect();
+    ctx.symbols
+        .iter()
+        .filter(|s| callees.contains(s.name.as_str()) && !entry.symbols.contains(&s.name))
+        .map(|s| (relative(&s.file), s.line_start, s.line_end))
+        .filter(|(path, _, _)| !is_test_path(path))
+        .collect()
+}
+
+/// Mutation-tests the code exercised by the latest accepted generated
+/// tests, running only those tests, and stores the score on the ledger
+/// entry. Returns the entry's commit and the score.
+pub fn measure_strength(repo_root: &Path) -> Result<(String, Strength), String> {
+    let mut entries = load(repo_root)?;
+    let index = entries
+        .iter()
+        .rposition(|e| e.outcome == Outcome::Accepted && !e.symbols.is_empty())
+        .ok_or("No accepted generated tests in the ledger yet.")?;
+    let entry = &entries[index];
+    let targets = tested_code(repo_root, entry);
+    if targets.is_empty() {
+        return Err(format!(
+            "No code called by {} found in the index.",
+            entry.symbols.join(", ")
+        ));
+    }
+    let framework = crate::test_harness::detect_frameworks(repo_root)
+        .first()
+        .copied()
+        .unwrap_or_default();
+    let group = crate::fix_tests::FailureGroup {
+        file: (entry.files.len() == 1).then(|| entry.files[0].clone()),
+        tests: entry.symbols.clone(),
+        excerpt: String::new(),
+    };
+    let test_target = crate::fix_tests::rerun_target(framework, &group);
+    let strength = mutation::measure(repo_root, &targets, test_target.as_deref())?;
+    let commit = entry.commit.clone().unwrap_or_default();
+    entries[index].strength = Some(strength.clone());
+    store(repo_root, entries)?;
+    Ok((commit, strength))
+}
+
+/// A touched symbol and the tests that already call it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestCandidate {
+    pub symbol: String,
+    pub path: String,
+    pub line: usize,
+    pub covered_by: Vec<String>,
+    /// Its changed lines executed by the last `/coverage` run.
+    pub changed_lines_covered: Option<LineCoverage>,
+}
+
+/// JSON report of `osmogrep analyze`.
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    pub base: String,
+    pub files: Vec<working_diff::ChangedFile>,
+    pub symbols: Vec<working_diff::TouchedSymbol>,
+    pub callers: Vec<String>,
+    /// Workspace packages owning the changed files.
+    pub packages: Vec<crate::workspace::Package>,
+    /// Command testing only those packages, when they share a runner.
+    pub test_command: Option<String>,
+    pub test_candidates: Vec<TestCandidate>,
+    /// True when some touched non-test symbol has no test calling it.
+    pub requires_tests: bool,
+    /// Score from `.osmogrep/risk.toml`; absent for a clean tree.
+    pub risk: Option<crate::context::risk::RiskScore>,
+}
+
+/// Touched non-test symbols with the tests that call them. A caller counts
+/// as a test when it lives in a test file or its name starts with `test`.
+/// With coverage collected, the least covered changes come first.
+pub fn test_candidates(repo_root: &Path, diff: &working_diff::WorkingDiff) -> Vec<TestCandidate> {
+    let ctx = indexer::load_or_build(repo_root);
+    let coverage = Coverage::load(repo_root);
+    let is_test = |name: &str| {
+        name.to_ascii_lowercase().starts_with("test")
+            || ctx.symbols.iter().any(|s| {
+                s.name == name
+                    && is_test_path(
+                        &Path::new(&s.file)
+                            .strip_prefix(repo_root)
+                            .unwrap_or(Path::new(&s.file))
+                            .to_string_lossy(),
+                    )
+            })
+    };
+    let mut candidates: Vec<TestCandidate> = diff
+        .symbols
+        .iter()
+        .filter(|touched| !is_test_path(&touched.path) && !is_test(&touched.name))
+        .map(|touched| {
+            let callers: BTreeSet<&str> = ctx
+                .symbols
+                .iter()
+                .filter(|s| s.name == touched.name)
+                .flat_map(|s| s.called_by.iter().map(String::as_str))
+                .collect();
+            TestCandidate {
+                symbol: touched.name.clone(),
+                path: touched.path.clone(),
+                line: touched.line,
+                covered_by: callers
+                    .into_iter()
+                    .filter(|caller| is_test(caller))
+                    .map(str::to_string)
+                    .collect(),
+                changed_lines_covered: coverage.as_ref().and_then(|coverage| {
+                    let ranges: Vec<(usize, usize)> = diff
+                        .hunks
+                        .iter()
+                        .filter(|(path, _, _)| *path == touched.path)
+                        .map(|(_, start, end)| (*start.max(&touched.line), *end.min(&touched.end)))
+                        .filter(|(start, end)| start <= end)
+                        .collect();
+                    coverage.lines_in(&touched.path, &ranges)
+                }),
+            }
+        })
+        .collect();
+    // Stable, so candidates without coverage data keep their order at the end.
+    candidates.sort_by(|a, b| {
+        let percent = |c: &TestCandidate| c.changed_lines_covered.map(|c| c.percent());
+        match (percent(a), percent(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+    });
+    candidates
+}
+
+/// Orders `candidates` for the picker: those whose symbol or path names a
+/// word of `intent` first, then untested ones, then by the risk points of
+/// their file. Ties keep the coverage order of [`test_candidates`].
+pub fn rank_candidates(candidates: &mut [TestCandidate], risk: &RiskScore, intent: &str) {
+    let words: Vec<String> = intent
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.len() >= 3)
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let hits = |c: &TestCandidate| {
+        let symbol = c.symbol.to_ascii_lowercase();
+        let path = c.path.to_ascii_lowercase();
+        words
+            .iter()
+            .filter(|word| symbol.contains(word.as_str()) || path.contains(word.as_str()))
+            .count()
+    };
+    candidates.sort_by_cached_key(|c| {
+        (
+            std::cmp::Reverse(hits(c)),
+            !c.covered_by.is_empty(),
+            std::cmp::Reverse(risk.points_for(&c.path)),
+        )
+    });
+}
+
+/// Agent task that writes tests for the picked candidates. Edits and the
+/// commit go through the usual approval flow and land in the ledger.
+pub fn candidates_prompt(picked: &[&TestCandidate]) -> String {
+    let mut prompt = String::from(
+        "Write tests for these functions changed in the working tree, one focused test per \
+         behavior the change affects.\n\n",
+    );
+    for candidate in picked {
+        prompt.push_str(&format!(
+            "- `{}` in {}:{}",
+            candidate.symbol, candidate.path, candidate.line
+        ));
+        if candidate.covered_by.is_empty() {
+            prompt.push_str("; no test calls it yet");
+        } else {
+            prompt.push_str(&format!(
+                "; already called by {}",
+                candidate.covered_by.join(", ")
+            ));
+        }
+        if let Some(covered) = candidate.changed_lines_covered {
+            prompt.push_str(&format!("; changed lines covered: {covered}"));
+        }
+        prompt.push_str(".\n");
+    }
+    prompt.push_str(
+        "\nRules:\n\
+         1) Read each function and the diff before writing; put the tests where this repo keeps \
+            tests for that file and follow their style.\n\
+         2) Extend an existing test only when it already covers the function; otherwise add \
+            new tests. Do not change the functions under test.\n\
+         3) Run the new tests, then commit them with a message naming the functions.\n",
+    );
+    prompt
+}
+
+/// Diff analysis against `base` plus test candidates, without the TUI.
+pub fn diff_report(repo_root: &Path, base: &str) -> DiffReport {
+    let Some(diff) = working_diff::analyze_against(repo_root, base) else {
+        return DiffReport {
+            base: base.to_string(),
+            files: Vec::new(),
+            symbols: Vec::new(),
+            callers: Vec::new(),
+            packages: Vec::new(),
+            test_command: None,
+            test_candidates: Vec::new(),
+            requires_tests: false,
+            risk: None,
+        };
+    };
+    let test_candidates = test_candidates(repo_root, &diff);
+    let test_command =
+        crate::workspace::test_command(repo_root, &diff.packages).map(|(_, command)| command);
+    DiffReport {
+        base: base.to_string(),
+        requires_tests: test_candidates.iter().any(|c| c.covered_by.is_empty()),
+        files: diff.files,
+        symbols: diff.symbols,
+        callers: diff.callers,
+        packages: diff.packages,
+        test_command,
+        test_candidates,
+        risk: Some(diff.risk),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use uuid::Uuid;
+
+    fn entry(at: &str, outcome: Outcome, gate: Option<&str>) -> LedgerEntry {
+        LedgerEntry {
+            recorded_at: at.to_string(),
+            outcome,
+            commit: None,
+            diff_hash: "h".into(),
+            files: vec!["tests/a.rs".into()],
+            symbols: Vec::new(),
+            model: "m".into(),
+            prompt_version: 1,
+            gate: gate.map(str::to_string),
+            strength: None,
+            reason: None,
+        }
+    }
+
+    fn candidate(symbol: &str, path: &str, covered_by: &[&str]) -> TestCandidate {
+        TestCandidate {
+            symbol: symbol.into(),
+            path: path.into(),
+            line: 1,
+            covered_by: covered_by.iter().map(|t| t.to_string()).collect(),
+            changed_lines_covered: None,
+        }
+    }
+
+    #[test]
+    fn ranks_candidates_by_intent_then_untested_then_risk() {
+        let risk = crate::context::risk::RiskRules::parse(
+            "[[paths]]\npattern = \"src/db/**\"\nweight = 40\n",
+        )
+        .unwrap()
+        .assess(
+            &["src/db/pool.rs", "src/ui.rs", "src/auth.rs"].map(|path| working_diff::ChangedFile {
+                path: path.into(),
+                added: 1,
+                removed: 0,
+                untracked: false,
+                coverage: None,
+            }),
+            0,
+        );
+        let mut candidates = vec![
+            candidate("render", "src/ui.rs", &[]),
+            candidate("connect", "src/db/pool.rs", &["test_connect"]),
+            candidate("checkout", "src/db/pool.rs", &[]),
+            candidate("login", "src/auth.rs", &["test_login"]),
+        ];
+        let order = |candidates: &[TestCandidate]| -> Vec<String> {
+            candidates.iter().map(|c| c.symbol.clone()).collect()
+        };
+
+        rank_candidates(&mut candidates, &risk, "");
+        assert_eq!(
+            order(&candidates),
+            ["checkout", "render", "connect", "login"]
+        );
+
+        rank_candidates(&mut candidates, &risk, "fix the Login flow, it is broken");
+        assert_eq!(
+            order(&candidates),
+            ["login", "checkout", "render", "connect"]
+        );
+
+        let prompt = candidates_prompt(&[&candidates[0], &candidates[2]]);
+        assert!(prompt.contains("- `login` in src/auth.rs:1; already called by test_login.\n"));
+        assert!(prompt.contains("- `render` in src/ui.rs:1; no test calls it yet.\n"));
+    }
+
+    #[test]
+    fn detects_test_paths_and_symbols() {
+        assert!(is_test_path("tests/cli.rs"));
+        assert!(is_test_path("pkg/test_parser.py"));
+        assert!(is_test_path("web/src/app.spec.ts"));
+        assert!(is_test_path("go/server_test.go"));
+        assert!(!is_test_path("src/agent.rs"));
+
+        let patch = "+++ b/tests/cli.rs\n@@ -1 +1 @@\n fn old_test() {}\n+#[test]\n+fn parses_flags() {}\n+fn helper() {}\n\
+                     -fn removed_test() {}\n+def test_load():\n+it('renders rows', () => {})\n";
+        assert_eq!(
+            test_symbols(patch),
+            vec!["parses_flags", "test_load", "renders rows"]
+        );
+    }
+
+    #[test]
+    fn ledger_round_trips_and_summarizes_by_week() {
+        let root = std::env::temp_dir().join(format!("osmogrep-testgen-{}", Uuid::new_v4()));
+        record_rejection(&root, "tests/a.rs", "+fn test_a() {}", "gpt", 1).unwrap();
+        record_discard(
+            &root,
+            &candidate("parse", "src/a.rs", &[]),
+            "trivial",
+            "gpt",
+            1,
+        )
+        .unwrap();
+        let loaded = load(&root).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].symbols, vec!["test_a"]);
+        assert_eq!(loaded[1].outcome, Outcome::Discarded);
+        assert_eq!(loaded[1].reason.as_deref(), Some("trivial"));
+        let (total, _) = stats(&loaded);
+        assert_eq!((total.rejected, total.discarded), (1, 1));
+        assert_eq!(total.acceptance_rate(), Some(0.0));
+        assert!(stats_report(&loaded)[0].ends_with("discarded candidates 1"));
+        let _ = fs::remove_dir_all(&root);
+
+        let entries = vec![
+            entry("2024-03-04T10:00:00Z", Outcome::Accepted, Some("passed")),
+            entry("2024-03-05T10:00:00Z", Outcome::Accepted, Some("failed")),
+            entry("2024-03-06T10:00:00Z", Outcome::Rejected, None),
+            entry("2024-03-12T10:00:00Z", Outcome::Accepted, Some("passed")),
+        ];
+        let (total, weekly) = stats(&entries);
+        assert_eq!(total.acceptance_rate(), Some(0.75));
+        assert_eq!(total.gate_pass_rate(), Some(2.0 / 3.0));
+        assert_eq!(weekly.len(), 2);
+        assert_eq!(weekly[0].0, "2024-W10");
+
+        let report = stats_report(&entries);
+        assert!(report[0].contains("acceptance 75%"));
+        assert!(report[0].contains("gate pass 67%"));
+        assert_eq!(report.len(), 5);
+
+        let mut scored = entries;
+        scored[0].strength = Some(Strength {
+            killed: 3,
+            total: 4,
+            survivors: vec!["src/lib.rs:2 `+` -> `-`".into()],
+        });
+        scored[3].strength = Some(Strength {
+            killed: 1,
+            total: 4,
+            survivors: Vec::new(),
+        });
+        let report = stats_report(&scored);
+        assert_eq!(
+            report.last().unwrap(),
+            "test strength 50% on average over 2 measured commit(s)"
+        );
+    }
+
+    #[test]
+    fn finds_generated_tests_whose_callees_changed() {
+        let root = std::env::temp_dir().join(format!("osmogrep-maintain-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        let run = |args: &[&str]| {
+            assert!(Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(args)
+                .status()
+                .unwrap()
+                .success());
+        };
+        run(&["init", "-q"]);
+        run(&["config", "user.email", "test@example.com"]);
+        run(&["config", "user.name", "Osmogrep Test"]);
+        fs::write(root.join(".gitignore"), ".context/\n.osmogrep/\n").unwrap();
+        let lib = "pub fn total(a: u32) -> u32 {\n    a + 1\n}\n\npub fn other() {}\n";
+        fs::write(root.join("src/lib.rs"), lib).unwrap();
+        fs::write(
+            root.join("tests/total.rs"),
+            "#[test]\nfn adds_one() {\n    let got = total(1);\n    assert_eq!(got, 2);\n}\n",
+        )
+        .unwrap();
+        run(&["add", "."]);
+        run(&["commit", "-q", "-m", "add tests"]);
+        let entry = record_commit(&root, "gpt", 1).unwrap().unwrap();
+        assert_eq!(entry.symbols, vec!["adds_one"]);
+        assert_eq!(
+            tested_code(&root, &entry),
+            [("src/lib.rs".to_string(), 1, 3)]
+        );
+
+        let entries = load(&root).unwrap();
+        assert!(changed_since_generation(&root, &entries).is_empty());
+        fs::write(root.join("src/lib.rs"), lib.replace("a + 1", "a + 2")).unwrap();
+        assert_eq!(
+            changed_since_generation(&root, &entries),
+            vec![StaleTest {
+                test: "adds_one".into(),
+                file: "tests/total.rs".into(),
+                commit: entry.commit.unwrap(),
+                changed: vec!["total".into()],
+            }]
+        );
+
+        let changed = lib
+            .replace("a + 1", "a + 2")
+            .replace("other() {}", "other() {\n    total(0);\n}");
+        fs::write(root.join("src/lib.rs"), changed).unwrap();
+        let report = diff_report(&root, "HEAD");
+        assert!(report.requires_tests);
+        let coverage: Vec<(&str, &[String])> = report
+            .test_candidates
+            .iter()
+            .map(|c| (c.symbol.as_str(), c.covered_by.as_slice()))
+            .collect();
+        assert_eq!(
+            coverage,
+            vec![("total", &["adds_one".to_string()][..]), ("other", &[][..])]
+        );
+
+        fs::write(
+            root.join(crate::coverage::LCOV_FILE),
+            "SF:src/lib.rs\nDA:1,1\nDA:2,1\nDA:5,1\nDA:6,0\nend_of_record\n",
+        )
+        .unwrap();
+        let ranked: Vec<(String, f64)> = diff_report(&root, "HEAD")
+            .test_candidates
+            .into_iter()
+            .map(|c| (c.symbol, c.changed_lines_covered.unwrap().percent()))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("other".into(), 50.0), ("total".into(), 100.0)]
+        );
+        let _ = fs::remove_dir_all(&root);
+
+        let output =
+            "test adds_one ... FAILED\ntest adds_one_twice ... ok\n--- FAIL: TestParse (0.00s)\n";
+        assert!(reported_failing(output, "adds_one"));
+        assert!(reported_failing(output, "TestParse"));
+        assert!(!reported_failing(output, "adds_one_twice"));
+        assert!(!reported_failing(output, "adds"));
+    }
+}
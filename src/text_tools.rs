@@ -0,0 +1,366 @@
+//! text_tools.rs
+//!
+//! Tool calling for models without native function calls, switched on by
+//! `text_tools = true` in a tool profile. The tool schema goes into the
+//! system prompt instead of the request's `tools`, the model answers with
+//!
+//! ```text
+//! <tool_call>
+//! {"name": "read_file", "arguments": {"path": "src/main.rs"}}
+//! </tool_call>
+//! ```
+//!
+//! and the calls are pulled out of its text and turned back into
+//! `function_call` items, so the agent loop runs unchanged. Earlier calls
+//! and their results are replayed to the model as plain messages.
+//!
+//! Extraction is lenient: `<tool_call>` blocks, fenced JSON blocks and bare
+//! JSON objects are accepted, with `arguments`, `parameters` or `args`,
+//! arguments as an object or a JSON string, and trailing commas. Only
+//! objects naming an offered tool count as calls.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Instructions and tool list appended to the system prompt.
+pub fn tool_prompt(schemas: &[Value]) -> String {
+    let mut prompt = String::from(
+        "\n\n## Tools\n\
+         You can call the tools below. To call one, reply with only a block like this \
+         (one block per call, several blocks allowed):\n\
+         <tool_call>\n\
+         {\"name\": \"<tool name>\", \"arguments\": {<arguments as JSON>}}\n\
+         </tool_call>\n\
+         Each result comes back in a <tool_result> block. When the task is done, reply \
+         in plain text with no tool_call block.\n",
+    );
+    for schema in schemas {
+        let name = schema.get("name").and_then(Value::as_str).unwrap_or("");
+        let description = schema
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        let params = schema.get("parameters").cloned().unwrap_or(json!({}));
+        prompt.push_str(&format!(
+            "\n### {name}\n{description}\nParameters: {params}\n"
+        ));
+    }
+    prompt
+}
+
+/// `input` with the tool prompt added to the system message and earlier
+/// `function_call`/`function_call_output` items rewritten as messages.
+pub fn adapt_input(input: &Value, schemas: &[Value]) -> Value {
+    let items = input.as_array().cloned().unwrap_or_default();
+    let names: HashMap<String, String> = items
+        .iter()
+        .filter(|item| item_type(item) == Some("function_call"))
+        .filter_map(|item| Some((call_id(item)?, item.get("name")?.as_str()?.to_string())))
+        .collect();
+
+    let mut out: Vec<Value> = Vec::new();
+    let mut prompt_added = false;
+    for item in items {
+        match item_type(&item) {
+            Some("function_call") => {
+                let call = json!({
+                    "name": item.get("name").cloned().unwrap_or(Value::Null),
+                    "arguments": item
+                        .get("arguments")
+                        .and_then(Value::as_str)
+                        .and_then(|raw| serde_json::from_str::<Value>(raw).ok())
+                        .unwrap_or_else(|| json!({})),
+                });
+                append_message(
+                    &mut out,
+                    "assistant",
+                    format!("<tool_call>\n{call}\n</tool_call>"),
+                );
+            }
+            Some("function_call_output") => {
+                let id = call_id(&item).unwrap_or_default();
+                let name = names.get(&id).map_or("tool", String::as_str);
+                let output = item.get("output").and_then(Value::as_str).unwrap_or("");
+                append_message(
+                    &mut out,
+                    "user",
+                    format!("<tool_result name=\"{name}\">\n{output}\n</tool_result>"),
+                );
+            }
+            // Reasoning items only make sense next to native calls.
+            Some("reasoning") => {}
+            _ => {
+                let mut item = item;
+                if !prompt_added && item.get("role").and_then(Value::as_str) == Some("system") {
+                    if let Some(Value::String(content)) = item.get_mut("content") {
+                        content.push_str(&tool_prompt(schemas));
+                        prompt_added = true;
+                    }
+                }
+                out.push(item);
+            }
+        }
+    }
+    if !prompt_added {
+        out.insert(
+            0,
+            json!({ "role": "system", "content": tool_prompt(schemas).trim_start() }),
+        );
+    }
+    Value::Array(out)
+}
+
+/// Replaces the text of `resp["output"]` with `function_call` items when it
+/// holds calls to any of `known` tools; a reply without calls is left as is.
+pub fn adapt_output(resp: &mut Value, known: &[String]) {
+    let Some(output) = resp.get_mut("output").and_then(Value::as_array_mut) else {
+        return;
+    };
+    let text = output
+        .iter()
+        .flat_map(|item| match item_type(item) {
+            Some("output_text") => vec![item.get("text")],
+            Some("message") => item
+                .get("content")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter(|c| item_type(c) == Some("output_text"))
+                .map(|c| c.get("text"))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .filter_map(|text| text.and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let calls = extract_calls(&text, known);
+    if calls.is_empty() {
+        return;
+    }
+    output.retain(|item| !matches!(item_type(item), Some("output_text" | "message")));
+    output.extend(calls.into_iter().map(|(name, args)| {
+        json!({
+            "type": "function_call",
+            "name": name,
+            "arguments": args.to_string(),
+            "call_id": format!("call_{}", Uuid::new_v4().simple()),
+        })
+    }));
+}
+
+/// Tool calls in `text`, as `(name, arguments)`, in order.
+pub fn extract_calls(text: &str, known: &[String]) -> Vec<(String, Value)> {
+    let tagged = Regex::new(r"(?s)<tool_call>(.*?)(?:</tool_call>|\z)").unwrap();
+    let blocks: Vec<&str> = tagged
+        .captures_iter(text)
+        .filter_map(|cap| cap.get(1).map(|m| m.as_str()))
+        .collect();
+    let calls = calls_in_blocks(&blocks, known);
+    if !calls.is_empty() {
+        return calls;
+    }
+
+    let fenced = Regex::new(r"(?s)```[a-zA-Z]*\s*\n(.*?)```").unwrap();
+    let blocks: Vec<&str> = fenced
+        .captures_iter(text)
+        .filter_map(|cap| cap.get(1).map(|m| m.as_str()))
+        .collect();
+    let calls = calls_in_blocks(&blocks, known);
+    if !calls.is_empty() {
+        return calls;
+    }
+
+    calls_in_text(text, known)
+}
+
+fn calls_in_blocks(blocks: &[&str], known: &[String]) -> Vec<(String, Value)> {
+    let trailing_comma = Regex::new(r",(\s*[}\]])").unwrap();
+    let fence = Regex::new(r"^\s*```[a-zA-Z]*\s*|\s*```\s*$").unwrap();
+    let mut calls = Vec::new();
+    for block in blocks {
+        let block = fence.replace_all(block, "");
+        let parsed = serde_json::from_str::<Value>(block.trim()).or_else(|_| {
+            serde_json::from_str::<Value>(trailing_comma.replace_all(block.trim(), "$1").as_ref())
+        });
+        match parsed {
+            Ok(value) => collect_calls(&value, known, &mut calls),
+            Err(_) => calls.extend(calls_in_text(&block, known)),
+        }
+    }
+    calls
+}
+
+/// Calls in JSON values embedded anywhere in `text`.
+fn calls_in_text(text: &str, known: &[String]) -> Vec<(String, Value)> {
+    let mut calls = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find(['{', '[']) {
+        let start = pos + offset;
+        let mut values = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+        match values.next() {
+            Some(Ok(value)) => {
+                let before = calls.len();
+                collect_calls(&value, known, &mut calls);
+                pos = if calls.len() > before {
+                    start + values.byte_offset()
+                } else {
+                    start + 1
+                };
+            }
+            _ => pos = start + 1,
+        }
+    }
+    calls
+}
+
+fn collect_calls(value: &Value, known: &[String], calls: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_calls(item, known, calls)),
+        Value::Object(map) => {
+            // OpenAI-style {"function": {"name", "arguments"}} nests the call.
+            if let Some(function @ Value::Object(_)) = map.get("function") {
+                return collect_calls(function, known, calls);
+            }
+            if let Some(Value::Array(nested)) = map.get("tool_calls") {
+                return nested
+                    .iter()
+                    .for_each(|item| collect_calls(item, known, calls));
+            }
+            let Some(name) = ["name", "tool", "tool_name"]
+                .iter()
+                .find_map(|key| map.get(*key).and_then(Value::as_str))
+            else {
+                return;
+            };
+            if !known.iter().any(|k| k == name) {
+                return;
+            }
+            let args = ["arguments", "parameters", "args", "input"]
+                .iter()
+                .find_map(|key| map.get(*key))
+                .map(|args| match args {
+                    Value::String(raw) => serde_json::from_str(raw).unwrap_or_else(|_| json!({})),
+                    other => other.clone(),
+                })
+                .filter(Value::is_object)
+                .unwrap_or_else(|| json!({}));
+            calls.push((name.to_string(), args));
+        }
+        _ => {}
+    }
+}
+
+fn item_type(item: &Value) -> Option<&str> {
+    item.get("type").and_then(Value::as_str)
+}
+
+fn call_id(item: &Value) -> Option<String> {
+    item.get("call_id").map(|id| match id {
+        Value::String(id) => id.clone(),
+        other => other.to_string(),
+    })
+}
+
+fn append_message(out: &mut Vec<Value>, role: &str, text: String) {
+    if let Some(last) = out.last_mut() {
+        if last.get("role").and_then(Value::as_str) == Some(role) && item_type(last).is_none() {
+            if let Some(Value::String(content)) = last.get_mut("content") {
+                content.push('\n');
+                content.push_str(&text);
+                return;
+            }
+        }
+    }
+    out.push(json!({ "role": role, "content": text }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known() -> Vec<String> {
+        vec!["read_file".to_string(), "run_shell".to_string()]
+    }
+
+    #[test]
+    fn extracts_calls_from_tags_fences_and_bare_json() {
+        let text = "Let me look.\n<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"a.rs\",}}\n</tool_call>\n\
+                    <tool_call>{\"name\": \"run_shell\", \"arguments\": \"{\\\"cmd\\\": \\\"ls\\\"}\"}</tool_call>";
+        assert_eq!(
+            extract_calls(text, &known()),
+            [
+                ("read_file".to_string(), json!({ "path": "a.rs" })),
+                ("run_shell".to_string(), json!({ "cmd": "ls" })),
+            ]
+        );
+
+        let fenced =
+            "```json\n{\"tool\": \"read_file\", \"parameters\": {\"path\": \"b.rs\"}}\n```";
+        assert_eq!(
+            extract_calls(fenced, &known()),
+            [("read_file".to_string(), json!({ "path": "b.rs" }))]
+        );
+
+        let bare = "I'll run {\"function\": {\"name\": \"run_shell\", \"arguments\": {\"cmd\": \"pwd\"}}} now";
+        assert_eq!(
+            extract_calls(bare, &known()),
+            [("run_shell".to_string(), json!({ "cmd": "pwd" }))]
+        );
+
+        let prose = "The config is {\"name\": \"demo\", \"version\": 1} and nothing else.";
+        assert!(extract_calls(prose, &known()).is_empty());
+    }
+
+    #[test]
+    fn input_replays_calls_as_messages_and_output_becomes_function_calls() {
+        let schemas = vec![json!({
+            "type": "function",
+            "name": "read_file",
+            "description": "Read a file",
+            "parameters": { "type": "object", "properties": { "path": { "type": "string" } } }
+        })];
+        let input = json!([
+            { "role": "system", "content": "You are a coding agent." },
+            { "role": "user", "content": "show a.rs" },
+            { "type": "reasoning", "summary": [] },
+            { "type": "function_call", "name": "read_file", "call_id": "c1",
+              "arguments": "{\"path\":\"a.rs\"}" },
+            { "type": "function_call_output", "call_id": "c1", "output": "fn a() {}" }
+        ]);
+        let adapted = adapt_input(&input, &schemas);
+        let items = adapted.as_array().unwrap();
+        assert_eq!(items.len(), 4);
+        let system = items[0]["content"].as_str().unwrap();
+        assert!(system.starts_with("You are a coding agent.\n\n## Tools"));
+        assert!(system.contains("### read_file\nRead a file\nParameters: {"));
+        assert_eq!(
+            items[2]["content"],
+            "<tool_call>\n{\"arguments\":{\"path\":\"a.rs\"},\"name\":\"read_file\"}\n</tool_call>"
+        );
+        assert_eq!(
+            items[3],
+            json!({ "role": "user", "content": "<tool_result name=\"read_file\">\nfn a() {}\n</tool_result>" })
+        );
+
+        let mut resp = json!({ "output": [{ "type": "message", "content": [
+            { "type": "output_text", "text": "<tool_call>{\"name\":\"read_file\",\"arguments\":{\"path\":\"b.rs\"}}</tool_call>" }
+        ]}]});
+        adapt_output(&mut resp, &known());
+        let output = resp["output"].as_array().unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0]["type"], "function_call");
+        assert_eq!(output[0]["name"], "read_file");
+        assert_eq!(output[0]["arguments"], "{\"path\":\"b.rs\"}");
+        assert!(output[0]["call_id"].as_str().unwrap().starts_with("call_"));
+
+        let mut plain = json!({ "output": [{ "type": "output_text", "text": "All done." }] });
+        adapt_output(&mut plain, &known());
+        assert_eq!(plain["output"][0]["text"], "All done.");
+    }
+}
@@ -0,0 +1,108 @@
+//! timefmt.rs
+//!
+//! Shared time and duration formatting: humanized durations for spinners,
+//! status lines and reports, log timestamp prefixes, and the precise
+//! timestamps written to persisted transcripts.
+//!
+//! Clock times use the local timezone. `OSMOGREP_TIME_FORMAT` takes a
+//! strftime pattern (e.g. `%I:%M:%S %p`) for locales that want one.
+
+use std::env;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, SecondsFormat};
+
+const DEFAULT_CLOCK_FORMAT: &str = "%H:%M:%S";
+
+/// `850ms`, `4.2s`, `3m 12s`, `2h 5m`, `1d 3h`.
+pub fn duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs == 0 {
+        return format!("{}ms", d.as_millis());
+    }
+    if secs < 60 {
+        return format!("{:.1}s", d.as_secs_f64());
+    }
+    let (days, hours, mins) = (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else {
+        format!("{mins}m {}s", secs % 60)
+    }
+}
+
+pub fn duration_ms(ms: u128) -> String {
+    duration(Duration::from_millis(ms.min(u64::MAX as u128) as u64))
+}
+
+/// `now`, `12s ago`, `3m ago`, `2h ago`, `4d ago`.
+pub fn relative(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0 => "now".to_string(),
+        1..=59 => format!("{secs}s ago"),
+        60..=3_599 => format!("{}m ago", secs / 60),
+        3_600..=86_399 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Wall-clock time in the local timezone and configured format.
+pub fn clock(at: &DateTime<Local>) -> String {
+    let pattern = env::var("OSMOGREP_TIME_FORMAT")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_CLOCK_FORMAT.to_string());
+    clock_with(at, &pattern)
+}
+
+fn clock_with(at: &DateTime<Local>, pattern: &str) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    // Invalid patterns make chrono's Display fail; fall back to the default.
+    if write!(out, "{}", at.format(pattern)).is_err() {
+        out = at.format(DEFAULT_CLOCK_FORMAT).to_string();
+    }
+    out
+}
+
+/// RFC 3339 with milliseconds and offset, for transcripts.
+pub fn precise(at: &DateTime<Local>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn humanizes_durations_and_ages() {
+        assert_eq!(duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(duration(Duration::from_millis(4_230)), "4.2s");
+        assert_eq!(duration(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(
+            duration(Duration::from_secs(2 * 3_600 + 5 * 60 + 9)),
+            "2h 5m"
+        );
+        assert_eq!(duration(Duration::from_secs(27 * 3_600)), "1d 3h");
+        assert_eq!(duration_ms(61_000), "1m 1s");
+
+        assert_eq!(relative(Duration::from_millis(400)), "now");
+        assert_eq!(relative(Duration::from_secs(42)), "42s ago");
+        assert_eq!(relative(Duration::from_secs(200)), "3m ago");
+        assert_eq!(relative(Duration::from_secs(90_000)), "1d ago");
+    }
+
+    #[test]
+    fn formats_clock_and_precise_timestamps() {
+        let at = Local.with_ymd_and_hms(2024, 3, 9, 14, 3, 27).unwrap();
+        assert_eq!(clock_with(&at, DEFAULT_CLOCK_FORMAT), "14:03:27");
+        assert_eq!(clock_with(&at, "%I:%M %p"), "02:03 PM");
+        assert_eq!(clock_with(&at, "%Q"), "14:03:27");
+        assert!(precise(&at).starts_with("2024-03-09T14:03:27.000"));
+    }
+}
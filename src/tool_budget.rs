@@ -1,3 +1,12 @@
+//! tool_budget.rs
+//!
+//! Keeps oversized tool output out of the conversation. Output past the
+//! budget (`[tool_output] limit`, default 40k characters) is saved as
+//! `.context/tool-output/<id>.txt` and replaced by a summary: the first and
+//! last lines, plus error-looking lines from the part in between with their
+//! line offsets, so the model can fetch exactly the range it needs with the
+//! `read_tool_output` tool.
+
 use std::{
     fs::{self, OpenOptions},
     io::Write,
@@ -5,11 +14,25 @@ use std::{
 };
 
 use chrono::Utc;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use uuid::Uuid;
 
-const DEFAULT_THRESHOLD_CHARS: usize = 100_000;
-const DEFAULT_PREVIEW_CHARS: usize = 1_500;
+pub const OUTPUT_DIR: &str = ".context/tool-output";
+const DEFAULT_LIMIT_CHARS: usize = 40_000;
+const DEFAULT_PREVIEW_CHARS: usize = 4_000;
+const DEFAULT_READ_LINES: usize = 200;
+const MAX_NOTABLE_LINES: usize = 20;
+const NOTABLE_LINE_CHARS: usize = 200;
+const NOTABLE_MARKERS: &[&str] = &[
+    "error",
+    "fail",
+    "panic",
+    "warning",
+    "exception",
+    "traceback",
+    "assert",
+];
 
 #[derive(Debug, Clone, Serialize)]
 pub struct BudgetedText {
@@ -17,33 +40,73 @@ pub struct BudgetedText {
     pub original_chars: usize,
     pub truncated: bool,
     pub full_path: Option<String>,
+    /// File stem of `full_path`, which names the saved output in summaries.
+    pub output_id: Option<String>,
+}
+
+/// `[tool_output]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputBudget {
+    /// Characters a tool result may take in the conversation.
+    pub limit: usize,
+    /// Characters of the summary that replaces a larger result.
+    pub preview: usize,
+}
+
+impl Default for OutputBudget {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT_CHARS,
+            preview: DEFAULT_PREVIEW_CHARS,
+        }
+    }
 }
 
+impl OutputBudget {
+    /// The configured budget; `OSMOGREP_TOOL_RESULT_LIMIT` and
+    /// `OSMOGREP_TOOL_PREVIEW_LIMIT` override it.
+    pub fn load() -> Self {
+        let config = crate::agent::tool_output_config();
+        Self {
+            limit: env_usize("OSMOGREP_TOOL_RESULT_LIMIT", config.limit),
+            preview: env_usize("OSMOGREP_TOOL_PREVIEW_LIMIT", config.preview),
+        }
+    }
+}
+
+/// Budgets one stream of tool output, saving it under a fresh id.
 pub fn budget_text(repo_root: &Path, label: &str, text: &str) -> BudgetedText {
-    let threshold = env_usize("OSMOGREP_TOOL_RESULT_LIMIT", DEFAULT_THRESHOLD_CHARS);
-    let preview_chars = env_usize("OSMOGREP_TOOL_PREVIEW_LIMIT", DEFAULT_PREVIEW_CHARS);
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+    let id = format!("{stamp}-{}-{}", Uuid::new_v4().simple(), safe_label(label));
+    budget_text_with(repo_root, &id, text, OutputBudget::load())
+}
+
+fn budget_text_with(repo_root: &Path, id: &str, text: &str, budget: OutputBudget) -> BudgetedText {
     let original_chars = text.chars().count();
 
-    if original_chars <= threshold {
+    if original_chars <= budget.limit {
         return BudgetedText {
             text: text.to_string(),
             original_chars,
             truncated: false,
             full_path: None,
+            output_id: None,
         };
     }
 
-    let preview = preview_text(text, preview_chars);
-    let path = persist_result(repo_root, label, text).ok();
-    let text = if let Some(path) = &path {
-        format!(
-            "<persisted-output>\nTool output was too large ({original_chars} characters).\nFull output saved to: {}\n\nPreview:\n{preview}\n</persisted-output>",
+    let summary = summarize(text, budget.preview);
+    let total_lines = text.lines().count();
+    let path = persist_result(repo_root, id, text).ok();
+    let output_id = path.as_ref().map(|_| id.to_string());
+    let text = match &path {
+        Some(path) => format!(
+            "<persisted-output id=\"{id}\">\nTool output was too large ({original_chars} characters, {total_lines} lines).\nFull output saved to: {}\nRead a range with read_tool_output using this id, an offset (0-based line) and a limit.\n\n{summary}\n</persisted-output>",
             path.display()
-        )
-    } else {
-        format!(
-            "{preview}\n\n[Truncated: tool output was {original_chars} characters and could not be persisted.]"
-        )
+        ),
+        None => format!(
+            "{summary}\n\n[Truncated: tool output was {original_chars} characters and could not be persisted.]"
+        ),
     };
 
     BudgetedText {
@@ -51,7 +114,123 @@ pub fn budget_text(repo_root: &Path, label: &str, text: &str) -> BudgetedText {
         original_chars,
         truncated: true,
         full_path: path.map(|p| p.display().to_string()),
+        output_id,
+    }
+}
+
+/// Budgets a whole tool result before it goes into the conversation; the
+/// saved output is named after `call_id`. The largest string fields are
+/// summarized first; if the result is still over the limit (e.g. long match
+/// lists), the whole result is saved as JSON and only its scalar fields are
+/// kept inline.
+pub fn budget_result(
+    repo_root: &Path,
+    call_id: &str,
+    result: Value,
+    budget: OutputBudget,
+) -> Value {
+    if result.to_string().chars().count() <= budget.limit {
+        return result;
+    }
+    let id = safe_label(call_id);
+    let Value::Object(mut map) = result else {
+        let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+        return Value::String(budget_text_with(repo_root, &id, &text, budget).text);
+    };
+
+    let mut fields: Vec<(String, usize)> = map
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.chars().count())))
+        .filter(|(_, chars)| *chars > budget.preview)
+        .collect();
+    fields.sort_by_key(|(_, chars)| std::cmp::Reverse(*chars));
+    let field_budget = OutputBudget {
+        limit: budget.preview,
+        ..budget
+    };
+    for (key, _) in fields {
+        let Some(Value::String(text)) = map.get(&key) else {
+            continue;
+        };
+        let field_id = format!("{id}-{}", safe_label(&key));
+        let budgeted = budget_text_with(repo_root, &field_id, text, field_budget);
+        map.insert(key, Value::String(budgeted.text));
+        if Value::Object(map.clone()).to_string().chars().count() <= budget.limit {
+            return Value::Object(map);
+        }
+    }
+
+    let full = Value::Object(map);
+    let text = serde_json::to_string_pretty(&full).unwrap_or_default();
+    let mut kept: Map<String, Value> = full
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, value)| match value {
+            Value::String(s) => s.chars().count() <= NOTABLE_LINE_CHARS,
+            Value::Array(_) | Value::Object(_) => false,
+            _ => true,
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let budgeted = budget_text_with(repo_root, &id, &text, OutputBudget { limit: 0, ..budget });
+    kept.insert("persisted_output".to_string(), Value::String(budgeted.text));
+    Value::Object(kept)
+}
+
+/// Head and tail lines within `max_chars`, and the error-looking lines
+/// between them.
+fn summarize(text: &str, max_chars: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let half = (max_chars / 2).max(1);
+
+    let mut head_end = 0;
+    let mut used = 0;
+    while head_end < lines.len() && (head_end == 0 || used + lines[head_end].len() < half) {
+        used += lines[head_end].len() + 1;
+        head_end += 1;
+    }
+    let mut tail_start = lines.len();
+    used = 0;
+    while tail_start > head_end && used + lines[tail_start - 1].len() < half {
+        used += lines[tail_start - 1].len() + 1;
+        tail_start -= 1;
     }
+
+    let head = preview_text(&lines[..head_end].join("\n"), half);
+    if tail_start == head_end {
+        return head;
+    }
+    let notable: Vec<String> = (head_end..tail_start)
+        .filter(|&i| {
+            let lower = lines[i].to_ascii_lowercase();
+            NOTABLE_MARKERS.iter().any(|m| lower.contains(m))
+        })
+        .take(MAX_NOTABLE_LINES)
+        .map(|i| format!("  [offset {i}] {}", clip_line(lines[i].trim_end())))
+        .collect();
+
+    let mut out = format!(
+        "Head:\n{head}\n\n[... {} lines omitted, offsets {head_end}-{} ...]\n",
+        tail_start - head_end,
+        tail_start - 1
+    );
+    if !notable.is_empty() {
+        out.push_str(&format!(
+            "\nNotable omitted lines:\n{}\n",
+            notable.join("\n")
+        ));
+    }
+    out.push_str(&format!("\nTail:\n{}", lines[tail_start..].join("\n")));
+    out
+}
+
+fn clip_line(line: &str) -> String {
+    if line.chars().count() <= NOTABLE_LINE_CHARS {
+        return line.to_string();
+    }
+    let clipped: String = line.chars().take(NOTABLE_LINE_CHARS).collect();
+    format!("{clipped}...")
 }
 
 fn env_usize(key: &str, default: usize) -> usize {
@@ -62,14 +241,13 @@ fn env_usize(key: &str, default: usize) -> usize {
         .unwrap_or(default)
 }
 
-fn persist_result(repo_root: &Path, label: &str, text: &str) -> Result<PathBuf, String> {
-    let dir = repo_root.join(".context").join("osmogrep-results");
+fn persist_result(repo_root: &Path, id: &str, text: &str) -> Result<PathBuf, String> {
+    let dir = repo_root.join(OUTPUT_DIR);
     fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
-    let safe_label = safe_label(label);
-    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
-    let path = dir.join(format!("{}-{}-{}.txt", stamp, Uuid::new_v4(), safe_label));
+    let path = dir.join(format!("{id}.txt"));
     let mut file = OpenOptions::new()
-        .create_new(true)
+        .create(true)
+        .truncate(true)
         .write(true)
         .open(&path)
         .map_err(|e| e.to_string())?;
@@ -77,6 +255,35 @@ fn persist_result(repo_root: &Path, label: &str, text: &str) -> Result<PathBuf,
     Ok(path)
 }
 
+/// Lines `offset..offset + limit` of a saved output, for `read_tool_output`.
+pub fn read_output(
+    repo_root: &Path,
+    id: &str,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<Value, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if id.is_empty() || id.starts_with('.') || !id.chars().all(valid) {
+        return Err(format!("invalid output id: {id}"));
+    }
+    let path = repo_root.join(OUTPUT_DIR).join(format!("{id}.txt"));
+    let text = fs::read_to_string(&path).map_err(|e| format!("output {id}: {e}"))?;
+    let lines: Vec<&str> = text.lines().collect();
+    let end = offset
+        .saturating_add(limit.unwrap_or(DEFAULT_READ_LINES))
+        .min(lines.len());
+    let start = offset.min(end);
+    let next_offset = (end < lines.len()).then_some(end);
+    Ok(json!({
+        "id": id,
+        "text": preview_text(&lines[start..end].join("\n"), DEFAULT_LIMIT_CHARS),
+        "offset": start,
+        "lines": end - start,
+        "total_lines": lines.len(),
+        "next_offset": next_offset,
+    }))
+}
+
 fn safe_label(label: &str) -> String {
     let safe = label
         .chars()
@@ -120,4 +327,43 @@ mod tests {
         assert!(!out.truncated);
         assert_eq!(out.text, "small");
     }
+
+    #[test]
+    fn summarizes_large_output_and_saves_the_full_text() {
+        let root = std::env::temp_dir().join(format!("osmogrep-budget-{}", Uuid::new_v4()));
+        let mut log: Vec<String> = (0..200).map(|i| format!("test case_{i} ... ok")).collect();
+        log[120] = "test case_120 ... FAILED".to_string();
+        log.push("test result: FAILED. 199 passed; 1 failed".to_string());
+        let output = log.join("\n");
+        let budget = OutputBudget {
+            limit: 1_000,
+            preview: 400,
+        };
+
+        let result = budget_result(
+            &root,
+            "call_42",
+            serde_json::json!({ "output": output, "exit_code": 101 }),
+            budget,
+        );
+        assert_eq!(result["exit_code"], 101);
+        let text = result["output"].as_str().unwrap();
+        assert!(text.starts_with("<persisted-output id=\"call_42-output\">"));
+        assert!(text.contains("test case_0 ... ok"));
+        assert!(text.contains("[offset 120] test case_120 ... FAILED"));
+        assert!(text.ends_with("199 passed; 1 failed\n</persisted-output>"));
+        assert!(!text.contains("case_100 "));
+
+        let saved = root.join(OUTPUT_DIR).join("call_42-output.txt");
+        assert_eq!(fs::read_to_string(saved).unwrap(), output);
+
+        let page = read_output(&root, "call_42-output", 119, Some(2)).unwrap();
+        assert_eq!(
+            page["text"],
+            "test case_119 ... ok\ntest case_120 ... FAILED"
+        );
+        assert_eq!(page["next_offset"], 121);
+        assert!(read_output(&root, "../secrets", 0, None).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
 }
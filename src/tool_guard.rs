@@ -130,12 +130,16 @@ fn is_idempotent_tool(tool_name: &str) -> bool {
             | "list_dir"
             | "git_diff"
             | "git_log"
+            | "git_blame"
+            | "file_history"
             | "regex_search"
             | "web_fetch"
             | "web_search"
             | "dynamic_workflow"
             | "find_definition"
             | "find_references"
+            | "impact_analysis"
+            | "semantic_search"
             | "diagnostics"
     )
 }
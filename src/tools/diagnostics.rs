@@ -75,7 +75,7 @@ impl Tool for Diagnostics {
     }
 }
 
-fn default_command() -> String {
+pub(super) fn default_command() -> String {
     if Path::new("Cargo.toml").exists() {
         "cargo check --message-format short".to_string()
     } else if Path::new("pyproject.toml").exists() {
@@ -89,7 +89,7 @@ fn default_command() -> String {
     }
 }
 
-fn parse_issues(output: &str) -> Vec<Value> {
+pub(super) fn parse_issues(output: &str) -> Vec<Value> {
     let mut items = Vec::new();
     let re = Regex::new(r"^([^:\n]+):(\d+):(\d+):\s*(.*)$").unwrap();
 
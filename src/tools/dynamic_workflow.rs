@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use super::{Tool, ToolResult, ToolSafety};
+use crate::http_policy;
 
 const DEFAULT_QUERY_COUNT: usize = 4;
 const DEFAULT_SOURCES_PER_QUERY: usize = 3;
@@ -705,15 +706,11 @@ struct SearchResult {
 
 fn search_web(query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
     let url = format!("https://duckduckgo.com/html/?q={}", encode_query(query));
-    let client = reqwest::blocking::Client::builder()
+    let client = crate::net::client_builder()?
         .timeout(Duration::from_secs(20))
         .build()
         .map_err(|e| e.to_string())?;
-    let html = client
-        .get(url)
-        .header("User-Agent", "osmogrep/0.3")
-        .send()
-        .map_err(|e| e.to_string())?
+    let html = http_policy::send(client.get(url).header("User-Agent", "osmogrep/0.3"))?
         .text()
         .map_err(|e| e.to_string())?;
 
@@ -759,11 +756,11 @@ fn fetch_source(result: SearchResult) -> Value {
 }
 
 fn fetch_text(url: &str, max_chars: usize) -> Result<(u16, String, String), String> {
-    let client = reqwest::blocking::Client::builder()
+    let client = crate::net::client_builder()?
         .timeout(Duration::from_secs(25))
         .build()
         .map_err(|e| e.to_string())?;
-    let resp = client.get(url).send().map_err(|e| e.to_string())?;
+    let resp = http_policy::send(client.get(url))?;
     let status = resp.status().as_u16();
     let headers = resp.headers().clone();
     let body = resp.text().map_err(|e| e.to_string())?;
@@ -0,0 +1,163 @@
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+use super::{Tool, ToolResult, ToolSafety};
+
+pub struct FileHistory;
+
+impl Tool for FileHistory {
+    fn name(&self) -> &'static str {
+        "file_history"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "file_history",
+            "description": "List recent commits that touched a file, or only a line range of it, with full messages and authors",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer" },
+                    "end_line": { "type": "integer" },
+                    "limit": { "type": "integer" }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Safe
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        self.call_cancellable(args, &|| false)
+    }
+
+    fn call_cancellable(&self, args: Value, is_cancelled: &dyn Fn() -> bool) -> ToolResult {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or("missing path")?;
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .unwrap_or(10)
+            .min(100);
+        let repo_root = args.get("_repo_root").and_then(Value::as_str);
+        let start = args.get("start_line").and_then(Value::as_u64);
+        let end = args.get("end_line").and_then(Value::as_u64);
+
+        let mut cmd = Command::new("git");
+        if let Some(repo_root) = repo_root {
+            cmd.current_dir(repo_root);
+        }
+        cmd.arg("log")
+            .arg(format!("-n{}", limit))
+            .arg("--pretty=format:%x1e%h%x1f%an%x1f%ad%x1f%s%x1f%b")
+            .arg("--date=short");
+        match (start, end) {
+            (None, None) => {
+                cmd.arg("--follow").arg("--").arg(path);
+            }
+            (start, end) => {
+                // `-L` follows the region through edits, not just the file.
+                let start = start.unwrap_or(1).max(1);
+                let end = end.unwrap_or(start).max(start);
+                cmd.arg("--no-patch").arg(format!("-L{start},{end}:{path}"));
+            }
+        }
+
+        let timeout = crate::process_runner::timeout_from_env("OSMOGREP_GIT_TIMEOUT_SECS", 120);
+        let out = crate::process_runner::run_command_cancellable(cmd, timeout, is_cancelled)?;
+
+        Ok(json!({
+            "exit_code": out.exit_code,
+            "timed_out": out.timed_out,
+            "cancelled": out.cancelled,
+            "path": path,
+            "commits": parse_records(&String::from_utf8_lossy(&out.stdout)),
+            "stderr": String::from_utf8_lossy(&out.stderr)
+        }))
+    }
+}
+
+fn parse_records(text: &str) -> Vec<Value> {
+    text.split('\x1e')
+        .filter(|record| !record.trim().is_empty())
+        .map(|record| {
+            let mut parts = record.splitn(5, '\x1f');
+            let mut next = || parts.next().unwrap_or("").trim().to_string();
+            json!({
+                "sha": next(),
+                "author": next(),
+                "date": next(),
+                "subject": next(),
+                "body": next(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+    use uuid::Uuid;
+
+    fn git(root: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .current_dir(root)
+            .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {args:?}");
+    }
+
+    #[test]
+    fn history_of_a_region_skips_commits_that_touched_other_lines() {
+        let root = std::env::temp_dir().join(format!("osmogrep-history-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        git(&root, &["init", "-q"]);
+        fs::write(root.join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-qm", "Add a and b"]);
+        fs::write(root.join("lib.rs"), "fn a() {}\nfn b() { todo!() }\n").unwrap();
+        git(
+            &root,
+            &[
+                "commit",
+                "-qam",
+                "Stub b\n\nb panics until the parser lands.",
+            ],
+        );
+
+        let call = |extra: Value| {
+            let mut args = json!({ "path": "lib.rs", "_repo_root": root.display().to_string() });
+            args.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            FileHistory.call(args).unwrap()
+        };
+        let all = call(json!({}));
+        let commits = all["commits"].as_array().unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0]["subject"], "Stub b");
+        assert_eq!(commits[0]["body"], "b panics until the parser lands.");
+        assert_eq!(commits[0]["author"], "Ada");
+
+        let first_line = call(json!({ "start_line": 1, "end_line": 1 }));
+        let commits = first_line["commits"].as_array().unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0]["subject"], "Add a and b");
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
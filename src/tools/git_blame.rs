@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+use super::{Tool, ToolResult, ToolSafety};
+
+const MAX_LINES: u64 = 400;
+
+pub struct GitBlame;
+
+impl Tool for GitBlame {
+    fn name(&self) -> &'static str {
+        "git_blame"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "git_blame",
+            "description": "Show which commit last changed each line of a file region, with author, date and commit subject",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer" },
+                    "end_line": { "type": "integer" }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Safe
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        self.call_cancellable(args, &|| false)
+    }
+
+    fn call_cancellable(&self, args: Value, is_cancelled: &dyn Fn() -> bool) -> ToolResult {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or("missing path")?;
+        let repo_root = args.get("_repo_root").and_then(Value::as_str);
+        // `-L` past the end of the file is an error, so clamp to its length.
+        let line_count = fs::read_to_string(Path::new(repo_root.unwrap_or(".")).join(path))
+            .map(|text| text.lines().count().max(1) as u64)
+            .unwrap_or(u64::MAX);
+        let start = args
+            .get("start_line")
+            .and_then(Value::as_u64)
+            .unwrap_or(1)
+            .clamp(1, line_count);
+        let last = start.saturating_add(MAX_LINES - 1);
+        let end = args
+            .get("end_line")
+            .and_then(Value::as_u64)
+            .unwrap_or(last)
+            .clamp(start, last)
+            .min(line_count);
+
+        let mut cmd = Command::new("git");
+        if let Some(repo_root) = repo_root {
+            cmd.current_dir(repo_root);
+        }
+        cmd.arg("blame")
+            .arg("--line-porcelain")
+            .arg(format!("-L{start},{end}"))
+            .arg("--")
+            .arg(path);
+
+        let timeout = crate::process_runner::timeout_from_env("OSMOGREP_GIT_TIMEOUT_SECS", 120);
+        let out = crate::process_runner::run_command_cancellable(cmd, timeout, is_cancelled)?;
+
+        let (lines, commits) = parse_line_porcelain(&String::from_utf8_lossy(&out.stdout));
+        Ok(json!({
+            "exit_code": out.exit_code,
+            "timed_out": out.timed_out,
+            "cancelled": out.cancelled,
+            "path": path,
+            "lines": lines,
+            "commits": commits,
+            "stderr": String::from_utf8_lossy(&out.stderr)
+        }))
+    }
+}
+
+/// One entry per blamed line, and the distinct commits in order of first
+/// appearance.
+fn parse_line_porcelain(text: &str) -> (Vec<Value>, Vec<Value>) {
+    let mut lines = Vec::new();
+    let mut commits: Vec<Value> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut header: Option<(String, u64)> = None;
+    let mut fields: HashMap<&str, String> = HashMap::new();
+
+    for line in text.lines() {
+        if let Some(content) = line.strip_prefix('\t') {
+            let Some((sha, line_no)) = header.take() else {
+                continue;
+            };
+            let short = sha.get(..8).unwrap_or(&sha).to_string();
+            let uncommitted = sha.bytes().all(|b| b == b'0');
+            if seen.insert(sha.clone()) {
+                let date = fields
+                    .get("author-time")
+                    .and_then(|t| t.parse::<i64>().ok())
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|at| at.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default();
+                commits.push(json!({
+                    "sha": short,
+                    "author": fields.get("author").cloned().unwrap_or_default(),
+                    "date": date,
+                    "subject": if uncommitted {
+                        "Not committed yet".to_string()
+                    } else {
+                        fields.get("summary").cloned().unwrap_or_default()
+                    },
+                }));
+            }
+            lines.push(json!({ "line": line_no, "sha": short, "text": content }));
+            fields.clear();
+            continue;
+        }
+        if header.is_none() {
+            let mut parts = line.split(' ');
+            let sha = parts.next().unwrap_or_default();
+            let line_no = parts.nth(1).and_then(|n| n.parse::<u64>().ok());
+            if let (true, Some(line_no)) = (sha.len() >= 40, line_no) {
+                header = Some((sha.to_string(), line_no));
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(' ') {
+            if matches!(key, "author" | "author-time" | "summary") {
+                fields.insert(key, value.to_string());
+            }
+        }
+    }
+    (lines, commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn porcelain_lines_share_one_entry_per_commit() {
+        let sha = "a".repeat(40);
+        let text = format!(
+            "{sha} 1 1 2\nauthor Ada\nauthor-time 1760000000\nsummary Add parser\nfilename src/p.rs\n\tfn parse() {{\n\
+             {sha} 2 2\nauthor Ada\nauthor-time 1760000000\nsummary Add parser\nfilename src/p.rs\n\t}}\n\
+             {zero} 3 3 1\nauthor Not Committed Yet\nauthor-time 1760100000\nsummary Version of src/p.rs from src/p.rs\nfilename src/p.rs\n\t// todo\n",
+            zero = "0".repeat(40)
+        );
+        let (lines, commits) = parse_line_porcelain(&text);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            json!({ "line": 2, "sha": "aaaaaaaa", "text": "}" })
+        );
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0]["subject"], "Add parser");
+        assert_eq!(commits[0]["date"], "2025-10-09");
+        assert_eq!(commits[1]["subject"], "Not committed yet");
+    }
+}
@@ -1,8 +1,11 @@
+use std::fs;
+use std::path::Path;
 use std::process::Command;
 
 use serde_json::{json, Value};
 
 use super::{Tool, ToolResult, ToolSafety};
+use crate::binary_assets::summarize_git_diff;
 
 pub struct GitDiff;
 
@@ -51,16 +54,52 @@ impl Tool for GitDiff {
         let timeout = crate::process_runner::timeout_from_env("OSMOGREP_GIT_TIMEOUT_SECS", 120);
         let out = crate::process_runner::run_command_cancellable(cmd, timeout, is_cancelled)?;
 
+        let root = Path::new(repo_root.unwrap_or("."));
+        let (diff, assets) = summarize_git_diff(&String::from_utf8_lossy(&out.stdout), |asset| {
+            if !asset.added {
+                asset.old_size = index_blob_size(root, &asset.path);
+            }
+            if !asset.deleted {
+                asset.new_size = fs::metadata(root.join(&asset.path)).ok().map(|m| m.len());
+            }
+        });
+        let binary_assets: Vec<Value> = assets
+            .iter()
+            .map(|asset| {
+                json!({
+                    "path": asset.path,
+                    "lfs": asset.lfs,
+                    "old_size": asset.old_size,
+                    "new_size": asset.new_size,
+                    "summary": asset.summary(),
+                })
+            })
+            .collect();
+
         Ok(json!({
             "exit_code": out.exit_code,
             "timed_out": out.timed_out,
             "cancelled": out.cancelled,
-            "diff": truncate(&String::from_utf8_lossy(&out.stdout), 12000),
+            "diff": truncate(&diff, 12000),
+            "binary_assets": binary_assets,
             "stderr": truncate(&String::from_utf8_lossy(&out.stderr), 2000)
         }))
     }
 }
 
+/// Size of the staged blob, which is what `git diff` compares against.
+fn index_blob_size(root: &Path, path: &str) -> Option<u64> {
+    let out = Command::new("git")
+        .current_dir(root)
+        .args(["cat-file", "-s", &format!(":{path}")])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
 fn truncate(s: &str, n: usize) -> String {
     if s.chars().count() <= n {
         s.to_string()
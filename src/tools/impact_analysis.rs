@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use super::{Tool, ToolResult, ToolSafety};
+use crate::context::{impact, indexer};
+
+pub struct ImpactAnalysis;
+
+impl Tool for ImpactAnalysis {
+    fn name(&self) -> &'static str {
+        "impact_analysis"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "impact_analysis",
+            "description": "List every indexed function that calls a symbol directly or transitively, nearest first, to see what a change to it can affect",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "symbol": { "type": "string" },
+                    "max_depth": { "type": "integer" }
+                },
+                "required": ["symbol"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Safe
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        let symbol = args
+            .get("symbol")
+            .and_then(Value::as_str)
+            .ok_or("missing symbol")?;
+        let max_depth = args
+            .get("max_depth")
+            .and_then(Value::as_u64)
+            .map(|n| n as usize)
+            .unwrap_or(impact::DEFAULT_DEPTH)
+            .clamp(1, 20);
+        let root = Path::new(
+            args.get("_repo_root")
+                .and_then(Value::as_str)
+                .unwrap_or("."),
+        );
+
+        let ctx = indexer::load_or_build(root);
+        let impact = impact::analyze(&ctx, root, symbol, max_depth)?;
+        Ok(json!({
+            "symbol": impact.symbol,
+            "defined_at": impact.defined_at,
+            "count": impact.affected.len(),
+            "affected": impact.affected,
+            "truncated": impact.truncated,
+        }))
+    }
+}
@@ -3,24 +3,35 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
 
+use crate::permissions::{Decision, PermissionPolicy};
+
 mod diagnostics;
 mod dynamic_workflow;
 mod edit;
+mod file_history;
 mod find_definition;
 mod find_references;
+mod git_blame;
 mod git_commit;
 mod git_diff;
 mod git_log;
 mod glob;
+mod impact_analysis;
 mod list_dir;
 mod mcp_call;
+mod multi_edit;
 mod notebook_edit;
 mod patch;
 mod plan;
+mod profile;
 mod read;
+mod read_tool_output;
 mod regex_search;
+mod rename_symbol;
 mod search;
+mod semantic_search;
 mod shell;
+mod spawn_subagent;
 mod test;
 mod web_fetch;
 mod web_search;
@@ -30,27 +41,45 @@ mod write;
 pub use diagnostics::Diagnostics;
 pub use dynamic_workflow::DynamicWorkflow;
 pub use edit::Edit;
+pub use file_history::FileHistory;
 pub use find_definition::FindDefinition;
 pub use find_references::FindReferences;
+pub use git_blame::GitBlame;
 pub use git_commit::GitCommit;
 pub use git_diff::GitDiff;
 pub use git_log::GitLog;
 pub use glob::Glob;
+pub use impact_analysis::ImpactAnalysis;
 pub use list_dir::ListDir;
 pub use mcp_call::McpCall;
+pub use multi_edit::MultiEdit;
 pub use notebook_edit::NotebookEdit;
 pub use patch::Patch;
 pub use plan::Plan;
+pub use profile::ToolProfile;
 pub use read::Read;
+pub use read_tool_output::ReadToolOutput;
 pub use regex_search::RegexSearch;
+pub use rename_symbol::RenameSymbol;
 pub use search::Search;
+pub use semantic_search::SemanticSearch;
 pub use shell::Shell;
+pub use spawn_subagent::{
+    SpawnSubagent, SUBAGENT_MAX_DEPTH, SUBAGENT_MAX_TOKENS, SUBAGENT_TOKEN_BUDGET,
+};
 pub use test::Test;
 pub use web_fetch::WebFetch;
-pub use web_search::WebSearch;
+pub use web_search::{SearchProvider, WebSearch, WebSearchConfig};
 pub use worktree_swarm::WorktreeSwarm;
 pub use write::Write;
 
+pub use crate::tool_budget::OutputBudget;
+
+// Shared with the agent loop; not part of the embedding API.
+pub(crate) use multi_edit::multi_edit_targets;
+pub(crate) use plan::seed_plan;
+pub(crate) use rename_symbol::{rename_preview, rename_targets};
+
 pub type ToolResult = Result<Value, String>;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -71,7 +100,90 @@ pub trait Tool: Send + Sync {
 
 pub struct ToolRegistry {
     tools: HashMap<&'static str, Box<dyn Tool>>,
+    safety_overrides: HashMap<&'static str, ToolSafety>,
+    output_budget: OutputBudget,
+    permissions: PermissionPolicy,
+    repo_root: PathBuf,
+}
+
+/// Assembles a [`ToolRegistry`] for embedders: start from the built-in tools
+/// (or none), add custom tools, drop unwanted ones and override safety levels.
+pub struct ToolRegistryBuilder {
     repo_root: PathBuf,
+    builtins: bool,
+    tools: Vec<Box<dyn Tool>>,
+    removed: Vec<String>,
+    safety_overrides: HashMap<&'static str, ToolSafety>,
+    output_budget: Option<OutputBudget>,
+    permissions: PermissionPolicy,
+}
+
+impl ToolRegistryBuilder {
+    /// Skips the built-in tool set; only tools added with [`Self::tool`] are
+    /// registered.
+    pub fn without_builtins(mut self) -> Self {
+        self.builtins = false;
+        self
+    }
+
+    /// Adds a tool. A later tool with the same name replaces an earlier one,
+    /// including built-ins.
+    pub fn tool(mut self, tool: Box<dyn Tool>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Removes a tool by name, whether built-in or added earlier.
+    pub fn remove(mut self, name: &str) -> Self {
+        self.removed.push(name.to_string());
+        self
+    }
+
+    /// Overrides the safety level reported for `name`. Downgrading a tool to
+    /// `Safe` skips the permission prompt but never makes it parallel-safe.
+    pub fn safety(mut self, name: &'static str, safety: ToolSafety) -> Self {
+        self.safety_overrides.insert(name, safety);
+        self
+    }
+
+    /// Sets how much of a tool result may enter the conversation; larger
+    /// results are summarized and saved for `read_tool_output`. Defaults to
+    /// the `[tool_output]` config.
+    pub fn output_budget(mut self, budget: OutputBudget) -> Self {
+        self.output_budget = Some(budget);
+        self
+    }
+
+    /// Applies a per-repo policy: denied tools are hidden from the model and
+    /// refused when called; see [`ToolRegistry::permission_decision`].
+    pub(crate) fn permissions(mut self, policy: PermissionPolicy) -> Self {
+        self.permissions = policy;
+        self
+    }
+
+    pub fn build(self) -> ToolRegistry {
+        let mut registry = ToolRegistry {
+            tools: HashMap::new(),
+            safety_overrides: self.safety_overrides,
+            output_budget: self.output_budget.unwrap_or_else(OutputBudget::load),
+            permissions: self.permissions,
+            repo_root: self.repo_root,
+        };
+
+        if self.builtins {
+            for tool in builtin_tools() {
+                registry.register(tool);
+            }
+        }
+        for tool in self.tools {
+            registry.register(tool);
+        }
+        for name in &self.removed {
+            registry.tools.remove(name.as_str());
+        }
+
+        registry
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -82,7 +194,12 @@ pub struct ToolScope {
     include_git_commit: bool,
     include_worktree_swarm: bool,
     include_dynamic_workflow: bool,
+    include_subagents: bool,
     read_only: bool,
+    /// Set for `spawn_subagent` children.
+    subagent: bool,
+    /// A sub-agent's tool set; other tools are neither offered nor run.
+    only: Option<Vec<String>>,
 }
 
 impl ToolScope {
@@ -151,7 +268,10 @@ impl ToolScope {
                         "current docs",
                     ],
                 ),
+            include_subagents: true,
             read_only: false,
+            subagent: false,
+            only: None,
         }
     }
 
@@ -160,8 +280,42 @@ impl ToolScope {
         self
     }
 
-    fn allows(&self, name: &str) -> bool {
-        if self.read_only && is_mutating_tool(name) {
+    /// The scope of a `spawn_subagent` child: `tools` when given (never
+    /// more than this scope allows), otherwise the read-only tools. The
+    /// plan stays with the main agent, and a child at the depth limit
+    /// cannot spawn its own.
+    pub fn for_subagent(mut self, tools: Option<Vec<String>>, can_spawn: bool) -> Self {
+        match tools {
+            Some(names) => {
+                self.only = Some(match self.only.take() {
+                    Some(allowed) => names.into_iter().filter(|n| allowed.contains(n)).collect(),
+                    None => names,
+                });
+            }
+            None => self.read_only = true,
+        }
+        self.include_subagents &= can_spawn;
+        self.subagent = true;
+        self
+    }
+
+    /// Whether a call to `name` falls outside a sub-agent's tool set.
+    pub fn excludes(&self, name: &str) -> bool {
+        match name {
+            "spawn_subagent" if !self.include_subagents => true,
+            "update_plan" if self.subagent => true,
+            _ => self
+                .only
+                .as_ref()
+                .is_some_and(|names| !names.iter().any(|n| n == name)),
+        }
+    }
+
+    fn allows(&self, name: &str, safety: Option<ToolSafety>) -> bool {
+        if self.read_only && (is_mutating_tool(name) || safety == Some(ToolSafety::Dangerous)) {
+            return false;
+        }
+        if self.excludes(name) {
             return false;
         }
         match name {
@@ -182,9 +336,11 @@ fn is_mutating_tool(name: &str) -> bool {
         "run_shell"
             | "write_file"
             | "edit_file"
+            | "multi_edit"
             | "run_tests"
             | "diagnostics"
             | "patch"
+            | "rename_symbol"
             | "mcp_call"
             | "notebook_edit"
             | "git_commit"
@@ -205,39 +361,30 @@ fn has_ultracode_suffix(prompt: &str) -> bool {
 
 impl ToolRegistry {
     pub fn with_root(repo_root: PathBuf) -> Self {
-        let mut tools: HashMap<&'static str, Box<dyn Tool>> = HashMap::new();
-
-        let list: Vec<Box<dyn Tool>> = vec![
-            Box::new(Shell),
-            Box::new(Read),
-            Box::new(Write),
-            Box::new(Edit),
-            Box::new(Search),
-            Box::new(Glob),
-            Box::new(Test),
-            Box::new(ListDir),
-            Box::new(GitDiff),
-            Box::new(GitLog),
-            Box::new(RegexSearch),
-            Box::new(WebFetch),
-            Box::new(McpCall),
-            Box::new(FindDefinition),
-            Box::new(FindReferences),
-            Box::new(GitCommit),
-            Box::new(Patch),
-            Box::new(Plan),
-            Box::new(NotebookEdit),
-            Box::new(WebSearch),
-            Box::new(Diagnostics),
-            Box::new(WorktreeSwarm),
-            Box::new(DynamicWorkflow),
-        ];
-
-        for tool in list {
-            tools.insert(tool.name(), tool);
+        Self::builder(repo_root).build()
+    }
+
+    pub fn builder(repo_root: PathBuf) -> ToolRegistryBuilder {
+        ToolRegistryBuilder {
+            repo_root,
+            builtins: true,
+            tools: Vec::new(),
+            removed: Vec::new(),
+            safety_overrides: HashMap::new(),
+            output_budget: None,
+            permissions: PermissionPolicy::default(),
         }
+    }
 
-        Self { tools, repo_root }
+    /// Registers `tool`, returning the tool it replaced if the name was taken.
+    pub fn register(&mut self, tool: Box<dyn Tool>) -> Option<Box<dyn Tool>> {
+        self.tools.insert(tool.name(), tool)
+    }
+
+    pub fn tool_names(&self) -> Vec<&'static str> {
+        let mut names = self.tools.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
     }
 
     pub fn call_cancellable(
@@ -250,13 +397,28 @@ impl ToolRegistry {
             .tools
             .get(name)
             .ok_or_else(|| format!("unknown tool: {}", name))?;
+        self.permissions.check_tool(name)?;
 
         self.call_in_repo_root(tool.as_ref(), args, is_cancelled)
     }
 
+    /// What the repo's permission policy says about a call that writes
+    /// `targets`; consulted before the permission profile prompts.
+    pub(crate) fn permission_decision(
+        &self,
+        name: &str,
+        args: &Value,
+        targets: &[String],
+    ) -> Decision {
+        self.permissions.decide(name, args, targets)
+    }
+
     pub fn parallel_safe(&self, name: &str) -> bool {
+        // Parallel calls skip the repo-root cwd switch, so the tool itself
+        // must be read-only regardless of any safety override.
         matches!(self.safety(name), Some(ToolSafety::Safe))
-            && !matches!(name, "update_plan" | "dynamic_workflow")
+            && self.tools.get(name).map(|t| t.safety()) == Some(ToolSafety::Safe)
+            && !matches!(name, "update_plan" | "dynamic_workflow" | "spawn_subagent")
     }
 
     pub fn call_parallel_safe_cancellable(
@@ -268,6 +430,7 @@ impl ToolRegistry {
         if !self.parallel_safe(name) {
             return Err(format!("tool is not parallel safe: {}", name));
         }
+        self.permissions.check_tool(name)?;
 
         let tool = self
             .tools
@@ -276,14 +439,32 @@ impl ToolRegistry {
         tool.call_cancellable(self.resolve_parallel_args(name, args), is_cancelled)
     }
 
+    /// `result` as it should enter the conversation: within the output
+    /// budget, with anything larger saved as `.context/tool-output/<call_id>.txt`.
+    pub fn budget_result(&self, call_id: &Value, result: Value) -> Value {
+        let call_id = match call_id {
+            Value::String(id) => id.clone(),
+            other => other.to_string(),
+        };
+        crate::tool_budget::budget_result(&self.repo_root, &call_id, result, self.output_budget)
+    }
+
     pub fn safety(&self, name: &str) -> Option<ToolSafety> {
-        self.tools.get(name).map(|t| t.safety())
+        let tool = self.tools.get(name)?;
+        Some(
+            self.safety_overrides
+                .get(name)
+                .copied()
+                .unwrap_or_else(|| tool.safety()),
+        )
     }
 
     pub fn scoped_schema(&self, scope: &ToolScope) -> Vec<Value> {
         self.tools
             .iter()
-            .filter_map(|(name, tool)| scope.allows(name).then(|| tool.schema()))
+            .filter(|(name, _)| scope.allows(name, self.safety(name)))
+            .filter(|(name, _)| !self.permissions.denies_tool(name))
+            .map(|(_, tool)| tool.schema())
             .collect()
     }
 
@@ -335,7 +516,8 @@ impl ToolRegistry {
             "list_dir" | "find_definition" | "find_references" | "glob_files" => {
                 self.resolve_path_field(&mut map, "path", true);
             }
-            "git_diff" | "git_log" => {
+            "git_diff" | "git_log" | "git_blame" | "file_history" | "read_tool_output"
+            | "impact_analysis" | "semantic_search" => {
                 map.insert(
                     "_repo_root".to_string(),
                     Value::String(self.repo_root.display().to_string()),
@@ -380,6 +562,42 @@ impl ToolRegistry {
     }
 }
 
+fn builtin_tools() -> Vec<Box<dyn Tool>> {
+    vec![
+        Box::new(Shell::default()),
+        Box::new(Read),
+        Box::new(ReadToolOutput),
+        Box::new(Write),
+        Box::new(Edit),
+        Box::new(MultiEdit),
+        Box::new(Search),
+        Box::new(Glob),
+        Box::new(Test),
+        Box::new(ListDir),
+        Box::new(GitDiff),
+        Box::new(GitLog),
+        Box::new(GitBlame),
+        Box::new(FileHistory),
+        Box::new(RegexSearch),
+        Box::new(WebFetch),
+        Box::new(McpCall),
+        Box::new(FindDefinition),
+        Box::new(FindReferences),
+        Box::new(ImpactAnalysis),
+        Box::new(SemanticSearch),
+        Box::new(RenameSymbol),
+        Box::new(GitCommit),
+        Box::new(Patch),
+        Box::new(Plan),
+        Box::new(NotebookEdit),
+        Box::new(WebSearch),
+        Box::new(Diagnostics),
+        Box::new(WorktreeSwarm),
+        Box::new(DynamicWorkflow),
+        Box::new(SpawnSubagent),
+    ]
+}
+
 fn contains_any(haystack: &str, needles: &[&str]) -> bool {
     needles.iter().any(|needle| haystack.contains(needle))
 }
@@ -443,6 +661,99 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn run_shell_keeps_one_session_per_registry() {
+        let root = std::env::temp_dir().join(format!("osmogrep-shell-session-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let registry = ToolRegistry::with_root(root.clone());
+        let shell = |args: Value| {
+            registry
+                .call_cancellable("run_shell", args, &|| false)
+                .unwrap()
+        };
+
+        let result = shell(json!({ "cmd": "cd sub && export GREETING=hi && echo oops >&2" }));
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["stderr"], "oops\n");
+        assert!(result["cwd"].as_str().unwrap().ends_with("/sub"));
+        let result = shell(json!({ "cmd": "printf '%s in %s' \"$GREETING\" \"${PWD##*/}\"" }));
+        assert_eq!(result["stdout"], "hi in sub");
+
+        // Aliases, functions and traps do not outlive the call that set
+        // them, so a later `cargo test` runs cargo.
+        let result = shell(json!({
+            "cmd": "alias ls='echo hijacked'; probe() { echo hijacked; }; trap 'echo trapped' DEBUG"
+        }));
+        assert_eq!(result["exit_code"], 0);
+        let result = shell(json!({
+            "cmd": "type probe >/dev/null 2>&1 && echo kept || echo gone; alias ls || echo no-alias"
+        }));
+        assert_eq!(result["stdout"], "gone\nno-alias\n");
+
+        let result = shell(json!({ "cmd": "echo start; sleep 3; echo end", "timeout_secs": 1 }));
+        assert_eq!(result["running"], true);
+        assert_eq!(result["exit_code"], Value::Null);
+        let blocked = shell(json!({ "cmd": "true" }));
+        assert!(blocked["error"].as_str().unwrap().contains("still running"));
+        let mut output = result["stdout"].as_str().unwrap().to_string();
+        let result = shell(json!({ "timeout_secs": 10 }));
+        output.push_str(result["stdout"].as_str().unwrap());
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(output, "start\nend\n");
+
+        assert_eq!(shell(json!({ "reset_shell": true }))["reset"], true);
+        let result = shell(json!({ "cmd": "echo \"[$GREETING]\"; exit 3" }));
+        assert_eq!(result["stdout"], "[]\n");
+        assert_eq!(result["exit_code"], 3);
+        assert_eq!(result["session_ended"], true);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn run_shell_keeps_one_session_per_registry() {
+        let root = std::env::temp_dir().join(format!("osmogrep-shell-session-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let registry = ToolRegistry::with_root(root.clone());
+        let shell = |args: Value| {
+            registry
+                .call_cancellable("run_shell", args, &|| false)
+                .unwrap()
+        };
+        let stdout = |result: &Value| result["stdout"].as_str().unwrap().trim().to_string();
+
+        let result = shell(json!({ "cmd": "Set-Location sub; $env:GREETING = 'hi'" }));
+        assert_eq!(result["exit_code"], 0);
+        assert!(result["cwd"].as_str().unwrap().ends_with("\\sub"));
+        let result = shell(
+            json!({ "cmd": "Write-Output \"$env:GREETING in $(Split-Path -Leaf $PWD.Path)\"" }),
+        );
+        assert_eq!(stdout(&result), "hi in sub");
+
+        let result = shell(json!({
+            "cmd": "Write-Output start; Start-Sleep 3; Write-Output end",
+            "timeout_secs": 1
+        }));
+        assert_eq!(result["running"], true);
+        let mut output = stdout(&result);
+        let result = shell(json!({ "timeout_secs": 10 }));
+        output.push('\n');
+        output.push_str(&stdout(&result));
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(
+            output.split_whitespace().collect::<Vec<_>>(),
+            ["start", "end"]
+        );
+
+        assert_eq!(shell(json!({ "reset_shell": true }))["reset"], true);
+        let result = shell(json!({ "cmd": "Write-Output \"[$env:GREETING]\"" }));
+        assert_eq!(stdout(&result), "[]");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
     #[test]
     fn run_shell_tool_reports_cancelled_process() {
         let root =
@@ -717,6 +1028,31 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn subagent_scope_narrows_the_parent_scope() {
+        let root =
+            std::env::temp_dir().join(format!("osmogrep-subagent-scope-root-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let registry = ToolRegistry::with_root(root.clone());
+        let parent = ToolScope::for_prompt("fix the parser");
+
+        let names = schema_names(&registry.scoped_schema(&parent.clone().for_subagent(None, true)));
+        assert!(names.contains(&"read_file".to_string()));
+        assert!(names.contains(&"spawn_subagent".to_string()));
+        assert!(!names.contains(&"edit_file".to_string()));
+        assert!(!names.contains(&"update_plan".to_string()));
+
+        let tools = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let child = parent.for_subagent(Some(tools(&["read_file", "edit_file"])), false);
+        let mut names = schema_names(&registry.scoped_schema(&child));
+        names.sort();
+        assert_eq!(names, tools(&["edit_file", "read_file"]));
+        let grandchild = child.for_subagent(Some(tools(&["read_file", "run_shell"])), false);
+        assert!(grandchild.excludes("run_shell"));
+        assert!(!grandchild.excludes("read_file"));
+        let _ = fs::remove_dir_all(root);
+    }
+
     #[test]
     fn tool_scope_exposes_worktree_swarm_for_parallel_audits() {
         let root =
@@ -731,6 +1067,70 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    struct Deploy;
+
+    impl Tool for Deploy {
+        fn name(&self) -> &'static str {
+            "deploy"
+        }
+
+        fn schema(&self) -> Value {
+            json!({ "type": "function", "name": "deploy", "parameters": { "type": "object" } })
+        }
+
+        fn safety(&self) -> ToolSafety {
+            ToolSafety::Dangerous
+        }
+
+        fn call(&self, _args: Value) -> ToolResult {
+            Ok(json!({ "cwd": std::env::current_dir().unwrap().display().to_string() }))
+        }
+    }
+
+    #[test]
+    fn builder_registers_custom_tools_and_safety_overrides() {
+        let root = std::env::temp_dir().join(format!("osmogrep-custom-tool-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+
+        let mut registry = ToolRegistry::builder(root.clone())
+            .tool(Box::new(Deploy))
+            .remove("web_search")
+            .safety("run_shell", ToolSafety::Safe)
+            .build();
+
+        assert!(registry.tool_names().contains(&"deploy"));
+        assert!(!registry.tool_names().contains(&"web_search"));
+        assert_eq!(registry.safety("deploy"), Some(ToolSafety::Dangerous));
+        assert_eq!(registry.safety("run_shell"), Some(ToolSafety::Safe));
+        assert!(!registry.parallel_safe("run_shell"));
+
+        let result = registry
+            .call_cancellable("deploy", json!({}), &|| false)
+            .unwrap();
+        assert_eq!(
+            result.get("cwd").and_then(Value::as_str),
+            Some(root.display().to_string().as_str())
+        );
+
+        assert!(registry.register(Box::new(Deploy)).is_some());
+        let read_only =
+            schema_names(&registry.scoped_schema(&ToolScope::for_prompt("deploy it").read_only()));
+        assert!(!read_only.contains(&"deploy".to_string()));
+        assert!(read_only.contains(&"read_file".to_string()));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn builder_without_builtins_only_exposes_added_tools() {
+        let registry = ToolRegistry::builder(std::env::temp_dir())
+            .without_builtins()
+            .tool(Box::new(Deploy))
+            .build();
+
+        assert_eq!(registry.tool_names(), vec!["deploy"]);
+        assert!(registry.safety("read_file").is_none());
+    }
+
     fn schema_names(schema: &[Value]) -> Vec<String> {
         schema
             .iter()
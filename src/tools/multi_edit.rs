@@ -0,0 +1,257 @@
+// src/tools/multi_edit.rs
+
+use serde_json::{json, Value};
+use similar::TextDiff;
+use std::fs;
+use std::path::Path;
+
+use super::{Tool, ToolResult, ToolSafety};
+
+pub struct MultiEdit;
+
+impl Tool for MultiEdit {
+    fn name(&self) -> &'static str {
+        "multi_edit"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "multi_edit",
+            "description": "Apply a batch of text replacements across one or more files in one step. Edits run in order, so a later edit to a file sees the earlier ones. Every `old` must match or no file is written. Returns a combined unified diff.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "edits": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": { "type": "string" },
+                                "old": { "type": "string" },
+                                "new": { "type": "string" },
+                                "all_occ": { "type": "boolean" }
+                            },
+                            "required": ["path", "old", "new"],
+                            "additionalProperties": false
+                        }
+                    }
+                },
+                "required": ["edits"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Dangerous
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        let root = std::env::current_dir().map_err(|e| e.to_string())?;
+        multi_edit(&root, &args)
+    }
+}
+
+struct FileEdit {
+    path: String,
+    before: String,
+    after: String,
+}
+
+/// Files a `multi_edit` call would change, in the order first edited.
+pub fn multi_edit_targets(args: &Value) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for edit in args
+        .get("edits")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(path) = edit.get("path").and_then(Value::as_str) {
+            if !paths.iter().any(|p| p == path) {
+                paths.push(path.to_string());
+            }
+        }
+    }
+    paths
+}
+
+fn multi_edit(root: &Path, args: &Value) -> ToolResult {
+    let edits = args
+        .get("edits")
+        .and_then(Value::as_array)
+        .ok_or("missing edits")?;
+    if edits.is_empty() {
+        return Err("edits is empty".into());
+    }
+
+    let files = plan(root, edits)?;
+    let pre_hooks: Vec<_> = files
+        .iter()
+        .map(|f| {
+            crate::hooks::run_hook("pre_edit", &[("path", &f.path)])
+                .ok()
+                .flatten()
+        })
+        .collect();
+    apply(root, &files)?;
+    let verification_stale =
+        crate::verification::mark_workspace_edited(root, files.iter().map(|f| f.path.as_str()));
+
+    let mut diff = String::new();
+    let mut changes = Vec::new();
+    for (file, pre_hook) in files.iter().zip(pre_hooks) {
+        let post_hook = crate::hooks::run_hook("post_edit", &[("path", &file.path)])
+            .ok()
+            .flatten();
+        diff.push_str(
+            &TextDiff::from_lines(&file.before, &file.after)
+                .unified_diff()
+                .header(&format!("a/{}", file.path), &format!("b/{}", file.path))
+                .to_string(),
+        );
+        changes.push(json!({
+            "path": file.path,
+            "before": file.before,
+            "after": file.after,
+            "pre_hook": pre_hook,
+            "post_hook": post_hook,
+        }));
+    }
+
+    Ok(json!({
+        "edits": edits.len(),
+        "files_changed": files.len(),
+        "diff": diff,
+        "changes": changes,
+        "verification_stale": crate::verification::staleness_to_json(&verification_stale)
+    }))
+}
+
+/// Every file's final text, or the first edit that does not apply.
+fn plan(root: &Path, edits: &[Value]) -> Result<Vec<FileEdit>, String> {
+    let mut files: Vec<FileEdit> = Vec::new();
+    for (idx, edit) in edits.iter().enumerate() {
+        let n = idx + 1;
+        let field = |key: &str| {
+            edit.get(key)
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("edit {n}: missing {key}"))
+        };
+        let (path, old, new) = (field("path")?, field("old")?, field("new")?);
+        let all = edit
+            .get("all_occ")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if old.is_empty() {
+            return Err(format!("edit {n} ({path}): old is empty"));
+        }
+
+        let file = match files.iter().position(|f| f.path == path) {
+            Some(pos) => &mut files[pos],
+            None => {
+                let before = fs::read_to_string(root.join(path))
+                    .map_err(|e| format!("edit {n}: failed to read {path}: {e}"))?;
+                files.push(FileEdit {
+                    path: path.to_string(),
+                    after: before.clone(),
+                    before,
+                });
+                files.last_mut().unwrap()
+            }
+        };
+        if !file.after.contains(old) {
+            return Err(format!(
+                "edit {n} ({path}): old string not found; no files changed"
+            ));
+        }
+        file.after = if all {
+            file.after.replace(old, new)
+        } else {
+            file.after.replacen(old, new, 1)
+        };
+    }
+    files.retain(|f| f.before != f.after);
+    Ok(files)
+}
+
+/// Writes every file, or restores the ones already written and fails.
+fn apply(root: &Path, files: &[FileEdit]) -> Result<(), String> {
+    for (idx, file) in files.iter().enumerate() {
+        if let Err(e) = fs::write(root.join(&file.path), &file.after) {
+            for written in &files[..idx] {
+                let _ = fs::write(root.join(&written.path), &written.before);
+            }
+            return Err(format!(
+                "failed to write {}: {e}; no files changed",
+                file.path
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn repo() -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("osmogrep-multi-edit-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn old() {}\nfn a() { old(); }\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { lib::old(); }\n").unwrap();
+        root
+    }
+
+    #[test]
+    fn applies_every_edit_and_returns_one_diff() {
+        let root = repo();
+        let args = json!({ "edits": [
+            { "path": "src/lib.rs", "old": "old", "new": "new", "all_occ": true },
+            { "path": "src/main.rs", "old": "lib::old", "new": "lib::new" },
+            { "path": "src/lib.rs", "old": "fn a()", "new": "fn b()" },
+        ]});
+        assert_eq!(
+            multi_edit_targets(&args),
+            vec!["src/lib.rs".to_string(), "src/main.rs".to_string()]
+        );
+
+        let result = multi_edit(&root, &args).unwrap();
+        assert_eq!(result["edits"], 3);
+        assert_eq!(result["files_changed"], 2);
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "pub fn new() {}\nfn b() { new(); }\n"
+        );
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+        assert!(diff.contains("-fn main() { lib::old(); }\n+fn main() { lib::new(); }\n"));
+        assert_eq!(result["changes"][1]["path"], "src/main.rs");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn one_missing_match_writes_nothing() {
+        let root = repo();
+        let args = json!({ "edits": [
+            { "path": "src/lib.rs", "old": "pub fn old", "new": "pub fn new" },
+            { "path": "src/main.rs", "old": "lib::gone", "new": "lib::new" },
+        ]});
+        let err = multi_edit(&root, &args).unwrap_err();
+        assert_eq!(
+            err,
+            "edit 2 (src/main.rs): old string not found; no files changed"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "pub fn old() {}\nfn a() { old(); }\n"
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -13,6 +13,8 @@ struct PlanItem {
     id: u64,
     text: String,
     status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<String>,
 }
 
 impl Tool for Plan {
@@ -34,13 +36,30 @@ impl Tool for Plan {
                     },
                     "items": {
                         "type": "array",
-                        "items": { "type": "string" },
-                        "description": "Plan item texts for action=set"
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "text": { "type": "string" },
+                                "files": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "Repo-relative files or globs the step will change"
+                                }
+                            },
+                            "required": ["text"],
+                            "additionalProperties": false
+                        },
+                        "description": "Plan steps for action=set"
                     },
                     "item": {
                         "type": "string",
                         "description": "Plan item text for action=add"
                     },
+                    "files": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Files the step from action=add will change"
+                    },
                     "id": {
                         "type": "integer",
                         "description": "Plan item id for action=done"
@@ -72,16 +91,20 @@ impl Tool for Plan {
                     .get("items")
                     .and_then(Value::as_array)
                     .ok_or("action=set requires items")?;
+                // Steps may also come as bare strings.
                 items = raw_items
                     .iter()
-                    .filter_map(Value::as_str)
-                    .map(str::trim)
-                    .filter(|text| !text.is_empty())
+                    .filter_map(|raw| {
+                        let text = raw.as_str().or_else(|| raw.get("text")?.as_str())?;
+                        Some((text.trim(), string_list(raw.get("files"))))
+                    })
+                    .filter(|(text, _)| !text.is_empty())
                     .enumerate()
-                    .map(|(idx, text)| PlanItem {
+                    .map(|(idx, (text, files))| PlanItem {
                         id: (idx as u64) + 1,
                         text: text.to_string(),
                         status: if idx == 0 { "in_progress" } else { "pending" }.to_string(),
+                        files,
                     })
                     .collect();
                 save_plan(&path, &items)?;
@@ -98,6 +121,7 @@ impl Tool for Plan {
                     id: next_id,
                     text: text.to_string(),
                     status: "pending".to_string(),
+                    files: string_list(args.get("files")),
                 });
                 save_plan(&path, &items)?;
             }
@@ -137,8 +161,46 @@ impl Tool for Plan {
     }
 }
 
+/// Replaces the stored plan with an approved checklist, so the agent's
+/// `done` calls use its step numbers.
+pub fn seed_plan(root: &Path, steps: &[crate::state::PlanItem]) -> Result<(), String> {
+    let items: Vec<PlanItem> = steps
+        .iter()
+        .enumerate()
+        .map(|(idx, step)| PlanItem {
+            id: (idx as u64) + 1,
+            text: step.text.clone(),
+            status: if step.done {
+                "completed"
+            } else if step.active {
+                "in_progress"
+            } else {
+                "pending"
+            }
+            .to_string(),
+            files: step.files.clone(),
+        })
+        .collect();
+    save_plan(&plan_path(root), &items)
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|list| {
+            list.iter()
+                .filter_map(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn plan_path(root: &Path) -> PathBuf {
-    root.join(".context").join("osmogrep-plan.json")
+    root.join(".context")
+        .join(crate::instance::artifact_name("osmogrep-plan", "json"))
 }
 
 fn load_plan(path: &Path) -> Result<Vec<PlanItem>, String> {
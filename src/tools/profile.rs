@@ -0,0 +1,203 @@
+//! Per-provider/model capability profiles that trim the tool schema before
+//! it is sent. Built-in profiles cover providers known to struggle with
+//! large schemas; `[tool_profiles]` in the config file overrides them:
+//!
+//! ```toml
+//! [tool_profiles.ollama]
+//! drop_tools = ["notebook_edit", "worktree_swarm"]
+//! collapse_enums = true
+//!
+//! [tool_profiles."ollama/qwen2.5-coder"]   # provider/model wins over provider
+//! drop_tools = []
+//! text_tools = false                       # the model calls tools natively
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolProfile {
+    /// Tools never offered to the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drop_tools: Vec<String>,
+    /// Replace `enum` constraints with the allowed values in the description.
+    #[serde(default)]
+    pub collapse_enums: bool,
+    /// Drop parameter descriptions to save tokens.
+    #[serde(default)]
+    pub strip_param_descriptions: bool,
+    /// Describe tools in the prompt and parse calls out of the reply text,
+    /// for models without native function calls; see `text_tools`.
+    #[serde(default)]
+    pub text_tools: bool,
+}
+
+impl ToolProfile {
+    /// Most specific match: `provider/model` in `overrides`, then `provider`,
+    /// then the built-in profile. Returns the profile and the key it came from.
+    pub fn resolve(
+        provider: &str,
+        model: &str,
+        overrides: &BTreeMap<String, ToolProfile>,
+    ) -> (Self, String) {
+        let specific = format!("{provider}/{model}");
+        if let Some(profile) = overrides.get(&specific) {
+            return (profile.clone(), specific);
+        }
+        if let Some(profile) = overrides.get(provider) {
+            return (profile.clone(), provider.to_string());
+        }
+        (Self::builtin(provider), format!("{provider} (built-in)"))
+    }
+
+    fn builtin(provider: &str) -> Self {
+        match provider {
+            // Local models lose track of long tool lists, often emit
+            // values outside an enum and many have no native tool calls.
+            "ollama" => Self {
+                drop_tools: [
+                    "notebook_edit",
+                    "worktree_swarm",
+                    "dynamic_workflow",
+                    "mcp_call",
+                ]
+                .map(String::from)
+                .to_vec(),
+                collapse_enums: true,
+                strip_param_descriptions: false,
+                text_tools: true,
+            },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Schemas left after the profile, and the names it dropped.
+    pub fn apply(&self, schemas: Vec<Value>) -> (Vec<Value>, Vec<String>) {
+        let mut dropped = Vec::new();
+        let kept = schemas
+            .into_iter()
+            .filter(|schema| {
+                let name = schema.get("name").and_then(Value::as_str).unwrap_or("");
+                let drop = self.drop_tools.iter().any(|d| d == name);
+                if drop {
+                    dropped.push(name.to_string());
+                }
+                !drop
+            })
+            .map(|mut schema| {
+                if let Some(params) = schema.get_mut("parameters") {
+                    self.simplify(params);
+                }
+                schema
+            })
+            .collect();
+        dropped.sort();
+        (kept, dropped)
+    }
+
+    fn simplify(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                let mut hint = None;
+                if self.collapse_enums {
+                    if let Some(Value::Array(options)) = map.remove("enum") {
+                        let options: Vec<String> = options
+                            .iter()
+                            .map(|o| o.as_str().map_or_else(|| o.to_string(), str::to_string))
+                            .collect();
+                        hint = Some(format!("One of: {}.", options.join(", ")));
+                    }
+                }
+                // A non-string `description` is a property of that name.
+                if self.strip_param_descriptions
+                    && map.get("description").is_some_and(Value::is_string)
+                {
+                    map.remove("description");
+                }
+                if let Some(hint) = hint {
+                    let description = match map.get("description").and_then(Value::as_str) {
+                        Some(existing) => format!("{existing} {hint}"),
+                        None => hint,
+                    };
+                    map.insert("description".to_string(), Value::String(description));
+                }
+                for child in map.values_mut() {
+                    self.simplify(child);
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.simplify(item)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(name: &str) -> Value {
+        json!({
+            "type": "function",
+            "name": name,
+            "description": "top level",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["list", "set"] },
+                    "path": { "type": "string", "description": "File path" }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn resolves_most_specific_profile() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(
+            "ollama/qwen".to_string(),
+            ToolProfile {
+                strip_param_descriptions: true,
+                ..ToolProfile::default()
+            },
+        );
+
+        let (profile, source) = ToolProfile::resolve("ollama", "qwen", &overrides);
+        assert_eq!(source, "ollama/qwen");
+        assert!(profile.drop_tools.is_empty());
+
+        let (profile, source) = ToolProfile::resolve("ollama", "llama3", &overrides);
+        assert_eq!(source, "ollama (built-in)");
+        assert!(profile.drop_tools.contains(&"notebook_edit".to_string()));
+        assert!(profile.text_tools);
+        assert!(ToolProfile::resolve("openai", "gpt-5.2", &overrides)
+            .0
+            .is_noop());
+    }
+
+    #[test]
+    fn drops_tools_and_collapses_enums() {
+        let profile = ToolProfile {
+            drop_tools: vec!["notebook_edit".into()],
+            collapse_enums: true,
+            strip_param_descriptions: true,
+            text_tools: false,
+        };
+        let (kept, dropped) = profile.apply(vec![schema("update_plan"), schema("notebook_edit")]);
+        assert_eq!(dropped, vec!["notebook_edit"]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0]["description"], "top level");
+        let props = &kept[0]["parameters"]["properties"];
+        assert_eq!(
+            props["action"],
+            json!({ "type": "string", "description": "One of: list, set." })
+        );
+        assert_eq!(props["path"], json!({ "type": "string" }));
+    }
+}
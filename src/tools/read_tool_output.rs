@@ -0,0 +1,51 @@
+// src/tools/read_tool_output.rs
+
+use serde_json::{json, Value};
+use std::path::Path;
+
+use super::{Tool, ToolResult, ToolSafety};
+
+pub struct ReadToolOutput;
+
+impl Tool for ReadToolOutput {
+    fn name(&self) -> &'static str {
+        "read_tool_output"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "read_tool_output",
+            "description": "Read lines of a tool output that was too large for the conversation, by the id from its <persisted-output> summary. Use next_offset to continue.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id":     { "type": "string" },
+                    "offset": { "type": "integer", "description": "0-based line, default 0" },
+                    "limit":  { "type": "integer", "description": "Lines to return, default 200" }
+                },
+                "required": ["id"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Safe
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        let id = args.get("id").and_then(Value::as_str).ok_or("missing id")?;
+        let offset = args.get("offset").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map(|v| v as usize);
+        let root = args
+            .get("_repo_root")
+            .and_then(Value::as_str)
+            .unwrap_or(".");
+
+        crate::tool_budget::read_output(Path::new(root), id, offset, limit)
+    }
+}
@@ -0,0 +1,427 @@
+// src/tools/rename_symbol.rs
+
+use regex::{NoExpand, Regex};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use super::diagnostics::{default_command, parse_issues};
+use super::{Tool, ToolResult, ToolSafety};
+
+pub struct RenameSymbol;
+
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+const MAX_LOCATIONS: usize = 200;
+const MAX_ISSUES: usize = 20;
+
+impl Tool for RenameSymbol {
+    fn name(&self) -> &'static str {
+        "rename_symbol"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "rename_symbol",
+            "description": "Rename an identifier across the repo in one step. Replaces whole-word occurrences in every text file under path, writes all files or none, then runs diagnostics and rolls the rename back if it breaks a previously clean build. Use dry_run to preview every location first; the user sees the full diff before approving.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "symbol": { "type": "string" },
+                    "new_name": { "type": "string" },
+                    "path": { "type": "string", "description": "Directory or file to limit the rename to" },
+                    "dry_run": { "type": "boolean", "description": "Only list the locations that would change" },
+                    "force": { "type": "boolean", "description": "Rename even if new_name already appears in the touched files" },
+                    "verify": { "type": "boolean", "description": "Run diagnostics before and after (default true)" },
+                    "verify_cmd": { "type": "string" }
+                },
+                "required": ["symbol", "new_name"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Dangerous
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        self.call_cancellable(args, &|| false)
+    }
+
+    fn call_cancellable(&self, args: Value, is_cancelled: &dyn Fn() -> bool) -> ToolResult {
+        let root = std::env::current_dir().map_err(|e| e.to_string())?;
+        rename(&root, &args, is_cancelled)
+    }
+}
+
+struct FileRename {
+    path: String,
+    before: String,
+    after: String,
+    /// `(line, column, new line text)` for each occurrence, 1-based.
+    locations: Vec<(usize, usize, String)>,
+}
+
+struct RenamePlan {
+    files: Vec<FileRename>,
+    /// Touched files that already contain `new_name`.
+    conflicts: Vec<String>,
+}
+
+/// Repo-relative files a `rename_symbol` call would change.
+pub fn rename_targets(repo_root: &Path, args: &Value) -> Vec<String> {
+    rename_preview(repo_root, args)
+        .into_iter()
+        .map(|(path, _, _)| path)
+        .collect()
+}
+
+/// `(path, before, after)` for each file a `rename_symbol` call would
+/// change, shown as a diff before the call is approved.
+pub fn rename_preview(repo_root: &Path, args: &Value) -> Vec<(String, String, String)> {
+    let symbol = args.get("symbol").and_then(Value::as_str).unwrap_or("");
+    let new_name = args.get("new_name").and_then(Value::as_str).unwrap_or("");
+    let scope = args.get("path").and_then(Value::as_str).unwrap_or(".");
+    plan(repo_root, scope, symbol, new_name)
+        .map(|plan| {
+            plan.files
+                .into_iter()
+                .map(|f| (f.path, f.before, f.after))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rename(root: &Path, args: &Value, is_cancelled: &dyn Fn() -> bool) -> ToolResult {
+    let symbol = args
+        .get("symbol")
+        .and_then(Value::as_str)
+        .ok_or("missing symbol")?;
+    let new_name = args
+        .get("new_name")
+        .and_then(Value::as_str)
+        .ok_or("missing new_name")?;
+    let scope = args.get("path").and_then(Value::as_str).unwrap_or(".");
+    let flag = |key: &str, default: bool| args.get(key).and_then(Value::as_bool).unwrap_or(default);
+    let (dry_run, force, verify) = (
+        flag("dry_run", false),
+        flag("force", false),
+        flag("verify", true),
+    );
+
+    let plan = plan(root, scope, symbol, new_name)?;
+    if plan.files.is_empty() {
+        return Err(format!("no occurrences of {symbol} under {scope}"));
+    }
+
+    let occurrences: usize = plan.files.iter().map(|f| f.locations.len()).sum();
+    let locations: Vec<Value> = plan
+        .files
+        .iter()
+        .flat_map(|f| {
+            f.locations.iter().map(|(line, column, text)| {
+                json!({ "path": f.path, "line": line, "column": column, "text": text })
+            })
+        })
+        .take(MAX_LOCATIONS)
+        .collect();
+    let mut result = json!({
+        "symbol": symbol,
+        "new_name": new_name,
+        "dry_run": dry_run,
+        "files_changed": plan.files.len(),
+        "occurrences": occurrences,
+        "definitions": definitions(root, symbol),
+        "locations": locations,
+        "locations_truncated": occurrences > MAX_LOCATIONS,
+        "conflicts": plan.conflicts,
+    });
+
+    if !plan.conflicts.is_empty() && !force {
+        result["error"] = json!(format!(
+            "{new_name} already appears in {}; pass force to rename anyway",
+            plan.conflicts.join(", ")
+        ));
+        return Ok(result);
+    }
+    if dry_run {
+        return Ok(result);
+    }
+
+    let verify_cmd = args
+        .get("verify_cmd")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(default_command);
+    let baseline = if verify {
+        Some(run_diagnostics(root, &verify_cmd, is_cancelled)?)
+    } else {
+        None
+    };
+
+    apply(root, &plan.files)?;
+    crate::verification::mark_workspace_edited(root, plan.files.iter().map(|f| f.path.as_str()));
+
+    if let Some((baseline_exit, _)) = baseline {
+        let (exit_code, output) = run_diagnostics(root, &verify_cmd, is_cancelled)?;
+        let issues = parse_issues(&output);
+        result["verification"] = json!({
+            "command": verify_cmd,
+            "baseline_exit_code": baseline_exit,
+            "exit_code": exit_code,
+            "issue_count": issues.len(),
+            "issues": issues.into_iter().take(MAX_ISSUES).collect::<Vec<_>>(),
+        });
+        if baseline_exit == 0 && exit_code != 0 {
+            restore(root, &plan.files)?;
+            result["rolled_back"] = json!(true);
+            result["error"] = json!(format!(
+                "`{verify_cmd}` failed after the rename; all edits were rolled back"
+            ));
+            return Ok(result);
+        }
+    }
+
+    result["rolled_back"] = json!(false);
+    result["changes"] = plan
+        .files
+        .iter()
+        .map(|f| json!({ "path": f.path, "before": f.before, "after": f.after }))
+        .collect();
+    Ok(result)
+}
+
+fn plan(root: &Path, scope: &str, symbol: &str, new_name: &str) -> Result<RenamePlan, String> {
+    let ident = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    for name in [symbol, new_name] {
+        if !ident.is_match(name) {
+            return Err(format!("{name:?} is not an identifier"));
+        }
+    }
+    if symbol == new_name {
+        return Err("new_name is the same as symbol".to_string());
+    }
+    let old_re = word_regex(symbol)?;
+    let new_re = word_regex(new_name)?;
+
+    let mut files = Vec::new();
+    let mut conflicts = Vec::new();
+    let base = root.join(scope);
+    let mut paths: Vec<_> = WalkDir::new(&base)
+        .max_depth(12)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|ent| ent.file_type().is_file())
+        .map(|ent| ent.into_path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let rel = path.strip_prefix(root).unwrap_or(&path);
+        if is_skipped(rel) || fs::metadata(&path).map_or(true, |m| m.len() > MAX_FILE_BYTES) {
+            continue;
+        }
+        let Ok(bytes) = fs::read(&path) else {
+            continue;
+        };
+        if crate::binary_assets::is_asset(&bytes) {
+            continue;
+        }
+        let Ok(before) = String::from_utf8(bytes) else {
+            continue;
+        };
+        if !old_re.is_match(&before) {
+            continue;
+        }
+
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        let after = old_re.replace_all(&before, NoExpand(new_name)).to_string();
+        let locations = before
+            .lines()
+            .enumerate()
+            .flat_map(|(idx, line)| {
+                let renamed = old_re
+                    .replace_all(line, NoExpand(new_name))
+                    .trim()
+                    .to_string();
+                old_re
+                    .find_iter(line)
+                    .map(move |m| {
+                        (
+                            idx + 1,
+                            line[..m.start()].chars().count() + 1,
+                            renamed.clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if new_re.is_match(&before) {
+            conflicts.push(rel.clone());
+        }
+        files.push(FileRename {
+            path: rel,
+            before,
+            after,
+            locations,
+        });
+    }
+
+    Ok(RenamePlan { files, conflicts })
+}
+
+fn word_regex(name: &str) -> Result<Regex, String> {
+    Regex::new(&format!(r"\b{}\b", regex::escape(name))).map_err(|e| e.to_string())
+}
+
+fn is_skipped(rel: &Path) -> bool {
+    crate::context::indexer::should_ignore(rel)
+        || rel.components().any(|c| {
+            matches!(
+                c.as_os_str().to_string_lossy().as_ref(),
+                ".context" | ".osmogrep"
+            )
+        })
+}
+
+/// Definition sites from the symbol index, as `file:line`.
+fn definitions(root: &Path, symbol: &str) -> Vec<String> {
+    crate::context::indexer::load_or_build(root)
+        .symbols
+        .iter()
+        .filter(|s| s.name == symbol)
+        .map(|s| {
+            let file = Path::new(&s.file);
+            let file = file.strip_prefix(root).unwrap_or(file);
+            format!("{}:{}", file.display(), s.line_start)
+        })
+        .collect()
+}
+
+/// Writes every file, or restores the ones already written and fails.
+fn apply(root: &Path, files: &[FileRename]) -> Result<(), String> {
+    for (idx, file) in files.iter().enumerate() {
+        if let Err(e) = fs::write(root.join(&file.path), &file.after) {
+            let _ = restore(root, &files[..idx]);
+            return Err(format!(
+                "failed to write {}: {e}; no files changed",
+                file.path
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn restore(root: &Path, files: &[FileRename]) -> Result<(), String> {
+    for file in files {
+        fs::write(root.join(&file.path), &file.before)
+            .map_err(|e| format!("failed to restore {}: {e}", file.path))?;
+    }
+    Ok(())
+}
+
+fn run_diagnostics(
+    root: &Path,
+    cmd: &str,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Result<(i32, String), String> {
+    let timeout = crate::process_runner::timeout_from_env("OSMOGREP_DIAGNOSTICS_TIMEOUT_SECS", 300);
+    let out = crate::process_runner::run_shell_command_cancellable(
+        cmd,
+        Some(root),
+        timeout,
+        is_cancelled,
+    )?;
+    if out.cancelled {
+        return Err("cancelled".to_string());
+    }
+    let mut text = String::from_utf8_lossy(&out.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&out.stderr));
+    Ok((out.exit_code, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn repo() -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("osmogrep-rename-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn parse_args() {}\nfn parse_args_twice() { parse_args(); }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() { lib::parse_args(); }\n",
+        )
+        .unwrap();
+        fs::write(root.join("README.md"), "Nothing here.\n").unwrap();
+        root
+    }
+
+    #[test]
+    fn previews_then_renames_whole_words_in_every_file() {
+        let root = repo();
+        let args = json!({ "symbol": "parse_args", "new_name": "parse_cli", "dry_run": true });
+        let preview = rename(&root, &args, &|| false).unwrap();
+        assert_eq!(preview["files_changed"], 2);
+        assert_eq!(preview["occurrences"], 3);
+        assert_eq!(preview["definitions"], json!(["src/lib.rs:1"]));
+        assert_eq!(
+            preview["locations"][1],
+            json!({
+                "path": "src/lib.rs",
+                "line": 2,
+                "column": 25,
+                "text": "fn parse_args_twice() { parse_cli(); }"
+            })
+        );
+        assert_eq!(
+            rename_targets(&root, &args),
+            vec!["src/lib.rs".to_string(), "src/main.rs".to_string()]
+        );
+        let (path, before, after) = rename_preview(&root, &args).pop().unwrap();
+        assert_eq!(path, "src/main.rs");
+        assert_eq!(before, "fn main() { lib::parse_args(); }\n");
+        assert_eq!(after, "fn main() { lib::parse_cli(); }\n");
+        assert!(fs::read_to_string(root.join("src/main.rs"))
+            .unwrap()
+            .contains("parse_args"));
+
+        let args = json!({ "symbol": "parse_args", "new_name": "parse_cli", "verify": false });
+        let applied = rename(&root, &args, &|| false).unwrap();
+        assert_eq!(applied["changes"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "pub fn parse_cli() {}\nfn parse_args_twice() { parse_cli(); }\n"
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn refuses_names_that_already_exist_and_rolls_back_failed_verification() {
+        let root = repo();
+        let args = json!({ "symbol": "parse_args", "new_name": "main", "verify": false });
+        let result = rename(&root, &args, &|| false).unwrap();
+        assert_eq!(result["conflicts"], json!(["src/main.rs"]));
+        assert!(result["error"].as_str().unwrap().contains("force"));
+
+        let args = json!({
+            "symbol": "parse_args",
+            "new_name": "parse_cli",
+            "verify_cmd": "! grep -rq parse_cli src",
+        });
+        let result = rename(&root, &args, &|| false).unwrap();
+        assert_eq!(result["rolled_back"], true);
+        assert_eq!(result["verification"]["baseline_exit_code"], 0);
+        assert!(fs::read_to_string(root.join("src/main.rs"))
+            .unwrap()
+            .contains("parse_args"));
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use super::{Tool, ToolResult, ToolSafety};
+use crate::context::{indexer, search};
+
+pub struct SemanticSearch;
+
+impl Tool for SemanticSearch {
+    fn name(&self) -> &'static str {
+        "semantic_search"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "semantic_search",
+            "description": "Rank indexed functions, classes and files by relevance to a natural-language query (BM25 over identifiers, doc comments and comments). Use it to find where something is implemented before grepping",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer" }
+                },
+                "required": ["query"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        ToolSafety::Safe
+    }
+
+    fn call(&self, args: Value) -> ToolResult {
+        let query = args
+            .get("query")
+            .and_then(Value::as_str)
+            .ok_or("missing query")?;
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map(|n| n as usize)
+            .unwrap_or(search::DEFAULT_LIMIT)
+            .clamp(1, 50);
+        let root = Path::new(
+            args.get("_repo_root")
+                .and_then(Value::as_str)
+                .unwrap_or("."),
+        );
+
+        let ctx = indexer::load_or_build(root);
+        let hits = search::search(&ctx, root, query, limit);
+        Ok(json!({
+            "query": query,
+            "count": hits.len(),
+            "hits": hits,
+        }))
+    }
+}
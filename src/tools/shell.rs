@@ -1,8 +1,30 @@
 // src/tools/shell.rs
 
 use super::{Tool, ToolResult, ToolSafety};
+use crate::permissions::PermissionPolicy;
+use crate::platform::{self, Platform};
+use crate::sandbox::SandboxPolicy;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
 use serde_json::{json, Value};
-pub struct Shell;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const MARKER: &str = "__OSMOGREP_DONE_";
+const MAX_TIMEOUT_SECS: u64 = 3600;
+
+/// Runs commands in one shell that lives as long as the tool registry, i.e.
+/// one agent run, so `cd`, exported variables and activated virtualenvs
+/// carry over between calls.
+#[derive(Default)]
+pub struct Shell {
+    session: Mutex<Option<ShellSession>>,
+}
 
 impl Tool for Shell {
     fn name(&self) -> &'static str {
@@ -13,13 +35,14 @@ impl Tool for Shell {
         json!({
             "type": "function",
             "name": "run_shell",
-            "description": "Run a shell command and return stdout, stderr, and exit code",
+            "description": "Run a shell command and return stdout, stderr, exit code and the working directory afterwards. Commands share one persistent shell for the whole run, so cd, exports and activated virtualenvs carry over. A command still going at the timeout keeps running (`running: true`): call again without cmd to collect its new output, or with reset_shell to kill it and start a fresh shell.",
             "parameters": {
                 "type": "object",
                 "properties": {
-                    "cmd": { "type": "string" }
+                    "cmd": { "type": "string" },
+                    "timeout_secs": { "type": "integer", "description": "How long to wait for output (default 120)" },
+                    "reset_shell": { "type": "boolean", "description": "Kill the shell (and anything running in it) before cmd" }
                 },
-                "required": ["cmd"],
                 "additionalProperties": false
             }
         })
@@ -37,34 +60,112 @@ impl Tool for Shell {
         let cmd = args
             .get("cmd")
             .and_then(Value::as_str)
-            .ok_or("missing cmd")?;
-        if let Err(e) = crate::shell_guard::check_shell_command(cmd) {
-            return Ok(json!({
-                "error": e,
-                "blocked": true,
-                "exit_code": null
-            }));
+            .filter(|cmd| !cmd.trim().is_empty());
+        let reset = args
+            .get("reset_shell")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let timeout = match args.get("timeout_secs").and_then(Value::as_u64) {
+            Some(secs) => Duration::from_secs(secs.clamp(1, MAX_TIMEOUT_SECS)),
+            None => crate::process_runner::timeout_from_env("OSMOGREP_SHELL_TIMEOUT_SECS", 120),
+        };
+        if let Some(cmd) = cmd {
+            if let Err(e) = crate::shell_guard::check_shell_command(cmd) {
+                return Ok(json!({
+                    "error": e,
+                    "blocked": true,
+                    "exit_code": null
+                }));
+            }
         }
         let root = std::env::current_dir().map_err(|e| e.to_string())?;
 
-        let pre_hook = crate::hooks::run_hook("pre_shell", &[("cmd", cmd)])
-            .ok()
-            .flatten();
-
-        let timeout = crate::process_runner::timeout_from_env("OSMOGREP_SHELL_TIMEOUT_SECS", 120);
-        let out =
-            crate::process_runner::run_shell_command_cancellable(cmd, None, timeout, is_cancelled)?;
-        let stdout_raw = String::from_utf8_lossy(&out.stdout).to_string();
-        let stderr_raw = String::from_utf8_lossy(&out.stderr).to_string();
-        let exit_code = out.exit_code;
-        let mut combined = stdout_raw.clone();
-        if !stderr_raw.is_empty() {
-            if !combined.ends_with('\n') {
-                combined.push('\n');
-            }
-            combined.push_str(&stderr_raw);
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|_| "shell session lock poisoned".to_string())?;
+        if reset {
+            *session = None;
+            if cmd.is_none() {
+                return Ok(json!({ "reset": true }));
+            }
         }
-        let verification = crate::verification::record_command(&root, cmd, exit_code, &combined);
+
+        let pre_hook = match cmd {
+            Some(cmd) => {
+                let running = session.as_ref().and_then(|s| s.running.as_ref());
+                if let Some(running) = running {
+                    return Ok(json!({
+                        "error": format!(
+                            "`{}` is still running; call run_shell without cmd to wait for it, or with reset_shell to kill it",
+                            running.cmd
+                        ),
+                        "running": true,
+                        "exit_code": null
+                    }));
+                }
+                let pre_hook = crate::hooks::run_hook("pre_shell", &[("cmd", cmd)])
+                    .ok()
+                    .flatten();
+                if session.is_none() {
+                    let sandbox = PermissionPolicy::load(&root)?.sandbox;
+                    *session = Some(ShellSession::start(&root, &sandbox)?);
+                }
+                if let Some(session) = session.as_mut() {
+                    session.send(cmd)?;
+                }
+                pre_hook
+            }
+            None if session.as_ref().is_some_and(|s| s.running.is_some()) => None,
+            None => return Err("missing cmd".into()),
+        };
+
+        let Some(live) = session.as_mut() else {
+            return Err("missing cmd".into());
+        };
+        let outcome = live.wait(Instant::now() + timeout, is_cancelled);
+        let running = live.running.as_mut().ok_or("no command running")?;
+        let cmd = running.cmd.clone();
+        let duration_ms = running.started.elapsed().as_millis() as u64;
+        let stderr_raw = running.take_stderr();
+
+        let timed_out = matches!(outcome, Wait::TimedOut);
+        let session_ended = matches!(outcome, Wait::Exited(_));
+        let cancelled = matches!(outcome, Wait::Cancelled);
+        let (stdout_raw, exit_code, cwd) = match outcome {
+            Wait::Done { exit_code, cwd } => {
+                let stdout = running.take_stdout(0);
+                live.finish();
+                (stdout, Some(exit_code), Some(cwd))
+            }
+            Wait::TimedOut => (running.take_stdout(MARKER.len() + 40), None, None),
+            Wait::Exited(exit_code) => {
+                let stdout = running.take_stdout(0);
+                *session = None;
+                (stdout, exit_code, None)
+            }
+            Wait::Cancelled => {
+                let stdout = running.take_stdout(0);
+                *session = None;
+                (stdout, None, None)
+            }
+        };
+        let stderr_raw = if cancelled {
+            format!("{stderr_raw}cancelled; the shell session was reset")
+        } else {
+            stderr_raw
+        };
+
+        let verification = exit_code.map(|exit_code| {
+            let mut combined = stdout_raw.clone();
+            if !stderr_raw.is_empty() {
+                if !combined.ends_with('\n') {
+                    combined.push('\n');
+                }
+                combined.push_str(&stderr_raw);
+            }
+            crate::verification::record_command(&root, &cmd, exit_code, &combined)
+        });
         let stdout = crate::tool_budget::budget_text(&root, "run_shell_stdout", &stdout_raw);
         let stderr = crate::tool_budget::budget_text(&root, "run_shell_stderr", &stderr_raw);
 
@@ -72,11 +173,14 @@ impl Tool for Shell {
             "stdout": stdout.text,
             "stderr": stderr.text,
             "exit_code": exit_code,
-            "duration_ms": out.duration_ms,
-            "timed_out": out.timed_out,
-            "cancelled": out.cancelled,
+            "duration_ms": duration_ms,
+            "timed_out": timed_out,
+            "running": timed_out,
+            "session_ended": session_ended,
+            "cancelled": cancelled,
+            "cwd": cwd,
             "hook": pre_hook,
-            "verification": crate::verification::to_json(&verification),
+            "verification": verification.map(|v| crate::verification::to_json(&v)),
             "output_budget": {
                 "stdout": stdout,
                 "stderr": stderr
@@ -84,3 +188,253 @@ impl Tool for Shell {
         }))
     }
 }
+
+enum Wait {
+    Done {
+        exit_code: i32,
+        cwd: String,
+    },
+    TimedOut,
+    /// The shell itself exited, e.g. on `exit` or under `set -e`.
+    Exited(Option<i32>),
+    Cancelled,
+}
+
+/// A shell on a PTY (bash or sh, PowerShell on Windows). Commands are
+/// written to a script that the shell sources between two marker lines,
+/// the second carrying the exit code and directory; stdout is read from
+/// the PTY between the markers and stderr goes to a file.
+struct ShellSession {
+    child: Box<dyn Child + Send + Sync>,
+    writer: Box<dyn Write + Send>,
+    output: Receiver<Vec<u8>>,
+    _master: Box<dyn MasterPty + Send>,
+    running: Option<RunningCommand>,
+}
+
+struct RunningCommand {
+    cmd: String,
+    nonce: String,
+    script: PathBuf,
+    stderr_path: PathBuf,
+    stderr_read: usize,
+    /// Whether the marker before the script has been seen; anything
+    /// earlier is the terminal echoing the command line.
+    begun: bool,
+    stdout: Vec<u8>,
+    started: Instant,
+}
+
+impl ShellSession {
+    fn start(cwd: &Path, sandbox: &SandboxPolicy) -> Result<Self, String> {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 50,
+                cols: 200,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("failed to open a pty: {e}"))?;
+        let command = |argv: &[&str]| -> Result<CommandBuilder, String> {
+            let argv = sandbox.wrap_argv(cwd, argv.iter().map(|arg| arg.to_string()).collect())?;
+            let mut command = CommandBuilder::new(&argv[0]);
+            command.args(&argv[1..]);
+            command.cwd(cwd);
+            for (key, value) in [
+                ("PS1", ""),
+                ("PS2", ""),
+                ("TERM", "dumb"),
+                ("PAGER", "cat"),
+                ("GIT_PAGER", "cat"),
+            ] {
+                command.env(key, value);
+            }
+            Ok(command)
+        };
+        let platform = Platform::current();
+        let mut spawned = Err("no shell to start".to_string());
+        for argv in platform.session_shells() {
+            spawned = pair
+                .slave
+                .spawn_command(command(argv)?)
+                .map_err(|e| format!("failed to start a shell: {e}"));
+            if spawned.is_ok() {
+                break;
+            }
+        }
+        let child = spawned?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+        let (tx, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut session = Self {
+            child,
+            writer,
+            output,
+            _master: pair.master,
+            running: None,
+        };
+        // Whatever the shell prints before the first marker (a banner, the
+        // echo of this line) is dropped with the setup command, and the
+        // sandbox limits it sets bind every later command.
+        session.send(&platform.session_setup(&sandbox.ulimits()))?;
+        match session.wait(Instant::now() + Duration::from_secs(10), &|| false) {
+            Wait::Done { .. } => {
+                session.finish();
+                Ok(session)
+            }
+            _ => Err("the shell did not start".into()),
+        }
+    }
+
+    fn send(&mut self, cmd: &str) -> Result<(), String> {
+        let nonce = Uuid::new_v4().simple().to_string();
+        let dir = std::env::temp_dir();
+        let platform = Platform::current();
+        let script = dir.join(format!("osmogrep-shell-{nonce}.{}", platform.script_ext()));
+        let stderr_path = dir.join(format!("osmogrep-shell-{nonce}.err"));
+        fs::write(&script, format!("{cmd}\n")).map_err(|e| e.to_string())?;
+        let line = platform.session_line(&script, &stderr_path, MARKER, &nonce);
+        self.writer
+            .write_all(line.as_bytes())
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("failed to write to the shell: {e}"))?;
+        self.running = Some(RunningCommand {
+            cmd: cmd.to_string(),
+            nonce,
+            script,
+            stderr_path,
+            stderr_read: 0,
+            begun: false,
+            stdout: Vec::new(),
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn wait(&mut self, deadline: Instant, is_cancelled: &dyn Fn() -> bool) -> Wait {
+        let Some(running) = self.running.as_mut() else {
+            return Wait::Exited(None);
+        };
+        let start = format!("{MARKER}{}+", running.nonce).into_bytes();
+        let needle = format!("\n{MARKER}{} ", running.nonce).into_bytes();
+        let mut searched = 0;
+        loop {
+            if !running.begun {
+                if let Some(end) = line_after(&running.stdout, &start) {
+                    running.stdout.drain(..end);
+                    running.begun = true;
+                    searched = 0;
+                }
+            }
+            let found = running
+                .begun
+                .then(|| find(&running.stdout[searched..], &needle))
+                .flatten();
+            if let Some(at) = found.map(|i| i + searched) {
+                let rest = &running.stdout[at + needle.len()..];
+                if let Some(end) = rest.iter().position(|b| *b == b'\n') {
+                    let line = String::from_utf8_lossy(&rest[..end]).to_string();
+                    let (code, cwd) = line
+                        .trim_end_matches('\r')
+                        .split_once(' ')
+                        .unwrap_or((&line, ""));
+                    let exit_code = code.parse().unwrap_or(-1);
+                    running.stdout.truncate(at);
+                    return Wait::Done {
+                        exit_code,
+                        cwd: cwd.to_string(),
+                    };
+                }
+            }
+            searched = running.stdout.len().saturating_sub(needle.len() + 1);
+
+            if is_cancelled() {
+                let _ = self.child.kill();
+                return Wait::Cancelled;
+            }
+            if Instant::now() >= deadline {
+                return Wait::TimedOut;
+            }
+            match self.output.recv_timeout(Duration::from_millis(50)) {
+                Ok(bytes) => running.stdout.extend(bytes),
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Ok(Some(status)) = self.child.try_wait() {
+                        while let Ok(bytes) = self.output.try_recv() {
+                            running.stdout.extend(bytes);
+                        }
+                        return Wait::Exited(Some(status.exit_code() as i32));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    // The PTY closes as the shell exits; reap it for the code.
+                    let code = self.child.wait().ok();
+                    return Wait::Exited(code.map(|status| status.exit_code() as i32));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.cleanup();
+        }
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        self.finish();
+    }
+}
+
+impl RunningCommand {
+    /// New stdout, leaving the last `keep` bytes in case they are the
+    /// start of the marker.
+    fn take_stdout(&mut self, keep: usize) -> String {
+        if !self.begun {
+            return String::new();
+        }
+        let cut = self.stdout.len().saturating_sub(keep);
+        let taken: Vec<u8> = self.stdout.drain(..cut).collect();
+        String::from_utf8_lossy(&taken).replace("\r\n", "\n")
+    }
+
+    /// Stderr written since the last call.
+    fn take_stderr(&mut self) -> String {
+        let bytes = fs::read(&self.stderr_path).unwrap_or_default();
+        let text = platform::decode_text(&bytes);
+        let new = text.get(self.stderr_read..).unwrap_or_default().to_string();
+        self.stderr_read = text.len();
+        new
+    }
+
+    fn cleanup(&self) {
+        let _ = fs::remove_file(&self.script);
+        let _ = fs::remove_file(&self.stderr_path);
+    }
+}
+
+/// The end of the line holding `needle`, past its newline.
+fn line_after(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let at = find(haystack, needle)? + needle.len();
+    let end = haystack[at..].iter().position(|b| *b == b'\n')?;
+    Some(at + end + 1)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
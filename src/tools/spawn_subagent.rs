@@ -0,0 +1,57 @@
+use serde_json::{json, Value};
+
+use super::{Tool, ToolResult, ToolSafety};
+
+/// Tokens a sub-agent may spend when the call does not say.
+pub const SUBAGENT_TOKEN_BUDGET: usize = 60_000;
+pub const SUBAGENT_MAX_TOKENS: usize = 400_000;
+/// A sub-agent this many levels below the main agent cannot spawn its own.
+pub const SUBAGENT_MAX_DEPTH: usize = 2;
+
+/// Schema for delegating a task to a child agent. The agent loop runs the
+/// child itself, since it needs the model, the event channel and the
+/// permission prompts; calling the tool directly is an error.
+pub struct SpawnSubagent;
+
+impl Tool for SpawnSubagent {
+    fn name(&self) -> &'static str {
+        "spawn_subagent"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": "spawn_subagent",
+            "description": "Delegate a self-contained task to a sub-agent with a fresh conversation, a restricted tool set and a token budget; returns its final summary. Use it to investigate or change one area without filling this conversation with the details.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "string",
+                        "description": "Everything the sub-agent needs to know; it does not see this conversation"
+                    },
+                    "tools": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Tool names the sub-agent may call. Defaults to the read-only tools"
+                    },
+                    "max_tokens": {
+                        "type": "integer",
+                        "description": "Token budget; the sub-agent is stopped when it is spent (default 60000)"
+                    }
+                },
+                "required": ["task"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn safety(&self) -> ToolSafety {
+        // The sub-agent asks for its own dangerous calls.
+        ToolSafety::Safe
+    }
+
+    fn call(&self, _args: Value) -> ToolResult {
+        Err("spawn_subagent only runs inside an agent run".to_string())
+    }
+}
@@ -13,11 +13,12 @@ impl Tool for Test {
         json!({
             "type": "function",
             "name": "run_tests",
-            "description": "Detect and run repository tests. Optional target narrows scope.",
+            "description": "Detect and run repository tests. Optional target narrows scope; affected_only runs just the workspace packages with uncommitted changes.",
             "parameters": {
                 "type": "object",
                 "properties": {
-                    "target": { "type": "string" }
+                    "target": { "type": "string" },
+                    "affected_only": { "type": "boolean" }
                 },
                 "required": [],
                 "additionalProperties": false
@@ -37,8 +38,17 @@ impl Tool for Test {
         let target = args.get("target").and_then(Value::as_str);
         let root = std::env::current_dir().map_err(|e| e.to_string())?;
 
-        let run = crate::test_harness::run_tests_cancellable(&root, target, is_cancelled)
-            .map_err(|e| format!("test harness error: {}", e))?;
+        let affected_only = args
+            .get("affected_only")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let sandbox = crate::permissions::PermissionPolicy::load(&root)?.sandbox;
+        let run = if affected_only && target.is_none() {
+            crate::test_harness::run_affected_tests_cancellable(&root, Some(&sandbox), is_cancelled)
+        } else {
+            crate::test_harness::run_tests_cancellable(&root, target, Some(&sandbox), is_cancelled)
+        }
+        .map_err(|e| format!("test harness error: {}", e))?;
 
         Ok(json!({
             "framework": run.framework,
@@ -48,6 +58,8 @@ impl Tool for Test {
             "success": run.success,
             "passed": run.passed,
             "failed": run.failed,
+            "failures": run.failures,
+            "slowest": run.slowest,
             "timed_out": run.timed_out,
             "cancelled": run.cancelled,
             "output": run.output,
@@ -1,6 +1,44 @@
+//! web_fetch.rs
+//!
+//! Fetches a URL for the model. HTML pages are reduced to their main
+//! content (the largest `<article>`, else `<main>`, else the body without
+//! navigation, scripts and forms) and converted to markdown. Fetches honor
+//! robots.txt and go through an on-disk cache under `.context/web-cache/`:
+//! a page fetched in the last few minutes is served without a request, and
+//! older ones are revalidated with their ETag or Last-Modified date.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use html5ever::serialize::{SerializeOpts, TraversalScope};
+use html5ever::tendril::TendrilSink;
+use markup5ever_rcdom::{Handle, NodeData, RcDom, SerializableHandle};
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use url::Url;
 
 use super::{Tool, ToolResult, ToolSafety};
+use crate::http_policy;
+
+const CACHE_DIR: &str = ".context/web-cache";
+const PAGE_FRESH_FOR: Duration = Duration::from_secs(10 * 60);
+const ROBOTS_FRESH_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+/// Bytes read from a response body; the rest is dropped.
+const MAX_BODY_BYTES: u64 = 5 * 1024 * 1024;
+const ROBOTS_AGENT: &str = "osmogrep";
+
+/// Elements that never hold page content.
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "form", "button",
+    "input", "select", "nav", "aside", "dialog",
+];
+const DROPPED_ROLES: &[&str] = &["navigation", "complementary", "search", "dialog"];
 
 pub struct WebFetch;
 
@@ -13,12 +51,13 @@ impl Tool for WebFetch {
         json!({
             "type": "function",
             "name": "web_fetch",
-            "description": "Fetch URL content (text/html/json) with output truncation",
+            "description": "Fetch a URL. HTML pages come back as markdown of their main content (raw = true returns the HTML). Honors robots.txt; repeated fetches are served from a local cache.",
             "parameters": {
                 "type": "object",
                 "properties": {
                     "url": { "type": "string" },
-                    "max_chars": { "type": "integer" }
+                    "max_chars": { "type": "integer" },
+                    "raw": { "type": "boolean" }
                 },
                 "required": ["url"],
                 "additionalProperties": false
@@ -41,32 +80,479 @@ impl Tool for WebFetch {
             .map(|n| n as usize)
             .unwrap_or(10_000)
             .clamp(500, 50_000);
+        let raw = args.get("raw").and_then(Value::as_bool).unwrap_or(false);
 
-        let client = reqwest::blocking::Client::builder()
+        let parsed = Url::parse(url).map_err(|e| format!("invalid url {url}: {e}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("unsupported url scheme: {}", parsed.scheme()));
+        }
+        let client = crate::net::client_builder()?
             .timeout(std::time::Duration::from_secs(25))
+            .user_agent(concat!("osmogrep/", env!("CARGO_PKG_VERSION")))
             .build()
             .map_err(|e| e.to_string())?;
+        let cache_dir = std::env::current_dir()
+            .map_err(|e| e.to_string())?
+            .join(CACHE_DIR);
 
-        let resp = client.get(url).send().map_err(|e| e.to_string())?;
-        let status = resp.status().as_u16();
-        let headers = resp.headers().clone();
-        let body = resp.text().map_err(|e| e.to_string())?;
+        if let Some(robots_url) = robots_url(&parsed) {
+            // An unreachable robots.txt does not block the fetch.
+            if let Ok(robots) = fetch_cached(&client, &cache_dir, &robots_url, ROBOTS_FRESH_FOR) {
+                if (200..300).contains(&robots.status)
+                    && !robots_allows(&robots.body, ROBOTS_AGENT, &robots_path(&parsed))
+                {
+                    return Err(format!(
+                        "robots.txt at {robots_url} disallows fetching {url}"
+                    ));
+                }
+            }
+        }
 
-        let text = if body.chars().count() > max_chars {
-            let clipped: String = body.chars().take(max_chars).collect();
+        let page = fetch_cached(&client, &cache_dir, url, PAGE_FRESH_FOR)?;
+        let is_html = page.content_type.contains("html")
+            || (page.content_type.is_empty()
+                && page
+                    .body
+                    .trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("<!doctype html"));
+        let (title, text) = if is_html && !raw {
+            readable_markdown(&page.body)
+        } else {
+            (None, page.body.clone())
+        };
+
+        let truncated = page.truncated || text.chars().count() > max_chars;
+        let text = if text.chars().count() > max_chars {
+            let clipped: String = text.chars().take(max_chars).collect();
             format!("{}\n...truncated...", clipped)
         } else {
-            body
+            text
         };
 
         Ok(json!({
             "url": url,
-            "status": status,
-            "content_type": headers
-                .get("content-type")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or(""),
+            "status": page.status,
+            "content_type": page.content_type,
+            "title": title,
+            "format": if is_html && !raw { "markdown" } else { "text" },
             "text": text,
+            "truncated": truncated,
+            "cached": page.from_cache,
         }))
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedPage {
+    url: String,
+    status: u16,
+    content_type: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix seconds of the last fetch or successful revalidation.
+    fetched_at: i64,
+    body: String,
+    /// Whether the body was cut at `MAX_BODY_BYTES`.
+    truncated: bool,
+    #[serde(skip)]
+    from_cache: bool,
+}
+
+fn cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(format!(
+        "{}.json",
+        hex::encode(Sha256::digest(url.as_bytes()))
+    ))
+}
+
+fn load_cached(cache_dir: &Path, url: &str) -> Option<CachedPage> {
+    let raw = fs::read_to_string(cache_path(cache_dir, url)).ok()?;
+    serde_json::from_str::<CachedPage>(&raw)
+        .ok()
+        .filter(|page| page.url == url)
+}
+
+fn store_cached(cache_dir: &Path, page: &CachedPage) {
+    if fs::create_dir_all(cache_dir).is_err() {
+        return;
+    }
+    if let Ok(raw) = serde_json::to_string(page) {
+        let _ = fs::write(cache_path(cache_dir, &page.url), raw);
+    }
+}
+
+/// `url` from the cache when fetched within `fresh_for`, otherwise from the
+/// network, revalidating a cached copy when it carries a validator.
+fn fetch_cached(
+    client: &Client,
+    cache_dir: &Path,
+    url: &str,
+    fresh_for: Duration,
+) -> Result<CachedPage, String> {
+    let now = chrono::Utc::now().timestamp();
+    let cached = load_cached(cache_dir, url);
+    if let Some(page) = &cached {
+        if now.saturating_sub(page.fetched_at) < fresh_for.as_secs() as i64 {
+            return Ok(CachedPage {
+                from_cache: true,
+                ..page.clone()
+            });
+        }
+    }
+
+    let mut request = client.get(url);
+    if let Some(page) = &cached {
+        if let Some(etag) = &page.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &page.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+    let resp = http_policy::send(request)?;
+    let status = resp.status().as_u16();
+    if let (304, Some(page)) = (status, cached) {
+        let page = CachedPage {
+            fetched_at: now,
+            ..page
+        };
+        store_cached(cache_dir, &page);
+        return Ok(CachedPage {
+            from_cache: true,
+            ..page
+        });
+    }
+
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_type = header(CONTENT_TYPE).unwrap_or_default();
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let mut bytes = Vec::new();
+    resp.take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    let truncated = bytes.len() as u64 > MAX_BODY_BYTES;
+    bytes.truncate(MAX_BODY_BYTES as usize);
+
+    let page = CachedPage {
+        url: url.to_string(),
+        status,
+        content_type,
+        etag,
+        last_modified,
+        fetched_at: now,
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+        truncated,
+        from_cache: false,
+    };
+    if status < 500 {
+        store_cached(cache_dir, &page);
+    }
+    Ok(page)
+}
+
+fn robots_url(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+    Some(format!("{}://{host}{port}/robots.txt", url.scheme()))
+}
+
+fn robots_path(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    }
+}
+
+#[derive(Default)]
+struct RobotsGroup {
+    agents: Vec<String>,
+    /// `(allow, path pattern)` in file order.
+    rules: Vec<(bool, String)>,
+}
+
+/// Whether `robots` lets `agent` fetch `path`: the group naming the agent,
+/// else the `*` group, with the longest matching rule winning and `Allow`
+/// winning ties.
+fn robots_allows(robots: &str, agent: &str, path: &str) -> bool {
+    let agent = agent.to_ascii_lowercase();
+    let mut groups: Vec<RobotsGroup> = Vec::new();
+    let mut in_agents = false;
+    for line in robots.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "user-agent" => {
+                if !in_agents {
+                    groups.push(RobotsGroup::default());
+                }
+                in_agents = true;
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+            }
+            rule @ ("allow" | "disallow") => {
+                in_agents = false;
+                if let Some(group) = groups.last_mut() {
+                    if !value.is_empty() {
+                        group.rules.push((rule == "allow", value.to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let named = groups.iter().find(|group| {
+        group
+            .agents
+            .iter()
+            .any(|a| a != "*" && agent.contains(a.as_str()))
+    });
+    let Some(group) = named.or_else(|| {
+        groups
+            .iter()
+            .find(|group| group.agents.iter().any(|a| a == "*"))
+    }) else {
+        return true;
+    };
+
+    let mut best: Option<(usize, bool)> = None;
+    for (allow, pattern) in &group.rules {
+        if !robots_pattern_matches(pattern, path) {
+            continue;
+        }
+        let better = match best {
+            None => true,
+            Some((len, best_allow)) => {
+                pattern.len() > len || (pattern.len() == len && *allow && !best_allow)
+            }
+        };
+        if better {
+            best = Some((pattern.len(), *allow));
+        }
+    }
+    !matches!(best, Some((_, false)))
+}
+
+/// A robots.txt path pattern: a prefix, with `*` matching anything and a
+/// trailing `$` anchoring the end.
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(rest) => (rest, true),
+        None => (pattern, false),
+    };
+    let body = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    let re = format!("^{body}{}", if anchored { "$" } else { "" });
+    Regex::new(&re).is_ok_and(|re| re.is_match(path))
+}
+
+fn element_name(node: &Handle) -> Option<String> {
+    match &node.data {
+        NodeData::Element { name, .. } => Some(name.local.to_string()),
+        _ => None,
+    }
+}
+
+fn attribute(node: &Handle, key: &str) -> Option<String> {
+    match &node.data {
+        NodeData::Element { attrs, .. } => attrs
+            .borrow()
+            .iter()
+            .find(|attr| &*attr.name.local == key)
+            .map(|attr| attr.value.to_string()),
+        _ => None,
+    }
+}
+
+fn descendants(node: &Handle, out: &mut Vec<Handle>) {
+    for child in node.children.borrow().iter() {
+        out.push(child.clone());
+        descendants(child, out);
+    }
+}
+
+fn text_of(node: &Handle) -> String {
+    let mut nodes = Vec::new();
+    descendants(node, &mut nodes);
+    nodes
+        .iter()
+        .filter_map(|n| match &n.data {
+            NodeData::Text { contents } => Some(contents.borrow().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_boilerplate(node: &Handle, landmarks: bool) -> bool {
+    let Some(name) = element_name(node) else {
+        return matches!(node.data, NodeData::Comment { .. });
+    };
+    let role = attribute(node, "role").unwrap_or_default();
+    DROPPED_TAGS.contains(&name.as_str())
+        || DROPPED_ROLES.contains(&role.as_str())
+        || attribute(node, "hidden").is_some()
+        || attribute(node, "aria-hidden").as_deref() == Some("true")
+        || (landmarks
+            && (matches!(name.as_str(), "header" | "footer")
+                || matches!(role.as_str(), "banner" | "contentinfo")))
+}
+
+/// Drops boilerplate below `node`; `landmarks` also drops page headers and
+/// footers.
+fn prune(node: &Handle, landmarks: bool) {
+    node.children
+        .borrow_mut()
+        .retain(|child| !is_boilerplate(child, landmarks));
+    for child in node.children.borrow().iter() {
+        prune(child, landmarks);
+    }
+}
+
+/// The page title and its main content as markdown.
+fn readable_markdown(html: &str) -> (Option<String>, String) {
+    let dom = html5ever::parse_document(RcDom::default(), Default::default()).one(html);
+    let mut nodes = Vec::new();
+    descendants(&dom.document, &mut nodes);
+    let named = |tag: &str| {
+        nodes
+            .iter()
+            .filter(|n| element_name(n).as_deref() == Some(tag))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let title = named("title")
+        .first()
+        .map(|t| text_of(t).split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|t| !t.is_empty());
+
+    let article = named("article")
+        .into_iter()
+        .max_by_key(|a| text_of(a).trim().len());
+    let main = article
+        .or_else(|| named("main").into_iter().next())
+        .or_else(|| {
+            nodes
+                .iter()
+                .find(|n| attribute(n, "role").as_deref() == Some("main"))
+                .cloned()
+        });
+    let (root, landmarks) = match main {
+        Some(main) => (main, false),
+        None => (
+            named("body")
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| dom.document.clone()),
+            true,
+        ),
+    };
+    prune(&root, landmarks);
+
+    let mut serialized = Vec::new();
+    let opts = SerializeOpts {
+        traversal_scope: if element_name(&root).is_some() {
+            TraversalScope::IncludeNode
+        } else {
+            TraversalScope::ChildrenOnly(None)
+        },
+        ..Default::default()
+    };
+    if html5ever::serialize(&mut serialized, &SerializableHandle::from(root), opts).is_err() {
+        return (title, String::new());
+    }
+    let markdown = html2md::parse_html(&String::from_utf8_lossy(&serialized));
+    (title, markdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_is_reduced_to_the_main_content_as_markdown() {
+        let html = r#"<!doctype html><html><head><title> Widgets
+            guide </title><script>track()</script></head><body>
+            <header><a href="/">Home</a></header>
+            <nav><ul><li><a href="/a">A</a></li></ul></nav>
+            <article><h1>Widgets</h1><p>Build a <code>Widget</code> first.</p>
+            <aside>Related posts</aside><pre><code>let w = Widget::new();</code></pre>
+            <ul><li>fast</li><li>small</li></ul></article>
+            <footer>Copyright</footer></body></html>"#;
+        let (title, text) = readable_markdown(html);
+        assert_eq!(title.as_deref(), Some("Widgets guide"));
+        assert!(text.starts_with("Widgets\n=========="), "{text}");
+        assert!(text.contains("Build a `Widget` first."));
+        assert!(text.contains("let w = Widget::new();"));
+        assert!(text.contains("* fast\n* small"));
+        for boilerplate in ["Home", "Related", "Copyright", "track()", "/a"] {
+            assert!(!text.contains(boilerplate), "{boilerplate} in {text}");
+        }
+
+        let (_, text) = readable_markdown(
+            "<body><header>Site</header><div><p>Body text</p></div><footer>Foot</footer></body>",
+        );
+        assert_eq!(text, "Body text");
+    }
+
+    #[test]
+    fn robots_rules_pick_the_agent_group_and_longest_match() {
+        let robots =
+            "User-agent: *\nDisallow: /private\nAllow: /private/docs\nDisallow: /*.pdf$\n\n\
+                      User-agent: BadBot\nUser-agent: other\nDisallow: /\n";
+        assert!(robots_allows(robots, "osmogrep", "/docs/intro"));
+        assert!(!robots_allows(robots, "osmogrep", "/private/keys"));
+        assert!(robots_allows(robots, "osmogrep", "/private/docs/a"));
+        assert!(!robots_allows(robots, "osmogrep", "/files/a.pdf"));
+        assert!(robots_allows(robots, "osmogrep", "/files/a.pdf?x=1"));
+        assert!(!robots_allows(robots, "badbot", "/docs"));
+        assert!(robots_allows("", "osmogrep", "/anything"));
+
+        let url = Url::parse("https://docs.rs:8443/a/b?c=d").unwrap();
+        assert_eq!(
+            robots_url(&url).as_deref(),
+            Some("https://docs.rs:8443/robots.txt")
+        );
+        assert_eq!(robots_path(&url), "/a/b?c=d");
+    }
+
+    #[test]
+    fn cached_pages_round_trip_by_url() {
+        let dir = std::env::temp_dir().join(format!("osmogrep-web-cache-{}", uuid::Uuid::new_v4()));
+        let page = CachedPage {
+            url: "https://example.com/a".into(),
+            status: 200,
+            content_type: "text/html".into(),
+            etag: Some("\"v1\"".into()),
+            last_modified: None,
+            fetched_at: chrono::Utc::now().timestamp(),
+            body: "<p>hi</p>".into(),
+            truncated: false,
+            from_cache: false,
+        };
+        store_cached(&dir, &page);
+        let loaded = load_cached(&dir, "https://example.com/a").unwrap();
+        assert_eq!(loaded.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(loaded.body, "<p>hi</p>");
+        assert!(load_cached(&dir, "https://example.com/b").is_none());
+
+        let client = Client::new();
+        let fresh = fetch_cached(&client, &dir, "https://example.com/a", PAGE_FRESH_FOR).unwrap();
+        assert!(fresh.from_cache);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
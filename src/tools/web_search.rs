@@ -1,7 +1,95 @@
+//! web_search.rs
+//!
+//! Web search through the provider set in the `[web_search]` section of the
+//! config file:
+//!
+//! ```toml
+//! [web_search]
+//! provider = "brave"             # "brave", "searxng" or "duckduckgo" (default)
+//! api_key_env = "BRAVE_API_KEY"  # where the Brave key is read from
+//! searxng_url = "https://searx.example.org"
+//! fallback = true                # retry on DuckDuckGo's HTML page on failure
+//! ```
+//!
+//! Every provider's results come back as `{title, url, snippet}`.
+
 use regex::Regex;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use url::Url;
 
 use super::{Tool, ToolResult, ToolSafety};
+use crate::http_policy;
+
+const DEFAULT_BRAVE_KEY_ENV: &str = "BRAVE_API_KEY";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchProvider {
+    Brave,
+    Searxng,
+    #[default]
+    Duckduckgo,
+}
+
+impl SearchProvider {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Brave => "brave",
+            Self::Searxng => "searxng",
+            Self::Duckduckgo => "duckduckgo",
+        }
+    }
+}
+
+/// `[web_search]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSearchConfig {
+    pub provider: SearchProvider,
+    /// Environment variable holding the Brave Search API key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Base URL of a SearXNG instance with the JSON format enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub searxng_url: Option<String>,
+    /// Search DuckDuckGo's HTML page when the provider fails.
+    pub fallback: bool,
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        Self {
+            provider: SearchProvider::default(),
+            api_key_env: None,
+            searxng_url: None,
+            fallback: true,
+        }
+    }
+}
+
+impl WebSearchConfig {
+    /// Settings the configured provider cannot work without.
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.provider, self.searxng_url.as_deref()) {
+            (SearchProvider::Searxng, None) => {
+                Err("web_search.searxng_url is required for provider = \"searxng\"".into())
+            }
+            (_, Some(raw)) => Url::parse(raw)
+                .map(|_| ())
+                .map_err(|e| format!("web_search.searxng_url: {e}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SearchHit {
+    title: String,
+    url: String,
+    snippet: String,
+}
 
 pub struct WebSearch;
 
@@ -14,7 +102,7 @@ impl Tool for WebSearch {
         json!({
             "type": "function",
             "name": "web_search",
-            "description": "Search the web and return top result links",
+            "description": "Search the web and return the top results as title, url and snippet",
             "parameters": {
                 "type": "object",
                 "properties": {
@@ -41,46 +129,213 @@ impl Tool for WebSearch {
             .and_then(Value::as_u64)
             .map(|n| n as usize)
             .unwrap_or(8)
-            .min(20);
+            .clamp(1, 20);
 
-        let url = format!("https://duckduckgo.com/html/?q={}", encode_query(query));
-        let client = reqwest::blocking::Client::builder()
+        let config = crate::agent::web_search_config();
+        config.validate()?;
+        let client = crate::net::client_builder()?
             .timeout(std::time::Duration::from_secs(20))
+            .user_agent(concat!("osmogrep/", env!("CARGO_PKG_VERSION")))
             .build()
             .map_err(|e| e.to_string())?;
 
-        let html = client
-            .get(url)
-            .header("User-Agent", "osmogrep/0.3")
-            .send()
-            .map_err(|e| e.to_string())?
-            .text()
-            .map_err(|e| e.to_string())?;
-
-        let re = Regex::new(r#"<a[^>]*class=\"result__a\"[^>]*href=\"([^\"]+)\"[^>]*>(.*?)</a>"#)
-            .map_err(|e| e.to_string())?;
-
-        let mut results = Vec::new();
-        for cap in re.captures_iter(&html).take(limit) {
-            let href = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-            let title = cap.get(2).map(|m| m.as_str()).unwrap_or("");
-            results.push(json!({
-                "title": strip_tags(title),
-                "url": href,
-            }));
-        }
+        let mut provider = config.provider;
+        let mut fallback_reason = None;
+        let hits = match search(&client, &config, provider, query, limit) {
+            Ok(hits) => hits,
+            Err(e) if config.fallback && provider != SearchProvider::Duckduckgo => {
+                fallback_reason = Some(format!("{} failed: {e}", provider.label()));
+                provider = SearchProvider::Duckduckgo;
+                search(&client, &config, provider, query, limit)?
+            }
+            Err(e) => return Err(format!("{} search failed: {e}", provider.label())),
+        };
 
-        Ok(json!({
+        let results: Vec<Value> = hits
+            .into_iter()
+            .take(limit)
+            .map(|hit| json!({ "title": hit.title, "url": hit.url, "snippet": hit.snippet }))
+            .collect();
+        let mut out = json!({
             "query": query,
+            "provider": provider.label(),
             "count": results.len(),
             "results": results,
-        }))
+        });
+        if let Some(reason) = fallback_reason {
+            out["fallback_reason"] = json!(reason);
+        }
+        Ok(out)
+    }
+}
+
+fn search(
+    client: &Client,
+    config: &WebSearchConfig,
+    provider: SearchProvider,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    match provider {
+        SearchProvider::Brave => {
+            let key_env = config
+                .api_key_env
+                .as_deref()
+                .unwrap_or(DEFAULT_BRAVE_KEY_ENV);
+            let key = std::env::var(key_env)
+                .ok()
+                .filter(|k| !k.trim().is_empty())
+                .ok_or_else(|| format!("no API key in ${key_env}"))?;
+            let url = format!(
+                "https://api.search.brave.com/res/v1/web/search?q={}&count={limit}",
+                encode_query(query)
+            );
+            let request = client
+                .get(url)
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", key);
+            Ok(parse_brave(&get_json(request)?))
+        }
+        SearchProvider::Searxng => {
+            let base = config
+                .searxng_url
+                .as_deref()
+                .ok_or("web_search.searxng_url is not set")?;
+            let url = format!(
+                "{}/search?q={}&format=json",
+                base.trim_end_matches('/'),
+                encode_query(query)
+            );
+            Ok(parse_searxng(&get_json(client.get(url))?))
+        }
+        SearchProvider::Duckduckgo => {
+            let url = format!("https://duckduckgo.com/html/?q={}", encode_query(query));
+            let html = http_policy::send(client.get(url))?
+                .text()
+                .map_err(|e| e.to_string())?;
+            Ok(parse_duckduckgo(&html))
+        }
+    }
+}
+
+fn get_json(request: reqwest::blocking::RequestBuilder) -> Result<Value, String> {
+    let resp = http_policy::send(request)?;
+    let status = resp.status();
+    let body = resp.text().map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        let detail: String = body.chars().take(200).collect();
+        return Err(format!("HTTP {}: {}", status.as_u16(), detail.trim()));
+    }
+    serde_json::from_str(&body).map_err(|e| format!("invalid JSON response: {e}"))
+}
+
+fn hit(title: &str, url: &str, snippet: &str) -> Option<SearchHit> {
+    let url = url.trim();
+    if url.is_empty() {
+        return None;
     }
+    Some(SearchHit {
+        title: clean_text(title),
+        url: url.to_string(),
+        snippet: clean_text(snippet),
+    })
+}
+
+fn parse_brave(body: &Value) -> Vec<SearchHit> {
+    body.pointer("/web/results")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|r| {
+            let field = |key| r.get(key).and_then(Value::as_str).unwrap_or("");
+            hit(field("title"), field("url"), field("description"))
+        })
+        .collect()
+}
+
+fn parse_searxng(body: &Value) -> Vec<SearchHit> {
+    body.get("results")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|r| {
+            let field = |key| r.get(key).and_then(Value::as_str).unwrap_or("");
+            hit(field("title"), field("url"), field("content"))
+        })
+        .collect()
+}
+
+fn parse_duckduckgo(html: &str) -> Vec<SearchHit> {
+    let link = Regex::new(r#"<a[^>]*class="result__a"[^>]*href="([^"]+)"[^>]*>(.*?)</a>"#)
+        .expect("valid regex");
+    let snippet = Regex::new(r#"(?s)class="result__snippet"[^>]*>(.*?)</a>"#).expect("valid regex");
+
+    let links: Vec<_> = link.captures_iter(html).collect();
+    links
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, cap)| {
+            // The snippet sits between this result's link and the next one.
+            let end = links
+                .get(idx + 1)
+                .map_or(html.len(), |next| next.get(0).unwrap().start());
+            let rest = &html[cap.get(0).unwrap().end()..end];
+            let text = snippet
+                .captures(rest)
+                .and_then(|s| s.get(1))
+                .map_or("", |m| m.as_str());
+            hit(&cap[2], &duckduckgo_target(&decode_entities(&cap[1])), text)
+        })
+        .collect()
+}
+
+/// DuckDuckGo links go through `/l/?uddg=<target>`; returns the target.
+fn duckduckgo_target(href: &str) -> String {
+    let absolute = if href.starts_with("//") {
+        format!("https:{href}")
+    } else {
+        href.to_string()
+    };
+    Url::parse(&absolute)
+        .ok()
+        .filter(|u| u.path() == "/l/")
+        .and_then(|u| {
+            u.query_pairs()
+                .find(|(k, _)| k == "uddg")
+                .map(|(_, v)| v.into_owned())
+        })
+        .unwrap_or(absolute)
 }
 
-fn strip_tags(s: &str) -> String {
+/// Tags stripped, entities decoded and whitespace collapsed.
+fn clean_text(s: &str) -> String {
     let re = Regex::new(r"<[^>]+>").unwrap();
-    re.replace_all(s, "").to_string()
+    decode_entities(&re.replace_all(s, ""))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn decode_entities(s: &str) -> String {
+    let re = Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-z]+);").unwrap();
+    re.replace_all(s, |cap: &regex::Captures| {
+        let entity = &cap[1];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|n| n.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        decoded.map_or_else(|| cap[0].to_string(), String::from)
+    })
+    .into_owned()
 }
 
 fn encode_query(s: &str) -> String {
@@ -96,3 +351,62 @@ fn encode_query(s: &str) -> String {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers_normalize_to_title_url_snippet() {
+        let brave = json!({ "web": { "results": [
+            { "title": "Rust &amp; you", "url": "https://rust-lang.org/",
+              "description": "A <strong>fast</strong> language" },
+            { "title": "no url" }
+        ]}});
+        assert_eq!(
+            parse_brave(&brave),
+            [SearchHit {
+                title: "Rust & you".into(),
+                url: "https://rust-lang.org/".into(),
+                snippet: "A fast language".into(),
+            }]
+        );
+
+        let searxng = json!({ "results": [
+            { "title": "Docs", "url": "https://docs.rs/", "content": "Crate docs" }
+        ]});
+        assert_eq!(parse_searxng(&searxng)[0].snippet, "Crate docs");
+
+        let html = r#"
+            <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fserde.rs%2F&amp;rut=abc">Serde <b>docs</b></a>
+            <a class="result__snippet" href="x">Serialization
+              framework &#x27;serde&#39;</a>
+            <a rel="nofollow" class="result__a" href="https://example.com/">Example</a>
+        "#;
+        let hits = parse_duckduckgo(html);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].title, "Serde docs");
+        assert_eq!(hits[0].url, "https://serde.rs/");
+        assert_eq!(hits[0].snippet, "Serialization framework 'serde'");
+        assert_eq!(hits[1].url, "https://example.com/");
+        assert_eq!(hits[1].snippet, "");
+    }
+
+    #[test]
+    fn config_defaults_to_duckduckgo_and_requires_a_searxng_url() {
+        let config: WebSearchConfig = toml::from_str("").unwrap();
+        assert_eq!(config.provider, SearchProvider::Duckduckgo);
+        assert!(config.fallback);
+
+        let config: WebSearchConfig = toml::from_str("provider = \"searxng\"").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "web_search.searxng_url is required for provider = \"searxng\""
+        );
+        let config: WebSearchConfig =
+            toml::from_str("provider = \"searxng\"\nsearxng_url = \"https://searx.local\"")
+                .unwrap();
+        assert!(config.validate().is_ok());
+        assert!(toml::from_str::<WebSearchConfig>("provider = \"bing\"").is_err());
+    }
+}
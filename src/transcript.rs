@@ -0,0 +1,284 @@
+//! transcript.rs
+//!
+//! Auditable record of one agent run: every tool call with its arguments,
+//! result summary and the diffs it produced, token usage and the final
+//! answer. The run ledger collects it and writes `.context/runs/<time>.json`
+//! plus a rendered `.md` next to it when the run ends, however it ends.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+
+pub const RUNS_DIR: &str = ".context/runs";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Transcript {
+    pub run_id: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    /// `completed`, `failed`, or `incomplete` when the run stopped without
+    /// an answer or a recorded error.
+    pub status: String,
+    pub prompt: String,
+    pub provider: String,
+    pub model: String,
+    pub permission_profile: String,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub usage: TokenUsage,
+    pub final_answer: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRecord {
+    pub iteration: usize,
+    pub tool: String,
+    pub args: Value,
+    /// `ok` or `error`.
+    pub status: &'static str,
+    pub summary: String,
+    pub duration_ms: u128,
+    pub diffs: Vec<FileDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+    /// Unified diff with three lines of context.
+    pub patch: String,
+}
+
+/// Tokens as reported by the provider; zero when it sends no usage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub model_calls: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl FileDiff {
+    pub fn new(path: &str, before: &str, after: &str) -> Self {
+        let diff = TextDiff::from_lines(before, after);
+        let (mut added, mut removed) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => added += 1,
+                ChangeTag::Delete => removed += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        let patch = diff
+            .unified_diff()
+            .header(&format!("a/{path}"), &format!("b/{path}"))
+            .to_string();
+        Self {
+            path: path.to_string(),
+            added,
+            removed,
+            patch,
+        }
+    }
+}
+
+impl TokenUsage {
+    /// Adds one model response's `usage` object.
+    pub fn add(&mut self, usage: Option<&Value>) {
+        self.model_calls += 1;
+        if let Some((input, output)) = reported_tokens(usage) {
+            self.input_tokens += input;
+            self.output_tokens += output;
+        }
+    }
+}
+
+/// Input and output tokens of a response's `usage` object; understands both
+/// the Responses (`input_tokens`) and Chat Completions (`prompt_tokens`)
+/// field names.
+pub fn reported_tokens(usage: Option<&Value>) -> Option<(u64, u64)> {
+    let usage = usage?;
+    let field = |names: [&str; 2]| {
+        names
+            .iter()
+            .find_map(|name| usage.get(*name).and_then(Value::as_u64))
+    };
+    Some((
+        field(["input_tokens", "prompt_tokens"])?,
+        field(["output_tokens", "completion_tokens"])?,
+    ))
+}
+
+impl Transcript {
+    /// Writes the JSON and markdown files and returns the JSON path.
+    pub fn write(&self, repo_root: &Path) -> Result<PathBuf, String> {
+        let dir = repo_root.join(RUNS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let stamp = file_stamp(&self.started_at);
+        let mut json_path = dir.join(format!("{stamp}.json"));
+        if json_path.exists() {
+            let short = self.run_id.get(..8).unwrap_or(&self.run_id);
+            json_path = dir.join(format!("{stamp}-{short}.json"));
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&json_path, json).map_err(|e| format!("{}: {e}", json_path.display()))?;
+        let md_path = json_path.with_extension("md");
+        fs::write(&md_path, self.render_markdown())
+            .map_err(|e| format!("{}: {e}", md_path.display()))?;
+        Ok(json_path)
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let mut out = format!("# Agent run {}\n\n", self.run_id);
+        out.push_str(&format!("- Status: {}\n", self.status));
+        out.push_str(&format!("- Started: {}\n", self.started_at));
+        if let Some(finished) = &self.finished_at {
+            out.push_str(&format!("- Finished: {finished}\n"));
+        }
+        out.push_str(&format!("- Model: {}/{}\n", self.provider, self.model));
+        out.push_str(&format!(
+            "- Permission profile: {}\n",
+            self.permission_profile
+        ));
+        out.push_str(&format!(
+            "- Tokens: {} in / {} out over {} model call(s)\n",
+            self.usage.input_tokens, self.usage.output_tokens, self.usage.model_calls
+        ));
+        let files: usize = self.tool_calls.iter().map(|c| c.diffs.len()).sum();
+        out.push_str(&format!(
+            "- Tool calls: {} ({files} file change(s))\n",
+            self.tool_calls.len()
+        ));
+
+        out.push_str("\n## Prompt\n\n");
+        out.push_str(&fenced("text", &self.prompt));
+
+        if !self.tool_calls.is_empty() {
+            out.push_str("\n## Tool calls\n");
+        }
+        for (i, call) in self.tool_calls.iter().enumerate() {
+            out.push_str(&format!(
+                "\n### {}. `{}` ({}, {} ms, turn {})\n\n",
+                i + 1,
+                call.tool,
+                call.status,
+                call.duration_ms,
+                call.iteration
+            ));
+            let args = serde_json::to_string_pretty(&call.args).unwrap_or_default();
+            out.push_str(&fenced("json", &args));
+            out.push_str(&format!("\n{}\n", call.summary));
+            for diff in &call.diffs {
+                out.push_str(&format!(
+                    "\n`{}` (+{} -{})\n\n",
+                    diff.path, diff.added, diff.removed
+                ));
+                out.push_str(&fenced("diff", &diff.patch));
+            }
+        }
+
+        if let Some(answer) = &self.final_answer {
+            out.push_str("\n## Final answer\n\n");
+            out.push_str(answer.trim_end());
+            out.push('\n');
+        }
+        if let Some(error) = &self.error {
+            out.push_str(&format!("\n## Error\n\n{error}\n"));
+        }
+        out
+    }
+}
+
+// A fence longer than any backtick run inside `text`.
+fn fenced(lang: &str, text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in text.chars() {
+        run = if ch == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{lang}\n{}\n{fence}\n", text.trim_end_matches('\n'))
+}
+
+// `2026-10-16T09:30:05.123+00:00` -> `20261016T093005Z`
+fn file_stamp(rfc3339: &str) -> String {
+    let digits: String = rfc3339
+        .chars()
+        .take(19)
+        .filter(|c| c.is_ascii_digit() || *c == 'T')
+        .collect();
+    format!("{digits}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn writes_json_and_markdown_with_calls_diffs_and_usage() {
+        let root = std::env::temp_dir().join(format!("osmogrep-transcript-{}", Uuid::new_v4()));
+        let mut usage = TokenUsage::default();
+        usage.add(Some(&json!({ "input_tokens": 1200, "output_tokens": 80 })));
+        usage.add(Some(
+            &json!({ "prompt_tokens": 300, "completion_tokens": 20 }),
+        ));
+        usage.add(None);
+        assert_eq!(
+            usage,
+            TokenUsage {
+                model_calls: 3,
+                input_tokens: 1500,
+                output_tokens: 100
+            }
+        );
+
+        let transcript = Transcript {
+            run_id: "0123456789ab".into(),
+            started_at: "2026-10-16T09:30:05.123+00:00".into(),
+            status: "completed".into(),
+            prompt: "rename the flag".into(),
+            provider: "openai".into(),
+            model: "gpt-5".into(),
+            permission_profile: "workspace-auto".into(),
+            tool_calls: vec![ToolCallRecord {
+                iteration: 1,
+                tool: "edit_file".into(),
+                args: json!({ "path": "src/main.rs", "old": "```", "new": "x" }),
+                status: "ok",
+                summary: "edited src/main.rs".into(),
+                duration_ms: 4,
+                diffs: vec![FileDiff::new("src/main.rs", "a\nb\n", "a\nc\n")],
+            }],
+            usage,
+            final_answer: Some("Renamed.".into()),
+            ..Transcript::default()
+        };
+        let path = transcript.write(&root).unwrap();
+        assert!(path.ends_with(".context/runs/20261016T093005Z.json"));
+        let again = transcript.write(&root).unwrap();
+        assert!(again.ends_with(".context/runs/20261016T093005Z-01234567.json"));
+
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["tool_calls"][0]["args"]["path"], "src/main.rs");
+        assert_eq!(json["tool_calls"][0]["diffs"][0]["added"], 1);
+        assert_eq!(json["usage"]["input_tokens"], 1500);
+
+        let md = fs::read_to_string(path.with_extension("md")).unwrap();
+        assert!(md.contains("- Tokens: 1500 in / 100 out over 3 model call(s)"));
+        assert!(md.contains("### 1. `edit_file` (ok, 4 ms, turn 1)"));
+        assert!(md.contains("````json\n"));
+        assert!(md.contains("--- a/src/main.rs\n+++ b/src/main.rs\n"));
+        assert!(md.contains("-b\n+c\n"));
+        assert!(md.ends_with("## Final answer\n\nRenamed.\n"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -4,18 +4,44 @@ use std::env;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
 
 use chrono::{DateTime, Duration, Utc};
 use clap::Args;
+use regex::Regex;
 use reqwest::blocking::Client;
 use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT};
 use serde::{Deserialize, Serialize};
 
+use crate::codeowners::CodeOwners;
+use crate::http_policy;
+use crate::pull_request::PrDraft;
+use crate::ui::chart::{self, sparkline};
+
 #[derive(Args, Debug, Clone)]
 pub struct TriageArgs {
-    #[arg(long, help = "GitHub repository in owner/name form")]
+    #[arg(
+        long,
+        help = "Repository in owner/name form (a GitLab project path may have subgroups)"
+    )]
     pub repo: String,
 
+    #[arg(
+        long,
+        default_value = "github",
+        help = "Forge: github | gitlab | gitea"
+    )]
+    pub forge: String,
+
+    #[arg(
+        long,
+        help = "Base URL of a self-hosted forge, e.g. https://gitlab.example.com (required for gitea)"
+    )]
+    pub forge_url: Option<String>,
+
     #[arg(long, default_value = "open", help = "Item state: open | closed | all")]
     pub state: String,
 
@@ -36,6 +62,13 @@ pub struct TriageArgs {
     )]
     pub deep_review_all: bool,
 
+    #[arg(
+        long,
+        default_value_t = 4,
+        help = "Parallel deep-review fetches (1-16)"
+    )]
+    pub deep_review_concurrency: usize,
+
     #[arg(
         long,
         default_value_t = 0.62,
@@ -63,23 +96,38 @@ pub struct TriageArgs {
     #[arg(long, help = "Path to incremental triage state JSON")]
     pub state_file: Option<PathBuf>,
 
+    #[arg(
+        long,
+        help = "Run offline from an exported JSON snapshot (osmogrep or gh CLI format)"
+    )]
+    pub from_snapshot: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write fetched PRs/issues/deep signals to a JSON snapshot"
+    )]
+    pub export_snapshot: Option<PathBuf>,
+
     #[arg(long, help = "Path to a vision document for scope alignment")]
     pub vision: Option<PathBuf>,
 
-    #[arg(long, help = "GitHub token (or set GITHUB_TOKEN)")]
+    #[arg(
+        long,
+        help = "API token (or set GITHUB_TOKEN, GITLAB_TOKEN or GITEA_TOKEN)"
+    )]
     pub token: Option<String>,
 
     #[arg(
         long,
         default_value_t = false,
-        help = "Apply labels/comments to GitHub issues/PRs"
+        help = "Apply labels/comments to issues and PRs on the forge"
     )]
     pub apply_actions: bool,
 
     #[arg(
         long,
         default_value_t = false,
-        help = "Include duplicate/vision comments in action plan"
+        help = "Include duplicate/vision/linked-PR comments in action plan"
     )]
     pub comment_actions: bool,
 
@@ -107,11 +155,52 @@ pub struct TriageArgs {
     )]
     pub label_reject: String,
 
-    #[arg(long, help = "Write full report JSON to this file")]
+    #[arg(
+        long,
+        default_value = "triage:stale",
+        help = "Label for items with no recent activity"
+    )]
+    pub label_stale: String,
+
+    #[arg(
+        long,
+        default_value_t = 60,
+        help = "Days without activity before an open item is stale (0 disables)"
+    )]
+    pub stale_days: i64,
+
+    #[arg(
+        long,
+        help = "Local clone for CODEOWNERS/blame reviewer suggestions (default: the current directory when its origin is --repo)"
+    )]
+    pub checkout: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Plan request_review actions for suggested reviewers"
+    )]
+    pub request_reviews: bool,
+
+    #[arg(long, help = "Write the report to this file, in --format")]
     pub out: Option<PathBuf>,
 
+    #[arg(
+        long,
+        default_value = "json",
+        help = "Report format: json | md | html (md/html render a digest)"
+    )]
+    pub format: String,
+
     #[arg(long, default_value_t = false, help = "Only print JSON report")]
     pub json_only: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Record this run in the state history (full runs only) and report backlog trend"
+    )]
+    pub trend: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -151,6 +240,40 @@ struct DuplicatePair {
     rationale: String,
 }
 
+/// An issue and the open PRs that claim to fix it or whose titles match it.
+#[derive(Debug, Serialize, Clone)]
+struct IssuePrLink {
+    issue_number: u64,
+    issue_title: String,
+    issue_url: String,
+    prs: Vec<LinkedPr>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct LinkedPr {
+    number: u64,
+    title: String,
+    url: String,
+    /// `reference` for a closing keyword such as "fixes #N", `title` when
+    /// only the titles match.
+    via: String,
+    title_similarity: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct StaleItem {
+    kind: ItemKind,
+    number: u64,
+    title: String,
+    url: String,
+    author: String,
+    updated_at: String,
+    idle_days: i64,
+    /// `inactive`, or `awaiting_author` for a PR whose requested changes
+    /// have had no follow-up.
+    reason: String,
+}
+
 #[derive(Debug, Serialize)]
 struct PrScoreReport {
     number: u64,
@@ -161,6 +284,20 @@ struct PrScoreReport {
     decision: String,
     rationale: Vec<String>,
     signals: PrSignals,
+    suggested_reviewers: Vec<SuggestedReviewer>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct SuggestedReviewer {
+    /// Login or `org/team`; a git author name when blame cannot tell the
+    /// login.
+    reviewer: String,
+    /// `codeowners` or `blame`.
+    source: String,
+    /// Changed paths owned, or lines of the changed paths last touched.
+    weight: u64,
+    /// Whether the forge can be asked for a review from `reviewer`.
+    requestable: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -174,6 +311,8 @@ struct PrSignals {
     mergeable_state: Option<String>,
     approvals: u64,
     change_requests: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes_requested_at: Option<String>,
     ci_state: Option<String>,
     vision_alignment: Option<f64>,
 }
@@ -190,9 +329,13 @@ struct TriageReport {
     scanned_prs: usize,
     scanned_issues: usize,
     duplicate_pairs: Vec<DuplicatePair>,
+    issue_pr_links: Vec<IssuePrLink>,
+    stale_items: Vec<StaleItem>,
     ranked_prs: Vec<PrScoreReport>,
     planned_actions: Vec<TriageAction>,
     applied_action_count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    trend: Vec<TriageSnapshot>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -213,21 +356,51 @@ struct TriageState {
     state: String,
     last_run_at: Option<String>,
     last_seen_updated_at: Option<String>,
+    #[serde(default)]
+    history: Vec<TriageSnapshot>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct TriageSnapshot {
+    run_at: String,
+    incremental: bool,
+    open_prs: usize,
+    open_issues: usize,
+    duplicate_pairs: usize,
+    avg_pr_score: f64,
+}
+
+const MAX_TREND_HISTORY: usize = 104;
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct ItemSnapshot {
+    #[serde(default)]
+    repo: Option<String>,
+    #[serde(default)]
+    exported_at: Option<String>,
+    #[serde(default)]
+    pulls: Vec<GithubPull>,
+    #[serde(default)]
+    issues: Vec<GithubIssue>,
+    #[serde(default)]
+    deep_signals: HashMap<u64, DeepSignals>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct GithubUser {
     login: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct PullRef {
     sha: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct GithubPull {
     number: u64,
+    #[serde(default)]
+    state: Option<String>,
     title: Option<String>,
     body: Option<String>,
     html_url: Option<String>,
@@ -249,9 +422,11 @@ struct PullDetailFile {
     filename: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct GithubIssue {
     number: u64,
+    #[serde(default)]
+    state: Option<String>,
     title: Option<String>,
     body: Option<String>,
     html_url: Option<String>,
@@ -265,6 +440,8 @@ struct GithubIssue {
 struct GithubReview {
     user: Option<GithubUser>,
     state: Option<String>,
+    #[serde(default)]
+    submitted_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -272,10 +449,13 @@ struct CommitStatus {
     state: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct DeepSignals {
     approvals: u64,
     change_requests: u64,
+    /// When the latest still-standing change request was submitted.
+    #[serde(default)]
+    changes_requested_at: Option<String>,
     ci_state: Option<String>,
     changed_paths: Vec<String>,
 }
@@ -287,16 +467,70 @@ struct VisionModel {
 }
 
 pub fn run(args: TriageArgs) -> Result<(), Box<dyn Error>> {
+    let forge_kind = normalize_forge(&args.forge)?;
+    let format = normalize_format(&args.format)?;
+    if args.json_only && format != "json" {
+        return Err("--json-only cannot be combined with --format md|html".into());
+    }
+    let token_var = format!("{}_TOKEN", forge_kind.to_ascii_uppercase());
+    let token = args.token.clone().or_else(|| env::var(&token_var).ok());
+
+    let forge: Box<dyn Forge> = match args.from_snapshot.as_deref() {
+        Some(path) => {
+            if args.apply_actions {
+                return Err("--apply-actions cannot be used with --from-snapshot".into());
+            }
+            Box::new(SnapshotForge::new(load_item_snapshot(path)?))
+        }
+        None => {
+            if args.apply_actions && token.is_none() {
+                return Err(format!("--apply-actions requires --token or {token_var}").into());
+            }
+            forge_client(forge_kind, args.forge_url.as_deref(), token)?
+        }
+    };
+
+    let report = run_with_forge(&args, forge.as_ref())?;
+    let json_report = serde_json::to_string_pretty(&report)?;
+    let rendered = match format {
+        "md" => render_markdown(&report),
+        "html" => render_html(&report),
+        _ => json_report.clone(),
+    };
+
+    if let Some(path) = args.out.as_ref() {
+        fs::write(path, &rendered)?;
+    }
+
+    if args.json_only {
+        println!("{}", json_report);
+        return Ok(());
+    }
+
+    // Without --out the digest alone goes to stdout, so it can be piped.
+    if format != "json" && args.out.is_none() {
+        print!("{}", rendered);
+        return Ok(());
+    }
+
+    print_summary(&report, args.out.as_ref(), args.apply_actions);
+    if format == "json" {
+        println!("\n{}", json_report);
+    }
+
+    Ok(())
+}
+
+/// Runs the fetch/dedupe/score/plan pipeline against any forge backend.
+fn run_with_forge(args: &TriageArgs, forge: &dyn Forge) -> Result<TriageReport, Box<dyn Error>> {
     let state = normalize_state(&args.state)?;
-    let token = args.token.clone().or_else(|| env::var("GITHUB_TOKEN").ok());
     let state_file = resolve_state_file(args.state_file.clone(), &args.repo);
 
     let persisted = load_state_file(&state_file)?;
-    let since = resolve_since(&args, persisted.as_ref())?;
+    let since = resolve_since(args, persisted.as_ref())?;
 
-    let gh = GithubClient::new(token.clone())?;
-    let pulls = gh.fetch_pulls(&args.repo, state, args.limit, since.as_ref())?;
-    let issues = gh.fetch_issues(&args.repo, state, args.limit, since.as_ref())?;
+    let pulls = forge.fetch_pulls(&args.repo, state, args.limit, since.as_ref())?;
+    let issues = forge.fetch_issues(&args.repo, state, args.limit, since.as_ref())?;
 
     let vision_model = if let Some(path) = args.vision.as_ref() {
         let content = fs::read_to_string(path)?;
@@ -316,6 +550,12 @@ pub fn run(args: TriageArgs) -> Result<(), Box<dyn Error>> {
     }
 
     let duplicate_pairs = find_duplicates(&items, args.dedupe_threshold, args.max_pair_comparisons);
+    let open_prs = pulls
+        .iter()
+        .filter(|pr| pr.state.as_deref().unwrap_or("open") == "open")
+        .map(|pr| pr.number)
+        .collect::<HashSet<_>>();
+    let issue_pr_links = find_issue_pr_links(&items, &open_prs, &args.repo);
 
     let mut scored: Vec<PrScoreReport> = pulls
         .iter()
@@ -330,45 +570,114 @@ pub fn run(args: TriageArgs) -> Result<(), Box<dyn Error>> {
         args.deep_review_top.min(scored.len())
     };
 
-    for report in scored.iter_mut().take(deep_count) {
-        if let Some(pr) = pulls.iter().find(|p| p.number == report.number) {
-            let deep = gh.fetch_deep_signals(
-                &args.repo,
-                pr.number,
-                pr.head.as_ref().and_then(|h| h.sha.clone()),
-            )?;
+    let deep_targets = scored
+        .iter()
+        .take(deep_count)
+        .filter_map(|report| pulls.iter().find(|p| p.number == report.number))
+        .collect::<Vec<_>>();
+    let progress = !args.json_only;
+    let mut exported_deep = HashMap::new();
+    let mut changed_paths = HashMap::new();
+    let mut apply_deep = |pr: &GithubPull, deep: DeepSignals, done: usize| {
+        if args.export_snapshot.is_some() {
+            exported_deep.insert(pr.number, deep.clone());
+        }
+        changed_paths.insert(pr.number, deep.changed_paths.clone());
+        if let Some(report) = scored.iter_mut().find(|r| r.number == pr.number) {
             *report = score_pr(pr, Some(deep), vision_model.as_ref());
         }
+        if progress {
+            eprint!("\rdeep-reviewed {}/{}", done, deep_targets.len());
+        }
+    };
+    fetch_deep_signals_parallel(
+        forge,
+        &args.repo,
+        &deep_targets,
+        args.deep_review_concurrency,
+        &mut apply_deep,
+    )?;
+    if progress && !deep_targets.is_empty() {
+        eprintln!();
+    }
+
+    if let Some(path) = args.export_snapshot.as_ref() {
+        let snapshot = ItemSnapshot {
+            repo: Some(args.repo.clone()),
+            exported_at: Some(Utc::now().to_rfc3339()),
+            pulls: pulls.clone(),
+            issues: issues.clone(),
+            deep_signals: exported_deep,
+        };
+        fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
     }
 
     scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
 
-    let mut action_plan = build_action_plan(&scored, &duplicate_pairs, &args);
+    if let Some(root) = local_checkout(args) {
+        let owners = CodeOwners::load(&root);
+        let mut blame = BlameStats::new(root);
+        for report in scored.iter_mut() {
+            if let Some(paths) = changed_paths.get(&report.number) {
+                report.suggested_reviewers =
+                    suggest_reviewers(paths, &report.author, owners.as_ref(), &mut blame);
+            }
+        }
+    }
+
+    let stale_items = find_stale_items(&pulls, &issues, &scored, args.stale_days, Utc::now());
+
+    let mut action_plan = build_action_plan(
+        &scored,
+        &duplicate_pairs,
+        &issue_pr_links,
+        &stale_items,
+        args,
+    );
     if action_plan.len() > args.action_limit {
         action_plan.truncate(args.action_limit);
     }
 
     let mut applied_action_count = 0usize;
     if args.apply_actions {
-        if token.is_none() {
-            return Err("--apply-actions requires --token or GITHUB_TOKEN".into());
-        }
-        applied_action_count = apply_actions(&gh, &args.repo, &mut action_plan, args.action_limit);
+        applied_action_count =
+            apply_actions(forge, &args.repo, &mut action_plan, args.action_limit);
     }
 
     let max_seen_updated_at = latest_seen_updated_at(&pulls, &issues);
+    let scanned_issues = issues.iter().filter(|i| i.pull_request.is_none()).count();
+
+    let mut history = persisted.map(|p| p.history).unwrap_or_default();
+    // A run limited by `since` only sees what changed, so its counts are
+    // not the backlog's and would break the trend.
+    if args.trend && since.is_some() {
+        if progress {
+            eprintln!("trend: not recorded; runs limited by --since or --incremental see only updated items");
+        }
+    } else if args.trend {
+        let snapshot = TriageSnapshot {
+            run_at: Utc::now().to_rfc3339(),
+            incremental: args.incremental,
+            open_prs: pulls.len(),
+            open_issues: scanned_issues,
+            duplicate_pairs: duplicate_pairs.len(),
+            avg_pr_score: average_score(&scored),
+        };
+        push_snapshot(&mut history, snapshot);
+    }
 
-    if args.incremental || args.state_file.is_some() {
+    if args.incremental || args.state_file.is_some() || args.trend {
         let state_value = TriageState {
             repo: args.repo.clone(),
             state: state.to_string(),
             last_run_at: Some(Utc::now().to_rfc3339()),
             last_seen_updated_at: max_seen_updated_at.clone().or_else(|| since.clone()),
+            history: history.clone(),
         };
         save_state_file(&state_file, &state_value)?;
     }
 
-    let report = TriageReport {
+    Ok(TriageReport {
         repo: args.repo.clone(),
         state: state.to_string(),
         generated_at: Utc::now().to_rfc3339(),
@@ -377,28 +686,15 @@ pub fn run(args: TriageArgs) -> Result<(), Box<dyn Error>> {
         state_file: state_file.display().to_string(),
         max_seen_updated_at,
         scanned_prs: pulls.len(),
-        scanned_issues: issues.iter().filter(|i| i.pull_request.is_none()).count(),
+        scanned_issues,
         duplicate_pairs,
+        issue_pr_links,
+        stale_items,
         ranked_prs: scored,
         planned_actions: action_plan,
         applied_action_count,
-    };
-
-    let json_report = serde_json::to_string_pretty(&report)?;
-
-    if let Some(path) = args.out.as_ref() {
-        fs::write(path, &json_report)?;
-    }
-
-    if args.json_only {
-        println!("{}", json_report);
-        return Ok(());
-    }
-
-    print_summary(&report, args.out.as_ref(), args.apply_actions);
-    println!("\n{}", json_report);
-
-    Ok(())
+        trend: if args.trend { history } else { Vec::new() },
+    })
 }
 
 fn print_summary(report: &TriageReport, out: Option<&PathBuf>, apply_actions: bool) {
@@ -409,6 +705,30 @@ fn print_summary(report: &TriageReport, out: Option<&PathBuf>, apply_actions: bo
         report.scanned_prs, report.scanned_issues
     );
     println!("duplicates found: {}", report.duplicate_pairs.len());
+    if !report.stale_items.is_empty() {
+        let awaiting = report
+            .stale_items
+            .iter()
+            .filter(|item| item.reason == "awaiting_author")
+            .count();
+        println!(
+            "stale items: {} ({} awaiting author)",
+            report.stale_items.len(),
+            awaiting
+        );
+    }
+    if !report.issue_pr_links.is_empty() {
+        let contested = report
+            .issue_pr_links
+            .iter()
+            .filter(|link| link.prs.len() > 1)
+            .count();
+        println!(
+            "issues with linked PRs: {} ({} with competing PRs)",
+            report.issue_pr_links.len(),
+            contested
+        );
+    }
     if let Some(since) = report.since.as_ref() {
         println!("since: {}", since);
     }
@@ -431,50 +751,527 @@ fn print_summary(report: &TriageReport, out: Option<&PathBuf>, apply_actions: bo
         println!("applied actions: {}", report.applied_action_count);
     }
 
+    if report.trend.len() >= 2 {
+        print_trend(&report.trend);
+    }
+
     if let Some(path) = out {
         println!("report written to: {}", path.display());
     }
 }
 
-struct GithubClient {
-    client: Client,
+/// One table of the digest; the markdown and HTML renderers share these.
+struct DigestSection {
+    title: &'static str,
+    empty: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<DigestCell>>,
 }
 
-impl GithubClient {
-    fn new(token: Option<String>) -> Result<Self, Box<dyn Error>> {
-        let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, HeaderValue::from_static("osmogrep-triage"));
-        headers.insert(
-            ACCEPT,
-            HeaderValue::from_static("application/vnd.github+json"),
+/// Comma-separated parts, each optionally linked.
+struct DigestCell(Vec<(String, Option<String>)>);
+
+fn text_cell(text: impl Into<String>) -> DigestCell {
+    DigestCell(vec![(text.into(), None)])
+}
+
+fn link_cell(text: impl Into<String>, url: &str) -> DigestCell {
+    let url = (!url.is_empty()).then(|| url.to_string());
+    DigestCell(vec![(text.into(), url)])
+}
+
+fn item_label(kind: &ItemKind, number: u64) -> String {
+    match kind {
+        ItemKind::PullRequest => format!("PR #{number}"),
+        ItemKind::Issue => format!("Issue #{number}"),
+    }
+}
+
+fn digest_summary(report: &TriageReport) -> Vec<String> {
+    let mut lines = vec![
+        format!("Generated: {}", report.generated_at),
+        format!("State: {}", report.state),
+        format!(
+            "Scanned: {} PRs, {} issues",
+            report.scanned_prs, report.scanned_issues
+        ),
+        format!("Duplicate pairs: {}", report.duplicate_pairs.len()),
+        format!(
+            "Planned actions: {} ({} applied)",
+            report.planned_actions.len(),
+            report.applied_action_count
+        ),
+    ];
+    if let Some(since) = report.since.as_ref() {
+        lines.insert(2, format!("Since: {}", since));
+    }
+    lines
+}
+
+/// Kind, number, title and URL of one duplicate-cluster member.
+type ClusterItem = (ItemKind, u64, String, String);
+
+/// Groups duplicate pairs into connected clusters, each with the highest
+/// similarity inside it, strongest first.
+fn duplicate_clusters(pairs: &[DuplicatePair]) -> Vec<(Vec<ClusterItem>, f64)> {
+    let mut clusters: Vec<(Vec<ClusterItem>, f64)> = Vec::new();
+    for pair in pairs {
+        let left = (
+            pair.left_kind.clone(),
+            pair.left_number,
+            pair.left_title.clone(),
+            pair.left_url.clone(),
         );
+        let right = (
+            pair.right_kind.clone(),
+            pair.right_number,
+            pair.right_title.clone(),
+            pair.right_url.clone(),
+        );
+        let holds = |cluster: &[ClusterItem], item: &ClusterItem| {
+            cluster.iter().any(|c| c.0 == item.0 && c.1 == item.1)
+        };
+        let mut touching = clusters
+            .iter()
+            .enumerate()
+            .filter(|(_, (items, _))| holds(items, &left) || holds(items, &right))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
 
-        if let Some(tok) = token {
-            let value = format!("Bearer {}", tok);
-            headers.insert(AUTHORIZATION, HeaderValue::from_str(&value)?);
+        let mut merged = (Vec::new(), pair.similarity);
+        while let Some(idx) = touching.pop() {
+            let (items, similarity) = clusters.remove(idx);
+            merged.0.extend(items);
+            merged.1 = merged.1.max(similarity);
+        }
+        for item in [left, right] {
+            if !holds(&merged.0, &item) {
+                merged.0.push(item);
+            }
+        }
+        clusters.push(merged);
+    }
+    for (items, _) in clusters.iter_mut() {
+        items.sort_by_key(|item| item.1);
+    }
+    clusters.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    clusters
+}
+
+fn digest_sections(report: &TriageReport) -> Vec<DigestSection> {
+    let ranked = report
+        .ranked_prs
+        .iter()
+        .take(25)
+        .map(|pr| {
+            let reviewers = pr
+                .suggested_reviewers
+                .iter()
+                .map(|r| r.reviewer.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            vec![
+                link_cell(format!("#{}", pr.number), &pr.url),
+                text_cell(format!("{:.1}", pr.score)),
+                text_cell(pr.decision.clone()),
+                text_cell(pr.title.clone()),
+                text_cell(pr.author.clone()),
+                text_cell(pr.signals.ci_state.clone().unwrap_or_else(|| "-".into())),
+                text_cell(pr.signals.approvals.to_string()),
+                text_cell(reviewers),
+            ]
+        })
+        .collect();
+
+    let clusters = duplicate_clusters(&report.duplicate_pairs)
+        .into_iter()
+        .take(20)
+        .map(|(items, similarity)| {
+            let parts = items
+                .iter()
+                .map(|(kind, number, title, url)| {
+                    let url = (!url.is_empty()).then(|| url.clone());
+                    (format!("{} {}", item_label(kind, *number), title), url)
+                })
+                .collect();
+            vec![DigestCell(parts), text_cell(format!("{:.2}", similarity))]
+        })
+        .collect();
+
+    let links = report
+        .issue_pr_links
+        .iter()
+        .take(20)
+        .map(|link| {
+            let prs = link
+                .prs
+                .iter()
+                .map(|pr| {
+                    let url = (!pr.url.is_empty()).then(|| pr.url.clone());
+                    (format!("#{} ({})", pr.number, pr.via), url)
+                })
+                .collect();
+            vec![
+                link_cell(format!("#{}", link.issue_number), &link.issue_url),
+                text_cell(link.issue_title.clone()),
+                DigestCell(prs),
+            ]
+        })
+        .collect();
+
+    let stale = report
+        .stale_items
+        .iter()
+        .take(25)
+        .map(|item| {
+            vec![
+                link_cell(item_label(&item.kind, item.number), &item.url),
+                text_cell(item.title.clone()),
+                text_cell(item.idle_days.to_string()),
+                text_cell(item.reason.clone()),
+            ]
+        })
+        .collect();
+
+    let actions = report
+        .planned_actions
+        .iter()
+        .map(|action| {
+            vec![
+                link_cell(
+                    item_label(&action.item_kind, action.item_number),
+                    &action.item_url,
+                ),
+                text_cell(action.action_type.clone()),
+                text_cell(action.value.clone()),
+                text_cell(action.reason.clone()),
+                text_cell(match action.error.as_ref() {
+                    Some(error) => format!("{}: {}", action.status, error),
+                    None => action.status.clone(),
+                }),
+            ]
+        })
+        .collect();
+
+    vec![
+        DigestSection {
+            title: "Ranked PRs",
+            empty: "No PRs ranked.",
+            headers: &[
+                "PR",
+                "Score",
+                "Decision",
+                "Title",
+                "Author",
+                "CI",
+                "Approvals",
+                "Suggested reviewers",
+            ],
+            rows: ranked,
+        },
+        DigestSection {
+            title: "Duplicate clusters",
+            empty: "No probable duplicates found.",
+            headers: &["Items", "Similarity"],
+            rows: clusters,
+        },
+        DigestSection {
+            title: "Issues with linked PRs",
+            empty: "No open PRs reference the scanned issues.",
+            headers: &["Issue", "Title", "PRs"],
+            rows: links,
+        },
+        DigestSection {
+            title: "Stale items",
+            empty: "Nothing stale.",
+            headers: &["Item", "Title", "Idle days", "Reason"],
+            rows: stale,
+        },
+        DigestSection {
+            title: "Action plan",
+            empty: "No actions planned.",
+            headers: &["Item", "Action", "Value", "Reason", "Status"],
+            rows: actions,
+        },
+    ]
+}
+
+fn render_markdown(report: &TriageReport) -> String {
+    // GitHub renders inline HTML, so `<` is escaped too.
+    let escape = |text: &str| {
+        text.replace('|', "\\|")
+            .replace('<', "&lt;")
+            .replace(['\n', '\r'], " ")
+    };
+    let mut md = format!("# Triage digest: {}\n\n", report.repo);
+    for line in digest_summary(report) {
+        md.push_str(&format!("- {}\n", line));
+    }
+    for section in digest_sections(report) {
+        md.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            md.push_str(&format!("_{}_\n", section.empty));
+            continue;
+        }
+        md.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+        md.push_str(&format!("|{}\n", " --- |".repeat(section.headers.len())));
+        for row in section.rows {
+            let cells = row
+                .iter()
+                .map(|cell| {
+                    cell.0
+                        .iter()
+                        .map(|(text, url)| match url {
+                            Some(url) => format!("[{}]({})", escape(text), url),
+                            None => escape(text),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .collect::<Vec<_>>();
+            md.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+    md
+}
+
+fn render_html(report: &TriageReport) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let title = format!("Triage digest: {}", escape(&report.repo));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 80rem; padding: 0 1rem; }}\n\
+         table {{ border-collapse: collapse; width: 100%; }}\n\
+         th, td {{ border: 1px solid #d0d7de; padding: 0.3rem 0.5rem; text-align: left; vertical-align: top; }}\n\
+         th {{ background: #f6f8fa; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<ul>\n"
+    );
+    for line in digest_summary(report) {
+        html.push_str(&format!("<li>{}</li>\n", escape(&line)));
+    }
+    html.push_str("</ul>\n");
+    for section in digest_sections(report) {
+        html.push_str(&format!("<h2>{}</h2>\n", section.title));
+        if section.rows.is_empty() {
+            html.push_str(&format!("<p><em>{}</em></p>\n", section.empty));
+            continue;
+        }
+        html.push_str("<table>\n<thead><tr>");
+        for header in section.headers {
+            html.push_str(&format!("<th>{}</th>", header));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in section.rows {
+            html.push_str("<tr>");
+            for cell in row {
+                let parts = cell
+                    .0
+                    .iter()
+                    .map(|(text, url)| match url {
+                        Some(url) => format!("<a href=\"{}\">{}</a>", escape(url), escape(text)),
+                        None => escape(text),
+                    })
+                    .collect::<Vec<_>>();
+                html.push_str(&format!("<td>{}</td>", parts.join(", ")));
+            }
+            html.push_str("</tr>\n");
         }
+        html.push_str("</tbody>\n</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn print_trend(history: &[TriageSnapshot]) {
+    let prs = history
+        .iter()
+        .map(|s| s.open_prs as f64)
+        .collect::<Vec<_>>();
+    let issues = history
+        .iter()
+        .map(|s| s.open_issues as f64)
+        .collect::<Vec<_>>();
+    let dups = history
+        .iter()
+        .map(|s| s.duplicate_pairs as f64)
+        .collect::<Vec<_>>();
+    let scores = history.iter().map(|s| s.avg_pr_score).collect::<Vec<_>>();
+
+    println!("trend ({} runs):", history.len());
+    let series = [
+        ("open PRs    ", &prs, 0, [86, 156, 214]),
+        ("open issues ", &issues, 0, [220, 170, 90]),
+        ("duplicates  ", &dups, 0, [220, 95, 90]),
+        ("avg PR score", &scores, 1, [70, 190, 120]),
+    ];
+    for (label, values, precision, color) in series {
+        let delta = trend_delta(values, precision);
+        if !chart::print_line_chart(&format!("  {label} {delta}"), values, color) {
+            println!("  {label} {} {delta}", sparkline(values));
+        }
+    }
+}
+
+fn trend_delta(values: &[f64], precision: usize) -> String {
+    let (Some(first), Some(last)) = (values.first(), values.last()) else {
+        return String::new();
+    };
+    format!(
+        "{:.p$} -> {:.p$} ({:+.p$})",
+        first,
+        last,
+        last - first,
+        p = precision
+    )
+}
+
+fn average_score(scored: &[PrScoreReport]) -> f64 {
+    if scored.is_empty() {
+        return 0.0;
+    }
+    scored.iter().map(|r| r.score).sum::<f64>() / scored.len() as f64
+}
 
-        let client = Client::builder().default_headers(headers).build()?;
-        Ok(Self { client })
+fn push_snapshot(history: &mut Vec<TriageSnapshot>, snapshot: TriageSnapshot) {
+    history.push(snapshot);
+    if history.len() > MAX_TREND_HISTORY {
+        let overflow = history.len() - MAX_TREND_HISTORY;
+        history.drain(..overflow);
     }
+}
 
+/// Source of PRs/issues and sink for triage actions. `GithubClient`,
+/// `GitlabClient` and `GiteaClient` talk to the REST APIs and map their items
+/// onto the GitHub shapes; `SnapshotForge` serves an exported snapshot and
+/// records actions in memory, which keeps the pipeline testable without
+/// network I/O.
+trait Forge: Sync {
     fn fetch_pulls(
         &self,
         repo: &str,
         state: &str,
         limit: usize,
         since: Option<&String>,
-    ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
-        let since_dt = since.and_then(|s| parse_date(s));
+    ) -> Result<Vec<GithubPull>, Box<dyn Error>>;
 
-        let mut page = 1;
-        let mut out = Vec::new();
-        while out.len() < limit {
-            let url = format!(
-                "https://api.github.com/repos/{repo}/pulls?state={state}&per_page=100&page={page}&sort=updated&direction=desc"
+    fn fetch_issues(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubIssue>, Box<dyn Error>>;
+
+    /// Returns `None` when the backend has no deep signals for the PR.
+    fn fetch_deep_signals(
+        &self,
+        repo: &str,
+        pr_number: u64,
+        head_sha: Option<String>,
+    ) -> Result<Option<DeepSignals>, Box<dyn Error>>;
+
+    /// `kind` matters where merge requests and issues are numbered apart.
+    fn add_labels(
+        &self,
+        repo: &str,
+        kind: &ItemKind,
+        number: u64,
+        labels: &[String],
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn add_comment(
+        &self,
+        repo: &str,
+        kind: &ItemKind,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// `reviewer` is a login, or `org/team` for a team.
+    fn request_review(&self, repo: &str, number: u64, reviewer: &str)
+        -> Result<(), Box<dyn Error>>;
+}
+
+const GITHUB_API: &str = "https://api.github.com";
+
+struct GithubClient {
+    client: Client,
+    /// `https://api.github.com`, or `<host>/api/v3` for GitHub Enterprise.
+    api: String,
+}
+
+impl GithubClient {
+    fn new(api: &str, token: Option<String>) -> Result<Self, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("osmogrep-triage"));
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+
+        if let Some(tok) = token {
+            let value = format!("Bearer {}", tok);
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(&value)?);
+        }
+
+        let client = crate::net::client_builder()?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            api: api.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn create_pull(&self, repo: &str, draft: &PrDraft) -> Result<String, Box<dyn Error>> {
+        let url = format!("{}/repos/{repo}/pulls", self.api);
+        let payload = serde_json::json!({
+            "title": draft.title,
+            "body": draft.body,
+            "head": draft.head,
+            "base": draft.base,
+        });
+        let created: serde_json::Value = http_policy::send(self.client.post(&url).json(&payload))?
+            .error_for_status()?
+            .json()?;
+        Ok(created
+            .get("html_url")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+/// Opens a pull request through the API, for `/pr` without the GitHub CLI.
+pub fn create_pull(token: String, repo: &str, draft: &PrDraft) -> Result<String, Box<dyn Error>> {
+    GithubClient::new(GITHUB_API, Some(token))?.create_pull(repo, draft)
+}
+
+impl Forge for GithubClient {
+    fn fetch_pulls(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
+        let since_dt = since.and_then(|s| parse_date(s));
+
+        let mut page = 1;
+        let mut out = Vec::new();
+        while out.len() < limit {
+            let url = format!(
+                "{}/repos/{repo}/pulls?state={state}&per_page=100&page={page}&sort=updated&direction=desc",
+                self.api
             );
-            let chunk: Vec<GithubPull> =
-                self.client.get(&url).send()?.error_for_status()?.json()?;
+            let chunk: Vec<GithubPull> = http_policy::send(self.client.get(&url))?
+                .error_for_status()?
+                .json()?;
             if chunk.is_empty() {
                 break;
             }
@@ -502,8 +1299,627 @@ impl GithubClient {
             page += 1;
         }
 
-        out.truncate(limit);
-        Ok(out)
+        out.truncate(limit);
+        Ok(out)
+    }
+
+    fn fetch_issues(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubIssue>, Box<dyn Error>> {
+        let mut page = 1;
+        let mut out = Vec::new();
+        while out.len() < limit {
+            let mut url = format!(
+                "{}/repos/{repo}/issues?state={state}&per_page=100&page={page}&sort=updated&direction=desc",
+                self.api
+            );
+            if let Some(since_ts) = since {
+                url.push_str("&since=");
+                url.push_str(since_ts);
+            }
+            let mut chunk: Vec<GithubIssue> = http_policy::send(self.client.get(&url))?
+                .error_for_status()?
+                .json()?;
+            if chunk.is_empty() {
+                break;
+            }
+            out.append(&mut chunk);
+            page += 1;
+        }
+        out.truncate(limit);
+        Ok(out)
+    }
+
+    fn fetch_deep_signals(
+        &self,
+        repo: &str,
+        pr_number: u64,
+        head_sha: Option<String>,
+    ) -> Result<Option<DeepSignals>, Box<dyn Error>> {
+        let reviews_url = format!(
+            "{}/repos/{repo}/pulls/{pr_number}/reviews?per_page=100",
+            self.api
+        );
+        let reviews: Vec<GithubReview> = http_policy::send(self.client.get(&reviews_url))?
+            .error_for_status()?
+            .json()?;
+        let (approvals, change_requests, changes_requested_at) = review_counts(reviews);
+
+        let ci_state = if let Some(sha) = head_sha {
+            let status_url = format!("{}/repos/{repo}/commits/{sha}/status", self.api);
+            let status: CommitStatus = http_policy::send(self.client.get(&status_url))?
+                .error_for_status()?
+                .json()?;
+            status.state
+        } else {
+            None
+        };
+
+        let files_url = format!(
+            "{}/repos/{repo}/pulls/{pr_number}/files?per_page=100",
+            self.api
+        );
+        let files: Vec<PullDetailFile> = http_policy::send(self.client.get(&files_url))?
+            .error_for_status()?
+            .json()?;
+
+        let changed_paths = files
+            .into_iter()
+            .filter_map(|f| f.filename)
+            .collect::<Vec<_>>();
+
+        Ok(Some(DeepSignals {
+            approvals,
+            change_requests,
+            changes_requested_at,
+            ci_state,
+            changed_paths,
+        }))
+    }
+
+    fn add_labels(
+        &self,
+        repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        labels: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/repos/{repo}/issues/{number}/labels", self.api);
+        http_policy::send(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "labels": labels })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    fn add_comment(
+        &self,
+        repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/repos/{repo}/issues/{number}/comments", self.api);
+        http_policy::send(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "body": body })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    fn request_review(
+        &self,
+        repo: &str,
+        number: u64,
+        reviewer: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "{}/repos/{repo}/pulls/{number}/requested_reviewers",
+            self.api
+        );
+        http_policy::send(self.client.post(&url).json(&review_request(reviewer)))?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// `org/team` goes to `team_reviewers`, which takes the bare team name.
+fn review_request(reviewer: &str) -> serde_json::Value {
+    match reviewer.split_once('/') {
+        Some((_, team)) => serde_json::json!({ "team_reviewers": [team] }),
+        None => serde_json::json!({ "reviewers": [reviewer] }),
+    }
+}
+
+/// Latest review per reviewer, counted as approvals and change requests,
+/// plus when the newest standing change request was made. GitHub reports
+/// `CHANGES_REQUESTED`, Gitea `REQUEST_CHANGES`.
+fn review_counts(reviews: Vec<GithubReview>) -> (u64, u64, Option<String>) {
+    let mut latest_by_user: HashMap<String, (String, Option<String>)> = HashMap::new();
+    for review in reviews {
+        if let (Some(user), Some(state)) = (
+            review.user.and_then(|u| u.login),
+            review.state.map(|s| s.to_ascii_uppercase()),
+        ) {
+            latest_by_user.insert(user, (state, review.submitted_at));
+        }
+    }
+
+    let approvals = latest_by_user
+        .values()
+        .filter(|(s, _)| s.as_str() == "APPROVED")
+        .count() as u64;
+
+    let requested = latest_by_user
+        .values()
+        .filter(|(s, _)| matches!(s.as_str(), "CHANGES_REQUESTED" | "REQUEST_CHANGES"))
+        .collect::<Vec<_>>();
+    let changes_requested_at = requested
+        .iter()
+        .filter_map(|(_, at)| at.clone())
+        .max_by_key(|at| parse_date(at));
+
+    (approvals, requested.len() as u64, changes_requested_at)
+}
+
+/// Pages through a list sorted by most recently updated until `limit` items
+/// are kept or an item not updated after `since` shows up.
+fn fetch_recent<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    limit: usize,
+    since: Option<&String>,
+    page_url: impl Fn(usize) -> String,
+    updated_at: impl Fn(&T) -> Option<&String>,
+    keep: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let since_dt = since.and_then(|s| parse_date(s));
+
+    let mut page = 1;
+    let mut out = Vec::new();
+    while out.len() < limit {
+        let chunk: Vec<T> = http_policy::send(client.get(page_url(page)))?
+            .error_for_status()?
+            .json()?;
+        if chunk.is_empty() {
+            break;
+        }
+        for item in chunk {
+            let updated = updated_at(&item).and_then(|d| parse_date(d));
+            if let (Some(since_dt), Some(updated)) = (since_dt, updated) {
+                if updated <= since_dt {
+                    return Ok(out);
+                }
+            }
+            if keep(&item) {
+                out.push(item);
+            }
+            if out.len() >= limit {
+                break;
+            }
+        }
+        page += 1;
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabUser {
+    username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabPipeline {
+    status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabUserRef {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabMergeRequest {
+    iid: u64,
+    state: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    web_url: Option<String>,
+    author: Option<GitlabUser>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    draft: Option<bool>,
+    user_notes_count: Option<u64>,
+    detailed_merge_status: Option<String>,
+    sha: Option<String>,
+    head_pipeline: Option<GitlabPipeline>,
+    #[serde(default)]
+    reviewers: Vec<GitlabUserRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabIssue {
+    iid: u64,
+    state: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    web_url: Option<String>,
+    author: Option<GitlabUser>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabReviewer {
+    state: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabApprovals {
+    #[serde(default)]
+    approved_by: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabDiff {
+    new_path: Option<String>,
+}
+
+// GitLab says `opened` and keeps `merged` apart from `closed`; scoring only
+// knows open and closed.
+fn gitlab_state(state: Option<String>) -> Option<String> {
+    state.map(|s| if s == "opened" { "open" } else { "closed" }.to_string())
+}
+
+/// `detailed_merge_status` in the vocabulary of GitHub's `mergeable_state`.
+fn gitlab_mergeable_state(status: &str) -> Option<String> {
+    let state = match status {
+        "mergeable" => "clean",
+        "conflict" | "need_rebase" => "dirty",
+        "checking" | "unchecked" | "preparing" => return None,
+        _ => "blocked",
+    };
+    Some(state.to_string())
+}
+
+/// Pipeline status in the vocabulary of GitHub's combined commit status.
+fn gitlab_ci_state(status: &str) -> Option<String> {
+    let state = match status {
+        "success" => "success",
+        "failed" => "failure",
+        "canceled" | "skipped" | "manual" => return None,
+        _ => "pending",
+    };
+    Some(state.to_string())
+}
+
+impl From<GitlabMergeRequest> for GithubPull {
+    fn from(mr: GitlabMergeRequest) -> Self {
+        GithubPull {
+            number: mr.iid,
+            state: gitlab_state(mr.state),
+            title: mr.title,
+            body: mr.description,
+            html_url: mr.web_url,
+            user: mr.author.map(|a| GithubUser { login: a.username }),
+            created_at: mr.created_at,
+            updated_at: mr.updated_at,
+            draft: mr.draft,
+            comments: mr.user_notes_count,
+            review_comments: None,
+            additions: None,
+            deletions: None,
+            changed_files: None,
+            mergeable_state: mr
+                .detailed_merge_status
+                .as_deref()
+                .and_then(gitlab_mergeable_state),
+            head: Some(PullRef { sha: mr.sha }),
+        }
+    }
+}
+
+impl From<GitlabIssue> for GithubIssue {
+    fn from(issue: GitlabIssue) -> Self {
+        GithubIssue {
+            number: issue.iid,
+            state: gitlab_state(issue.state),
+            title: issue.title,
+            body: issue.description,
+            html_url: issue.web_url,
+            user: issue.author.map(|a| GithubUser { login: a.username }),
+            created_at: issue.created_at,
+            updated_at: issue.updated_at,
+            pull_request: None,
+        }
+    }
+}
+
+/// GitLab REST API v4. Merge requests and issues are addressed by their
+/// project-scoped `iid`, which is what the report calls a number.
+struct GitlabClient {
+    client: Client,
+    /// `<host>/api/v4`.
+    api: String,
+}
+
+impl GitlabClient {
+    fn new(base: &str, token: Option<String>) -> Result<Self, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("osmogrep-triage"));
+        if let Some(tok) = token {
+            headers.insert("PRIVATE-TOKEN", HeaderValue::from_str(&tok)?);
+        }
+        let client = crate::net::client_builder()?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            api: format!("{}/api/v4", base.trim_end_matches('/')),
+        })
+    }
+
+    // Project paths may contain subgroups, so the slashes are encoded.
+    fn project_url(&self, repo: &str) -> String {
+        format!("{}/projects/{}", self.api, repo.replace('/', "%2F"))
+    }
+
+    fn item_url(&self, repo: &str, kind: &ItemKind, number: u64) -> String {
+        let collection = match kind {
+            ItemKind::PullRequest => "merge_requests",
+            ItemKind::Issue => "issues",
+        };
+        format!("{}/{collection}/{number}", self.project_url(repo))
+    }
+}
+
+impl Forge for GitlabClient {
+    fn fetch_pulls(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
+        // `state=closed` would leave out merged requests.
+        let query = if state == "open" { "opened" } else { "all" };
+        let project = self.project_url(repo);
+        let mrs = fetch_recent(
+            &self.client,
+            limit,
+            since,
+            |page| {
+                format!(
+                    "{project}/merge_requests?state={query}&order_by=updated_at&sort=desc&per_page=100&page={page}"
+                )
+            },
+            |mr: &GitlabMergeRequest| mr.updated_at.as_ref(),
+            |mr| state != "closed" || mr.state.as_deref() != Some("opened"),
+        )?;
+        Ok(mrs.into_iter().map(GithubPull::from).collect())
+    }
+
+    fn fetch_issues(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubIssue>, Box<dyn Error>> {
+        let query = match state {
+            "open" => "opened",
+            "closed" => "closed",
+            _ => "all",
+        };
+        let project = self.project_url(repo);
+        let issues = fetch_recent(
+            &self.client,
+            limit,
+            since,
+            |page| {
+                format!(
+                    "{project}/issues?state={query}&order_by=updated_at&sort=desc&per_page=100&page={page}"
+                )
+            },
+            |issue: &GitlabIssue| issue.updated_at.as_ref(),
+            |_| true,
+        )?;
+        Ok(issues.into_iter().map(GithubIssue::from).collect())
+    }
+
+    fn fetch_deep_signals(
+        &self,
+        repo: &str,
+        pr_number: u64,
+        _head_sha: Option<String>,
+    ) -> Result<Option<DeepSignals>, Box<dyn Error>> {
+        let mr_url = self.item_url(repo, &ItemKind::PullRequest, pr_number);
+
+        let approvals: GitlabApprovals =
+            http_policy::send(self.client.get(format!("{mr_url}/approvals")))?
+                .error_for_status()?
+                .json()?;
+
+        // Reviewer states arrived in GitLab 16; older servers just have none.
+        let change_requests = http_policy::send(self.client.get(format!("{mr_url}/reviewers")))
+            .ok()
+            .and_then(|resp| resp.error_for_status().ok())
+            .and_then(|resp| resp.json::<Vec<GitlabReviewer>>().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.state.as_deref() == Some("requested_changes"))
+            .count() as u64;
+
+        // The list endpoint leaves out `head_pipeline`.
+        let mr: GitlabMergeRequest = http_policy::send(self.client.get(&mr_url))?
+            .error_for_status()?
+            .json()?;
+        let ci_state = mr
+            .head_pipeline
+            .and_then(|p| p.status)
+            .and_then(|s| gitlab_ci_state(&s));
+
+        let diffs: Vec<GitlabDiff> =
+            http_policy::send(self.client.get(format!("{mr_url}/diffs?per_page=100")))?
+                .error_for_status()?
+                .json()?;
+        let changed_paths = diffs.into_iter().filter_map(|d| d.new_path).collect();
+
+        Ok(Some(DeepSignals {
+            approvals: approvals.approved_by.len() as u64,
+            change_requests,
+            changes_requested_at: None,
+            ci_state,
+            changed_paths,
+        }))
+    }
+
+    fn add_labels(
+        &self,
+        repo: &str,
+        kind: &ItemKind,
+        number: u64,
+        labels: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let url = self.item_url(repo, kind, number);
+        http_policy::send(
+            self.client
+                .put(&url)
+                .json(&serde_json::json!({ "add_labels": labels.join(",") })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    fn add_comment(
+        &self,
+        repo: &str,
+        kind: &ItemKind,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/notes", self.item_url(repo, kind, number));
+        http_policy::send(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "body": body })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    // Reviewers are set by user id, and setting them replaces the list.
+    fn request_review(
+        &self,
+        repo: &str,
+        number: u64,
+        reviewer: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if reviewer.contains('/') {
+            return Err(format!("GitLab cannot request a review from group {reviewer}").into());
+        }
+        let users_url = format!("{}/users?username={reviewer}", self.api);
+        let users: Vec<GitlabUserRef> = http_policy::send(self.client.get(&users_url))?
+            .error_for_status()?
+            .json()?;
+        let id = users
+            .first()
+            .ok_or_else(|| format!("no GitLab user {reviewer}"))?
+            .id;
+
+        let mr_url = self.item_url(repo, &ItemKind::PullRequest, number);
+        let mr: GitlabMergeRequest = http_policy::send(self.client.get(&mr_url))?
+            .error_for_status()?
+            .json()?;
+        let mut ids = mr.reviewers.iter().map(|r| r.id).collect::<Vec<_>>();
+        if ids.contains(&id) {
+            return Ok(());
+        }
+        ids.push(id);
+        http_policy::send(
+            self.client
+                .put(&mr_url)
+                .json(&serde_json::json!({ "reviewer_ids": ids })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaPull {
+    #[serde(flatten)]
+    pull: GithubPull,
+    mergeable: Option<bool>,
+}
+
+impl From<GiteaPull> for GithubPull {
+    fn from(gitea: GiteaPull) -> Self {
+        let mut pull = gitea.pull;
+        if pull.mergeable_state.is_none() {
+            pull.mergeable_state = gitea
+                .mergeable
+                .map(|ok| if ok { "clean" } else { "dirty" }.to_string());
+        }
+        pull
+    }
+}
+
+/// Gitea (and Forgejo) REST API v1, which mirrors GitHub's shapes closely.
+struct GiteaClient {
+    client: Client,
+    /// `<host>/api/v1`.
+    api: String,
+}
+
+impl GiteaClient {
+    fn new(base: &str, token: Option<String>) -> Result<Self, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("osmogrep-triage"));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        if let Some(tok) = token {
+            let value = format!("token {}", tok);
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(&value)?);
+        }
+        let client = crate::net::client_builder()?
+            .default_headers(headers)
+            .build()?;
+        Ok(Self {
+            client,
+            api: format!("{}/api/v1", base.trim_end_matches('/')),
+        })
+    }
+}
+
+impl Forge for GiteaClient {
+    fn fetch_pulls(
+        &self,
+        repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
+        let pulls = fetch_recent(
+            &self.client,
+            limit,
+            since,
+            |page| {
+                format!(
+                    "{}/repos/{repo}/pulls?state={state}&sort=recentupdate&limit=50&page={page}",
+                    self.api
+                )
+            },
+            |pr: &GiteaPull| pr.pull.updated_at.as_ref(),
+            |_| true,
+        )?;
+        Ok(pulls.into_iter().map(GithubPull::from).collect())
     }
 
     fn fetch_issues(
@@ -513,26 +1929,20 @@ impl GithubClient {
         limit: usize,
         since: Option<&String>,
     ) -> Result<Vec<GithubIssue>, Box<dyn Error>> {
-        let mut page = 1;
-        let mut out = Vec::new();
-        while out.len() < limit {
-            let mut url = format!(
-                "https://api.github.com/repos/{repo}/issues?state={state}&per_page=100&page={page}&sort=updated&direction=desc"
-            );
-            if let Some(since_ts) = since {
-                url.push_str("&since=");
-                url.push_str(since_ts);
-            }
-            let mut chunk: Vec<GithubIssue> =
-                self.client.get(&url).send()?.error_for_status()?.json()?;
-            if chunk.is_empty() {
-                break;
-            }
-            out.append(&mut chunk);
-            page += 1;
+        // Issues come newest-created first, so `since` is left to the server.
+        let mut query = format!("state={state}&type=issues&limit=50");
+        if let Some(since_ts) = since {
+            query.push_str("&since=");
+            query.push_str(since_ts);
         }
-        out.truncate(limit);
-        Ok(out)
+        fetch_recent(
+            &self.client,
+            limit,
+            None,
+            |page| format!("{}/repos/{repo}/issues?{query}&page={page}", self.api),
+            |issue: &GithubIssue| issue.updated_at.as_ref(),
+            |_| true,
+        )
     }
 
     fn fetch_deep_signals(
@@ -540,92 +1950,277 @@ impl GithubClient {
         repo: &str,
         pr_number: u64,
         head_sha: Option<String>,
-    ) -> Result<DeepSignals, Box<dyn Error>> {
-        let reviews_url =
-            format!("https://api.github.com/repos/{repo}/pulls/{pr_number}/reviews?per_page=100");
-        let reviews: Vec<GithubReview> = self
-            .client
-            .get(&reviews_url)
-            .send()?
-            .error_for_status()?
-            .json()?;
-
-        let mut latest_by_user: HashMap<String, String> = HashMap::new();
-        for review in reviews {
-            if let (Some(user), Some(state)) = (
-                review.user.and_then(|u| u.login),
-                review.state.map(|s| s.to_ascii_uppercase()),
-            ) {
-                latest_by_user.insert(user, state);
-            }
-        }
-
-        let approvals = latest_by_user
-            .values()
-            .filter(|s| s.as_str() == "APPROVED")
-            .count() as u64;
-
-        let change_requests = latest_by_user
-            .values()
-            .filter(|s| s.as_str() == "CHANGES_REQUESTED")
-            .count() as u64;
+    ) -> Result<Option<DeepSignals>, Box<dyn Error>> {
+        let pull_url = format!("{}/repos/{repo}/pulls/{pr_number}", self.api);
+        let reviews: Vec<GithubReview> =
+            http_policy::send(self.client.get(format!("{pull_url}/reviews")))?
+                .error_for_status()?
+                .json()?;
+        let (approvals, change_requests, changes_requested_at) = review_counts(reviews);
 
         let ci_state = if let Some(sha) = head_sha {
-            let status_url = format!("https://api.github.com/repos/{repo}/commits/{sha}/status");
-            let status: CommitStatus = self
-                .client
-                .get(&status_url)
-                .send()?
+            let status_url = format!("{}/repos/{repo}/commits/{sha}/status", self.api);
+            let status: CommitStatus = http_policy::send(self.client.get(&status_url))?
                 .error_for_status()?
                 .json()?;
-            status.state
+            // An empty combined status is reported as "" rather than null.
+            status.state.filter(|s| !s.is_empty())
         } else {
             None
         };
 
-        let files_url =
-            format!("https://api.github.com/repos/{repo}/pulls/{pr_number}/files?per_page=100");
-        let files: Vec<PullDetailFile> = self
-            .client
-            .get(&files_url)
-            .send()?
-            .error_for_status()?
-            .json()?;
-
-        let changed_paths = files
-            .into_iter()
-            .filter_map(|f| f.filename)
-            .collect::<Vec<_>>();
+        let files: Vec<PullDetailFile> =
+            http_policy::send(self.client.get(format!("{pull_url}/files?limit=100")))?
+                .error_for_status()?
+                .json()?;
+        let changed_paths = files.into_iter().filter_map(|f| f.filename).collect();
 
-        Ok(DeepSignals {
+        Ok(Some(DeepSignals {
             approvals,
             change_requests,
+            changes_requested_at,
             ci_state,
             changed_paths,
-        })
+        }))
     }
 
-    fn add_labels(&self, repo: &str, number: u64, labels: &[String]) -> Result<(), Box<dyn Error>> {
-        let url = format!("https://api.github.com/repos/{repo}/issues/{number}/labels");
-        self.client
-            .post(&url)
-            .json(&serde_json::json!({ "labels": labels }))
-            .send()?
-            .error_for_status()?;
+    fn add_labels(
+        &self,
+        repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        labels: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/repos/{repo}/issues/{number}/labels", self.api);
+        http_policy::send(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "labels": labels })),
+        )?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    fn add_comment(
+        &self,
+        repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/repos/{repo}/issues/{number}/comments", self.api);
+        http_policy::send(
+            self.client
+                .post(&url)
+                .json(&serde_json::json!({ "body": body })),
+        )?
+        .error_for_status()?;
         Ok(())
     }
 
-    fn add_comment(&self, repo: &str, number: u64, body: &str) -> Result<(), Box<dyn Error>> {
-        let url = format!("https://api.github.com/repos/{repo}/issues/{number}/comments");
-        self.client
-            .post(&url)
-            .json(&serde_json::json!({ "body": body }))
-            .send()?
+    fn request_review(
+        &self,
+        repo: &str,
+        number: u64,
+        reviewer: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "{}/repos/{repo}/pulls/{number}/requested_reviewers",
+            self.api
+        );
+        http_policy::send(self.client.post(&url).json(&review_request(reviewer)))?
             .error_for_status()?;
         Ok(())
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedAction {
+    number: u64,
+    action_type: &'static str,
+    value: String,
+}
+
+struct SnapshotForge {
+    snapshot: ItemSnapshot,
+    recorded: Mutex<Vec<RecordedAction>>,
+}
+
+impl SnapshotForge {
+    fn new(snapshot: ItemSnapshot) -> Self {
+        Self {
+            snapshot,
+            recorded: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, number: u64, action_type: &'static str, value: String) {
+        if let Ok(mut recorded) = self.recorded.lock() {
+            recorded.push(RecordedAction {
+                number,
+                action_type,
+                value,
+            });
+        }
+    }
+
+    #[cfg(test)]
+    fn recorded_actions(&self) -> Vec<RecordedAction> {
+        self.recorded
+            .lock()
+            .map(|recorded| recorded.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Forge for SnapshotForge {
+    fn fetch_pulls(
+        &self,
+        _repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
+        Ok(snapshot_pulls(&self.snapshot, state, limit, since))
+    }
+
+    fn fetch_issues(
+        &self,
+        _repo: &str,
+        state: &str,
+        limit: usize,
+        since: Option<&String>,
+    ) -> Result<Vec<GithubIssue>, Box<dyn Error>> {
+        Ok(snapshot_issues(&self.snapshot, state, limit, since))
+    }
+
+    fn fetch_deep_signals(
+        &self,
+        _repo: &str,
+        pr_number: u64,
+        _head_sha: Option<String>,
+    ) -> Result<Option<DeepSignals>, Box<dyn Error>> {
+        Ok(self.snapshot.deep_signals.get(&pr_number).cloned())
+    }
+
+    fn add_labels(
+        &self,
+        _repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        labels: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        for label in labels {
+            self.record(number, "label", label.clone());
+        }
+        Ok(())
+    }
+
+    fn add_comment(
+        &self,
+        _repo: &str,
+        _kind: &ItemKind,
+        number: u64,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.record(number, "comment", body.to_string());
+        Ok(())
+    }
+
+    fn request_review(
+        &self,
+        _repo: &str,
+        number: u64,
+        reviewer: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.record(number, "request_review", reviewer.to_string());
+        Ok(())
+    }
+}
+
+const MAX_DEEP_REVIEW_CONCURRENCY: usize = 16;
+
+/// Fetches deep signals for `targets` on a bounded worker pool, handing each
+/// result to `on_result` as soon as it arrives. Requests go through
+/// `http_policy`, so a rate limit one worker hits (`Retry-After`, or
+/// `x-ratelimit-remaining: 0`) holds the others until it resets; a fetch
+/// that still fails stops dispatch and is returned.
+fn fetch_deep_signals_parallel<'a>(
+    forge: &dyn Forge,
+    repo: &str,
+    targets: &[&'a GithubPull],
+    concurrency: usize,
+    mut on_result: impl FnMut(&'a GithubPull, DeepSignals, usize),
+) -> Result<(), Box<dyn Error>> {
+    let workers = concurrency
+        .clamp(1, MAX_DEEP_REVIEW_CONCURRENCY)
+        .min(targets.len());
+    if workers == 0 {
+        return Ok(());
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel::<(usize, Result<Option<DeepSignals>, String>)>();
+
+    let mut first_error = None;
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let next = &next;
+            let failed = &failed;
+            scope.spawn(move || loop {
+                if failed.load(AtomicOrdering::Relaxed) {
+                    break;
+                }
+                let idx = next.fetch_add(1, AtomicOrdering::Relaxed);
+                let Some(pr) = targets.get(idx) else {
+                    break;
+                };
+                let result = forge
+                    .fetch_deep_signals(
+                        repo,
+                        pr.number,
+                        pr.head.as_ref().and_then(|h| h.sha.clone()),
+                    )
+                    .map_err(|e| e.to_string());
+                if result.is_err() {
+                    failed.store(true, AtomicOrdering::Relaxed);
+                }
+                if tx.send((idx, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut done = 0usize;
+        for (idx, result) in rx {
+            match result {
+                Ok(deep) => {
+                    done += 1;
+                    if let Some(deep) = deep {
+                        on_result(targets[idx], deep, done);
+                    }
+                }
+                Err(e) => {
+                    if first_error.is_none() {
+                        first_error = Some(format!(
+                            "deep review of #{} failed: {}",
+                            targets[idx].number, e
+                        ));
+                    }
+                }
+            }
+        }
+    });
+
+    match first_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
 fn normalize_state(state: &str) -> Result<&'static str, Box<dyn Error>> {
     match state {
         "open" => Ok("open"),
@@ -635,6 +2230,42 @@ fn normalize_state(state: &str) -> Result<&'static str, Box<dyn Error>> {
     }
 }
 
+fn normalize_forge(forge: &str) -> Result<&'static str, Box<dyn Error>> {
+    match forge {
+        "github" => Ok("github"),
+        "gitlab" => Ok("gitlab"),
+        "gitea" => Ok("gitea"),
+        _ => Err(format!("invalid --forge '{}'; expected github|gitlab|gitea", forge).into()),
+    }
+}
+
+fn normalize_format(format: &str) -> Result<&'static str, Box<dyn Error>> {
+    match format {
+        "json" => Ok("json"),
+        "md" | "markdown" => Ok("md"),
+        "html" => Ok("html"),
+        _ => Err(format!("invalid --format '{}'; expected md|html|json", format).into()),
+    }
+}
+
+fn forge_client(
+    forge: &str,
+    url: Option<&str>,
+    token: Option<String>,
+) -> Result<Box<dyn Forge>, Box<dyn Error>> {
+    Ok(match forge {
+        "gitlab" => Box::new(GitlabClient::new(
+            url.unwrap_or("https://gitlab.com"),
+            token,
+        )?),
+        "gitea" => {
+            let url = url.ok_or("--forge gitea requires --forge-url, e.g. https://codeberg.org")?;
+            Box::new(GiteaClient::new(url, token)?)
+        }
+        _ => Box::new(GithubClient::new(url.unwrap_or(GITHUB_API), token)?),
+    })
+}
+
 fn work_item_from_pr(pr: &GithubPull) -> WorkItem {
     let title = pr.title.clone().unwrap_or_default();
     let body = pr.body.clone().unwrap_or_default();
@@ -769,61 +2400,395 @@ fn find_duplicates(
         }
     }
 
-    out.sort_by(|x, y| {
-        y.similarity
-            .partial_cmp(&x.similarity)
-            .unwrap_or(Ordering::Equal)
-    });
+    out.sort_by(|x, y| {
+        y.similarity
+            .partial_cmp(&x.similarity)
+            .unwrap_or(Ordering::Equal)
+    });
+    out
+}
+
+fn candidate_pairs(items: &[WorkItem], max_pair_comparisons: usize) -> Vec<(usize, usize)> {
+    let mut by_title_token: HashMap<&str, Vec<usize>> = HashMap::new();
+
+    for (idx, item) in items.iter().enumerate() {
+        for token in &item.title_token_set {
+            by_title_token.entry(token.as_str()).or_default().push(idx);
+        }
+    }
+
+    let mut pair_votes: HashMap<(usize, usize), u16> = HashMap::new();
+
+    for indexes in by_title_token.values() {
+        if indexes.len() < 2 || indexes.len() > 300 {
+            continue;
+        }
+
+        for i in 0..indexes.len() {
+            for j in (i + 1)..indexes.len() {
+                let a = indexes[i];
+                let b = indexes[j];
+                let key = if a < b { (a, b) } else { (b, a) };
+                let entry = pair_votes.entry(key).or_insert(0);
+                *entry = entry.saturating_add(1);
+            }
+        }
+    }
+
+    let mut pairs: Vec<((usize, usize), u16)> = pair_votes.into_iter().collect();
+    pairs.sort_by(|a, b| b.1.cmp(&a.1));
+
+    if pairs.is_empty() {
+        let mut fallback = Vec::new();
+        for i in 0..items.len() {
+            for j in (i + 1)..items.len() {
+                fallback.push((i, j));
+                if fallback.len() >= max_pair_comparisons {
+                    return fallback;
+                }
+            }
+        }
+        return fallback;
+    }
+
+    pairs
+        .into_iter()
+        .take(max_pair_comparisons)
+        .map(|(pair, _)| pair)
+        .collect()
+}
+
+const MAX_SUGGESTED_REVIEWERS: usize = 3;
+const MAX_BLAMED_PATHS: usize = 10;
+
+/// The clone to read CODEOWNERS and blame from: `--checkout`, or the current
+/// directory when its `origin` is the triaged repo.
+fn local_checkout(args: &TriageArgs) -> Option<PathBuf> {
+    if let Some(path) = args.checkout.as_ref() {
+        return Some(path.clone());
+    }
+    let out = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    let url = String::from_utf8_lossy(&out.stdout)
+        .trim()
+        .trim_end_matches(".git")
+        .to_ascii_lowercase();
+    let repo = args.repo.to_ascii_lowercase();
+    (url.ends_with(&format!("/{repo}")) || url.ends_with(&format!(":{repo}")))
+        .then(|| PathBuf::from("."))
+}
+
+/// Lines per author in files of a local clone, blamed once per path.
+struct BlameStats {
+    root: PathBuf,
+    by_path: HashMap<String, Vec<(String, bool, u64)>>,
+}
+
+impl BlameStats {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            by_path: HashMap::new(),
+        }
+    }
+
+    /// `(author, is_login, lines)`, most lines first.
+    fn authors(&mut self, path: &str) -> &[(String, bool, u64)] {
+        let root = &self.root;
+        self.by_path
+            .entry(path.to_string())
+            .or_insert_with(|| blame_authors(root, path))
+    }
+}
+
+fn blame_authors(root: &Path, path: &str) -> Vec<(String, bool, u64)> {
+    let Ok(out) = Command::new("git")
+        .current_dir(root)
+        .args(["blame", "--line-porcelain", "--", path])
+        .output()
+    else {
+        return Vec::new();
+    };
+    if !out.status.success() {
+        return Vec::new();
+    }
+
+    let mut counts: HashMap<(String, bool), u64> = HashMap::new();
+    let mut name = String::new();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        if let Some(author) = line.strip_prefix("author ") {
+            name = author.to_string();
+        } else if let Some(mail) = line.strip_prefix("author-mail ") {
+            if mail == "<not.committed.yet>" {
+                continue;
+            }
+            let author = match noreply_login(mail) {
+                Some(login) => (login, true),
+                None => (name.clone(), false),
+            };
+            *counts.entry(author).or_default() += 1;
+        }
+    }
+    let mut authors = counts
+        .into_iter()
+        .map(|((who, login), lines)| (who, login, lines))
+        .collect::<Vec<_>>();
+    authors.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+    authors
+}
+
+/// `<123+ada@users.noreply.github.com>` -> `ada`; other addresses say
+/// nothing about the forge login.
+fn noreply_login(mail: &str) -> Option<String> {
+    let local = mail
+        .trim_matches(['<', '>'])
+        .strip_suffix("@users.noreply.github.com")?;
+    let login = local.split_once('+').map_or(local, |(_, login)| login);
+    Some(login.to_string())
+}
+
+/// CODEOWNERS of the changed paths first, by paths owned, then the authors
+/// who last touched most of their lines. The PR author is never suggested.
+fn suggest_reviewers(
+    paths: &[String],
+    author: &str,
+    owners: Option<&CodeOwners>,
+    blame: &mut BlameStats,
+) -> Vec<SuggestedReviewer> {
+    let mut owned: HashMap<String, u64> = HashMap::new();
+    for path in paths {
+        for owner in owners.map(|o| o.owners(path)).unwrap_or(&[]) {
+            *owned.entry(owner.clone()).or_default() += 1;
+        }
+    }
+    let mut blamed: HashMap<(String, bool), u64> = HashMap::new();
+    for path in paths.iter().take(MAX_BLAMED_PATHS) {
+        for (who, login, lines) in blame.authors(path) {
+            *blamed.entry((who.clone(), *login)).or_default() += lines;
+        }
+    }
+
+    let mut by_owner = owned
+        .into_iter()
+        .map(|(owner, paths)| SuggestedReviewer {
+            requestable: !owner.contains('@'),
+            reviewer: owner,
+            source: "codeowners".to_string(),
+            weight: paths,
+        })
+        .collect::<Vec<_>>();
+    let mut by_blame = blamed
+        .into_iter()
+        .map(|((who, login), lines)| SuggestedReviewer {
+            reviewer: who,
+            source: "blame".to_string(),
+            weight: lines,
+            requestable: login,
+        })
+        .collect::<Vec<_>>();
+    for list in [&mut by_owner, &mut by_blame] {
+        list.sort_by(|a, b| b.weight.cmp(&a.weight).then(a.reviewer.cmp(&b.reviewer)));
+    }
+
+    let mut out: Vec<SuggestedReviewer> = Vec::new();
+    for suggestion in by_owner.into_iter().chain(by_blame) {
+        let taken = out
+            .iter()
+            .any(|s| s.reviewer.eq_ignore_ascii_case(&suggestion.reviewer));
+        if !taken && !suggestion.reviewer.eq_ignore_ascii_case(author) {
+            out.push(suggestion);
+        }
+        if out.len() == MAX_SUGGESTED_REVIEWERS {
+            break;
+        }
+    }
+    out
+}
+
+/// Open items idle for `stale_days`, and open PRs whose change requests have
+/// gone unanswered for half that: nothing happened on the PR after the
+/// review. Longest idle first.
+fn find_stale_items(
+    pulls: &[GithubPull],
+    issues: &[GithubIssue],
+    scored: &[PrScoreReport],
+    stale_days: i64,
+    now: DateTime<Utc>,
+) -> Vec<StaleItem> {
+    if stale_days <= 0 {
+        return Vec::new();
+    }
+    let requested_at: HashMap<u64, DateTime<Utc>> = scored
+        .iter()
+        .filter_map(|pr| {
+            let at = pr.signals.changes_requested_at.as_deref()?;
+            Some((pr.number, parse_date(at)?))
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    let open_pulls = pulls
+        .iter()
+        .filter(|pr| pr.state.as_deref().unwrap_or("open") == "open")
+        .map(work_item_from_pr);
+    let open_issues = issues
+        .iter()
+        .filter(|i| i.pull_request.is_none() && i.state.as_deref().unwrap_or("open") == "open")
+        .map(work_item_from_issue);
+    for item in open_pulls.chain(open_issues) {
+        let Some(updated) = parse_date(&item.updated_at) else {
+            continue;
+        };
+        let idle_days = (now - updated).num_days();
+        let awaiting_author = item.kind == ItemKind::PullRequest
+            && requested_at.get(&item.number).is_some_and(|at| {
+                updated <= *at + Duration::hours(1) && (now - *at).num_days() >= stale_days / 2
+            });
+        let reason = if awaiting_author {
+            "awaiting_author"
+        } else if idle_days >= stale_days {
+            "inactive"
+        } else {
+            continue;
+        };
+        out.push(StaleItem {
+            kind: item.kind,
+            number: item.number,
+            title: item.title,
+            url: item.url,
+            author: item.author,
+            updated_at: item.updated_at,
+            idle_days,
+            reason: reason.to_string(),
+        });
+    }
+    out.sort_by_key(|s| std::cmp::Reverse(s.idle_days));
     out
 }
 
-fn candidate_pairs(items: &[WorkItem], max_pair_comparisons: usize) -> Vec<(usize, usize)> {
-    let mut by_title_token: HashMap<&str, Vec<usize>> = HashMap::new();
+const TITLE_LINK_THRESHOLD: f64 = 0.6;
 
-    for (idx, item) in items.iter().enumerate() {
-        for token in &item.title_token_set {
+/// Groups open PRs under the fetched issues they reference with a closing
+/// keyword or whose titles closely match. Issues with several PRs, where
+/// effort is being duplicated, come first.
+fn find_issue_pr_links(
+    items: &[WorkItem],
+    open_prs: &HashSet<u64>,
+    repo: &str,
+) -> Vec<IssuePrLink> {
+    let issues = items
+        .iter()
+        .filter(|item| item.kind == ItemKind::Issue)
+        .collect::<Vec<_>>();
+    let by_number: HashMap<u64, usize> = issues
+        .iter()
+        .enumerate()
+        .map(|(idx, issue)| (issue.number, idx))
+        .collect();
+    let mut by_title_token: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, issue) in issues.iter().enumerate() {
+        for token in &issue.title_token_set {
             by_title_token.entry(token.as_str()).or_default().push(idx);
         }
     }
 
-    let mut pair_votes: HashMap<(usize, usize), u16> = HashMap::new();
-
-    for indexes in by_title_token.values() {
-        if indexes.len() < 2 || indexes.len() > 300 {
-            continue;
-        }
-
-        for i in 0..indexes.len() {
-            for j in (i + 1)..indexes.len() {
-                let a = indexes[i];
-                let b = indexes[j];
-                let key = if a < b { (a, b) } else { (b, a) };
-                let entry = pair_votes.entry(key).or_insert(0);
-                *entry = entry.saturating_add(1);
+    let mut linked: Vec<Vec<LinkedPr>> = vec![Vec::new(); issues.len()];
+    let prs = items
+        .iter()
+        .filter(|item| item.kind == ItemKind::PullRequest && open_prs.contains(&item.number));
+    for pr in prs {
+        let referenced: HashSet<u64> =
+            closing_references(&format!("{}\n{}", pr.title, pr.body), repo)
+                .into_iter()
+                .collect();
+
+        let mut shared_tokens: HashMap<usize, usize> = HashMap::new();
+        for token in &pr.title_token_set {
+            for &idx in by_title_token.get(token.as_str()).into_iter().flatten() {
+                *shared_tokens.entry(idx).or_default() += 1;
             }
         }
-    }
 
-    let mut pairs: Vec<((usize, usize), u16)> = pair_votes.into_iter().collect();
-    pairs.sort_by(|a, b| b.1.cmp(&a.1));
-
-    if pairs.is_empty() {
-        let mut fallback = Vec::new();
-        for i in 0..items.len() {
-            for j in (i + 1)..items.len() {
-                fallback.push((i, j));
-                if fallback.len() >= max_pair_comparisons {
-                    return fallback;
-                }
-            }
+        let mut targets = referenced
+            .iter()
+            .filter_map(|number| by_number.get(number).copied())
+            .chain(
+                shared_tokens
+                    .into_iter()
+                    .filter(|(_, shared)| *shared >= 2)
+                    .map(|(idx, _)| idx),
+            )
+            .collect::<Vec<_>>();
+        targets.sort_unstable();
+        targets.dedup();
+
+        for idx in targets {
+            let issue = issues[idx];
+            let title_similarity = dice_coefficient(&pr.title, &issue.title);
+            let via = if referenced.contains(&issue.number) {
+                "reference"
+            } else if title_similarity >= TITLE_LINK_THRESHOLD {
+                "title"
+            } else {
+                continue;
+            };
+            linked[idx].push(LinkedPr {
+                number: pr.number,
+                title: pr.title.clone(),
+                url: pr.url.clone(),
+                via: via.to_string(),
+                title_similarity,
+            });
         }
-        return fallback;
     }
 
-    pairs
+    let mut out = issues
         .into_iter()
-        .take(max_pair_comparisons)
-        .map(|(pair, _)| pair)
+        .zip(linked)
+        .filter(|(_, prs)| !prs.is_empty())
+        .map(|(issue, mut prs)| {
+            prs.sort_by_key(|pr| pr.number);
+            IssuePrLink {
+                issue_number: issue.number,
+                issue_title: issue.title.clone(),
+                issue_url: issue.url.clone(),
+                prs,
+            }
+        })
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| {
+        b.prs
+            .len()
+            .cmp(&a.prs.len())
+            .then(a.issue_number.cmp(&b.issue_number))
+    });
+    out
+}
+
+/// Issues a PR says it closes: `fixes #12`, `Closes: owner/repo#12` or
+/// `resolves https://host/owner/repo/issues/12`. References to other
+/// repositories are dropped.
+fn closing_references(text: &str, repo: &str) -> Vec<u64> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+",
+            r"(?:([\w.\-]+(?:/[\w.\-]+)+)?#(\d+)",
+            r"|https?://[^\s/]+/([\w.\-]+(?:/[\w.\-]+)+?)(?:/-)?/issues/(\d+))"
+        ))
+        .expect("closing reference regex")
+    });
+
+    re.captures_iter(text)
+        .filter_map(|caps| {
+            let other_repo = caps.get(1).or_else(|| caps.get(3));
+            if other_repo.is_some_and(|r| !r.as_str().eq_ignore_ascii_case(repo)) {
+                return None;
+            }
+            caps.get(2).or_else(|| caps.get(4))?.as_str().parse().ok()
+        })
         .collect()
 }
 
@@ -887,6 +2852,7 @@ fn score_pr(
 
     let mut approvals = 0;
     let mut change_requests = 0;
+    let mut changes_requested_at = None;
     let mut ci_state = None;
     let mut mergeable_state = pr.mergeable_state.clone();
     let mut changed_paths = Vec::new();
@@ -894,6 +2860,7 @@ fn score_pr(
     if let Some(ds) = deep {
         approvals = ds.approvals;
         change_requests = ds.change_requests;
+        changes_requested_at = ds.changes_requested_at;
         ci_state = ds.ci_state;
         changed_paths = ds.changed_paths;
     }
@@ -1000,15 +2967,19 @@ fn score_pr(
             mergeable_state,
             approvals,
             change_requests,
+            changes_requested_at,
             ci_state,
             vision_alignment,
         },
+        suggested_reviewers: Vec::new(),
     }
 }
 
 fn build_action_plan(
     scored: &[PrScoreReport],
     duplicates: &[DuplicatePair],
+    links: &[IssuePrLink],
+    stale: &[StaleItem],
     args: &TriageArgs,
 ) -> Vec<TriageAction> {
     let mut actions = Vec::new();
@@ -1017,6 +2988,21 @@ fn build_action_plan(
     let pr_scores: HashMap<u64, f64> = scored.iter().map(|p| (p.number, p.score)).collect();
 
     for pr in scored {
+        if args.request_reviews && !pr.signals.draft {
+            for suggestion in pr.suggested_reviewers.iter().filter(|r| r.requestable) {
+                actions.push(TriageAction {
+                    item_kind: ItemKind::PullRequest,
+                    item_number: pr.number,
+                    item_url: pr.url.clone(),
+                    action_type: "request_review".to_string(),
+                    value: suggestion.reviewer.clone(),
+                    reason: format!("Suggested by {}", suggestion.source),
+                    status: "planned".to_string(),
+                    error: None,
+                });
+            }
+        }
+
         if pr.decision == "priority_review" {
             push_label_action(
                 &mut actions,
@@ -1102,6 +3088,84 @@ fn build_action_plan(
         }
     }
 
+    // A single PR that references its issue is already linked by the forge.
+    for link in links {
+        let title_only = link.prs.iter().any(|pr| pr.via == "title");
+        if !args.comment_actions || (link.prs.len() < 2 && !title_only) {
+            continue;
+        }
+        if !uniq_comment_keys.insert((ItemKind::Issue, link.issue_number)) {
+            continue;
+        }
+        let prs = link
+            .prs
+            .iter()
+            .map(|pr| format!("#{} ({})", pr.number, pr.url))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let value = if link.prs.len() > 1 {
+            format!(
+                "Triage note: {} open PRs address this issue: {}. Consider consolidating the work in one of them.",
+                link.prs.len(),
+                prs
+            )
+        } else {
+            format!("Triage note: this issue may be addressed by PR {}.", prs)
+        };
+        actions.push(TriageAction {
+            item_kind: ItemKind::Issue,
+            item_number: link.issue_number,
+            item_url: link.issue_url.clone(),
+            action_type: "comment".to_string(),
+            value,
+            reason: "Linked PRs".to_string(),
+            status: "planned".to_string(),
+            error: None,
+        });
+    }
+
+    for item in stale {
+        let reason = match item.reason.as_str() {
+            "awaiting_author" => format!(
+                "Changes requested, no follow-up for {} days",
+                item.idle_days
+            ),
+            _ => format!("No activity for {} days", item.idle_days),
+        };
+        push_label_action(
+            &mut actions,
+            &mut uniq_label_keys,
+            item.kind.clone(),
+            item.number,
+            item.url.clone(),
+            args.label_stale.clone(),
+            reason,
+        );
+
+        if args.comment_actions && uniq_comment_keys.insert((item.kind.clone(), item.number)) {
+            let value = match item.reason.as_str() {
+                "awaiting_author" => format!(
+                    "Triage note: changes were requested {} days ago with no follow-up. @{}, are you still working on this? Otherwise it is a candidate for closing.",
+                    item.idle_days, item.author
+                ),
+                _ => format!(
+                    "Triage note: no activity for {} days. This is a candidate for closing unless someone picks it up.",
+                    item.idle_days
+                ),
+            };
+            actions.push(TriageAction {
+                item_kind: item.kind.clone(),
+                item_number: item.number,
+                item_url: item.url.clone(),
+                action_type: "comment".to_string(),
+                value,
+                reason: "Close candidate".to_string(),
+                status: "planned".to_string(),
+                error: None,
+            });
+        }
+    }
+
     actions
 }
 
@@ -1183,7 +3247,7 @@ fn push_label_action(
 }
 
 fn apply_actions(
-    gh: &GithubClient,
+    forge: &dyn Forge,
     repo: &str,
     actions: &mut [TriageAction],
     action_limit: usize,
@@ -1192,8 +3256,16 @@ fn apply_actions(
 
     for action in actions.iter_mut().take(action_limit) {
         let result = match action.action_type.as_str() {
-            "label" => gh.add_labels(repo, action.item_number, &[action.value.clone()]),
-            "comment" => gh.add_comment(repo, action.item_number, &action.value),
+            "label" => forge.add_labels(
+                repo,
+                &action.item_kind,
+                action.item_number,
+                &[action.value.clone()],
+            ),
+            "comment" => {
+                forge.add_comment(repo, &action.item_kind, action.item_number, &action.value)
+            }
+            "request_review" => forge.request_review(repo, action.item_number, &action.value),
             _ => Err("unknown action type".into()),
         };
 
@@ -1242,6 +3314,171 @@ fn save_state_file(path: &Path, value: &TriageState) -> Result<(), Box<dyn Error
     Ok(())
 }
 
+fn load_item_snapshot(path: &Path) -> Result<ItemSnapshot, Box<dyn Error>> {
+    let raw = fs::read_to_string(path)?;
+    let value: serde_json::Value = serde_json::from_str(&raw)
+        .map_err(|e| format!("invalid snapshot {}: {e}", path.display()))?;
+    parse_item_snapshot(value)
+        .map_err(|e| format!("invalid snapshot {}: {e}", path.display()).into())
+}
+
+/// Accepts either an exported `{ "pulls": [...], "issues": [...] }` snapshot or
+/// a bare array as printed by `gh pr list --json ...` / `gh issue list --json ...`.
+fn parse_item_snapshot(value: serde_json::Value) -> Result<ItemSnapshot, serde_json::Error> {
+    use serde_json::Value;
+
+    let (pulls, issues, mut rest) = match value {
+        Value::Array(items) => {
+            let (pulls, issues): (Vec<Value>, Vec<Value>) =
+                items.into_iter().partition(looks_like_pull);
+            (pulls, issues, serde_json::Map::new())
+        }
+        Value::Object(mut map) => {
+            let take = |map: &mut serde_json::Map<String, Value>, key: &str| match map.remove(key) {
+                Some(Value::Array(items)) => items,
+                _ => Vec::new(),
+            };
+            let pulls = take(&mut map, "pulls");
+            let issues = take(&mut map, "issues");
+            (pulls, issues, map)
+        }
+        _ => (Vec::new(), Vec::new(), serde_json::Map::new()),
+    };
+
+    rest.insert(
+        "pulls".to_string(),
+        Value::Array(pulls.into_iter().map(normalize_gh_cli_item).collect()),
+    );
+    rest.insert(
+        "issues".to_string(),
+        Value::Array(issues.into_iter().map(normalize_gh_cli_item).collect()),
+    );
+    serde_json::from_value(Value::Object(rest))
+}
+
+fn looks_like_pull(item: &serde_json::Value) -> bool {
+    [
+        "head",
+        "headRefOid",
+        "headRefName",
+        "isDraft",
+        "draft",
+        "mergeStateStatus",
+    ]
+    .iter()
+    .any(|key| item.get(key).is_some())
+}
+
+/// Maps gh CLI camelCase fields onto the REST field names the pipeline uses.
+fn normalize_gh_cli_item(item: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    let Value::Object(mut map) = item else {
+        return item;
+    };
+
+    for (from, to) in [
+        ("url", "html_url"),
+        ("author", "user"),
+        ("createdAt", "created_at"),
+        ("updatedAt", "updated_at"),
+        ("isDraft", "draft"),
+        ("changedFiles", "changed_files"),
+        ("mergeStateStatus", "mergeable_state"),
+    ] {
+        if !map.contains_key(to) {
+            if let Some(value) = map.remove(from) {
+                map.insert(to.to_string(), value);
+            }
+        }
+    }
+
+    if !map.contains_key("head") {
+        if let Some(sha) = map.remove("headRefOid") {
+            map.insert("head".to_string(), serde_json::json!({ "sha": sha }));
+        }
+    }
+    if let Some(Value::Array(comments)) = map.get("comments") {
+        let count = comments.len();
+        map.insert("comments".to_string(), Value::from(count));
+    }
+    for key in ["state", "mergeable_state"] {
+        if let Some(Value::String(value)) = map.get(key) {
+            let lowered = match value.to_ascii_lowercase().as_str() {
+                "merged" => "closed".to_string(),
+                other => other.to_string(),
+            };
+            map.insert(key.to_string(), Value::String(lowered));
+        }
+    }
+
+    Value::Object(map)
+}
+
+fn snapshot_matches(
+    item_state: Option<&str>,
+    updated_at: Option<&str>,
+    state: &str,
+    since: Option<&DateTime<Utc>>,
+) -> bool {
+    let state_ok = state == "all" || item_state.is_none_or(|s| s == state);
+    let since_ok = match (since, updated_at.and_then(parse_date)) {
+        (Some(since), Some(updated)) => updated > *since,
+        _ => true,
+    };
+    state_ok && since_ok
+}
+
+fn snapshot_pulls(
+    snapshot: &ItemSnapshot,
+    state: &str,
+    limit: usize,
+    since: Option<&String>,
+) -> Vec<GithubPull> {
+    let since_dt = since.and_then(|s| parse_date(s));
+    let mut out = snapshot
+        .pulls
+        .iter()
+        .filter(|pr| {
+            snapshot_matches(
+                pr.state.as_deref(),
+                pr.updated_at.as_deref(),
+                state,
+                since_dt.as_ref(),
+            )
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    out.truncate(limit);
+    out
+}
+
+fn snapshot_issues(
+    snapshot: &ItemSnapshot,
+    state: &str,
+    limit: usize,
+    since: Option<&String>,
+) -> Vec<GithubIssue> {
+    let since_dt = since.and_then(|s| parse_date(s));
+    let mut out = snapshot
+        .issues
+        .iter()
+        .filter(|issue| {
+            snapshot_matches(
+                issue.state.as_deref(),
+                issue.updated_at.as_deref(),
+                state,
+                since_dt.as_ref(),
+            )
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    out.truncate(limit);
+    out
+}
+
 fn resolve_since(
     args: &TriageArgs,
     persisted: Option<&TriageState>,
@@ -1289,7 +3526,7 @@ fn latest_seen_updated_at(pulls: &[GithubPull], issues: &[GithubIssue]) -> Optio
     latest.map(|d| d.to_rfc3339())
 }
 
-fn tokenize(text: &str) -> HashSet<String> {
+pub fn tokenize(text: &str) -> HashSet<String> {
     text.to_ascii_lowercase()
         .split(|c: char| !c.is_ascii_alphanumeric())
         .filter(|t| t.len() >= 3)
@@ -1363,7 +3600,7 @@ fn is_stopword(token: &str) -> bool {
     )
 }
 
-fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+pub fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
     if a.is_empty() && b.is_empty() {
         return 1.0;
     }
@@ -1566,4 +3803,659 @@ mod tests {
         let pairs = candidate_pairs(&items, 100);
         assert!(!pairs.is_empty());
     }
+
+    #[test]
+    fn parse_item_snapshot_accepts_gh_cli_arrays() {
+        let value = serde_json::json!([
+            {
+                "number": 7,
+                "title": "Add retry to uploader",
+                "url": "https://github.com/a/b/pull/7",
+                "author": { "login": "octo" },
+                "createdAt": "2024-01-01T00:00:00Z",
+                "updatedAt": "2024-01-03T00:00:00Z",
+                "isDraft": false,
+                "headRefOid": "abc123",
+                "mergeStateStatus": "CLEAN",
+                "state": "MERGED",
+                "comments": [{ "body": "lgtm" }]
+            },
+            {
+                "number": 8,
+                "title": "Uploader does not retry",
+                "url": "https://github.com/a/b/issues/8",
+                "state": "OPEN",
+                "updatedAt": "2024-01-02T00:00:00Z"
+            }
+        ]);
+
+        let snapshot = parse_item_snapshot(value).unwrap();
+        assert_eq!(snapshot.pulls.len(), 1);
+        assert_eq!(snapshot.issues.len(), 1);
+
+        let pr = &snapshot.pulls[0];
+        assert_eq!(
+            pr.html_url.as_deref(),
+            Some("https://github.com/a/b/pull/7")
+        );
+        assert_eq!(
+            pr.user.as_ref().and_then(|u| u.login.as_deref()),
+            Some("octo")
+        );
+        assert_eq!(
+            pr.head.as_ref().and_then(|h| h.sha.as_deref()),
+            Some("abc123")
+        );
+        assert_eq!(pr.mergeable_state.as_deref(), Some("clean"));
+        assert_eq!(pr.state.as_deref(), Some("closed"));
+        assert_eq!(pr.comments, Some(1));
+
+        assert!(snapshot_pulls(&snapshot, "open", 10, None).is_empty());
+        assert_eq!(snapshot_issues(&snapshot, "open", 10, None).len(), 1);
+        let since = "2024-01-02T12:00:00Z".to_string();
+        assert!(snapshot_issues(&snapshot, "all", 10, Some(&since)).is_empty());
+    }
+
+    fn test_args(extra: &[&str]) -> TriageArgs {
+        #[derive(clap::Parser)]
+        struct TestCli {
+            #[command(flatten)]
+            args: TriageArgs,
+        }
+
+        let mut argv = vec!["triage", "--repo", "acme/widgets", "--json-only"];
+        argv.extend_from_slice(extra);
+        <TestCli as clap::Parser>::parse_from(argv).args
+    }
+
+    #[test]
+    fn pipeline_runs_against_snapshot_forge_and_records_actions() {
+        let root = std::env::temp_dir().join(format!("osmogrep-triage-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let state_file = root.join("state.json");
+
+        let snapshot = parse_item_snapshot(serde_json::json!({
+            "pulls": [{
+                "number": 10,
+                "state": "open",
+                "title": "Retry failed uploads with exponential backoff",
+                "body": "Uploads that fail are retried with exponential backoff.",
+                "html_url": "https://github.com/acme/widgets/pull/10",
+                "user": { "login": "alice" },
+                "created_at": "2024-03-01T00:00:00Z",
+                "updated_at": "2024-03-02T00:00:00Z",
+                "head": { "sha": "deadbeef" }
+            }],
+            "issues": [
+                {
+                    "number": 11,
+                    "state": "open",
+                    "title": "Retry failed uploads with exponential backoff",
+                    "body": "Uploads that fail should be retried with exponential backoff.",
+                    "html_url": "https://github.com/acme/widgets/issues/11",
+                    "user": { "login": "bob" },
+                    "created_at": "2024-03-01T00:00:00Z",
+                    "updated_at": "2024-03-01T12:00:00Z"
+                },
+                {
+                    "number": 12,
+                    "state": "closed",
+                    "title": "Dark mode for settings page",
+                    "html_url": "https://github.com/acme/widgets/issues/12",
+                    "updated_at": "2024-03-01T00:00:00Z"
+                }
+            ],
+            "deep_signals": {
+                "10": { "approvals": 2, "change_requests": 0, "ci_state": "success", "changed_paths": ["src/upload.rs"] }
+            }
+        }))
+        .unwrap();
+        let forge = SnapshotForge::new(snapshot);
+
+        let args = test_args(&[
+            "--apply-actions",
+            "--state-file",
+            state_file.to_str().unwrap(),
+        ]);
+        let report = run_with_forge(&args, &forge).unwrap();
+
+        assert_eq!(report.scanned_prs, 1);
+        assert_eq!(report.scanned_issues, 1);
+        assert_eq!(report.duplicate_pairs.len(), 1);
+        assert_eq!(report.ranked_prs[0].signals.approvals, 2);
+        assert_eq!(report.applied_action_count, report.planned_actions.len());
+
+        let recorded = forge.recorded_actions();
+        assert!(recorded
+            .iter()
+            .any(|a| a.action_type == "label" && a.value == "triage:duplicate-candidate"));
+        assert!(state_file.exists());
+
+        // Only full runs land in the trend: an incremental run sees just
+        // the items updated since the last one.
+        let trend_len = |extra: &[&str]| {
+            let mut argv = vec!["--trend", "--state-file", state_file.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            run_with_forge(&test_args(&argv), &forge)
+                .unwrap()
+                .trend
+                .len()
+        };
+        assert_eq!(trend_len(&[]), 1);
+        assert_eq!(trend_len(&["--incremental"]), 1);
+        assert_eq!(trend_len(&[]), 2);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    /// Serves a snapshot, failing the deep review of `fail_on`.
+    struct FailingDeepForge {
+        inner: SnapshotForge,
+        fail_on: u64,
+        fetched: Mutex<Vec<u64>>,
+    }
+
+    impl Forge for FailingDeepForge {
+        fn fetch_pulls(
+            &self,
+            repo: &str,
+            state: &str,
+            limit: usize,
+            since: Option<&String>,
+        ) -> Result<Vec<GithubPull>, Box<dyn Error>> {
+            self.inner.fetch_pulls(repo, state, limit, since)
+        }
+
+        fn fetch_issues(
+            &self,
+            repo: &str,
+            state: &str,
+            limit: usize,
+            since: Option<&String>,
+        ) -> Result<Vec<GithubIssue>, Box<dyn Error>> {
+            self.inner.fetch_issues(repo, state, limit, since)
+        }
+
+        fn fetch_deep_signals(
+            &self,
+            repo: &str,
+            pr_number: u64,
+            head_sha: Option<String>,
+        ) -> Result<Option<DeepSignals>, Box<dyn Error>> {
+            self.fetched.lock().unwrap().push(pr_number);
+            if pr_number == self.fail_on {
+                return Err("403 rate limit exceeded".into());
+            }
+            self.inner.fetch_deep_signals(repo, pr_number, head_sha)
+        }
+
+        fn add_labels(
+            &self,
+            repo: &str,
+            kind: &ItemKind,
+            number: u64,
+            labels: &[String],
+        ) -> Result<(), Box<dyn Error>> {
+            self.inner.add_labels(repo, kind, number, labels)
+        }
+
+        fn add_comment(
+            &self,
+            repo: &str,
+            kind: &ItemKind,
+            number: u64,
+            body: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            self.inner.add_comment(repo, kind, number, body)
+        }
+
+        fn request_review(
+            &self,
+            repo: &str,
+            number: u64,
+            reviewer: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            self.inner.request_review(repo, number, reviewer)
+        }
+    }
+
+    fn deep_review_snapshot() -> ItemSnapshot {
+        let pulls = (1..=6)
+            .map(|n| serde_json::json!({ "number": n, "title": format!("PR {n}") }))
+            .collect::<Vec<_>>();
+        // #4 has no deep signals: it counts as done but is not scored.
+        let deep_signals = [1, 2, 3, 5, 6]
+            .into_iter()
+            .map(|n: u64| {
+                (
+                    n.to_string(),
+                    serde_json::json!({ "approvals": n, "change_requests": 0, "changed_paths": [] }),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+        parse_item_snapshot(serde_json::json!({ "pulls": pulls, "deep_signals": deep_signals }))
+            .unwrap()
+    }
+
+    #[test]
+    fn deep_signals_stream_in_as_they_arrive_and_stop_at_the_first_error() {
+        let forge = SnapshotForge::new(deep_review_snapshot());
+        let pulls = forge.snapshot.pulls.clone();
+        let targets = pulls.iter().collect::<Vec<_>>();
+
+        let mut seen = Vec::new();
+        fetch_deep_signals_parallel(&forge, "acme/widgets", &targets, 1, |pr, deep, done| {
+            assert_eq!(deep.approvals, pr.number);
+            seen.push((pr.number, done));
+        })
+        .unwrap();
+        assert_eq!(seen, vec![(1, 1), (2, 2), (3, 3), (5, 5), (6, 6)]);
+
+        let mut seen = Vec::new();
+        fetch_deep_signals_parallel(&forge, "acme/widgets", &targets, 4, |pr, _, done| {
+            seen.push((pr.number, done));
+        })
+        .unwrap();
+        assert!(seen.windows(2).all(|w| w[0].1 < w[1].1));
+        let mut numbers = seen.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+        numbers.sort();
+        assert_eq!(numbers, vec![1, 2, 3, 5, 6]);
+
+        let failing = FailingDeepForge {
+            inner: SnapshotForge::new(deep_review_snapshot()),
+            fail_on: 3,
+            fetched: Mutex::new(Vec::new()),
+        };
+        let mut seen = Vec::new();
+        let err = fetch_deep_signals_parallel(&failing, "acme/widgets", &targets, 1, |pr, _, _| {
+            seen.push(pr.number)
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "deep review of #3 failed: 403 rate limit exceeded"
+        );
+        assert_eq!(seen, vec![1, 2]);
+        assert_eq!(*failing.fetched.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn sparkline_scales_between_min_and_max() {
+        assert_eq!(sparkline(&[1.0, 5.0, 9.0]), "▁▅█");
+        assert_eq!(sparkline(&[3.0, 3.0]), "▅▅");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn push_snapshot_caps_history_and_old_state_files_still_load() {
+        let legacy: TriageState = serde_json::from_str(
+            r#"{"repo":"a/b","state":"open","last_run_at":null,"last_seen_updated_at":null}"#,
+        )
+        .unwrap();
+        let mut history = legacy.history;
+        assert!(history.is_empty());
+
+        for i in 0..(MAX_TREND_HISTORY + 3) {
+            push_snapshot(
+                &mut history,
+                TriageSnapshot {
+                    run_at: i.to_string(),
+                    incremental: false,
+                    open_prs: i,
+                    open_issues: 0,
+                    duplicate_pairs: 0,
+                    avg_pr_score: 0.0,
+                },
+            );
+        }
+        assert_eq!(history.len(), MAX_TREND_HISTORY);
+        assert_eq!(history[0].open_prs, 3);
+    }
+
+    #[test]
+    fn gitlab_and_gitea_items_map_onto_github_shapes() {
+        let mr: GitlabMergeRequest = serde_json::from_value(serde_json::json!({
+            "iid": 42,
+            "state": "merged",
+            "title": "Fix parser",
+            "description": "Closes #7",
+            "web_url": "https://gitlab.example.com/g/sub/p/-/merge_requests/42",
+            "author": { "username": "ada" },
+            "updated_at": "2026-10-01T10:00:00Z",
+            "user_notes_count": 3,
+            "detailed_merge_status": "conflict",
+            "sha": "abc123",
+            "head_pipeline": { "status": "failed" }
+        }))
+        .unwrap();
+        assert_eq!(
+            mr.head_pipeline
+                .as_ref()
+                .and_then(|p| p.status.as_deref())
+                .and_then(gitlab_ci_state)
+                .as_deref(),
+            Some("failure")
+        );
+        let pr = GithubPull::from(mr);
+        assert_eq!(pr.number, 42);
+        assert_eq!(pr.state.as_deref(), Some("closed"));
+        assert_eq!(pr.body.as_deref(), Some("Closes #7"));
+        assert_eq!(pr.user.and_then(|u| u.login).as_deref(), Some("ada"));
+        assert_eq!(pr.mergeable_state.as_deref(), Some("dirty"));
+        assert_eq!(pr.comments, Some(3));
+        assert_eq!(pr.head.and_then(|h| h.sha).as_deref(), Some("abc123"));
+        assert_eq!(gitlab_mergeable_state("checking"), None);
+        assert_eq!(
+            gitlab_mergeable_state("not_approved").as_deref(),
+            Some("blocked")
+        );
+
+        let client = GitlabClient::new("https://gitlab.example.com/", None).unwrap();
+        assert_eq!(
+            client.item_url("g/sub/p", &ItemKind::Issue, 7),
+            "https://gitlab.example.com/api/v4/projects/g%2Fsub%2Fp/issues/7"
+        );
+
+        let gitea: GiteaPull = serde_json::from_value(serde_json::json!({
+            "number": 5,
+            "state": "open",
+            "title": "Add docs",
+            "user": { "login": "lin" },
+            "mergeable": true,
+            "head": { "sha": "def456" }
+        }))
+        .unwrap();
+        let pr = GithubPull::from(gitea);
+        assert_eq!(pr.number, 5);
+        assert_eq!(pr.mergeable_state.as_deref(), Some("clean"));
+
+        let review = |user: &str, state: &str, at: &str| GithubReview {
+            user: Some(GithubUser {
+                login: Some(user.to_string()),
+            }),
+            state: Some(state.to_string()),
+            submitted_at: Some(at.to_string()),
+        };
+        let reviews = vec![
+            review("a", "REQUEST_CHANGES", "2026-01-01T00:00:00Z"),
+            review("a", "APPROVED", "2026-01-02T00:00:00Z"),
+            review("b", "REQUEST_CHANGES", "2026-01-03T00:00:00Z"),
+        ];
+        assert_eq!(
+            review_counts(reviews),
+            (1, 1, Some("2026-01-03T00:00:00Z".to_string()))
+        );
+    }
+
+    #[test]
+    fn forge_selection_validates_the_kind_and_gitea_url() {
+        assert!(normalize_forge("bitbucket").is_err());
+        assert_eq!(normalize_forge("gitlab").unwrap(), "gitlab");
+        let err = forge_client("gitea", None, None).err().unwrap();
+        assert!(err.to_string().contains("--forge-url"));
+        assert!(forge_client("gitea", Some("https://codeberg.org"), None).is_ok());
+    }
+
+    #[test]
+    fn closing_references_keep_only_this_repository() {
+        let text = "Fixes #12, closes: acme/widgets#13 and resolves other/repo#14.\n\
+                    Resolved https://gitlab.example.com/acme/widgets/-/issues/15\n\
+                    See #16; prefix#17 is not a keyword.";
+        assert_eq!(closing_references(text, "acme/widgets"), vec![12, 13, 15]);
+    }
+
+    #[test]
+    fn links_open_prs_to_issues_and_plans_a_cross_link_comment() {
+        let snapshot = parse_item_snapshot(serde_json::json!({
+            "pulls": [
+                { "number": 20, "state": "open", "title": "Handle empty config files",
+                  "body": "Fixes #5", "html_url": "https://github.com/acme/widgets/pull/20" },
+                { "number": 21, "state": "open", "title": "Crash when the config file is empty",
+                  "html_url": "https://github.com/acme/widgets/pull/21" },
+                { "number": 22, "state": "closed", "title": "Old attempt", "body": "fixes #5" },
+                { "number": 23, "state": "open", "title": "Speed up startup", "body": "closes #6" }
+            ],
+            "issues": [
+                { "number": 5, "state": "open", "title": "Crash when the config file is empty",
+                  "html_url": "https://github.com/acme/widgets/issues/5" },
+                { "number": 6, "state": "open", "title": "Startup is slow",
+                  "html_url": "https://github.com/acme/widgets/issues/6" }
+            ]
+        }))
+        .unwrap();
+        let forge = SnapshotForge::new(snapshot);
+        let state_dir =
+            std::env::temp_dir().join(format!("osmogrep-triage-{}", uuid::Uuid::new_v4()));
+        let state_file = state_dir.join("state.json");
+        let args = test_args(&[
+            "--state",
+            "all",
+            "--comment-actions",
+            "--state-file",
+            state_file.to_str().unwrap(),
+        ]);
+        let report = run_with_forge(&args, &forge).unwrap();
+
+        let links = &report.issue_pr_links;
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].issue_number, 5);
+        let linked = links[0]
+            .prs
+            .iter()
+            .map(|pr| (pr.number, pr.via.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(linked, vec![(20, "reference"), (21, "title")]);
+        assert_eq!(links[1].issue_number, 6);
+
+        let comments = report
+            .planned_actions
+            .iter()
+            .filter(|a| a.action_type == "comment" && a.reason == "Linked PRs")
+            .collect::<Vec<_>>();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].item_number, 5);
+        assert!(comments[0].value.contains("2 open PRs"));
+
+        let _ = fs::remove_dir_all(state_dir);
+    }
+
+    #[test]
+    fn stale_items_cover_idle_items_and_unanswered_change_requests() {
+        let now = parse_date("2026-06-01T00:00:00Z").unwrap();
+        let pull = |number: u64, updated: &str| GithubPull {
+            number,
+            state: Some("open".to_string()),
+            title: Some(format!("PR {number}")),
+            body: None,
+            html_url: None,
+            user: Some(GithubUser {
+                login: Some("ada".to_string()),
+            }),
+            created_at: None,
+            updated_at: Some(updated.to_string()),
+            draft: None,
+            comments: None,
+            review_comments: None,
+            additions: None,
+            deletions: None,
+            changed_files: None,
+            mergeable_state: None,
+            head: None,
+        };
+        let pulls = vec![
+            pull(1, "2026-05-30T00:00:00Z"),
+            pull(2, "2026-05-01T00:00:00Z"),
+            pull(3, "2026-05-01T00:00:00Z"),
+        ];
+        let issues: Vec<GithubIssue> = serde_json::from_value(serde_json::json!([
+            { "number": 4, "state": "open", "title": "Old", "updated_at": "2026-01-01T00:00:00Z" },
+            { "number": 5, "state": "closed", "title": "Done", "updated_at": "2026-01-01T00:00:00Z" }
+        ]))
+        .unwrap();
+        let requested = |at: &str| DeepSignals {
+            approvals: 0,
+            change_requests: 1,
+            changes_requested_at: Some(at.to_string()),
+            ci_state: None,
+            changed_paths: Vec::new(),
+        };
+        let scored = vec![
+            score_pr(&pulls[1], Some(requested("2026-05-01T00:00:00Z")), None),
+            // The author pushed after this review.
+            score_pr(&pulls[2], Some(requested("2026-04-20T00:00:00Z")), None),
+        ];
+
+        let stale = find_stale_items(&pulls, &issues, &scored, 60, now);
+        let found = stale
+            .iter()
+            .map(|item| (item.number, item.reason.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(4, "inactive"), (2, "awaiting_author")]);
+        assert_eq!(stale[1].idle_days, 31);
+        assert!(find_stale_items(&pulls, &issues, &scored, 0, now).is_empty());
+
+        let args = test_args(&["--comment-actions", "--stale-days", "60"]);
+        let actions = build_action_plan(&scored, &[], &[], &stale, &args);
+        assert!(actions
+            .iter()
+            .any(|a| a.item_number == 4 && a.value == "triage:stale"));
+        let nudge = actions
+            .iter()
+            .find(|a| a.item_number == 2 && a.reason == "Close candidate")
+            .unwrap();
+        assert!(nudge.value.contains("@ada"));
+    }
+
+    #[test]
+    fn reviewers_come_from_codeowners_then_blame_and_become_requests() {
+        let root = std::env::temp_dir().join(format!("osmogrep-owners-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        let git = |author: &str, args: &[&str]| {
+            let ok = Command::new("git")
+                .current_dir(&root)
+                .args(["-c", &format!("user.name={author}")])
+                .args(["-c", &format!("user.email={author}")])
+                .args(args)
+                .output()
+                .unwrap()
+                .status
+                .success();
+            assert!(ok, "git {args:?}");
+        };
+        git("x", &["init", "-q"]);
+        fs::write(root.join("CODEOWNERS"), "src/api/ @acme/api\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "a\nb\nc\n").unwrap();
+        git("7+ada@users.noreply.github.com", &["add", "."]);
+        git("7+ada@users.noreply.github.com", &["commit", "-qm", "init"]);
+        fs::write(root.join("src/lib.rs"), "a\nb\nc\nd\n").unwrap();
+        git("grace@example.com", &["commit", "-qam", "more"]);
+
+        let owners = CodeOwners::load(&root);
+        let mut blame = BlameStats::new(root.clone());
+        let paths = vec!["src/api/mod.rs".to_string(), "src/lib.rs".to_string()];
+        let suggested = suggest_reviewers(&paths, "bob", owners.as_ref(), &mut blame);
+        let names = suggested
+            .iter()
+            .map(|s| (s.reviewer.as_str(), s.source.as_str(), s.requestable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("acme/api", "codeowners", true),
+                ("ada", "blame", true),
+                ("grace@example.com", "blame", false)
+            ]
+        );
+        assert_eq!(suggested[1].weight, 3);
+        let by_author = suggest_reviewers(&paths, "ADA", owners.as_ref(), &mut blame);
+        assert!(by_author.iter().all(|s| s.reviewer != "ada"));
+
+        let pr: GithubPull = serde_json::from_value(serde_json::json!({
+            "number": 9, "state": "open", "title": "API tweak", "draft": false
+        }))
+        .unwrap();
+        let mut report = score_pr(&pr, None, None);
+        report.suggested_reviewers = suggested;
+        let args = test_args(&["--request-reviews"]);
+        let mut actions = build_action_plan(&[report], &[], &[], &[], &args);
+        let requests = actions
+            .iter()
+            .filter(|a| a.action_type == "request_review")
+            .map(|a| a.value.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(requests, vec!["acme/api", "ada"]);
+
+        let forge = SnapshotForge::new(ItemSnapshot::default());
+        apply_actions(&forge, "acme/widgets", &mut actions, 10);
+        assert!(forge
+            .recorded_actions()
+            .iter()
+            .any(|a| a.action_type == "request_review" && a.value == "ada"));
+        assert_eq!(
+            review_request("acme/api"),
+            serde_json::json!({ "team_reviewers": ["api"] })
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn digests_render_clusters_and_escape_titles() {
+        let pair = |left: u64, right: u64, similarity: f64| DuplicatePair {
+            left_kind: ItemKind::Issue,
+            left_number: left,
+            left_title: format!("Crash <{left}> | upload"),
+            left_url: format!("https://github.com/acme/widgets/issues/{left}"),
+            right_kind: ItemKind::Issue,
+            right_number: right,
+            right_title: format!("Crash <{right}> | upload"),
+            right_url: String::new(),
+            similarity,
+            rationale: String::new(),
+        };
+        let pairs = vec![pair(1, 2, 0.7), pair(7, 8, 0.9), pair(2, 3, 0.8)];
+        let clusters = duplicate_clusters(&pairs);
+        let numbers = clusters
+            .iter()
+            .map(|(items, sim)| (items.iter().map(|i| i.1).collect::<Vec<_>>(), *sim))
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, vec![(vec![7, 8], 0.9), (vec![1, 2, 3], 0.8)]);
+
+        let report = TriageReport {
+            repo: "acme/widgets".into(),
+            state: "open".into(),
+            generated_at: "2026-10-16T00:00:00Z".into(),
+            incremental: false,
+            since: None,
+            state_file: String::new(),
+            max_seen_updated_at: None,
+            scanned_prs: 0,
+            scanned_issues: 5,
+            duplicate_pairs: pairs,
+            issue_pr_links: Vec::new(),
+            stale_items: Vec::new(),
+            ranked_prs: Vec::new(),
+            planned_actions: Vec::new(),
+            applied_action_count: 0,
+            trend: Vec::new(),
+        };
+
+        let md = render_markdown(&report);
+        assert!(
+            md.starts_with("# Triage digest: acme/widgets\n\n- Generated: 2026-10-16T00:00:00Z\n")
+        );
+        assert!(md.contains("## Ranked PRs\n\n_No PRs ranked._\n"));
+        assert!(md.contains("| Items | Similarity |\n| --- | --- |\n"));
+        assert!(md.contains(
+            "| [Issue #7 Crash &lt;7> \\| upload](https://github.com/acme/widgets/issues/7), Issue #8 Crash &lt;8> \\| upload | 0.90 |"
+        ));
+
+        let html = render_html(&report);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(
+            "<td><a href=\"https://github.com/acme/widgets/issues/7\">Issue #7 Crash &lt;7&gt; | upload</a>, Issue #8 Crash &lt;8&gt; | upload</td>"
+        ));
+        assert!(html.contains("<p><em>Nothing stale.</em></p>"));
+        assert!(normalize_format("pdf").is_err());
+    }
 }
@@ -0,0 +1,389 @@
+//! ui/chart.rs
+//!
+//! Small inline charts. Unicode blocks work everywhere and are what the TUI
+//! log panel uses; when stdout is a terminal that speaks the kitty graphics
+//! protocol or sixel, line charts are drawn as images instead.
+//!
+//! `OSMOGREP_GRAPHICS=kitty|sixel|blocks` overrides detection.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    Kitty,
+    Sixel,
+    Blocks,
+}
+
+impl Graphics {
+    pub fn detect() -> Self {
+        Self::detect_from(|key| env::var(key).ok())
+    }
+
+    pub fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(forced) = var("OSMOGREP_GRAPHICS") {
+            match forced.trim().to_ascii_lowercase().as_str() {
+                "kitty" => return Self::Kitty,
+                "sixel" => return Self::Sixel,
+                "blocks" | "off" | "none" => return Self::Blocks,
+                _ => {}
+            }
+        }
+        // Multiplexers swallow graphics escapes unless passthrough is set up.
+        if var("TMUX").is_some() || var("STY").is_some() {
+            return Self::Blocks;
+        }
+
+        let term = var("TERM").unwrap_or_default().to_ascii_lowercase();
+        let program = var("TERM_PROGRAM").unwrap_or_default().to_ascii_lowercase();
+        if var("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || matches!(program.as_str(), "wezterm" | "ghostty")
+        {
+            return Self::Kitty;
+        }
+        if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || matches!(program.as_str(), "iterm.app" | "mintty")
+        {
+            return Self::Sixel;
+        }
+        Self::Blocks
+    }
+}
+
+/// One block character per value, scaled between min and max.
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+
+    values
+        .iter()
+        .map(|v| {
+            if span <= f64::EPSILON {
+                BARS[BARS.len() / 2]
+            } else {
+                let idx = ((v - min) / span * (BARS.len() - 1) as f64).round() as usize;
+                BARS[idx.min(BARS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/// Horizontal bar at eighth-cell resolution, `value / max` of `width` cells.
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    if width == 0 || max <= 0.0 || value <= 0.0 {
+        return String::new();
+    }
+    let eighths = ((value / max).min(1.0) * (width * 8) as f64).round() as usize;
+    let mut out = "█".repeat(eighths / 8);
+    let rest = eighths % 8;
+    if rest > 0 {
+        out.push(EIGHTHS[rest]);
+    }
+    out
+}
+
+/// Labelled bar rows sharing one scale, e.g. for `/metrics`.
+pub fn bar_rows(items: &[(&str, f64)], width: usize) -> Vec<String> {
+    let max = items.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let items = items
+        .iter()
+        .map(|(label, value)| (label.to_string(), *value, value.round().to_string()))
+        .collect::<Vec<_>>();
+    captioned_bar_rows(&items, max, width)
+}
+
+/// Labelled bars scaled to `max`, each followed by its caption, e.g.
+/// coverage percentages out of 100.
+pub fn captioned_bar_rows(items: &[(String, f64, String)], max: f64, width: usize) -> Vec<String> {
+    let label_width = items
+        .iter()
+        .map(|(label, _, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    items
+        .iter()
+        .map(|(label, value, caption)| {
+            format!(
+                "{label:<label_width$} {:<width$} {caption}",
+                bar(*value, max, width)
+            )
+        })
+        .collect()
+}
+
+/// RGBA pixel buffer for image-protocol output.
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Raster {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0, 0, 0, 0]; width * height],
+        }
+    }
+
+    fn put(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.pixels[y as usize * self.width + x as usize] = color;
+        }
+    }
+
+    fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), color: [u8; 4]) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+        loop {
+            // Two pixels thick so the line survives terminal scaling.
+            self.put(x, y, color);
+            self.put(x, y + 1, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+/// Polyline of `values` on a transparent background.
+pub fn line_chart(values: &[f64], width: usize, height: usize, color: [u8; 3]) -> Raster {
+    let mut raster = Raster::new(width, height);
+    if values.is_empty() || width < 2 || height < 3 {
+        return raster;
+    }
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = (max - min).max(f64::EPSILON);
+    let usable = (height - 2) as f64;
+    let point = |i: usize, v: f64| {
+        let x = if values.len() == 1 {
+            0
+        } else {
+            (i * (width - 1) / (values.len() - 1)) as i64
+        };
+        let y = if max - min <= f64::EPSILON {
+            usable / 2.0
+        } else {
+            usable - (v - min) / span * usable
+        };
+        (x, y.round() as i64)
+    };
+
+    let rgba = [color[0], color[1], color[2], 255];
+    let mut prev = point(0, values[0]);
+    for (i, v) in values.iter().enumerate().skip(1) {
+        let next = point(i, *v);
+        raster.line(prev, next, rgba);
+        prev = next;
+    }
+    if values.len() == 1 {
+        raster.line(prev, (width as i64 - 1, prev.1), rgba);
+    }
+    raster
+}
+
+/// Kitty graphics protocol, raw RGBA transmitted in chunks.
+pub fn encode_kitty(raster: &Raster) -> String {
+    let raw: Vec<u8> = raster.pixels.iter().flatten().copied().collect();
+    let data = B64.encode(raw);
+    let chunks: Vec<&str> = data
+        .as_bytes()
+        .chunks(KITTY_CHUNK)
+        .map(|c| std::str::from_utf8(c).unwrap_or_default())
+        .collect();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=32,s={},v={},m={more};{chunk}\x1b\\",
+                raster.width, raster.height
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    out
+}
+
+/// Sixel with one palette entry per distinct opaque color; transparent
+/// pixels are left as background.
+pub fn encode_sixel(raster: &Raster) -> String {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    for px in &raster.pixels {
+        let rgb = [px[0], px[1], px[2]];
+        if px[3] > 0 && !palette.contains(&rgb) {
+            palette.push(rgb);
+        }
+    }
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", raster.width, raster.height);
+    for (idx, rgb) in palette.iter().enumerate() {
+        let pct = |c: u8| c as u32 * 100 / 255;
+        out.push_str(&format!(
+            "#{idx};2;{};{};{}",
+            pct(rgb[0]),
+            pct(rgb[1]),
+            pct(rgb[2])
+        ));
+    }
+
+    for band in (0..raster.height).step_by(6) {
+        for (idx, rgb) in palette.iter().enumerate() {
+            out.push_str(&format!("#{idx}"));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..raster.width {
+                let mut bits = 0u8;
+                for dy in 0..6 {
+                    let y = band + dy;
+                    if y < raster.height {
+                        let px = raster.pixels[y * raster.width + x];
+                        if px[3] > 0 && [px[0], px[1], px[2]] == *rgb {
+                            bits |= 1 << dy;
+                        }
+                    }
+                }
+                let ch = (63 + bits) as char;
+                run = match run {
+                    Some((c, n)) if c == ch => Some((c, n + 1)),
+                    Some((c, n)) => {
+                        push_sixel_run(&mut out, c, n);
+                        Some((ch, 1))
+                    }
+                    None => Some((ch, 1)),
+                };
+            }
+            if let Some((c, n)) = run {
+                push_sixel_run(&mut out, c, n);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_sixel_run(out: &mut String, ch: char, n: usize) {
+    if n > 3 {
+        out.push_str(&format!("!{n}{ch}"));
+    } else {
+        out.extend(std::iter::repeat_n(ch, n));
+    }
+}
+
+/// Prints `label` followed by an inline line chart image when stdout is a
+/// graphics-capable terminal. Returns false when the caller should fall
+/// back to text.
+pub fn print_line_chart(label: &str, values: &[f64], color: [u8; 3]) -> bool {
+    if values.len() < 2 || !io::stdout().is_terminal() {
+        return false;
+    }
+    let encoded = match Graphics::detect() {
+        Graphics::Kitty => encode_kitty(&line_chart(values, 160, 24, color)),
+        Graphics::Sixel => encode_sixel(&line_chart(values, 160, 24, color)),
+        Graphics::Blocks => return false,
+    };
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{label}").is_ok()
+        && writeln!(stdout, "{encoded}").is_ok()
+        && stdout.flush().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect(vars: &[(&str, &str)]) -> Graphics {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Graphics::detect_from(|key| vars.get(key).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn detects_terminal_graphics_support() {
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), Graphics::Kitty);
+        assert_eq!(detect(&[("TERM_PROGRAM", "WezTerm")]), Graphics::Kitty);
+        assert_eq!(detect(&[("TERM", "foot")]), Graphics::Sixel);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), Graphics::Blocks);
+        assert_eq!(
+            detect(&[
+                ("TERM", "xterm-kitty"),
+                ("TMUX", "/tmp/tmux-1000/default,1,0")
+            ]),
+            Graphics::Blocks
+        );
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty"), ("OSMOGREP_GRAPHICS", "blocks")]),
+            Graphics::Blocks
+        );
+    }
+
+    #[test]
+    fn block_bars_use_eighth_cells() {
+        assert_eq!(bar(10.0, 10.0, 4), "████");
+        assert_eq!(bar(5.0, 10.0, 3), "█▌");
+        assert_eq!(bar(0.0, 10.0, 4), "");
+        let rows = bar_rows(&[("prompt", 300.0), ("output", 150.0)], 4);
+        assert_eq!(rows, vec!["prompt ████ 300", "output ██   150"]);
+        let rows = captioned_bar_rows(
+            &[
+                ("src/a.rs".into(), 25.0, "25% of 8 lines".into()),
+                ("b.rs".into(), 100.0, "100% of 2 lines".into()),
+            ],
+            100.0,
+            4,
+        );
+        assert_eq!(
+            rows,
+            vec![
+                "src/a.rs █    25% of 8 lines",
+                "b.rs     ████ 100% of 2 lines"
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_line_chart_for_kitty_and_sixel() {
+        let raster = line_chart(&[1.0, 3.0, 2.0], 12, 8, [255, 0, 0]);
+        assert!(raster.pixels.iter().any(|px| px[3] == 255));
+        assert!(raster.pixels.iter().any(|px| px[3] == 0));
+
+        let kitty = encode_kitty(&raster);
+        assert!(kitty.starts_with("\x1b_Ga=T,f=32,s=12,v=8,m=0;"));
+        assert!(kitty.ends_with("\x1b\\"));
+
+        let sixel = encode_sixel(&raster);
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;12;8#0;2;100;0;0"));
+        assert_eq!(sixel.matches('-').count(), 2);
+        assert!(sixel.ends_with("\x1b\\"));
+    }
+}
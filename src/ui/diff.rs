@@ -2,7 +2,10 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span},
 };
-use similar::{ChangeTag, TextDiff};
+use serde_json::Value;
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+use crate::binary_assets::{self, AssetChange};
 
 const CONTEXT_RADIUS: usize = 3;
 const MAX_RENDER_LINES: usize = 500;
@@ -13,6 +16,11 @@ pub struct Diff {
     pub lines: Vec<DiffRenderLine>,
     pub added: usize,
     pub removed: usize,
+    /// Set for LFS pointer and binary changes, which get no line diff.
+    pub asset: Option<AssetChange>,
+    /// Jupyter notebook diffed cell by cell; line numbers count lines of
+    /// [`notebook_text`], not of the JSON file.
+    pub notebook: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -34,6 +42,27 @@ pub enum DiffLineKind {
 
 impl Diff {
     pub fn from_texts(file: String, before: &str, after: &str) -> Self {
+        if let Some(asset) = binary_assets::classify_texts(&file, before, after) {
+            return Self {
+                file,
+                lines: Vec::new(),
+                added: 0,
+                removed: 0,
+                asset: Some(asset),
+                notebook: false,
+            };
+        }
+
+        let notebook = file
+            .ends_with(".ipynb")
+            .then(|| Some((notebook_text(before)?, notebook_text(after)?)))
+            .flatten();
+        let (before, after) = match notebook.as_ref() {
+            Some((before, after)) => (before.as_str(), after.as_str()),
+            None => (before, after),
+        };
+        let new_lines: Vec<&str> = after.lines().collect();
+
         let diff = TextDiff::from_lines(before, after);
         let mut lines = Vec::new();
         let mut added = 0usize;
@@ -54,17 +83,27 @@ impl Diff {
 
             for op in group {
                 if !header_written {
+                    let mut text = format!(
+                        "@@ -{},{} +{},{} @@",
+                        op.old_range().start + 1,
+                        op.old_range().len(),
+                        op.new_range().start + 1,
+                        op.new_range().len()
+                    );
+                    // Name the cell of the first change, not of the leading context.
+                    let first_change = group.iter().find(|op| op.tag() != DiffTag::Equal);
+                    let cell = first_change
+                        .filter(|_| notebook.is_some())
+                        .and_then(|op| enclosing_cell(&new_lines, op.new_range().start));
+                    if let Some(cell) = cell {
+                        text.push(' ');
+                        text.push_str(cell);
+                    }
                     lines.push(DiffRenderLine {
                         kind: DiffLineKind::Header,
                         old_lineno: Some(op.old_range().start + 1),
                         new_lineno: Some(op.new_range().start + 1),
-                        text: format!(
-                            "@@ -{},{} +{},{} @@",
-                            op.old_range().start + 1,
-                            op.old_range().len(),
-                            op.new_range().start + 1,
-                            op.new_range().len()
-                        ),
+                        text,
                     });
                     header_written = true;
                 }
@@ -109,6 +148,8 @@ impl Diff {
                             lines,
                             added,
                             removed,
+                            asset: None,
+                            notebook: notebook.is_some(),
                         };
                     }
                 }
@@ -120,13 +161,81 @@ impl Diff {
             lines,
             added,
             removed,
+            asset: None,
+            notebook: notebook.is_some(),
+        }
+    }
+}
+
+/// The cell sources of a notebook, each under a `# %% [kind] cell N`
+/// marker. Outputs, execution counts and metadata are left out so that
+/// re-running a notebook does not show up as a change. None when `text` is
+/// not a notebook; an empty file (a created or deleted notebook) has no
+/// cells.
+pub fn notebook_text(text: &str) -> Option<String> {
+    if text.trim().is_empty() {
+        return Some(String::new());
+    }
+    let nb: Value = serde_json::from_str(text).ok()?;
+    let cells = nb.get("cells")?.as_array()?;
+
+    let mut out = String::new();
+    for (idx, cell) in cells.iter().enumerate() {
+        let kind = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("code");
+        let source = match cell.get("source") {
+            Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+            Some(Value::String(s)) => s.clone(),
+            _ => String::new(),
+        };
+        out.push_str(&format!("# %% [{kind}] cell {}\n", idx + 1));
+        for line in source.lines() {
+            out.push_str(line);
+            out.push('\n');
         }
     }
+    Some(out)
+}
+
+// The marker of the cell containing line `idx` of a notebook rendering.
+fn enclosing_cell<'a>(lines: &[&'a str], idx: usize) -> Option<&'a str> {
+    lines[..(idx + 1).min(lines.len())]
+        .iter()
+        .rev()
+        .find_map(|l| l.strip_prefix("# %% "))
 }
 
 pub fn render_diff(diff: &Diff, width: u16) -> Vec<Line<'static>> {
     let mut out = Vec::new();
 
+    if let Some(asset) = diff.asset.as_ref() {
+        out.push(Line::from(vec![
+            Span::styled(
+                format!("▌ {}", diff.file),
+                Style::default()
+                    .fg(Color::Rgb(210, 210, 210))
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("  "),
+            Span::styled(
+                if asset.lfs { "LFS" } else { "binary" },
+                Style::default().fg(Color::Rgb(150, 150, 210)),
+            ),
+        ]));
+        out.push(Line::from(Span::styled(
+            fit_line(
+                &format!("   {}", asset.summary()),
+                width.saturating_sub(2) as usize,
+            ),
+            Style::default()
+                .fg(Color::Rgb(145, 145, 145))
+                .add_modifier(Modifier::ITALIC),
+        )));
+        return out;
+    }
+
     out.push(Line::from(vec![
         Span::styled(
             format!("▌ {}", diff.file),
@@ -145,6 +254,12 @@ pub fn render_diff(diff: &Diff, width: u16) -> Vec<Line<'static>> {
             Style::default().fg(Color::Rgb(220, 95, 90)),
         ),
     ]));
+    if diff.notebook {
+        out[0].spans.extend([
+            Span::raw("  "),
+            Span::styled("notebook", Style::default().fg(Color::Rgb(150, 150, 210))),
+        ]);
+    }
     out.push(Line::from(""));
 
     let content_width = width.saturating_sub(2) as usize;
@@ -209,7 +324,11 @@ pub fn render_diff(diff: &Diff, width: u16) -> Vec<Line<'static>> {
 
     if diff.lines.is_empty() {
         out.push(Line::from(Span::styled(
-            "(no visible line-level diff)",
+            if diff.notebook {
+                "(only outputs, execution counts or metadata changed)"
+            } else {
+                "(no visible line-level diff)"
+            },
             Style::default()
                 .fg(Color::Rgb(125, 125, 125))
                 .add_modifier(Modifier::ITALIC),
@@ -231,3 +350,51 @@ fn fit_line(s: &str, max: usize) -> String {
         out
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notebook(cells: &[(&str, &str, usize)]) -> String {
+        let cells: Vec<Value> = cells
+            .iter()
+            .map(|(kind, source, count)| {
+                serde_json::json!({
+                    "cell_type": kind,
+                    "execution_count": count,
+                    "metadata": {},
+                    "outputs": [{ "output_type": "stream", "text": [format!("run {count}")] }],
+                    "source": source.split_inclusive('\n').collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&serde_json::json!({ "cells": cells, "nbformat": 4 })).unwrap()
+    }
+
+    #[test]
+    fn notebooks_diff_cell_sources_without_outputs() {
+        let before = notebook(&[("markdown", "# Title", 0), ("code", "x = 1\nprint(x)", 1)]);
+        let rerun = notebook(&[("markdown", "# Title", 0), ("code", "x = 1\nprint(x)", 7)]);
+        let edited = notebook(&[("markdown", "# Title", 0), ("code", "x = 2\nprint(x)", 8)]);
+
+        let diff = Diff::from_texts("a.ipynb".into(), &before, &rerun);
+        assert!(diff.notebook);
+        assert!(diff.lines.is_empty());
+
+        let diff = Diff::from_texts("a.ipynb".into(), &before, &edited);
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        assert!(diff.lines[0].text.ends_with("[code] cell 2"));
+        let changed: Vec<_> = diff
+            .lines
+            .iter()
+            .filter(|l| l.kind != DiffLineKind::Context && l.kind != DiffLineKind::Header)
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(changed, ["x = 1", "x = 2"]);
+
+        let created = Diff::from_texts("a.ipynb".into(), "", &before);
+        assert!(created.notebook);
+        assert_eq!(created.added, 5);
+        assert!(!Diff::from_texts("a.ipynb".into(), "{", &before).notebook);
+    }
+}
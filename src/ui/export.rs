@@ -0,0 +1,144 @@
+//! ui/export.rs
+//!
+//! `/export panel` and `Ctrl+S`: write what the focused panel shows (the
+//! diff view, or the visible slice of the execution log) to a file as plain
+//! text, or with its colors as ANSI escapes, for pasting into chat or issues.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Line,
+};
+
+use crate::state::AgentState;
+
+pub const DEFAULT_DIR: &str = ".osmogrep/exports";
+
+/// A fresh timestamped file under [`DEFAULT_DIR`].
+pub fn default_path(repo_root: &Path) -> PathBuf {
+    repo_root
+        .join(DEFAULT_DIR)
+        .join(Local::now().format("panel-%Y%m%d-%H%M%S.txt").to_string())
+}
+
+/// Writes the focused panel to `path` and returns how many lines it wrote.
+pub fn export_panel(state: &AgentState, path: &Path, ansi: bool) -> Result<usize, String> {
+    let lines = crate::ui::tui::panel_lines(state, panel_width());
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {e}", parent.display()))?;
+    }
+    fs::write(path, render(&lines, ansi)).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(lines.len())
+}
+
+/// The execution panel's width in the current terminal, so exported rows
+/// wrap where they do on screen.
+fn panel_width() -> u16 {
+    crossterm::terminal::size()
+        .map(|(w, _)| w.saturating_sub(4))
+        .unwrap_or(100)
+        .max(20)
+}
+
+/// One text line per row. Plain output drops trailing padding; ANSI output
+/// wraps each styled span in SGR codes and resets after it.
+pub fn render(lines: &[Line], ansi: bool) -> String {
+    let mut out = String::new();
+    for line in lines {
+        let mut row = String::new();
+        for span in &line.spans {
+            let style = line.style.patch(span.style);
+            let sgr = if ansi { sgr_codes(style) } else { Vec::new() };
+            if sgr.is_empty() {
+                row.push_str(&span.content);
+            } else {
+                row.push_str(&format!("\x1b[{}m{}\x1b[0m", sgr.join(";"), span.content));
+            }
+        }
+        if ansi {
+            out.push_str(&row);
+        } else {
+            out.push_str(row.trim_end());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn sgr_codes(style: Style) -> Vec<String> {
+    let mut codes = Vec::new();
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::CROSSED_OUT, "9"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    codes.extend(style.fg.and_then(|c| color_code(c, false)));
+    codes.extend(style.bg.and_then(|c| color_code(c, true)));
+    codes
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = |code: u8| Some((if background { code + 10 } else { code }).to_string());
+    let layer = if background { 48 } else { 38 };
+    match color {
+        Color::Reset => None,
+        Color::Black => base(30),
+        Color::Red => base(31),
+        Color::Green => base(32),
+        Color::Yellow => base(33),
+        Color::Blue => base(34),
+        Color::Magenta => base(35),
+        Color::Cyan => base(36),
+        Color::Gray => base(37),
+        Color::DarkGray => base(90),
+        Color::LightRed => base(91),
+        Color::LightGreen => base(92),
+        Color::LightYellow => base(93),
+        Color::LightBlue => base(94),
+        Color::LightMagenta => base(95),
+        Color::LightCyan => base(96),
+        Color::White => base(97),
+        Color::Indexed(n) => Some(format!("{layer};5;{n}")),
+        Color::Rgb(r, g, b) => Some(format!("{layer};2;{r};{g};{b}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::text::Span;
+
+    #[test]
+    fn renders_plain_or_with_ansi_colors() {
+        let lines = vec![
+            Line::from(vec![
+                Span::styled("+ added", Style::default().fg(Color::Rgb(70, 190, 120))),
+                Span::raw("   "),
+            ]),
+            Line::from(Span::styled(
+                "warn",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+        ];
+
+        assert_eq!(render(&lines, false), "+ added\nwarn\n");
+        assert_eq!(
+            render(&lines, true),
+            "\x1b[38;2;70;190;120m+ added\x1b[0m   \n\x1b[1;33mwarn\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! ui/harness.rs
+//!
+//! Scripted end-to-end driver for the TUI. Key events go through the same
+//! `handle_event` path as the real loop, pending input is dispatched the way
+//! `run_tui` does it, and frames are rendered with ratatui's `TestBackend`
+//! so whole screens can be snapshotted with insta.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::{backend::TestBackend, layout::Rect, Terminal};
+use uuid::Uuid;
+
+use crate::commands;
+use crate::logger::log_user_input;
+use crate::state::{
+    AgentState, ConversationHistory, DiffSnapshot, InputMode, LogBuffer, LogTimestamps,
+    PermissionProfile, UiAccent, UiDensity, UiState, UiTheme, UsageStats, VoiceState,
+};
+use crate::ui::{main_ui::handle_event, tui::draw_ui};
+
+pub struct TuiHarness {
+    pub state: AgentState,
+    terminal: Terminal<TestBackend>,
+    fixture: PathBuf,
+    input_rect: Rect,
+    exec_rect: Rect,
+}
+
+impl TuiHarness {
+    pub fn new(width: u16, height: u16) -> Self {
+        let fixture = std::env::temp_dir().join(format!("osmogrep-tui-{}", Uuid::new_v4()));
+        fs::create_dir_all(fixture.join("src")).unwrap();
+        fs::write(fixture.join("README.md"), "# fixture\n").unwrap();
+        fs::write(
+            fixture.join("src/lib.rs"),
+            "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n",
+        )
+        .unwrap();
+
+        let ui = UiState {
+            repo_branch: Some("main".to_string()),
+            ..UiState::default()
+        };
+
+        let state = AgentState {
+            ui,
+            logs: LogBuffer::new(),
+            session_changes: Vec::new(),
+            reviewed_change_count: 0,
+            undo_stack: Vec::new(),
+            usage: UsageStats::default(),
+            steer: None,
+            steer_history: Vec::new(),
+            run_budget: None,
+            interrupted: None,
+            auto_eval: false,
+            permission_profile: PermissionProfile::WorkspaceAuto,
+            jobs: Vec::new(),
+            job_queue: Vec::new(),
+            next_job_id: 1,
+            plan_items: Vec::new(),
+            session_name: None,
+            theme: UiTheme::default(),
+            accent: UiAccent::default(),
+            density: UiDensity::Compact,
+            timestamps: LogTimestamps::default(),
+            plan_mode: false,
+            plan_approved: false,
+            started_at: Instant::now(),
+            repo_root: fixture.clone(),
+            repo_lock: None,
+            sandbox: None,
+            rebase: None,
+            voice: VoiceState::default(),
+            conversation: ConversationHistory::new(),
+        };
+
+        Self {
+            state,
+            terminal: Terminal::new(TestBackend::new(width, height)).unwrap(),
+            fixture,
+            input_rect: Rect::default(),
+            exec_rect: Rect::default(),
+        }
+    }
+
+    pub fn fixture_path(&self, rel: &str) -> PathBuf {
+        self.fixture.join(rel)
+    }
+
+    /// Records an edit to a fixture file as if an agent tool had made it.
+    pub fn record_change(&mut self, rel: &str, after: &str) {
+        let path = self.fixture_path(rel);
+        let created = !path.exists();
+        let before = fs::read_to_string(&path).unwrap_or_default();
+        fs::write(&path, after).unwrap();
+        self.state.session_changes.push(DiffSnapshot {
+            tool: "edit_file".to_string(),
+            target: rel.to_string(),
+            before,
+            after: after.to_string(),
+            created,
+            deleted: false,
+        });
+    }
+
+    pub fn key(&mut self, code: KeyCode) {
+        self.key_with(code, KeyModifiers::NONE);
+    }
+
+    pub fn key_with(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        handle_event(
+            &mut self.state,
+            Event::Key(KeyEvent::new(code, modifiers)),
+            self.input_rect,
+            Rect::default(),
+            self.exec_rect,
+        );
+        self.pump();
+    }
+
+    pub fn click(&mut self, column: u16, row: u16) {
+        handle_event(
+            &mut self.state,
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            }),
+            self.input_rect,
+            Rect::default(),
+            self.exec_rect,
+        );
+        self.pump();
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.key(KeyCode::Char(c));
+        }
+    }
+
+    pub fn submit(&mut self, text: &str) {
+        self.type_text(text);
+        self.key(KeyCode::Enter);
+    }
+
+    /// Mirrors the non-agent part of the `run_tui` loop: dispatch pending
+    /// commands, then refresh palette hints.
+    fn pump(&mut self) {
+        if self.state.ui.execution_pending {
+            self.state.ui.execution_pending = false;
+            let mode = self.state.ui.input_mode;
+            let raw = self.state.commit_input();
+            let text = raw.trim();
+            self.state.ui.hint = None;
+            self.state.ui.autocomplete = None;
+
+            if !text.is_empty() {
+                log_user_input(&mut self.state, text);
+            }
+            if mode == InputMode::Command {
+                commands::handle_command(&mut self.state, text, None, None, None);
+            }
+        }
+
+        if !self.state.ui.execution_pending {
+            commands::update_command_hints(&mut self.state);
+        }
+    }
+
+    /// Draws a frame and returns it as plain text with machine-specific
+    /// values (fixture path, crate version) redacted.
+    pub fn render(&mut self) -> String {
+        // The temp dir length differs per machine and would shift the header
+        // layout, so draw with a fixed placeholder root.
+        let repo_root = std::mem::replace(&mut self.state.repo_root, PathBuf::from("[repo]"));
+        let drawn = draw_ui(&mut self.terminal, &self.state);
+        self.state.repo_root = repo_root;
+        let (input_rect, _, exec_rect) = drawn.unwrap();
+        self.input_rect = input_rect;
+        self.exec_rect = exec_rect;
+
+        let buffer = self.terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        let mut out = String::new();
+        for row in buffer.content.chunks(width.max(1)) {
+            let line = row.iter().map(|cell| cell.symbol()).collect::<String>();
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+
+        out.replace(&self.fixture.display().to_string(), "[repo]")
+            .replace(env!("CARGO_PKG_VERSION"), "[version]")
+    }
+}
+
+impl Drop for TuiHarness {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.fixture);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TuiHarness;
+    use crate::logger::{log_tool_call, log_tool_result};
+    use crate::state::InterruptedRun;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use serde_json::json;
+
+    #[test]
+    fn idle_screen() {
+        let mut ui = TuiHarness::new(80, 20);
+        insta::assert_snapshot!(ui.render());
+    }
+
+    #[test]
+    fn command_palette_filters_and_runs_selection() {
+        let mut ui = TuiHarness::new(80, 24);
+        ui.type_text("/he");
+        insta::assert_snapshot!("command_palette_filtered", ui.render());
+
+        ui.key(KeyCode::Enter);
+        assert!(ui.state.ui.command_items.is_empty());
+        insta::assert_snapshot!("command_palette_ran_help", ui.render());
+    }
+
+    #[test]
+    fn escape_closes_command_palette() {
+        let mut ui = TuiHarness::new(80, 20);
+        ui.type_text("/");
+        assert!(!ui.state.ui.command_items.is_empty());
+
+        ui.key(KeyCode::Esc);
+        ui.key(KeyCode::Backspace);
+        assert!(ui.state.ui.command_items.is_empty());
+        assert!(!ui.state.ui.should_exit);
+    }
+
+    #[test]
+    fn diff_command_opens_session_changes() {
+        let mut ui = TuiHarness::new(80, 24);
+        ui.record_change(
+            "src/lib.rs",
+            "pub fn add(a: i32, b: i32) -> i32 {\n    a.saturating_add(b)\n}\n",
+        );
+        ui.submit("/diff");
+
+        assert!(ui.state.ui.diff_active);
+        insta::assert_snapshot!(ui.render());
+    }
+
+    #[test]
+    fn clicking_file_reference_shows_excerpt() {
+        let mut ui = TuiHarness::new(80, 20);
+        crate::logger::log(
+            &mut ui.state,
+            crate::state::LogLevel::Error,
+            "error: overflow at src/lib.rs:2:7",
+        );
+        let screen = ui.render();
+        let (row, line) = screen
+            .lines()
+            .enumerate()
+            .find(|(_, line)| line.contains("src/lib.rs:2"))
+            .unwrap();
+        let column = line.find("src/lib.rs").unwrap() as u16 + 3;
+
+        ui.click(column, row as u16);
+        let texts: Vec<String> = ui.state.logs.iter().map(|l| l.text.clone()).collect();
+        assert!(texts.iter().any(|t| t == "● src/lib.rs:2:7"));
+        assert!(texts.iter().any(|t| t.contains(">2 │     a + b")));
+    }
+
+    #[test]
+    fn inspect_commands_log_session_state() {
+        let mut ui = TuiHarness::new(100, 16);
+        ui.submit("/profile");
+        ui.submit("/plan");
+        insta::assert_snapshot!(ui.render());
+    }
+
+    #[test]
+    fn steers_are_listed_resent_and_templated() {
+        let mut ui = TuiHarness::new(100, 30);
+        ui.submit("/steer now stop editing tests");
+        ui.submit("/steer resend 1");
+        assert_eq!(ui.state.steer_history.len(), 2);
+        assert!(ui
+            .state
+            .steer_history
+            .iter()
+            .all(|r| !r.live && r.text == "stop editing tests"));
+
+        ui.submit("/steer history");
+        let screen = ui.render();
+        assert!(screen.contains("Steering · 2 this run"));
+        assert!(screen.contains("queued stop editing tests"));
+
+        ui.submit("/steer resend 9");
+        assert_eq!(ui.state.steer_history.len(), 2);
+
+        ui.submit("/steer tests-first");
+        let template = crate::agent::steer_templates()["tests-first"].clone();
+        assert_eq!(ui.state.steer.as_deref(), Some(template.as_str()));
+    }
+
+    #[test]
+    fn plan_is_edited_then_approved_for_execution() {
+        let mut ui = TuiHarness::new(100, 30);
+        ui.submit("/plan mode on");
+        ui.submit("/plan add inspect the parser");
+        ui.submit("/plan add patch the parser");
+        ui.submit("/plan add update docs");
+        ui.submit("/plan files 2 src/parser.rs, tests/**");
+        ui.submit("/plan edit 1 read the parser");
+        ui.submit("/plan drop 3");
+        ui.submit("/plan done 1");
+        ui.submit("/plan approve");
+
+        assert!(!ui.state.plan_mode);
+        assert!(ui.state.plan_approved);
+        assert_eq!(ui.state.plan_items.len(), 2);
+        assert_eq!(ui.state.plan_items[0].text, "read the parser");
+        assert_eq!(ui.state.plan_items[1].files, ["src/parser.rs", "tests/**"]);
+        assert!(ui.state.plan_items[1].active);
+        assert_eq!(
+            ui.state.ui.queued_agent_prompt.as_deref(),
+            Some("Execute the approved plan.")
+        );
+        let screen = ui.render();
+        assert!(screen.contains("Plan · 1/2 · approved"));
+        assert!(screen.contains("src/parser.rs, tests/**"));
+
+        ui.submit("/plan edit 2 patch the lexer");
+        assert!(!ui.state.plan_approved);
+    }
+
+    #[test]
+    fn continue_resumes_the_failed_run() {
+        let mut ui = TuiHarness::new(80, 24);
+        ui.submit("/continue");
+        assert!(ui
+            .state
+            .logs
+            .iter()
+            .any(|l| l.text == "No failed run to continue."));
+
+        let messages = vec![
+            json!({ "role": "user", "content": "fix the parser" }),
+            json!({ "type": "function_call_output", "call_id": "c1", "output": "{}" }),
+        ];
+        ui.state.interrupted = Some(InterruptedRun {
+            messages: messages.clone(),
+            error: "HTTP 503".to_string(),
+            transient: true,
+        });
+        ui.state.ui.auto_continued = true;
+        ui.submit("/continue");
+
+        assert!(ui.state.interrupted.is_none());
+        assert!(!ui.state.ui.auto_continued);
+        assert_eq!(ui.state.conversation.messages, messages);
+        assert_eq!(
+            ui.state.ui.queued_agent_prompt.as_deref(),
+            Some(
+                "Continue the task from where you stopped; the last attempt failed with: HTTP 503"
+            )
+        );
+    }
+
+    #[test]
+    fn tool_output_is_collapsed_until_enter() {
+        let mut ui = TuiHarness::new(80, 24);
+        log_tool_call(&mut ui.state, "run_shell", "cargo test");
+        log_tool_result(
+            &mut ui.state,
+            "running 3 tests\ntest a ... ok\ntest b ... ok",
+        );
+        log_tool_call(&mut ui.state, "read_file", "src/lib.rs");
+        log_tool_result(&mut ui.state, "pub fn add\n}");
+
+        let screen = ui.render();
+        assert!(screen.contains("└ running 3 tests"));
+        assert!(!screen.contains("test a ... ok"));
+        assert!(screen.contains("… +2 lines\n"));
+        assert!(screen.contains("… +1 line (Enter to expand)"));
+
+        // Enter on an empty prompt toggles the latest block.
+        ui.key(KeyCode::Enter);
+        let screen = ui.render();
+        assert!(screen.contains("└ }"));
+        assert!(!screen.contains("test a ... ok"));
+
+        ui.key_with(KeyCode::Up, KeyModifiers::ALT);
+        ui.key_with(KeyCode::Up, KeyModifiers::ALT);
+        ui.key(KeyCode::Enter);
+        let screen = ui.render();
+        assert!(screen.contains("test a ... ok"));
+        assert!(screen.contains("test b ... ok"));
+        assert!(screen.contains("└ }"));
+    }
+}
@@ -64,9 +64,9 @@ pub fn running_pulse(start: Option<Instant>) -> Option<String> {
     let t = (start.elapsed().as_millis() / 90) as usize;
     let frames = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
     Some(format!(
-        "{} {:>4.1}s",
+        "{} {:>6}",
         frames[t % frames.len()],
-        start.elapsed().as_secs_f32()
+        crate::timefmt::duration(start.elapsed())
     ))
 }
 
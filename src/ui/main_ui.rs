@@ -1,5 +1,10 @@
-use crate::state::{AgentState, InputMode};
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crate::change_budget::BudgetDecision;
+use crate::keymap::KeyAction;
+use crate::review_queue::Decision;
+use crate::state::{AgentState, InputMode, TabRequest};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::layout::Rect;
 const SCROLL_LINE_STEP: usize = 3;
 const SCROLL_PAGE_STEP: usize = 12;
@@ -30,19 +35,21 @@ pub fn handle_event(
     event: impl Into<Event>,
     _input_rect: Rect,
     _diff_rect: Rect,
-    _exec_rect: Rect,
+    exec_rect: Rect,
 ) {
     match event.into() {
         Event::Key(k) => handle_key(state, k),
         Event::Paste(text) => handle_paste(state, &text),
-        Event::Mouse(m) => handle_mouse(state, m),
+        Event::Mouse(m) => handle_mouse(state, m, exec_rect),
+        Event::FocusLost => state.ui.focus_lost = true,
+        Event::FocusGained => state.ui.focus_lost = false,
         _ => {}
     }
 }
 
 fn handle_paste(state: &mut AgentState, text: &str) {
     let text = normalize_paste_text(text);
-    state.insert_text(&text);
+    state.insert_paste(&text);
 }
 
 fn normalize_paste_text(text: &str) -> String {
@@ -50,13 +57,69 @@ fn normalize_paste_text(text: &str) -> String {
 }
 
 fn handle_key(state: &mut AgentState, k: KeyEvent) {
-    if matches!(k.code, KeyCode::Esc) && state.ui.agent_running {
+    if state.voice.connected && state.voice.push_to_talk() && state.voice.ptt_key.matches(&k) {
+        if k.kind == KeyEventKind::Release {
+            state.voice.ptt_release();
+        } else {
+            state.voice.ptt_press();
+        }
+        return;
+    }
+    // Releases are only reported while push-to-talk needs them.
+    if k.kind == KeyEventKind::Release {
+        return;
+    }
+
+    if let Some(tab) = tab_key(&k) {
+        state.ui.tab_request = Some(TabRequest::Switch(tab));
+        return;
+    }
+
+    // Only back-to-back kills add up to one yank.
+    let kills = matches!(
+        input_control_action(&k),
+        Some(
+            InputControlAction::KillToLineEnd
+                | InputControlAction::DeletePreviousWord
+                | InputControlAction::KillWordBackward
+                | InputControlAction::KillWordForward
+        )
+    );
+    if !kills {
+        state.ui.input_kill_chain = false;
+    }
+
+    if state.ui.pending_exit.is_some() {
+        handle_exit_prompt(state, &k);
+        return;
+    }
+
+    if state.ui.agent_running && state.ui.keymap.action(&k) == Some(KeyAction::Cancel) {
         request_agent_cancel(state);
         return;
     }
 
-    if let Some(pending) = state.ui.pending_permission.take() {
+    if state.ui.pending_budget.is_some() {
+        handle_budget_prompt(state, &k);
+        return;
+    }
+
+    if state.ui.candidates.is_some() && handle_candidates_key(state, &k) {
+        return;
+    }
+
+    if state.ui.review.is_some() && handle_review_key(state, &k) {
+        return;
+    }
+
+    if let Some(mut pending) = state.ui.pending_permission.take() {
         match k.code {
+            KeyCode::Char('y') | KeyCode::Char('Y')
+                if pending.protected.is_some() && !pending.confirmed_once =>
+            {
+                pending.confirmed_once = true;
+                state.ui.pending_permission = Some(pending);
+            }
             KeyCode::Char('y') | KeyCode::Char('Y') => {
                 let _ = pending.reply_tx.send(true);
                 crate::logger::log(
@@ -73,14 +136,25 @@ fn handle_key(state: &mut AgentState, k: KeyEvent) {
                     format!("Denied {} {}", pending.tool_name, pending.args_summary),
                 );
             }
-            KeyCode::Char('a') | KeyCode::Char('A') => {
+            KeyCode::Char('a') | KeyCode::Char('A') if pending.protected.is_none() => {
                 let _ = pending.reply_tx.send(true);
-                state.ui.auto_approve = true;
-                crate::logger::log(
-                    state,
-                    crate::state::LogLevel::Info,
-                    "Auto-approve enabled for dangerous tools.",
-                );
+                let rule = format!("{}: {}", pending.tool_name, pending.always);
+                match crate::approvals::remember(
+                    state.edit_root(),
+                    &pending.tool_name,
+                    &pending.always,
+                ) {
+                    Ok(_) => crate::logger::log(
+                        state,
+                        crate::state::LogLevel::Info,
+                        format!("Always allowing `{rule}` in this repo (/approvals to revoke)."),
+                    ),
+                    Err(e) => crate::logger::log(
+                        state,
+                        crate::state::LogLevel::Warn,
+                        format!("Approved once; could not remember `{rule}`: {e}"),
+                    ),
+                }
             }
             _ => {
                 state.ui.pending_permission = Some(pending);
@@ -107,6 +181,24 @@ fn handle_key(state: &mut AgentState, k: KeyEvent) {
 
     let palette_active = !state.ui.command_items.is_empty();
 
+    if let Some(action) = state.ui.keymap.action(&k) {
+        if apply_key_action(state, action, palette_active) {
+            return;
+        }
+    }
+
+    // A bare `e` opens the file in view only while the log is scrolled
+    // back, so a prompt can still start with it.
+    if k.code == KeyCode::Char('e')
+        && k.modifiers.is_empty()
+        && !state.ui.follow_tail
+        && state.ui.input.is_empty()
+        && !palette_active
+        && crate::commands::edit_current_file(state)
+    {
+        return;
+    }
+
     if let Some(action) = input_control_action(&k) {
         apply_input_control_action(state, action);
         return;
@@ -225,30 +317,8 @@ fn handle_key(state: &mut AgentState, k: KeyEvent) {
             }
         }
 
-        /* ---------- Execution scrolling ---------- */
-        KeyCode::PageUp => {
-            scroll_execution_back(state, SCROLL_PAGE_STEP);
-        }
-
-        KeyCode::PageDown => {
-            scroll_execution_toward_tail(state, SCROLL_PAGE_STEP);
-        }
-
-        KeyCode::Up if k.modifiers.contains(KeyModifiers::CONTROL) => {
-            scroll_execution_back(state, SCROLL_LINE_STEP);
-        }
-
-        KeyCode::Down if k.modifiers.contains(KeyModifiers::CONTROL) => {
-            scroll_execution_toward_tail(state, SCROLL_LINE_STEP);
-        }
-
         KeyCode::End => {
-            if state.ui.input.is_empty() {
-                state.ui.exec_scroll = usize::MAX;
-                state.ui.follow_tail = true;
-            } else {
-                state.move_cursor_line_end();
-            }
+            state.move_cursor_line_end();
         }
 
         KeyCode::Home => {
@@ -268,7 +338,7 @@ fn handle_key(state: &mut AgentState, k: KeyEvent) {
             if state.ui.agent_running {
                 state.ui.cancel_requested = true;
             } else {
-                state.ui.should_exit = true;
+                state.request_exit();
             }
         }
 
@@ -276,11 +346,196 @@ fn handle_key(state: &mut AgentState, k: KeyEvent) {
     }
 }
 
+/// Runs a bound action. False when it does not apply right now, so the key
+/// keeps its usual meaning: End still moves the cursor in a non-empty
+/// prompt, and Enter still picks from the palette.
+fn apply_key_action(state: &mut AgentState, action: KeyAction, palette_active: bool) -> bool {
+    let prompt_empty = state.ui.input.trim().is_empty();
+    match action {
+        KeyAction::ScrollUp => scroll_execution_back(state, SCROLL_LINE_STEP),
+        KeyAction::ScrollDown => scroll_execution_toward_tail(state, SCROLL_LINE_STEP),
+        KeyAction::PageUp => scroll_execution_back(state, SCROLL_PAGE_STEP),
+        KeyAction::PageDown => scroll_execution_toward_tail(state, SCROLL_PAGE_STEP),
+        KeyAction::ScrollBottom if prompt_empty => {
+            state.ui.exec_scroll = usize::MAX;
+            state.ui.follow_tail = true;
+        }
+        KeyAction::Cancel if state.ui.agent_running => request_agent_cancel(state),
+        KeyAction::CopyOutput => crate::commands::copy_latest_output(state),
+        KeyAction::ExportPanel => crate::commands::export_focused_panel(state),
+        KeyAction::OpenFileRef => crate::commands::open_latest_file_ref(state),
+        KeyAction::EditFile => return crate::commands::edit_current_file(state),
+        KeyAction::PrevTool if !palette_active => state.select_tool_block(true),
+        KeyAction::NextTool if !palette_active => state.select_tool_block(false),
+        KeyAction::ToggleTool if prompt_empty && !palette_active => {
+            return state.toggle_tool_block();
+        }
+        KeyAction::ToggleDiff => crate::commands::toggle_session_diff(state),
+        KeyAction::ToggleSplit => crate::commands::toggle_split_diff(state),
+        KeyAction::ToggleMinimap => crate::commands::toggle_diff_minimap(state),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_exit_prompt(state: &mut AgentState, k: &KeyEvent) {
+    match k.code {
+        KeyCode::Char('c') | KeyCode::Char('C') => {
+            state.ui.pending_exit = None;
+            if state.ui.agent_running {
+                request_agent_cancel(state);
+            }
+            crate::logger::log(state, crate::state::LogLevel::Warn, "Exiting Osmogrep.");
+            state.ui.should_exit = true;
+        }
+        KeyCode::Char('w') | KeyCode::Char('W') => {
+            state.ui.pending_exit = None;
+            state.ui.exit_when_idle = true;
+            crate::logger::log(
+                state,
+                crate::state::LogLevel::Info,
+                "Osmogrep will exit when the current work is done.",
+            );
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            state.ui.pending_exit = None;
+            crate::logger::log_status(state, "Exit aborted.");
+        }
+        _ => {}
+    }
+}
+
+/// Keys of the open review queue. Other keys that would type text are
+/// swallowed too; scrolling and the like still work.
+fn handle_review_key(state: &mut AgentState, k: &KeyEvent) -> bool {
+    let Some(review) = state.ui.review.as_mut() else {
+        return false;
+    };
+    let diff_open = state.ui.diff_active;
+    match k.code {
+        KeyCode::Up | KeyCode::Char('k') if k.modifiers.is_empty() => review.move_selection(true),
+        KeyCode::Down | KeyCode::Char('j') if k.modifiers.is_empty() => {
+            review.move_selection(false)
+        }
+        KeyCode::Char('a') => review.decide(Decision::Accept),
+        KeyCode::Char('r') => review.decide(Decision::Reject),
+        KeyCode::Char('A') => review.decide_all(Decision::Accept),
+        KeyCode::Char('R') => review.decide_all(Decision::Reject),
+        KeyCode::Char('d') | KeyCode::Enter => {
+            crate::commands::show_review_diff(state);
+            return true;
+        }
+        KeyCode::Char('e') => {
+            crate::commands::edit_current_file(state);
+            return true;
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') => {
+            crate::commands::confirm_review(state);
+            return true;
+        }
+        KeyCode::Esc => {
+            state.ui.review = None;
+            state.ui.diff_active = false;
+            crate::logger::log_status(state, "Review closed; every change stays.");
+            return true;
+        }
+        KeyCode::Char(_) => {
+            return !k
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        }
+        _ => return false,
+    }
+    // The open diff follows the selection.
+    if diff_open {
+        crate::commands::show_review_diff(state);
+    }
+    true
+}
+
+/// Keys of the open test candidate list. While a discard reason is being
+/// typed, text goes into it instead.
+fn handle_candidates_key(state: &mut AgentState, k: &KeyEvent) -> bool {
+    let Some(queue) = state.ui.candidates.as_mut() else {
+        return false;
+    };
+    let typing = !k
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    if let Some(reason) = queue.reason.as_mut() {
+        match k.code {
+            KeyCode::Char(c) if typing => reason.push(c),
+            KeyCode::Backspace => {
+                reason.pop();
+            }
+            KeyCode::Esc => queue.reason = None,
+            KeyCode::Enter => {
+                if let Err(e) = queue.finish_discard(&state.repo_root) {
+                    crate::logger::log(state, crate::state::LogLevel::Warn, e);
+                }
+            }
+            _ => return false,
+        }
+        return true;
+    }
+    match k.code {
+        KeyCode::Up | KeyCode::Char('k') if k.modifiers.is_empty() => queue.move_selection(true),
+        KeyCode::Down | KeyCode::Char('j') if k.modifiers.is_empty() => queue.move_selection(false),
+        KeyCode::Char(' ') => queue.toggle_pick(),
+        KeyCode::Char('x') => queue.start_discard(),
+        KeyCode::Enter | KeyCode::Char('w') => crate::commands::materialize_test_candidates(state),
+        KeyCode::Esc => {
+            state.ui.candidates = None;
+            crate::logger::log_status(state, "Test candidates closed.");
+        }
+        KeyCode::Char(_) => return typing,
+        _ => return false,
+    }
+    true
+}
+
+fn handle_budget_prompt(state: &mut AgentState, k: &KeyEvent) {
+    let decision = match k.code {
+        KeyCode::Char('c') | KeyCode::Char('C') => BudgetDecision::Continue,
+        KeyCode::Char('s') | KeyCode::Char('S') => BudgetDecision::Split,
+        KeyCode::Char('r') | KeyCode::Char('R') => BudgetDecision::Rollback,
+        _ => return,
+    };
+    let Some(pending) = state.ui.pending_budget.take() else {
+        return;
+    };
+    let _ = pending.reply_tx.send(decision);
+    state.ui.diff_active = false;
+    match decision {
+        BudgetDecision::Continue => {
+            crate::logger::log_status(state, "Continuing past the change budget.");
+        }
+        BudgetDecision::Split => {
+            // The follow-up is left in the input for the user to send or edit.
+            state.ui.input =
+                "Continue the previous task from where the change budget stopped it.".to_string();
+            state.ui.input_cursor = state.ui.input.len();
+            crate::logger::log_status(
+                state,
+                "Run stopped; edits kept. The follow-up task is in the input.",
+            );
+        }
+        BudgetDecision::Rollback => {
+            crate::logger::log_status(state, "Rolling back this run's edits.");
+        }
+    }
+}
+
 fn request_agent_cancel(state: &mut AgentState) {
     state.ui.cancel_requested = true;
     state.ui.command_items.clear();
     state.ui.command_selected = 0;
 
+    // Cancelling keeps the run's edits, like stopping at the budget.
+    if let Some(pending) = state.ui.pending_budget.take() {
+        let _ = pending.reply_tx.send(BudgetDecision::Split);
+    }
+
     if let Some(pending) = state.ui.pending_permission.take() {
         let _ = pending.reply_tx.send(false);
         crate::logger::log(
@@ -334,7 +589,6 @@ fn scroll_toward_tail_offset(current: usize, step: usize) -> (usize, bool) {
 enum InputControlAction {
     SelectAll,
     CopyAll,
-    CopyOutput,
     CutAll,
     Paste,
     ClearAll,
@@ -343,15 +597,26 @@ enum InputControlAction {
     Yank,
     DeletePreviousWord,
     DeleteForward,
+    WordLeft,
+    WordRight,
+    KillWordBackward,
+    KillWordForward,
+    InsertNewline,
 }
 
 fn input_control_action(k: &KeyEvent) -> Option<InputControlAction> {
+    if k.modifiers.contains(KeyModifiers::ALT) {
+        return input_meta_action(k);
+    }
     if !k.modifiers.contains(KeyModifiers::CONTROL) {
         return None;
     }
 
-    let KeyCode::Char(c) = k.code else {
-        return None;
+    let c = match k.code {
+        KeyCode::Left => return Some(InputControlAction::WordLeft),
+        KeyCode::Right => return Some(InputControlAction::WordRight),
+        KeyCode::Char(c) => c,
+        _ => return None,
     };
 
     match c.to_ascii_lowercase() {
@@ -360,7 +625,6 @@ fn input_control_action(k: &KeyEvent) -> Option<InputControlAction> {
         'd' => Some(InputControlAction::DeleteForward),
         'e' => Some(InputControlAction::LineEnd),
         'k' => Some(InputControlAction::KillToLineEnd),
-        'o' => Some(InputControlAction::CopyOutput),
         'x' => Some(InputControlAction::CutAll),
         'v' => Some(InputControlAction::Paste),
         'u' => Some(InputControlAction::ClearAll),
@@ -370,6 +634,34 @@ fn input_control_action(k: &KeyEvent) -> Option<InputControlAction> {
     }
 }
 
+/// Readline's Meta bindings; terminals send them as Alt+key or Esc, key.
+fn input_meta_action(k: &KeyEvent) -> Option<InputControlAction> {
+    match k.code {
+        KeyCode::Left | KeyCode::Char('b') => Some(InputControlAction::WordLeft),
+        KeyCode::Right | KeyCode::Char('f') => Some(InputControlAction::WordRight),
+        KeyCode::Char('d') => Some(InputControlAction::KillWordForward),
+        KeyCode::Backspace => Some(InputControlAction::KillWordBackward),
+        // Shift+Enter only arrives on terminals that report it.
+        KeyCode::Enter => Some(InputControlAction::InsertNewline),
+        _ => None,
+    }
+}
+
+/// Ctrl+1..9 picks a tab. Alt+1..9 too, since most terminals send Ctrl
+/// with a digit as the bare digit.
+fn tab_key(k: &KeyEvent) -> Option<usize> {
+    if !k
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return None;
+    }
+    match k.code {
+        KeyCode::Char(c @ '1'..='9') => Some(c as usize - '1' as usize),
+        _ => None,
+    }
+}
+
 fn apply_input_control_action(state: &mut AgentState, action: InputControlAction) {
     match action {
         InputControlAction::SelectAll => {
@@ -385,9 +677,6 @@ fn apply_input_control_action(state: &mut AgentState, action: InputControlAction
                 crate::logger::log_status(state, "Cancel requested.");
             }
         }
-        InputControlAction::CopyOutput => {
-            crate::commands::copy_latest_output(state);
-        }
         InputControlAction::CutAll => {
             if state.cut_input() {
                 crate::logger::log_status(state, "Cut input.");
@@ -424,6 +713,21 @@ fn apply_input_control_action(state: &mut AgentState, action: InputControlAction
         InputControlAction::DeleteForward => {
             state.delete_forward();
         }
+        InputControlAction::WordLeft => {
+            state.move_cursor_word_left();
+        }
+        InputControlAction::WordRight => {
+            state.move_cursor_word_right();
+        }
+        InputControlAction::KillWordBackward => {
+            state.kill_word_backward();
+        }
+        InputControlAction::KillWordForward => {
+            state.kill_word_forward();
+        }
+        InputControlAction::InsertNewline => {
+            state.push_char('\n');
+        }
     }
 }
 
@@ -448,11 +752,15 @@ mod tests {
         handle_event, handle_key, input_control_action, scroll_back_offset,
         scroll_toward_tail_offset, update_prompt_action, InputControlAction, UpdatePromptAction,
     };
+    use crate::keymap::{KeyAction, Keymap};
     use crate::state::{
-        AgentState, ConversationHistory, LogBuffer, PermissionProfile, UiAccent, UiDensity,
-        UiState, UiTheme, UsageStats, VoiceState,
+        AgentState, ConversationHistory, LogBuffer, LogTimestamps, PermissionProfile, TabRequest,
+        UiAccent, UiDensity, UiState, UiTheme, UsageStats, VoiceState,
+    };
+    use crate::ui::scroll::ScrollMetrics;
+    use crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
     };
-    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
     use ratatui::layout::Rect;
     use std::{path::PathBuf, sync::mpsc, time::Instant};
 
@@ -473,6 +781,9 @@ mod tests {
             undo_stack: Vec::new(),
             usage: UsageStats::default(),
             steer: None,
+            steer_history: Vec::new(),
+            run_budget: None,
+            interrupted: None,
             auto_eval: false,
             permission_profile: PermissionProfile::WorkspaceAuto,
             jobs: Vec::new(),
@@ -483,9 +794,14 @@ mod tests {
             theme: UiTheme::default(),
             accent: UiAccent::default(),
             density: UiDensity::default(),
+            timestamps: LogTimestamps::default(),
             plan_mode: false,
+            plan_approved: false,
             started_at: Instant::now(),
             repo_root: PathBuf::from("."),
+            repo_lock: None,
+            sandbox: None,
+            rebase: None,
             voice: VoiceState::default(),
             conversation: ConversationHistory::new(),
         }
@@ -514,8 +830,8 @@ mod tests {
             Some(InputControlAction::KillToLineEnd)
         );
         assert_eq!(
-            input_control_action(&ctrl('o')),
-            Some(InputControlAction::CopyOutput)
+            Keymap::default().action(&ctrl('o')),
+            Some(KeyAction::CopyOutput)
         );
         assert_eq!(
             input_control_action(&ctrl('x')),
@@ -539,12 +855,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ctrl_or_alt_digits_switch_tabs() {
+        let mut state = agent_state();
+        handle_key(&mut state, ctrl('3'));
+        assert_eq!(state.ui.tab_request, Some(TabRequest::Switch(2)));
+        state.ui.tab_request = None;
+        handle_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('1'), KeyModifiers::ALT),
+        );
+        assert_eq!(state.ui.tab_request, Some(TabRequest::Switch(0)));
+        state.ui.tab_request = None;
+        handle_key(&mut state, key(KeyCode::Char('1')));
+        assert_eq!(state.ui.tab_request, None);
+        assert_eq!(state.ui.input, "1");
+    }
+
     #[test]
     fn input_control_ignores_plain_text_keys() {
         assert_eq!(input_control_action(&key(KeyCode::Char('a'))), None);
         assert_eq!(input_control_action(&key(KeyCode::Enter)), None);
     }
 
+    #[test]
+    fn meta_keys_move_by_word_and_alt_enter_breaks_the_line() {
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+        assert_eq!(
+            input_control_action(&alt(KeyCode::Char('b'))),
+            Some(InputControlAction::WordLeft)
+        );
+        assert_eq!(
+            input_control_action(&KeyEvent::new(KeyCode::Right, KeyModifiers::CONTROL)),
+            Some(InputControlAction::WordRight)
+        );
+
+        let mut state = agent_state();
+        for c in "run tests".chars() {
+            handle_key(&mut state, key(KeyCode::Char(c)));
+        }
+        handle_key(&mut state, alt(KeyCode::Left));
+        handle_key(&mut state, alt(KeyCode::Enter));
+        assert_eq!(state.ui.input, "run \ntests");
+
+        handle_key(&mut state, alt(KeyCode::Char('d')));
+        handle_key(&mut state, alt(KeyCode::Backspace));
+        handle_key(&mut state, alt(KeyCode::Backspace));
+        assert_eq!(state.ui.input, "");
+        assert_eq!(state.ui.input_clipboard, "run \ntests");
+
+        // Anything in between starts a fresh yank.
+        handle_key(&mut state, key(KeyCode::Char('x')));
+        handle_key(&mut state, ctrl('w'));
+        assert_eq!(state.ui.input_clipboard, "x");
+    }
+
+    #[test]
+    fn clicking_scroll_gutter_jumps_execution_panel() {
+        let mut state = agent_state();
+        state.ui.exec_metrics.set(ScrollMetrics {
+            total: 100,
+            visible: 20,
+            top: 80,
+        });
+        let exec_rect = Rect::new(0, 0, 40, 22);
+        let click = |column, row| {
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+
+        handle_event(
+            &mut state,
+            click(10, 1),
+            Rect::default(),
+            Rect::default(),
+            exec_rect,
+        );
+        assert!(state.ui.follow_tail);
+
+        handle_event(
+            &mut state,
+            click(39, 1),
+            Rect::default(),
+            Rect::default(),
+            exec_rect,
+        );
+        assert!(!state.ui.follow_tail);
+        assert_eq!(state.ui.exec_scroll, 80);
+
+        handle_event(
+            &mut state,
+            click(38, 20),
+            Rect::default(),
+            Rect::default(),
+            exec_rect,
+        );
+        assert!(state.ui.follow_tail);
+        assert_eq!(state.ui.exec_scroll, usize::MAX);
+    }
+
     #[test]
     fn paste_event_bulk_inserts_normalized_text_at_cursor() {
         let mut state = agent_state();
@@ -628,6 +1041,79 @@ mod tests {
         assert!(!state.ui.should_exit);
     }
 
+    #[test]
+    fn exit_with_running_work_asks_first() {
+        let mut state = agent_state();
+        state.jobs.push(crate::state::JobRecord {
+            id: 1,
+            kind: crate::state::JobKind::Test,
+            status: crate::state::JobStatus::Running,
+            input: String::new(),
+            output: None,
+            run_id: None,
+        });
+
+        handle_key(&mut state, key(KeyCode::Esc));
+        assert!(!state.ui.should_exit);
+        let prompt = state.ui.pending_exit.as_ref().expect("exit prompt");
+        assert_eq!(prompt.reasons, vec!["1 background job is not finished"]);
+
+        handle_key(&mut state, key(KeyCode::Esc));
+        assert!(state.ui.pending_exit.is_none());
+        assert!(!state.ui.should_exit);
+
+        handle_key(&mut state, key(KeyCode::Esc));
+        handle_key(&mut state, key(KeyCode::Char('w')));
+        assert!(state.ui.exit_when_idle);
+        assert!(!state.ui.should_exit);
+
+        state.jobs.clear();
+        handle_key(&mut state, key(KeyCode::Esc));
+        assert!(state.ui.should_exit);
+    }
+
+    #[test]
+    fn exit_asks_about_an_open_review() {
+        let mut state = agent_state();
+        state.ui.review = crate::review_queue::ReviewQueue::new(vec![crate::state::DiffSnapshot {
+            target: "src/lib.rs".to_string(),
+            before: "a".to_string(),
+            after: "b".to_string(),
+            ..Default::default()
+        }]);
+
+        // Esc closes the review itself; /exit and /quit come here.
+        state.request_exit();
+        let prompt = state.ui.pending_exit.as_ref().expect("exit prompt");
+        assert_eq!(
+            prompt.reasons,
+            vec!["the review of 1 changed file is not confirmed"]
+        );
+    }
+
+    #[test]
+    fn exit_asks_about_work_in_other_tabs() {
+        let mut state = agent_state();
+        let tab = |title: &str, exit_blockers: Vec<String>| crate::state::TabLabel {
+            title: title.to_string(),
+            running: false,
+            exit_blockers,
+        };
+        state.ui.tabs = vec![
+            tab("api", Vec::new()),
+            tab("web", vec!["a permission prompt is waiting".to_string()]),
+        ];
+        state.ui.active_tab = 0;
+
+        handle_key(&mut state, key(KeyCode::Esc));
+        assert!(!state.ui.should_exit);
+        let prompt = state.ui.pending_exit.as_ref().expect("exit prompt");
+        assert_eq!(
+            prompt.reasons,
+            vec!["in tab 2 (web), a permission prompt is waiting"]
+        );
+    }
+
     #[test]
     fn esc_denies_pending_permission_while_cancelling_agent() {
         let mut state = agent_state();
@@ -636,6 +1122,9 @@ mod tests {
         state.ui.pending_permission = Some(crate::state::PendingPermission {
             tool_name: "patch".to_string(),
             args_summary: "README.md".to_string(),
+            protected: None,
+            always: "**".to_string(),
+            confirmed_once: false,
             reply_tx: tx,
         });
 
@@ -645,9 +1134,62 @@ mod tests {
         assert!(state.ui.pending_permission.is_none());
         assert_eq!(rx.try_recv(), Ok(false));
     }
+
+    #[test]
+    fn protected_edit_needs_two_confirmations() {
+        let mut state = agent_state();
+        state.ui.agent_running = true;
+        let (tx, rx) = mpsc::channel();
+        state.ui.pending_permission = Some(crate::state::PendingPermission {
+            tool_name: "write_file".to_string(),
+            args_summary: "Cargo.lock".to_string(),
+            protected: Some("Cargo.lock (matches *.lock)".to_string()),
+            always: "**".to_string(),
+            confirmed_once: false,
+            reply_tx: tx,
+        });
+
+        handle_key(&mut state, key(KeyCode::Char('a')));
+        assert!(!state.ui.auto_approve);
+        handle_key(&mut state, key(KeyCode::Char('y')));
+        assert!(rx.try_recv().is_err());
+        assert!(state
+            .ui
+            .pending_permission
+            .as_ref()
+            .is_some_and(|p| p.confirmed_once));
+
+        handle_key(&mut state, key(KeyCode::Char('y')));
+        assert_eq!(rx.try_recv(), Ok(true));
+        assert!(state.ui.pending_permission.is_none());
+    }
+
+    #[test]
+    fn always_answer_is_remembered_for_the_repo() {
+        let mut state = agent_state();
+        state.repo_root =
+            std::env::temp_dir().join(format!("osmogrep-always-{}", uuid::Uuid::new_v4()));
+        state.ui.agent_running = true;
+        let (tx, rx) = mpsc::channel();
+        state.ui.pending_permission = Some(crate::state::PendingPermission {
+            tool_name: "run_shell".to_string(),
+            args_summary: "cargo test".to_string(),
+            protected: None,
+            always: "cargo *".to_string(),
+            confirmed_once: false,
+            reply_tx: tx,
+        });
+
+        handle_key(&mut state, key(KeyCode::Char('a')));
+        assert_eq!(rx.try_recv(), Ok(true));
+        assert!(!state.ui.auto_approve);
+        let approvals = crate::approvals::Approvals::load(&state.repo_root);
+        assert_eq!(approvals.entries[0].describe(), "run_shell: cargo *");
+        let _ = std::fs::remove_dir_all(&state.repo_root);
+    }
 }
 
-fn handle_mouse(state: &mut AgentState, m: MouseEvent) {
+fn handle_mouse(state: &mut AgentState, m: MouseEvent, exec_rect: Rect) {
     match m.kind {
         MouseEventKind::ScrollUp => {
             scroll_execution_back(state, SCROLL_WHEEL_STEP);
@@ -657,6 +1199,57 @@ fn handle_mouse(state: &mut AgentState, m: MouseEvent) {
             scroll_execution_toward_tail(state, SCROLL_WHEEL_STEP);
         }
 
+        // A click outside the scrollbar may land on a `path:line` reference.
+        MouseEventKind::Down(MouseButton::Left)
+            if !jump_execution_to(state, m.column, m.row, exec_rect) =>
+        {
+            open_clicked_file_ref(state, m.column, m.row);
+        }
+
+        MouseEventKind::Drag(MouseButton::Left) => {
+            jump_execution_to(state, m.column, m.row, exec_rect);
+        }
+
         _ => {}
     }
 }
+
+fn open_clicked_file_ref(state: &mut AgentState, column: u16, row: u16) {
+    let target = state
+        .ui
+        .exec_refs
+        .borrow()
+        .iter()
+        .find(|hit| hit.row == row && (hit.start..hit.end).contains(&column))
+        .map(|hit| hit.target.clone());
+    if let Some(target) = target {
+        crate::commands::open_file_ref(state, &target);
+    }
+}
+
+/// Clicks on the scrollbar or minimap gutter jump to that position.
+fn jump_execution_to(state: &mut AgentState, column: u16, row: u16, exec_rect: Rect) -> bool {
+    let metrics = state.ui.exec_metrics.get();
+    let gutter_start = exec_rect.right().saturating_sub(2);
+    let track_top = exec_rect.y + 1;
+    let track_height = exec_rect.height.saturating_sub(2);
+    if !metrics.scrollable()
+        || column < gutter_start
+        || column >= exec_rect.right()
+        || row < track_top
+        || row >= track_top + track_height
+    {
+        return false;
+    }
+
+    let top = metrics.jump_top((row - track_top) as usize, track_height as usize);
+    let offset = metrics.max_top() - top;
+    if offset == 0 {
+        state.ui.exec_scroll = usize::MAX;
+        state.ui.follow_tail = true;
+    } else {
+        state.ui.exec_scroll = offset;
+        state.ui.follow_tail = false;
+    }
+    true
+}
@@ -2,72 +2,522 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span},
 };
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum MdState {
-    Normal,
-    CodeBlock,
-}
+const TEXT: Color = Color::Rgb(200, 200, 200);
+const MUTED: Color = Color::Rgb(120, 120, 120);
+const RULE: Color = Color::Rgb(90, 90, 90);
+const CODE_BG: Color = Color::Rgb(35, 35, 35);
 
+/// Renders assistant markdown one source line at a time, as it streams in.
+/// Fenced code keeps its state across lines; table rows are held back
+/// until the table ends so the columns line up, and `flush` renders a
+/// table that is still open.
+#[derive(Default)]
 pub struct Markdown {
-    state: MdState,
+    code: Option<CodeBlock>,
+    table: Vec<String>,
+}
+
+struct CodeBlock {
+    marker: char,
+    fence_len: usize,
+    indent: usize,
+    syntax: Option<Syntax>,
+    in_comment: bool,
 }
 
 impl Markdown {
     pub fn new() -> Self {
-        Self {
-            state: MdState::Normal,
-        }
+        Self::default()
+    }
+
+    /// Whether the last line pushed was a table row still being held.
+    pub fn holding_table(&self) -> bool {
+        !self.table.is_empty()
     }
 
-    pub fn render_line(&mut self, input: &str) -> Line<'static> {
+    /// The lines `input` completes: nothing while a table row is held,
+    /// the finished table followed by `input` once the table ends.
+    pub fn push(&mut self, input: &str) -> Vec<Line<'static>> {
         let line = input.trim_end();
 
-        if line.starts_with("```") {
-            self.state = match self.state {
-                MdState::Normal => MdState::CodeBlock,
-                MdState::CodeBlock => MdState::Normal,
-            };
+        if self.code.is_none() && is_table_row(line) {
+            self.table.push(line.to_string());
+            return Vec::new();
+        }
+        let mut out = self.flush();
+        out.push(self.render_line(line));
+        out
+    }
 
-            return Line::from(Span::styled(
-                line.to_string(),
-                Style::default().fg(Color::Rgb(120, 120, 120)),
-            ));
+    /// Renders a held table, e.g. at the end of the log.
+    pub fn flush(&mut self) -> Vec<Line<'static>> {
+        if self.table.is_empty() {
+            return Vec::new();
         }
+        render_table(&std::mem::take(&mut self.table))
+    }
 
-        if self.state == MdState::CodeBlock {
-            return Line::from(Span::styled(
-                format!("  {}", line),
-                Style::default()
-                    .fg(Color::Rgb(200, 200, 200))
-                    .bg(Color::Rgb(35, 35, 35)),
-            ));
+    fn render_line(&mut self, line: &str) -> Line<'static> {
+        if let Some(code) = &mut self.code {
+            if fence(line).is_some_and(|(marker, len, info)| {
+                marker == code.marker && len >= code.fence_len && info.is_empty()
+            }) {
+                self.code = None;
+                return fence_line(line);
+            }
+            return code.render(line);
         }
 
-        if line == "---" {
-            return Line::from(Span::styled(
-                "────────────────────────────────".to_string(),
-                Style::default().fg(Color::Rgb(90, 90, 90)),
+        if let Some((marker, fence_len, info)) = fence(line) {
+            self.code = Some(CodeBlock {
+                marker,
+                fence_len,
+                indent: leading_spaces(line),
+                syntax: info.split_whitespace().next().and_then(Syntax::for_lang),
+                in_comment: false,
+            });
+            return fence_line(line);
+        }
+
+        if let Some((depth, rest)) = blockquote(line) {
+            let bar = Style::default().fg(RULE);
+            let mut spans = vec![Span::styled("│ ".repeat(depth), bar)];
+            spans.extend(block_spans(
+                rest,
+                Style::default().fg(Color::Rgb(170, 170, 170)),
             ));
+            return Line::from(spans);
         }
 
-        if let Some(rest) = line.strip_prefix("### ") {
-            return heading(rest);
+        Line::from(block_spans(line, Style::default().fg(TEXT)))
+    }
+}
+
+/* ---------------- Blocks ---------------- */
+
+fn block_spans(line: &str, base: Style) -> Vec<Span<'static>> {
+    if matches!(line.trim(), "---" | "***" | "___") {
+        return vec![Span::styled(
+            "────────────────────────────────".to_string(),
+            Style::default().fg(RULE),
+        )];
+    }
+
+    if let Some(rest) = heading(line) {
+        return vec![Span::styled(
+            rest.to_string(),
+            Style::default()
+                .fg(Color::Rgb(220, 220, 220))
+                .add_modifier(Modifier::BOLD),
+        )];
+    }
+
+    if let Some(item) = list_item(line) {
+        let mut spans = vec![
+            Span::raw(" ".repeat(item.indent)),
+            Span::styled(format!("{} ", item.marker), Style::default().fg(MUTED)),
+        ];
+        spans.extend(inline_spans(item.text, base));
+        return spans;
+    }
+
+    inline_spans(line, base)
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    line[level..].strip_prefix(' ')
+}
+
+fn blockquote(line: &str) -> Option<(usize, &str)> {
+    let mut rest = line.trim_start().strip_prefix('>')?;
+    let mut depth = 1;
+    loop {
+        rest = rest.strip_prefix(' ').unwrap_or(rest);
+        match rest.strip_prefix('>') {
+            Some(inner) => {
+                rest = inner;
+                depth += 1;
+            }
+            None => return Some((depth, rest)),
         }
-        if let Some(rest) = line.strip_prefix("## ") {
-            return heading(rest);
+    }
+}
+
+struct ListItem<'a> {
+    indent: usize,
+    marker: String,
+    text: &'a str,
+}
+
+fn list_item(line: &str) -> Option<ListItem<'_>> {
+    let indent = leading_spaces(line);
+    let trimmed = line.trim_start();
+    let (marker, text) = if let Some(text) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| trimmed.strip_prefix(bullet))
+    {
+        let bullets = ["•", "◦", "▪"];
+        (bullets[(indent / 2) % bullets.len()].to_string(), text)
+    } else {
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        let rest = &trimmed[digits..];
+        let text = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "));
+        match text {
+            Some(text) if digits > 0 => (format!("{}.", &trimmed[..digits]), text),
+            _ => return None,
         }
-        if let Some(rest) = line.strip_prefix("# ") {
-            return heading(rest);
+    };
+
+    let (marker, text) = if let Some(text) = text.strip_prefix("[ ] ") {
+        (format!("{marker} ☐"), text)
+    } else if let Some(text) = text
+        .strip_prefix("[x] ")
+        .or_else(|| text.strip_prefix("[X] "))
+    {
+        (format!("{marker} ☑"), text)
+    } else {
+        (marker, text)
+    };
+    Some(ListItem {
+        indent,
+        marker,
+        text,
+    })
+}
+
+/* ---------------- Code ---------------- */
+
+/// The fence character, its run length and the info string after it.
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = trimmed.chars().take_while(|c| *c == marker).count();
+    (len >= 3).then(|| (marker, len, trimmed[len..].trim()))
+}
+
+fn fence_line(line: &str) -> Line<'static> {
+    Line::from(Span::styled(line.to_string(), Style::default().fg(MUTED)))
+}
+
+impl CodeBlock {
+    fn render(&mut self, line: &str) -> Line<'static> {
+        // Code under an indented fence (e.g. in a list item) is indented
+        // by the same amount; only the fence's own indent is dropped.
+        let strip = leading_spaces(line).min(self.indent);
+        let code = &line[strip..];
+        let base = Style::default().fg(TEXT).bg(CODE_BG);
+
+        let mut spans = vec![Span::raw(" ".repeat(self.indent)), Span::styled("  ", base)];
+        match &self.syntax {
+            Some(syntax) => spans.extend(syntax.highlight(code, &mut self.in_comment)),
+            None => spans.push(Span::styled(code.to_string(), base)),
         }
+        Line::from(spans)
+    }
+}
 
-        render_inline_owned(line)
+#[derive(Clone, Copy)]
+enum Syntax {
+    Code {
+        /// Space-separated.
+        keywords: &'static str,
+        line_comment: &'static str,
+        block_comments: bool,
+        quotes: &'static str,
+    },
+    Diff,
+}
+
+impl Syntax {
+    fn for_lang(lang: &str) -> Option<Self> {
+        let code = |keywords, line_comment, block_comments, quotes| Syntax::Code {
+            keywords,
+            line_comment,
+            block_comments,
+            quotes,
+        };
+        Some(match lang.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => code(RUST_KEYWORDS, "//", true, "\""),
+            "python" | "py" => code(PYTHON_KEYWORDS, "#", false, "\"'"),
+            "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" => {
+                code(JS_KEYWORDS, "//", true, "\"'`")
+            }
+            "go" => code(GO_KEYWORDS, "//", true, "\"'`"),
+            "c" | "h" | "cpp" | "c++" | "java" | "kotlin" | "swift" | "cs" | "csharp" => {
+                code(C_KEYWORDS, "//", true, "\"'")
+            }
+            "sh" | "bash" | "zsh" | "shell" | "console" => code(SHELL_KEYWORDS, "#", false, "\"'"),
+            "toml" | "yaml" | "yml" | "ini" => code("true false", "#", false, "\"'"),
+            "diff" | "patch" => Syntax::Diff,
+            _ => return None,
+        })
     }
+
+    fn highlight(&self, line: &str, in_comment: &mut bool) -> Vec<Span<'static>> {
+        let base = Style::default().fg(TEXT).bg(CODE_BG);
+        let (keywords, line_comment, block_comments, quotes) = match *self {
+            Syntax::Diff => {
+                let color = if line.starts_with('+') {
+                    Color::Rgb(70, 190, 120)
+                } else if line.starts_with('-') {
+                    Color::Rgb(220, 95, 90)
+                } else if line.starts_with("@@") {
+                    Color::Rgb(90, 170, 210)
+                } else {
+                    TEXT
+                };
+                return vec![Span::styled(line.to_string(), base.fg(color))];
+            }
+            Syntax::Code {
+                keywords,
+                line_comment,
+                block_comments,
+                quotes,
+            } => (keywords, line_comment, block_comments, quotes),
+        };
+        let comment = base
+            .fg(Color::Rgb(110, 110, 110))
+            .add_modifier(Modifier::ITALIC);
+        let string = base.fg(Color::Rgb(152, 195, 121));
+        let number = base.fg(Color::Rgb(209, 154, 102));
+        let keyword = base.fg(Color::Rgb(198, 120, 221));
+
+        let mut spans = Vec::new();
+        let mut plain = String::new();
+        let mut rest = line;
+        let push = |spans: &mut Vec<Span<'static>>, plain: &mut String, text: &str, style| {
+            if !plain.is_empty() {
+                spans.push(Span::styled(std::mem::take(plain), base));
+            }
+            spans.push(Span::styled(text.to_string(), style));
+        };
+
+        while let Some(c) = rest.chars().next() {
+            if *in_comment {
+                let end = match rest.find("*/") {
+                    Some(i) => {
+                        *in_comment = false;
+                        i + 2
+                    }
+                    None => rest.len(),
+                };
+                push(&mut spans, &mut plain, &rest[..end], comment);
+                rest = &rest[end..];
+                continue;
+            }
+            if rest.starts_with(line_comment) {
+                push(&mut spans, &mut plain, rest, comment);
+                break;
+            }
+            if block_comments && rest.starts_with("/*") {
+                *in_comment = true;
+                push(&mut spans, &mut plain, "/*", comment);
+                rest = &rest[2..];
+                continue;
+            }
+            if quotes.contains(c) {
+                let end = string_end(rest, c);
+                push(&mut spans, &mut plain, &rest[..end], string);
+                rest = &rest[end..];
+                continue;
+            }
+            let after_ident = plain
+                .chars()
+                .last()
+                .is_some_and(|prev| prev.is_alphanumeric() || prev == '_');
+            if (c.is_alphanumeric() || c == '_') && !after_ident {
+                let end = rest
+                    .find(|ch: char| {
+                        !(ch.is_alphanumeric() || ch == '_' || ch == '.' && c.is_ascii_digit())
+                    })
+                    .unwrap_or(rest.len());
+                let word = &rest[..end];
+                if c.is_ascii_digit() {
+                    push(&mut spans, &mut plain, word, number);
+                } else if keywords.split_whitespace().any(|k| k == word) {
+                    push(&mut spans, &mut plain, word, keyword);
+                } else {
+                    plain.push_str(word);
+                }
+                rest = &rest[end..];
+                continue;
+            }
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        if !plain.is_empty() {
+            spans.push(Span::styled(plain, base));
+        }
+        spans
+    }
+}
+
+/// Byte index just past the closing quote, or the end of the line.
+fn string_end(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+const RUST_KEYWORDS: &str =
+    "as async await break const continue crate dyn else enum extern false fn for if impl in \
+     let loop match mod move mut pub ref return self Self static struct super trait true \
+     type unsafe use where while";
+const PYTHON_KEYWORDS: &str =
+    "and as assert async await break class continue def del elif else except False finally \
+     for from global if import in is lambda None nonlocal not or pass raise return self \
+     True try while with yield";
+const JS_KEYWORDS: &str =
+    "async await break case catch class const continue default delete do else enum export \
+     extends false finally for from function if import in instanceof interface let new null \
+     return super switch this throw true try type typeof undefined var void while yield";
+const GO_KEYWORDS: &str =
+    "break case chan const continue default defer else false fallthrough for func go goto \
+     if import interface map nil package range return select struct switch true type var";
+const C_KEYWORDS: &str =
+    "auto bool break case char class const continue default do double else enum extern \
+     false float for if int long namespace new null nullptr private protected public return \
+     short static struct switch template this true typedef union unsigned void volatile \
+     while";
+const SHELL_KEYWORDS: &str =
+    "case do done elif else esac export fi for function if in local return then while";
+
+/* ---------------- Tables ---------------- */
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    let mut cells = vec![String::new()];
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push('|');
+                chars.next();
+            }
+            '|' => cells.push(String::new()),
+            _ => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells
+        .into_iter()
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+fn delimiter_align(cell: &str) -> Option<Align> {
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+        return None;
+    }
+    Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Align::Center,
+        (false, true) => Align::Right,
+        _ => Align::Left,
+    })
+}
+
+fn render_table(rows: &[String]) -> Vec<Line<'static>> {
+    let mut rows = rows.iter().map(|row| table_cells(row)).collect::<Vec<_>>();
+    let aligns = rows.get(1).and_then(|row| {
+        row.iter()
+            .map(|cell| delimiter_align(cell))
+            .collect::<Option<Vec<_>>>()
+    });
+    let has_header = aligns.is_some();
+    if has_header {
+        rows.remove(1);
+    }
+    let aligns = aligns.unwrap_or_default();
+
+    let base = Style::default().fg(TEXT);
+    let header = base.add_modifier(Modifier::BOLD);
+    let cells = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let style = if has_header && i == 0 { header } else { base };
+            row.iter()
+                .map(|cell| inline_spans(cell, style))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let mut widths = vec![3; columns];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(spans_width(cell));
+        }
+    }
+
+    let border = Style::default().fg(RULE);
+    let rule = |left: &str, mid: &str, right: &str| {
+        let segments = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+        Line::from(Span::styled(
+            format!("{left}{}{right}", segments.join(mid)),
+            border,
+        ))
+    };
+
+    let mut out = vec![rule("┌", "┬", "┐")];
+    for (i, row) in cells.into_iter().enumerate() {
+        let mut spans = vec![Span::styled("│", border)];
+        let mut row = row.into_iter();
+        for (col, width) in widths.iter().enumerate() {
+            let cell = row.next().unwrap_or_default();
+            let gap = width - spans_width(&cell);
+            let (left, right) = match aligns.get(col).copied().unwrap_or(Align::Left) {
+                Align::Left => (0, gap),
+                Align::Right => (gap, 0),
+                Align::Center => (gap / 2, gap - gap / 2),
+            };
+            spans.push(Span::raw(" ".repeat(left + 1)));
+            spans.extend(cell);
+            spans.push(Span::raw(" ".repeat(right + 1)));
+            spans.push(Span::styled("│", border));
+        }
+        out.push(Line::from(spans));
+        if has_header && i == 0 {
+            out.push(rule("├", "┼", "┤"));
+        }
+    }
+    out.push(rule("└", "┴", "┘"));
+    out
+}
+
+fn spans_width(spans: &[Span<'_>]) -> usize {
+    spans.iter().map(|span| span.content.width()).sum()
 }
 
 /* ---------------- Inline ---------------- */
 
-fn render_inline_owned(text: &str) -> Line<'static> {
+fn inline_spans(text: &str, base: Style) -> Vec<Span<'static>> {
     let mut spans: Vec<Span<'static>> = Vec::new();
     let mut chars = text.chars().peekable();
 
@@ -77,8 +527,7 @@ fn render_inline_owned(text: &str) -> Line<'static> {
             let content = take_until(&mut chars, "**");
             spans.push(Span::styled(
                 content,
-                Style::default()
-                    .fg(Color::Rgb(220, 220, 220))
+                base.fg(Color::Rgb(220, 220, 220))
                     .add_modifier(Modifier::BOLD),
             ));
             continue;
@@ -86,12 +535,7 @@ fn render_inline_owned(text: &str) -> Line<'static> {
 
         if c == '*' {
             let content = take_until(&mut chars, "*");
-            spans.push(Span::styled(
-                content,
-                Style::default()
-                    .fg(Color::Rgb(200, 200, 200))
-                    .add_modifier(Modifier::ITALIC),
-            ));
+            spans.push(Span::styled(content, base.add_modifier(Modifier::ITALIC)));
             continue;
         }
 
@@ -99,31 +543,25 @@ fn render_inline_owned(text: &str) -> Line<'static> {
             let content = take_until(&mut chars, "`");
             spans.push(Span::styled(
                 content,
-                Style::default()
-                    .fg(Color::Rgb(210, 210, 210))
+                base.fg(Color::Rgb(210, 210, 210))
                     .bg(Color::Rgb(50, 50, 50)),
             ));
             continue;
         }
 
-        spans.push(Span::styled(
-            c.to_string(),
-            Style::default().fg(Color::Rgb(200, 200, 200)),
-        ));
+        match spans.last_mut() {
+            Some(last) if last.style == base => last.content.to_mut().push(c),
+            _ => spans.push(Span::styled(c.to_string(), base)),
+        }
     }
 
-    Line::from(spans)
+    spans
 }
 
 /* ---------------- Helpers ---------------- */
 
-fn heading(text: &str) -> Line<'static> {
-    Line::from(Span::styled(
-        text.to_string(),
-        Style::default()
-            .fg(Color::Rgb(220, 220, 220))
-            .add_modifier(Modifier::BOLD),
-    ))
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
 }
 
 fn take_until<I>(it: &mut std::iter::Peekable<I>, end: &str) -> String
@@ -142,3 +580,92 @@ where
 
     buf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(markdown: &str) -> Vec<Line<'static>> {
+        let mut md = Markdown::new();
+        let mut lines = markdown
+            .lines()
+            .flat_map(|line| md.push(line))
+            .collect::<Vec<_>>();
+        lines.extend(md.flush());
+        lines
+    }
+
+    fn text(line: &Line<'_>) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn tables_are_aligned_once_they_end() {
+        let mut md = Markdown::new();
+        assert!(md.push("| file | lines |").is_empty());
+        assert!(md.push("|:-----|------:|").is_empty());
+        assert!(md.push("| `main.rs` | 12 |").is_empty());
+        assert!(md.holding_table());
+
+        let lines = md.push("Done.");
+        assert_eq!(
+            lines.iter().map(text).collect::<Vec<_>>(),
+            [
+                "┌─────────┬───────┐",
+                "│ file    │ lines │",
+                "├─────────┼───────┤",
+                "│ main.rs │    12 │",
+                "└─────────┴───────┘",
+                "Done.",
+            ]
+        );
+        assert!(lines[1].spans[2]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD));
+        assert!(!md.holding_table());
+        assert!(md.flush().is_empty());
+    }
+
+    #[test]
+    fn fenced_code_keeps_indentation_and_is_highlighted() {
+        let lines = render(
+            "1. Add:\n   ```rust\n   fn main() {\n       let s = \"x\"; // hi\n   }\n   ```\n| a |",
+        );
+        let shown = lines.iter().map(text).collect::<Vec<_>>();
+        assert_eq!(shown[0], "1. Add:");
+        assert_eq!(shown[2], "     fn main() {");
+        assert_eq!(shown[3], "         let s = \"x\"; // hi");
+        assert_eq!(shown[5], "   ```");
+        // The indented closing fence ended the block, so tables work again.
+        assert_eq!(shown[6], "┌─────┐");
+
+        let keyword = lines[2].spans.iter().find(|s| s.content == "fn").unwrap();
+        assert_eq!(keyword.style.fg, Some(Color::Rgb(198, 120, 221)));
+        let string = lines[3]
+            .spans
+            .iter()
+            .find(|s| s.content == "\"x\"")
+            .unwrap();
+        assert_eq!(string.style.fg, Some(Color::Rgb(152, 195, 121)));
+        assert_eq!(lines[3].spans.last().unwrap().content, "// hi");
+    }
+
+    #[test]
+    fn lists_nest_and_quotes_get_a_bar() {
+        let lines = render("- top\n  - nested\n    1. [x] deep\n> > quoted **text**\n* not italic");
+        assert_eq!(
+            lines.iter().map(text).collect::<Vec<_>>(),
+            [
+                "• top",
+                "  ◦ nested",
+                "    1. ☑ deep",
+                "│ │ quoted text",
+                "• not italic",
+            ]
+        );
+    }
+}
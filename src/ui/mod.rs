@@ -1,9 +1,15 @@
 //! ui/mod.rs
+pub mod chart;
 pub mod diff;
+pub mod export;
 pub mod frame;
 pub mod helper;
 pub mod main_ui;
 pub mod markdown;
 pub mod runtime;
+pub mod scroll;
 pub mod terminal;
 pub mod tui;
+
+#[cfg(test)]
+pub mod harness;
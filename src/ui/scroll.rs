@@ -0,0 +1,170 @@
+//! ui/scroll.rs
+//!
+//! Scrollbar, position label and diff minimap drawn in the right gutter of
+//! the execution panel, plus the click-to-jump math shared with main_ui.
+
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    Frame,
+};
+
+use crate::ui::diff::DiffLineKind;
+
+const MINIMAP_ADDED: Color = Color::Rgb(45, 175, 95);
+const MINIMAP_REMOVED: Color = Color::Rgb(220, 90, 80);
+const MINIMAP_MIXED: Color = Color::Rgb(215, 175, 70);
+
+/// Wrapped content height and viewport of the execution panel as of the
+/// last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScrollMetrics {
+    pub total: usize,
+    pub visible: usize,
+    pub top: usize,
+}
+
+impl ScrollMetrics {
+    pub fn scrollable(&self) -> bool {
+        self.total > self.visible
+    }
+
+    pub fn max_top(&self) -> usize {
+        self.total.saturating_sub(self.visible)
+    }
+
+    /// Vim-style position: `Top`, `Bot`, `All` or a percentage.
+    pub fn position_label(&self) -> String {
+        if !self.scrollable() {
+            "All".to_string()
+        } else if self.top == 0 {
+            "Top".to_string()
+        } else if self.top >= self.max_top() {
+            "Bot".to_string()
+        } else {
+            format!("{}%", self.top * 100 / self.max_top())
+        }
+    }
+
+    /// First line to show after a click on `row` of a gutter `height` rows
+    /// tall, centring the clicked position in the viewport.
+    pub fn jump_top(&self, row: usize, height: usize) -> usize {
+        if height <= 1 {
+            return 0;
+        }
+        let target = row.min(height - 1) * self.total.saturating_sub(1) / (height - 1);
+        target.saturating_sub(self.visible / 2).min(self.max_top())
+    }
+}
+
+/// Scrollbar thumb `(start, len)` in rows for a track `height` rows tall.
+pub fn thumb(metrics: ScrollMetrics, height: usize) -> (usize, usize) {
+    if !metrics.scrollable() || height == 0 {
+        return (0, height);
+    }
+    let len = (metrics.visible * height / metrics.total).clamp(1, height);
+    let start = metrics.top.min(metrics.max_top()) * (height - len) / metrics.max_top().max(1);
+    (start, len)
+}
+
+/// Colour per minimap row: green for additions, red for removals, amber
+/// when a row covers both.
+pub fn minimap_rows(kinds: &[Option<DiffLineKind>], height: usize) -> Vec<Option<Color>> {
+    if height == 0 || kinds.is_empty() {
+        return vec![None; height];
+    }
+    (0..height)
+        .map(|row| {
+            let start = row * kinds.len() / height;
+            let end = ((row + 1) * kinds.len() / height).max(start + 1);
+            let slice = &kinds[start..end.min(kinds.len())];
+            let added = slice.contains(&Some(DiffLineKind::Added));
+            let removed = slice.contains(&Some(DiffLineKind::Removed));
+            match (added, removed) {
+                (true, true) => Some(MINIMAP_MIXED),
+                (true, false) => Some(MINIMAP_ADDED),
+                (false, true) => Some(MINIMAP_REMOVED),
+                (false, false) => None,
+            }
+        })
+        .collect()
+}
+
+pub fn render_scrollbar(f: &mut Frame, track: Rect, metrics: ScrollMetrics, color: Color) {
+    if track.width == 0 || !metrics.scrollable() {
+        return;
+    }
+    let (start, len) = thumb(metrics, track.height as usize);
+    let buf = f.buffer_mut();
+    for row in 0..track.height as usize {
+        let in_thumb = row >= start && row < start + len;
+        buf.get_mut(track.x, track.y + row as u16)
+            .set_symbol(if in_thumb { "┃" } else { "│" })
+            .set_style(Style::default().fg(if in_thumb { color } else { Color::DarkGray }));
+    }
+}
+
+pub fn render_minimap(f: &mut Frame, track: Rect, kinds: &[Option<DiffLineKind>]) {
+    if track.width == 0 {
+        return;
+    }
+    let buf = f.buffer_mut();
+    for (row, color) in minimap_rows(kinds, track.height as usize)
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(color) = color {
+            buf.get_mut(track.x, track.y + row as u16)
+                .set_symbol("▐")
+                .set_style(Style::default().fg(color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_and_thumb_follow_position() {
+        let mut m = ScrollMetrics {
+            total: 100,
+            visible: 20,
+            top: 0,
+        };
+        assert_eq!(m.position_label(), "Top");
+        assert_eq!(thumb(m, 20), (0, 4));
+
+        m.top = 40;
+        assert_eq!(m.position_label(), "50%");
+        assert_eq!(thumb(m, 20), (8, 4));
+
+        m.top = 80;
+        assert_eq!(m.position_label(), "Bot");
+        assert_eq!(thumb(m, 20), (16, 4));
+
+        m.total = 10;
+        assert_eq!(m.position_label(), "All");
+    }
+
+    #[test]
+    fn click_jumps_and_minimap_marks_changes() {
+        let m = ScrollMetrics {
+            total: 100,
+            visible: 20,
+            top: 80,
+        };
+        assert_eq!(m.jump_top(0, 20), 0);
+        assert_eq!(m.jump_top(10, 20), 42);
+        assert_eq!(m.jump_top(19, 20), 80);
+
+        let mut kinds = vec![None; 8];
+        kinds[1] = Some(DiffLineKind::Added);
+        kinds[4] = Some(DiffLineKind::Removed);
+        kinds[5] = Some(DiffLineKind::Added);
+        assert_eq!(
+            minimap_rows(&kinds, 4),
+            vec![Some(MINIMAP_ADDED), None, Some(MINIMAP_MIXED), None]
+        );
+    }
+}
@@ -2,34 +2,90 @@ use std::{error::Error, io};
 
 use crossterm::{
     cursor::Show,
-    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 pub struct TerminalSession {
     active: bool,
     mouse_capture: bool,
+    key_release: bool,
 }
 
 impl TerminalSession {
+    /// Asks the terminal to report key releases, for push-to-talk. Returns
+    /// whether releases will arrive; terminals without the kitty keyboard
+    /// protocol never send them.
+    pub fn set_key_release_reporting(&mut self, on: bool) -> bool {
+        if !self.active || on == self.key_release {
+            return self.key_release;
+        }
+        let mut stdout = io::stdout();
+        if on {
+            if supports_keyboard_enhancement().unwrap_or(false)
+                && execute!(
+                    stdout,
+                    PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+                )
+                .is_ok()
+            {
+                self.key_release = true;
+            }
+        } else {
+            let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+            self.key_release = false;
+        }
+        self.key_release
+    }
+
+    /// Hands the terminal back, e.g. to an editor, until `resume`.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        self.restore_with(io::stdout())
+    }
+
+    pub fn resume(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.active {
+            *self = setup_terminal()?;
+        }
+        Ok(())
+    }
+
     fn restore_with<W: io::Write>(&mut self, mut writer: W) -> io::Result<()> {
         if !self.active {
             return Ok(());
         }
+        if self.key_release {
+            let _ = execute!(writer, PopKeyboardEnhancementFlags);
+            self.key_release = false;
+        }
 
         let raw_result = disable_raw_mode();
         let screen_result = if self.mouse_capture {
             execute!(
                 writer,
+                DisableFocusChange,
                 DisableBracketedPaste,
                 DisableMouseCapture,
                 LeaveAlternateScreen,
                 Show
             )
         } else {
-            execute!(writer, DisableBracketedPaste, LeaveAlternateScreen, Show)
+            execute!(
+                writer,
+                DisableFocusChange,
+                DisableBracketedPaste,
+                LeaveAlternateScreen,
+                Show
+            )
         };
         self.active = false;
 
@@ -49,7 +105,12 @@ pub fn setup_terminal() -> Result<TerminalSession, Box<dyn Error>> {
     enable_raw_mode()?;
 
     let mut stdout = io::stdout();
-    if let Err(err) = execute!(stdout, EnterAlternateScreen, EnableBracketedPaste) {
+    if let Err(err) = execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableBracketedPaste,
+        EnableFocusChange
+    ) {
         let _ = disable_raw_mode();
         return Err(Box::new(err));
     }
@@ -59,6 +120,7 @@ pub fn setup_terminal() -> Result<TerminalSession, Box<dyn Error>> {
         if let Err(err) = execute!(stdout, EnableMouseCapture) {
             let _ = execute!(
                 io::stdout(),
+                DisableFocusChange,
                 DisableBracketedPaste,
                 LeaveAlternateScreen,
                 Show
@@ -71,6 +133,7 @@ pub fn setup_terminal() -> Result<TerminalSession, Box<dyn Error>> {
     Ok(TerminalSession {
         active: true,
         mouse_capture,
+        key_release: false,
     })
 }
 
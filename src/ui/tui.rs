@@ -11,12 +11,21 @@ use ratatui::{
 };
 use unicode_width::UnicodeWidthChar;
 
+use crate::candidate_queue::{CandidateQueue, Mark};
+use crate::file_refs::{self, FileRef, RefHit};
+use crate::review_queue::{Decision, ReviewQueue};
+use crate::ui::diff::DiffLineKind;
 use crate::ui::helper::{calculate_input_lines, render_static_command_line, running_pulse};
+use crate::ui::scroll::{self, ScrollMetrics};
 use crate::{
-    logger::parse_user_input_log,
+    agent::{SubagentState, SubagentStatus},
+    logger::{is_tool_call_log, is_tool_result_log, parse_user_input_log},
     state::{
-        AgentState, InputMode, LogLevel, PendingUpdate, PlanItem, UiAccent, UiDensity, UiTheme,
+        merge_changes, AgentState, DiffSnapshot, InputMode, JobKind, JobStatus, LogLevel, LogLine,
+        LogTimestamps, PendingUpdate, PlanItem, TabLabel, UiAccent, UiDensity, UiTheme,
     },
+    timefmt,
+    voice::VoiceActivation,
 };
 
 const FG_MAIN: Color = Color::Rgb(220, 220, 220);
@@ -24,6 +33,8 @@ const FG_DIM: Color = Color::Rgb(170, 170, 170);
 const FG_MUTED: Color = Color::Rgb(120, 120, 120);
 const ACCENT_ORANGE: Color = Color::Rgb(255, 165, 80);
 const PROMPT: &str = ">_ ";
+/// Narrowest execution area that `/split` divides into two panes.
+const SPLIT_MIN_WIDTH: u16 = 100;
 const LOGO: [&str; 5] = [
     " ██████  ███████ ███    ███  ██████   ██████  ██████  ███████ ██████  ",
     "██    ██ ██      ████  ████ ██    ██ ██       ██   ██ ██      ██   ██ ",
@@ -236,8 +247,12 @@ pub fn draw_ui<B: Backend>(
             };
         }
 
+        let (log_rect, diff_rect) = split_execution_area(exec_rect_calc, state);
         render_header(f, header_rect, state);
-        render_execution(f, exec_rect_calc, state);
+        render_execution(f, log_rect, state);
+        if let Some(diff_rect) = diff_rect {
+            render_diff_pane(f, diff_rect, state);
+        }
         render_running_badge(f, running_rect, state);
         render_voice_bar(f, voice_rect, state);
         render_input_box(f, cmd_rect, state);
@@ -247,7 +262,7 @@ pub fn draw_ui<B: Backend>(
             render_command_palette(f, hint_rect, state);
         }
 
-        exec_rect = exec_rect_calc;
+        exec_rect = log_rect;
         input_rect = cmd_rect;
     })?;
 
@@ -332,6 +347,26 @@ fn render_header(f: &mut Frame, area: Rect, state: &AgentState) {
         Span::styled(branch, Style::default().fg(p.fg_dim)),
     ]);
 
+    if let Some(baseline) = &state.ui.diff_baseline {
+        repo_line
+            .spans
+            .push(Span::styled(" · diff: ", Style::default().fg(p.fg_muted)));
+        repo_line.spans.push(Span::styled(
+            baseline.clone(),
+            Style::default().fg(p.fg_dim),
+        ));
+    }
+
+    if !state.ui.repo_languages.is_empty() {
+        repo_line
+            .spans
+            .push(Span::styled(" · ", Style::default().fg(p.fg_muted)));
+        repo_line.spans.push(Span::styled(
+            state.ui.repo_languages.join("+"),
+            Style::default().fg(p.fg_dim),
+        ));
+    }
+
     if state.ui.indexing {
         repo_line.spans.push(Span::styled(
             " · indexing…",
@@ -339,10 +374,21 @@ fn render_header(f: &mut Frame, area: Rect, state: &AgentState) {
                 .fg(p.fg_muted)
                 .add_modifier(Modifier::ITALIC),
         ));
+    } else if state.ui.index_refreshing > 0 {
+        repo_line.spans.push(Span::styled(
+            format!(" · reindexing {} files…", state.ui.index_refreshing),
+            Style::default()
+                .fg(p.fg_muted)
+                .add_modifier(Modifier::ITALIC),
+        ));
     } else if state.ui.indexed {
+        let label = match state.ui.index_updated_at {
+            Some(at) => format!(" · indexed ✓ {}", at.format("%H:%M:%S")),
+            None => " · indexed ✓".to_string(),
+        };
         repo_line
             .spans
-            .push(Span::styled(" · indexed ✓", Style::default().fg(p.fg_dim)));
+            .push(Span::styled(label, Style::default().fg(p.fg_dim)));
     }
 
     let mut lines = Vec::new();
@@ -388,112 +434,279 @@ fn render_execution(f: &mut Frame, area: Rect, state: &AgentState) {
     };
 
     let height = padded.height.max(1) as usize;
-    let mut lines: Vec<Line> = Vec::new();
+    let ExecutionLines {
+        lines,
+        diff_marks,
+        diff_refs,
+    } = execution_lines(state, padded.width, p);
+
+    let (lines, origins) = wrap_lines_indexed(lines, padded.width as usize);
+    let max_scroll = lines.len().saturating_sub(height);
+    let scroll = if state.ui.follow_tail {
+        max_scroll
+    } else {
+        max_scroll.saturating_sub(state.ui.exec_scroll)
+    };
+    let metrics = ScrollMetrics {
+        total: lines.len(),
+        visible: height,
+        top: scroll,
+    };
+    state.ui.exec_metrics.set(metrics);
+    *state.ui.exec_refs.borrow_mut() = visible_file_refs(
+        &lines[scroll.min(lines.len())..],
+        &origins[scroll.min(lines.len())..],
+        &diff_refs,
+        padded,
+    );
+
+    f.render_widget(
+        Paragraph::new(lines).scroll((clamp_scroll_offset(scroll), 0)),
+        padded,
+    );
+
+    if !metrics.scrollable() {
+        return;
+    }
+    let track = |x: u16| Rect {
+        x,
+        y: padded.y,
+        width: 1,
+        height: padded.height,
+    };
+    scroll::render_scrollbar(f, track(area.right() - 1), metrics, p.accent);
+    if state.ui.diff_minimap && !diff_marks.is_empty() {
+        let mut kinds = vec![None; metrics.total];
+        for (kind, origin) in kinds.iter_mut().zip(&origins) {
+            if let Ok(i) = diff_marks.binary_search_by_key(origin, |(idx, _)| *idx) {
+                *kind = Some(diff_marks[i].1);
+            }
+        }
+        scroll::render_minimap(f, track(area.right() - 2), &kinds);
+    }
+
+    let label = metrics.position_label();
+    let width = label.chars().count() as u16;
+    if area.width > width + 4 {
+        f.render_widget(
+            Paragraph::new(Span::styled(label, Style::default().fg(p.fg_muted))),
+            Rect {
+                x: area.right() - 2 - width,
+                y: area.y,
+                width,
+                height: 1,
+            },
+        );
+    }
+}
+
+struct ExecutionLines<'a> {
+    lines: Vec<Line<'a>>,
+    /// Diff row kinds for the minimap, by unwrapped line index.
+    diff_marks: Vec<(usize, DiffLineKind)>,
+    /// File references for clickable rows, by unwrapped line index.
+    diff_refs: Vec<(usize, FileRef)>,
+}
+
+/// A tool call and the output lines under it. Collapsed blocks show the
+/// first output line and how many more there are.
+#[derive(Default)]
+struct ToolBlock {
+    open: bool,
+    expanded: bool,
+    selected: bool,
+    output_lines: usize,
+}
+
+impl ToolBlock {
+    fn close(&mut self, lines: &mut Vec<Line<'_>>, p: UiPalette) {
+        let hidden = self.output_lines.saturating_sub(1);
+        if self.open && !self.expanded && hidden > 0 {
+            let mut more = format!("    … +{hidden} line{}", if hidden == 1 { "" } else { "s" });
+            if self.selected {
+                more.push_str(" (Enter to expand)");
+            }
+            lines.push(Line::from(Span::styled(
+                more,
+                Style::default().fg(p.fg_muted),
+            )));
+        }
+        *self = Self::default();
+    }
+}
+
+/// Everything the execution panel can scroll through, before wrapping.
+fn execution_lines(state: &AgentState, width: u16, p: UiPalette) -> ExecutionLines<'_> {
+    let mut out = ExecutionLines {
+        lines: Vec::new(),
+        diff_marks: Vec::new(),
+        diff_refs: Vec::new(),
+    };
 
     let mut md = crate::ui::markdown::Markdown::new();
+    let pad = timestamp_pad(state.timestamps);
+    let mut block = ToolBlock::default();
+    let selected = state.ui.selected_tool.or_else(|| {
+        state
+            .logs
+            .iter()
+            .filter(|log| is_tool_call_log(&log.text))
+            .last()
+            .map(|log| log.id)
+    });
 
     for log in state.logs.iter() {
         let level = log.level;
         let fresh = log.at.elapsed().as_secs() <= 2;
         let text = log.text.as_str();
+        let stamp = timestamp_span(state.timestamps, log, p);
+        let markdown = parse_user_input_log(text).is_none()
+            && !["● ", "└ ", "· "]
+                .iter()
+                .any(|prefix| text.starts_with(prefix));
+        if !markdown {
+            out.lines.extend(
+                md.flush()
+                    .into_iter()
+                    .map(|line| with_timestamp(pad.clone(), line)),
+            );
+        }
+        if is_tool_result_log(text) && block.open {
+            block.output_lines += 1;
+            if !block.expanded && block.output_lines > 1 {
+                continue;
+            }
+        } else if !text.starts_with("· ") {
+            block.close(&mut out.lines, p);
+        }
         if let Some(input) = parse_user_input_log(text) {
-            lines.extend(render_static_command_line(input, padded.width as usize));
+            out.lines
+                .extend(render_static_command_line(input, width as usize));
+            // A new turn; an unclosed fence in the last answer ends here.
+            md = crate::ui::markdown::Markdown::new();
             continue;
         }
 
         if text.starts_with("● ") {
-            lines.push(Line::from(""));
-            lines.push(Line::from(Span::styled(
-                text,
-                Style::default().fg(p.fg_main),
-            )));
+            if is_tool_call_log(text) {
+                block = ToolBlock {
+                    open: true,
+                    expanded: state.ui.expanded_tools.contains(&log.id),
+                    selected: selected == Some(log.id),
+                    output_lines: 0,
+                };
+            }
+            let style = if block.selected {
+                Style::default().fg(p.accent).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(p.fg_main)
+            };
+            out.lines.push(Line::from(""));
+            out.lines
+                .push(with_timestamp(stamp, Line::from(Span::styled(text, style))));
             continue;
         }
 
         if text.starts_with("└ ") {
-            lines.push(Line::from(Span::styled(
-                text,
-                Style::default().fg(p.fg_dim),
-            )));
+            out.lines.push(with_timestamp(
+                stamp,
+                Line::from(Span::styled(text, Style::default().fg(p.fg_dim))),
+            ));
             continue;
         }
 
         if text.starts_with("· ") {
-            lines.push(Line::from(Span::styled(
-                text,
-                Style::default()
-                    .fg(p.fg_muted)
-                    .add_modifier(Modifier::ITALIC),
-            )));
+            out.lines.push(with_timestamp(
+                stamp,
+                Line::from(Span::styled(
+                    text,
+                    Style::default()
+                        .fg(p.fg_muted)
+                        .add_modifier(Modifier::ITALIC),
+                )),
+            ));
             continue;
         }
 
-        lines.push(style_log_line(md.render_line(text), level, fresh, p));
+        // Lines of a table that just ended belong to earlier log lines, so
+        // only the last line rendered gets this line's stamp.
+        let rendered = md.push(text);
+        let own = (!md.holding_table()).then(|| rendered.len().saturating_sub(1));
+        for (i, line) in rendered.into_iter().enumerate() {
+            out.lines.push(if Some(i) == own {
+                with_timestamp(stamp.clone(), style_log_line(line, level, fresh, p))
+            } else {
+                with_timestamp(pad.clone(), line)
+            });
+        }
     }
+    out.lines.extend(
+        md.flush()
+            .into_iter()
+            .map(|line| with_timestamp(pad.clone(), line)),
+    );
+    block.close(&mut out.lines, p);
 
     if state.ui.diff_active && !state.ui.diff_snapshot.is_empty() {
-        let rendered_diffs: Vec<_> = state
-            .ui
-            .diff_snapshot
-            .iter()
-            .map(|snap| {
-                crate::ui::diff::Diff::from_texts(snap.target.clone(), &snap.before, &snap.after)
-            })
-            .collect();
-        let total_added: usize = rendered_diffs.iter().map(|d| d.added).sum();
-        let total_removed: usize = rendered_diffs.iter().map(|d| d.removed).sum();
-
-        lines.push(Line::from(""));
-        lines.push(Line::from(vec![
-            Span::styled(
-                format!("Changes ({})", state.ui.diff_snapshot.len()),
-                Style::default().fg(p.fg_main).add_modifier(Modifier::BOLD),
-            ),
-            Span::raw("  "),
-            Span::styled(
-                format!("+{total_added}"),
-                Style::default().fg(Color::Rgb(70, 190, 120)),
-            ),
-            Span::raw(" "),
-            Span::styled(
-                format!("-{total_removed}"),
-                Style::default().fg(Color::Rgb(220, 95, 90)),
-            ),
-        ]));
-        lines.push(Line::from(Span::styled(
-            "Use /undo to revert latest change, /diff to revisit session changes.",
-            Style::default()
-                .fg(p.fg_muted)
-                .add_modifier(Modifier::ITALIC),
-        )));
+        push_diff_section(&mut out, &state.ui.diff_snapshot, true, width, p);
+    }
 
-        for (idx, diff) in rendered_diffs.iter().enumerate() {
-            lines.extend(crate::ui::diff::render_diff(diff, padded.width));
-            if idx + 1 < state.ui.diff_snapshot.len() {
-                lines.push(Line::from(Span::styled(
-                    "─".repeat(padded.width.saturating_sub(1) as usize),
-                    Style::default().fg(Color::Rgb(70, 70, 70)),
+    if let Some(p) = &state.ui.pending_permission {
+        out.lines.push(Line::from(""));
+        match &p.protected {
+            Some(paths) => {
+                let style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+                out.lines.push(Line::from(Span::styled(
+                    format!("Protected path: {paths}"),
+                    style,
                 )));
+                let prompt = if p.confirmed_once {
+                    format!("Really let {} change it? [y]es [n]o", p.tool_name)
+                } else {
+                    format!("Allow {} ({})? [y]es [n]o", p.tool_name, p.args_summary)
+                };
+                out.lines.push(Line::from(Span::styled(prompt, style)));
             }
+            None => out.lines.push(Line::from(Span::styled(
+                format!(
+                    "Allow {} ({})? [y]es [n]o [a]lways `{}`",
+                    p.tool_name, p.args_summary, p.always
+                ),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ))),
         }
     }
 
-    if let Some(p) = &state.ui.pending_permission {
-        lines.push(Line::from(""));
-        lines.push(Line::from(Span::styled(
-            format!(
-                "Allow {} ({})? [y]es [n]o [a]lways",
-                p.tool_name, p.args_summary
-            ),
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+    if let Some(budget) = &state.ui.pending_budget {
+        let style = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        out.lines.push(Line::from(""));
+        out.lines.push(Line::from(Span::styled(
+            format!("Change budget exceeded: {}.", budget.summary),
+            style,
         )));
+        out.lines.push(Line::from(Span::styled(
+            "[c]ontinue  [s]plit: stop and follow up later  [r]oll back this run",
+            style,
+        )));
+    }
+
+    if let Some(candidates) = &state.ui.candidates {
+        out.lines.push(Line::from(""));
+        out.lines.extend(render_candidate_lines(candidates, p));
+    }
+
+    if let Some(review) = &state.ui.review {
+        out.lines.push(Line::from(""));
+        out.lines.extend(render_review_lines(review, p));
     }
 
     if let Some(update) = &state.ui.pending_update {
-        lines.push(Line::from(""));
-        lines.push(Line::from(Span::styled(
+        out.lines.push(Line::from(""));
+        out.lines.push(Line::from(Span::styled(
             pending_update_prompt(update),
             Style::default()
                 .fg(Color::Yellow)
@@ -501,10 +714,25 @@ fn render_execution(f: &mut Frame, area: Rect, state: &AgentState) {
         )));
     }
 
+    if let Some(exit) = &state.ui.pending_exit {
+        let style = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        out.lines.push(Line::from(""));
+        out.lines.push(Line::from(Span::styled(
+            format!("Exit now? {}.", exit.reasons.join("; ")),
+            style,
+        )));
+        out.lines.push(Line::from(Span::styled(
+            "[c]ancel work and exit  [w]ait: exit when done  [n]o, stay",
+            style,
+        )));
+    }
+
     if state.ui.streaming_active {
         let partial = state.ui.streaming_buffer.rsplit('\n').next().unwrap_or("");
         if !partial.is_empty() {
-            lines.push(Line::from(Span::styled(
+            out.lines.push(Line::from(Span::styled(
                 format!("{partial}█"),
                 Style::default().fg(p.fg_dim),
             )));
@@ -512,24 +740,303 @@ fn render_execution(f: &mut Frame, area: Rect, state: &AgentState) {
     }
 
     if !state.plan_items.is_empty() {
-        lines.push(Line::from(""));
-        lines.extend(render_plan_lines(state, p));
+        out.lines.push(Line::from(""));
+        out.lines.extend(render_plan_lines(state, p));
     }
 
-    let lines = wrap_lines_safely(lines, padded.width as usize);
-    let max_scroll = lines.len().saturating_sub(height);
-    let scroll = if state.ui.follow_tail {
-        max_scroll
-    } else {
-        max_scroll.saturating_sub(state.ui.exec_scroll)
+    let tasks = render_task_lines(state, p);
+    if !tasks.is_empty() {
+        out.lines.push(Line::from(""));
+        out.lines.extend(tasks);
+    }
+
+    let steers = render_steer_lines(state, p);
+    if !steers.is_empty() {
+        out.lines.push(Line::from(""));
+        out.lines.extend(steers);
+    }
+
+    let subagents = render_subagent_lines(state, p);
+    if !subagents.is_empty() {
+        out.lines.push(Line::from(""));
+        out.lines.extend(subagents);
+    }
+
+    out
+}
+
+/// What the focused panel shows, wrapped to `width`: the diff view when it
+/// is open, otherwise the execution log rows visible in the last frame (all
+/// of them if the panel has not been drawn at this width).
+pub fn panel_lines(state: &AgentState, width: u16) -> Vec<Line<'static>> {
+    let p = palette(state);
+    if state.ui.diff_active && !state.ui.diff_snapshot.is_empty() {
+        let mut out = ExecutionLines {
+            lines: Vec::new(),
+            diff_marks: Vec::new(),
+            diff_refs: Vec::new(),
+        };
+        push_diff_section(&mut out, &state.ui.diff_snapshot, true, width, p);
+        return wrap_lines_safely(out.lines, width as usize);
+    }
+    let mut lines = wrap_lines_safely(execution_lines(state, width, p).lines, width as usize);
+    let metrics = state.ui.exec_metrics.get();
+    if metrics.total == lines.len() && metrics.visible > 0 {
+        let end = (metrics.top + metrics.visible).min(lines.len());
+        lines.truncate(end);
+        lines.drain(..metrics.top.min(end));
+    }
+    lines
+}
+
+/// The side-by-side diffs of `snapshots` with their header, and the line
+/// each file's diff starts on.
+fn push_diff_section(
+    out: &mut ExecutionLines<'_>,
+    snapshots: &[DiffSnapshot],
+    hint: bool,
+    width: u16,
+    p: UiPalette,
+) -> Vec<usize> {
+    let rendered_diffs: Vec<_> = snapshots
+        .iter()
+        .map(|snap| {
+            crate::ui::diff::Diff::from_texts(snap.target.clone(), &snap.before, &snap.after)
+        })
+        .collect();
+    let total_added: usize = rendered_diffs.iter().map(|d| d.added).sum();
+    let total_removed: usize = rendered_diffs.iter().map(|d| d.removed).sum();
+
+    out.lines.push(Line::from(""));
+    out.lines.push(Line::from(vec![
+        Span::styled(
+            format!("Changes ({})", snapshots.len()),
+            Style::default().fg(p.fg_main).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("  "),
+        Span::styled(
+            format!("+{total_added}"),
+            Style::default().fg(Color::Rgb(70, 190, 120)),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            format!("-{total_removed}"),
+            Style::default().fg(Color::Rgb(220, 95, 90)),
+        ),
+    ]));
+    if hint {
+        out.lines.push(Line::from(Span::styled(
+            "Use /undo to revert latest change, /diff to revisit session changes.",
+            Style::default()
+                .fg(p.fg_muted)
+                .add_modifier(Modifier::ITALIC),
+        )));
+    }
+
+    let mut starts = Vec::with_capacity(rendered_diffs.len());
+    for (idx, (diff, snap)) in rendered_diffs.iter().zip(snapshots).enumerate() {
+        starts.push(out.lines.len());
+        let mut rendered = crate::ui::diff::render_diff(diff, width);
+        let status = match (snap.created, snap.deleted) {
+            (true, false) => Some("new file"),
+            (false, true) => Some("deleted"),
+            _ => None,
+        };
+        if let (Some(status), Some(header)) = (status, rendered.first_mut()) {
+            header.spans.extend([
+                Span::raw("  "),
+                Span::styled(status, Style::default().fg(p.fg_muted)),
+            ]);
+        }
+        let file_ref = |line: Option<usize>| FileRef {
+            path: diff.file.clone(),
+            line: line.unwrap_or(1),
+            column: None,
+        };
+        // Notebook line numbers count cell source lines, not lines of the file.
+        let first_line = diff
+            .lines
+            .iter()
+            .find_map(|l| l.new_lineno.or(l.old_lineno))
+            .filter(|_| !diff.notebook);
+        out.diff_refs.push((out.lines.len(), file_ref(first_line)));
+        if diff.asset.is_none() && !diff.lines.is_empty() {
+            let first = out.lines.len() + rendered.len() - diff.lines.len();
+            for (j, line) in diff.lines.iter().enumerate() {
+                out.diff_marks.push((first + j, line.kind));
+                if diff.notebook {
+                    continue;
+                }
+                if let Some(n) = line.new_lineno.or(line.old_lineno) {
+                    out.diff_refs.push((first + j, file_ref(Some(n))));
+                }
+            }
+        }
+        out.lines.extend(rendered);
+        if idx + 1 < snapshots.len() {
+            out.lines.push(Line::from(Span::styled(
+                "─".repeat(width.saturating_sub(1) as usize),
+                Style::default().fg(Color::Rgb(70, 70, 70)),
+            )));
+        }
+    }
+    starts
+}
+
+/// The execution panel and, with `/split` on a wide enough terminal, the
+/// session diff pane to its right.
+fn split_execution_area(area: Rect, state: &AgentState) -> (Rect, Option<Rect>) {
+    if !state.ui.split_diff || area.width < SPLIT_MIN_WIDTH {
+        return (area, None);
+    }
+    let left = area.width / 2;
+    let log = Rect {
+        width: left,
+        ..area
+    };
+    let diff = Rect {
+        x: area.x + left,
+        width: area.width - left,
+        ..area
+    };
+    (log, Some(diff))
+}
+
+fn render_diff_pane(f: &mut Frame, area: Rect, state: &AgentState) {
+    let p = palette(state);
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .border_style(Style::default().fg(p.border))
+        .title(Span::styled(
+            " session diff ",
+            Style::default().fg(p.fg_muted),
+        ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let padded = Rect {
+        x: inner.x + 1,
+        y: inner.y + 1,
+        width: inner.width.saturating_sub(2),
+        height: inner.height.saturating_sub(1),
     };
+    if padded.width < 4 || padded.height == 0 {
+        return;
+    }
 
+    let (lines, scroll) = diff_pane_lines(
+        &state.session_changes,
+        padded.width,
+        padded.height as usize,
+        p,
+    );
+    if lines.is_empty() {
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                "No changes yet this session.",
+                Style::default().fg(p.fg_muted),
+            )),
+            padded,
+        );
+        return;
+    }
     f.render_widget(
         Paragraph::new(lines).scroll((clamp_scroll_offset(scroll), 0)),
         padded,
     );
 }
 
+/// The session's changes merged per file, wrapped to `width`, and the row
+/// to show at the top: where the most recently changed file starts, or the
+/// header when that is the first file.
+fn diff_pane_lines(
+    session_changes: &[DiffSnapshot],
+    width: u16,
+    height: usize,
+    p: UiPalette,
+) -> (Vec<Line<'static>>, usize) {
+    let changes = merge_changes(session_changes);
+    if changes.is_empty() {
+        return (Vec::new(), 0);
+    }
+    let mut out = ExecutionLines {
+        lines: Vec::new(),
+        diff_marks: Vec::new(),
+        diff_refs: Vec::new(),
+    };
+    let starts = push_diff_section(&mut out, &changes, false, width, p);
+    let latest = session_changes
+        .last()
+        .and_then(|last| changes.iter().position(|c| c.target == last.target))
+        .filter(|&i| i > 0)
+        .map_or(0, |i| starts[i]);
+    let (lines, origins) = wrap_lines_indexed(out.lines, width as usize);
+    let top = origins.iter().position(|&o| o >= latest).unwrap_or(0);
+    let scroll = top.min(lines.len().saturating_sub(height));
+    (lines, scroll)
+}
+
+/// Clickable `path:line` references on the visible rows. Diff rows link
+/// to their file and line as a whole.
+fn visible_file_refs(
+    rows: &[Line<'_>],
+    origins: &[usize],
+    diff_refs: &[(usize, FileRef)],
+    area: Rect,
+) -> Vec<RefHit> {
+    let mut hits = Vec::new();
+    for (offset, (row, origin)) in rows
+        .iter()
+        .zip(origins)
+        .take(area.height as usize)
+        .enumerate()
+    {
+        let y = area.y + offset as u16;
+        if let Ok(i) = diff_refs.binary_search_by_key(origin, |(idx, _)| *idx) {
+            hits.push(RefHit {
+                row: y,
+                start: area.x,
+                end: area.right(),
+                target: diff_refs[i].1.clone(),
+            });
+            continue;
+        }
+        let text: String = row.spans.iter().map(|span| span.content.as_ref()).collect();
+        for (start, end, target) in file_refs::find_refs(&text) {
+            hits.push(RefHit {
+                row: y,
+                start: area.x + start as u16,
+                end: area.x + end as u16,
+                target,
+            });
+        }
+    }
+    hits
+}
+
+fn timestamp_span(mode: LogTimestamps, log: &LogLine, p: UiPalette) -> Option<Span<'static>> {
+    let stamp = match mode {
+        LogTimestamps::Off => return None,
+        LogTimestamps::Absolute => timefmt::clock(&log.wall),
+        LogTimestamps::Relative => timefmt::relative(log.at.elapsed()),
+    };
+    Some(Span::styled(
+        format!("{stamp:>8} "),
+        Style::default().fg(p.fg_muted),
+    ))
+}
+
+/// Blank space as wide as a timestamp, for lines that have none.
+fn timestamp_pad(mode: LogTimestamps) -> Option<Span<'static>> {
+    (mode != LogTimestamps::Off).then(|| Span::raw(" ".repeat(9)))
+}
+
+fn with_timestamp<'a>(stamp: Option<Span<'static>>, mut line: Line<'a>) -> Line<'a> {
+    if let Some(stamp) = stamp {
+        line.spans.insert(0, stamp);
+    }
+    line
+}
+
 fn clamp_scroll_offset(scroll: usize) -> u16 {
     scroll.min(u16::MAX as usize) as u16
 }
@@ -564,7 +1071,14 @@ fn style_log_line(
 }
 
 fn render_plan_lines(state: &AgentState, p: UiPalette) -> Vec<Line<'static>> {
-    render_plan_lines_for_items(&state.plan_items, p)
+    let mut lines = render_plan_lines_for_items(&state.plan_items, p);
+    if state.plan_approved {
+        lines[0].spans.push(Span::styled(
+            " · approved",
+            Style::default().fg(Color::Rgb(70, 190, 120)),
+        ));
+    }
+    lines
 }
 
 fn render_plan_lines_for_items(plan_items: &[PlanItem], p: UiPalette) -> Vec<Line<'static>> {
@@ -596,6 +1110,12 @@ fn render_plan_lines_for_items(plan_items: &[PlanItem], p: UiPalette) -> Vec<Lin
             Span::styled(format!("{mark} "), style),
             Span::styled(item.text.clone(), Style::default().fg(p.fg_dim)),
         ]));
+        if !item.files.is_empty() && !item.done {
+            lines.push(Line::from(Span::styled(
+                format!("    {}", item.files.join(", ")),
+                Style::default().fg(p.fg_muted),
+            )));
+        }
     }
 
     if plan_items.len() > 8 {
@@ -608,6 +1128,285 @@ fn render_plan_lines_for_items(plan_items: &[PlanItem], p: UiPalette) -> Vec<Lin
     lines
 }
 
+/// Unfinished `/queue` tasks; empty when there are none.
+fn render_task_lines(state: &AgentState, p: UiPalette) -> Vec<Line<'static>> {
+    let tasks: Vec<_> = state
+        .jobs
+        .iter()
+        .filter(|job| matches!(job.kind, JobKind::Agent { .. }))
+        .filter(|job| matches!(job.status, JobStatus::Queued | JobStatus::Running))
+        .collect();
+    if tasks.is_empty() {
+        return Vec::new();
+    }
+    let running = tasks
+        .iter()
+        .filter(|job| job.status == JobStatus::Running)
+        .count();
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            "Tasks",
+            Style::default().fg(p.fg_main).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(" · {running} running, {} queued", tasks.len() - running),
+            Style::default().fg(p.fg_muted),
+        ),
+    ])];
+    for job in tasks.iter().take(8) {
+        let (mark, style) = if job.status == JobStatus::Running {
+            (
+                "[>]",
+                Style::default().fg(p.accent).add_modifier(Modifier::BOLD),
+            )
+        } else {
+            ("[ ]", Style::default().fg(p.fg_muted))
+        };
+        let sandbox = if matches!(job.kind, JobKind::Agent { sandboxed: true }) {
+            " (sandbox)"
+        } else {
+            ""
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{mark} #{} ", job.id), style),
+            Span::styled(
+                format!("{}{sandbox}", job.input),
+                Style::default().fg(p.fg_dim),
+            ),
+        ]));
+    }
+    if tasks.len() > 8 {
+        lines.push(Line::from(Span::styled(
+            format!("... {} more", tasks.len() - 8),
+            Style::default().fg(p.fg_muted),
+        )));
+    }
+    lines
+}
+
+/// Steers sent during the current run, latest last; `/steer resend <n>`
+/// takes the numbers shown. Empty when there are none.
+fn render_steer_lines(state: &AgentState, p: UiPalette) -> Vec<Line<'static>> {
+    let steers = &state.steer_history;
+    if steers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            "Steering",
+            Style::default().fg(p.fg_main).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(" · {} this run", steers.len()),
+            Style::default().fg(p.fg_muted),
+        ),
+    ])];
+    let skipped = steers.len().saturating_sub(6);
+    if skipped > 0 {
+        lines.push(Line::from(Span::styled(
+            format!("... {skipped} earlier"),
+            Style::default().fg(p.fg_muted),
+        )));
+    }
+    for (i, record) in steers.iter().enumerate().skip(skipped) {
+        let (mark, style) = if record.live {
+            ("live", Style::default().fg(p.accent))
+        } else {
+            ("queued", Style::default().fg(p.fg_muted))
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("[{}] {} ", i + 1, timefmt::clock(&record.at)),
+                Style::default().fg(p.fg_muted),
+            ),
+            Span::styled(format!("{mark} "), style),
+            Span::styled(record.text.clone(), Style::default().fg(p.fg_dim)),
+        ]));
+    }
+    lines
+}
+
+/// The run's sub-agents as a tree, each under the one that spawned it.
+/// Empty when the run spawned none.
+fn render_subagent_lines(state: &AgentState, p: UiPalette) -> Vec<Line<'static>> {
+    let subagents = &state.ui.subagents;
+    if subagents.is_empty() {
+        return Vec::new();
+    }
+    let running = subagents
+        .iter()
+        .filter(|s| s.state == SubagentState::Running)
+        .count();
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            "Sub-agents",
+            Style::default().fg(p.fg_main).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(" · {running} running, {} total", subagents.len()),
+            Style::default().fg(p.fg_muted),
+        ),
+    ])];
+    // Depth-first from the main agent's children.
+    let mut stack: Vec<(&SubagentStatus, usize)> = subagents
+        .iter()
+        .rev()
+        .filter(|s| s.parent.is_none())
+        .map(|s| (s, 0))
+        .collect();
+    while let Some((subagent, depth)) = stack.pop() {
+        let (mark, style) = match subagent.state {
+            SubagentState::Running => (
+                "[>]",
+                Style::default().fg(p.accent).add_modifier(Modifier::BOLD),
+            ),
+            SubagentState::Done => ("[x]", Style::default().fg(p.fg_dim)),
+            _ => ("[!]", Style::default().fg(p.accent)),
+        };
+        let mut spans = vec![
+            Span::raw("  ".repeat(depth)),
+            Span::styled(format!("{mark} #{} ", subagent.id), style),
+            Span::styled(subagent.task.clone(), Style::default().fg(p.fg_dim)),
+            Span::styled(
+                format!(
+                    " · {} tools · {}/{} tokens",
+                    subagent.tool_calls, subagent.tokens, subagent.token_budget
+                ),
+                Style::default().fg(p.fg_muted),
+            ),
+        ];
+        if !matches!(subagent.state, SubagentState::Running | SubagentState::Done) {
+            spans.push(Span::styled(
+                format!(" · {}", subagent.state.as_str()),
+                style,
+            ));
+        }
+        lines.push(Line::from(spans));
+        stack.extend(
+            subagents
+                .iter()
+                .rev()
+                .filter(|s| s.parent == Some(subagent.id))
+                .map(|s| (s, depth + 1)),
+        );
+    }
+    lines
+}
+
+/// The review queue: one row per file with its decision, the selected row
+/// marked, under the keys that drive it.
+fn render_review_lines(review: &ReviewQueue, p: UiPalette) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(Span::styled(
+        format!(
+            "Review {} file(s): [a]ccept [r]eject [A]/[R] all  [d]iff  [c]onfirm  [esc] close",
+            review.items.len()
+        ),
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    for (i, item) in review.items.iter().enumerate() {
+        let change = &item.change;
+        let diff =
+            crate::ui::diff::Diff::from_texts(change.target.clone(), &change.before, &change.after);
+        let (mark, color) = match item.decision {
+            None => ("?", p.fg_main),
+            Some(Decision::Accept) => ("✓", Color::Rgb(70, 190, 120)),
+            Some(Decision::Reject) => ("✗", Color::Rgb(220, 95, 90)),
+        };
+        let status = if change.created {
+            "  new file"
+        } else if change.deleted {
+            "  deleted"
+        } else {
+            ""
+        };
+        let selected = i == review.selected;
+        let mut style = Style::default().fg(color);
+        if selected {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(
+                if selected { "› " } else { "  " },
+                Style::default().fg(p.accent),
+            ),
+            Span::styled(format!("{mark} {}", change.target), style),
+            Span::styled(
+                format!("  +{} -{}{status}", diff.added, diff.removed),
+                Style::default().fg(p.fg_muted),
+            ),
+        ]));
+    }
+    lines
+}
+
+/// The test candidate list in ranked order, picked and discarded ones
+/// marked, with the discard reason prompt under it while one is typed.
+fn render_candidate_lines(queue: &CandidateQueue, p: UiPalette) -> Vec<Line<'static>> {
+    let header = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut lines = vec![Line::from(Span::styled(
+        format!(
+            "Test candidates {}: [space] pick  [x] discard  [w]rite tests  [esc] close",
+            queue.items.len()
+        ),
+        header,
+    ))];
+    for (i, item) in queue.items.iter().enumerate() {
+        let candidate = &item.candidate;
+        let (mark, color) = match item.mark {
+            None => ("·", p.fg_main),
+            Some(Mark::Picked) => ("✓", Color::Rgb(70, 190, 120)),
+            Some(Mark::Discarded(_)) => ("✗", Color::Rgb(220, 95, 90)),
+        };
+        let mut detail = if candidate.covered_by.is_empty() {
+            "  untested".to_string()
+        } else {
+            format!("  tested by {}", candidate.covered_by.join(", "))
+        };
+        if let Some(covered) = candidate.changed_lines_covered {
+            detail.push_str(&format!(", changed lines {covered}"));
+        }
+        if let Some(Mark::Discarded(reason)) = &item.mark {
+            detail = format!("  discarded: {reason}");
+        }
+        let selected = i == queue.selected;
+        let mut style = Style::default().fg(color);
+        if selected {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(
+                if selected { "› " } else { "  " },
+                Style::default().fg(p.accent),
+            ),
+            Span::styled(
+                format!(
+                    "{mark} {} {}:{}",
+                    candidate.symbol, candidate.path, candidate.line
+                ),
+                style,
+            ),
+            Span::styled(detail, Style::default().fg(p.fg_muted)),
+        ]));
+    }
+    if let Some(reason) = &queue.reason {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "Why discard {}? {reason}▏  [enter] record  [esc] cancel",
+                queue.items[queue.selected].candidate.symbol
+            ),
+            header,
+        )));
+    }
+    lines
+}
+
 fn pending_update_prompt(update: &PendingUpdate) -> String {
     if update.installing {
         format!("Installing Osmogrep {}...", update.latest_version)
@@ -628,10 +1427,17 @@ fn update_status_label(update: &PendingUpdate) -> &'static str {
 }
 
 fn wrap_lines_safely(lines: Vec<Line<'_>>, width: usize) -> Vec<Line<'static>> {
+    wrap_lines_indexed(lines, width).0
+}
+
+/// Like `wrap_lines_safely`, also returning the source line index of every
+/// wrapped row.
+fn wrap_lines_indexed(lines: Vec<Line<'_>>, width: usize) -> (Vec<Line<'static>>, Vec<usize>) {
     let width = width.max(1);
     let mut wrapped = Vec::new();
+    let mut origins = Vec::new();
 
-    for line in lines {
+    for (origin, line) in lines.into_iter().enumerate() {
         let mut current_spans: Vec<Span<'static>> = Vec::new();
         let mut current_text = String::new();
         let mut current_style = Style::default();
@@ -667,9 +1473,10 @@ fn wrap_lines_safely(lines: Vec<Line<'_>>, width: usize) -> Vec<Line<'static>> {
         } else {
             wrapped.push(Line::from(""));
         }
+        origins.resize(wrapped.len(), origin);
     }
 
-    wrapped
+    (wrapped, origins)
 }
 
 fn flush_wrapped_span(spans: &mut Vec<Span<'static>>, text: &mut String, style: Style) {
@@ -760,12 +1567,29 @@ fn recent_edited_summary(state: &AgentState, max: usize) -> String {
 }
 
 fn voice_bar_text(state: &AgentState) -> String {
-    let status = if state.voice.connected {
-        "VOICE"
-    } else if state.voice.enabled {
-        "VOICE (connecting)"
+    let voice = &state.voice;
+    let status = if !voice.connected {
+        if voice.enabled {
+            "VOICE (connecting)".to_string()
+        } else {
+            "VOICE (off)".to_string()
+        }
+    } else if voice.push_to_talk() {
+        if voice.ptt_held && !voice.paused {
+            "VOICE \u{25cf} REC".to_string()
+        } else {
+            format!("VOICE (hold {} to talk)", voice.ptt_key.label())
+        }
+    } else if voice.paused {
+        "VOICE (paused)".to_string()
+    } else if let VoiceActivation::WakeWord(phrase) = &voice.activation {
+        if voice.wake_armed() {
+            "VOICE (listening)".to_string()
+        } else {
+            format!("VOICE (say \"{phrase}\")")
+        }
     } else {
-        "VOICE (off)"
+        "VOICE".to_string()
     };
 
     let mut text = state
@@ -782,9 +1606,12 @@ fn voice_bar_text(state: &AgentState) -> String {
         .or_else(|| state.voice.partial.clone())
         .unwrap_or_default();
 
-    if text.is_empty() && state.voice.connected {
+    if text.is_empty() && voice.connected && !voice.paused {
         text = "listening...".into();
     }
+    if voice.speaking {
+        return format!("{status} (speaking) {text}");
+    }
 
     format!("{status} {text}")
 }
@@ -843,8 +1670,8 @@ fn render_input_box(f: &mut Frame, area: Rect, state: &AgentState) {
 
     let mut out = Vec::new();
 
-    // Top border
-    out.push(Line::from("─".repeat(inner_width)));
+    // Top border, naming any images or piped stdin attached to the next prompt
+    out.push(input_top_border(state, inner_width, p.accent));
 
     // Show visible lines
     let mut image_idx = 1usize;
@@ -1004,6 +1831,33 @@ fn wrap_visual_lines(raw: &str, width: usize) -> Vec<String> {
     visual
 }
 
+fn input_top_border(state: &AgentState, width: usize, accent: Color) -> Line<'static> {
+    if state.ui.images.is_empty() && state.ui.piped_input.is_none() {
+        return Line::from("─".repeat(width));
+    }
+    let names = state
+        .ui
+        .piped_input
+        .iter()
+        .map(|piped| format!("[{}]", piped.label()))
+        .chain(
+            state
+                .ui
+                .images
+                .iter()
+                .map(|image| format!("[image: {}]", image.name())),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+    let label = truncate_for_badge(&format!(" {names} "), width.saturating_sub(2));
+    let rest = width.saturating_sub(2 + label.chars().count());
+    Line::from(vec![
+        Span::raw("──"),
+        Span::styled(label, Style::default().fg(accent)),
+        Span::raw("─".repeat(rest)),
+    ])
+}
+
 fn render_image_alias_spans(
     line: &str,
     image_idx: &mut usize,
@@ -1072,10 +1926,14 @@ fn looks_like_image_path(token: &str) -> bool {
 fn render_status_bar(f: &mut Frame, area: Rect, state: &AgentState) {
     let p = palette(state);
     let voice_label = if state.voice.enabled {
-        if state.voice.connected {
-            "voice on"
-        } else {
+        if !state.voice.connected {
             "voice connecting"
+        } else if state.voice.push_to_talk() {
+            "voice ptt"
+        } else if state.voice.paused {
+            "voice paused"
+        } else {
+            "voice on"
         }
     } else {
         "voice off"
@@ -1089,14 +1947,15 @@ fn render_status_bar(f: &mut Frame, area: Rect, state: &AgentState) {
         "idle"
     };
     let total_tokens = state.usage.prompt_tokens + state.usage.completion_tokens;
-    let est_cost = ((state.usage.prompt_tokens as f64) * 0.0000025)
-        + ((state.usage.completion_tokens as f64) * 0.0000100);
     let usage_label = format!(
-        "tokens {} ctx {} ${:.4}",
-        total_tokens, state.conversation.token_estimate, est_cost
+        "tokens {} ctx {} {}",
+        total_tokens,
+        state.conversation.token_estimate,
+        state.usage.cost_label()
     );
 
-    let mut left = vec![
+    let mut left = tab_bar_spans(&state.ui.tabs, state.ui.active_tab, p);
+    left.extend([
         Span::styled(input_label, Style::default().fg(p.fg_dim)),
         Span::styled(
             format!(" · {run_label}"),
@@ -1111,10 +1970,19 @@ fn render_status_bar(f: &mut Frame, area: Rect, state: &AgentState) {
             format!(" · {}", state.permission_profile.as_str()),
             Style::default().fg(p.fg_dim),
         ),
-    ];
+    ]);
     if state.plan_mode {
         left.push(Span::styled(" · plan mode", Style::default().fg(p.accent)));
     }
+    if state.sandbox.is_some() {
+        left.push(Span::styled(" · sandbox", Style::default().fg(p.accent)));
+    }
+    if state.ui.pending_budget.is_some() {
+        left.push(Span::styled(
+            " · change budget",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
     if state.ui.pending_permission.is_some() {
         left.push(Span::styled(
             " · approval needed",
@@ -1169,6 +2037,28 @@ fn render_status_bar(f: &mut Frame, area: Rect, state: &AgentState) {
     );
 }
 
+/// `1 api ● 2 docs │ ` ahead of the status; nothing with a single tab.
+fn tab_bar_spans(tabs: &[TabLabel], active: usize, p: UiPalette) -> Vec<Span<'static>> {
+    if tabs.len() < 2 {
+        return Vec::new();
+    }
+    let mut spans = Vec::new();
+    for (i, tab) in tabs.iter().enumerate() {
+        let running = if tab.running { " ●" } else { "" };
+        let style = if i == active {
+            Style::default().fg(p.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(p.fg_muted)
+        };
+        spans.push(Span::styled(
+            format!("{} {}{running} ", i + 1, truncate_for_badge(&tab.title, 16)),
+            style,
+        ));
+    }
+    spans.push(Span::styled("│ ", Style::default().fg(p.fg_muted)));
+    spans
+}
+
 /// command hints pallete
 pub fn render_command_palette(f: &mut Frame, area: Rect, state: &AgentState) {
     if state.ui.command_items.is_empty() {
@@ -1233,11 +2123,12 @@ pub fn render_command_palette(f: &mut Frame, area: Rect, state: &AgentState) {
 #[cfg(test)]
 mod tests {
     use super::{
-        clamp_scroll_offset, input_cursor_visual_position, logo_header_enabled_for_density,
-        pending_update_prompt, render_plan_lines_for_items, update_status_label, wrap_lines_safely,
-        wrap_visual_lines, UiPalette, LOGO,
+        clamp_scroll_offset, diff_pane_lines, input_cursor_visual_position,
+        logo_header_enabled_for_density, pending_update_prompt, render_candidate_lines,
+        render_plan_lines_for_items, render_review_lines, tab_bar_spans, update_status_label,
+        wrap_lines_safely, wrap_visual_lines, UiPalette, LOGO,
     };
-    use crate::state::{PendingUpdate, PlanItem, UiDensity};
+    use crate::state::{DiffSnapshot, PendingUpdate, PlanItem, TabLabel, UiDensity};
     use ratatui::{style::Color, text::Line};
 
     #[test]
@@ -1334,16 +2225,19 @@ mod tests {
                     text: "inspect".to_string(),
                     done: true,
                     active: false,
+                    files: Vec::new(),
                 },
                 PlanItem {
                     text: "implement".to_string(),
                     done: false,
                     active: true,
+                    files: vec!["src/parser.rs".to_string(), "tests/parse.rs".to_string()],
                 },
                 PlanItem {
                     text: "verify".to_string(),
                     done: false,
                     active: false,
+                    files: Vec::new(),
                 },
             ],
             test_palette(),
@@ -1352,7 +2246,132 @@ mod tests {
 
         assert_eq!(
             snapshot,
-            vec!["Plan · 1/3", "[x] inspect", "[>] implement", "[ ] verify"]
+            vec![
+                "Plan · 1/3",
+                "[x] inspect",
+                "[>] implement",
+                "    src/parser.rs, tests/parse.rs",
+                "[ ] verify"
+            ]
+        );
+    }
+
+    #[test]
+    fn tab_bar_numbers_tabs_and_marks_running_ones() {
+        let tab = |title: &str, running| TabLabel {
+            title: title.to_string(),
+            running,
+            exit_blockers: Vec::new(),
+        };
+        assert!(tab_bar_spans(&[tab("api", false)], 0, test_palette()).is_empty());
+        let spans = tab_bar_spans(
+            &[tab("api", true), tab("a-very-long-repository-name", false)],
+            1,
+            test_palette(),
+        );
+        assert_eq!(
+            plain_text(&Line::from(spans.clone())),
+            "1 api ● 2 a-very-long-rep… │ "
+        );
+        assert_eq!(spans[1].style.fg, Some(Color::Yellow));
+    }
+
+    #[test]
+    fn diff_pane_scrolls_to_the_latest_changed_file() {
+        let snap = |target: &str, before: &str, after: &str| DiffSnapshot {
+            tool: "edit_file".to_string(),
+            target: target.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            ..DiffSnapshot::default()
+        };
+        let long: String = (0..30).map(|i| format!("line {i}\n")).collect();
+        let changes = [
+            snap("src/a.rs", "", &long),
+            snap("src/b.rs", "old\n", "new\n"),
+        ];
+        let (lines, scroll) = diff_pane_lines(&changes, 60, 8, test_palette());
+        let text: Vec<String> = lines.iter().map(plain_text).collect();
+        assert!(text[1].starts_with("Changes (2)"), "{text:?}");
+        assert!(text[scroll..].iter().any(|l| l.contains("src/b.rs")));
+        assert!(!text[scroll..].iter().any(|l| l.contains("src/a.rs")));
+
+        let (_, scroll) = diff_pane_lines(&changes[..1], 60, 8, test_palette());
+        assert_eq!(scroll, 0);
+        assert!(diff_pane_lines(&[], 60, 8, test_palette()).0.is_empty());
+    }
+
+    #[test]
+    fn review_lines_mark_decisions_and_the_selection() {
+        use crate::review_queue::{Decision, ReviewQueue};
+        let snap = |target: &str, before: &str, after: &str| DiffSnapshot {
+            tool: "edit_file".to_string(),
+            target: target.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            ..DiffSnapshot::default()
+        };
+        let mut review = ReviewQueue::new(vec![
+            snap("src/a.rs", "a\n", "b\n"),
+            DiffSnapshot {
+                created: true,
+                ..snap("src/new.rs", "", "x\ny\n")
+            },
+            snap("src/empty.rs", "", "z\n"),
+        ])
+        .unwrap();
+        review.decide(Decision::Reject);
+        let text: Vec<String> = render_review_lines(&review, test_palette())
+            .iter()
+            .map(plain_text)
+            .collect();
+        assert_eq!(
+            text[1..],
+            [
+                "  ✗ src/a.rs  +1 -1",
+                "› ? src/new.rs  +2 -0  new file",
+                "  ? src/empty.rs  +1 -0"
+            ]
+        );
+    }
+
+    #[test]
+    fn candidate_lines_mark_picks_discards_and_the_reason_prompt() {
+        use crate::candidate_queue::{CandidateQueue, Mark};
+        use crate::testgen::TestCandidate;
+        let candidate = |symbol: &str, covered_by: &[&str]| TestCandidate {
+            symbol: symbol.to_string(),
+            path: "src/lib.rs".to_string(),
+            line: 3,
+            covered_by: covered_by.iter().map(|t| t.to_string()).collect(),
+            changed_lines_covered: None,
+        };
+        let mut queue = CandidateQueue::new(
+            vec![
+                candidate("parse", &[]),
+                candidate("load", &["test_load"]),
+                candidate("render", &[]),
+            ],
+            "gpt",
+        )
+        .unwrap();
+        queue.items[0].mark = Some(Mark::Discarded("generated".into()));
+        queue.move_selection(false);
+        queue.toggle_pick();
+        queue.move_selection(false);
+        queue.reason = Some("dead co".into());
+        let text: Vec<String> = render_candidate_lines(&queue, test_palette())
+            .iter()
+            .map(plain_text)
+            .collect();
+        assert_eq!(
+            text[1..],
+            [
+                "  ✗ parse src/lib.rs:3  discarded: generated",
+                "  ✓ load src/lib.rs:3  tested by test_load",
+                "› · render src/lib.rs:3  untested",
+                "Why discard render? dead co▏  [enter] record  [esc] cancel",
+            ]
         );
     }
 
@@ -9,6 +9,8 @@ use std::time::Duration;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
 
+use crate::http_policy;
+
 const REPO: &str = "kaushal07wick/OsmoGrep";
 
 #[derive(Clone, Debug)]
@@ -98,14 +100,11 @@ fn check_for_update() -> Result<Option<UpdateInfo>, String> {
 
 fn fetch_latest_release() -> Result<Release, String> {
     let url = format!("https://api.github.com/repos/{REPO}/releases/latest");
-    reqwest::blocking::Client::builder()
+    let client = crate::net::client_builder()?
         .timeout(Duration::from_secs(20))
         .build()
-        .map_err(|e| e.to_string())?
-        .get(url)
-        .header("User-Agent", "osmogrep-updater")
-        .send()
-        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    http_policy::send(client.get(url).header("User-Agent", "osmogrep-updater"))?
         .error_for_status()
         .map_err(|e| e.to_string())?
         .json::<Release>()
@@ -133,14 +132,11 @@ fn install_update(info: &UpdateInfo) -> Result<(), String> {
 }
 
 fn download(url: &str, path: &Path) -> Result<(), String> {
-    let bytes = reqwest::blocking::Client::builder()
+    let client = crate::net::client_builder()?
         .timeout(Duration::from_secs(60))
         .build()
-        .map_err(|e| e.to_string())?
-        .get(url)
-        .header("User-Agent", "osmogrep-updater")
-        .send()
-        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let bytes = http_policy::send(client.get(url).header("User-Agent", "osmogrep-updater"))?
         .error_for_status()
         .map_err(|e| e.to_string())?
         .bytes()
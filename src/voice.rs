@@ -1,4 +1,5 @@
 use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
@@ -11,20 +12,93 @@ use base64::Engine;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{SampleFormat, Stream};
 use crossbeam_channel::Sender as CbSender;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tungstenite::{accept, connect, Message};
+use tungstenite::{accept, Message};
 use url::Url;
 
+use crate::keymap::KeyChord;
+use crate::net;
+use crate::speech::TtsBackend;
+use crate::whisper_local;
+
 use std::sync::mpsc::{Receiver, Sender};
 
-const TARGET_SAMPLE_RATE: u32 = 16_000;
+pub(crate) const TARGET_SAMPLE_RATE: u32 = 16_000;
 const CHUNK_MS: u32 = 100;
 const CHUNK_SAMPLES: usize = (TARGET_SAMPLE_RATE as usize * CHUNK_MS as usize) / 1000;
 
+/// `[voice]` section of the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoiceConfig {
+    #[serde(default)]
+    pub backend: VoiceBackend,
+    /// ggml model for `whisper-local`; see [`VoiceConfig::whisper_model_path`].
+    #[serde(default)]
+    pub whisper_model: Option<PathBuf>,
+    /// Spoken language for `whisper-local`, e.g. `en`; detected when unset.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Read final answers aloud from the start, as `/speak on` does.
+    #[serde(default)]
+    pub speak: bool,
+    #[serde(default)]
+    pub tts: TtsBackend,
+    /// OpenAI speech model and voice; `gpt-4o-mini-tts` and `alloy` by default.
+    #[serde(default)]
+    pub tts_model: Option<String>,
+    #[serde(default)]
+    pub tts_voice: Option<String>,
+    /// `.onnx` voice for `tts = "piper"`.
+    #[serde(default)]
+    pub piper_model: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VoiceBackend {
+    /// A vLLM realtime server over a websocket.
+    #[default]
+    Vllm,
+    /// whisper.cpp on this machine, no server needed.
+    WhisperLocal,
+}
+
+impl VoiceBackend {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Vllm => "vllm",
+            Self::WhisperLocal => "whisper-local",
+        }
+    }
+}
+
+impl VoiceConfig {
+    /// The configured model, else `ggml-base.en.bin` in the data directory.
+    pub fn whisper_model_path(&self) -> PathBuf {
+        self.whisper_model.clone().unwrap_or_else(|| {
+            dirs::data_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("osmogrep")
+                .join("whisper")
+                .join("ggml-base.en.bin")
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum VoiceCommand {
-    Start { url: String, model: String },
+    /// `paused` starts the session with the mic off, for push-to-talk.
+    Start {
+        url: String,
+        model: String,
+        paused: bool,
+    },
     Stop,
+    /// Turns the mic off and commits what was captured.
+    Pause,
+    Resume,
 }
 
 #[derive(Debug, Clone)]
@@ -35,20 +109,159 @@ pub enum VoiceEvent {
     Final(String),
     Error(String),
     Status(String),
+    Paused,
+    Resumed,
+}
+
+/// How long the wake phrase said on its own keeps the next utterance open.
+pub const WAKE_WINDOW: Duration = Duration::from_secs(8);
+
+/// Without release events, the push-to-talk key counts as released once
+/// key repeat has been quiet this long.
+pub const PTT_RELEASE_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// How the mic is gated while a session is connected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoiceActivation {
+    /// Always listening; silence commits.
+    Continuous,
+    /// Listening only while the push-to-talk key is held.
+    PushToTalk,
+    /// Always listening, but only utterances after the phrase are kept.
+    WakeWord(String),
+}
+
+/// The push-to-talk key, parsed from `f5`, `ctrl+space`, `alt+v` and the like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PttKey {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Default for PttKey {
+    fn default() -> Self {
+        Self {
+            code: KeyCode::F(5),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+}
+
+impl PttKey {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let chord = KeyChord::parse(&raw.to_ascii_lowercase())?;
+        // Enter, arrows and the like belong to the input box.
+        matches!(
+            chord.code,
+            KeyCode::F(_) | KeyCode::Char(_) | KeyCode::Tab | KeyCode::Insert
+        )
+        .then_some(Self {
+            code: chord.code,
+            modifiers: chord.modifiers,
+        })
+    }
+
+    /// Shift is ignored, since terminals disagree on reporting it.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        code == self.code && key.modifiers & relevant == self.modifiers & relevant
+    }
+
+    pub fn label(&self) -> String {
+        let code = match self.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_uppercase()),
+            code => code,
+        };
+        KeyChord {
+            code,
+            modifiers: self.modifiers,
+        }
+        .label()
+    }
+}
+
+/// What the wake-word gate does with one finished utterance.
+#[derive(Debug, PartialEq, Eq)]
+pub enum WakeGate {
+    /// Not addressed to us.
+    Drop,
+    /// The phrase alone: the next utterance is the input.
+    Arm,
+    /// The text to use, without the phrase.
+    Pass(String),
+}
+
+/// Looks for `phrase` as whole words, ignoring case and punctuation, and
+/// keeps what follows it. An armed gate passes the utterance as is.
+pub fn wake_gate(text: &str, phrase: &str, armed: bool) -> WakeGate {
+    let norm = |w: &str| {
+        w.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let wanted = phrase
+        .split_whitespace()
+        .map(norm)
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    // (normalized word, byte offset just past it)
+    let words = text
+        .split_whitespace()
+        .map(|w| {
+            let end = w.as_ptr() as usize - text.as_ptr() as usize + w.len();
+            (norm(w), end)
+        })
+        .filter(|(w, _)| !w.is_empty())
+        .collect::<Vec<_>>();
+
+    let found = (!wanted.is_empty())
+        .then(|| {
+            words
+                .windows(wanted.len())
+                .position(|window| window.iter().map(|(w, _)| w).eq(wanted.iter()))
+        })
+        .flatten();
+    match found {
+        Some(start) => {
+            let end = words[start + wanted.len() - 1].1;
+            let rest = text[end..]
+                .trim_start_matches(|c: char| !c.is_alphanumeric())
+                .trim_end();
+            if rest.is_empty() {
+                WakeGate::Arm
+            } else {
+                WakeGate::Pass(rest.to_string())
+            }
+        }
+        None if armed && !text.trim().is_empty() => WakeGate::Pass(text.trim().to_string()),
+        None => WakeGate::Drop,
+    }
 }
 
 pub fn spawn_voice_worker(
     cmd_rx: Receiver<VoiceCommand>,
     evt_tx: Sender<VoiceEvent>,
+    config: VoiceConfig,
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || loop {
         match cmd_rx.recv() {
-            Ok(VoiceCommand::Start { url, model }) => {
-                if let Err(e) = run_session(&cmd_rx, &evt_tx, &url, &model) {
+            Ok(VoiceCommand::Start { url, model, paused }) => {
+                let result = match config.backend {
+                    VoiceBackend::Vllm => run_session(&cmd_rx, &evt_tx, &url, &model, paused),
+                    VoiceBackend::WhisperLocal => {
+                        whisper_local::run_session(&cmd_rx, &evt_tx, &config, paused)
+                    }
+                };
+                if let Err(e) = result {
                     let _ = evt_tx.send(VoiceEvent::Error(e));
                 }
             }
-            Ok(VoiceCommand::Stop) => {
+            Ok(VoiceCommand::Stop | VoiceCommand::Pause | VoiceCommand::Resume) => {
                 let _ = evt_tx.send(VoiceEvent::Status("Voice not running.".into()));
             }
             Err(_) => {
@@ -99,10 +312,11 @@ fn run_session(
     evt_tx: &Sender<VoiceEvent>,
     url: &str,
     model: &str,
+    mut paused: bool,
 ) -> Result<(), String> {
     let url = normalize_ws_url(url)?;
 
-    let (mut ws, _resp) = connect(url.as_str()).map_err(|e| e.to_string())?;
+    let (mut ws, _resp) = net::ws_connect(url.as_str())?;
 
     if let tungstenite::stream::MaybeTlsStream::Plain(stream) = ws.get_mut() {
         let _ = stream.set_read_timeout(Some(Duration::from_millis(30)));
@@ -131,11 +345,16 @@ fn run_session(
 
     let stream = start_audio_capture(audio_tx, stop.clone())
         .map_err(|e| format!("Audio capture failed: {e}"))?;
-    stream
-        .play()
-        .map_err(|e| format!("Audio stream failed to start: {e}"))?;
+    if !paused {
+        stream
+            .play()
+            .map_err(|e| format!("Audio stream failed to start: {e}"))?;
+    }
 
     let _ = evt_tx.send(VoiceEvent::Connected);
+    if paused {
+        let _ = evt_tx.send(VoiceEvent::Paused);
+    }
 
     // Main loop: interleave sending audio and reading server events.
     loop {
@@ -155,6 +374,29 @@ fn run_session(
             Ok(VoiceCommand::Start { .. }) => {
                 let _ = evt_tx.send(VoiceEvent::Status("Voice already running.".into()));
             }
+            Ok(VoiceCommand::Pause) if !paused => {
+                paused = true;
+                // Not every host can pause; chunks are dropped below anyway.
+                let _ = stream.pause();
+                // Not `final`: that would end the session's input for good.
+                let commit = json!({
+                    "type": "input_audio_buffer.commit",
+                });
+                let _ = ws.send(Message::Text(commit.to_string()));
+                let _ = evt_tx.send(VoiceEvent::Paused);
+            }
+            Ok(VoiceCommand::Resume) if paused => {
+                paused = false;
+                while audio_rx.try_recv().is_ok() {}
+                if let Err(e) = stream.play() {
+                    let _ = evt_tx.send(VoiceEvent::Error(format!(
+                        "Audio stream failed to start: {e}"
+                    )));
+                    break;
+                }
+                let _ = evt_tx.send(VoiceEvent::Resumed);
+            }
+            Ok(VoiceCommand::Pause | VoiceCommand::Resume) => {}
             Err(std::sync::mpsc::TryRecvError::Empty) => {}
             Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                 stop.store(true, Ordering::SeqCst);
@@ -165,6 +407,9 @@ fn run_session(
 
         // Send audio chunk if available.
         if let Ok(chunk) = audio_rx.recv_timeout(Duration::from_millis(20)) {
+            if paused {
+                continue;
+            }
             let mut bytes = Vec::with_capacity(chunk.len() * 2);
             for s in chunk {
                 bytes.extend_from_slice(&s.to_le_bytes());
@@ -270,7 +515,7 @@ fn handle_proxy_connection(
     let mut client_ws = accept(stream).map_err(|e| e.to_string())?;
     let _ = evt_tx.send(VoiceEvent::Status("voice proxy client connected".into()));
     let url = normalize_ws_url(vllm_url)?;
-    let (mut vllm_ws, _resp) = connect(url.as_str()).map_err(|e| e.to_string())?;
+    let (mut vllm_ws, _resp) = net::ws_connect(url.as_str())?;
 
     if let tungstenite::stream::MaybeTlsStream::Plain(stream) = vllm_ws.get_mut() {
         let _ = stream.set_read_timeout(Some(Duration::from_millis(30)));
@@ -366,7 +611,7 @@ fn handle_proxy_connection(
     Ok(())
 }
 
-fn start_audio_capture(
+pub(crate) fn start_audio_capture(
     audio_tx: CbSender<Vec<i16>>,
     stop: Arc<AtomicBool>,
 ) -> Result<Stream, String> {
@@ -525,7 +770,7 @@ where
     Ok(stream)
 }
 
-fn resample_linear(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+pub(crate) fn resample_linear(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
     if input.is_empty() {
         return Vec::new();
     }
@@ -546,3 +791,41 @@ fn resample_linear(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wake_gate_keeps_only_what_follows_the_phrase() {
+        let gate = |text: &str, armed: bool| wake_gate(text, "Hey Osmo", armed);
+        assert_eq!(
+            gate("hey, osmo: run the tests", false),
+            WakeGate::Pass("run the tests".into())
+        );
+        assert_eq!(gate("Hey Osmo.", false), WakeGate::Arm);
+        assert_eq!(gate("so anyway the build broke", false), WakeGate::Drop);
+        assert_eq!(
+            gate("fix the flaky test", true),
+            WakeGate::Pass("fix the flaky test".into())
+        );
+        assert_eq!(gate("  ", true), WakeGate::Drop);
+    }
+
+    #[test]
+    fn ptt_keys_parse_and_match_case_insensitively() {
+        let key = PttKey::parse("Ctrl+Space").unwrap();
+        assert_eq!(key.label(), "Ctrl+Space");
+        assert!(key.matches(&KeyEvent::new(KeyCode::Char(' '), KeyModifiers::CONTROL)));
+        assert!(!key.matches(&KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE)));
+        let v = PttKey::parse("alt+v").unwrap();
+        assert!(v.matches(&KeyEvent::new(
+            KeyCode::Char('V'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT
+        )));
+        assert_eq!(PttKey::parse("f12").unwrap().code, KeyCode::F(12));
+        assert_eq!(PttKey::default().label(), "F5");
+        assert!(PttKey::parse("hyper+x").is_none());
+        assert!(PttKey::parse("enter").is_none());
+    }
+}
@@ -0,0 +1,314 @@
+//! whisper_local.rs
+//!
+//! Offline transcription with whisper.cpp, for `[voice] backend =
+//! "whisper-local"`. Whisper does not stream, so mic audio is cut into
+//! utterances at pauses and each one is transcribed whole; the text arrives
+//! as one `VoiceEvent::Final`, like a committed vLLM transcript. The
+//! whisper.cpp binding is behind the `whisper-local` cargo feature.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::voice::{VoiceCommand, VoiceConfig, VoiceEvent, TARGET_SAMPLE_RATE};
+
+/// RMS, as a fraction of full scale, above which a chunk counts as speech.
+const SPEECH_RMS: f32 = 0.015;
+/// Quiet after speech that ends an utterance.
+const END_SILENCE_MS: usize = 700;
+/// Whisper sees at most 30 seconds at a time.
+const MAX_UTTERANCE_MS: usize = 28_000;
+/// Shorter bursts are clicks and coughs, and make whisper hallucinate.
+const MIN_SPEECH_MS: usize = 250;
+/// Audio kept from before speech starts, so the first syllable survives.
+const PRE_ROLL_MS: usize = 200;
+
+/// Cuts 16kHz mono chunks into utterances on a simple energy threshold.
+#[derive(Debug, Default)]
+pub struct Segmenter {
+    utterance: Vec<f32>,
+    pre_roll: VecDeque<f32>,
+    speech_samples: usize,
+    silent_samples: usize,
+}
+
+impl Segmenter {
+    /// Returns a finished utterance once speech is followed by a pause.
+    pub fn push(&mut self, chunk: &[i16]) -> Option<Vec<f32>> {
+        let samples = chunk
+            .iter()
+            .map(|&s| s as f32 / i16::MAX as f32)
+            .collect::<Vec<_>>();
+        let loud = rms(&samples) >= SPEECH_RMS;
+
+        if self.utterance.is_empty() && !loud {
+            self.pre_roll.extend(samples);
+            let excess = self.pre_roll.len().saturating_sub(samples_for(PRE_ROLL_MS));
+            self.pre_roll.drain(..excess);
+            return None;
+        }
+        if self.utterance.is_empty() {
+            self.utterance.extend(self.pre_roll.drain(..));
+        }
+        if loud {
+            self.speech_samples += samples.len();
+            self.silent_samples = 0;
+        } else {
+            self.silent_samples += samples.len();
+        }
+        self.utterance.extend(samples);
+
+        if self.silent_samples >= samples_for(END_SILENCE_MS)
+            || self.utterance.len() >= samples_for(MAX_UTTERANCE_MS)
+        {
+            return self.finish();
+        }
+        None
+    }
+
+    /// Ends the current utterance early, e.g. on a push-to-talk release.
+    pub fn finish(&mut self) -> Option<Vec<f32>> {
+        let utterance = std::mem::take(&mut self.utterance);
+        let speech = std::mem::take(&mut self.speech_samples);
+        self.silent_samples = 0;
+        self.pre_roll.clear();
+        (speech >= samples_for(MIN_SPEECH_MS)).then_some(utterance)
+    }
+}
+
+fn samples_for(ms: usize) -> usize {
+    TARGET_SAMPLE_RATE as usize * ms / 1000
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Drops the non-speech annotations whisper emits for noise and silence,
+/// like `[BLANK_AUDIO]` or `(keyboard clicking)`.
+pub fn clean_transcript(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(not(feature = "whisper-local"))]
+pub fn run_session(
+    _cmd_rx: &Receiver<VoiceCommand>,
+    _evt_tx: &Sender<VoiceEvent>,
+    _config: &VoiceConfig,
+    _paused: bool,
+) -> Result<(), String> {
+    Err("voice.backend = \"whisper-local\" needs a build with `--features whisper-local`".into())
+}
+
+#[cfg(feature = "whisper-local")]
+pub fn run_session(
+    cmd_rx: &Receiver<VoiceCommand>,
+    evt_tx: &Sender<VoiceEvent>,
+    config: &VoiceConfig,
+    mut paused: bool,
+) -> Result<(), String> {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    };
+    use std::thread;
+    use std::time::Duration;
+
+    use cpal::traits::StreamTrait;
+    use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+    let model = config.whisper_model_path();
+    if !model.is_file() {
+        return Err(format!(
+            "Whisper model not found at {}; download a ggml model (e.g. ggml-base.en.bin) and set voice.whisper_model",
+            model.display()
+        ));
+    }
+    let _ = evt_tx.send(VoiceEvent::Status("Loading whisper model...".into()));
+    let ctx = WhisperContext::new_with_params(
+        &model.to_string_lossy(),
+        WhisperContextParameters::default(),
+    )
+    .map_err(|e| format!("Whisper model failed to load: {e}"))?;
+    let mut whisper = ctx
+        .create_state()
+        .map_err(|e| format!("Whisper model failed to load: {e}"))?;
+
+    // Transcribing an utterance takes longer than a chunk; doing it on its
+    // own thread keeps the capture channel from overflowing meanwhile.
+    let (utterance_tx, utterance_rx) = mpsc::channel::<Vec<f32>>();
+    let transcript_tx = evt_tx.clone();
+    let language = config.language.clone();
+    let transcriber = thread::spawn(move || {
+        for audio in utterance_rx {
+            match transcribe(&mut whisper, &audio, language.as_deref()) {
+                Ok(text) if !text.is_empty() => {
+                    let _ = transcript_tx.send(VoiceEvent::Final(text));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = transcript_tx.send(VoiceEvent::Status(format!("whisper: {e}")));
+                }
+            }
+        }
+    });
+
+    let (audio_tx, audio_rx) = crossbeam_channel::bounded::<Vec<i16>>(8);
+    let stop = Arc::new(AtomicBool::new(false));
+    let stream = crate::voice::start_audio_capture(audio_tx, stop.clone())
+        .map_err(|e| format!("Audio capture failed: {e}"))?;
+    if !paused {
+        stream
+            .play()
+            .map_err(|e| format!("Audio stream failed to start: {e}"))?;
+    }
+
+    let _ = evt_tx.send(VoiceEvent::Connected);
+    if paused {
+        let _ = evt_tx.send(VoiceEvent::Paused);
+    }
+
+    let mut segmenter = Segmenter::default();
+    loop {
+        match cmd_rx.try_recv() {
+            Ok(VoiceCommand::Stop) => {
+                stop.store(true, Ordering::SeqCst);
+                if let Some(utterance) = segmenter.finish() {
+                    let _ = utterance_tx.send(utterance);
+                }
+                break;
+            }
+            Ok(VoiceCommand::Start { .. }) => {
+                let _ = evt_tx.send(VoiceEvent::Status("Voice already running.".into()));
+            }
+            Ok(VoiceCommand::Pause) if !paused => {
+                paused = true;
+                let _ = stream.pause();
+                if let Some(utterance) = segmenter.finish() {
+                    let _ = utterance_tx.send(utterance);
+                }
+                let _ = evt_tx.send(VoiceEvent::Paused);
+            }
+            Ok(VoiceCommand::Resume) if paused => {
+                paused = false;
+                while audio_rx.try_recv().is_ok() {}
+                if let Err(e) = stream.play() {
+                    let _ = evt_tx.send(VoiceEvent::Error(format!(
+                        "Audio stream failed to start: {e}"
+                    )));
+                    break;
+                }
+                let _ = evt_tx.send(VoiceEvent::Resumed);
+            }
+            Ok(VoiceCommand::Pause | VoiceCommand::Resume) => {}
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => {
+                stop.store(true, Ordering::SeqCst);
+                break;
+            }
+        }
+
+        if let Ok(chunk) = audio_rx.recv_timeout(Duration::from_millis(20)) {
+            if paused {
+                continue;
+            }
+            if let Some(utterance) = segmenter.push(&chunk) {
+                let _ = utterance_tx.send(utterance);
+            }
+        }
+    }
+
+    // Let the last utterance finish before reporting the session closed.
+    drop(stream);
+    drop(utterance_tx);
+    let _ = transcriber.join();
+    let _ = evt_tx.send(VoiceEvent::Disconnected);
+    Ok(())
+}
+
+#[cfg(feature = "whisper-local")]
+fn transcribe(
+    whisper: &mut whisper_rs::WhisperState,
+    audio: &[f32],
+    language: Option<&str>,
+) -> Result<String, whisper_rs::WhisperError> {
+    use whisper_rs::{FullParams, SamplingStrategy};
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(language);
+    params.set_no_context(true);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_special(false);
+    params.set_print_timestamps(false);
+    whisper.full(params, audio)?;
+
+    let mut text = String::new();
+    for segment in 0..whisper.full_n_segments()? {
+        text.push_str(&whisper.full_get_segment_text_lossy(segment)?);
+        text.push(' ');
+    }
+    Ok(clean_transcript(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(amplitude: i16) -> Vec<i16> {
+        // 100ms, alternating sign so the RMS equals the amplitude.
+        (0..1600)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    #[test]
+    fn segmenter_cuts_utterances_at_pauses_and_drops_blips() {
+        let mut segmenter = Segmenter::default();
+        let quiet = chunk(50);
+        let loud = chunk(4000);
+
+        for _ in 0..5 {
+            assert!(segmenter.push(&quiet).is_none());
+        }
+        for _ in 0..6 {
+            assert!(segmenter.push(&loud).is_none());
+        }
+        let mut utterance = None;
+        for _ in 0..7 {
+            utterance = utterance.or(segmenter.push(&quiet));
+        }
+        // 200ms of pre-roll, 600ms of speech, 700ms of trailing silence.
+        assert_eq!(utterance.map(|u| u.len()), Some(1600 * 15));
+
+        // One loud chunk is a blip, not speech.
+        segmenter.push(&loud);
+        assert!(segmenter.finish().is_none());
+
+        for _ in 0..3 {
+            segmenter.push(&loud);
+        }
+        assert_eq!(segmenter.finish().map(|u| u.len()), Some(1600 * 3));
+    }
+
+    #[test]
+    fn transcripts_lose_noise_annotations() {
+        assert_eq!(clean_transcript(" [BLANK_AUDIO] "), "");
+        assert_eq!(
+            clean_transcript(" Run the (keyboard clicking) tests  again. "),
+            "Run the tests again."
+        );
+    }
+}
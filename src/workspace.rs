@@ -0,0 +1,363 @@
+//! workspace.rs
+//!
+//! Packages of a monorepo: Cargo workspace members, npm/yarn/pnpm
+//! workspaces and Python projects (any `pyproject.toml`, src-layout or not).
+//! The diff analysis maps changed files to the packages that own them, and
+//! `/test --changed` runs only those packages' tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{context::indexer, test_harness};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Package {
+    pub name: String,
+    /// Directory relative to the repo root; `.` for a root package.
+    pub root: String,
+    /// Name of the test runner that owns it (`cargo`, `jest`, `pytest`).
+    pub runner: &'static str,
+}
+
+impl Package {
+    fn contains(&self, path: &str) -> bool {
+        self.root == "."
+            || path == self.root
+            || path
+                .strip_prefix(&self.root)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Every package in the repo. A repo without workspaces has at most one
+/// root package per language.
+pub fn packages(repo_root: &Path) -> Vec<Package> {
+    let mut out = cargo_packages(repo_root);
+    out.extend(node_packages(repo_root));
+    out.extend(python_packages(repo_root));
+    out
+}
+
+/// The innermost package containing `path` (repo-relative).
+pub fn package_for<'a>(packages: &'a [Package], path: &str) -> Option<&'a Package> {
+    packages
+        .iter()
+        .filter(|p| p.contains(path))
+        .max_by_key(|p| if p.root == "." { 0 } else { p.root.len() })
+}
+
+/// Packages owning any of `paths`, in discovery order.
+pub fn affected(packages: &[Package], paths: &[&str]) -> Vec<Package> {
+    packages
+        .iter()
+        .filter(|pkg| {
+            paths
+                .iter()
+                .any(|path| package_for(packages, path) == Some(*pkg))
+        })
+        .cloned()
+        .collect()
+}
+
+/// One command testing just `packages`, which must share a runner. None
+/// when they do not, or when only the root package is affected.
+pub fn test_command(repo_root: &Path, packages: &[Package]) -> Option<(&'static str, String)> {
+    let runner = packages.first()?.runner;
+    if packages.iter().any(|p| p.runner != runner || p.root == ".") {
+        return None;
+    }
+    let names = |flag: &str| {
+        packages
+            .iter()
+            .map(|p| format!("{flag} {}", p.name))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let command = match runner {
+        "cargo" => format!(
+            "{} {}",
+            test_harness::runner("cargo")?.invocation(repo_root),
+            names("-p")
+        ),
+        "jest" if repo_root.join("pnpm-workspace.yaml").exists() => {
+            format!("pnpm {} test", names("--filter"))
+        }
+        "jest" => format!("npm test {}", names("-w")),
+        "pytest" => {
+            let roots: Vec<&str> = packages.iter().map(|p| p.root.as_str()).collect();
+            format!(
+                "{} {}",
+                test_harness::runner("pytest")?.invocation(repo_root),
+                roots.join(" ")
+            )
+        }
+        _ => return None,
+    };
+    Some((runner, command))
+}
+
+fn cargo_packages(repo_root: &Path) -> Vec<Package> {
+    let Some(manifest) = read_toml(&repo_root.join("Cargo.toml")) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    if let Some(name) = manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(toml::Value::as_str)
+    {
+        out.push(package(name, ".", "cargo"));
+    }
+    let workspace = manifest.get("workspace");
+    let list = |key: &str| -> Vec<String> {
+        workspace
+            .and_then(|w| w.get(key))
+            .and_then(toml::Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(toml::Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let excluded = expand(repo_root, &list("exclude"));
+    for dir in expand(repo_root, &list("members")) {
+        if excluded.contains(&dir) {
+            continue;
+        }
+        let name = read_toml(&repo_root.join(&dir).join("Cargo.toml"))
+            .and_then(|m| Some(m.get("package")?.get("name")?.as_str()?.to_string()));
+        if let Some(name) = name {
+            out.push(package(&name, &dir, "cargo"));
+        }
+    }
+    out
+}
+
+fn node_packages(repo_root: &Path) -> Vec<Package> {
+    let Some(manifest) = read_json(&repo_root.join("package.json")) else {
+        return Vec::new();
+    };
+    let mut patterns: Vec<String> = match manifest.get("workspaces") {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(Into::into)
+            .collect(),
+        Some(Value::Object(obj)) => obj
+            .get("packages")
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(Into::into)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    patterns.extend(pnpm_patterns(repo_root));
+
+    let mut out = Vec::new();
+    for dir in expand(repo_root, &patterns) {
+        let name = read_json(&repo_root.join(&dir).join("package.json"))
+            .and_then(|m| Some(m.get("name")?.as_str()?.to_string()));
+        if let Some(name) = name {
+            out.push(package(&name, &dir, "jest"));
+        }
+    }
+    if out.is_empty() && test_harness::detect_frameworks(repo_root).contains(&"jest") {
+        let name = manifest.get("name").and_then(Value::as_str).unwrap_or(".");
+        out.push(package(name, ".", "jest"));
+    }
+    out
+}
+
+// `packages:` entries of pnpm-workspace.yaml; negated globs are skipped.
+fn pnpm_patterns(repo_root: &Path) -> Vec<String> {
+    let Ok(text) = fs::read_to_string(repo_root.join("pnpm-workspace.yaml")) else {
+        return Vec::new();
+    };
+    let mut in_packages = false;
+    let mut out = Vec::new();
+    for line in text.lines() {
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            let item = item.trim().trim_matches(|c| c == '"' || c == '\'');
+            if !item.is_empty() && !item.starts_with('!') {
+                out.push(item.to_string());
+            }
+        }
+    }
+    out
+}
+
+/// Every `pyproject.toml` up to two levels deep is a project rooted at its
+/// directory; the src-layout needs nothing special since its tests sit next
+/// to `src/`.
+fn python_packages(repo_root: &Path) -> Vec<Package> {
+    let mut out = Vec::new();
+    for entry in walkdir::WalkDir::new(repo_root)
+        .max_depth(3)
+        .into_iter()
+        .filter_entry(|e| {
+            let rel = e.path().strip_prefix(repo_root).unwrap_or(e.path());
+            !indexer::should_ignore(rel) && !is_hidden(rel)
+        })
+        .filter_map(Result::ok)
+    {
+        if entry.file_name() != "pyproject.toml" {
+            continue;
+        }
+        let dir = entry.path().parent().unwrap_or(repo_root);
+        let root = relative(repo_root, dir);
+        let name = read_toml(entry.path())
+            .and_then(|m| Some(m.get("project")?.get("name")?.as_str()?.to_string()))
+            .unwrap_or_else(|| root.clone());
+        out.push(package(&name, &root, "pytest"));
+    }
+    out.sort_by(|a, b| a.root.cmp(&b.root));
+    out
+}
+
+fn is_hidden(rel: &Path) -> bool {
+    rel.components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+/// Directories matching workspace globs, relative to the repo root.
+fn expand(repo_root: &Path, patterns: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    for pattern in patterns {
+        let full = repo_root.join(pattern.trim_end_matches('/'));
+        let Ok(paths) = glob::glob(&full.to_string_lossy()) else {
+            continue;
+        };
+        for dir in paths.flatten().filter(|p| p.is_dir()) {
+            let dir = relative(repo_root, &dir);
+            if !out.contains(&dir) {
+                out.push(dir);
+            }
+        }
+    }
+    out
+}
+
+fn relative(repo_root: &Path, path: &Path) -> String {
+    let rel: PathBuf = path.strip_prefix(repo_root).unwrap_or(path).to_path_buf();
+    let rel = rel.to_string_lossy().replace('\\', "/");
+    if rel.is_empty() {
+        ".".into()
+    } else {
+        rel
+    }
+}
+
+fn package(name: &str, root: &str, runner: &'static str) -> Package {
+    Package {
+        name: name.to_string(),
+        root: root.to_string(),
+        runner,
+    }
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn finds_workspace_members_and_the_packages_a_change_touches() {
+        let root = std::env::temp_dir().join(format!("osmogrep-workspace-{}", Uuid::new_v4()));
+        write(
+            &root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n",
+        );
+        write(
+            &root,
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"app-core\"\n",
+        );
+        write(
+            &root,
+            "crates/cli/Cargo.toml",
+            "[package]\nname = \"app-cli\"\n",
+        );
+        write(
+            &root,
+            "crates/old/Cargo.toml",
+            "[package]\nname = \"old\"\n",
+        );
+        write(&root, "package.json", "{\"private\": true}");
+        write(
+            &root,
+            "pnpm-workspace.yaml",
+            "packages:\n  - 'web/*'\n  - '!web/tmp'\n",
+        );
+        write(&root, "web/ui/package.json", "{\"name\": \"@app/ui\"}");
+        write(
+            &root,
+            "py/tools/pyproject.toml",
+            "[project]\nname = \"app-tools\"\n",
+        );
+        write(&root, "py/tools/src/app_tools/__init__.py", "");
+
+        let all = packages(&root);
+        let names: Vec<&str> = all.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names.len(), 4);
+        for name in ["app-core", "app-cli", "@app/ui", "app-tools"] {
+            assert!(names.contains(&name), "{name} missing from {names:?}");
+        }
+
+        let touched = affected(
+            &all,
+            &[
+                "crates/core/src/lib.rs",
+                "crates/cli/src/main.rs",
+                "README.md",
+            ],
+        );
+        let (runner, cmd) = test_command(&root, &touched).unwrap();
+        assert_eq!(runner, "cargo");
+        assert!(cmd.starts_with("cargo test --color never -p app-c"));
+        assert_eq!(cmd.matches(" -p ").count(), 2);
+
+        let ui = affected(&all, &["web/ui/src/button.ts"]);
+        assert_eq!(
+            test_command(&root, &ui).unwrap().1,
+            "pnpm --filter @app/ui test"
+        );
+        let py = affected(&all, &["py/tools/src/app_tools/__init__.py"]);
+        assert_eq!(test_command(&root, &py).unwrap().1, "pytest -q py/tools");
+        let mixed = affected(&all, &["web/ui/a.ts", "crates/cli/src/main.rs"]);
+        assert_eq!(test_command(&root, &mixed), None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
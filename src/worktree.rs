@@ -1,7 +1,8 @@
 use std::{
     env, fs,
+    io::Write,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Stdio},
     thread,
 };
 
@@ -98,6 +99,229 @@ pub fn create_role_worktree(repo_root: &Path, role: &str) -> Result<WorktreeSess
     })
 }
 
+/// A worktree the agent edits instead of the live checkout (`/sandbox on`).
+/// It starts as a copy of the checkout, uncommitted and untracked files
+/// included; `promote` applies what changed since then back to the checkout.
+#[derive(Clone, Debug)]
+pub struct Sandbox {
+    pub branch: String,
+    pub path: PathBuf,
+    /// Sandbox commit holding the checkout as of creation or the last promote.
+    baseline: String,
+}
+
+/// One file the agent changed in the sandbox; empty text for a file that
+/// was created or deleted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxChange {
+    pub path: String,
+    pub before: String,
+    pub after: String,
+}
+
+// Scratch state of the tools; never promoted.
+const SANDBOX_PATHSPEC: [&str; 2] = [".", ":(exclude).context"];
+
+pub fn create_sandbox(repo_root: &Path) -> Result<Sandbox, String> {
+    let root = repository_root(repo_root)?;
+    let session = create_role_worktree(&root, "sandbox")?;
+    let sandbox = Sandbox {
+        branch: session.branch,
+        path: session.path,
+        baseline: String::new(),
+    };
+    match seed_sandbox(&root, sandbox) {
+        Ok(sandbox) => Ok(sandbox),
+        Err((sandbox, e)) => {
+            let _ = sandbox.remove(&root);
+            Err(e)
+        }
+    }
+}
+
+// Copies the checkout's uncommitted state into the fresh worktree and
+// records it as the baseline.
+fn seed_sandbox(root: &Path, mut sandbox: Sandbox) -> Result<Sandbox, (Sandbox, String)> {
+    let seeded = (|| {
+        let patch = git_output(root, &["diff", "HEAD", "--binary"])?;
+        git_apply(&sandbox.path, &patch)?;
+        let untracked = git_output(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        for rel in String::from_utf8_lossy(&untracked)
+            .split('\0')
+            .filter(|p| !p.is_empty())
+        {
+            let dest = sandbox.path.join(rel);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::copy(root.join(rel), &dest).map_err(|e| format!("copying {rel}: {e}"))?;
+        }
+        commit_baseline(&sandbox.path)
+    })();
+    match seeded {
+        Ok(baseline) => {
+            sandbox.baseline = baseline;
+            Ok(sandbox)
+        }
+        Err(e) => Err((sandbox, e)),
+    }
+}
+
+impl Sandbox {
+    /// Files changed since the baseline, with their text before and after.
+    pub fn changes(&self) -> Result<Vec<SandboxChange>, String> {
+        self.stage()?;
+        let names = git_output(
+            &self.path,
+            &[
+                &[
+                    "diff",
+                    "--cached",
+                    "--name-only",
+                    "-z",
+                    &self.baseline,
+                    "--",
+                ][..],
+                &SANDBOX_PATHSPEC[..],
+            ]
+            .concat(),
+        )?;
+        let mut out = Vec::new();
+        for path in String::from_utf8_lossy(&names)
+            .split('\0')
+            .filter(|p| !p.is_empty())
+        {
+            let before = git_output(&self.path, &["show", &format!("{}:{path}", self.baseline)])
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default();
+            let after = fs::read(self.path.join(path))
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default();
+            out.push(SandboxChange {
+                path: path.to_string(),
+                before,
+                after,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Applies the sandbox's changes to the checkout at `repo_root` and
+    /// makes them the new baseline. Fails without touching the checkout
+    /// when the patch does not apply, e.g. after an edit to the same lines.
+    pub fn promote(&mut self, repo_root: &Path) -> Result<Vec<String>, String> {
+        let files: Vec<String> = self.changes()?.into_iter().map(|c| c.path).collect();
+        if files.is_empty() {
+            return Ok(files);
+        }
+        let patch = git_output(
+            &self.path,
+            &[
+                &["diff", "--cached", "--binary", &self.baseline, "--"][..],
+                &SANDBOX_PATHSPEC[..],
+            ]
+            .concat(),
+        )?;
+        git_apply(&repository_root(repo_root)?, &patch)?;
+        self.baseline = commit_baseline(&self.path)?;
+        Ok(files)
+    }
+
+    /// Throws away everything changed since the baseline.
+    pub fn discard(&self) -> Result<(), String> {
+        git_output(&self.path, &["reset", "--hard", &self.baseline])?;
+        git_output(
+            &self.path,
+            &["clean", "-fdq", "--", ".", ":(exclude).context"],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the worktree and its branch.
+    pub fn remove(&self, repo_root: &Path) -> Result<(), String> {
+        let root = repository_root(repo_root)?;
+        let path = self.path.to_string_lossy();
+        git_output(&root, &["worktree", "remove", "--force", &path])?;
+        git_output(&root, &["branch", "-D", &self.branch])?;
+        Ok(())
+    }
+
+    fn stage(&self) -> Result<(), String> {
+        git_output(
+            &self.path,
+            &[&["add", "-A", "--"][..], &SANDBOX_PATHSPEC[..]].concat(),
+        )
+        .map(|_| ())
+    }
+}
+
+fn commit_baseline(sandbox: &Path) -> Result<String, String> {
+    git_output(
+        sandbox,
+        &[&["add", "-A", "--"][..], &SANDBOX_PATHSPEC[..]].concat(),
+    )?;
+    git_output(
+        sandbox,
+        &[
+            "-c",
+            "user.name=osmogrep",
+            "-c",
+            "user.email=osmogrep@localhost",
+            "commit",
+            "-q",
+            "--no-verify",
+            "--allow-empty",
+            "-m",
+            "osmogrep sandbox baseline",
+        ],
+    )?;
+    let head = git_output(sandbox, &["rev-parse", "HEAD"])?;
+    Ok(String::from_utf8_lossy(&head).trim().to_string())
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !out.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or(""),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(out.stdout)
+}
+
+fn git_apply(dir: &Path, patch: &[u8]) -> Result<(), String> {
+    if patch.is_empty() {
+        return Ok(());
+    }
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["apply", "--binary", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch).map_err(|e| e.to_string())?;
+    }
+    let out = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !out.status.success() {
+        return Err(format!(
+            "git apply failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
 fn run_headless_worktree_subagent(
     exe: &Path,
     session: &WorktreeSession,
@@ -277,6 +501,63 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn sandbox_edits_reach_the_checkout_only_when_promoted() {
+        let root =
+            std::env::temp_dir().join(format!("osmogrep-sandbox-test-{}", Uuid::new_v4().simple()));
+        fs::create_dir_all(&root).unwrap();
+        git(&root, &["init"]);
+        git(&root, &["config", "user.email", "test@example.com"]);
+        git(&root, &["config", "user.name", "Osmogrep Test"]);
+        fs::write(root.join("README.md"), "hello\n").unwrap();
+        git(&root, &["add", "README.md"]);
+        git(&root, &["commit", "-m", "init"]);
+        fs::write(root.join("README.md"), "hello, wip\n").unwrap();
+        fs::write(root.join("notes.txt"), "untracked\n").unwrap();
+
+        let mut sandbox = create_sandbox(&root).unwrap();
+        assert_eq!(
+            fs::read_to_string(sandbox.path.join("README.md")).unwrap(),
+            "hello, wip\n"
+        );
+        assert!(sandbox.path.join("notes.txt").is_file());
+        assert!(sandbox.changes().unwrap().is_empty());
+
+        fs::write(sandbox.path.join("README.md"), "hello, sandbox\n").unwrap();
+        fs::write(sandbox.path.join("new.rs"), "fn main() {}\n").unwrap();
+        fs::create_dir_all(sandbox.path.join(".context")).unwrap();
+        fs::write(sandbox.path.join(".context/index.bin"), "scratch").unwrap();
+        let changes = sandbox.changes().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "README.md");
+        assert_eq!(changes[0].before, "hello, wip\n");
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "hello, wip\n"
+        );
+
+        let promoted = sandbox.promote(&root).unwrap();
+        assert_eq!(promoted, vec!["README.md", "new.rs"]);
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "hello, sandbox\n"
+        );
+        assert!(root.join("new.rs").is_file());
+        assert!(!root.join(".context").exists());
+        assert!(sandbox.changes().unwrap().is_empty());
+
+        fs::write(sandbox.path.join("README.md"), "discarded\n").unwrap();
+        sandbox.discard().unwrap();
+        assert_eq!(
+            fs::read_to_string(sandbox.path.join("README.md")).unwrap(),
+            "hello, sandbox\n"
+        );
+
+        sandbox.remove(&root).unwrap();
+        assert!(!sandbox.path.exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
     fn git(root: &Path, args: &[&str]) {
         let out = Command::new("git")
             .arg("-C")